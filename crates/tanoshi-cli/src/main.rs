@@ -1,6 +1,8 @@
 extern crate log;
 
-use std::path::PathBuf;
+mod source_test;
+
+use std::{collections::HashMap, path::PathBuf};
 
 use clap::{Parser, Subcommand};
 use serde::Serialize;
@@ -8,6 +10,8 @@ use tanoshi_lib::prelude::SourceInfo;
 use tanoshi_vm::{prelude::ExtensionManager, PLUGIN_EXTENSION};
 
 const TARGET: &str = env!("TARGET");
+/// extensions loaded at a time
+const LOAD_CONCURRENCY: usize = 4;
 
 #[derive(Parser)]
 #[clap(version, about)]
@@ -22,6 +26,20 @@ struct Opts {
 enum Command {
     /// Generate index.json
     GenerateJson,
+    /// Merge index.json generated on each platform into output/index.json
+    MergeJson {
+        /// index.json files to merge
+        files: Vec<PathBuf>,
+    },
+    /// Run popular, latest, search, detail, chapters and pages of a source in `path` and
+    /// check what it returns, exits with 1 if anything failed
+    SourceTest {
+        /// source id
+        id: i64,
+        /// search query, defaults to the first word of the first popular title
+        #[clap(short, long)]
+        query: Option<String>,
+    },
 }
 
 #[derive(Debug, Serialize)]
@@ -30,6 +48,14 @@ struct SourceIndex {
     source: SourceInfo,
     rustc_version: String,
     lib_version: String,
+    artifacts: HashMap<String, Artifact>,
+}
+
+#[derive(Debug, Serialize)]
+struct Artifact {
+    path: String,
+    rustc_version: String,
+    lib_version: String,
 }
 
 #[tokio::main]
@@ -58,22 +84,69 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
 
             let extension_manager = ExtensionManager::new(&target_dir_path);
-            extension_manager.load_all().await?;
+            extension_manager.load_all(LOAD_CONCURRENCY).await?;
             let source_list = extension_manager.list().await?;
 
             let mut indexes = vec![];
             for source in source_list {
                 let (rustc_version, lib_version) = extension_manager.get_version(source.id)?;
+                let artifact = Artifact {
+                    path: format!("{TARGET}/{}.{PLUGIN_EXTENSION}", source.name.to_lowercase()),
+                    rustc_version: rustc_version.clone(),
+                    lib_version: lib_version.clone(),
+                };
                 indexes.push(SourceIndex {
                     source,
                     rustc_version,
                     lib_version,
+                    artifacts: HashMap::from([(tanoshi_vm::platform(), artifact)]),
                 })
             }
 
             let json = serde_json::to_string(&indexes)?;
             tokio::fs::write(target_dir_path.join("index").with_extension("json"), json).await?;
         }
+        Command::MergeJson { files } => {
+            // merged by source id, top level fields come from the first file
+            let mut merged: Vec<serde_json::Value> = vec![];
+            for file in files {
+                let indexes: Vec<serde_json::Value> =
+                    serde_json::from_slice(&tokio::fs::read(&file).await?)?;
+                for index in indexes {
+                    let existing = merged
+                        .iter_mut()
+                        .find(|merged| merged.get("id").is_some() && merged["id"] == index["id"]);
+                    match existing {
+                        Some(existing) => {
+                            if let (Some(artifacts), Some(other)) = (
+                                existing["artifacts"].as_object_mut(),
+                                index["artifacts"].as_object(),
+                            ) {
+                                artifacts.extend(other.clone());
+                            }
+                        }
+                        None => merged.push(index),
+                    }
+                }
+            }
+
+            tokio::fs::create_dir_all("output").await?;
+            let json = serde_json::to_string(&merged)?;
+            tokio::fs::write(PathBuf::new().join("output").join("index.json"), json).await?;
+        }
+        Command::SourceTest { id, query } => {
+            let extension_manager = ExtensionManager::new(&opts.path);
+            extension_manager.load_all(LOAD_CONCURRENCY).await?;
+            if !extension_manager.exists(id).await? {
+                return Err(format!("source {id} not found in {}", opts.path).into());
+            }
+
+            let report = source_test::run(&extension_manager, id, query).await;
+            report.print();
+            if !report.passed() {
+                std::process::exit(1);
+            }
+        }
     }
 
     Ok(())
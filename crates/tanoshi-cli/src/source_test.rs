@@ -0,0 +1,304 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use tanoshi_lib::prelude::{ChapterInfo, MangaInfo};
+use tanoshi_vm::prelude::ExtensionManager;
+
+/// chapters can't be uploaded further in the future than this, in seconds
+const MAX_CLOCK_SKEW: i64 = 24 * 60 * 60;
+
+/// Outcome of one call of the suite
+pub struct Step {
+    pub name: &'static str,
+    pub elapsed: Duration,
+    /// what was returned, or why the call failed
+    pub summary: String,
+    pub failed: bool,
+    /// returned entities breaking tanoshi-lib invariants
+    pub violations: Vec<String>,
+}
+
+impl Step {
+    fn passed(&self) -> bool {
+        !self.failed && self.violations.is_empty()
+    }
+}
+
+pub struct Report {
+    pub source_id: i64,
+    pub steps: Vec<Step>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(Step::passed)
+    }
+
+    pub fn print(&self) {
+        for step in self.steps.iter() {
+            println!(
+                "{} {:<8} {:>8.2?}  {}",
+                if step.passed() { "PASS" } else { "FAIL" },
+                step.name,
+                step.elapsed,
+                step.summary
+            );
+            for violation in step.violations.iter() {
+                println!("       - {violation}");
+            }
+        }
+
+        let failed = self.steps.iter().filter(|step| !step.passed()).count();
+        println!(
+            "\nsource {}: {} passed, {failed} failed",
+            self.source_id,
+            self.steps.len() - failed
+        );
+    }
+}
+
+fn is_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+fn check_manga(source_id: i64, manga: &MangaInfo, violations: &mut Vec<String>) {
+    let at = if manga.path.is_empty() {
+        manga.title.clone()
+    } else {
+        manga.path.clone()
+    };
+    if manga.source_id != source_id {
+        violations.push(format!("{at}: source_id is {}", manga.source_id));
+    }
+    if manga.title.trim().is_empty() {
+        violations.push(format!("{at}: empty title"));
+    }
+    if manga.path.trim().is_empty() {
+        violations.push(format!("{at}: empty path"));
+    }
+    if !is_url(&manga.cover_url) {
+        violations.push(format!(
+            "{at}: cover_url {:?} is not a url",
+            manga.cover_url
+        ));
+    }
+}
+
+fn check_manga_list(source_id: i64, manga: &[MangaInfo]) -> Vec<String> {
+    let mut violations = vec![];
+    if manga.is_empty() {
+        violations.push("no manga returned".to_string());
+    }
+
+    let mut paths = HashSet::new();
+    for manga in manga {
+        check_manga(source_id, manga, &mut violations);
+        if !paths.insert(manga.path.as_str()) {
+            violations.push(format!("{}: returned more than once", manga.path));
+        }
+    }
+
+    violations
+}
+
+fn check_chapters(source_id: i64, chapters: &[ChapterInfo]) -> Vec<String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs() as i64)
+        .unwrap_or_default();
+
+    let mut violations = vec![];
+    if chapters.is_empty() {
+        violations.push("no chapters returned".to_string());
+    }
+
+    let mut paths = HashSet::new();
+    for chapter in chapters {
+        let at = &chapter.path;
+        if chapter.source_id != source_id {
+            violations.push(format!("{at}: source_id is {}", chapter.source_id));
+        }
+        if chapter.title.trim().is_empty() {
+            violations.push(format!("{at}: empty title"));
+        }
+        if chapter.path.trim().is_empty() {
+            violations.push(format!("chapter {}: empty path", chapter.title));
+        }
+        if !chapter.number.is_finite() || chapter.number < 0.0 {
+            violations.push(format!("{at}: invalid number {}", chapter.number));
+        }
+        if chapter.uploaded <= 0 || chapter.uploaded > now + MAX_CLOCK_SKEW {
+            violations.push(format!(
+                "{at}: uploaded {} is not a unix timestamp in seconds",
+                chapter.uploaded
+            ));
+        }
+        if !paths.insert(chapter.path.as_str()) {
+            violations.push(format!("{at}: returned more than once"));
+        }
+    }
+
+    violations
+}
+
+fn check_pages(pages: &[String]) -> Vec<String> {
+    let mut violations = vec![];
+    if pages.is_empty() {
+        violations.push("no pages returned".to_string());
+    }
+    for page in pages {
+        if !is_url(page) {
+            violations.push(format!("{page:?} is not a url"));
+        }
+    }
+
+    violations
+}
+
+/// Run `f`, recording its outcome in `steps` with `check` validating what it returned
+async fn step<T, F, Fut, C, S>(
+    steps: &mut Vec<Step>,
+    name: &'static str,
+    f: F,
+    check: C,
+    summary: S,
+) -> Option<T>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<T>>,
+    C: FnOnce(&T) -> Vec<String>,
+    S: FnOnce(&T) -> String,
+{
+    let started = Instant::now();
+    let res = f().await;
+    let elapsed = started.elapsed();
+
+    match res {
+        Ok(value) => {
+            steps.push(Step {
+                name,
+                elapsed,
+                summary: summary(&value),
+                failed: false,
+                violations: check(&value),
+            });
+            Some(value)
+        }
+        Err(e) => {
+            steps.push(Step {
+                name,
+                elapsed,
+                summary: format!("{e}"),
+                failed: true,
+                violations: vec![],
+            });
+            None
+        }
+    }
+}
+
+fn skipped(steps: &mut Vec<Step>, name: &'static str, reason: &str) {
+    steps.push(Step {
+        name,
+        elapsed: Duration::default(),
+        summary: format!("skipped, {reason}"),
+        failed: true,
+        violations: vec![],
+    });
+}
+
+/// Call popular, latest, search, detail, chapters and pages of source `source_id` in turn,
+/// detail and below use the first manga and chapter returned before them.
+/// `query` defaults to the first word of the first popular title
+pub async fn run(manager: &ExtensionManager, source_id: i64, query: Option<String>) -> Report {
+    let mut steps = vec![];
+    let manga_summary = |manga: &Vec<MangaInfo>| format!("{} manga", manga.len());
+
+    let popular = step(
+        &mut steps,
+        "popular",
+        || manager.get_popular_manga(source_id, 1),
+        |manga| check_manga_list(source_id, manga),
+        manga_summary,
+    )
+    .await;
+
+    step(
+        &mut steps,
+        "latest",
+        || manager.get_latest_manga(source_id, 1),
+        |manga| check_manga_list(source_id, manga),
+        manga_summary,
+    )
+    .await;
+
+    let first = popular.as_ref().and_then(|manga| manga.first());
+    let query = query.or_else(|| {
+        first
+            .and_then(|manga| manga.title.split_whitespace().next())
+            .map(str::to_string)
+    });
+    match query {
+        Some(query) => {
+            step(
+                &mut steps,
+                "search",
+                || manager.search_manga(source_id, 1, Some(query.clone()), None),
+                |manga| check_manga_list(source_id, manga),
+                |manga| format!("{} manga for {query:?}", manga.len()),
+            )
+            .await;
+        }
+        None => skipped(&mut steps, "search", "no query"),
+    }
+
+    let path = match first {
+        Some(manga) => manga.path.clone(),
+        None => {
+            for name in ["detail", "chapters", "pages"] {
+                skipped(&mut steps, name, "no popular manga");
+            }
+            return Report { source_id, steps };
+        }
+    };
+
+    step(
+        &mut steps,
+        "detail",
+        || manager.get_manga_detail(source_id, path.clone()),
+        |manga| {
+            let mut violations = vec![];
+            check_manga(source_id, manga, &mut violations);
+            violations
+        },
+        |manga| manga.title.clone(),
+    )
+    .await;
+
+    let chapters = step(
+        &mut steps,
+        "chapters",
+        || manager.get_chapters(source_id, path.clone()),
+        |chapters| check_chapters(source_id, chapters),
+        |chapters| format!("{} chapters of {path}", chapters.len()),
+    )
+    .await;
+
+    match chapters.as_ref().and_then(|chapters| chapters.first()) {
+        Some(chapter) => {
+            step(
+                &mut steps,
+                "pages",
+                || manager.get_pages(source_id, chapter.path.clone()),
+                |pages| check_pages(pages),
+                |pages| format!("{} pages of {}", pages.len(), chapter.path),
+            )
+            .await;
+        }
+        None => skipped(&mut steps, "pages", "no chapters"),
+    }
+
+    Report { source_id, steps }
+}
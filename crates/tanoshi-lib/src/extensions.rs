@@ -22,6 +22,31 @@ pub trait Extension: Send + Sync {
         Ok(())
     }
 
+    /// Log in to a source account, the returned session (e.g. cookie) is stored by tanoshi
+    fn login(&self, _username: String, _password: String) -> Result<String> {
+        anyhow::bail!("source doesn't support login")
+    }
+
+    /// Proxy url (`http`, `https` or `socks5`) requests to the source should go through,
+    /// `None` for a direct connection
+    fn set_proxy(&mut self, _proxy: Option<String>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Headers set by the server admin, e.g. `User-Agent`, sent on every request to the
+    /// source in place of the extension's own
+    fn set_header_overrides(&mut self, _headers: HashMap<String, String>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Route `log` records of the extension to `logger`. The extension links its own
+    /// copy of `log`, so this default implementation has to run inside the extension
+    fn set_logger(&self, logger: &'static dyn log::Log, level: log::LevelFilter) {
+        if log::set_logger(logger).is_ok() {
+            log::set_max_level(level);
+        }
+    }
+
     fn get_popular_manga(&self, page: i64) -> Result<Vec<MangaInfo>>;
 
     fn get_latest_manga(&self, page: i64) -> Result<Vec<MangaInfo>>;
@@ -38,6 +63,40 @@ pub trait Extension: Send + Sync {
     fn get_chapters(&self, path: String) -> Result<Vec<ChapterInfo>>;
 
     fn get_pages(&self, path: String) -> Result<Vec<String>>;
+
+    // Calls made for a user logged in to the source, `session` being the one returned by
+    // `login`. An instance is shared by every user, so the session is only good for the call.
+    // Sources without accounts keep these defaults
+
+    fn get_popular_manga_with_session(&self, page: i64, _session: &str) -> Result<Vec<MangaInfo>> {
+        self.get_popular_manga(page)
+    }
+
+    fn get_latest_manga_with_session(&self, page: i64, _session: &str) -> Result<Vec<MangaInfo>> {
+        self.get_latest_manga(page)
+    }
+
+    fn search_manga_with_session(
+        &self,
+        page: i64,
+        query: Option<String>,
+        filters: Option<Vec<Input>>,
+        _session: &str,
+    ) -> Result<Vec<MangaInfo>> {
+        self.search_manga(page, query, filters)
+    }
+
+    fn get_manga_detail_with_session(&self, path: String, _session: &str) -> Result<MangaInfo> {
+        self.get_manga_detail(path)
+    }
+
+    fn get_chapters_with_session(&self, path: String, _session: &str) -> Result<Vec<ChapterInfo>> {
+        self.get_chapters(path)
+    }
+
+    fn get_pages_with_session(&self, path: String, _session: &str) -> Result<Vec<String>> {
+        self.get_pages(path)
+    }
 }
 
 /// A type represents an extension
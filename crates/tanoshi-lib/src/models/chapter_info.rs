@@ -8,5 +8,11 @@ pub struct ChapterInfo {
     pub path: String,
     pub number: f64,
     pub scanlator: Option<String>,
+    /// scanlation groups of this release, `scanlator` split by group
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// language code, `None` when the chapter is in the source language
+    #[serde(default)]
+    pub language: Option<String>,
     pub uploaded: i64,
 }
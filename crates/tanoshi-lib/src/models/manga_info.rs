@@ -5,6 +5,9 @@ use serde::{Deserialize, Serialize};
 pub struct MangaInfo {
     pub source_id: i64,
     pub title: String,
+    /// other titles of the manga, e.g. in other languages
+    #[serde(default)]
+    pub alt_titles: Vec<String>,
     pub author: Vec<String>,
     pub genre: Vec<String>,
     pub status: Option<String>,
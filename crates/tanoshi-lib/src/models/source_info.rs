@@ -8,6 +8,31 @@ pub enum Lang {
     Multi(Vec<String>),
 }
 
+/// What a source supports, so clients can hide actions it can't do instead of failing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Capabilities {
+    /// `get_latest_manga` returns recently updated manga
+    pub latest: bool,
+    /// `search_manga` applies the filters from `filter_list`
+    pub search_filters: bool,
+    /// `get_pages` returns every page of a chapter, so the page count is known before reading
+    pub page_count: bool,
+    /// browsing needs a session from `login`
+    pub login: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            latest: true,
+            search_filters: true,
+            page_count: true,
+            login: false,
+        }
+    }
+}
+
 /// A type represent source
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SourceInfo {
@@ -18,4 +43,7 @@ pub struct SourceInfo {
     pub icon: &'static str,
     pub languages: Lang,
     pub nsfw: bool,
+    /// missing in sources built before capabilities, which get the default
+    #[serde(default)]
+    pub capabilities: Capabilities,
 }
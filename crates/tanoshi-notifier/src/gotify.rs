@@ -25,7 +25,8 @@ impl Notifier for Gotify {
             .query(&[("token", token)])
             .json(&serde_json::json!({ "message": message }))
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
 
         Ok(())
     }
@@ -41,7 +42,8 @@ impl Notifier for Gotify {
             .query(&[("token", token)])
             .json(&serde_json::json!({ "message": message, "title": title }))
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
 
         Ok(())
     }
@@ -67,7 +69,8 @@ impl Notifier for Gotify {
                 }
             }))
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
 
         Ok(())
     }
@@ -3,6 +3,7 @@ extern crate log;
 
 pub mod gotify;
 pub mod pushover;
+pub mod smtp;
 pub mod telegram;
 
 use async_trait::async_trait;
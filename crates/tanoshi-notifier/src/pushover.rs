@@ -35,6 +35,17 @@ struct Payload {
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 struct Response {
     pub status: i32,
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
+
+/// Per message delivery options, unset fields fall back to pushover defaults
+#[derive(Debug, Clone, Default)]
+pub struct PushoverOptions {
+    /// -2 lowest to 1 high, emergency priority is not supported
+    pub priority: i64,
+    pub sound: Option<String>,
+    pub device: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -60,11 +71,40 @@ impl Pushover {
             .await?;
 
         if res.status != 1 {
-            return Err(anyhow!("error push test notification: {}", res.status));
+            return Err(anyhow!(
+                "pushover error {}: {}",
+                res.status,
+                res.errors.join(", ")
+            ));
         }
 
         Ok(())
     }
+
+    pub async fn send_notification_with_options(
+        &self,
+        user_key: &str,
+        title: Option<&str>,
+        message: &str,
+        url: Option<(&str, &str)>,
+        options: &PushoverOptions,
+    ) -> Result<(), Error> {
+        let (url, url_title) = url.unwrap_or_default();
+        let payload = Payload {
+            token: self.token.clone(),
+            user: user_key.to_string(),
+            title: title.unwrap_or_default().to_string(),
+            message: message.to_string(),
+            url: url.to_string(),
+            url_title: url_title.to_string(),
+            priority: options.priority.clamp(-2, 1).to_string(),
+            sound: options.sound.clone().unwrap_or_default(),
+            device: options.device.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+
+        self.send_payload(&payload).await
+    }
 }
 
 #[async_trait]
@@ -0,0 +1,130 @@
+use anyhow::Error;
+use async_trait::async_trait;
+use lettre::{
+    message::{header::ContentType, Attachment, Mailbox, MultiPart, SinglePart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use crate::Notifier;
+
+/// How the connection to the smtp server is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    /// implicit tls, usually port 465
+    Tls,
+    /// upgrade a plain connection, usually port 587
+    StartTls,
+    /// plain text, only for servers on localhost
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpOptions {
+    pub host: String,
+    pub port: Option<u16>,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// sender address, e.g. `Tanoshi <tanoshi@example.com>`
+    pub from: String,
+}
+
+#[derive(Clone)]
+pub struct Smtp {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl Smtp {
+    pub fn new(options: SmtpOptions) -> Result<Self, Error> {
+        let mut builder = match options.security {
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&options.host)?,
+            SmtpSecurity::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&options.host)?
+            }
+            SmtpSecurity::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&options.host)
+            }
+        };
+        if let Some(port) = options.port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (options.username, options.password) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: options.from.parse()?,
+        })
+    }
+
+    async fn send(&self, to: &str, subject: &str, body: SinglePart) -> Result<(), Error> {
+        self.send_multipart(to, subject, MultiPart::mixed().singlepart(body))
+            .await
+    }
+
+    async fn send_multipart(&self, to: &str, subject: &str, body: MultiPart) -> Result<(), Error> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(subject)
+            .multipart(body)?;
+        self.transport.send(message).await?;
+
+        Ok(())
+    }
+
+    /// Send `data` as an attachment named `filename`, e.g. a book to an e-reader's address
+    pub async fn send_attachment(
+        &self,
+        to: &str,
+        subject: &str,
+        filename: &str,
+        content_type: &str,
+        data: Vec<u8>,
+    ) -> Result<(), Error> {
+        let attachment =
+            Attachment::new(filename.to_string()).body(data, ContentType::parse(content_type)?);
+
+        self.send_multipart(
+            to,
+            subject,
+            MultiPart::mixed()
+                .singlepart(SinglePart::plain(subject.to_string()))
+                .singlepart(attachment),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl Notifier for Smtp {
+    async fn send_notification(&self, to: &str, message: &str) -> Result<(), Error> {
+        self.send(to, "Tanoshi", SinglePart::plain(message.to_string()))
+            .await
+    }
+
+    async fn send_notification_with_title(
+        &self,
+        to: &str,
+        title: &str,
+        message: &str,
+    ) -> Result<(), Error> {
+        self.send(to, title, SinglePart::plain(message.to_string()))
+            .await
+    }
+
+    async fn send_notification_with_title_and_url(
+        &self,
+        to: &str,
+        title: &str,
+        message: &str,
+        url: &str,
+        _: &str,
+    ) -> Result<(), Error> {
+        self.send(to, title, SinglePart::plain(format!("{message}\n\n{url}")))
+            .await
+    }
+}
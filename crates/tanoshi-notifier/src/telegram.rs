@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use anyhow::Result;
 use async_trait::async_trait;
 use teloxide::{adaptors::DefaultParseMode, prelude::*, utils::command::BotCommands};
@@ -65,41 +67,88 @@ impl Notifier for Telegram {
     }
 }
 
+/// Interactive commands are answered by the server, every method gets the chat id
+/// of the sender and returns the reply as html
+#[async_trait]
+pub trait TelegramHandler: Send + Sync {
+    /// link the chat to the account that requested pairing `code`
+    async fn pair(&self, chat_id: i64, code: &str) -> Result<String>;
+
+    async fn search(&self, chat_id: i64, query: &str) -> Result<String>;
+
+    async fn latest(&self, chat_id: i64) -> Result<String>;
+
+    async fn unread(&self, chat_id: i64) -> Result<String>;
+
+    async fn mark_read(&self, chat_id: i64, chapter_id: i64) -> Result<String>;
+}
+
 #[derive(BotCommands, Clone)]
 #[command(rename = "lowercase", description = "These commands are supported:")]
 enum TelegramCommand {
     #[command(description = "display this text.")]
     Help,
+    #[command(description = "pair this chat using code from tanoshi profile settings")]
+    Start(String),
     #[command(description = "notify me when there is an update")]
     NotifyMe,
+    #[command(description = "search manga on installed sources")]
+    Search(String),
+    #[command(description = "latest chapters in library")]
+    Latest,
+    #[command(description = "unread chapters in library")]
+    Unread,
+    #[command(description = "mark chapter as read, e.g. /markread 123")]
+    MarkRead(String),
+}
+
+/// Replies are sent in html parse mode, text that isn't markup has to be escaped
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
 }
 
 async fn answer(
     bot: DefaultParseMode<AutoSend<Bot>>,
     message: Message,
     command: TelegramCommand,
+    handler: Arc<dyn TelegramHandler>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    match command {
-        TelegramCommand::Help => {
-            bot.send_message(message.chat.id, TelegramCommand::descriptions().to_string())
-                .await?
-        }
-        TelegramCommand::NotifyMe => {
-            bot.send_message(
-                message.chat.id,
-                format!(
-                    "Put the following chat id on tanoshi profile settings: {}",
-                    message.chat.id
-                ),
-            )
-            .await?
-        }
+    let chat_id = message.chat.id.0;
+    let reply = match command {
+        TelegramCommand::Help => Ok(TelegramCommand::descriptions().to_string()),
+        TelegramCommand::Start(code) if code.trim().is_empty() => Ok(format!(
+            "Send <code>/start CODE</code> with the code from tanoshi profile settings, or put the following chat id on tanoshi profile settings: {chat_id}"
+        )),
+        TelegramCommand::Start(code) => handler.pair(chat_id, code.trim()).await,
+        TelegramCommand::NotifyMe => Ok(format!(
+            "Put the following chat id on tanoshi profile settings: {chat_id}"
+        )),
+        TelegramCommand::Search(query) => handler.search(chat_id, query.trim()).await,
+        TelegramCommand::Latest => handler.latest(chat_id).await,
+        TelegramCommand::Unread => handler.unread(chat_id).await,
+        TelegramCommand::MarkRead(chapter_id) => match chapter_id.trim().parse() {
+            Ok(chapter_id) => handler.mark_read(chat_id, chapter_id).await,
+            Err(_) => Ok("Usage: <code>/markread CHAPTER_ID</code>".to_string()),
+        },
     };
 
+    let reply = reply.unwrap_or_else(|e| format!("Error: {}", escape(&e.to_string())));
+    bot.send_message(message.chat.id, reply).await?;
+
     Ok(())
 }
 
-pub async fn run(bot: Telegram) {
+pub async fn run(bot: Telegram, handler: Arc<dyn TelegramHandler>) {
     info!("start telegram bot");
-    teloxide::commands_repl(bot.0, answer, TelegramCommand::ty()).await;
+    teloxide::commands_repl(
+        bot.0,
+        move |bot: DefaultParseMode<AutoSend<Bot>>, message: Message, command: TelegramCommand| {
+            let handler = handler.clone();
+            async move { answer(bot, message, command, handler).await }
+        },
+        TelegramCommand::ty(),
+    )
+    .await;
 }
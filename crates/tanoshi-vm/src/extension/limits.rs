@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+/// Bounds on a single extension call. Extensions are native libraries running
+/// in-process, so a call over its cpu time is only caught once it returns. Their memory
+/// is the heap of the server and can't be bounded, `memory` only applies to WebAssembly
+/// extensions
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// how long the caller waits for a call, the call itself keeps its thread until it returns
+    pub timeout: Duration,
+    /// cpu time of the thread running the call, results of calls over it are dropped
+    pub cpu_time: Duration,
+    /// items in a returned list, e.g. manga of a page or pages of a chapter
+    pub max_items: usize,
+    /// bytes of memory a WebAssembly extension may grow to, memory is never given back so
+    /// this bounds every call. Set when the extension is loaded
+    pub memory: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(60),
+            cpu_time: Duration::from_secs(30),
+            max_items: 5000,
+            memory: 256 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LimitError {
+    #[error("source {source_id} did not respond within {limit:?}")]
+    Timeout { source_id: i64, limit: Duration },
+    #[error("source {source_id} used {used:?} of cpu time, limit is {limit:?}")]
+    CpuTime {
+        source_id: i64,
+        used: Duration,
+        limit: Duration,
+    },
+    #[error("source {source_id} ran out of memory, limit is {limit} bytes")]
+    Memory { source_id: i64, limit: usize },
+    #[error("source {source_id} returned {len} items, limit is {limit}")]
+    TooManyItems {
+        source_id: i64,
+        len: usize,
+        limit: usize,
+    },
+}
+
+/// Cpu time used by the current thread, `None` where it can't be measured
+#[cfg(unix)]
+pub(crate) fn thread_cpu_time() -> Option<Duration> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let res = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+    if res != 0 {
+        return None;
+    }
+
+    Some(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn thread_cpu_time() -> Option<Duration> {
+    None
+}
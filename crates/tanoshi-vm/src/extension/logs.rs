@@ -0,0 +1,103 @@
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, RwLock},
+    time::SystemTime,
+};
+
+use fnv::FnvHashMap;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use once_cell::sync::Lazy;
+
+/// records kept per source, older ones are dropped
+const LOG_CAPACITY: usize = 500;
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: SystemTime,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+/// Logger handed to an extension, keeps its records and forwards them to the server logger
+pub(crate) struct SourceLogger {
+    entries: Mutex<VecDeque<LogEntry>>,
+}
+
+impl Log for SourceLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= LevelFilter::Debug
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() >= LOG_CAPACITY {
+                entries.pop_front();
+            }
+            entries.push_back(LogEntry {
+                time: SystemTime::now(),
+                level: record.level(),
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+            });
+        }
+
+        // sources built into the server share its `log`, where this may be the global logger
+        let server_logger = log::logger();
+        if !std::ptr::eq(
+            server_logger as *const dyn Log as *const u8,
+            self as *const Self as *const u8,
+        ) && server_logger.enabled(record.metadata())
+        {
+            server_logger.log(record);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+// loggers live as long as the process so extensions can hold `&'static` to them,
+// one per source id is reused across reloads
+static LOGGERS: Lazy<RwLock<FnvHashMap<i64, &'static SourceLogger>>> =
+    Lazy::new(|| RwLock::new(FnvHashMap::default()));
+
+pub(crate) fn source_logger(source_id: i64) -> Option<&'static SourceLogger> {
+    if let Some(logger) = LOGGERS.read().ok()?.get(&source_id) {
+        return Some(logger);
+    }
+
+    let mut loggers = LOGGERS.write().ok()?;
+    Some(*loggers.entry(source_id).or_insert_with(|| {
+        Box::leak(Box::new(SourceLogger {
+            entries: Mutex::new(VecDeque::with_capacity(LOG_CAPACITY)),
+        }))
+    }))
+}
+
+/// Recent records of source `source_id` at `level` or more severe, oldest first
+pub fn source_logs(source_id: i64, level: LevelFilter) -> Vec<LogEntry> {
+    let logger = match LOGGERS
+        .read()
+        .ok()
+        .and_then(|loggers| loggers.get(&source_id).copied())
+    {
+        Some(logger) => logger,
+        None => return vec![],
+    };
+
+    logger
+        .entries
+        .lock()
+        .map(|entries| {
+            entries
+                .iter()
+                .filter(|entry| entry.level <= level)
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
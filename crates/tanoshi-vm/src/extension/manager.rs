@@ -1,19 +1,58 @@
 use std::{
+    collections::HashMap,
     path::{Path, PathBuf},
-    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+    time::{Instant, SystemTime},
 };
 
 use anyhow::{anyhow, bail, Result};
 use fnv::FnvHashMap;
 use libloading::Library;
-use tanoshi_lib::prelude::{Input, PluginDeclaration, SourceInfo};
+use tanoshi_lib::prelude::{Extension, Input, PluginDeclaration, SourceInfo};
+use tokio::sync::Semaphore;
+
+use crate::{
+    extension::{
+        limits::thread_cpu_time,
+        logs::source_logger,
+        supervisor::{guarded, restart_backoff, MAX_RESTART_ATTEMPTS},
+    },
+    prelude::{CrashError, CrashState, LimitError, Limits, RateLimiter, RetryPolicy, Source},
+    PLUGIN_EXTENSION,
+};
+
+/// File name of extension `name` in the extension directory, without anything that
+/// would put it outside of it or hide it from [`ExtensionManager::load_all`]
+fn library_file_name(name: &str) -> String {
+    name.to_lowercase()
+        .replace(|c: char| matches!(c, '/' | '\\' | ':' | '\0'), "_")
+        .trim_start_matches('.')
+        .to_string()
+}
 
-use crate::{prelude::Source, PLUGIN_EXTENSION};
+/// Settings applied to a loaded source, applied again when it gets restarted
+#[derive(Default)]
+struct AppliedSettings {
+    preferences: Option<Vec<Input>>,
+    proxy: Option<String>,
+    header_overrides: Option<HashMap<String, String>>,
+}
 
 #[derive(Clone)]
 pub struct ExtensionManager {
     dir: PathBuf,
-    extensions: Arc<RwLock<FnvHashMap<i64, Source>>>,
+    /// sources are cloned out for calls, so the lock is never held while extension code runs
+    extensions: Arc<RwLock<FnvHashMap<i64, Arc<Source>>>>,
+    rate_limiter: Arc<RwLock<RateLimiter>>,
+    limits: Arc<RwLock<Limits>>,
+    retry: Arc<RwLock<RetryPolicy>>,
+    crashes: Arc<Mutex<FnvHashMap<i64, CrashState>>>,
+    settings: Arc<Mutex<FnvHashMap<i64, AppliedSettings>>>,
+    /// source session calls are made with, see [`ExtensionManager::with_session`]
+    session: Option<String>,
 }
 
 impl ExtensionManager {
@@ -21,33 +60,97 @@ impl ExtensionManager {
         Self {
             dir: PathBuf::new().join(extension_dir),
             extensions: Arc::new(RwLock::new(FnvHashMap::default())),
+            rate_limiter: Arc::new(RwLock::new(RateLimiter::default())),
+            limits: Arc::new(RwLock::new(Limits::default())),
+            retry: Arc::new(RwLock::new(RetryPolicy::default())),
+            crashes: Arc::new(Mutex::new(FnvHashMap::default())),
+            settings: Arc::new(Mutex::new(FnvHashMap::default())),
+            session: None,
+        }
+    }
+
+    /// Handle to the same extensions whose calls are made with a source session,
+    /// e.g. the one returned by [`ExtensionManager::login`] for a user
+    pub fn with_session(&self, session: Option<String>) -> Self {
+        Self {
+            session,
+            ..self.clone()
         }
     }
 
-    fn read(&self) -> Result<RwLockReadGuard<FnvHashMap<i64, Source>>> {
+    fn read(&self) -> Result<RwLockReadGuard<FnvHashMap<i64, Arc<Source>>>> {
         self.extensions
             .read()
             .map_err(|e| anyhow!("failed to lock read: {e}"))
     }
 
-    fn write(&self) -> Result<RwLockWriteGuard<FnvHashMap<i64, Source>>> {
+    fn write(&self) -> Result<RwLockWriteGuard<FnvHashMap<i64, Arc<Source>>>> {
         self.extensions
             .write()
             .map_err(|e| anyhow!("failed to lock write: {e}"))
     }
 
-    pub async fn load_all(&self) -> Result<()> {
+    /// Source `source_id`, the lock is released before any of its extension code runs
+    fn source(&self, source_id: i64) -> Result<Arc<Source>> {
+        self.read()?
+            .get(&source_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("no such source"))
+    }
+
+    /// Load every extension in the directory, up to `concurrency` at a time
+    pub async fn load_all(&self, concurrency: usize) -> Result<()> {
+        let started = Instant::now();
+
+        let mut names = vec![];
         let mut read_dir = tokio::fs::read_dir(&self.dir).await?;
         while let Some(entry) = read_dir.next_entry().await? {
             let mut name = format!("{:?}", entry.file_name());
             name.remove(0);
             name.remove(name.len() - 1);
             if name.ends_with(PLUGIN_EXTENSION) {
-                if let Err(e) = self.load(&name).await {
-                    error!("failed to load {name}: {e}");
-                }
+                names.push(name);
+            }
+            // a native library of the same name takes precedence
+            #[cfg(feature = "wasm")]
+            if name.ends_with(crate::extension::WASM_EXTENSION)
+                && !self
+                    .dir
+                    .join(&name)
+                    .with_extension(PLUGIN_EXTENSION)
+                    .exists()
+            {
+                names.push(name);
             }
         }
+
+        let concurrency = concurrency.max(1);
+        let semaphore = Arc::new(Semaphore::new(concurrency));
+        let count = names.len();
+        let handles: Vec<_> = names
+            .into_iter()
+            .map(|name| {
+                let manager = self.clone();
+                let semaphore = semaphore.clone();
+                tokio::spawn(async move {
+                    let _permit = semaphore.acquire_owned().await;
+                    let started = Instant::now();
+                    match manager.load(&name).await {
+                        Ok(_) => info!("loaded {name} in {:?}", started.elapsed()),
+                        Err(e) => error!("failed to load {name}: {e}"),
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await?;
+        }
+
+        info!(
+            "loaded {count} extensions in {:?} with {concurrency} workers",
+            started.elapsed()
+        );
+
         Ok(())
     }
 
@@ -56,9 +159,9 @@ impl ExtensionManager {
     }
 
     pub async fn list(&self) -> Result<Vec<SourceInfo>> {
-        Ok(self
-            .read()?
-            .values()
+        let sources: Vec<Arc<Source>> = self.read()?.values().cloned().collect();
+        Ok(sources
+            .iter()
             .filter_map(|s| s.extension.get().map(|s| s.get_source_info()))
             .collect())
     }
@@ -72,29 +175,112 @@ impl ExtensionManager {
             PLUGIN_EXTENSION
         );
 
-        info!("downloading {}", source_file_url);
+        self.install_from_url(&source_file_url, name).await
+    }
 
-        let contents = reqwest::get(&source_file_url).await?.bytes().await?;
+    /// Download and load extension from `url`, saved as `name`
+    pub async fn install_from_url(&self, url: &str, name: &str) -> Result<()> {
+        info!("downloading {}", url);
 
-        tokio::fs::write(
-            self.dir
-                .join(&name.to_lowercase())
-                .with_extension(PLUGIN_EXTENSION),
-            contents,
-        )
-        .await?;
+        let contents = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+
+        tokio::fs::write(self.plugin_path(name), contents).await?;
 
         let source = self.load_library(&name.to_lowercase())?;
         self.insert(source).await
     }
 
+    /// Load extension from package `contents` not published in a repository,
+    /// saved under the name of the source it declares, returns the source id
+    pub async fn install_from_bytes(&self, contents: &[u8]) -> Result<i64> {
+        static UPLOADS: AtomicUsize = AtomicUsize::new(0);
+
+        // unique per upload, and not ending with the plugin extension so one left behind
+        // by a crash is never loaded
+        let upload_path = self.dir.join(format!(
+            ".upload-{}-{}.part",
+            std::process::id(),
+            UPLOADS.fetch_add(1, Ordering::Relaxed)
+        ));
+        tokio::fs::write(&upload_path, contents).await?;
+
+        // loaded once just to read its name, the library is unloaded before being renamed
+        let declared = self.load_library_file(&upload_path).and_then(|source| {
+            source
+                .extension
+                .get()
+                .map(|extension| {
+                    let info = extension.get_source_info();
+                    (info.id, info.name.to_lowercase())
+                })
+                .ok_or_else(|| anyhow!("not initiated"))
+        });
+        let (source_id, name) = match declared {
+            Ok(declared) => declared,
+            Err(e) => {
+                tokio::fs::remove_file(&upload_path).await?;
+                return Err(e);
+            }
+        };
+
+        if self.exists(source_id).await? {
+            tokio::fs::remove_file(&upload_path).await?;
+            bail!("{name} installed, uninstall it first");
+        }
+
+        let name = library_file_name(&name);
+        if name.is_empty() {
+            tokio::fs::remove_file(&upload_path).await?;
+            bail!("source has no name to save it as");
+        }
+        tokio::fs::rename(&upload_path, self.plugin_path(&name)).await?;
+
+        let source = self.load_library(&name)?;
+        self.insert(source).await?;
+
+        Ok(source_id)
+    }
+
+    /// Path of extension `name`, with the `wasm` feature a WebAssembly module is used
+    /// when there is no native library of that name
+    fn library_path(&self, name: &str) -> PathBuf {
+        let path = self.plugin_path(name);
+        #[cfg(feature = "wasm")]
+        if !path.exists() {
+            let wasm_path = self
+                .dir
+                .join(library_file_name(name))
+                .with_extension(crate::extension::WASM_EXTENSION);
+            if wasm_path.exists() {
+                return wasm_path;
+            }
+        }
+
+        path
+    }
+
+    /// Path of the native library of extension `name`
+    fn plugin_path(&self, name: &str) -> PathBuf {
+        self.dir
+            .join(library_file_name(name))
+            .with_extension(PLUGIN_EXTENSION)
+    }
+
     fn load_library(&self, name: &str) -> Result<Source> {
-        let library_path = PathBuf::new()
-            .join(&self.dir)
-            .join(name)
-            .with_extension(PLUGIN_EXTENSION);
+        self.load_library_file(&self.library_path(name))
+    }
+
+    fn load_library_file(&self, library_path: &Path) -> Result<Source> {
         info!("load {:?}", library_path.display());
 
+        #[cfg(feature = "wasm")]
+        if library_path.extension() == Some(std::ffi::OsStr::new(crate::extension::WASM_EXTENSION))
+        {
+            let extension =
+                crate::extension::WasmExtension::load(library_path, self.limits()?.memory)?;
+            return Ok(Source::from(Box::new(extension)));
+        }
+
         #[cfg(target_os = "macos")]
         if let Err(e) = std::process::Command::new("install_name_tool")
             .current_dir(library_path.parent().unwrap())
@@ -107,7 +293,7 @@ impl ExtensionManager {
         }
 
         unsafe {
-            let library = Library::new(&library_path)?;
+            let library = Library::new(library_path)?;
 
             let decl = library
                 .get::<*mut PluginDeclaration>(b"plugin_declaration\0")?
@@ -137,7 +323,11 @@ impl ExtensionManager {
     }
 
     pub async fn load(&self, name: &str) -> Result<()> {
-        let mut source = self.load_library(name)?;
+        // opening the library runs its initialization, keep it off the runtime threads
+        let manager = self.clone();
+        let library_name = name.to_string();
+        let mut source =
+            tokio::task::spawn_blocking(move || manager.load_library(&library_name)).await??;
         let source_name = source
             .extension
             .get()
@@ -146,6 +336,7 @@ impl ExtensionManager {
             .name
             .to_lowercase();
 
+        // preferences file written by older versions, kept for upgrades
         if let Some(preferences) =
             tokio::fs::read_to_string(self.dir.join(source_name).with_extension("json"))
                 .await
@@ -163,12 +354,17 @@ impl ExtensionManager {
     }
 
     pub async fn insert(&self, source: Source) -> Result<()> {
-        let info = source
-            .extension
-            .get()
-            .map(|s| s.get_source_info())
-            .ok_or_else(|| anyhow!("error"))?;
-        self.write()?.insert(info.id, source);
+        let extension = source.extension.get().ok_or_else(|| anyhow!("error"))?;
+        let info = extension.get_source_info();
+        if let Some(logger) = source_logger(info.id) {
+            extension.set_logger(logger, log::LevelFilter::Debug);
+        }
+        self.write()?.insert(info.id, Arc::new(source));
+        if let Ok(mut crashes) = self.crashes.lock() {
+            if let Some(state) = crashes.get_mut(&info.id) {
+                state.restarting = false;
+            }
+        }
         Ok(())
     }
 
@@ -178,11 +374,13 @@ impl ExtensionManager {
             .remove(&source_id)
             .and_then(|s| s.extension.get().map(|s| s.get_source_info()))
         {
-            std::fs::remove_file(
-                self.dir
-                    .join(&source.name.to_lowercase())
-                    .with_extension(PLUGIN_EXTENSION),
-            )?;
+            std::fs::remove_file(self.library_path(&source.name.to_lowercase()))?;
+        }
+        if let Ok(mut crashes) = self.crashes.lock() {
+            crashes.remove(&source_id);
+        }
+        if let Ok(mut settings) = self.settings.lock() {
+            settings.remove(&source_id);
         }
         Ok(())
     }
@@ -191,19 +389,59 @@ impl ExtensionManager {
         self.unload(source_id).await
     }
 
+    fn find_by_name(&self, name: &str) -> Result<Option<i64>> {
+        let sources: Vec<(i64, Arc<Source>)> = self
+            .read()?
+            .iter()
+            .map(|(id, source)| (*id, source.clone()))
+            .collect();
+        Ok(sources
+            .iter()
+            .find(|(_, source)| {
+                source
+                    .extension
+                    .get()
+                    .map(|extension| library_file_name(&extension.get_source_info().name) == name)
+                    .unwrap_or(false)
+            })
+            .map(|(id, _)| *id))
+    }
+
+    /// Load the library file `name` again, replacing the source loaded from it before
+    pub async fn reload_file(&self, name: &str) -> Result<i64> {
+        let name = name.to_lowercase();
+        // the old library has to be closed first, otherwise opening the path returns it again
+        if let Some(id) = self.find_by_name(&name)? {
+            self.write()?.remove(&id);
+        }
+
+        let source = self.load_library(&name)?;
+        let source_id = source
+            .extension
+            .get()
+            .map(|extension| extension.get_source_info().id)
+            .ok_or_else(|| anyhow!("not initiated"))?;
+        self.insert(source).await?;
+
+        Ok(source_id)
+    }
+
+    /// Load source `source_id` again from its library file
+    pub async fn reload(&self, source_id: i64) -> Result<()> {
+        let name = self.get_source_info(source_id)?.name.to_lowercase();
+        self.reload_file(&name).await?;
+
+        Ok(())
+    }
+
     pub fn get_version(&self, source_id: i64) -> Result<(String, String)> {
-        let lock = self.read()?;
-        let source = lock
-            .get(&source_id)
-            .ok_or_else(|| anyhow!("no such source"))?;
+        let source = self.source(source_id)?;
         Ok((source.rustc_version.clone(), source.lib_version.clone()))
     }
 
     pub fn get_source_info(&self, source_id: i64) -> Result<SourceInfo> {
         Ok(self
-            .read()?
-            .get(&source_id)
-            .ok_or_else(|| anyhow!("no such source"))?
+            .source(source_id)?
             .extension
             .get()
             .ok_or_else(|| anyhow!("uninitiated"))?
@@ -212,9 +450,7 @@ impl ExtensionManager {
 
     pub fn filter_list(&self, source_id: i64) -> Result<Vec<Input>> {
         Ok(self
-            .read()?
-            .get(&source_id)
-            .ok_or_else(|| anyhow!("no such source"))?
+            .source(source_id)?
             .extension
             .get()
             .ok_or_else(|| anyhow!("uninitiated"))?
@@ -222,54 +458,403 @@ impl ExtensionManager {
     }
 
     pub fn get_preferences(&self, source_id: i64) -> Result<Vec<Input>> {
-        self.read()?
-            .get(&source_id)
-            .ok_or_else(|| anyhow!("no such source"))?
+        self.source(source_id)?
             .extension
             .get()
             .ok_or_else(|| anyhow!("uninitiated"))?
             .get_preferences()
     }
 
+    /// Only applied to the loaded extension, persisting them is up to the caller
     pub async fn set_preferences(&self, source_id: i64, preferences: Vec<Input>) -> Result<()> {
-        self.write()?
-            .get_mut(&source_id)
-            .ok_or_else(|| anyhow!("no such source"))?
-            .extension
-            .get_mut()
-            .ok_or_else(|| anyhow!("uninitiated"))?
-            .set_preferences(preferences.clone())?;
-
-        let source_info = self.get_source_info(source_id)?;
-        tokio::fs::write(
-            self.dir
-                .join(source_info.name.to_lowercase())
-                .with_extension("json"),
-            serde_json::to_string_pretty(&preferences)?,
-        )
+        let applied = preferences.clone();
+        self.configure(source_id, move |extension| {
+            extension.set_preferences(applied)
+        })
         .await?;
 
+        if let Ok(mut settings) = self.settings.lock() {
+            settings.entry(source_id).or_default().preferences = Some(preferences);
+        }
+
         Ok(())
     }
 
-    pub async fn get_popular_manga(
+    pub async fn set_proxy(&self, source_id: i64, proxy: Option<String>) -> Result<()> {
+        let applied = proxy.clone();
+        self.configure(source_id, move |extension| extension.set_proxy(applied))
+            .await?;
+
+        if let Ok(mut settings) = self.settings.lock() {
+            settings.entry(source_id).or_default().proxy = proxy;
+        }
+
+        Ok(())
+    }
+
+    pub async fn set_header_overrides(
         &self,
         source_id: i64,
-        page: i64,
-    ) -> Result<Vec<tanoshi_lib::prelude::MangaInfo>> {
-        let extensions = self.extensions.clone();
-        tokio::task::spawn_blocking(move || {
-            extensions
-                .read()
-                .map_err(|e| anyhow!("failed to lock read: {e}"))?
+        headers: HashMap<String, String>,
+    ) -> Result<()> {
+        let applied = headers.clone();
+        self.configure(source_id, move |extension| {
+            extension.set_header_overrides(applied)
+        })
+        .await?;
+
+        if let Ok(mut settings) = self.settings.lock() {
+            settings.entry(source_id).or_default().header_overrides = Some(headers);
+        }
+
+        Ok(())
+    }
+
+    /// Apply `f` to source `source_id` without holding the lock of the extensions. A source
+    /// with calls in flight can't be changed in place, `f` goes to a new instance of it then
+    async fn configure<F>(&self, source_id: i64, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut dyn Extension) -> Result<()>,
+    {
+        let unused = {
+            let mut extensions = self.write()?;
+            let in_use = extensions
                 .get(&source_id)
-                .ok_or_else(|| anyhow!("no such source"))?
+                .map(|source| Arc::strong_count(source) > 1)
+                .ok_or_else(|| anyhow!("no such source"))?;
+            // nothing else can clone it while the lock is held
+            if in_use {
+                None
+            } else {
+                extensions
+                    .remove(&source_id)
+                    .and_then(|source| Arc::try_unwrap(source).ok())
+            }
+        };
+
+        let mut source = match unused {
+            Some(source) => source,
+            None => self.new_instance(source_id).await?,
+        };
+
+        let res = guarded(source_id, || {
+            f(source
+                .extension
+                .get_mut()
+                .ok_or_else(|| anyhow!("uninitiated"))?
+                .as_mut())
+        });
+        self.write()?.insert(source_id, Arc::new(source));
+
+        res
+    }
+
+    /// Requests per minute of sources without their own limit, `None` is unlimited
+    pub fn set_default_rate_limit(&self, requests_per_minute: Option<u32>) -> Result<()> {
+        self.rate_limiter
+            .write()
+            .map_err(|e| anyhow!("failed to lock write: {e}"))?
+            .set_default(requests_per_minute);
+
+        Ok(())
+    }
+
+    /// Requests per minute of source `source_id`, `None` uses the default
+    pub fn set_rate_limit(&self, source_id: i64, requests_per_minute: Option<u32>) -> Result<()> {
+        self.rate_limiter
+            .write()
+            .map_err(|e| anyhow!("failed to lock write: {e}"))?
+            .set(source_id, requests_per_minute);
+
+        Ok(())
+    }
+
+    /// Wait for a free slot of source `source_id`, every source call does this,
+    /// requests made outside the extension for the source should too
+    pub async fn throttle(&self, source_id: i64) -> Result<()> {
+        let wait = self
+            .rate_limiter
+            .read()
+            .map_err(|e| anyhow!("failed to lock read: {e}"))?
+            .reserve(source_id);
+        if !wait.is_zero() {
+            debug!("source {source_id} rate limited for {wait:?}");
+            tokio::time::sleep(wait).await;
+        }
+
+        Ok(())
+    }
+
+    /// Applied to calls made after, [`Limits::memory`] to extensions loaded after
+    pub fn set_limits(&self, limits: Limits) -> Result<()> {
+        *self
+            .limits
+            .write()
+            .map_err(|e| anyhow!("failed to lock write: {e}"))? = limits;
+
+        Ok(())
+    }
+
+    fn limits(&self) -> Result<Limits> {
+        Ok(*self
+            .limits
+            .read()
+            .map_err(|e| anyhow!("failed to lock read: {e}"))?)
+    }
+
+    pub fn set_retry(&self, retry: RetryPolicy) -> Result<()> {
+        *self
+            .retry
+            .write()
+            .map_err(|e| anyhow!("failed to lock write: {e}"))? = retry;
+
+        Ok(())
+    }
+
+    fn retry(&self) -> Result<RetryPolicy> {
+        Ok(*self
+            .retry
+            .read()
+            .map_err(|e| anyhow!("failed to lock read: {e}"))?)
+    }
+
+    /// [`ExtensionManager::call_once`] retried with backoff as set by [`ExtensionManager::set_retry`]
+    async fn call<T, F>(&self, source_id: i64, f: F) -> Result<T>
+    where
+        F: FnOnce(&dyn Extension) -> Result<T> + Clone + Send + 'static,
+        T: Send + 'static,
+    {
+        if !self.read()?.contains_key(&source_id) {
+            bail!("no such source");
+        }
+
+        let retry = self.retry()?;
+        let mut attempt = 1;
+        loop {
+            match self.call_once(source_id, f.clone()).await {
+                Err(e) if attempt < retry.attempts && RetryPolicy::is_retryable(&e) => {
+                    let backoff = retry.backoff(attempt - 1);
+                    warn!("source {source_id} call failed, retrying in {backoff:?}: {e}");
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Run `f` on a blocking thread against source `source_id` within [`Limits`]
+    async fn call_once<T, F>(&self, source_id: i64, f: F) -> Result<T>
+    where
+        F: FnOnce(&dyn Extension) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        if self
+            .crash_state(source_id)
+            .map(|state| state.restarting)
+            .unwrap_or(false)
+        {
+            return Err(CrashError::Restarting { source_id }.into());
+        }
+
+        self.throttle(source_id).await?;
+
+        let limits = self.limits()?;
+        let source = self.source(source_id)?;
+        let task = tokio::task::spawn_blocking(move || {
+            let started = thread_cpu_time();
+            let res = Self::call_blocking(&source, source_id, f);
+            if let Some(used) = started
+                .zip(thread_cpu_time())
+                .map(|(started, ended)| ended.saturating_sub(started))
+            {
+                if used > limits.cpu_time {
+                    return Err(LimitError::CpuTime {
+                        source_id,
+                        used,
+                        limit: limits.cpu_time,
+                    }
+                    .into());
+                }
+            }
+
+            res
+        });
+
+        let res = match tokio::time::timeout(limits.timeout, task).await {
+            Ok(res) => res?,
+            Err(_) => Err(LimitError::Timeout {
+                source_id,
+                limit: limits.timeout,
+            }
+            .into()),
+        };
+        if let Err(e) = res.as_ref() {
+            if CrashState::is_crash(e) {
+                self.on_crash(source_id, e);
+            }
+        }
+
+        res
+    }
+
+    /// Crash state of source `source_id`, `None` if it never crashed
+    pub fn crash_state(&self, source_id: i64) -> Option<CrashState> {
+        self.crashes.lock().ok()?.get(&source_id).cloned()
+    }
+
+    /// Refuse calls to the source and restart it in the background, unless already restarting
+    fn on_crash(&self, source_id: i64, e: &anyhow::Error) {
+        {
+            let mut crashes = match self.crashes.lock() {
+                Ok(crashes) => crashes,
+                Err(_) => return,
+            };
+            let state = crashes.entry(source_id).or_default();
+            state.last_crash = Some(e.to_string());
+            state.last_crash_at = Some(SystemTime::now());
+            if state.restarting {
+                return;
+            }
+            state.restarting = true;
+        }
+
+        error!("source {source_id} crashed, restarting it: {e}");
+        let manager = self.with_session(None);
+        tokio::spawn(async move { manager.supervise_restart(source_id).await });
+    }
+
+    async fn supervise_restart(&self, source_id: i64) {
+        for attempt in 0..MAX_RESTART_ATTEMPTS {
+            tokio::time::sleep(restart_backoff(attempt)).await;
+            match self.restart(source_id).await {
+                Ok(_) => {
+                    if let Ok(mut crashes) = self.crashes.lock() {
+                        let state = crashes.entry(source_id).or_default();
+                        state.restarts += 1;
+                        state.restarting = false;
+                    }
+                    info!("source {source_id} restarted");
+                    return;
+                }
+                Err(e) => warn!(
+                    "failed to restart source {source_id}, attempt {}: {e}",
+                    attempt + 1
+                ),
+            }
+        }
+
+        error!("source {source_id} left down after {MAX_RESTART_ATTEMPTS} failed restarts, reload it to try again");
+    }
+
+    /// Replace source `source_id` with a new instance, calls still running on the old one
+    /// keep it until they return
+    async fn restart(&self, source_id: i64) -> Result<()> {
+        let source = self.new_instance(source_id).await?;
+        self.write()?.insert(source_id, Arc::new(source));
+
+        Ok(())
+    }
+
+    /// Initialize a new instance of source `source_id` with the settings applied to the old one
+    async fn new_instance(&self, source_id: i64) -> Result<Source> {
+        let source = self.source(source_id)?;
+        let name = guarded(source_id, || {
+            Ok(source
                 .extension
                 .get()
                 .ok_or_else(|| anyhow!("uninitiated"))?
-                .get_popular_manga(page)
+                .get_source_info()
+                .name)
+        })?
+        .to_lowercase();
+
+        // the library stays open, reopening it returns the same one and registers a new instance
+        let manager = self.clone();
+        let mut source = tokio::task::spawn_blocking(move || manager.load_library(&name)).await??;
+        let extension = source
+            .extension
+            .get_mut()
+            .ok_or_else(|| anyhow!("not initiated"))?;
+        if let Some(settings) = self
+            .settings
+            .lock()
+            .map_err(|e| anyhow!("failed to lock settings: {e}"))?
+            .get(&source_id)
+        {
+            if let Some(preferences) = settings.preferences.clone() {
+                extension.set_preferences(preferences)?;
+            }
+            if settings.proxy.is_some() {
+                extension.set_proxy(settings.proxy.clone())?;
+            }
+            if let Some(headers) = settings.header_overrides.clone() {
+                extension.set_header_overrides(headers)?;
+            }
+        }
+        if let Some(logger) = source_logger(source_id) {
+            extension.set_logger(logger, log::LevelFilter::Debug);
+        }
+
+        Ok(source)
+    }
+
+    /// Like [`ExtensionManager::call`] for calls returning a list, also bounded by [`Limits::max_items`]
+    async fn call_list<T, F>(&self, source_id: i64, f: F) -> Result<Vec<T>>
+    where
+        F: FnOnce(&dyn Extension) -> Result<Vec<T>> + Clone + Send + 'static,
+        T: Send + 'static,
+    {
+        let items = self.call(source_id, f).await?;
+        let limit = self.limits()?.max_items;
+        if items.len() > limit {
+            return Err(LimitError::TooManyItems {
+                source_id,
+                len: items.len(),
+                limit,
+            }
+            .into());
+        }
+
+        Ok(items)
+    }
+
+    fn call_blocking<T, F>(source: &Source, source_id: i64, f: F) -> Result<T>
+    where
+        F: FnOnce(&dyn Extension) -> Result<T>,
+    {
+        let extension = source
+            .extension
+            .get()
+            .ok_or_else(|| anyhow!("uninitiated"))?;
+
+        guarded(source_id, || f(extension.as_ref()))
+    }
+
+    pub async fn login(
+        &self,
+        source_id: i64,
+        username: String,
+        password: String,
+    ) -> Result<String> {
+        // wrong credentials won't get any better by retrying
+        self.call_once(source_id, move |extension| {
+            extension.login(username, password)
         })
-        .await?
+        .await
+    }
+
+    pub async fn get_popular_manga(
+        &self,
+        source_id: i64,
+        page: i64,
+    ) -> Result<Vec<tanoshi_lib::prelude::MangaInfo>> {
+        let session = self.session.clone();
+        self.call_list(source_id, move |extension| match session.as_deref() {
+            Some(session) => extension.get_popular_manga_with_session(page, session),
+            None => extension.get_popular_manga(page),
+        })
+        .await
     }
 
     pub async fn get_latest_manga(
@@ -277,19 +862,12 @@ impl ExtensionManager {
         source_id: i64,
         page: i64,
     ) -> Result<Vec<tanoshi_lib::prelude::MangaInfo>> {
-        let extensions = self.extensions.clone();
-        tokio::task::spawn_blocking(move || {
-            extensions
-                .read()
-                .map_err(|e| anyhow!("failed to lock read: {e}"))?
-                .get(&source_id)
-                .ok_or_else(|| anyhow!("no such source"))?
-                .extension
-                .get()
-                .ok_or_else(|| anyhow!("uninitiated"))?
-                .get_latest_manga(page)
+        let session = self.session.clone();
+        self.call_list(source_id, move |extension| match session.as_deref() {
+            Some(session) => extension.get_latest_manga_with_session(page, session),
+            None => extension.get_latest_manga(page),
         })
-        .await?
+        .await
     }
 
     pub async fn search_manga(
@@ -299,19 +877,12 @@ impl ExtensionManager {
         query: Option<String>,
         filters: Option<Vec<Input>>,
     ) -> Result<Vec<tanoshi_lib::prelude::MangaInfo>> {
-        let extensions = self.extensions.clone();
-        tokio::task::spawn_blocking(move || {
-            extensions
-                .read()
-                .map_err(|e| anyhow!("failed to lock read: {e}"))?
-                .get(&source_id)
-                .ok_or_else(|| anyhow!("no such source"))?
-                .extension
-                .get()
-                .ok_or_else(|| anyhow!("uninitiated"))?
-                .search_manga(page, query, filters)
+        let session = self.session.clone();
+        self.call_list(source_id, move |extension| match session.as_deref() {
+            Some(session) => extension.search_manga_with_session(page, query, filters, session),
+            None => extension.search_manga(page, query, filters),
         })
-        .await?
+        .await
     }
 
     pub async fn get_manga_detail(
@@ -319,19 +890,12 @@ impl ExtensionManager {
         source_id: i64,
         path: String,
     ) -> Result<tanoshi_lib::prelude::MangaInfo> {
-        let extensions = self.extensions.clone();
-        tokio::task::spawn_blocking(move || {
-            extensions
-                .read()
-                .map_err(|e| anyhow!("failed to lock read: {e}"))?
-                .get(&source_id)
-                .ok_or_else(|| anyhow!("no such source"))?
-                .extension
-                .get()
-                .ok_or_else(|| anyhow!("uninitiated"))?
-                .get_manga_detail(path)
+        let session = self.session.clone();
+        self.call(source_id, move |extension| match session.as_deref() {
+            Some(session) => extension.get_manga_detail_with_session(path, session),
+            None => extension.get_manga_detail(path),
         })
-        .await?
+        .await
     }
 
     pub async fn get_chapters(
@@ -339,34 +903,20 @@ impl ExtensionManager {
         source_id: i64,
         path: String,
     ) -> Result<Vec<tanoshi_lib::prelude::ChapterInfo>> {
-        let extensions = self.extensions.clone();
-        tokio::task::spawn_blocking(move || {
-            extensions
-                .read()
-                .map_err(|e| anyhow!("failed to lock read: {e}"))?
-                .get(&source_id)
-                .ok_or_else(|| anyhow!("no such source"))?
-                .extension
-                .get()
-                .ok_or_else(|| anyhow!("uninitiated"))?
-                .get_chapters(path)
+        let session = self.session.clone();
+        self.call_list(source_id, move |extension| match session.as_deref() {
+            Some(session) => extension.get_chapters_with_session(path, session),
+            None => extension.get_chapters(path),
         })
-        .await?
+        .await
     }
 
     pub async fn get_pages(&self, source_id: i64, path: String) -> Result<Vec<String>> {
-        let extensions = self.extensions.clone();
-        tokio::task::spawn_blocking(move || {
-            extensions
-                .read()
-                .map_err(|e| anyhow!("failed to lock read: {e}"))?
-                .get(&source_id)
-                .ok_or_else(|| anyhow!("no such source"))?
-                .extension
-                .get()
-                .ok_or_else(|| anyhow!("uninitiated"))?
-                .get_pages(path)
+        let session = self.session.clone();
+        self.call_list(source_id, move |extension| match session.as_deref() {
+            Some(session) => extension.get_pages_with_session(path, session),
+            None => extension.get_pages(path),
         })
-        .await?
+        .await
     }
 }
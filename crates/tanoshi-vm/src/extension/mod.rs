@@ -3,3 +3,23 @@ pub use source::*;
 
 pub mod manager;
 pub use manager::*;
+
+pub mod logs;
+pub use logs::{source_logs, LogEntry};
+
+pub mod limits;
+pub use limits::*;
+
+pub mod rate_limit;
+pub use rate_limit::*;
+
+pub mod retry;
+pub use retry::*;
+
+pub mod supervisor;
+pub use supervisor::{CrashError, CrashState, MAX_RESTART_ATTEMPTS};
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "wasm")]
+pub use wasm::{WasmExtension, WASM_ABI_VERSION, WASM_EXTENSION};
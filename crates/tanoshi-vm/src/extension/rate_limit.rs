@@ -0,0 +1,61 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use fnv::FnvHashMap;
+
+/// Spaces out calls to each source evenly to stay under its requests per minute
+#[derive(Default)]
+pub struct RateLimiter {
+    default: Option<u32>,
+    limits: FnvHashMap<i64, u32>,
+    next_slot: Mutex<FnvHashMap<i64, Instant>>,
+}
+
+impl RateLimiter {
+    /// Limit of sources without their own, `None` is unlimited
+    pub fn set_default(&mut self, requests_per_minute: Option<u32>) {
+        self.default = requests_per_minute.filter(|rpm| *rpm > 0);
+    }
+
+    /// `None` falls back to the default limit
+    pub fn set(&mut self, source_id: i64, requests_per_minute: Option<u32>) {
+        match requests_per_minute {
+            Some(rpm) => self.limits.insert(source_id, rpm),
+            None => self.limits.remove(&source_id),
+        };
+    }
+
+    pub fn get(&self, source_id: i64) -> Option<u32> {
+        self.limits
+            .get(&source_id)
+            .copied()
+            .or(self.default)
+            .filter(|rpm| *rpm > 0)
+    }
+
+    /// Reserve the next free slot of the source, returns how long to wait for it
+    pub fn reserve(&self, source_id: i64) -> Duration {
+        let rpm = match self.get(source_id) {
+            Some(rpm) => rpm,
+            None => return Duration::ZERO,
+        };
+
+        let interval = Duration::from_secs(60) / rpm;
+        let now = Instant::now();
+        let mut next_slot = match self.next_slot.lock() {
+            Ok(next_slot) => next_slot,
+            Err(_) => return Duration::ZERO,
+        };
+
+        let slot = next_slot
+            .get(&source_id)
+            .copied()
+            .filter(|slot| *slot > now)
+            .unwrap_or(now);
+        next_slot.insert(source_id, slot + interval);
+
+        slot - now
+    }
+}
@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use crate::extension::{CrashError, LimitError};
+
+/// How failed extension calls are retried, the wait doubles after every attempt
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// calls made in total, 1 never retries
+    pub attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before the `retry`th retry, starting from 0
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .checked_mul(2_u32.saturating_pow(retry))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+
+    /// Calls over a limit would most likely hit it again, and a crashed source is restarted
+    /// on its own schedule
+    pub fn is_retryable(e: &anyhow::Error) -> bool {
+        e.downcast_ref::<LimitError>().is_none() && e.downcast_ref::<CrashError>().is_none()
+    }
+}
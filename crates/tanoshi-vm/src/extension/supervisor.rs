@@ -0,0 +1,79 @@
+use std::{
+    any::Any,
+    panic::{catch_unwind, AssertUnwindSafe},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+
+use crate::extension::LimitError;
+
+/// restarts tried after a crash before the source is left down until reloaded by hand
+pub const MAX_RESTART_ATTEMPTS: u32 = 5;
+const INITIAL_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(300);
+
+#[derive(Debug, thiserror::Error)]
+pub enum CrashError {
+    #[error("source {source_id} panicked: {message}")]
+    Panicked { source_id: i64, message: String },
+    #[error("source {source_id} crashed and is restarting")]
+    Restarting { source_id: i64 },
+}
+
+/// Crashes of a loaded source, reset when the process restarts
+#[derive(Debug, Clone, Default)]
+pub struct CrashState {
+    /// successful automatic restarts
+    pub restarts: u32,
+    /// calls are refused until the source is loaded again
+    pub restarting: bool,
+    pub last_crash: Option<String>,
+    pub last_crash_at: Option<SystemTime>,
+}
+
+impl CrashState {
+    /// Panics and calls that never came back, a call over its cpu time did return
+    pub fn is_crash(e: &anyhow::Error) -> bool {
+        matches!(
+            e.downcast_ref::<CrashError>(),
+            Some(CrashError::Panicked { .. })
+        ) || matches!(
+            e.downcast_ref::<LimitError>(),
+            Some(LimitError::Timeout { .. })
+        )
+    }
+}
+
+/// Wait before the `attempt`th restart, starting from 0
+pub(crate) fn restart_backoff(attempt: u32) -> Duration {
+    INITIAL_RESTART_BACKOFF
+        .checked_mul(2_u32.saturating_pow(attempt))
+        .unwrap_or(MAX_RESTART_BACKOFF)
+        .min(MAX_RESTART_BACKOFF)
+}
+
+/// Run `f`, turning a panic unwinding out of the extension into [`CrashError::Panicked`].
+/// Extensions built with `panic = "abort"` still take the whole process down
+pub(crate) fn guarded<T, F>(source_id: i64, f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        Err(CrashError::Panicked {
+            source_id,
+            message: panic_message(payload),
+        }
+        .into())
+    })
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
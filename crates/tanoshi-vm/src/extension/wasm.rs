@@ -0,0 +1,574 @@
+use std::{collections::HashMap, io::Read, net::IpAddr, path::Path, sync::Mutex, time::Duration};
+
+use anyhow::{anyhow, bail, Result};
+use once_cell::sync::OnceCell;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tanoshi_lib::prelude::{
+    Capabilities, ChapterInfo, Extension, Input, Lang, MangaInfo, SourceInfo,
+};
+use tanoshi_util::http::{Headers, Request, Response};
+
+use crate::extension::LimitError;
+use wasmtime::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+    Trap, TypedFunc,
+};
+
+pub const WASM_EXTENSION: &str = "wasm";
+/// version of the interface described on [`WasmExtension`], bumped on breaking changes
+pub const WASM_ABI_VERSION: i32 = 1;
+
+/// instructions a single call may run, roughly a few seconds of cpu time
+const FUEL_PER_CALL: u64 = 10_000_000_000;
+/// size of a page of module memory, memory grows by whole pages
+const WASM_PAGE_SIZE: usize = 64 * 1024;
+const MAX_RESPONSE_SIZE: u64 = 32 * 1024 * 1024;
+const MAX_REDIRECTS: usize = 10;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// status of a response to a request that never got one, same as tanoshi-util
+const TRANSPORT_ERROR_STATUS: i32 = 9999;
+
+fn engine() -> Result<&'static Engine> {
+    static ENGINE: OnceCell<Engine> = OnceCell::new();
+    ENGINE.get_or_try_init(|| {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Engine::new(&config)
+    })
+}
+
+struct HostState {
+    name: String,
+    agent: ureq::Agent,
+    /// replace headers of the same name set by the module
+    header_overrides: HashMap<String, String>,
+    limits: StoreLimits,
+    logger: Option<&'static dyn log::Log>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum GuestResponse {
+    Ok(Value),
+    Err(String),
+}
+
+/// [`SourceInfo`] as sent by the module, the `&'static str` fields are leaked once per load
+#[derive(Deserialize)]
+struct GuestSourceInfo {
+    id: i64,
+    name: String,
+    url: String,
+    version: String,
+    icon: String,
+    languages: Lang,
+    nsfw: bool,
+    #[serde(default)]
+    capabilities: Capabilities,
+}
+
+#[derive(Deserialize)]
+struct HtmlSelect {
+    html: String,
+    selector: String,
+    attr: Option<String>,
+}
+
+struct Guest {
+    store: Store<HostState>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    dealloc: TypedFunc<(i32, i32), ()>,
+    call: TypedFunc<(i32, i32), i64>,
+    memory_limit: usize,
+}
+
+impl Guest {
+    /// Module memory can't grow by another page, failing calls most likely ran out of it
+    fn at_memory_limit(&self) -> bool {
+        self.memory.data_size(&self.store) + WASM_PAGE_SIZE > self.memory_limit
+    }
+
+    fn call<T: DeserializeOwned>(&mut self, request: &Value) -> Result<T> {
+        // every call starts with the same budget
+        let remaining = self.store.consume_fuel(0)?;
+        self.store
+            .add_fuel(FUEL_PER_CALL.saturating_sub(remaining))?;
+
+        let input = serde_json::to_vec(request)?;
+        let len = input.len() as i32;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory.write(&mut self.store, ptr as usize, &input)?;
+        let packed = self.call.call(&mut self.store, (ptr, len))?;
+        self.dealloc.call(&mut self.store, (ptr, len))?;
+
+        let (out_ptr, out_len) = unpack(packed);
+        let mut output = vec![0; out_len];
+        self.memory.read(&self.store, out_ptr, &mut output)?;
+        self.dealloc
+            .call(&mut self.store, (out_ptr as i32, out_len as i32))?;
+
+        match serde_json::from_slice(&output)? {
+            GuestResponse::Ok(value) => Ok(serde_json::from_value(value)?),
+            GuestResponse::Err(message) => bail!(message),
+        }
+    }
+}
+
+/// Extension compiled to WebAssembly, loaded when built with the `wasm` feature.
+///
+/// A module has no access to the host besides the functions imported from the `tanoshi`
+/// module, so it can be written in any language compiling to WebAssembly. Values cross
+/// the boundary as JSON in the module memory, a pointer and length pair returned from a
+/// function is packed in an `i64` as `ptr << 32 | len`.
+///
+/// The module exports
+/// - `memory`
+/// - `abi_version() -> i32`, [`WASM_ABI_VERSION`]
+/// - `alloc(len: i32) -> i32` and `dealloc(ptr: i32, len: i32)`, used by the host for
+///   every buffer passed in either direction
+/// - `call(ptr: i32, len: i32) -> i64`, with `{"method": "get_popular_manga", "params": {..},
+///   "session": ..}` as input, `method` being the name of an [`Extension`] method, and
+///   `{"ok": ..}` or `{"err": "message"}` as output
+///
+/// and may import
+/// - `http_request(ptr: i32, len: i32) -> i64`, [`tanoshi_util::http::Request`] in and
+///   [`tanoshi_util::http::Response`] out. Only `http` and `https` urls of public hosts
+///   are allowed
+/// - `html_select(ptr: i32, len: i32) -> i64`, `{"html": .., "selector": .., "attr": ..}`
+///   in, text or `attr` of every element matching the css selector out
+/// - `log(level: i32, ptr: i32, len: i32)`, level 1 is error down to 5 for trace
+pub struct WasmExtension {
+    info: SourceInfo,
+    guest: Mutex<Guest>,
+}
+
+impl WasmExtension {
+    /// Module memory is bounded to `memory_limit` bytes, see [`crate::extension::Limits`]
+    pub fn load(path: &Path, memory_limit: usize) -> Result<Self> {
+        let engine = engine()?;
+        let module = Module::from_file(engine, path)?;
+
+        let mut linker = Linker::new(engine);
+        linker.func_wrap("tanoshi", "http_request", host_http_request)?;
+        linker.func_wrap("tanoshi", "html_select", host_html_select)?;
+        linker.func_wrap("tanoshi", "log", host_log)?;
+
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut store = Store::new(
+            engine,
+            HostState {
+                name,
+                agent: agent(None)?,
+                header_overrides: HashMap::new(),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(memory_limit)
+                    .instances(1)
+                    .build(),
+                logger: None,
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store.add_fuel(FUEL_PER_CALL)?;
+
+        let instance = linker.instantiate(&mut store, &module)?;
+        let abi_version = instance
+            .get_typed_func::<(), i32, _>(&mut store, "abi_version")?
+            .call(&mut store, ())?;
+        if abi_version != WASM_ABI_VERSION {
+            bail!(
+                "Version mismatch: extension.abi_version={abi_version} != tanoshi_vm::abi_version={WASM_ABI_VERSION}"
+            );
+        }
+
+        let mut guest = Guest {
+            memory: instance
+                .get_memory(&mut store, "memory")
+                .ok_or_else(|| anyhow!("module doesn't export memory"))?,
+            alloc: instance.get_typed_func(&mut store, "alloc")?,
+            dealloc: instance.get_typed_func(&mut store, "dealloc")?,
+            call: instance.get_typed_func(&mut store, "call")?,
+            store,
+            memory_limit,
+        };
+
+        let info: GuestSourceInfo = guest.call(&json!({ "method": "get_source_info" }))?;
+        let info = SourceInfo {
+            id: info.id,
+            name: info.name,
+            url: info.url,
+            version: Box::leak(info.version.into_boxed_str()),
+            icon: Box::leak(info.icon.into_boxed_str()),
+            languages: info.languages,
+            nsfw: info.nsfw,
+            capabilities: info.capabilities,
+        };
+
+        Ok(Self {
+            info,
+            guest: Mutex::new(guest),
+        })
+    }
+
+    fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        self.call_with_session(method, params, None)
+    }
+
+    fn call_with_session<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+        session: Option<&str>,
+    ) -> Result<T> {
+        let mut guest = self
+            .guest
+            .lock()
+            .map_err(|e| anyhow!("failed to lock module: {e}"))?;
+        guest
+            .call(&json!({
+                "method": method,
+                "params": params,
+                "session": session,
+            }))
+            .map_err(|e| {
+                if guest.at_memory_limit() {
+                    LimitError::Memory {
+                        source_id: self.info.id,
+                        limit: guest.memory_limit,
+                    }
+                    .into()
+                } else {
+                    e
+                }
+            })
+    }
+}
+
+impl Extension for WasmExtension {
+    fn get_source_info(&self) -> SourceInfo {
+        self.info.clone()
+    }
+
+    fn headers(&self) -> HashMap<String, String> {
+        self.call("headers", Value::Null).unwrap_or_default()
+    }
+
+    fn filter_list(&self) -> Vec<Input> {
+        self.call("filter_list", Value::Null).unwrap_or_else(|e| {
+            error!("failed to get filters of {}: {e}", self.info.name);
+            vec![]
+        })
+    }
+
+    fn get_preferences(&self) -> Result<Vec<Input>> {
+        self.call("get_preferences", Value::Null)
+    }
+
+    fn set_preferences(&mut self, preferences: Vec<Input>) -> Result<()> {
+        self.call("set_preferences", json!({ "preferences": preferences }))
+    }
+
+    fn login(&self, username: String, password: String) -> Result<String> {
+        self.call(
+            "login",
+            json!({ "username": username, "password": password }),
+        )
+    }
+
+    fn set_proxy(&mut self, proxy: Option<String>) -> Result<()> {
+        let agent = agent(proxy.as_deref())?;
+        self.guest
+            .get_mut()
+            .map_err(|e| anyhow!("failed to lock module: {e}"))?
+            .store
+            .data_mut()
+            .agent = agent;
+        Ok(())
+    }
+
+    fn set_header_overrides(&mut self, headers: HashMap<String, String>) -> Result<()> {
+        self.guest
+            .get_mut()
+            .map_err(|e| anyhow!("failed to lock module: {e}"))?
+            .store
+            .data_mut()
+            .header_overrides = headers;
+        Ok(())
+    }
+
+    fn set_logger(&self, logger: &'static dyn log::Log, _level: log::LevelFilter) {
+        if let Ok(mut guest) = self.guest.lock() {
+            guest.store.data_mut().logger = Some(logger);
+        }
+    }
+
+    fn get_popular_manga(&self, page: i64) -> Result<Vec<MangaInfo>> {
+        self.call("get_popular_manga", json!({ "page": page }))
+    }
+
+    fn get_latest_manga(&self, page: i64) -> Result<Vec<MangaInfo>> {
+        self.call("get_latest_manga", json!({ "page": page }))
+    }
+
+    fn search_manga(
+        &self,
+        page: i64,
+        query: Option<String>,
+        filters: Option<Vec<Input>>,
+    ) -> Result<Vec<MangaInfo>> {
+        self.call(
+            "search_manga",
+            json!({ "page": page, "query": query, "filters": filters }),
+        )
+    }
+
+    fn get_manga_detail(&self, path: String) -> Result<MangaInfo> {
+        self.call("get_manga_detail", json!({ "path": path }))
+    }
+
+    fn get_chapters(&self, path: String) -> Result<Vec<ChapterInfo>> {
+        self.call("get_chapters", json!({ "path": path }))
+    }
+
+    fn get_pages(&self, path: String) -> Result<Vec<String>> {
+        self.call("get_pages", json!({ "path": path }))
+    }
+
+    fn get_popular_manga_with_session(&self, page: i64, session: &str) -> Result<Vec<MangaInfo>> {
+        self.call_with_session("get_popular_manga", json!({ "page": page }), Some(session))
+    }
+
+    fn get_latest_manga_with_session(&self, page: i64, session: &str) -> Result<Vec<MangaInfo>> {
+        self.call_with_session("get_latest_manga", json!({ "page": page }), Some(session))
+    }
+
+    fn search_manga_with_session(
+        &self,
+        page: i64,
+        query: Option<String>,
+        filters: Option<Vec<Input>>,
+        session: &str,
+    ) -> Result<Vec<MangaInfo>> {
+        self.call_with_session(
+            "search_manga",
+            json!({ "page": page, "query": query, "filters": filters }),
+            Some(session),
+        )
+    }
+
+    fn get_manga_detail_with_session(&self, path: String, session: &str) -> Result<MangaInfo> {
+        self.call_with_session("get_manga_detail", json!({ "path": path }), Some(session))
+    }
+
+    fn get_chapters_with_session(&self, path: String, session: &str) -> Result<Vec<ChapterInfo>> {
+        self.call_with_session("get_chapters", json!({ "path": path }), Some(session))
+    }
+
+    fn get_pages_with_session(&self, path: String, session: &str) -> Result<Vec<String>> {
+        self.call_with_session("get_pages", json!({ "path": path }), Some(session))
+    }
+}
+
+fn unpack(packed: i64) -> (usize, usize) {
+    ((packed >> 32) as u32 as usize, packed as u32 as usize)
+}
+
+fn agent(proxy: Option<&str>) -> Result<ureq::Agent> {
+    let mut builder = ureq::AgentBuilder::new()
+        .timeout(REQUEST_TIMEOUT)
+        // followed by hand so every hop goes through `check_url`
+        .redirects(0);
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(ureq::Proxy::new(proxy)?);
+    }
+
+    Ok(builder.build())
+}
+
+/// Modules may only reach public http hosts, not the server itself or its network
+fn check_url(url: &str) -> Result<()> {
+    let url = reqwest::Url::parse(url)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!("scheme {} not allowed", url.scheme());
+    }
+
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("url without host"))?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_lowercase();
+    if host == "localhost" || host.ends_with(".localhost") {
+        bail!("host {host} not allowed");
+    }
+    let private = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+        }
+        Ok(IpAddr::V6(ip)) => ip.is_loopback() || ip.is_unspecified(),
+        Err(_) => false,
+    };
+    if private {
+        bail!("host {host} not allowed");
+    }
+
+    Ok(())
+}
+
+fn http_request(state: &HostState, req: Request) -> Result<Response> {
+    let mut url = req.url;
+    for _ in 0..=MAX_REDIRECTS {
+        check_url(&url)?;
+
+        let mut request = state.agent.request(&req.method, &url);
+        for (key, values) in req.headers.iter().flatten() {
+            for value in values {
+                request = request.set(key, value);
+            }
+        }
+        for (key, value) in state.header_overrides.iter() {
+            request = request.set(key, value);
+        }
+        let res = match req.body.as_deref() {
+            Some(body) => request.send_string(body),
+            None => request.call(),
+        };
+        let response = match res {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(e.into()),
+        };
+
+        if (300..400).contains(&response.status()) {
+            if let Some(location) = response.header("location") {
+                url = reqwest::Url::parse(&url)?.join(location)?.to_string();
+                continue;
+            }
+        }
+
+        let status = response.status() as i32;
+        let mut headers = Headers::new();
+        for name in response.headers_names() {
+            headers.insert(
+                name.clone(),
+                response.all(&name).iter().map(|v| v.to_string()).collect(),
+            );
+        }
+        let mut body = String::new();
+        response
+            .into_reader()
+            .take(MAX_RESPONSE_SIZE)
+            .read_to_string(&mut body)?;
+
+        return Ok(Response {
+            headers,
+            body,
+            status,
+        });
+    }
+
+    bail!("too many redirects")
+}
+
+fn html_select(req: HtmlSelect) -> Result<Vec<String>> {
+    let document = scraper::Html::parse_document(&req.html);
+    let selector = scraper::Selector::parse(&req.selector)
+        .map_err(|e| anyhow!("invalid selector {}: {e:?}", req.selector))?;
+
+    Ok(document
+        .select(&selector)
+        .filter_map(|element| match req.attr.as_deref() {
+            Some(attr) => element.value().attr(attr).map(str::to_string),
+            None => Some(element.text().collect()),
+        })
+        .collect())
+}
+
+fn memory(caller: &mut Caller<'_, HostState>) -> Result<Memory, Trap> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| Trap::new("module doesn't export memory"))
+}
+
+fn read_bytes(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Result<Vec<u8>, Trap> {
+    let mut buf = vec![0; len as usize];
+    memory(caller)?
+        .read(&*caller, ptr as usize, &mut buf)
+        .map_err(|e| Trap::new(e.to_string()))?;
+
+    Ok(buf)
+}
+
+/// Copy `value` as JSON into a buffer allocated by the module, returns it packed
+fn write_json<T: Serialize>(caller: &mut Caller<'_, HostState>, value: &T) -> Result<i64, Trap> {
+    let output = serde_json::to_vec(value).map_err(|e| Trap::new(e.to_string()))?;
+    let alloc = caller
+        .get_export("alloc")
+        .and_then(Extern::into_func)
+        .ok_or_else(|| Trap::new("module doesn't export alloc"))?
+        .typed::<i32, i32, _>(&*caller)
+        .map_err(|e| Trap::new(e.to_string()))?;
+    let ptr = alloc.call(&mut *caller, output.len() as i32)?;
+    memory(caller)?
+        .write(&mut *caller, ptr as usize, &output)
+        .map_err(|e| Trap::new(e.to_string()))?;
+
+    Ok(((ptr as i64) << 32) | output.len() as i64)
+}
+
+fn host_http_request(mut caller: Caller<'_, HostState>, ptr: i32, len: i32) -> Result<i64, Trap> {
+    let input = read_bytes(&mut caller, ptr, len)?;
+    let response = serde_json::from_slice::<Request>(&input)
+        .map_err(anyhow::Error::from)
+        .and_then(|req| http_request(caller.data(), req))
+        .unwrap_or_else(|e| Response {
+            headers: Headers::new(),
+            body: e.to_string(),
+            status: TRANSPORT_ERROR_STATUS,
+        });
+
+    write_json(&mut caller, &response)
+}
+
+fn host_html_select(mut caller: Caller<'_, HostState>, ptr: i32, len: i32) -> Result<i64, Trap> {
+    let input = read_bytes(&mut caller, ptr, len)?;
+    let selected = serde_json::from_slice::<HtmlSelect>(&input)
+        .map_err(anyhow::Error::from)
+        .and_then(html_select)
+        .map_err(|e| Trap::new(e.to_string()))?;
+
+    write_json(&mut caller, &selected)
+}
+
+fn host_log(mut caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32) -> Result<(), Trap> {
+    let message = String::from_utf8_lossy(&read_bytes(&mut caller, ptr, len)?).to_string();
+    let level = match level {
+        1 => log::Level::Error,
+        2 => log::Level::Warn,
+        3 => log::Level::Info,
+        4 => log::Level::Debug,
+        _ => log::Level::Trace,
+    };
+
+    let state = caller.data();
+    match state.logger {
+        Some(logger) => logger.log(
+            &log::Record::builder()
+                .args(format_args!("{message}"))
+                .level(level)
+                .target(&state.name)
+                .build(),
+        ),
+        None => log!(target: "extension", level, "[{}] {message}", state.name),
+    }
+
+    Ok(())
+}
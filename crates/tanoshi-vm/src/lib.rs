@@ -10,3 +10,8 @@ pub const PLUGIN_EXTENSION: &str = "dll";
 pub const PLUGIN_EXTENSION: &str = "dylib";
 #[cfg(target_os = "linux")]
 pub const PLUGIN_EXTENSION: &str = "so";
+
+/// Key of extension artifacts built for the running platform, e.g. `linux-aarch64`
+pub fn platform() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
@@ -1,10 +1,11 @@
 use std::rc::Rc;
 
-use dominator::{clone, html, routing, Dom};
+use dominator::{clone, events, html, routing, Dom};
 use futures_signals::{
     map_ref,
     signal::{Mutable, Signal, SignalExt},
 };
+use pulldown_cmark::{html::push_html, Event, Parser, Tag};
 use wasm_bindgen::UnwrapThrowExt;
 
 use crate::{
@@ -18,15 +19,48 @@ use crate::{
     manga::Manga,
     query,
     reader::Reader,
+    reading_list::ReadingList,
     settings::Settings,
+    share::Share,
     tracker_login::TrackerLogin,
     tracker_redirect::TrackerRedirect,
     updates::Updates,
     utils::{local_storage, AsyncLoader},
 };
 
+/// Announcement markdown as html. Html written in it is shown as text and links only keep
+/// http, https and mailto urls, so an announcement can't run scripts
+fn announcement_html(markdown: &str) -> String {
+    let safe_url = |url: &str| {
+        let url = url.trim().to_lowercase();
+        let scheme_end = url
+            .find(|c| matches!(c, '/' | '?' | '#'))
+            .unwrap_or(url.len());
+        !url[..scheme_end].contains(':')
+            || ["http:", "https:", "mailto:"]
+                .iter()
+                .any(|scheme| url.starts_with(scheme))
+    };
+
+    let events = Parser::new(markdown).map(|event| match event {
+        Event::Html(html) => Event::Text(html),
+        Event::Start(Tag::Link(kind, url, title)) if !safe_url(&url) => {
+            Event::Start(Tag::Link(kind, "".into(), title))
+        }
+        Event::Start(Tag::Image(kind, url, title)) if !safe_url(&url) => {
+            Event::Start(Tag::Image(kind, "".into(), title))
+        }
+        event => event,
+    });
+
+    let mut html = String::new();
+    push_html(&mut html, events);
+    html
+}
+
 pub struct App {
     pub server_status: Mutable<Option<ServerStatus>>,
+    pub announcement: Mutable<Option<String>>,
     pub spinner: Rc<Spinner>,
     pub loader: AsyncLoader,
 }
@@ -35,6 +69,7 @@ impl App {
     pub fn new() -> Rc<Self> {
         Rc::new(App {
             server_status: Mutable::new(None),
+            announcement: Mutable::new(None),
             spinner: Spinner::new(),
             loader: AsyncLoader::new(),
         })
@@ -49,6 +84,9 @@ impl App {
                         version: server_status.version,
                         loggedin: server_status.loggedin
                     }));
+
+                    let dismissed = local_storage().get("dismissed_announcement").unwrap_throw();
+                    app.announcement.set(server_status.announcement.filter(|announcement| Some(announcement) != dismissed.as_ref()));
                 }
                 Err(e) => {
                     snackbar::show(format!("error check server status: {}", e));
@@ -66,6 +104,31 @@ impl App {
         }
     }
 
+    fn render_announcement(app: Rc<Self>) -> impl Signal<Item = Option<Dom>> {
+        app.announcement.signal_cloned().map(clone!(app => move |announcement| {
+            announcement.map(|announcement| html!("div", {
+                .class("announcement")
+                .children(&mut [
+                    html!("div", {
+                        .children(&mut [
+                            html!("div", {
+                                .class("announcement-message")
+                                .prop("innerHTML", announcement_html(&announcement))
+                            }),
+                            html!("button", {
+                                .text("Dismiss")
+                                .event(clone!(app, announcement => move |_: events::Click| {
+                                    local_storage().set("dismissed_announcement", &announcement).unwrap_throw();
+                                    app.announcement.set(None);
+                                }))
+                            }),
+                        ])
+                    })
+                ])
+            }))
+        }))
+    }
+
     pub fn render(app: Rc<Self>) -> Dom {
         Self::fetch_server_status(app.clone());
 
@@ -107,6 +170,9 @@ impl App {
                     Route::Library(category_id) => Some(
                         Library::render(Library::new(category_id)),
                     ),
+                    Route::ReadingList(id) => Some(
+                        ReadingList::render(ReadingList::new(id)),
+                    ),
                     Route::CatalogueList => Some(
                         CatalogueList::render(CatalogueList::new()),
                     ),
@@ -128,6 +194,9 @@ impl App {
                     Route::Histories => Some(
                         Histories::render(Histories::new(), app.clone()),
                     ),
+                    Route::Share(url) => Some(
+                        Share::render(Share::new(url)),
+                    ),
                     Route::Settings(category) => {
                         let server_version = app.server_status.get_cloned().unwrap_or_default().version;
                         Some(Settings::new(server_version, category).render())
@@ -147,7 +216,7 @@ impl App {
             })))
             .child_signal(Route::signal().map(|x| {
                 match x {
-                    Route::LibraryList | Route::Library(_) | Route::CatalogueList | Route::Updates | Route::Histories | Route::Settings(SettingCategory::None) => Some(html!("div", {
+                    Route::LibraryList | Route::Library(_) | Route::ReadingList(_) | Route::CatalogueList | Route::Updates | Route::Histories | Route::Settings(SettingCategory::None) => Some(html!("div", {
                         .children(&mut [
                             html!("div", {
                                 .class("bottombar-spacing")
@@ -158,6 +227,7 @@ impl App {
                     _ => None,
                 }
             }))
+            .child_signal(Self::render_announcement(app.clone()))
             .children(&mut [
                 snackbar::render(),
             ])
@@ -145,10 +145,10 @@ impl Catalogue {
                     }
                 }
             } else {
-                match query::get_popular_manga(catalogue.source_id, catalogue.page.get()).await {
+                match query::get_catalogue_manga(catalogue.source_id, catalogue.page.get()).await {
                     Ok(data) => {
                         catalogue.source_name.set(data.source.name.clone());
-                        map_data_to_cover!(data, catalogue, get_popular_manga);
+                        map_data_to_cover!(data, catalogue, get_catalogue_manga);
                     }
                     Err(e) => {
                         snackbar::show(format!("Fetch manga from source failed: {}", e))
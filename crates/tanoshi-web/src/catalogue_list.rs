@@ -144,8 +144,28 @@ impl CatalogueList {
             .class("topbar")
             .class_signal("tauri", is_tauri_signal())
             .child_signal(catalogue.is_search.signal().map(|is_search| {
-                (!is_search).then(|| html!("div", {
-                    .style("width", "24px")
+                (!is_search).then(|| html!("button", {
+                    .attribute("id", "open-url")
+                    .event(|_: events::Click| {
+                        routing::go_to_url(&Route::Share(None).url());
+                    })
+                    .children(&mut [
+                        svg!("svg", {
+                            .attribute("xmlns", "http://www.w3.org/2000/svg")
+                            .attribute("fill", "none")
+                            .attribute("viewBox", "0 0 24 24")
+                            .attribute("stroke", "currentColor")
+                            .class("icon")
+                            .children(&mut [
+                                svg!("path", {
+                                    .attribute("stroke-linecap", "round")
+                                    .attribute("stroke-linejoin", "round")
+                                    .attribute("stroke-width", "2")
+                                    .attribute("d", "M13.828 10.172a4 4 0 00-5.656 0l-4 4a4 4 0 105.656 5.656l1.102-1.101m-.758-4.899a4 4 0 005.656 0l4-4a4 4 0 00-5.656-5.656l-1.1 1.1")
+                                })
+                            ])
+                        }),
+                    ])
                 }))
             }))
             .child_signal(catalogue.is_search.signal().map(clone!(catalogue => move |is_search| {
@@ -25,7 +25,7 @@ impl Bottombar {
             .class_signal("tauri", is_tauri_signal())
             .children(&mut [
                 link!(Route::Root.url(), {
-                    .class_signal("active", Route::signal().map(|x| matches!(x, Route::LibraryList | Route::Library(_))))
+                    .class_signal("active", Route::signal().map(|x| matches!(x, Route::LibraryList | Route::Library(_) | Route::ReadingList(_))))
                     .children(&mut [
                         svg!("svg", {
                             .attribute("xmlns", "http://www.w3.org/2000/svg")
@@ -123,8 +123,11 @@ impl LibrarySettings {
                     for c in res {
                         categories.push_cloned(Category {
                             id: c.id,
+                            parent_id: c.parent_id,
                             name: c.name.clone(),
+                            is_default: c.is_default,
                             count: c.count,
+                            unread_count: c.unread_count,
                         });
                     }
                 }
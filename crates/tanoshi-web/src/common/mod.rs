@@ -44,5 +44,8 @@ pub use input_list::*;
 mod select_category;
 pub use select_category::SelectCategoryModal;
 
+mod select_reading_list;
+pub use select_reading_list::SelectReadingListModal;
+
 mod select_track_manga;
 pub use select_track_manga::{SelectTrackMangaModal, TrackerStatus};
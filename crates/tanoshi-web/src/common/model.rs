@@ -52,8 +52,33 @@ pub struct DownloadQueue {
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Category {
     pub id: Option<i64>,
+    #[serde(default)]
+    pub parent_id: Option<i64>,
     pub name: String,
+    #[serde(default)]
+    pub is_default: bool,
     pub count: i64,
+    #[serde(default)]
+    pub unread_count: i64,
+}
+
+impl Category {
+    /// How many categories this is nested in
+    pub fn depth(&self, categories: &[Category]) -> usize {
+        let mut depth = 0;
+        let mut parent_id = self.parent_id;
+        while let Some(parent) =
+            parent_id.and_then(|id| categories.iter().find(|c| c.id == Some(id)))
+        {
+            if depth >= categories.len() {
+                break;
+            }
+            depth += 1;
+            parent_id = parent.parent_id;
+        }
+
+        depth
+    }
 }
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
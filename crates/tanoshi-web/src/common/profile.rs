@@ -2,10 +2,11 @@ use std::rc::Rc;
 
 use dominator::{clone, html, routing, Dom};
 use dominator::{with_node, EventOptions};
+use futures_signals::map_ref;
 use futures_signals::signal::Mutable;
 use futures_signals::signal::SignalExt;
 use wasm_bindgen::UnwrapThrowExt;
-use web_sys::HtmlInputElement;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 
 use crate::common::{events, snackbar, Route};
 use crate::query;
@@ -16,8 +17,19 @@ pub struct Profile {
     new_password: Mutable<String>,
     confirm_password: Mutable<String>,
     telegram_chat_id: Mutable<Option<String>>,
+    telegram_pairing_code: Mutable<Option<String>>,
     pushover_user_key: Mutable<Option<String>>,
     gotify_token: Mutable<Option<String>>,
+    pushover_device: Mutable<Option<String>>,
+    pushover_sound: Mutable<Option<String>>,
+    pushover_chapter_priority: Mutable<i64>,
+    pushover_alert_priority: Mutable<i64>,
+    notification_digest: Mutable<String>,
+    notify_chapter_category_ids: Mutable<Option<Vec<i64>>>,
+    notify_download_failures: Mutable<bool>,
+    notify_announcements: Mutable<bool>,
+    data_saver: Mutable<bool>,
+    categories: Mutable<Vec<(i64, String)>>,
     myanimelist_status: Mutable<bool>,
     anilist_status: Mutable<bool>,
     pub loader: AsyncLoader,
@@ -30,8 +42,19 @@ impl Profile {
             new_password: Mutable::new("".to_string()),
             confirm_password: Mutable::new("".to_string()),
             telegram_chat_id: Mutable::new(None),
+            telegram_pairing_code: Mutable::new(None),
             pushover_user_key: Mutable::new(None),
             gotify_token: Mutable::new(None),
+            pushover_device: Mutable::new(None),
+            pushover_sound: Mutable::new(None),
+            pushover_chapter_priority: Mutable::new(0),
+            pushover_alert_priority: Mutable::new(0),
+            notification_digest: Mutable::new("OFF".to_string()),
+            notify_chapter_category_ids: Mutable::new(None),
+            notify_download_failures: Mutable::new(true),
+            notify_announcements: Mutable::new(true),
+            data_saver: Mutable::new(false),
+            categories: Mutable::new(vec![]),
             myanimelist_status: Mutable::new(false),
             anilist_status: Mutable::new(false),
             loader: AsyncLoader::new(),
@@ -45,6 +68,15 @@ impl Profile {
                     profile.telegram_chat_id.set(result.telegram_chat_id.map(|id| id.to_string()));
                     profile.pushover_user_key.set(result.pushover_user_key);
                     profile.gotify_token.set(result.gotify_token);
+                    profile.pushover_device.set(result.pushover_device);
+                    profile.pushover_sound.set(result.pushover_sound);
+                    profile.pushover_chapter_priority.set(result.pushover_chapter_priority);
+                    profile.pushover_alert_priority.set(result.pushover_alert_priority);
+                    profile.notification_digest.set(format!("{:?}", result.notification_digest));
+                    profile.notify_chapter_category_ids.set(result.notify_chapter_category_ids);
+                    profile.notify_download_failures.set(result.notify_download_failures);
+                    profile.notify_announcements.set(result.notify_announcements);
+                    profile.data_saver.set(result.data_saver);
                     profile.myanimelist_status.set(result.myanimelist_status);
                     profile.anilist_status.set(result.anilist_status);
                 },
@@ -52,6 +84,16 @@ impl Profile {
                     snackbar::show(format!("{}", err));
                 }
             }
+
+            match query::fetch_categories().await {
+                Ok(categories) => {
+                    // default category has no id, it is stored as 0 in notification preferences
+                    profile.categories.set(categories.into_iter().map(|category| (category.id.unwrap_or(0), category.name)).collect());
+                }
+                Err(err) => {
+                    snackbar::show(format!("{}", err));
+                }
+            }
         }));
     }
 
@@ -72,6 +114,17 @@ impl Profile {
         }
     }
 
+    fn pair_telegram(profile: Rc<Self>) {
+        profile.loader.load(clone!(profile => async move {
+            match query::create_telegram_pairing_code().await {
+                Ok(code) => profile.telegram_pairing_code.set(Some(code)),
+                Err(err) => {
+                    snackbar::show(format!("{}", err));
+                }
+            }
+        }));
+    }
+
     fn test_pushover(profile: Rc<Self>) {
         if let Some(user_key) = profile.pushover_user_key.get_cloned() {
             profile.loader.load(async move {
@@ -109,6 +162,165 @@ impl Profile {
         });
     }
 
+    fn update_notification_digest(profile: Rc<Self>) {
+        let digest = profile.notification_digest.get_cloned();
+        profile.loader.load(async move {
+            if let Err(err) = query::update_notification_digest(&digest).await {
+                snackbar::show(format!("{}", err));
+            }
+        });
+    }
+
+    fn update_notification_preferences(profile: Rc<Self>) {
+        let chapter_category_ids = profile.notify_chapter_category_ids.get_cloned();
+        let download_failures = profile.notify_download_failures.get();
+        let announcements = profile.notify_announcements.get();
+        profile.loader.load(async move {
+            if let Err(err) = query::update_notification_preferences(
+                chapter_category_ids,
+                download_failures,
+                announcements,
+            )
+            .await
+            {
+                snackbar::show(format!("{}", err));
+            }
+        });
+    }
+
+    fn update_data_saver(profile: Rc<Self>) {
+        let enabled = profile.data_saver.get();
+        profile.loader.load(async move {
+            if let Err(err) = query::update_data_saver(enabled).await {
+                snackbar::show(format!("{}", err));
+            }
+        });
+    }
+
+    fn render_pushover_priority(label: &str, priority: Mutable<i64>) -> Dom {
+        html!("div", {
+            .style("display", "flex")
+            .style("align-items", "center")
+            .style("justify-content", "space-between")
+            .style("margin", "0.25rem")
+            .children(&mut [
+                html!("label", {
+                    .text(label)
+                }),
+                html!("select" => HtmlSelectElement, {
+                    .children(&mut [
+                        (-2, "Lowest, no alert"),
+                        (-1, "Low, quiet"),
+                        (0, "Normal"),
+                        (1, "High, bypass quiet hours"),
+                    ].map(|(value, label)| html!("option", {
+                        .attribute("value", &value.to_string())
+                        .attribute_signal("selected", priority.signal().map(move |priority| (priority == value).then(|| "")))
+                        .text(label)
+                    })))
+                    .with_node!(select => {
+                        .event(clone!(priority => move |_: events::Change| {
+                            priority.set(select.value().parse().unwrap_or_default());
+                        }))
+                    })
+                }),
+            ])
+        })
+    }
+
+    fn render_notify_checkbox(profile: Rc<Self>, label: &str, checked: Mutable<bool>) -> Dom {
+        html!("div", {
+            .style("display", "flex")
+            .style("align-items", "center")
+            .style("margin", "0.25rem")
+            .children(&mut [
+                html!("input" => HtmlInputElement, {
+                    .attribute("type", "checkbox")
+                    .style("margin-right", "0.5rem")
+                    .attribute_signal("checked", checked.signal().map(|x| if x {Some("checked")} else {None}))
+                    .with_node!(element => {
+                        .event(clone!(profile, checked => move |_: events::Change| {
+                            checked.set_neq(element.checked());
+                            Self::update_notification_preferences(profile.clone());
+                        }))
+                    })
+                }),
+                html!("label", {
+                    .text(label)
+                }),
+            ])
+        })
+    }
+
+    fn render_notify_categories(profile: Rc<Self>) -> Dom {
+        html!("div", {
+            .style("display", "flex")
+            .style("flex-direction", "column")
+            .style("margin", "0.25rem")
+            .children(&mut [
+                html!("div", {
+                    .style("display", "flex")
+                    .style("align-items", "center")
+                    .children(&mut [
+                        html!("input" => HtmlInputElement, {
+                            .attribute("type", "checkbox")
+                            .style("margin-right", "0.5rem")
+                            .attribute_signal("checked", profile.notify_chapter_category_ids.signal_ref(|ids| ids.is_none().then(|| "checked")))
+                            .with_node!(element => {
+                                .event(clone!(profile => move |_: events::Change| {
+                                    if element.checked() {
+                                        profile.notify_chapter_category_ids.set(None);
+                                    } else {
+                                        let all = profile.categories.lock_ref().iter().map(|(id, _)| *id).collect();
+                                        profile.notify_chapter_category_ids.set(Some(all));
+                                    }
+                                    Self::update_notification_preferences(profile.clone());
+                                }))
+                            })
+                        }),
+                        html!("label", {
+                            .text("New chapters in all categories")
+                        }),
+                    ])
+                }),
+            ])
+            .children_signal_vec(map_ref! {
+                let filtered = profile.notify_chapter_category_ids.signal_ref(|ids| ids.is_some()).dedupe(),
+                let categories = profile.categories.signal_cloned() =>
+                (*filtered, categories.clone())
+            }.map(clone!(profile => move |(filtered, categories)| if filtered {
+                categories.into_iter().map(|(id, name)| html!("div", {
+                    .style("display", "flex")
+                    .style("align-items", "center")
+                    .style("margin-left", "1.5rem")
+                    .children(&mut [
+                        html!("input" => HtmlInputElement, {
+                            .attribute("type", "checkbox")
+                            .style("margin-right", "0.5rem")
+                            .attribute_signal("checked", profile.notify_chapter_category_ids.signal_ref(move |ids| ids.as_ref().map(|ids| ids.contains(&id)).unwrap_or(true).then(|| "checked")))
+                            .with_node!(element => {
+                                .event(clone!(profile => move |_: events::Change| {
+                                    let mut ids = profile.notify_chapter_category_ids.get_cloned().unwrap_or_default();
+                                    ids.retain(|category_id| *category_id != id);
+                                    if element.checked() {
+                                        ids.push(id);
+                                    }
+                                    profile.notify_chapter_category_ids.set(Some(ids));
+                                    Self::update_notification_preferences(profile.clone());
+                                }))
+                            })
+                        }),
+                        html!("label", {
+                            .text(&name)
+                        }),
+                    ])
+                })).collect()
+            } else {
+                vec![]
+            })).to_signal_vec())
+        })
+    }
+
     fn tracker_logout(profile: Rc<Self>, tracker: String) {
         profile.loader.load(clone!(profile => async move {
             match query::tracker_logout(tracker).await {
@@ -140,7 +352,19 @@ impl Profile {
             let telegram_chat_id = profile.telegram_chat_id.get_cloned().and_then(|telegram_chat_id| telegram_chat_id.parse().ok());
             let pushover_user_key = profile.pushover_user_key.get_cloned();
             let gotify_token = profile.gotify_token.get_cloned();
-            match query::update_profile(telegram_chat_id, pushover_user_key, gotify_token).await {
+            let pushover_device = profile.pushover_device.get_cloned();
+            let pushover_sound = profile.pushover_sound.get_cloned();
+            let pushover_chapter_priority = profile.pushover_chapter_priority.get();
+            let pushover_alert_priority = profile.pushover_alert_priority.get();
+            match query::update_profile(
+                telegram_chat_id,
+                pushover_user_key,
+                gotify_token,
+                pushover_device,
+                pushover_sound,
+                pushover_chapter_priority,
+                pushover_alert_priority,
+            ).await {
                 Ok(_) => {
                     // routing::go_to_url(Route::Settings(SettingCategory::None).url().as_str());
                 },
@@ -293,6 +517,15 @@ impl Profile {
                                 }))
                             })
                         }),
+                        html!("input", {
+                            .attribute("type", "button")
+                            .attribute("value", "Pair")
+                            .text("Pair Telegram")
+                            .event_with_options(&EventOptions::preventable(), clone!(profile => move |e: events::Click| {
+                                e.prevent_default();
+                                Self::pair_telegram(profile.clone());
+                            }))
+                        }),
                         html!("input", {
                             .attribute("type", "button")
                             .attribute("value", "Test")
@@ -304,6 +537,11 @@ impl Profile {
                         }),
                     ])
                 }),
+                html!("span", {
+                    .style("margin", "0.25rem")
+                    .visible_signal(profile.telegram_pairing_code.signal_ref(|code| code.is_some()))
+                    .text_signal(profile.telegram_pairing_code.signal_cloned().map(|code| format!("Send /start {} to the telegram bot within 10 minutes, then reload this page", code.unwrap_or_default())))
+                }),
                 // Pushover
                 html!("div", {
                     .style("display", "flex")
@@ -330,6 +568,35 @@ impl Profile {
                         }),
                     ])
                 }),
+                html!("div", {
+                    .style("display", "flex")
+                    .children(&mut [
+                        html!("input" => HtmlInputElement, {
+                            .style("width", "100%")
+                            .attribute("type", "text")
+                            .attribute("placeholder", "Pushover device, empty for all devices")
+                            .property_signal("value", profile.pushover_device.signal_cloned().map(|device| device.unwrap_or_else(|| "".to_string())))
+                            .with_node!(input => {
+                                .event(clone!(profile => move |_: events::Input| {
+                                    profile.pushover_device.set(Some(input.value()));
+                                }))
+                            })
+                        }),
+                        html!("input" => HtmlInputElement, {
+                            .style("width", "100%")
+                            .attribute("type", "text")
+                            .attribute("placeholder", "Pushover sound, empty for default")
+                            .property_signal("value", profile.pushover_sound.signal_cloned().map(|sound| sound.unwrap_or_else(|| "".to_string())))
+                            .with_node!(input => {
+                                .event(clone!(profile => move |_: events::Input| {
+                                    profile.pushover_sound.set(Some(input.value()));
+                                }))
+                            })
+                        }),
+                    ])
+                }),
+                Self::render_pushover_priority("Pushover new chapter priority", profile.pushover_chapter_priority.clone()),
+                Self::render_pushover_priority("Pushover alert priority", profile.pushover_alert_priority.clone()),
                 // Gotify
                 html!("div", {
                     .style("display", "flex")
@@ -356,6 +623,39 @@ impl Profile {
                         }),
                     ])
                 }),
+                // Digest
+                html!("div", {
+                    .style("display", "flex")
+                    .style("align-items", "center")
+                    .style("justify-content", "space-between")
+                    .style("margin", "0.25rem")
+                    .children(&mut [
+                        html!("label", {
+                            .text("Chapter notification")
+                        }),
+                        html!("select" => HtmlSelectElement, {
+                            .children(&mut [
+                                ("OFF", "Every chapter"),
+                                ("HOURLY", "Hourly digest"),
+                                ("DAILY", "Daily digest"),
+                            ].map(|(value, label)| html!("option", {
+                                .attribute("value", value)
+                                .attribute_signal("selected", profile.notification_digest.signal_cloned().map(move |digest| (digest == value).then(|| "")))
+                                .text(label)
+                            })))
+                            .with_node!(select => {
+                                .event(clone!(profile => move |_: events::Change| {
+                                    profile.notification_digest.set(select.value());
+                                    Self::update_notification_digest(profile.clone());
+                                }))
+                            })
+                        }),
+                    ])
+                }),
+                // Events
+                Self::render_notify_categories(profile.clone()),
+                Self::render_notify_checkbox(profile.clone(), "Download failures", profile.notify_download_failures.clone()),
+                Self::render_notify_checkbox(profile.clone(), "Server announcements", profile.notify_announcements.clone()),
                 html!("div", {
                     .style("display", "flex")
                     .style("justify-content", "flex-end")
@@ -375,6 +675,49 @@ impl Profile {
         })
     }
 
+    fn render_reading_setting(profile: Rc<Self>) -> Dom {
+        html!("form", {
+            .class("content")
+            .style("display", "flex")
+            .style("flex-direction", "column")
+            .style("max-width", "1024px")
+            .style("margin-left", "auto")
+            .style("margin-right", "auto")
+            .style("margin-bottom", "0.5rem")
+            .style("padding", "0.5rem")
+            .style("border-radius", "0.5rem")
+            .style("border", "var(--list-group-border)")
+            .children(&mut [
+                html!("span", {
+                    .style("margin-left", "0.25rem")
+                    .style("margin-bottom", "0.5rem")
+                    .text("Reading")
+                }),
+                html!("div", {
+                    .style("display", "flex")
+                    .style("align-items", "center")
+                    .style("margin", "0.25rem")
+                    .children(&mut [
+                        html!("input" => HtmlInputElement, {
+                            .attribute("type", "checkbox")
+                            .style("margin-right", "0.5rem")
+                            .attribute_signal("checked", profile.data_saver.signal().map(|x| if x {Some("checked")} else {None}))
+                            .with_node!(element => {
+                                .event(clone!(profile => move |_: events::Change| {
+                                    profile.data_saver.set_neq(element.checked());
+                                    Self::update_data_saver(profile.clone());
+                                }))
+                            })
+                        }),
+                        html!("label", {
+                            .text("Data saver, pages are loaded smaller and at a lower quality")
+                        }),
+                    ])
+                }),
+            ])
+        })
+    }
+
     fn render_tracker_setting(profile: Rc<Self>) -> Dom {
         html!("form", {
             .class("content")
@@ -484,6 +827,7 @@ impl Profile {
             .children(&mut [
                 Self::render_change_password(profile.clone()),
                 Self::render_notification_setting(profile.clone()),
+                Self::render_reading_setting(profile.clone()),
                 Self::render_tracker_setting(profile),
                 html!("div", {
                     .style("max-width", "1024px")
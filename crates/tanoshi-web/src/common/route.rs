@@ -26,6 +26,7 @@ pub enum Route {
     Login,
     LibraryList,
     Library(Option<i64>),
+    ReadingList(i64),
     CatalogueList,
     Catalogue {
         id: i64,
@@ -37,6 +38,7 @@ pub enum Route {
     Chapter(i64, i64),
     Updates,
     Histories,
+    Share(Option<String>),
     Settings(SettingCategory),
     TrackerLogin(String),
     TrackerRedirect {
@@ -62,8 +64,19 @@ impl Route {
                     ["libraries"] => Route::LibraryList,
                     ["library"] => Route::Library(None),
                     ["library", category_id] => Route::Library(category_id.parse().ok()),
+                    ["reading-list", id] => {
+                        if let Ok(id) = id.parse() {
+                            Route::ReadingList(id)
+                        } else {
+                            Route::NotFound
+                        }
+                    }
                     ["updates"] => Route::Updates,
                     ["histories"] => Route::Histories,
+                    ["share"] => {
+                        let params = url.search_params();
+                        Route::Share(params.get("url").or_else(|| params.get("text")))
+                    }
                     ["catalogue"] => Route::CatalogueList,
                     ["catalogue", id] => {
                         if let Ok(id) = id.parse() {
@@ -187,6 +200,7 @@ impl Route {
                     "/library".to_string()
                 }
             }
+            Route::ReadingList(id) => format!("/reading-list/{}", id),
             Route::CatalogueList => "/catalogue".to_string(),
             Route::Catalogue { id, latest, query } => {
                 if *latest {
@@ -212,6 +226,11 @@ impl Route {
             }
             Route::Updates => "/updates".to_string(),
             Route::Histories => "/histories".to_string(),
+            Route::Share(Some(url)) => format!(
+                "/share?url={}",
+                String::from(js_sys::encode_uri_component(url))
+            ),
+            Route::Share(None) => "/share".to_string(),
             Route::Settings(SettingCategory::None) => "/settings".to_string(),
             Route::Settings(SettingCategory::Appearance) => "/settings/appearance".to_string(),
             Route::Settings(SettingCategory::Chapters) => "/settings/chapters".to_string(),
@@ -1,5 +1,5 @@
 
-use crate::{common::{Modal,snackbar}, query, utils::{AsyncLoader,}};
+use crate::{common::{self, Modal,snackbar}, query, utils::{AsyncLoader,}};
 use dominator::{Dom, clone, events, html, with_node};
 use futures_signals::{signal::{ Mutable, SignalExt}, signal_vec::{MutableVec, SignalVecExt}};
 use std::rc::Rc;
@@ -9,6 +9,7 @@ use web_sys::HtmlInputElement;
 pub struct Category {
     id: Option<i64>,
     name: String,
+    depth: usize,
     selected: Mutable<bool>
 }
 
@@ -37,11 +38,21 @@ impl SelectCategoryModal {
                         return;
                     }
                     
+                    let categories: Vec<common::Category> = res.into_iter().map(|c| common::Category{
+                        id: c.id,
+                        parent_id: c.parent_id,
+                        name: c.name,
+                        is_default: c.is_default,
+                        count: c.count,
+                        unread_count: c.unread_count,
+                    }).collect();
+
                     select.modal.show();
-                    select.categories.lock_mut().replace_cloned(res.into_iter().filter_map(|c| (c.id.is_some()).then(|| Category{
+                    select.categories.lock_mut().replace_cloned(categories.iter().filter_map(|c| (c.id.is_some()).then(|| Category{
                         id: c.id,
                         name: c.name.clone(),
-                        selected: Mutable::new(false),
+                        depth: c.depth(&categories),
+                        selected: Mutable::new(c.is_default),
                     })).collect());
                 }
                 Err(e) => {
@@ -81,6 +92,7 @@ impl SelectCategoryModal {
             .children_signal_vec(select.categories.signal_vec_cloned().map(|cat| html!{"li", {
                 .class("list-item")
                 .style("padding", "0.5rem")
+                .style("padding-left", format!("{}rem", cat.depth as f64 * 1.5 + 0.5))
                 .children(&mut [
                     html!("input" => HtmlInputElement, {
                         .attribute("type", "checkbox")
@@ -0,0 +1,200 @@
+use crate::{
+    common::{snackbar, Modal},
+    query,
+    utils::AsyncLoader,
+};
+use dominator::{clone, events, html, with_node, Dom, EventOptions};
+use futures_signals::{
+    signal::{Mutable, SignalExt},
+    signal_vec::{MutableVec, SignalVecExt},
+};
+use std::rc::Rc;
+use web_sys::HtmlInputElement;
+
+#[derive(Clone)]
+pub struct ReadingList {
+    id: i64,
+    name: String,
+    in_list: bool,
+    selected: Mutable<bool>,
+}
+
+pub struct SelectReadingListModal {
+    manga_id: i64,
+    reading_lists: MutableVec<ReadingList>,
+    modal: Rc<Modal>,
+    loader: AsyncLoader,
+}
+
+impl SelectReadingListModal {
+    pub fn new(manga_id: i64) -> Rc<Self> {
+        Rc::new(Self {
+            manga_id,
+            reading_lists: MutableVec::new(),
+            modal: Modal::new(),
+            loader: AsyncLoader::new(),
+        })
+    }
+
+    pub fn fetch_reading_lists(self: &Rc<Self>) {
+        let select = self.clone();
+        self.loader.load(clone!(select => async move {
+            match query::fetch_manga_reading_lists(select.manga_id).await {
+                Ok(res) => {
+                    let in_lists: Vec<i64> = res.manga.reading_lists.iter().map(|r| r.id).collect();
+                    select.reading_lists.lock_mut().replace_cloned(res.reading_lists.into_iter().map(|r| ReadingList{
+                        id: r.id,
+                        name: r.name,
+                        in_list: in_lists.contains(&r.id),
+                        selected: Mutable::new(in_lists.contains(&r.id)),
+                    }).collect());
+                }
+                Err(e) => {
+                    snackbar::show(format!("failed to fetch reading lists {}", e));
+                }
+            }
+        }));
+    }
+
+    fn create_reading_list(self: &Rc<Self>, name: String) {
+        if name.trim().is_empty() {
+            return;
+        }
+
+        let select = self.clone();
+        self.loader.load(clone!(select => async move {
+            match query::create_reading_list(&name).await {
+                Ok(id) => {
+                    select.reading_lists.lock_mut().push_cloned(ReadingList{
+                        id,
+                        name,
+                        in_list: false,
+                        selected: Mutable::new(true),
+                    });
+                }
+                Err(e) => {
+                    snackbar::show(format!("failed to create reading list {}", e));
+                }
+            }
+        }));
+    }
+
+    fn save<F>(self: &Rc<Self>, f: F)
+    where
+        F: Fn() + Clone + 'static,
+    {
+        let select = self.clone();
+        let reading_lists = self.reading_lists.lock_ref().to_vec();
+        self.loader.load(clone!(select => async move {
+            for reading_list in reading_lists.iter().filter(|r| r.in_list != r.selected.get()) {
+                let res = if reading_list.selected.get() {
+                    query::add_to_reading_list(reading_list.id, &[select.manga_id]).await
+                } else {
+                    query::remove_from_reading_list(reading_list.id, &[select.manga_id]).await
+                };
+
+                if let Err(e) = res {
+                    snackbar::show(format!("failed to update reading list {}", e));
+                }
+            }
+            f();
+        }));
+    }
+
+    pub fn render_header<F>(self: &Rc<Self>, f: F) -> Dom
+    where
+        F: Fn() + Clone + 'static,
+    {
+        let select = self.clone();
+        html!("div", {
+            .style("display", "flex")
+            .style("justify-content", "space-between")
+            .style("margin-bottom", "0.5rem")
+            .children(&mut [
+                html!("span", {
+                    .style("font-size", "large")
+                    .text("Select Reading Lists")
+                }),
+                html!("button", {
+                    .text("OK")
+                    .event(clone!(select, f => move |_: events::Click| {
+                        select.save(f.clone());
+                        select.modal.hide();
+                    }))
+                })
+            ])
+        })
+    }
+
+    pub fn render_main(self: &Rc<Self>) -> Dom {
+        let select = self.clone();
+        html!("ul", {
+            .class("list")
+            .children_signal_vec(select.reading_lists.signal_vec_cloned().map(|reading_list| html!{"li", {
+                .class("list-item")
+                .style("padding", "0.5rem")
+                .children(&mut [
+                    html!("input" => HtmlInputElement, {
+                        .attribute("type", "checkbox")
+                        .style("height", "0.75rem")
+                        .style("width", "0.75rem")
+                        .style("margin-left", "0.5rem")
+                        .style("margin-right", "0.5rem")
+                        .style("margin-top", "auto")
+                        .style("margin-bottom", "auto")
+                        .with_node!(input => {
+                            .future(reading_list.selected.signal().for_each(clone!(input => move |selected| {
+                                input.set_checked(selected);
+
+                                async{}
+                            })))
+                            .event(clone!(reading_list => move |_: events::Change| {
+                                reading_list.selected.set_neq(input.checked());
+                            }))
+                        })
+                    }),
+                    html!("span", {
+                        .style("margin-left", "0.5rem")
+                        .text(&reading_list.name)
+                    })
+                ])
+                .event(clone!(reading_list => move |_: events::Click| {
+                    reading_list.selected.set_neq(!reading_list.selected.get());
+                }))
+            }}))
+            .children(&mut [
+                html!("li", {
+                    .class("list-item")
+                    .style("padding", "0.5rem")
+                    .children(&mut [
+                        html!("input" => HtmlInputElement, {
+                            .style("width", "100%")
+                            .attribute("type", "text")
+                            .attribute("placeholder", "New Reading List")
+                            .with_node!(input => {
+                                .event_with_options(&EventOptions::preventable(), clone!(input, select => move |event: events::KeyDown| {
+                                    if event.key() == "Enter" {
+                                        event.prevent_default();
+                                        select.create_reading_list(input.value());
+                                        input.set_value("");
+                                    }
+                                }))
+                            })
+                        })
+                    ])
+                })
+            ])
+        })
+    }
+
+    pub fn render<F>(self: &Rc<Self>, f: F) -> Dom
+    where
+        F: Fn() + Clone + 'static,
+    {
+        self.fetch_reading_lists();
+        self.modal.show();
+        let select = self.clone();
+        self.modal
+            .render(&mut [select.render_header(f), select.render_main()])
+    }
+}
@@ -13,11 +13,13 @@ mod manga;
 #[allow(dead_code)]
 mod query;
 mod reader;
+mod reading_list;
 mod settings;
 mod settings_categories;
 mod settings_download_queue;
 mod settings_manage_downloads;
 mod settings_source;
+mod share;
 mod tracker_login;
 mod tracker_redirect;
 mod updates;
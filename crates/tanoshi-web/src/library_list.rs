@@ -2,16 +2,25 @@ use std::rc::Rc;
 
 use crate::{common::Spinner, utils::AsyncLoader};
 use crate::{
-    common::{snackbar, Category, Route},
+    common::{events, snackbar, Category, Route},
     query,
     utils::is_tauri_signal,
 };
-use dominator::{clone, html, link, routing, svg, Dom};
+use dominator::{clone, html, link, routing, svg, with_node, Dom, EventOptions};
 use futures_signals::signal::{self, SignalExt};
 use futures_signals::signal_vec::{MutableVec, SignalVecExt};
+use web_sys::HtmlInputElement;
+
+#[derive(Debug, Clone)]
+struct ReadingList {
+    id: i64,
+    name: String,
+    count: i64,
+}
 
 pub struct LibraryList {
     categories: MutableVec<Category>,
+    reading_lists: MutableVec<ReadingList>,
     loader: AsyncLoader,
     spinner: Rc<Spinner>,
 }
@@ -20,6 +29,7 @@ impl LibraryList {
     pub fn new() -> Rc<Self> {
         Rc::new(Self {
             categories: MutableVec::new(),
+            reading_lists: MutableVec::new(),
             loader: AsyncLoader::new(),
             spinner: Spinner::new(),
         })
@@ -28,17 +38,33 @@ impl LibraryList {
     pub fn fetch_categories(library: Rc<Self>) {
         library.spinner.set_active(true);
         library.loader.load(clone!(library => async move {
+            let reading_lists = match query::fetch_reading_lists().await {
+                Ok(res) => res,
+                Err(e) => {
+                    snackbar::show(format!("failed to fetch reading lists {}", e));
+                    vec![]
+                }
+            };
+
             match query::fetch_categories().await {
                 Ok(res) => {
-                    if res.len() == 1 {
+                    if res.len() == 1 && reading_lists.is_empty() {
                         routing::go_to_url(&Route::Library(None).url());
                         return
                     }
 
                     library.categories.lock_mut().replace_cloned(res.into_iter().map(|c| Category{
                         id: c.id,
+                        parent_id: c.parent_id,
                         name: c.name.clone(),
+                        is_default: c.is_default,
                         count: c.count,
+                        unread_count: c.unread_count,
+                    }).collect());
+                    library.reading_lists.lock_mut().replace_cloned(reading_lists.into_iter().map(|r| ReadingList{
+                        id: r.id,
+                        name: r.name,
+                        count: r.count,
                     }).collect());
                 }
                 Err(e) => {
@@ -49,6 +75,23 @@ impl LibraryList {
         }));
     }
 
+    fn create_reading_list(library: Rc<Self>, name: String) {
+        if name.trim().is_empty() {
+            return;
+        }
+
+        library.loader.load(clone!(library => async move {
+            match query::create_reading_list(&name).await {
+                Ok(id) => {
+                    routing::go_to_url(&Route::ReadingList(id).url());
+                }
+                Err(e) => {
+                    snackbar::show(format!("failed to create reading list {}", e));
+                }
+            }
+        }));
+    }
+
     pub fn render_topbar(_library: Rc<Self>) -> Dom {
         html!("div", {
             .class("topbar")
@@ -66,11 +109,12 @@ impl LibraryList {
         html!("ul", {
             .class("content")
             .class("list")
-            .children_signal_vec(library.categories.signal_vec_cloned().map(|cat| html!("li", {
+            .children_signal_vec(library.categories.signal_vec_cloned().map(clone!(library => move |cat| html!("li", {
                 .class("list-item")
                 .children(&mut [
                     link!(Route::Library(cat.id).url(), {
                         .class("source-item")
+                        .style("padding-left", format!("{}rem", cat.depth(&library.categories.lock_ref()) as f64 * 1.5))
                         .children(&mut [
                             html!("div", {
                                 .style_signal("visibility", signal::always(cat.id).map(|id| if id.is_some() {
@@ -99,10 +143,87 @@ impl LibraryList {
                             html!("span", {
                                 .text(format!("{} ({})", cat.name, cat.count).as_str())
                             }),
+                            html!("span", {
+                                .style("margin-left", "auto")
+                                .style("margin-right", "0.5rem")
+                                .style("font-size", "smaller")
+                                .visible(cat.unread_count > 0)
+                                .text(format!("{} unread", cat.unread_count).as_str())
+                            }),
+                        ])
+                    }),
+                ])
+            }))))
+        })
+    }
+
+    pub fn render_reading_lists(library: Rc<Self>) -> Dom {
+        html!("ul", {
+            .class("content")
+            .class("list")
+            .children(&mut [
+                html!("li", {
+                    .class("list-item")
+                    .style("padding", "0.5rem")
+                    .children(&mut [
+                        html!("span", {
+                            .class("header")
+                            .text("Reading Lists")
+                        })
+                    ])
+                })
+            ])
+            .children_signal_vec(library.reading_lists.signal_vec_cloned().map(|reading_list| html!("li", {
+                .class("list-item")
+                .children(&mut [
+                    link!(Route::ReadingList(reading_list.id).url(), {
+                        .class("source-item")
+                        .children(&mut [
+                            svg!("svg", {
+                                .attribute("xmlns", "http://www.w3.org/2000/svg")
+                                .attribute("fill", "none")
+                                .attribute("viewBox", "0 0 24 24")
+                                .attribute("stroke", "currentColor")
+                                .class("icon")
+                                .children(&mut [
+                                    svg!("path", {
+                                        .attribute("stroke-linecap", "round")
+                                        .attribute("stroke-linejoin", "round")
+                                        .attribute("stroke-width", "2")
+                                        .attribute("d", "M4 6h16M4 10h16M4 14h16M4 18h16")
+                                    })
+                                ])
+                            }),
+                            html!("span", {
+                                .text(format!("{} ({})", reading_list.name, reading_list.count).as_str())
+                            }),
                         ])
                     }),
                 ])
             })))
+            .children(&mut [
+                html!("li", {
+                    .class("list-item")
+                    .style("display", "flex")
+                    .style("align-items", "center")
+                    .children(&mut [
+                        html!("input" => HtmlInputElement, {
+                            .style("width", "100%")
+                            .style_important("background-color", "initial")
+                            .attribute("type", "text")
+                            .attribute("placeholder", "New Reading List")
+                            .with_node!(input => {
+                                .event_with_options(&EventOptions::preventable(), clone!(input, library => move |event: events::KeyDown| {
+                                    if event.key() == "Enter" {
+                                        event.prevent_default();
+                                        Self::create_reading_list(library.clone(), input.value());
+                                    }
+                                }))
+                            })
+                        }),
+                    ])
+                })
+            ])
         })
     }
 
@@ -117,7 +238,8 @@ impl LibraryList {
                 })
             ])
             .children(&mut [
-                Self::render_main(self),
+                Self::render_main(self.clone()),
+                Self::render_reading_lists(self),
                 html!("div", {
                     .class("bottombar-spacing")
                 })
@@ -1,6 +1,6 @@
 use crate::{
     common::{
-        ChapterSettings, ChapterSort, Filter,  Order, Route, Sort, Spinner, snackbar, SelectCategoryModal, SelectTrackMangaModal, TrackerStatus
+        ChapterSettings, ChapterSort, Filter,  Order, Route, Sort, Spinner, snackbar, SelectCategoryModal, SelectReadingListModal, SelectTrackMangaModal, TrackerStatus
     }, 
     query, 
     utils::{AsyncLoader, proxied_image_url, window}
@@ -50,7 +50,8 @@ impl Default for Chapter {
 enum SelectState {
     None,
     Category,
-    Tracker
+    Tracker,
+    ReadingList
 }
 
 pub struct Manga {
@@ -690,6 +691,37 @@ impl Manga {
                     }))
                 })
             ))))
+            .child_signal(manga.id.signal().map(clone!(manga => move |id| (id > 0).then(|| html!("button", {
+                .class("action-button")
+                .style("display", "flex")
+                .style("padding", "0.5rem")
+                .style("margin-left", "0.5rem")
+                .style("margin-top", "0.5rem")
+                .style("margin-bottom", "0.5rem")
+                .style("align-items", "center")
+                .children(&mut [
+                    svg!("svg", {
+                        .attribute("xmlns", "http://www.w3.org/2000/svg")
+                        .attribute("fill", "currentColor")
+                        .attribute("viewBox", "0 0 20 20")
+                        .class("icon-sm")
+                        .children(&mut [
+                            svg!("path", {
+                                .attribute("fill-rule", "evenodd")
+                                .attribute("d", "M3 5a1 1 0 011-1h12a1 1 0 110 2H4a1 1 0 01-1-1zM3 10a1 1 0 011-1h12a1 1 0 110 2H4a1 1 0 01-1-1zM3 15a1 1 0 011-1h12a1 1 0 110 2H4a1 1 0 01-1-1z")
+                                .attribute("clip-rule", "evenodd")
+                            })
+                        ])
+                    }),
+                    html!("span", {
+                        .style("margin-left", "0.5rem")
+                        .text("Reading Lists")
+                    })
+                ])
+                .event(clone!(manga => move |_: events::Click| {
+                    manga.select_state.set(SelectState::ReadingList);
+                }))
+            })))))
         })
     }
 
@@ -979,6 +1011,11 @@ impl Manga {
                             manga_page.select_state.set(SelectState::None);                          
                         })))
                     }
+                    SelectState::ReadingList => {
+                        Some(SelectReadingListModal::new(manga_page.id.get()).render(clone!(manga_page => move || {
+                            manga_page.select_state.set(SelectState::None);
+                        })))
+                    }
                     SelectState::Category => {
                         Some(SelectCategoryModal::new().render(clone!(manga_page => move |category_ids: Vec<i64>| {
                             Self::add_to_library(manga_page.clone(), category_ids);
@@ -69,6 +69,20 @@ pub async fn fetch_manga_from_source(
     Ok(data)
 }
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/resolve_url.graphql",
+    response_derives = "Debug, Clone"
+)]
+pub struct ResolveUrl;
+
+pub async fn resolve_url(url: String) -> Result<resolve_url::ResolveUrlResolveUrl, Box<dyn Error>> {
+    let var = resolve_url::Variables { url };
+    let data: resolve_url::ResponseData = post_graphql::<ResolveUrl>(var).await?;
+    Ok(data.resolve_url)
+}
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/schema.graphql",
@@ -90,17 +104,17 @@ pub async fn get_latest_manga(
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/schema.graphql",
-    query_path = "graphql/get_popular_manga.graphql",
+    query_path = "graphql/get_catalogue_manga.graphql",
     response_derives = "Debug, Clone"
 )]
-pub struct GetPopularManga;
+pub struct GetCatalogueManga;
 
-pub async fn get_popular_manga(
+pub async fn get_catalogue_manga(
     source_id: i64,
     page: i64,
-) -> Result<get_popular_manga::ResponseData, Box<dyn Error>> {
-    let var = get_popular_manga::Variables { source_id, page };
-    let data: get_popular_manga::ResponseData = post_graphql::<GetPopularManga>(var).await?;
+) -> Result<get_catalogue_manga::ResponseData, Box<dyn Error>> {
+    let var = get_catalogue_manga::Variables { source_id, page };
+    let data: get_catalogue_manga::ResponseData = post_graphql::<GetCatalogueManga>(var).await?;
     Ok(data)
 }
 
@@ -295,9 +309,10 @@ pub async fn fetch_categories(
 )]
 pub struct CreateCategory;
 
-pub async fn create_category(name: &str) -> Result<(), Box<dyn Error>> {
+pub async fn create_category(name: &str, parent_id: Option<i64>) -> Result<(), Box<dyn Error>> {
     let var = create_category::Variables {
         name: Some(name.to_string()),
+        parent_id,
     };
     let _ = post_graphql::<CreateCategory>(var).await?;
 
@@ -322,6 +337,51 @@ pub async fn update_category(id: i64, name: &str) -> Result<(), Box<dyn Error>>
     Ok(())
 }
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/set_category_parent.graphql",
+    response_derives = "Debug"
+)]
+pub struct SetCategoryParent;
+
+pub async fn set_category_parent(id: i64, parent_id: Option<i64>) -> Result<(), Box<dyn Error>> {
+    let var = set_category_parent::Variables { id, parent_id };
+    let _ = post_graphql::<SetCategoryParent>(var).await?;
+
+    Ok(())
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/reorder_categories.graphql",
+    response_derives = "Debug"
+)]
+pub struct ReorderCategories;
+
+pub async fn reorder_categories(ids: &[i64]) -> Result<(), Box<dyn Error>> {
+    let var = reorder_categories::Variables { ids: ids.to_vec() };
+    let _ = post_graphql::<ReorderCategories>(var).await?;
+
+    Ok(())
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/set_default_category.graphql",
+    response_derives = "Debug"
+)]
+pub struct SetDefaultCategory;
+
+pub async fn set_default_category(id: Option<i64>) -> Result<(), Box<dyn Error>> {
+    let var = set_default_category::Variables { id };
+    let _ = post_graphql::<SetDefaultCategory>(var).await?;
+
+    Ok(())
+}
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/schema.graphql",
@@ -615,18 +675,97 @@ pub async fn update_profile(
     telegram_chat_id: Option<i64>,
     pushover_user_key: Option<String>,
     gotify_token: Option<String>,
+    pushover_device: Option<String>,
+    pushover_sound: Option<String>,
+    pushover_chapter_priority: i64,
+    pushover_alert_priority: i64,
 ) -> Result<(), Box<dyn Error>> {
     let var = update_profile::Variables {
         input: update_profile::ProfileInput {
             telegram_chat_id,
             pushover_user_key,
             gotify_token,
+            pushover_device,
+            pushover_sound,
+            pushover_chapter_priority: Some(pushover_chapter_priority),
+            pushover_alert_priority: Some(pushover_alert_priority),
         },
     };
     let _ = post_graphql::<UpdateProfile>(var).await?;
     Ok(())
 }
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/update_notification_digest.graphql",
+    response_derives = "Debug"
+)]
+pub struct UpdateNotificationDigest;
+
+pub async fn update_notification_digest(digest: &str) -> Result<(), Box<dyn Error>> {
+    let digest = match digest {
+        "HOURLY" => update_notification_digest::NotificationDigest::HOURLY,
+        "DAILY" => update_notification_digest::NotificationDigest::DAILY,
+        _ => update_notification_digest::NotificationDigest::OFF,
+    };
+    let var = update_notification_digest::Variables { digest };
+    let _ = post_graphql::<UpdateNotificationDigest>(var).await?;
+    Ok(())
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/update_data_saver.graphql",
+    response_derives = "Debug"
+)]
+pub struct UpdateDataSaver;
+
+pub async fn update_data_saver(enabled: bool) -> Result<(), Box<dyn Error>> {
+    let var = update_data_saver::Variables { enabled };
+    let _ = post_graphql::<UpdateDataSaver>(var).await?;
+    Ok(())
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/update_notification_preferences.graphql",
+    response_derives = "Debug"
+)]
+pub struct UpdateNotificationPreferences;
+
+pub async fn update_notification_preferences(
+    chapter_category_ids: Option<Vec<i64>>,
+    download_failures: bool,
+    announcements: bool,
+) -> Result<(), Box<dyn Error>> {
+    let var = update_notification_preferences::Variables {
+        input: update_notification_preferences::NotificationPreferencesInput {
+            chapter_category_ids,
+            download_failures,
+            announcements,
+        },
+    };
+    let _ = post_graphql::<UpdateNotificationPreferences>(var).await?;
+    Ok(())
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/create_telegram_pairing_code.graphql",
+    response_derives = "Debug"
+)]
+pub struct CreateTelegramPairingCode;
+
+pub async fn create_telegram_pairing_code() -> Result<String, Box<dyn Error>> {
+    let var = create_telegram_pairing_code::Variables {};
+    let data = post_graphql::<CreateTelegramPairingCode>(var).await?;
+    Ok(data.create_telegram_pairing_code)
+}
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/schema.graphql",
@@ -1074,3 +1213,167 @@ pub async fn update_tracker_status(
 
     Ok(())
 }
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/fetch_reading_lists.graphql",
+    response_derives = "Debug"
+)]
+pub struct FetchReadingLists;
+
+pub async fn fetch_reading_lists(
+) -> Result<Vec<fetch_reading_lists::FetchReadingListsReadingLists>, Box<dyn Error>> {
+    let var = fetch_reading_lists::Variables {};
+    let data = post_graphql::<FetchReadingLists>(var).await?;
+
+    Ok(data.reading_lists)
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/fetch_reading_list.graphql",
+    response_derives = "Debug"
+)]
+pub struct FetchReadingList;
+
+pub async fn fetch_reading_list(
+    id: i64,
+) -> Result<fetch_reading_list::FetchReadingListReadingList, Box<dyn Error>> {
+    let var = fetch_reading_list::Variables { id };
+    let data = post_graphql::<FetchReadingList>(var).await?;
+
+    Ok(data.reading_list)
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/fetch_manga_reading_lists.graphql",
+    response_derives = "Debug"
+)]
+pub struct FetchMangaReadingLists;
+
+pub async fn fetch_manga_reading_lists(
+    manga_id: i64,
+) -> Result<fetch_manga_reading_lists::ResponseData, Box<dyn Error>> {
+    let var = fetch_manga_reading_lists::Variables { manga_id };
+    let data = post_graphql::<FetchMangaReadingLists>(var).await?;
+
+    Ok(data)
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/create_reading_list.graphql",
+    response_derives = "Debug"
+)]
+pub struct CreateReadingList;
+
+pub async fn create_reading_list(name: &str) -> Result<i64, Box<dyn Error>> {
+    let var = create_reading_list::Variables {
+        name: name.to_string(),
+    };
+    let data = post_graphql::<CreateReadingList>(var).await?;
+
+    Ok(data.create_reading_list.id)
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/update_reading_list.graphql",
+    response_derives = "Debug"
+)]
+pub struct UpdateReadingList;
+
+pub async fn update_reading_list(
+    id: i64,
+    name: &str,
+    description: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    let var = update_reading_list::Variables {
+        id,
+        name: name.to_string(),
+        description,
+    };
+    let _ = post_graphql::<UpdateReadingList>(var).await?;
+
+    Ok(())
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/delete_reading_list.graphql",
+    response_derives = "Debug"
+)]
+pub struct DeleteReadingList;
+
+pub async fn delete_reading_list(id: i64) -> Result<(), Box<dyn Error>> {
+    let var = delete_reading_list::Variables { id };
+    let _ = post_graphql::<DeleteReadingList>(var).await?;
+
+    Ok(())
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/add_to_reading_list.graphql",
+    response_derives = "Debug"
+)]
+pub struct AddToReadingList;
+
+pub async fn add_to_reading_list(id: i64, manga_ids: &[i64]) -> Result<(), Box<dyn Error>> {
+    let var = add_to_reading_list::Variables {
+        id,
+        manga_ids: manga_ids.to_vec(),
+    };
+    let _ = post_graphql::<AddToReadingList>(var).await?;
+
+    Ok(())
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/remove_from_reading_list.graphql",
+    response_derives = "Debug"
+)]
+pub struct RemoveFromReadingList;
+
+pub async fn remove_from_reading_list(id: i64, manga_ids: &[i64]) -> Result<(), Box<dyn Error>> {
+    let var = remove_from_reading_list::Variables {
+        id,
+        manga_ids: manga_ids.to_vec(),
+    };
+    let _ = post_graphql::<RemoveFromReadingList>(var).await?;
+
+    Ok(())
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/move_in_reading_list.graphql",
+    response_derives = "Debug"
+)]
+pub struct MoveInReadingList;
+
+pub async fn move_in_reading_list(
+    id: i64,
+    manga_id: i64,
+    position: i64,
+) -> Result<(), Box<dyn Error>> {
+    let var = move_in_reading_list::Variables {
+        id,
+        manga_id,
+        position,
+    };
+    let _ = post_graphql::<MoveInReadingList>(var).await?;
+
+    Ok(())
+}
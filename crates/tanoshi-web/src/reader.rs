@@ -153,8 +153,7 @@ impl Reader {
 
                     this.pages_loaded.set(ContinousLoaded::Initial);
 
-                    let source_url = result.source.url;
-                    let pages = result.pages.iter().map(|page| (format!("{}?referer={}", page, source_url), PageStatus::Initial)).collect();
+                    let pages = result.pages.iter().map(|page| (page.clone(), PageStatus::Initial)).collect();
                     this.pages.lock_mut().replace_cloned(pages);
                     
                     Self::replace_state_with_url(chapter_id, page + 1);
@@ -0,0 +1,346 @@
+use std::rc::Rc;
+
+use dominator::{clone, html, link, routing, svg, with_node, Dom, EventOptions};
+use futures_signals::{
+    signal::{Mutable, SignalExt},
+    signal_vec::{MutableVec, SignalVecExt},
+};
+use web_sys::HtmlInputElement;
+
+use crate::{
+    common::{events, snackbar, Cover, Route, Spinner},
+    query,
+    utils::{is_tauri_signal, AsyncLoader},
+};
+
+pub struct ReadingList {
+    id: i64,
+    name: Mutable<String>,
+    description: Mutable<Option<String>>,
+    is_edit: Mutable<bool>,
+    cover_list: MutableVec<Cover>,
+    loader: AsyncLoader,
+    spinner: Rc<Spinner>,
+}
+
+impl ReadingList {
+    pub fn new(id: i64) -> Rc<Self> {
+        Rc::new(Self {
+            id,
+            name: Mutable::new("".to_string()),
+            description: Mutable::new(None),
+            is_edit: Mutable::new(false),
+            cover_list: MutableVec::new(),
+            loader: AsyncLoader::new(),
+            spinner: Spinner::new_with_fullscreen(true),
+        })
+    }
+
+    fn fetch_reading_list(reading_list: Rc<Self>) {
+        reading_list.spinner.set_active(true);
+        reading_list.loader.load(clone!(reading_list => async move {
+            match query::fetch_reading_list(reading_list.id).await {
+                Ok(res) => {
+                    reading_list.name.set(res.name);
+                    reading_list.description.set(res.description);
+                    reading_list.cover_list.lock_mut().replace_cloned(res.manga.into_iter().map(|item| Cover::new(
+                        item.id,
+                        0,
+                        item.path,
+                        item.title,
+                        item.cover_url,
+                        false,
+                        item.last_read_at.as_ref().and_then(|read_at| {
+                            chrono::NaiveDateTime::parse_from_str(read_at, "%Y-%m-%dT%H:%M:%S%.f").ok()
+                        }),
+                        item.unread_chapter_count,
+                    )).collect());
+                }
+                Err(e) => {
+                    snackbar::show(format!("failed to fetch reading list {}", e));
+                }
+            }
+            reading_list.spinner.set_active(false);
+        }));
+    }
+
+    fn update_reading_list(reading_list: Rc<Self>) {
+        reading_list.loader.load(clone!(reading_list => async move {
+            let name = reading_list.name.get_cloned();
+            let description = reading_list.description.get_cloned();
+            if let Err(e) = query::update_reading_list(reading_list.id, &name, description).await {
+                snackbar::show(format!("failed to update reading list {}", e));
+            }
+            Self::fetch_reading_list(reading_list);
+        }));
+    }
+
+    fn delete_reading_list(reading_list: Rc<Self>) {
+        reading_list.loader.load(clone!(reading_list => async move {
+            match query::delete_reading_list(reading_list.id).await {
+                Ok(_) => {
+                    routing::go_to_url(&Route::LibraryList.url());
+                }
+                Err(e) => {
+                    snackbar::show(format!("failed to delete reading list {}", e));
+                }
+            }
+        }));
+    }
+
+    fn move_manga(reading_list: Rc<Self>, manga_id: i64, position: i64) {
+        reading_list.loader.load(clone!(reading_list => async move {
+            if let Err(e) = query::move_in_reading_list(reading_list.id, manga_id, position).await {
+                snackbar::show(format!("failed to move manga {}", e));
+            }
+            Self::fetch_reading_list(reading_list);
+        }));
+    }
+
+    fn remove_manga(reading_list: Rc<Self>, manga_id: i64) {
+        reading_list.loader.load(clone!(reading_list => async move {
+            if let Err(e) = query::remove_from_reading_list(reading_list.id, &[manga_id]).await {
+                snackbar::show(format!("failed to remove manga {}", e));
+            }
+            Self::fetch_reading_list(reading_list);
+        }));
+    }
+
+    pub fn render_topbar(reading_list: Rc<Self>) -> Dom {
+        html!("div", {
+            .class("topbar")
+            .class_signal("tauri", is_tauri_signal())
+            .children(&mut [
+                link!(Route::LibraryList.url(), {
+                    .class("button")
+                    .style("display", "flex")
+                    .style("align-items", "center")
+                    .style("min-width", "5rem")
+                    .children(&mut [
+                        svg!("svg", {
+                            .attribute("xmlns", "http://www.w3.org/2000/svg")
+                            .attribute("fill", "none")
+                            .attribute("viewBox", "0 0 24 24")
+                            .attribute("stroke", "currentColor")
+                            .class("icon")
+                            .children(&mut [
+                                svg!("path", {
+                                    .attribute("stroke-linecap", "round")
+                                    .attribute("stroke-linejoin", "round")
+                                    .attribute("stroke-width", "2")
+                                    .attribute("d", "M15 19l-7-7 7-7")
+                                })
+                            ])
+                        }),
+                        html!("span", {
+                            .text("Library")
+                        })
+                    ])
+                }),
+                html!("span", {
+                    .text_signal(reading_list.name.signal_cloned())
+                }),
+                html!("button", {
+                    .style("min-width", "5rem")
+                    .text_signal(reading_list.is_edit.signal().map(|x| if x { "Done" } else { "Edit" }))
+                    .event(clone!(reading_list => move |_: events::Click| {
+                        reading_list.is_edit.set(!reading_list.is_edit.get());
+                    }))
+                }),
+            ])
+        })
+    }
+
+    pub fn render_main(reading_list: Rc<Self>) -> Dom {
+        html!("div", {
+            .class("main")
+            .style("padding", "0.5rem")
+            .child_signal(reading_list.description.signal_cloned().map(|description| description.map(|description| html!("p", {
+                .style("white-space", "pre-wrap")
+                .style("margin", "0.5rem")
+                .text(&description)
+            }))))
+            .children(&mut [
+                html!("div", {
+                    .class("manga-grid")
+                    .children_signal_vec(reading_list.cover_list.signal_vec_cloned().map(|cover| cover.render()))
+                })
+            ])
+        })
+    }
+
+    pub fn render_edit(reading_list: Rc<Self>) -> Dom {
+        html!("div", {
+            .class("content")
+            .children(&mut [
+                html!("ul", {
+                    .class("list")
+                    .children(&mut [
+                        html!("li", {
+                            .class("list-item")
+                            .style("padding", "0.5rem")
+                            .children(&mut [
+                                html!("input" => HtmlInputElement, {
+                                    .style("width", "100%")
+                                    .attribute("type", "text")
+                                    .attribute("placeholder", "Name")
+                                    .attribute("value", &reading_list.name.get_cloned())
+                                    .with_node!(input => {
+                                        .event(clone!(input, reading_list => move |_: events::Change| {
+                                            reading_list.name.set(input.value());
+                                            Self::update_reading_list(reading_list.clone());
+                                        }))
+                                        .event_with_options(&EventOptions::preventable(), |event: events::KeyDown| {
+                                            if event.key() == "Enter" {
+                                                event.prevent_default();
+                                            }
+                                        })
+                                    })
+                                }),
+                            ])
+                        }),
+                        html!("li", {
+                            .class("list-item")
+                            .style("padding", "0.5rem")
+                            .children(&mut [
+                                html!("input" => HtmlInputElement, {
+                                    .style("width", "100%")
+                                    .attribute("type", "text")
+                                    .attribute("placeholder", "Description")
+                                    .attribute("value", &reading_list.description.get_cloned().unwrap_or_default())
+                                    .with_node!(input => {
+                                        .event(clone!(input, reading_list => move |_: events::Change| {
+                                            reading_list.description.set(Some(input.value()));
+                                            Self::update_reading_list(reading_list.clone());
+                                        }))
+                                        .event_with_options(&EventOptions::preventable(), |event: events::KeyDown| {
+                                            if event.key() == "Enter" {
+                                                event.prevent_default();
+                                            }
+                                        })
+                                    })
+                                }),
+                            ])
+                        }),
+                    ])
+                }),
+                html!("ul", {
+                    .class("list")
+                    .children_signal_vec(reading_list.cover_list.signal_vec_cloned().enumerate().map(clone!(reading_list => move |(index, cover)| html!("li", {
+                        .class("list-item")
+                        .style("display", "flex")
+                        .style("align-items", "center")
+                        .style("padding", "0.5rem")
+                        .children(&mut [
+                            html!("span", {
+                                .style("flex-grow", "1")
+                                .style("overflow", "hidden")
+                                .style("text-overflow", "ellipsis")
+                                .style("white-space", "nowrap")
+                                .text(&cover.title)
+                            }),
+                            html!("button", {
+                                .style("margin-left", "0.5rem")
+                                .attribute("title", "Move up")
+                                .event(clone!(reading_list, index, cover => move |_: events::Click| {
+                                    if let Some(position) = index.get().filter(|i| *i > 0) {
+                                        Self::move_manga(reading_list.clone(), cover.id, position as i64 - 1);
+                                    }
+                                }))
+                                .children(&mut [
+                                    svg!("svg", {
+                                        .attribute("xmlns", "http://www.w3.org/2000/svg")
+                                        .attribute("fill", "currentColor")
+                                        .attribute("viewBox", "0 0 20 20")
+                                        .class("icon-sm")
+                                        .children(&mut [
+                                            svg!("path", {
+                                                .attribute("fill-rule", "evenodd")
+                                                .attribute("clip-rule", "evenodd")
+                                                .attribute("d", "M14.707 12.707a1 1 0 01-1.414 0L10 9.414l-3.293 3.293a1 1 0 01-1.414-1.414l4-4a1 1 0 011.414 0l4 4a1 1 0 010 1.414z")
+                                            })
+                                        ])
+                                    }),
+                                ])
+                            }),
+                            html!("button", {
+                                .style("margin-left", "0.5rem")
+                                .attribute("title", "Move down")
+                                .event(clone!(reading_list, index, cover => move |_: events::Click| {
+                                    if let Some(position) = index.get() {
+                                        Self::move_manga(reading_list.clone(), cover.id, position as i64 + 1);
+                                    }
+                                }))
+                                .children(&mut [
+                                    svg!("svg", {
+                                        .attribute("xmlns", "http://www.w3.org/2000/svg")
+                                        .attribute("fill", "currentColor")
+                                        .attribute("viewBox", "0 0 20 20")
+                                        .class("icon-sm")
+                                        .children(&mut [
+                                            svg!("path", {
+                                                .attribute("fill-rule", "evenodd")
+                                                .attribute("clip-rule", "evenodd")
+                                                .attribute("d", "M5.293 7.293a1 1 0 011.414 0L10 10.586l3.293-3.293a1 1 0 111.414 1.414l-4 4a1 1 0 01-1.414 0l-4-4a1 1 0 010-1.414z")
+                                            })
+                                        ])
+                                    }),
+                                ])
+                            }),
+                            html!("button", {
+                                .style("margin-left", "0.5rem")
+                                .style("color", "darkred")
+                                .attribute("title", "Remove")
+                                .event(clone!(reading_list, cover => move |_: events::Click| {
+                                    Self::remove_manga(reading_list.clone(), cover.id);
+                                }))
+                                .children(&mut [
+                                    svg!("svg", {
+                                        .attribute("xmlns", "http://www.w3.org/2000/svg")
+                                        .attribute("fill", "currentColor")
+                                        .attribute("viewBox", "0 0 20 20")
+                                        .class("icon-sm")
+                                        .children(&mut [
+                                            svg!("path", {
+                                                .attribute("fill-rule", "evenodd")
+                                                .attribute("clip-rule", "evenodd")
+                                                .attribute("d", "M9 2a1 1 0 00-.894.553L7.382 4H4a1 1 0 000 2v10a2 2 0 002 2h8a2 2 0 002-2V6a1 1 0 100-2h-3.382l-.724-1.447A1 1 0 0011 2H9zM7 8a1 1 0 012 0v6a1 1 0 11-2 0V8zm5-1a1 1 0 00-1 1v6a1 1 0 102 0V8a1 1 0 00-1-1z")
+                                            })
+                                        ])
+                                    }),
+                                ])
+                            }),
+                        ])
+                    }))))
+                }),
+                html!("button", {
+                    .style("margin", "0.5rem")
+                    .style("color", "darkred")
+                    .text("Delete Reading List")
+                    .event(clone!(reading_list => move |_: events::Click| {
+                        Self::delete_reading_list(reading_list.clone());
+                    }))
+                }),
+            ])
+        })
+    }
+
+    pub fn render(reading_list: Rc<Self>) -> Dom {
+        Self::fetch_reading_list(reading_list.clone());
+
+        html!("div", {
+            .children(&mut [
+                Self::render_topbar(reading_list.clone()),
+                html!("div", {
+                    .class("topbar-spacing")
+                }),
+                Spinner::render(reading_list.spinner.clone()),
+            ])
+            .child_signal(reading_list.is_edit.signal().map(clone!(reading_list => move |is_edit| if is_edit {
+                Some(Self::render_edit(reading_list.clone()))
+            } else {
+                Some(Self::render_main(reading_list.clone()))
+            })))
+        })
+    }
+}
@@ -12,12 +12,13 @@ use futures_signals::{
 };
 use std::rc::Rc;
 use wasm_bindgen::UnwrapThrowExt;
-use web_sys::HtmlInputElement;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 
 pub struct SettingsCategories {
     pub is_edit: Mutable<bool>,
     categories: MutableVec<Category>,
     new_category: Mutable<Option<String>>,
+    new_category_parent: Mutable<Option<i64>>,
     loader: AsyncLoader,
 }
 
@@ -26,6 +27,7 @@ impl SettingsCategories {
         Rc::new(Self {
             is_edit: Mutable::new(false),
             new_category: Mutable::new(None),
+            new_category_parent: Mutable::new(None),
             categories: MutableVec::new(),
             loader: AsyncLoader::new(),
         })
@@ -35,7 +37,7 @@ impl SettingsCategories {
         AsyncLoader::new().load({
             let settings = self.clone();
             async move {
-                match query::create_category(&name).await {
+                match query::create_category(&name, settings.new_category_parent.get()).await {
                     Ok(_) => {
                         settings.new_category.set(None);
                         settings.is_edit.set(false);
@@ -49,6 +51,83 @@ impl SettingsCategories {
         });
     }
 
+    fn set_category_parent(self: &Rc<Self>, id: i64, parent_id: Option<i64>) {
+        AsyncLoader::new().load({
+            let settings = self.clone();
+            async move {
+                match query::set_category_parent(id, parent_id).await {
+                    Ok(_) => {
+                        settings.fetch_categories();
+                    }
+                    Err(err) => {
+                        snackbar::show(format!("{}", err));
+                    }
+                }
+            }
+        });
+    }
+
+    /// Swap category `id` with the sibling before it, or after it if `down`
+    fn move_category(self: &Rc<Self>, id: i64, down: bool) {
+        let mut sibling_ids: Vec<i64> = {
+            let categories = self.categories.lock_ref();
+            let parent_id = categories
+                .iter()
+                .find(|c| c.id == Some(id))
+                .and_then(|c| c.parent_id);
+            categories
+                .iter()
+                .filter(|c| c.parent_id == parent_id)
+                .filter_map(|c| c.id)
+                .collect()
+        };
+        let index = match sibling_ids.iter().position(|sibling_id| *sibling_id == id) {
+            Some(index) => index,
+            None => return,
+        };
+        let other = if down {
+            index + 1
+        } else if index > 0 {
+            index - 1
+        } else {
+            return;
+        };
+        if other >= sibling_ids.len() {
+            return;
+        }
+        sibling_ids.swap(index, other);
+
+        AsyncLoader::new().load({
+            let settings = self.clone();
+            async move {
+                match query::reorder_categories(&sibling_ids).await {
+                    Ok(_) => {
+                        settings.fetch_categories();
+                    }
+                    Err(err) => {
+                        snackbar::show(format!("{}", err));
+                    }
+                }
+            }
+        });
+    }
+
+    fn set_default_category(self: &Rc<Self>, id: Option<i64>) {
+        AsyncLoader::new().load({
+            let settings = self.clone();
+            async move {
+                match query::set_default_category(id).await {
+                    Ok(_) => {
+                        settings.fetch_categories();
+                    }
+                    Err(err) => {
+                        snackbar::show(format!("{}", err));
+                    }
+                }
+            }
+        });
+    }
+
     fn delete_category(self: &Rc<Self>, id: i64) {
         AsyncLoader::new().load({
             let settings = self.clone();
@@ -89,8 +168,11 @@ impl SettingsCategories {
                 Ok(res) => {
                     let res: Vec<Category> = res.into_iter().filter_map(|c| (c.id.is_some()).then(|| Category{
                         id: c.id,
+                        parent_id: c.parent_id,
                         name: c.name.clone(),
+                        is_default: c.is_default,
                         count: c.count,
+                        unread_count: c.unread_count,
                     })).collect();
                     settings.categories.lock_mut().replace_cloned(res);
                 }
@@ -101,6 +183,39 @@ impl SettingsCategories {
         }));
     }
 
+    /// `None` for top level, `id` itself is left out
+    fn render_parent_select<F>(
+        self: &Rc<Self>,
+        id: Option<i64>,
+        parent_id: Option<i64>,
+        f: F,
+    ) -> Dom
+    where
+        F: Fn(Option<i64>) + 'static,
+    {
+        let settings = self.clone();
+        html!("select" => HtmlSelectElement, {
+            .style("margin-right", "0.5rem")
+            .children(&mut [
+                html!("option", {
+                    .attribute("value", "")
+                    .apply(|dom| if parent_id.is_none() { dom.attribute("selected", "") } else { dom })
+                    .text("Top level")
+                })
+            ])
+            .children(&mut settings.categories.lock_ref().iter().filter(|cat| cat.id.is_some() && cat.id != id).map(|cat| html!("option", {
+                .attribute("value", &cat.id.unwrap_or_default().to_string())
+                .apply(|dom| if cat.id == parent_id { dom.attribute("selected", "") } else { dom })
+                .text(&cat.name)
+            })).collect::<Vec<_>>())
+            .with_node!(select => {
+                .event(move |_: events::Change| {
+                    f(select.value().parse().ok());
+                })
+            })
+        })
+    }
+
     pub fn render(settings: Rc<Self>) -> Dom {
         settings.clone().fetch_categories();
         html!("div", {
@@ -119,6 +234,7 @@ impl SettingsCategories {
                             .style("display", "flex")
                             .style("align-items", "center")
                             .style("padding", "0.25rem")
+                            .style("padding-left", format!("{}rem", cat.depth(&settings.categories.lock_ref()) as f64 * 1.5 + 0.25))
                             .children(&mut [
                                 html!("div", {
                                     .style("width", "100%")
@@ -157,6 +273,15 @@ impl SettingsCategories {
                                                         .text(&cat.name)
                                                     })
                                                 ])
+                                                .apply(|dom| if cat.is_default {
+                                                    dom.child(html!("span", {
+                                                        .style("margin", "0.25rem")
+                                                        .style("font-size", "smaller")
+                                                        .text("(default)")
+                                                    }))
+                                                } else {
+                                                    dom
+                                                })
                                             })
                                         };
 
@@ -164,6 +289,76 @@ impl SettingsCategories {
                                     })))
                                 }),
                             ])
+                            .child_signal(settings.is_edit.signal().map(clone!(settings, cat => move |is_edit| {
+                                is_edit.then(|| settings.render_parent_select(cat.id, cat.parent_id, clone!(settings, cat => move |parent_id| {
+                                    if let Some(cat_id) = cat.id {
+                                        settings.set_category_parent(cat_id, parent_id);
+                                    }
+                                })))
+                            })))
+                            .child_signal(settings.is_edit.signal().map(clone!(settings, cat => move |is_edit| {
+                                is_edit.then(|| html!("button", {
+                                    .style("margin-right", "0.5rem")
+                                    .style("white-space", "nowrap")
+                                    .attribute("title", "New library entries go to the default category")
+                                    .text(if cat.is_default { "Unset default" } else { "Set default" })
+                                    .event(clone!(cat, settings => move |_: events::Click| {
+                                        settings.set_default_category(if cat.is_default { None } else { cat.id });
+                                    }))
+                                }))
+                            })))
+                            .child_signal(settings.is_edit.signal().map(clone!(settings, cat => move |is_edit| {
+                                is_edit.then(|| html!("button", {
+                                    .style("margin-right", "0.5rem")
+                                    .attribute("title", "Move up")
+                                    .event(clone!(cat, settings => move |_: events::Click| {
+                                        if let Some(cat_id) = cat.id {
+                                            settings.move_category(cat_id, false);
+                                        }
+                                    }))
+                                    .children(&mut [
+                                        svg!("svg", {
+                                            .attribute("xmlns", "http://www.w3.org/2000/svg")
+                                            .attribute("fill", "currentColor")
+                                            .attribute("viewBox", "0 0 20 20")
+                                            .class("icon-sm")
+                                            .children(&mut [
+                                                svg!("path", {
+                                                    .attribute("fill-rule", "evenodd")
+                                                    .attribute("clip-rule", "evenodd")
+                                                    .attribute("d", "M14.707 12.707a1 1 0 01-1.414 0L10 9.414l-3.293 3.293a1 1 0 01-1.414-1.414l4-4a1 1 0 011.414 0l4 4a1 1 0 010 1.414z")
+                                                })
+                                            ])
+                                        }),
+                                    ])
+                                }))
+                            })))
+                            .child_signal(settings.is_edit.signal().map(clone!(settings, cat => move |is_edit| {
+                                is_edit.then(|| html!("button", {
+                                    .style("margin-right", "0.5rem")
+                                    .attribute("title", "Move down")
+                                    .event(clone!(cat, settings => move |_: events::Click| {
+                                        if let Some(cat_id) = cat.id {
+                                            settings.move_category(cat_id, true);
+                                        }
+                                    }))
+                                    .children(&mut [
+                                        svg!("svg", {
+                                            .attribute("xmlns", "http://www.w3.org/2000/svg")
+                                            .attribute("fill", "currentColor")
+                                            .attribute("viewBox", "0 0 20 20")
+                                            .class("icon-sm")
+                                            .children(&mut [
+                                                svg!("path", {
+                                                    .attribute("fill-rule", "evenodd")
+                                                    .attribute("clip-rule", "evenodd")
+                                                    .attribute("d", "M5.293 7.293a1 1 0 011.414 0L10 10.586l3.293-3.293a1 1 0 111.414 1.414l-4 4a1 1 0 01-1.414 0l-4-4a1 1 0 010-1.414z")
+                                                })
+                                            ])
+                                        }),
+                                    ])
+                                }))
+                            })))
                             .child_signal(settings.is_edit.signal().map(clone!(settings => move |is_edit| {
                                 is_edit.then(|| html!("button", {
                                     .style("margin-right","0.5rem")
@@ -215,6 +410,9 @@ impl SettingsCategories {
                                         }))
                                     })
                                 }),
+                                settings.render_parent_select(None, settings.new_category_parent.get(), clone!(settings => move |parent_id| {
+                                    settings.new_category_parent.set(parent_id);
+                                })),
                                 html!("button", {
                                     .style("margin-right","0.5rem")
                                     .event(clone!(settings => move |_: events::Click| {
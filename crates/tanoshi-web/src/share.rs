@@ -0,0 +1,142 @@
+use std::rc::Rc;
+
+use dominator::{clone, events, html, routing, with_node, Dom, EventOptions};
+use futures_signals::signal::{Mutable, SignalExt};
+use web_sys::HtmlInputElement;
+
+use crate::{
+    common::Route,
+    query,
+    utils::{is_tauri_signal, AsyncLoader},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ResolveState {
+    Idle,
+    Resolving,
+    Failed(String),
+}
+
+pub struct Share {
+    url: Mutable<String>,
+    state: Mutable<ResolveState>,
+    loader: AsyncLoader,
+}
+
+impl Share {
+    pub fn new(shared: Option<String>) -> Rc<Self> {
+        // shared text from other apps may contain more than url, e.g. "Title https://..."
+        let url = shared
+            .and_then(|text| {
+                text.split_whitespace()
+                    .find(|word| word.starts_with("http://") || word.starts_with("https://"))
+                    .map(|word| word.to_string())
+            })
+            .unwrap_or_default();
+
+        Rc::new(Self {
+            url: Mutable::new(url),
+            state: Mutable::new(ResolveState::Idle),
+            loader: AsyncLoader::new(),
+        })
+    }
+
+    fn resolve(share: Rc<Self>) {
+        let url = share.url.get_cloned();
+        if url.is_empty() {
+            return;
+        }
+
+        share.state.set_neq(ResolveState::Resolving);
+        share.loader.load(clone!(share => async move {
+            match query::resolve_url(url).await {
+                Ok(resolved) => {
+                    let route = match resolved.chapter_id {
+                        Some(chapter_id) => Route::Chapter(chapter_id, 0),
+                        None => Route::Manga(resolved.manga.id),
+                    };
+                    routing::go_to_url(&route.url());
+                }
+                Err(e) => {
+                    share.state.set_neq(ResolveState::Failed(format!("{e}")));
+                }
+            }
+        }));
+    }
+
+    pub fn render_topbar() -> Dom {
+        html!("div", {
+            .class("topbar")
+            .class_signal("tauri", is_tauri_signal())
+            .children(&mut [
+                html!("button", {
+                    .text("Close")
+                    .event(|_: events::Click| {
+                        routing::go_to_url(&Route::CatalogueList.url());
+                    })
+                }),
+                html!("span", {
+                    .text("Open URL")
+                }),
+                html!("div", {
+                    .style("width", "24px")
+                }),
+            ])
+        })
+    }
+
+    pub fn render(share: Rc<Self>) -> Dom {
+        Self::resolve(share.clone());
+
+        html!("div", {
+            .children(&mut [
+                Self::render_topbar(),
+                html!("div", {
+                    .class("topbar-spacing")
+                }),
+                html!("div", {
+                    .class("content")
+                    .style("display", "flex")
+                    .style("flex-direction", "column")
+                    .style("max-width", "1024px")
+                    .style("margin", "auto")
+                    .style("padding", "0.5rem")
+                    .children(&mut [
+                        html!("input" => HtmlInputElement, {
+                            .attribute("type", "url")
+                            .attribute("placeholder", "Paste manga or chapter url")
+                            .property_signal("value", share.url.signal_cloned())
+                            .with_node!(input => {
+                                .event(clone!(share => move |_: events::Input| {
+                                    share.url.set_neq(input.value());
+                                }))
+                                .event_with_options(&EventOptions::preventable(), clone!(share => move |event: events::KeyDown| {
+                                    if event.key() == "Enter" {
+                                        event.prevent_default();
+                                        Self::resolve(share.clone());
+                                    }
+                                }))
+                            })
+                        }),
+                        html!("button", {
+                            .style("margin-top", "0.5rem")
+                            .text("Open")
+                            .event(clone!(share => move |_: events::Click| {
+                                Self::resolve(share.clone());
+                            }))
+                        }),
+                        html!("div", {
+                            .style("padding", "0.5rem")
+                            .style("text-align", "center")
+                            .text_signal(share.state.signal_cloned().map(|state| match state {
+                                ResolveState::Idle => "".to_string(),
+                                ResolveState::Resolving => "Opening...".to_string(),
+                                ResolveState::Failed(e) => format!("Failed to open url: {e}"),
+                            }))
+                        })
+                    ])
+                })
+            ])
+        })
+    }
+}
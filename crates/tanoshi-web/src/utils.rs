@@ -105,6 +105,11 @@ pub fn proxied_image_url(image_url: &str) -> String {
     format!("{}/{}", image_proxy_host(), image_url)
 }
 
+/// Cover resized by the server to twice the width it takes in a grid
+pub fn proxied_cover_url(image_url: &str) -> String {
+    format!("{}?width=320", proxied_image_url(image_url))
+}
+
 pub fn initialize_urls() {
     match js_sys::eval("window.__TAURI__") {
         Ok(val) if !val.is_undefined() => {
@@ -2,30 +2,41 @@
 extern crate log;
 extern crate argon2;
 
+use std::sync::Arc;
+
 use clap::Parser;
 use futures::future::OptionFuture;
 use tanoshi::{
     application::worker,
     domain::services::{
-        chapter::ChapterService, download::DownloadService, history::HistoryService,
-        image::ImageService, library::LibraryService, manga::MangaService, source::SourceService,
-        tracker::TrackerService, user::UserService,
+        automation::AutomationService, chapter::ChapterService, download::DownloadService,
+        export::ExportService, history::HistoryService, image::ImageService, inbox::InboxService,
+        library::LibraryService, manga::MangaService, setting::SettingService,
+        source::SourceService, tracker::TrackerService, user::UserService,
     },
     infrastructure::{
-        config::{self, Config},
+        config::Config,
+        covers::CoverStore,
         database,
         domain::repositories::{
-            chapter::ChapterRepositoryImpl, download::DownloadRepositoryImpl,
-            history::HistoryRepositoryImpl, image::ImageRepositoryImpl,
-            image_cache::ImageCacheRepositoryImpl, library::LibraryRepositoryImpl,
-            manga::MangaRepositoryImpl, source::SourceRepositoryImpl,
+            automation::AutomationRepositoryImpl, chapter::ChapterRepositoryImpl,
+            download::DownloadRepositoryImpl, history::HistoryRepositoryImpl,
+            image::ImageRepositoryImpl, image_cache::ImageCacheRepositoryImpl,
+            inbox::InboxRepositoryImpl, library::LibraryRepositoryImpl, manga::MangaRepositoryImpl,
+            setting::SettingRepositoryImpl, source::SourceRepositoryImpl,
             tracker::TrackerRepositoryImpl, user::UserRepositoryImpl,
         },
+        headers::HeaderTable,
         local, notification,
+        path_template::PathTemplate,
+        proxy::{ProxyRule, ProxyTable},
+        repository_index::RepositoryIndex,
+        source_cache::SourceCache,
+        storage::Storage,
     },
-    presentation::{graphql::loader::DatabaseLoader, ServerBuilder},
+    presentation::{graphql::loader::DatabaseLoader, telegram::TelegramBotHandler, ServerBuilder},
 };
-use tanoshi_notifier::{gotify::Gotify, pushover::Pushover, telegram::Telegram};
+use tanoshi_notifier::{gotify::Gotify, pushover::Pushover, smtp::Smtp, telegram::Telegram};
 use tanoshi_tracker::{AniList, MyAnimeList};
 use tanoshi_vm::{extension::ExtensionManager, prelude::Source};
 
@@ -63,16 +74,56 @@ async fn main() -> Result<(), anyhow::Error> {
 
     let extension_manager = ExtensionManager::new(&config.plugin_path);
 
-    extension_manager.load_all().await?;
+    // memory of WebAssembly extensions is bounded when they are loaded
+    extension_manager.set_limits((&config.extension_limits).into())?;
+    extension_manager
+        .load_all(config.extension_load_concurrency)
+        .await?;
+    extension_manager.set_default_rate_limit(config.source_rate_limit)?;
+    extension_manager.set_retry((&config.source_retry).into())?;
+
+    let proxies = ProxyTable::new(config.proxy.clone())
+        .with_rules(config.proxy_rules.iter().map(ProxyRule::from).collect());
+    let headers = HeaderTable::new();
+
+    let storage =
+        Storage::new(&config.storage).map_err(|e| anyhow::anyhow!("Invalid config: {e}"))?;
+
+    let source_cache = SourceCache::new(
+        std::time::Duration::from_secs(config.source_cache_ttl),
+        config
+            .source_cache_spill
+            .then(|| std::path::Path::new(&config.cache_path).join("source")),
+    );
+
+    let repository_index =
+        RepositoryIndex::new(std::time::Duration::from_secs(config.extension_index_ttl));
+
+    let source_repo = SourceRepositoryImpl::new(
+        pool.clone(),
+        extension_manager.clone(),
+        &config.secret,
+        proxies.clone(),
+        headers.clone(),
+        source_cache,
+        repository_index.clone(),
+    );
+    let source_svc = SourceService::new(source_repo.clone());
 
-    let source_repo = SourceRepositoryImpl::new(extension_manager.clone());
-    let source_svc = SourceService::new(source_repo);
+    if let Err(e) = source_svc.restore_preferences().await {
+        error!("failed to restore source preferences: {e}");
+    }
+
+    if config.extension_dev_mode {
+        worker::extension_watch::start(&config.plugin_path, source_repo.clone());
+    }
 
     let manga_repo = MangaRepositoryImpl::new(pool.clone());
     let manga_svc = MangaService::new(manga_repo.clone(), extension_manager.clone());
 
     let chapter_repo = ChapterRepositoryImpl::new(pool.clone());
-    let chapter_svc = ChapterService::new(chapter_repo.clone(), extension_manager.clone());
+    let chapter_svc = ChapterService::new(chapter_repo.clone(), extension_manager.clone())
+        .with_storage(storage.clone());
 
     let library_repo = LibraryRepositoryImpl::new(pool.clone());
     let libary_svc = LibraryService::new(library_repo.clone());
@@ -80,37 +131,42 @@ async fn main() -> Result<(), anyhow::Error> {
     let history_repo = HistoryRepositoryImpl::new(pool.clone());
     let history_svc = HistoryService::new(chapter_repo.clone(), history_repo.clone());
 
-    match &config.local_path {
-        config::LocalFolders::Single(local_path) => {
-            extension_manager
-                .insert(Source::from(Box::new(local::Local::new(
-                    10000,
-                    "Local".to_string(),
-                    local_path,
-                ))))
-                .await?;
-        }
-        config::LocalFolders::Multiple(local_paths) => {
-            for (index, local_path) in local_paths.iter().enumerate() {
-                // source id starts from 10000
-                let index = index + 10000;
-                extension_manager
-                    .insert(Source::from(Box::new(local::Local::new(
-                        index as i64,
-                        local_path.name.clone(),
-                        &local_path.path,
-                    ))))
-                    .await?;
-            }
-        }
+    let local_folders = config
+        .local_path
+        .sources()
+        .map_err(|e| anyhow::anyhow!("Invalid config: {e}"))?;
+    for (id, local_folder) in local_folders.iter() {
+        let local = local::Local::from_folder(*id, local_folder)
+            .map_err(|e| anyhow::anyhow!("Invalid config: {e}"))?;
+        extension_manager
+            .insert(Source::from(Box::new(local)))
+            .await?;
     }
 
-    let mut notifier_builder = notification::Builder::new(user_repo.clone());
+    let (automation_sender, automation_receiver) = worker::automation::channel();
+
+    let inbox_repo = InboxRepositoryImpl::new(pool.clone());
+    let inbox_svc = InboxService::new(inbox_repo.clone());
+
+    let mut notifier_builder = notification::Builder::new(user_repo.clone()).inbox(inbox_repo);
 
     let mut telegram_bot_fut: OptionFuture<_> = None.into();
     if let Some(telegram_config) = config.telegram.clone() {
         let bot = Telegram::new(telegram_config.token);
-        telegram_bot_fut = Some(tanoshi_notifier::telegram::run(bot.clone())).into();
+        let handler = TelegramBotHandler::new(
+            config.base_url.clone(),
+            user_svc.clone(),
+            source_svc.clone(),
+            MangaService::new(manga_repo.clone(), extension_manager.clone()),
+            LibraryService::new(library_repo.clone()),
+            HistoryService::new(chapter_repo.clone(), history_repo.clone()),
+            automation_sender.clone(),
+        );
+        telegram_bot_fut = Some(tanoshi_notifier::telegram::run(
+            bot.clone(),
+            Arc::new(handler),
+        ))
+        .into();
         notifier_builder = notifier_builder.telegram(bot);
     }
 
@@ -123,37 +179,105 @@ async fn main() -> Result<(), anyhow::Error> {
         notifier_builder = notifier_builder.gotify(Gotify::new(gotify_cfg.base_url.clone()));
     }
 
+    if let Some(smtp_cfg) = config.smtp.as_ref() {
+        let smtp =
+            Smtp::new(smtp_cfg.into()).map_err(|e| anyhow::anyhow!("Invalid config: smtp {e}"))?;
+        notifier_builder = notifier_builder.smtp(smtp);
+    }
+
     if let Some(base_url) = config.base_url.as_ref() {
         notifier_builder = notifier_builder.base_url(base_url.clone());
     }
 
-    let notifier = notifier_builder.finish();
+    let notifier = notifier_builder
+        .templates(config.notification_template.clone())
+        .finish();
 
     let (download_sender, download_receiver) = worker::downloads::channel();
 
     let download_repo = DownloadRepositoryImpl::new(pool.clone());
-    let download_svc = DownloadService::new(download_repo.clone(), download_sender.clone());
+    let download_svc = DownloadService::new(download_repo.clone(), download_sender.clone())
+        .with_storage(storage.clone());
 
+    let download_layout = PathTemplate::new(&config.download_layout)
+        .map_err(|e| anyhow::anyhow!("Invalid config: {e}"))?;
     let download_worker_handle = worker::downloads::start(
         &config.download_path,
         chapter_repo.clone(),
         manga_repo.clone(),
         download_repo.clone(),
+        source_repo.clone(),
         extension_manager.clone(),
         notifier.clone(),
+        download_layout,
+        config.download_format.clone(),
+        storage.clone(),
+        config.download_concurrency.clone(),
+        &config.download_bandwidth,
+        config.download_verify_interval,
+        config.download_compact_interval,
+        config.archive_optimize.clone(),
         download_sender.clone(),
         download_receiver,
     );
 
+    let automation_repo = AutomationRepositoryImpl::new(pool.clone());
+    let automation_svc = AutomationService::new(automation_repo.clone());
+
+    let automation_worker_handle = worker::automation::start(
+        automation_repo,
+        chapter_repo.clone(),
+        library_repo.clone(),
+        manga_repo.clone(),
+        download_sender.clone(),
+        notifier.clone(),
+        automation_receiver,
+    );
+
+    let (download_ahead_sender, download_ahead_receiver) = worker::download_ahead::channel();
+
+    let download_ahead_worker_handle = worker::download_ahead::start(
+        chapter_repo.clone(),
+        download_repo.clone(),
+        history_repo.clone(),
+        library_repo.clone(),
+        download_sender.clone(),
+        download_ahead_receiver,
+        storage.clone(),
+    );
+
+    let (import_sender, import_receiver) = worker::library_import::channel();
+
+    let import_worker_handle = worker::library_import::start(
+        chapter_repo.clone(),
+        history_repo.clone(),
+        library_repo.clone(),
+        manga_repo.clone(),
+        extension_manager.clone(),
+        notifier.clone(),
+        import_receiver,
+    );
+
+    let image_repo = ImageRepositoryImpl::new(proxies, headers, storage.clone());
+    let image_cache_repo = ImageCacheRepositoryImpl::new(&config.cache_path)
+        .with_storage(storage.clone())
+        .with_max_size(config.image_cache_max_size);
+    let image_svc = ImageService::new(image_repo.clone(), image_cache_repo)
+        .with_cover_store(CoverStore::new(&config.cover_path));
+
     let update_worker_handle = worker::updates::start(
         config.update_interval,
         library_repo.clone(),
         chapter_repo.clone(),
+        source_repo.clone(),
         extension_manager.clone(),
         download_sender.clone(),
+        automation_sender.clone(),
         config.auto_download_chapters,
         notifier.clone(),
+        image_svc.clone(),
         config.extension_repository.clone(),
+        repository_index,
         &config.cache_path,
     );
 
@@ -194,9 +318,41 @@ async fn main() -> Result<(), anyhow::Error> {
     let tracker_repo = TrackerRepositoryImpl::new(pool.clone(), mal_client.clone(), al_client);
     let tracker_svc = TrackerService::new(tracker_repo.clone());
 
-    let image_repo = ImageRepositoryImpl::new();
-    let image_cache_repo = ImageCacheRepositoryImpl::new(&config.cache_path);
-    let image_svc = ImageService::new(image_repo, image_cache_repo);
+    let export_svc = ExportService::new(
+        chapter_svc.clone(),
+        manga_repo.clone(),
+        image_repo.clone(),
+        library_repo.clone(),
+        extension_manager.clone(),
+    );
+
+    let (prefetch_sender, prefetch_receiver) = worker::prefetch::channel();
+
+    let prefetch_worker_handle = worker::prefetch::start(
+        config.prefetch.clone(),
+        &config.secret,
+        chapter_svc.clone(),
+        image_svc.clone(),
+        source_repo.clone(),
+        user_repo.clone(),
+        prefetch_receiver,
+    );
+
+    let setting_repo = SettingRepositoryImpl::new(pool.clone());
+    let setting_svc = SettingService::new(setting_repo);
+
+    worker::download_cleanup::start(
+        download_repo.clone(),
+        storage.clone(),
+        &config.download_retention,
+    );
+
+    worker::telemetry::start(
+        config.clone(),
+        library_repo.clone(),
+        setting_svc.clone(),
+        extension_manager.clone(),
+    );
 
     let loader = DatabaseLoader::new(history_repo, library_repo, manga_repo, tracker_repo);
 
@@ -211,8 +367,17 @@ async fn main() -> Result<(), anyhow::Error> {
         .with_library_svc(libary_svc)
         .with_history_svc(history_svc)
         .with_download_svc(download_svc)
+        .with_export_svc(export_svc)
+        .with_export_jobs(worker::export::ExportJobs::new(&config.cache_path))
+        .with_setting_svc(setting_svc)
+        .with_automation_svc(automation_svc)
+        .with_inbox_svc(inbox_svc)
         .with_ext_manager(extension_manager)
         .with_download_tx(download_sender)
+        .with_automation_tx(automation_sender)
+        .with_download_ahead_tx(download_ahead_sender)
+        .with_prefetch_tx(prefetch_sender)
+        .with_import_tx(import_sender)
         .with_notifier(notifier)
         .with_loader(loader);
 
@@ -232,6 +397,18 @@ async fn main() -> Result<(), anyhow::Error> {
         _ = download_worker_handle => {
             info!("download worker quit");
         }
+        _ = automation_worker_handle => {
+            info!("automation worker quit");
+        }
+        _ = download_ahead_worker_handle => {
+            info!("download ahead worker quit");
+        }
+        _ = prefetch_worker_handle => {
+            info!("prefetch worker quit");
+        }
+        _ = import_worker_handle => {
+            info!("import worker quit");
+        }
         Some(_) = telegram_bot_fut => {
             info!("worker shutdown");
         }
@@ -0,0 +1,8 @@
+CREATE TABLE manga_link (
+    id INTEGER PRIMARY KEY,
+    manga_id INTEGER NOT NULL,
+    linked_manga_id INTEGER NOT NULL,
+    UNIQUE(manga_id, linked_manga_id),
+    FOREIGN KEY (manga_id) REFERENCES manga(id) ON DELETE CASCADE,
+    FOREIGN KEY (linked_manga_id) REFERENCES manga(id) ON DELETE CASCADE
+);
\ No newline at end of file
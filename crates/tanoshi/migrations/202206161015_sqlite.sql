@@ -0,0 +1 @@
+ALTER TABLE "user" ADD COLUMN notification_digest_interval INTEGER DEFAULT NULL;
\ No newline at end of file
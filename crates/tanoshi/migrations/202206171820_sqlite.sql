@@ -0,0 +1,4 @@
+CREATE TABLE setting (
+    key VARCHAR(256) PRIMARY KEY,
+    value TEXT
+);
\ No newline at end of file
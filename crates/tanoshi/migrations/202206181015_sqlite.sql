@@ -0,0 +1,3 @@
+ALTER TABLE "user" ADD COLUMN notify_chapter_category_ids TEXT DEFAULT NULL;
+ALTER TABLE "user" ADD COLUMN notify_download_failures BOOLEAN NOT NULL DEFAULT true;
+ALTER TABLE "user" ADD COLUMN notify_announcements BOOLEAN NOT NULL DEFAULT true;
@@ -0,0 +1,4 @@
+ALTER TABLE "user" ADD COLUMN pushover_device TEXT DEFAULT NULL;
+ALTER TABLE "user" ADD COLUMN pushover_sound TEXT DEFAULT NULL;
+ALTER TABLE "user" ADD COLUMN pushover_chapter_priority INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE "user" ADD COLUMN pushover_alert_priority INTEGER NOT NULL DEFAULT 0;
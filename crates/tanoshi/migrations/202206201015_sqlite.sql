@@ -0,0 +1,3 @@
+ALTER TABLE "user" ADD COLUMN catalogue_sort TEXT NOT NULL DEFAULT 'popular';
+ALTER TABLE "user" ADD COLUMN catalogue_language TEXT DEFAULT NULL;
+ALTER TABLE "user" ADD COLUMN catalogue_hide_library BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,13 @@
+CREATE TABLE notifications (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    kind VARCHAR(256) NOT NULL,
+    title TEXT,
+    body TEXT NOT NULL,
+    url TEXT,
+    is_read BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+CREATE INDEX notifications_user_id_created_at ON notifications(user_id, created_at);
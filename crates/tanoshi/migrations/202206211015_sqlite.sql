@@ -0,0 +1 @@
+ALTER TABLE user_library ADD COLUMN download_ahead INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,5 @@
+CREATE TABLE source_preference (
+    source_id INTEGER PRIMARY KEY,
+    preferences TEXT NOT NULL,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
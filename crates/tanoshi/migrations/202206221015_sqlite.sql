@@ -0,0 +1,2 @@
+ALTER TABLE user_category ADD COLUMN is_private BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE user_category ADD COLUMN pin TEXT;
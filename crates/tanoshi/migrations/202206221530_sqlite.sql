@@ -0,0 +1,10 @@
+CREATE TABLE source_credential (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    source_id INTEGER NOT NULL,
+    username TEXT NOT NULL,
+    session TEXT NOT NULL,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(user_id, source_id),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
@@ -0,0 +1,10 @@
+CREATE TABLE source_health (
+    source_id INTEGER PRIMARY KEY,
+    error_rate REAL NOT NULL DEFAULT 0,
+    consecutive_failures INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    last_success_at DATETIME,
+    last_failure_at DATETIME,
+    failing_since DATETIME,
+    disabled_at DATETIME
+);
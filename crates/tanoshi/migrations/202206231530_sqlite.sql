@@ -0,0 +1,5 @@
+CREATE TABLE source_proxy (
+    source_id INTEGER PRIMARY KEY,
+    url TEXT NOT NULL,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,5 @@
+CREATE TABLE source_rate_limit (
+    source_id INTEGER PRIMARY KEY,
+    requests_per_minute INTEGER NOT NULL,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
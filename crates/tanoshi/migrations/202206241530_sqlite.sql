@@ -0,0 +1 @@
+ALTER TABLE "user" ADD COLUMN enabled_languages TEXT DEFAULT NULL;
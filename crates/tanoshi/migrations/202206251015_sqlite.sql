@@ -0,0 +1,8 @@
+CREATE TABLE source_pin (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    source_id INTEGER NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(user_id, source_id),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
@@ -0,0 +1,13 @@
+CREATE TABLE source_header (
+    source_id INTEGER PRIMARY KEY,
+    user_agent TEXT,
+    headers TEXT NOT NULL DEFAULT '{}',
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE domain_header (
+    domain TEXT PRIMARY KEY,
+    user_agent TEXT,
+    headers TEXT NOT NULL DEFAULT '{}',
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
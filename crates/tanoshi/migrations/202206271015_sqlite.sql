@@ -0,0 +1,2 @@
+ALTER TABLE chapter ADD COLUMN scanlation_groups TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE chapter ADD COLUMN language TEXT;
@@ -0,0 +1 @@
+ALTER TABLE "user" ADD COLUMN hide_nsfw BOOLEAN NOT NULL DEFAULT false;
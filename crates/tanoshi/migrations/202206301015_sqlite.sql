@@ -0,0 +1 @@
+ALTER TABLE download_queue ADD COLUMN paused BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,4 @@
+ALTER TABLE download_queue ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE download_queue ADD COLUMN last_error TEXT;
+ALTER TABLE download_queue ADD COLUMN retry_at INTEGER;
+ALTER TABLE download_queue ADD COLUMN failed BOOLEAN NOT NULL DEFAULT false;
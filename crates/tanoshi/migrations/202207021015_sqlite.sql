@@ -0,0 +1,4 @@
+ALTER TABLE user_library ADD COLUMN auto_download BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE user_library ADD COLUMN auto_download_limit INTEGER;
+ALTER TABLE user_category ADD COLUMN auto_download BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE user_category ADD COLUMN auto_download_limit INTEGER;
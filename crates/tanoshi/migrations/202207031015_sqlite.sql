@@ -0,0 +1 @@
+ALTER TABLE chapter ADD COLUMN downloaded_page_count INTEGER;
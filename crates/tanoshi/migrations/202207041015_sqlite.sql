@@ -0,0 +1 @@
+ALTER TABLE chapter ADD COLUMN downloaded_size INTEGER;
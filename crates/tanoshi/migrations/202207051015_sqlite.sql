@@ -0,0 +1 @@
+ALTER TABLE "user" ADD COLUMN device_email VARCHAR(255) DEFAULT NULL;
@@ -0,0 +1 @@
+ALTER TABLE "user" ADD COLUMN data_saver BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,17 @@
+CREATE TABLE user_tag (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    UNIQUE(user_id, name),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+CREATE TABLE library_tag (
+    library_id INTEGER NOT NULL,
+    tag_id INTEGER NOT NULL,
+    PRIMARY KEY(library_id, tag_id),
+    FOREIGN KEY (library_id) REFERENCES user_library(id) ON DELETE CASCADE,
+    FOREIGN KEY (tag_id) REFERENCES user_tag(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_library_tag_tag_id ON library_tag(tag_id);
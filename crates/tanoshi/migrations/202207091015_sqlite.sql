@@ -0,0 +1,9 @@
+CREATE TABLE user_duplicate_ignore (
+    user_id INTEGER NOT NULL,
+    manga_id INTEGER NOT NULL,
+    other_id INTEGER NOT NULL,
+    PRIMARY KEY(user_id, manga_id, other_id),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE,
+    FOREIGN KEY (manga_id) REFERENCES manga(id) ON DELETE CASCADE,
+    FOREIGN KEY (other_id) REFERENCES manga(id) ON DELETE CASCADE
+);
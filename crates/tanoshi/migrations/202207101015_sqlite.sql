@@ -0,0 +1,8 @@
+ALTER TABLE manga_link ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+
+-- linked manga form one series, link every manga of it to each other
+INSERT INTO manga_link(manga_id, linked_manga_id)
+SELECT a.linked_manga_id, b.linked_manga_id FROM manga_link a
+JOIN manga_link b ON a.manga_id = b.manga_id AND a.linked_manga_id <> b.linked_manga_id
+WHERE true
+ON CONFLICT DO NOTHING;
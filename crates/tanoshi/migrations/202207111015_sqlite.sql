@@ -0,0 +1,9 @@
+CREATE TABLE manga_override (
+    manga_id INTEGER PRIMARY KEY,
+    title TEXT,
+    author TEXT,
+    genre TEXT,
+    status TEXT,
+    description TEXT,
+    FOREIGN KEY (manga_id) REFERENCES manga(id) ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+CREATE TABLE manga_alt_title (
+    manga_id INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    from_user BOOLEAN NOT NULL DEFAULT false,
+    PRIMARY KEY(manga_id, title),
+    FOREIGN KEY (manga_id) REFERENCES manga(id) ON DELETE CASCADE
+);
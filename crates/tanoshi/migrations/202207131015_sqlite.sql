@@ -0,0 +1,19 @@
+CREATE TABLE reading_list (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    UNIQUE(user_id, name),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+CREATE TABLE reading_list_manga (
+    reading_list_id INTEGER NOT NULL,
+    manga_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    PRIMARY KEY(reading_list_id, manga_id),
+    FOREIGN KEY (reading_list_id) REFERENCES reading_list(id) ON DELETE CASCADE,
+    FOREIGN KEY (manga_id) REFERENCES manga(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_reading_list_manga_manga_id ON reading_list_manga(manga_id);
@@ -0,0 +1,32 @@
+CREATE TABLE user_category_new (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    parent_id INTEGER,
+    name VARCHAR(255) NOT NULL,
+    is_private BOOLEAN NOT NULL DEFAULT false,
+    pin TEXT,
+    auto_download BOOLEAN NOT NULL DEFAULT false,
+    auto_download_limit INTEGER,
+    rules TEXT DEFAULT NULL,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE,
+    FOREIGN KEY (parent_id) REFERENCES user_category(id) ON DELETE CASCADE
+);
+
+INSERT INTO user_category_new(id, user_id, name, is_private, pin, auto_download, auto_download_limit, rules)
+SELECT id, user_id, name, is_private, pin, auto_download, auto_download_limit, rules FROM user_category;
+
+-- dropping user_category deletes library_category rows through the foreign key
+CREATE TEMP TABLE library_category_old AS SELECT * FROM library_category;
+
+DROP TABLE user_category;
+
+ALTER TABLE user_category_new RENAME TO user_category;
+
+INSERT INTO library_category(library_id, category_id) SELECT library_id, category_id FROM library_category_old;
+
+DROP TABLE library_category_old;
+
+CREATE INDEX idx_user_category_name ON user_category(name);
+CREATE INDEX idx_user_category_parent_id ON user_category(parent_id);
+-- top level categories have a null parent, which UNIQUE would treat as distinct
+CREATE UNIQUE INDEX idx_user_category_user_parent_name ON user_category(user_id, IFNULL(parent_id, 0), name);
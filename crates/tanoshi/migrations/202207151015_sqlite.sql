@@ -0,0 +1,8 @@
+ALTER TABLE user_category ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE user_category ADD COLUMN is_default BOOLEAN NOT NULL DEFAULT false;
+
+-- categories were ordered by name so far
+UPDATE user_category SET position = (
+    SELECT COUNT(1) FROM user_category other
+    WHERE other.user_id = user_category.user_id AND other.name < user_category.name
+);
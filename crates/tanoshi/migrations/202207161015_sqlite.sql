@@ -0,0 +1 @@
+ALTER TABLE user_library ADD COLUMN preferred_group TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE user ADD COLUMN chapter_languages TEXT;
+ALTER TABLE user_library ADD COLUMN chapter_languages TEXT;
@@ -0,0 +1,6 @@
+ALTER TABLE chapter ADD COLUMN sort_key REAL NOT NULL DEFAULT 0;
+
+-- recomputed from titles on the next refresh of each manga
+UPDATE chapter SET sort_key = number;
+
+CREATE INDEX idx_chapter_manga_id_sort_key ON chapter(manga_id, sort_key);
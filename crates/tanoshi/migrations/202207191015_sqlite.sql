@@ -0,0 +1,8 @@
+ALTER TABLE user_library ADD COLUMN date_added TIMESTAMP;
+
+-- closest known time for manga already in a library
+UPDATE user_library SET date_added = (
+    SELECT datetime(manga.date_added) FROM manga WHERE manga.id = user_library.manga_id
+);
+
+CREATE INDEX idx_user_library_user_id_date_added ON user_library(user_id, date_added);
@@ -0,0 +1,11 @@
+-- chapters waiting for the next digest of a user, kept across restarts
+CREATE TABLE chapter_digest (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    chapter_id INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE,
+    FOREIGN KEY (chapter_id) REFERENCES chapter(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_chapter_digest_user_id ON chapter_digest(user_id);
@@ -0,0 +1,2 @@
+-- bumped when a user locks private categories again, tokens unlocking them before are ignored
+ALTER TABLE user ADD COLUMN category_lock_generation INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,357 @@
+use std::net::{IpAddr, SocketAddr};
+
+use reqwest::{redirect::Policy, Url};
+use serde::Serialize;
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+};
+
+use crate::{
+    application::worker::downloads::{Command as DownloadCommand, DownloadSender},
+    domain::{
+        entities::{
+            automation::{AutomationAction, AutomationEvent, AutomationRule},
+            chapter::Chapter,
+            manga::Manga,
+        },
+        repositories::{
+            automation::AutomationRepository, chapter::ChapterRepository,
+            library::LibraryRepository, manga::MangaRepository,
+        },
+    },
+    infrastructure::{domain::repositories::user::UserRepositoryImpl, notification::Notification},
+};
+
+/// Most recent chapters queued by a download rule when a manga is added to library,
+/// unless the library entry has its own auto download limit
+const ADDED_TO_LIBRARY_DOWNLOAD_LIMIT: i64 = 10;
+
+pub type AutomationSender = UnboundedSender<Event>;
+type AutomationReceiver = UnboundedReceiver<Event>;
+
+/// Domain events published by graphql resolvers and workers,
+/// each one is evaluated against the user's enabled rules
+#[derive(Debug, Clone)]
+pub enum Event {
+    NewChapter {
+        user_id: i64,
+        manga_id: i64,
+        chapter_id: i64,
+    },
+    AddedToLibrary {
+        user_id: i64,
+        manga_id: i64,
+    },
+    ChapterRead {
+        user_id: i64,
+        chapter_id: i64,
+    },
+}
+
+impl Event {
+    fn kind(&self) -> AutomationEvent {
+        match self {
+            Event::NewChapter { .. } => AutomationEvent::NewChapter,
+            Event::AddedToLibrary { .. } => AutomationEvent::AddedToLibrary,
+            Event::ChapterRead { .. } => AutomationEvent::ChapterRead,
+        }
+    }
+
+    fn user_id(&self) -> i64 {
+        match self {
+            Event::NewChapter { user_id, .. }
+            | Event::AddedToLibrary { user_id, .. }
+            | Event::ChapterRead { user_id, .. } => *user_id,
+        }
+    }
+
+    fn chapter_id(&self) -> Option<i64> {
+        match self {
+            Event::NewChapter { chapter_id, .. } | Event::ChapterRead { chapter_id, .. } => {
+                Some(*chapter_id)
+            }
+            Event::AddedToLibrary { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    rule: &'a str,
+    event: AutomationEvent,
+    user_id: i64,
+    manga_id: i64,
+    manga_title: &'a str,
+    chapter_id: Option<i64>,
+    chapter_title: Option<&'a str>,
+}
+
+struct AutomationWorker<A, C, L, M>
+where
+    A: AutomationRepository + 'static,
+    C: ChapterRepository + 'static,
+    L: LibraryRepository + 'static,
+    M: MangaRepository + 'static,
+{
+    automation_repo: A,
+    chapter_repo: C,
+    library_repo: L,
+    manga_repo: M,
+    download_tx: DownloadSender,
+    notifier: Notification<UserRepositoryImpl>,
+    rx: AutomationReceiver,
+}
+
+impl<A, C, L, M> AutomationWorker<A, C, L, M>
+where
+    A: AutomationRepository + 'static,
+    C: ChapterRepository + 'static,
+    L: LibraryRepository + 'static,
+    M: MangaRepository + 'static,
+{
+    async fn handle_event(&self, event: &Event) -> Result<(), anyhow::Error> {
+        let rules = self
+            .automation_repo
+            .get_enabled_rules_by_event(event.user_id(), event.kind())
+            .await?;
+        if rules.is_empty() {
+            return Ok(());
+        }
+
+        let chapter = match event.chapter_id() {
+            Some(chapter_id) => Some(self.chapter_repo.get_chapter_by_id(chapter_id).await?),
+            None => None,
+        };
+        let manga_id = match (event, chapter.as_ref()) {
+            (Event::NewChapter { manga_id, .. }, _)
+            | (Event::AddedToLibrary { manga_id, .. }, _) => *manga_id,
+            (_, Some(chapter)) => chapter.manga_id,
+            _ => anyhow::bail!("event has no manga"),
+        };
+        let manga = self.manga_repo.get_manga_by_id(manga_id).await?;
+
+        for rule in rules {
+            debug!("running automation rule {} for {:?}", rule.name, event);
+            if let Err(e) = self
+                .run_action(&rule, event, &manga, chapter.as_ref())
+                .await
+            {
+                error!("automation rule {} failed: {e}", rule.name);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn run_action(
+        &self,
+        rule: &AutomationRule,
+        event: &Event,
+        manga: &Manga,
+        chapter: Option<&Chapter>,
+    ) -> Result<(), anyhow::Error> {
+        match &rule.action {
+            AutomationAction::Download => {
+                let chapter_ids = match (event, chapter) {
+                    (Event::NewChapter { chapter_id, .. }, _) => vec![*chapter_id],
+                    (Event::ChapterRead { .. }, Some(chapter)) => {
+                        chapter.next.into_iter().collect()
+                    }
+                    _ => {
+                        let limit = self
+                            .library_repo
+                            .get_auto_download(rule.user_id, manga.id)
+                            .await?
+                            .limit
+                            .unwrap_or(ADDED_TO_LIBRARY_DOWNLOAD_LIMIT);
+                        self.chapter_repo
+                            .get_chapters_by_manga_id(manga.id, Some(limit), None, false)
+                            .await?
+                            .into_iter()
+                            .filter(|chapter| chapter.downloaded_path.is_none())
+                            .map(|chapter| chapter.id)
+                            .collect()
+                    }
+                };
+
+                for chapter_id in chapter_ids {
+                    self.download_tx
+                        .send(DownloadCommand::InsertIntoQueue(chapter_id))?;
+                }
+            }
+            AutomationAction::MoveToCategory { category_id } => {
+                let category_ids: Vec<i64> = category_id.iter().copied().collect();
+                self.library_repo
+                    .set_manga_categories(rule.user_id, manga.id, &category_ids)
+                    .await?;
+            }
+            AutomationAction::Notify { channel } => {
+                let body = match (event.kind(), chapter) {
+                    (AutomationEvent::NewChapter, Some(chapter)) => {
+                        format!("New chapter: {}", chapter.title)
+                    }
+                    (AutomationEvent::ChapterRead, Some(chapter)) => {
+                        format!("Read {}", chapter.title)
+                    }
+                    _ => "Added to library".to_string(),
+                };
+                self.notifier
+                    .send_to_user_channel(rule.user_id, *channel, Some(manga.title.clone()), &body)
+                    .await?;
+            }
+            AutomationAction::Webhook { url } => {
+                let payload = WebhookPayload {
+                    rule: &rule.name,
+                    event: event.kind(),
+                    user_id: rule.user_id,
+                    manga_id: manga.id,
+                    manga_title: &manga.title,
+                    chapter_id: chapter.map(|chapter| chapter.id),
+                    chapter_title: chapter.map(|chapter| chapter.title.as_str()),
+                };
+
+                let url = Url::parse(url)?;
+                let host = url.host_str().unwrap_or_default().to_string();
+                let addr = resolve_public_addr(&url).await?;
+                // pinned to the checked address so the host can't resolve elsewhere afterwards,
+                // and redirects aren't followed to reach internal services either
+                reqwest::Client::builder()
+                    .redirect(Policy::none())
+                    .resolve(&host, addr)
+                    .build()?
+                    .post(url)
+                    .header(
+                        "User-Agent",
+                        format!("Tanoshi/{}", env!("CARGO_PKG_VERSION")).as_str(),
+                    )
+                    .json(&payload)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn run(mut self) {
+        while let Some(event) = self.rx.recv().await {
+            if let Err(e) = self.handle_event(&event).await {
+                error!("failed to handle {:?}: {e}", event);
+            }
+        }
+    }
+}
+
+/// Resolve webhook host, refusing loopback, private and link local destinations
+async fn resolve_public_addr(url: &Url) -> Result<SocketAddr, anyhow::Error> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("webhook url has no host"))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+    if addrs.is_empty() {
+        anyhow::bail!("webhook host {host} doesn't resolve");
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        anyhow::bail!(
+            "webhook host {host} resolves to non public address {}",
+            addr.ip()
+        );
+    }
+
+    Ok(addrs[0])
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                // shared address space 100.64.0.0/10
+                || (octets[0] == 100 && (octets[1] & 0xc0) == 64)
+                || octets[0] == 0)
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            if segments[..5] == [0; 5] && segments[5] == 0xffff {
+                let [a, b] = segments[6].to_be_bytes();
+                let [c, d] = segments[7].to_be_bytes();
+                return is_public_ip(IpAddr::from([a, b, c, d]));
+            }
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                // unique local fc00::/7
+                || (segments[0] & 0xfe00) == 0xfc00
+                // link local fe80::/10
+                || (segments[0] & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+pub fn channel() -> (AutomationSender, AutomationReceiver) {
+    tokio::sync::mpsc::unbounded_channel::<Event>()
+}
+
+pub fn start<A, C, L, M>(
+    automation_repo: A,
+    chapter_repo: C,
+    library_repo: L,
+    manga_repo: M,
+    download_tx: DownloadSender,
+    notifier: Notification<UserRepositoryImpl>,
+    rx: AutomationReceiver,
+) -> JoinHandle<()>
+where
+    A: AutomationRepository + 'static,
+    C: ChapterRepository + 'static,
+    L: LibraryRepository + 'static,
+    M: MangaRepository + 'static,
+{
+    let worker = AutomationWorker {
+        automation_repo,
+        chapter_repo,
+        library_repo,
+        manga_repo,
+        download_tx,
+        notifier,
+        rx,
+    };
+
+    tokio::spawn(worker.run())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_public_ip() {
+        for ip in ["1.1.1.1", "2606:4700:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashSet;
+
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+    time,
+};
+
+use crate::{
+    application::worker::downloads::{Command as DownloadCommand, DownloadSender},
+    domain::{
+        entities::{chapter::Chapter, library::DownloadAhead},
+        repositories::{
+            chapter::ChapterRepository, download::DownloadRepository, history::HistoryRepository,
+            library::LibraryRepository,
+        },
+        services::chapter::superseded_chapter_ids,
+    },
+    infrastructure::storage::Storage,
+};
+
+pub type DownloadAheadSender = UnboundedSender<Command>;
+type DownloadAheadReceiver = UnboundedReceiver<Command>;
+
+/// how often every buffer is topped up, catches chapters found by the update worker
+const REFRESH_PERIOD: time::Duration = time::Duration::from_secs(3600);
+
+#[derive(Debug)]
+pub enum Command {
+    /// Re-evaluate a manga after its setting changed
+    Refresh { manga_id: i64 },
+    /// Re-evaluate the manga of a chapter a user just finished
+    ChapterRead { chapter_id: i64 },
+}
+
+struct DownloadAheadWorker<C, D, H, L>
+where
+    C: ChapterRepository + 'static,
+    D: DownloadRepository + 'static,
+    H: HistoryRepository + 'static,
+    L: LibraryRepository + 'static,
+{
+    chapter_repo: C,
+    download_repo: D,
+    history_repo: H,
+    library_repo: L,
+    download_tx: DownloadSender,
+    rx: DownloadAheadReceiver,
+    storage: Storage,
+}
+
+impl<C, D, H, L> DownloadAheadWorker<C, D, H, L>
+where
+    C: ChapterRepository + 'static,
+    D: DownloadRepository + 'static,
+    H: HistoryRepository + 'static,
+    L: LibraryRepository + 'static,
+{
+    /// Next `count` unread chapters after the furthest completed one, skipping releases
+    /// of other groups when the preferred group released the same number,
+    /// and every chapter the user has completed
+    async fn user_chapters(
+        &self,
+        entry: &DownloadAhead,
+        chapters: &[Chapter],
+    ) -> Result<(Vec<i64>, HashSet<i64>), anyhow::Error> {
+        let groups: Vec<String> = entry.preferred_group.iter().cloned().collect();
+        let superseded = superseded_chapter_ids(chapters, &groups);
+
+        let progress = self
+            .history_repo
+            .get_chapter_progress_by_manga_id(entry.user_id, entry.manga_id)
+            .await?;
+
+        let furthest = progress
+            .iter()
+            .rposition(|chapter| chapter.is_complete)
+            .map(|pos| pos + 1)
+            .unwrap_or(0);
+        let wanted = progress[furthest..]
+            .iter()
+            .filter(|chapter| !chapter.is_complete && !superseded.contains(&chapter.chapter_id))
+            .take(entry.count as usize)
+            .map(|chapter| chapter.chapter_id)
+            .collect();
+        let completed = progress
+            .iter()
+            .filter(|chapter| chapter.is_complete)
+            .map(|chapter| chapter.chapter_id)
+            .collect();
+
+        Ok((wanted, completed))
+    }
+
+    async fn refresh_manga(&self, manga_id: i64) -> Result<(), anyhow::Error> {
+        let entries: Vec<DownloadAhead> = self
+            .library_repo
+            .get_download_ahead_entries()
+            .await?
+            .into_iter()
+            .filter(|entry| entry.manga_id == manga_id)
+            .collect();
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let chapters = self
+            .chapter_repo
+            .get_chapters_by_manga_id(manga_id, None, None, true)
+            .await?;
+
+        // downloads are shared, so a chapter is only kept or pruned
+        // when every user buffering this manga agrees
+        let mut wanted = HashSet::new();
+        let mut completed_by_all: Option<HashSet<i64>> = None;
+        for entry in entries.iter() {
+            let (user_wanted, user_completed) = self.user_chapters(entry, &chapters).await?;
+            wanted.extend(user_wanted);
+            completed_by_all = Some(match completed_by_all {
+                Some(completed) => completed.intersection(&user_completed).copied().collect(),
+                None => user_completed,
+            });
+        }
+        let completed_by_all = completed_by_all.unwrap_or_default();
+
+        let queued: HashSet<i64> = self
+            .download_repo
+            .get_download_queue()
+            .await?
+            .into_iter()
+            .map(|queue| queue.chapter_id)
+            .collect();
+
+        for chapter in chapters.iter() {
+            if wanted.contains(&chapter.id)
+                && chapter.downloaded_path.is_none()
+                && !queued.contains(&chapter.id)
+            {
+                debug!("downloading ahead chapter {}", chapter.id);
+                self.download_tx
+                    .send(DownloadCommand::InsertIntoQueueFirst(chapter.id))?;
+            } else if !wanted.contains(&chapter.id) && completed_by_all.contains(&chapter.id) {
+                if let Some(downloaded_path) = chapter.downloaded_path.as_ref() {
+                    debug!("pruning read chapter {}", chapter.id);
+                    if let Err(e) = self.storage.remove(downloaded_path).await {
+                        error!("error removing file: {e}");
+                    }
+                    self.download_repo
+                        .update_chapter_downloaded_path(chapter.id, None)
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn refresh_all(&self) -> Result<(), anyhow::Error> {
+        let manga_ids: HashSet<i64> = self
+            .library_repo
+            .get_download_ahead_entries()
+            .await?
+            .into_iter()
+            .map(|entry| entry.manga_id)
+            .collect();
+
+        for manga_id in manga_ids {
+            if let Err(e) = self.refresh_manga(manga_id).await {
+                error!("failed to download ahead manga {manga_id}: {e}");
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_command(&self, cmd: &Command) -> Result<(), anyhow::Error> {
+        match cmd {
+            Command::Refresh { manga_id } => self.refresh_manga(*manga_id).await,
+            Command::ChapterRead { chapter_id } => {
+                let chapter = self.chapter_repo.get_chapter_by_id(*chapter_id).await?;
+                self.refresh_manga(chapter.manga_id).await
+            }
+        }
+    }
+
+    async fn run(mut self) {
+        let mut refresh_interval = time::interval(REFRESH_PERIOD);
+
+        loop {
+            tokio::select! {
+                _ = refresh_interval.tick() => {
+                    if let Err(e) = self.refresh_all().await {
+                        error!("failed to refresh download ahead: {e}");
+                    }
+                }
+                Some(cmd) = self.rx.recv() => {
+                    if let Err(e) = self.handle_command(&cmd).await {
+                        error!("failed to handle {:?}: {e}", cmd);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn channel() -> (DownloadAheadSender, DownloadAheadReceiver) {
+    tokio::sync::mpsc::unbounded_channel::<Command>()
+}
+
+pub fn start<C, D, H, L>(
+    chapter_repo: C,
+    download_repo: D,
+    history_repo: H,
+    library_repo: L,
+    download_tx: DownloadSender,
+    rx: DownloadAheadReceiver,
+    storage: Storage,
+) -> JoinHandle<()>
+where
+    C: ChapterRepository + 'static,
+    D: DownloadRepository + 'static,
+    H: HistoryRepository + 'static,
+    L: LibraryRepository + 'static,
+{
+    let worker = DownloadAheadWorker {
+        chapter_repo,
+        download_repo,
+        history_repo,
+        library_repo,
+        download_tx,
+        rx,
+        storage,
+    };
+
+    tokio::spawn(worker.run())
+}
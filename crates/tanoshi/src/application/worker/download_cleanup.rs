@@ -0,0 +1,67 @@
+use tokio::{task::JoinHandle, time};
+
+use crate::{
+    domain::{
+        entities::download::RetentionPolicy,
+        repositories::download::DownloadRepository,
+        services::download::{apply_cleanup, plan_cleanup, DownloadError},
+    },
+    infrastructure::{config::DownloadRetentionConfig, storage::Storage},
+};
+
+struct DownloadCleanupWorker<R>
+where
+    R: DownloadRepository + 'static,
+{
+    download_repo: R,
+    storage: Storage,
+    policy: RetentionPolicy,
+    period: time::Duration,
+}
+
+impl<R> DownloadCleanupWorker<R>
+where
+    R: DownloadRepository + 'static,
+{
+    async fn cleanup(&self) -> Result<usize, DownloadError> {
+        let candidates = plan_cleanup(&self.download_repo, &self.policy).await?;
+
+        apply_cleanup(&self.download_repo, &self.storage, &candidates).await
+    }
+
+    async fn run(self) {
+        if self.policy.is_empty() {
+            debug!("no download retention policy set");
+            return;
+        }
+
+        let mut interval = time::interval(self.period);
+        loop {
+            interval.tick().await;
+
+            match self.cleanup().await {
+                Ok(0) => {}
+                Ok(removed) => info!("removed {removed} downloaded chapters"),
+                Err(e) => error!("failed to clean up downloads: {e}"),
+            }
+        }
+    }
+}
+
+pub fn start<R>(
+    download_repo: R,
+    storage: Storage,
+    config: &DownloadRetentionConfig,
+) -> JoinHandle<()>
+where
+    R: DownloadRepository + 'static,
+{
+    let worker = DownloadCleanupWorker {
+        download_repo,
+        storage,
+        policy: RetentionPolicy::from(config),
+        period: time::Duration::from_secs(config.interval.max(60)),
+    };
+
+    tokio::spawn(worker.run())
+}
@@ -1,100 +1,579 @@
 use crate::{
     domain::{
-        entities::{chapter::Chapter, download::DownloadQueue},
+        entities::{
+            chapter::Chapter,
+            download::{DownloadQueue, DownloadedChapterFile},
+            inbox::InboxKind,
+        },
         repositories::{
             chapter::ChapterRepository, download::DownloadRepository, manga::MangaRepository,
+            source::SourceRepository,
+        },
+    },
+    infrastructure::{
+        bandwidth::BandwidthLimiter,
+        comic_info::ComicInfo,
+        config::{
+            ArchiveOptimizeConfig, DownloadBandwidthConfig, DownloadConcurrencyConfig,
+            DownloadFormat, DownloadFormatConfig,
         },
+        domain::repositories::user::UserRepositoryImpl,
+        notification::{summarize_error, AlertThrottle, Notification},
+        optimize,
+        page_store::{self, Manifest, ManifestPage, PageStore},
+        path_template::{PathTemplate, PathValues},
+        storage::{self, Storage},
     },
-    infrastructure::{domain::repositories::user::UserRepositoryImpl, notification::Notification},
 };
 use anyhow::{anyhow, Result};
 use chrono::Utc;
+use futures::future::join_all;
 use reqwest::Url;
 use std::{
+    collections::{HashMap, HashSet},
     fs::File,
-    io::Write,
+    io::{Cursor, Read, Seek, Write},
     path::{Path, PathBuf},
 };
 use tanoshi_vm::extension::ExtensionManager;
-use zip::{ZipArchive, ZipWriter};
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
 
 use tokio::{
     sync::mpsc::{UnboundedReceiver, UnboundedSender},
     task::JoinHandle,
 };
 
+/// page downloads of a source failing in a row before admins are notified
+const FAILURE_ALERT_THRESHOLD: u32 = 3;
+const FAILURE_ALERT_COOLDOWN: std::time::Duration = std::time::Duration::from_secs(6 * 3600);
+/// attempts at a page before its chapter is marked failed
+const MAX_ATTEMPTS: i64 = 5;
+/// wait before the first retry of a page, doubled on every attempt after
+const RETRY_BACKOFF: std::time::Duration = std::time::Duration::from_secs(30);
+const MAX_RETRY_BACKOFF: std::time::Duration = std::time::Duration::from_secs(3600);
+/// metadata entry of downloaded archives, not a page
+const COMIC_INFO: &str = "ComicInfo.xml";
+
+/// Wait before retrying a page that failed `attempts` times
+fn retry_backoff(attempts: i64) -> std::time::Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    RETRY_BACKOFF
+        .saturating_mul(2u32.pow(exponent))
+        .min(MAX_RETRY_BACKOFF)
+}
+
 pub type DownloadSender = UnboundedSender<Command>;
 type DownloadReceiver = UnboundedReceiver<Command>;
 
 #[derive(Debug)]
 pub enum Command {
     InsertIntoQueue(i64),
+    /// queue a chapter ahead of everything else, e.g. the next one being read
+    InsertIntoQueueFirst(i64),
     InsertIntoQueueBySourcePath(i64, String),
     Download,
+    /// check downloaded archives, queueing broken chapters again
+    Verify,
+    /// move downloads into the page store and remove pages no chapter uses
+    Compact,
+    /// re-encode the pages of archives as configured in `archive_optimize`
+    Optimize,
+}
+
+/// What's wrong with a downloaded chapter
+enum Damage {
+    /// gone, not a zip or empty
+    Unreadable,
+    /// pages failing their checksum, or empty files in a folder
+    Corrupt(Vec<String>),
+    /// fewer pages than the chapter was completed with
+    Incomplete,
+}
+
+fn damage(pages: usize, corrupt: Vec<String>, page_count: Option<i64>) -> Option<Damage> {
+    if !corrupt.is_empty() {
+        Some(Damage::Corrupt(corrupt))
+    } else if page_count
+        .map(|count| (pages as i64) < count)
+        .unwrap_or(false)
+    {
+        Some(Damage::Incomplete)
+    } else {
+        None
+    }
+}
+
+/// Read every page of an archive through
+fn inspect_zip<R: Read + Seek>(reader: R, page_count: Option<i64>) -> Option<Damage> {
+    let mut zip = match ZipArchive::new(reader) {
+        Ok(zip) if !zip.is_empty() => zip,
+        _ => return Some(Damage::Unreadable),
+    };
+
+    let mut corrupt = vec![];
+    for i in 0..zip.len() {
+        match zip.by_index(i) {
+            Ok(mut entry) => {
+                if std::io::copy(&mut entry, &mut std::io::sink()).is_err() {
+                    corrupt.push(entry.name().to_string());
+                }
+            }
+            Err(_) => match zip.by_index_raw(i) {
+                Ok(entry) => corrupt.push(entry.name().to_string()),
+                Err(_) => return Some(Damage::Unreadable),
+            },
+        }
+    }
+
+    let pages = zip.len() - zip.file_names().filter(|name| *name == COMIC_INFO).count();
+    damage(pages, corrupt, page_count)
+}
+
+fn inspect_archive(path: &Path, page_count: Option<i64>) -> Option<Damage> {
+    match File::open(path) {
+        Ok(file) => inspect_zip(file, page_count),
+        Err(_) => Some(Damage::Unreadable),
+    }
+}
+
+fn inspect_folder(path: &Path, page_count: Option<i64>) -> Option<Damage> {
+    let pages: Vec<_> = match path.read_dir() {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_file())
+            .collect(),
+        Err(_) => return Some(Damage::Unreadable),
+    };
+    if pages.is_empty() {
+        return Some(Damage::Unreadable);
+    }
+
+    let corrupt = pages
+        .iter()
+        .filter(|page| page.metadata().map(|m| m.len() == 0).unwrap_or(true))
+        .map(|page| page.file_name().to_string_lossy().to_string())
+        .collect();
+
+    damage(pages.len(), corrupt, page_count)
+}
+
+fn inspect_manifest(path: &Path, page_count: Option<i64>) -> Option<Damage> {
+    let manifest = match Manifest::open(path) {
+        Ok(manifest) if !manifest.pages.is_empty() => manifest,
+        _ => return Some(Damage::Unreadable),
+    };
+
+    let corrupt = manifest
+        .pages
+        .iter()
+        .filter(|page| {
+            manifest
+                .blob_path(page)
+                .metadata()
+                .map(|m| m.len() == 0)
+                .unwrap_or(true)
+        })
+        .map(|page| page.name.clone())
+        .collect();
+
+    damage(manifest.pages.len(), corrupt, page_count)
+}
+
+/// Check a downloaded chapter, `None` if nothing is wrong
+fn inspect_download(path: &Path, page_count: Option<i64>) -> Option<Damage> {
+    if page_store::is_manifest(path) {
+        inspect_manifest(path, page_count)
+    } else if path.is_dir() {
+        inspect_folder(path, page_count)
+    } else {
+        inspect_archive(path, page_count)
+    }
+}
+
+/// Remove `pages` from the downloaded chapter at `path`, archives are rewritten without them
+fn drop_pages(path: &Path, pages: &[String]) -> Result<()> {
+    if page_store::is_manifest(path) {
+        let mut manifest = Manifest::open(path)?;
+        manifest.pages.retain(|page| !pages.contains(&page.name));
+        return manifest.save(path);
+    }
+
+    if path.is_dir() {
+        for page in pages {
+            std::fs::remove_file(path.join(page))?;
+        }
+        return Ok(());
+    }
+
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+    let part_path = path.with_extension("cbz.part");
+    let mut writer = ZipWriter::new(File::create(&part_path)?);
+    for i in 0..zip.len() {
+        let entry = zip.by_index_raw(i)?;
+        if !pages.iter().any(|page| page == entry.name()) {
+            writer.raw_copy_file(entry)?;
+        }
+    }
+    writer.finish()?;
+
+    std::fs::rename(part_path, path)?;
+
+    Ok(())
+}
+
+/// Bytes taken by a downloaded chapter, an archive or a folder of pages.
+/// Pages of a manifest are counted in full even if other chapters share them
+fn download_size(path: &Path) -> u64 {
+    if page_store::is_manifest(path) {
+        return Manifest::open(path)
+            .map(|manifest| {
+                manifest
+                    .pages
+                    .iter()
+                    .filter_map(|page| manifest.blob_path(page).metadata().ok())
+                    .map(|m| m.len())
+                    .sum()
+            })
+            .unwrap_or_default();
+    }
+
+    if !path.is_dir() {
+        return path.metadata().map(|m| m.len()).unwrap_or_default();
+    }
+
+    path.read_dir()
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter_map(|entry| entry.metadata().ok())
+                .map(|m| m.len())
+                .sum()
+        })
+        .unwrap_or_default()
+}
+
+/// Write a page so it's either there in full or not at all, even if the server dies midway
+fn write_page(dir: &Path, filename: &str, contents: &[u8]) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+
+    let part_path = dir.join(format!("{filename}.part"));
+    std::fs::write(&part_path, contents)?;
+    std::fs::rename(part_path, dir.join(filename))?;
+
+    Ok(())
+}
+
+/// Pack the pages staged in `pages_path` into the archive at `archive_path`, keeping pages
+/// an existing archive already has, then remove the staged pages. `comic_info` replaces
+/// the `ComicInfo.xml` of the archive, with the page count filled in
+fn pack_archive(
+    pages_path: &Path,
+    archive_path: &Path,
+    comic_info: Option<ComicInfo>,
+) -> Result<()> {
+    let part_path = archive_path.with_extension("cbz.part");
+    let mut writer = ZipWriter::new(File::create(&part_path)?);
+
+    let mut packed = HashSet::new();
+    for entry in pages_path.read_dir()?.filter_map(Result::ok) {
+        let filename = entry.file_name().to_string_lossy().to_string();
+        if !entry.path().is_file() || filename.ends_with(".part") {
+            continue;
+        }
+
+        writer.start_file(
+            &filename,
+            FileOptions::default().compression_method(CompressionMethod::Stored),
+        )?;
+        std::io::copy(&mut File::open(entry.path())?, &mut writer)?;
+        packed.insert(filename);
+    }
+
+    if let Ok(mut zip) = File::open(archive_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| Ok(ZipArchive::new(file)?))
+    {
+        for i in 0..zip.len() {
+            let entry = zip.by_index_raw(i)?;
+            if entry.name() == COMIC_INFO {
+                if comic_info.is_none() {
+                    writer.raw_copy_file(entry)?;
+                }
+            } else if !packed.contains(entry.name()) {
+                packed.insert(entry.name().to_string());
+                writer.raw_copy_file(entry)?;
+            }
+        }
+    }
+    if let Some(mut comic_info) = comic_info {
+        comic_info.page_count = packed.len();
+        writer.start_file(COMIC_INFO, Default::default())?;
+        writer.write_all(comic_info.to_xml().as_bytes())?;
+    }
+    writer.finish()?;
+
+    std::fs::rename(part_path, archive_path)?;
+    std::fs::remove_dir_all(pages_path)?;
+
+    Ok(())
+}
+
+/// Move the pages staged in `pages_path` into `store`, listing them in the manifest at
+/// `manifest_path` along with pages it already has, then remove the staged pages
+fn pack_manifest(store: &PageStore, pages_path: &Path, manifest_path: &Path) -> Result<()> {
+    let mut pages: Vec<ManifestPage> = Manifest::open(manifest_path)
+        .map(|manifest| manifest.pages)
+        .unwrap_or_default();
+
+    for entry in pages_path.read_dir()?.filter_map(Result::ok) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !entry.path().is_file() || name.ends_with(".part") {
+            continue;
+        }
+
+        let blob = store.insert(&name, &std::fs::read(entry.path())?)?;
+        pages.retain(|page| page.name != name);
+        pages.push(ManifestPage { name, blob });
+    }
+
+    store.manifest(pages).save(manifest_path)?;
+    std::fs::remove_dir_all(pages_path)?;
+
+    Ok(())
 }
 
-pub struct DownloadWorker<C, D, M>
+/// Move a chapter downloaded as an archive or folder into `store`, returns its manifest
+fn migrate_to_store(store: &PageStore, path: &Path) -> Result<PathBuf> {
+    let mut pages = vec![];
+    let manifest_path = if path.is_dir() {
+        for entry in path.read_dir()?.filter_map(Result::ok) {
+            if entry.path().is_file() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let blob = store.insert(&name, &std::fs::read(entry.path())?)?;
+                pages.push(ManifestPage { name, blob });
+            }
+        }
+        // folders are named after the chapter, which may contain dots
+        let mut manifest_path = path.as_os_str().to_owned();
+        manifest_path.push(format!(".{}", page_store::MANIFEST_EXTENSION));
+        PathBuf::from(manifest_path)
+    } else {
+        let mut zip = ZipArchive::new(File::open(path)?)?;
+        for i in 0..zip.len() {
+            let mut entry = zip.by_index(i)?;
+            if !entry.is_file() {
+                continue;
+            }
+            let name = entry.name().to_string();
+            let mut contents = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut contents)?;
+            let blob = store.insert(&name, &contents)?;
+            pages.push(ManifestPage { name, blob });
+        }
+        path.with_extension(page_store::MANIFEST_EXTENSION)
+    };
+
+    if pages.is_empty() {
+        return Err(anyhow!("{} has no pages", path.display()));
+    }
+
+    store.manifest(pages).save(&manifest_path)?;
+    remove_download(path);
+
+    Ok(manifest_path)
+}
+
+fn remove_download(path: &Path) {
+    let _ = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+}
+
+/// Queued page with where it's stored
+struct PendingPage {
+    queue: DownloadQueue,
+    url: Url,
+    filename: String,
+    format: DownloadFormat,
+    /// archive or folder of the chapter
+    chapter_path: PathBuf,
+    /// where the page is written, archives are packed from here once every page is in
+    pages_path: PathBuf,
+}
+
+/// Bandwidth budgets a download counts against
+struct Bandwidth {
+    global: Option<BandwidthLimiter>,
+    sources: HashMap<i64, BandwidthLimiter>,
+}
+
+impl From<&DownloadBandwidthConfig> for Bandwidth {
+    fn from(config: &DownloadBandwidthConfig) -> Self {
+        Self {
+            global: config
+                .limit
+                .filter(|limit| *limit > 0)
+                .map(BandwidthLimiter::new),
+            sources: config
+                .sources
+                .iter()
+                .filter(|(_, limit)| **limit > 0)
+                .map(|(source_id, limit)| (*source_id, BandwidthLimiter::new(*limit)))
+                .collect(),
+        }
+    }
+}
+
+impl Bandwidth {
+    async fn consume(&self, source_id: i64, bytes: usize) {
+        if let Some(limiter) = self.global.as_ref() {
+            limiter.consume(bytes).await;
+        }
+        if let Some(limiter) = self.sources.get(&source_id) {
+            limiter.consume(bytes).await;
+        }
+    }
+}
+
+async fn fetch_page(
+    client: &reqwest::Client,
+    ext: &ExtensionManager,
+    bandwidth: &Bandwidth,
+    page: &PendingPage,
+) -> Result<Vec<u8>> {
+    ext.throttle(page.queue.source_id).await?;
+
+    let referrer = ext
+        .get_source_info(page.queue.source_id)
+        .map(|s| s.url)
+        .unwrap_or_default();
+
+    let mut res = client
+        .request(reqwest::Method::GET, page.url.clone())
+        .header("referer", referrer)
+        .send()
+        .await?;
+
+    let mut contents = vec![];
+    while let Some(chunk) = res.chunk().await? {
+        bandwidth.consume(page.queue.source_id, chunk.len()).await;
+        contents.extend_from_slice(&chunk);
+    }
+
+    Ok(contents)
+}
+
+pub struct DownloadWorker<C, D, M, S>
 where
     C: ChapterRepository + 'static,
     D: DownloadRepository + 'static,
     M: MangaRepository + 'static,
+    S: SourceRepository + 'static,
 {
     dir: PathBuf,
+    layout: PathTemplate,
+    format: DownloadFormatConfig,
+    storage: Storage,
+    pages: PageStore,
     client: reqwest::Client,
     chapter_repo: C,
     manga_repo: M,
     download_repo: D,
+    source_repo: S,
     ext: ExtensionManager,
-    _notifier: Notification<UserRepositoryImpl>,
+    notifier: Notification<UserRepositoryImpl>,
+    concurrency: DownloadConcurrencyConfig,
+    bandwidth: Bandwidth,
+    optimize: Option<ArchiveOptimizeConfig>,
+    alerts: AlertThrottle,
+    /// page downloads failed in a row, by source id
+    consecutive_failures: HashMap<i64, u32>,
     tx: DownloadSender,
     rx: DownloadReceiver,
 }
 
-impl<C, D, M> DownloadWorker<C, D, M>
+impl<C, D, M, S> DownloadWorker<C, D, M, S>
 where
     C: ChapterRepository + 'static,
     D: DownloadRepository + 'static,
     M: MangaRepository + 'static,
+    S: SourceRepository + 'static,
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new<P: AsRef<Path>>(
         dir: P,
         chapter_repo: C,
         manga_repo: M,
         download_repo: D,
+        source_repo: S,
         ext: ExtensionManager,
         notifier: Notification<UserRepositoryImpl>,
+        layout: PathTemplate,
+        format: DownloadFormatConfig,
+        storage: Storage,
+        concurrency: DownloadConcurrencyConfig,
+        bandwidth: &DownloadBandwidthConfig,
+        optimize: Option<ArchiveOptimizeConfig>,
         download_sender: DownloadSender,
         download_receiver: DownloadReceiver,
     ) -> Self {
         Self {
+            pages: PageStore::new(&dir),
             dir: PathBuf::new().join(dir),
+            layout,
+            format,
+            storage,
             client: reqwest::ClientBuilder::new().build().unwrap(),
             chapter_repo,
             manga_repo,
             download_repo,
+            source_repo,
             ext,
-            _notifier: notifier,
+            notifier,
+            concurrency,
+            bandwidth: bandwidth.into(),
+            optimize,
+            alerts: AlertThrottle::new(FAILURE_ALERT_COOLDOWN),
+            consecutive_failures: HashMap::new(),
             tx: download_sender,
             rx: download_receiver,
         }
     }
 
-    async fn insert_to_queue(&mut self, chapter: &Chapter) -> Result<(), anyhow::Error> {
+    async fn insert_to_queue(
+        &mut self,
+        chapter: &Chapter,
+        first: bool,
+    ) -> Result<(), anyhow::Error> {
         // numbe 1 and greater than 10000 reserved for local source
         if chapter.source_id >= 10000 {
             anyhow::bail!("local source can't be downloaded");
         }
 
-        let priority = self
-            .download_repo
-            .get_download_queue_last_priority()
-            .await?
-            .map(|p| p + 1)
-            .unwrap_or(0);
+        let priority = if first {
+            self.download_repo
+                .get_download_queue_first_priority()
+                .await?
+                .map(|p| p - 1)
+                .unwrap_or(0)
+        } else {
+            self.download_repo
+                .get_download_queue_last_priority()
+                .await?
+                .map(|p| p + 1)
+                .unwrap_or(0)
+        };
 
         let manga = self.manga_repo.get_manga_by_id(chapter.manga_id).await?;
+        let session = self
+            .source_repo
+            .get_library_session(manga.source_id, manga.id)
+            .await?;
         let pages = self
             .ext
+            .with_session(session)
             .get_pages(manga.source_id, chapter.path.clone())
             .await?;
 
@@ -111,10 +590,13 @@ where
                 manga_title: manga.title.clone(),
                 chapter_id: chapter.id,
                 chapter_title: format!("{} - {}", chapter.number, chapter.title.clone()),
+                chapter_number: chapter.number,
+                category: None,
                 rank: rank as _,
                 url: page.clone(),
                 priority,
                 date_added,
+                attempts: 0,
             })
         }
 
@@ -123,6 +605,139 @@ where
         Ok(())
     }
 
+    async fn notify_download_failure(&mut self, chapter: &Chapter, e: &anyhow::Error) {
+        if !self.alerts.allow(&chapter.source_id.to_string()) {
+            debug!(
+                "download failure alert for source {} throttled",
+                chapter.source_id
+            );
+            return;
+        }
+
+        let source_name = self
+            .ext
+            .get_source_info(chapter.source_id)
+            .map(|source| source.name)
+            .unwrap_or_default();
+        let manga_title = self
+            .manga_repo
+            .get_manga_by_id(chapter.manga_id)
+            .await
+            .map(|manga| manga.title)
+            .unwrap_or_default();
+        let message = format!(
+            "{source_name}: {manga_title} - {}\n{}",
+            chapter.title,
+            summarize_error(e)
+        );
+        if let Err(e) = self
+            .notifier
+            .send_download_failure_to_admins(Some("Download Failed".to_string()), &message)
+            .await
+        {
+            error!("failed to send download failure notification, reason {e}");
+        }
+    }
+
+    fn wake_after(&self, delay: std::time::Duration) {
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let _ = tx.send(Command::Download);
+        });
+    }
+
+    async fn notify_chapter_failed(
+        &mut self,
+        queue: &DownloadQueue,
+        attempts: i64,
+        e: &anyhow::Error,
+    ) {
+        if !self.alerts.allow(&format!("failed:{}", queue.source_id)) {
+            debug!(
+                "download failed alert for source {} throttled",
+                queue.source_id
+            );
+            return;
+        }
+
+        let message = format!(
+            "{}: {} - {} failed after {attempts} attempts\n{}",
+            queue.source_name,
+            queue.manga_title,
+            queue.chapter_title,
+            summarize_error(e)
+        );
+        if let Err(e) = self
+            .notifier
+            .send_download_failure_to_admins(Some("Download Failed".to_string()), &message)
+            .await
+        {
+            error!("failed to send download failure notification, reason {e}");
+        }
+    }
+
+    /// Schedule the page for a retry, or mark its chapter failed once it ran out of attempts
+    async fn record_download_failure(&mut self, queue: &DownloadQueue, e: &anyhow::Error) {
+        let attempts = queue.attempts + 1;
+        let backoff = retry_backoff(attempts);
+        let retry_at = Utc::now().timestamp() + backoff.as_secs() as i64;
+        if let Err(e) = self
+            .download_repo
+            .record_download_queue_failure(queue.id, &summarize_error(e), retry_at)
+            .await
+        {
+            error!("failed to record download failure, reason {e}");
+        }
+
+        if attempts >= MAX_ATTEMPTS {
+            // every page of the chapter runs out of attempts, only the first one alerts
+            let marked = match self
+                .download_repo
+                .mark_chapter_download_failed(queue.chapter_id)
+                .await
+            {
+                Ok(marked) => marked,
+                Err(e) => {
+                    error!("failed to mark chapter download failed, reason {e}");
+                    false
+                }
+            };
+            if marked {
+                self.notify_chapter_failed(queue, attempts, e).await;
+            }
+        } else {
+            // wake up for the retry in case nothing else is left in queue by then
+            self.wake_after(backoff);
+        }
+
+        let failures = self
+            .consecutive_failures
+            .entry(queue.source_id)
+            .or_default();
+        *failures += 1;
+        let failures = *failures;
+        if failures < FAILURE_ALERT_THRESHOLD || !self.alerts.allow(&queue.source_id.to_string()) {
+            return;
+        }
+
+        let message = format!(
+            "{}: {} - {} failed {} times in a row\n{}",
+            queue.source_name,
+            queue.manga_title,
+            queue.chapter_title,
+            failures,
+            summarize_error(e)
+        );
+        if let Err(e) = self
+            .notifier
+            .send_download_failure_to_admins(Some("Download Failing".to_string()), &message)
+            .await
+        {
+            error!("failed to send download failure notification, reason {e}");
+        }
+    }
+
     async fn paused(&self) -> bool {
         self.dir.join(".pause").exists()
     }
@@ -132,33 +747,34 @@ where
         Ok(zip::ZipArchive::new(file)?)
     }
 
-    fn open_or_create_writeble_zip_file<P: AsRef<Path>>(
-        &self,
-        manga_path: P,
-        archive_path: P,
-    ) -> Result<ZipWriter<File>> {
-        if let Ok(file) = std::fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(&archive_path)
-        {
-            return Ok(zip::ZipWriter::new_append(file)?);
-        } else if let Ok(file) =
-            std::fs::create_dir_all(manga_path).and_then(|_| std::fs::File::create(&archive_path))
-        {
-            return Ok(zip::ZipWriter::new(file));
+    /// Pages of `candidates` to fetch together without exceeding the concurrency limits
+    fn select_batch(&self, candidates: Vec<DownloadQueue>) -> Vec<DownloadQueue> {
+        let mut chapters: Vec<i64> = vec![];
+        let mut pages_per_source: HashMap<i64, usize> = HashMap::new();
+
+        let mut batch = vec![];
+        for queue in candidates {
+            if !chapters.contains(&queue.chapter_id) {
+                if chapters.len() >= self.concurrency.chapters.max(1) {
+                    continue;
+                }
+                chapters.push(queue.chapter_id);
+            }
+
+            let source_pages = pages_per_source.entry(queue.source_id).or_default();
+            if *source_pages >= self.concurrency.source_limit(queue.source_id) {
+                continue;
+            }
+            *source_pages += 1;
+
+            batch.push(queue);
         }
 
-        Err(anyhow!("cannot open or create new zip file"))
+        batch
     }
 
-    async fn download(&mut self) -> Result<()> {
-        let mut queue = self
-            .download_repo
-            .get_single_download_queue()
-            .await?
-            .ok_or_else(|| anyhow!("no queue"))?;
-
+    /// Resolve where `queue` is stored, `None` if it's already in the archive
+    async fn prepare(&self, queue: DownloadQueue) -> Result<Option<PendingPage>> {
         debug!("got {}", queue.url);
 
         let url = Url::parse(&queue.url)?;
@@ -169,55 +785,79 @@ where
             .map(|s| s.to_string())
             .ok_or_else(|| anyhow!("no filename"))?;
 
-        queue.source_name = queue
-            .source_name
-            .replace(&['\\', '/', ':', '*', '?', '\"', '<', '>', '|'][..], "");
-        queue.manga_title = queue
-            .manga_title
-            .replace(&['\\', '/', ':', '*', '?', '\"', '<', '>', '|'][..], "");
-        queue.chapter_title = queue
-            .chapter_title
-            .replace(&['\\', '/', ':', '*', '?', '\"', '<', '>', '|'][..], "");
-
-        let manga_path = self.dir.join(&queue.source_name).join(&queue.manga_title);
-
-        let archive_path = manga_path.join(format!("{}.cbz", queue.chapter_title));
+        // only archives are uploaded, a folder would be an object per page
+        let format = if self.storage.is_remote() {
+            DownloadFormat::Cbz
+        } else {
+            self.format.for_category(queue.category.as_deref())
+        };
+        let chapter_path = self.dir.join(self.layout.render(
+            &PathValues {
+                source: &queue.source_name,
+                category: queue.category.as_deref(),
+                manga: &queue.manga_title,
+                chapter: &queue.chapter_title,
+                number: queue.chapter_number,
+            },
+            match format {
+                DownloadFormat::Cbz => Some("cbz"),
+                DownloadFormat::Folder => None,
+                DownloadFormat::Dedup => Some(page_store::MANIFEST_EXTENSION),
+            },
+        ));
+        let pages_path = match format {
+            DownloadFormat::Cbz | DownloadFormat::Dedup => chapter_path.with_extension("part"),
+            DownloadFormat::Folder => chapter_path.clone(),
+        };
 
-        if let Ok(mut zip) = self.open_readable_zip_file(&archive_path) {
-            if zip.by_name(&filename).is_ok() {
-                debug!("file already downloaded, mark as compeleted then skip");
-                self.download_repo
-                    .mark_single_download_queue_as_completed(queue.id)
-                    .await?;
-                if !self.paused().await {
-                    self.tx.send(Command::Download).unwrap();
-                }
-                return Ok(());
-            }
+        // pages written before a restart are picked up where they were left
+        let downloaded = pages_path.join(&filename).is_file()
+            || match format {
+                DownloadFormat::Cbz => self
+                    .open_readable_zip_file(&chapter_path)
+                    .map(|mut zip| zip.by_name(&filename).is_ok())
+                    .unwrap_or(false),
+                DownloadFormat::Dedup => Manifest::open(&chapter_path)
+                    .map(|manifest| manifest.contains(&filename))
+                    .unwrap_or(false),
+                DownloadFormat::Folder => false,
+            };
+        if downloaded {
+            debug!("file already downloaded, mark as compeleted then skip");
+            self.download_repo
+                .mark_single_download_queue_as_completed(queue.id)
+                .await?;
+            return Ok(None);
         }
 
-        let mut zip = self.open_or_create_writeble_zip_file(&manga_path, &archive_path)?;
-
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        Ok(Some(PendingPage {
+            queue,
+            url,
+            filename,
+            format,
+            chapter_path,
+            pages_path,
+        }))
+    }
 
-        let referrer = self
-            .ext
-            .get_source_info(queue.source_id)
-            .map(|s| s.url)
-            .unwrap_or_default();
+    /// Metadata written into the archive of a chapter, it's only missed by readers
+    /// so the chapter is packed without it if it can't be looked up
+    async fn comic_info(&self, chapter_id: i64) -> Option<ComicInfo> {
+        let chapter = self.chapter_repo.get_chapter_by_id(chapter_id).await.ok()?;
+        let manga = self
+            .manga_repo
+            .get_manga_by_id(chapter.manga_id)
+            .await
+            .ok()?;
 
-        let contents = self
-            .client
-            .request(reqwest::Method::GET, url.clone())
-            .header("referer", referrer)
-            .send()
-            .await?
-            .bytes()
-            .await?;
+        Some(ComicInfo::new(&manga, &chapter, 0))
+    }
 
-        zip.start_file(&filename, Default::default())?;
+    /// Write a fetched page, finishing the chapter with its last page
+    async fn store(&mut self, page: &PendingPage, contents: &[u8]) -> Result<()> {
+        let queue = &page.queue;
 
-        zip.write_all(contents.to_vec().as_slice())?;
+        write_page(&page.pages_path, &page.filename, contents)?;
 
         self.download_repo
             .mark_single_download_queue_as_completed(queue.id)
@@ -229,19 +869,352 @@ where
             .await
             .unwrap_or_default()
         {
+            let pages_path = page.pages_path.clone();
+            let chapter_path = page.chapter_path.clone();
+            match page.format {
+                DownloadFormat::Cbz => {
+                    let comic_info = self.comic_info(queue.chapter_id).await;
+                    tokio::task::spawn_blocking(move || {
+                        pack_archive(&pages_path, &chapter_path, comic_info)
+                    })
+                    .await??
+                }
+                DownloadFormat::Dedup => {
+                    let store = self.pages.clone();
+                    tokio::task::spawn_blocking(move || {
+                        pack_manifest(&store, &pages_path, &chapter_path)
+                    })
+                    .await??
+                }
+                DownloadFormat::Folder => {}
+            }
+
+            let size = download_size(&page.chapter_path);
+            let key = page
+                .chapter_path
+                .strip_prefix(&self.dir)
+                .unwrap_or(&page.chapter_path)
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let location = self.storage.store(&page.chapter_path, &key).await?;
+
             self.download_repo
-                .update_chapter_downloaded_path(
-                    queue.chapter_id,
-                    Some(archive_path.display().to_string()),
-                )
+                .update_chapter_downloaded_path(queue.chapter_id, Some(location))
+                .await?;
+
+            self.download_repo
+                .update_chapter_downloaded_page_count(queue.chapter_id)
+                .await?;
+
+            self.download_repo
+                .update_chapter_downloaded_size(queue.chapter_id, size as i64)
                 .await?;
 
             self.download_repo
                 .delete_single_chapter_download_queue(queue.chapter_id)
                 .await?;
+
+            if let Err(e) = self
+                .notifier
+                .add_to_admins_inbox(
+                    InboxKind::Download,
+                    Some("Download Completed"),
+                    &format!("{} - {}", queue.manga_title, queue.chapter_title),
+                    None,
+                )
+                .await
+            {
+                error!("failed to store download notification, reason {e}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check a download on disk, dropping broken pages. `true` if it needs downloading again
+    async fn inspect_local(&self, path: &str, page_count: Option<i64>) -> Result<bool> {
+        let path = PathBuf::from(path);
+        let damage = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || inspect_download(&path, page_count)).await?
+        };
+
+        match damage {
+            None => return Ok(false),
+            Some(Damage::Unreadable) => remove_download(&path),
+            Some(Damage::Corrupt(pages)) => {
+                if let Err(e) = drop_pages(&path, &pages) {
+                    error!("failed to repair {}, reason {e}", path.display());
+                    remove_download(&path);
+                }
+            }
+            Some(Damage::Incomplete) => {}
+        }
+
+        Ok(true)
+    }
+
+    /// Check every downloaded archive, broken pages are dropped and their chapter queued again
+    /// so only what's missing is downloaded
+    async fn verify(&mut self) -> Result<()> {
+        let mut repaired = vec![];
+        for file in self.download_repo.get_downloaded_chapter_files().await? {
+            // archives in a bucket aren't repaired in place, they're downloaded again in full
+            if storage::is_remote(&file.path) {
+                let data = match self.storage.read(&file.path).await {
+                    Ok(data) => data,
+                    Err(e) => {
+                        error!("failed to fetch {} for checking, reason {e}", file.path);
+                        continue;
+                    }
+                };
+                let page_count = file.page_count;
+                let damage =
+                    tokio::task::spawn_blocking(move || inspect_zip(Cursor::new(data), page_count))
+                        .await?;
+                if damage.is_none() {
+                    continue;
+                }
+                if let Err(e) = self.storage.remove(&file.path).await {
+                    error!("failed to remove {}, reason {e}", file.path);
+                }
+            } else if !self.inspect_local(&file.path, file.page_count).await? {
+                continue;
+            }
+
+            info!("queueing {} again", file.path);
+            self.download_repo
+                .update_chapter_downloaded_path(file.chapter_id, None)
+                .await?;
+            let _ = self.tx.send(Command::InsertIntoQueue(file.chapter_id));
+
+            repaired.push(format!("{} - {}", file.manga_title, file.chapter_title));
+        }
+
+        if !repaired.is_empty() {
+            if let Err(e) = self
+                .notifier
+                .add_to_admins_inbox(
+                    InboxKind::Download,
+                    Some("Downloads Repaired"),
+                    &repaired.join("\n"),
+                    None,
+                )
+                .await
+            {
+                error!("failed to store repair notification, reason {e}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move downloads on disk into the page store when it's the configured format,
+    /// then remove stored pages no manifest lists anymore
+    async fn compact(&mut self) -> Result<()> {
+        if self.format.format == DownloadFormat::Dedup {
+            for file in self.download_repo.get_downloaded_chapter_files().await? {
+                let path = PathBuf::from(&file.path);
+                if storage::is_remote(&file.path) || page_store::is_manifest(&path) {
+                    continue;
+                }
+
+                let store = self.pages.clone();
+                match tokio::task::spawn_blocking(move || migrate_to_store(&store, &path)).await? {
+                    Ok(manifest_path) => {
+                        self.download_repo
+                            .update_chapter_downloaded_path(
+                                file.chapter_id,
+                                Some(manifest_path.display().to_string()),
+                            )
+                            .await?;
+                    }
+                    Err(e) => error!("failed to move {} into page store, reason {e}", file.path),
+                }
+            }
+        }
+
+        // a manifest that can't be read could still list pages, nothing is removed then
+        let mut referenced = HashSet::new();
+        for file in self.download_repo.get_downloaded_chapter_files().await? {
+            let path = PathBuf::from(&file.path);
+            if !page_store::is_manifest(&path) {
+                continue;
+            }
+            let manifest = Manifest::open(&path)
+                .map_err(|e| anyhow!("failed to read {}, reason {e}", file.path))?;
+            referenced.extend(manifest.pages.into_iter().map(|page| page.blob));
+        }
+
+        let store = self.pages.clone();
+        let freed =
+            tokio::task::spawn_blocking(move || store.collect_garbage(&referenced)).await??;
+        if freed > 0 {
+            info!("removed {freed} bytes of pages no chapter uses");
+        }
+
+        Ok(())
+    }
+
+    /// Rewrite downloaded archives, and those in local folders when configured, with their
+    /// pages re-encoded, then tell admins how much smaller every manga got
+    async fn optimize(&mut self) -> Result<()> {
+        let config = match self.optimize.clone() {
+            Some(config) => config,
+            None => {
+                info!("archive optimization is not configured");
+                return Ok(());
+            }
+        };
+
+        let downloads = self.download_repo.get_downloaded_chapter_files().await?;
+        let local = if config.include_local {
+            self.download_repo.get_local_chapter_files().await?
+        } else {
+            vec![]
+        };
+
+        let mut saved: Vec<(DownloadedChapterFile, u64)> = vec![];
+        let files = downloads
+            .into_iter()
+            .map(|file| (file, true))
+            .chain(local.into_iter().map(|file| (file, false)));
+        for (file, downloaded) in files {
+            let path = PathBuf::from(&file.path);
+            if storage::is_remote(&file.path) || !optimize::is_optimizable(&path) {
+                continue;
+            }
+
+            let options = config.clone();
+            let optimized = match tokio::task::spawn_blocking(move || {
+                optimize::optimize_archive(&path, &options)
+            })
+            .await?
+            {
+                Ok(Some(optimized)) => optimized,
+                Ok(None) => continue,
+                Err(e) => {
+                    error!("failed to optimize {}, reason {e}", file.path);
+                    continue;
+                }
+            };
+
+            if downloaded {
+                self.download_repo
+                    .update_chapter_downloaded_size(file.chapter_id, optimized.after as i64)
+                    .await?;
+            }
+
+            let bytes = optimized.saved();
+            match saved.iter_mut().find(|(f, _)| f.manga_id == file.manga_id) {
+                Some((_, total)) => *total += bytes,
+                None => saved.push((file, bytes)),
+            }
+        }
+
+        if saved.is_empty() {
+            return Ok(());
+        }
+
+        saved.sort_by(|(_, a), (_, b)| b.cmp(a));
+        let total: u64 = saved.iter().map(|(_, bytes)| bytes).sum();
+        let mut lines: Vec<String> = saved
+            .iter()
+            .map(|(file, bytes)| {
+                format!("{} - {:.1} MB", file.manga_title, *bytes as f64 / 1048576.0)
+            })
+            .collect();
+        lines.push(format!("Total - {:.1} MB", total as f64 / 1048576.0));
+        info!("optimized archives, {}", lines.join(", "));
+
+        if let Err(e) = self
+            .notifier
+            .add_to_admins_inbox(
+                InboxKind::Download,
+                Some("Archives Optimized"),
+                &lines.join("\n"),
+                None,
+            )
+            .await
+        {
+            error!("failed to store optimization notification, reason {e}");
+        }
+
+        Ok(())
+    }
+
+    /// Record the size of chapters downloaded before sizes were tracked
+    async fn record_missing_sizes(&self) -> Result<()> {
+        for file in self.download_repo.get_downloaded_chapter_files().await? {
+            if file.size.is_some() || storage::is_remote(&file.path) {
+                continue;
+            }
+
+            let path = PathBuf::from(&file.path);
+            let size = tokio::task::spawn_blocking(move || download_size(&path)).await?;
+            self.download_repo
+                .update_chapter_downloaded_size(file.chapter_id, size as i64)
+                .await?;
         }
 
-        zip.flush()?;
+        Ok(())
+    }
+
+    /// Fetch the next pages at once, then store them one by one as archives aren't shared
+    async fn download(&mut self) -> Result<()> {
+        let pages_per_chapter = self.concurrency.pages.max(1);
+        let limit = pages_per_chapter * self.concurrency.chapters.max(1);
+        let candidates = self
+            .download_repo
+            .get_next_download_queue(pages_per_chapter as i64, limit as i64)
+            .await?;
+
+        let batch = self.select_batch(candidates);
+        if batch.is_empty() {
+            debug!("no queue");
+            return Ok(());
+        }
+
+        let mut pending = vec![];
+        for queue in batch {
+            match self.prepare(queue.clone()).await {
+                Ok(Some(page)) => pending.push(page),
+                Ok(None) => {}
+                Err(e) => {
+                    error!("{e}");
+                    self.record_download_failure(&queue, &e).await;
+                }
+            }
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+
+        let fetched = join_all(
+            pending
+                .iter()
+                .map(|page| fetch_page(&self.client, &self.ext, &self.bandwidth, page)),
+        )
+        .await;
+
+        for (page, contents) in pending.iter().zip(fetched) {
+            let res = match contents {
+                Ok(contents) => self.store(page, &contents).await,
+                Err(e) => Err(e),
+            };
+
+            match res {
+                Ok(_) => {
+                    self.consecutive_failures.remove(&page.queue.source_id);
+                }
+                // failed page waits out its backoff, the rest carry on
+                Err(e) => {
+                    error!("{e}");
+                    self.record_download_failure(&page.queue, &e).await;
+                }
+            }
+        }
 
         if !self.paused().await {
             self.tx.send(Command::Download).unwrap();
@@ -251,17 +1224,34 @@ where
     }
 
     pub async fn run(mut self) {
+        if let Err(e) = self.record_missing_sizes().await {
+            error!("failed to record download sizes, reason {e}");
+        }
+
+        // retries scheduled before a restart
+        match self.download_repo.get_next_download_retry_at().await {
+            Ok(Some(retry_at)) => {
+                let delay = (retry_at - Utc::now().timestamp()).max(0) as u64;
+                self.wake_after(std::time::Duration::from_secs(delay));
+            }
+            Ok(None) => {}
+            Err(e) => error!("failed to get next download retry, reason {e}"),
+        }
+
         if !self.paused().await {
             self.tx.send(Command::Download).unwrap();
         }
 
         while let Some(cmd) = self.rx.recv().await {
             match cmd {
-                Command::InsertIntoQueue(chapter_id) => {
+                Command::InsertIntoQueue(chapter_id)
+                | Command::InsertIntoQueueFirst(chapter_id) => {
+                    let first = matches!(cmd, Command::InsertIntoQueueFirst(_));
                     match self.chapter_repo.get_chapter_by_id(chapter_id).await {
                         Ok(chapter) => {
-                            if let Err(e) = self.insert_to_queue(&chapter).await {
+                            if let Err(e) = self.insert_to_queue(&chapter, first).await {
                                 error!("failed to insert queue, reason {}", e);
+                                self.notify_download_failure(&chapter, &e).await;
                                 continue;
                             }
                             self.tx.send(Command::Download).unwrap();
@@ -278,8 +1268,9 @@ where
                         .await
                     {
                         Ok(chapter) => {
-                            if let Err(e) = self.insert_to_queue(&chapter).await {
+                            if let Err(e) = self.insert_to_queue(&chapter, false).await {
                                 error!("failed to insert queue, reason {e}");
+                                self.notify_download_failure(&chapter, &e).await;
                                 continue;
                             }
                             let _ = self.tx.send(Command::Download);
@@ -295,7 +1286,22 @@ where
                     }
 
                     if let Err(e) = self.download().await {
-                        error!("{e}")
+                        error!("failed to download queue, reason {e}");
+                    }
+                }
+                Command::Verify => {
+                    if let Err(e) = self.verify().await {
+                        error!("failed to verify downloads, reason {e}");
+                    }
+                }
+                Command::Compact => {
+                    if let Err(e) = self.compact().await {
+                        error!("failed to compact downloads, reason {e}");
+                    }
+                }
+                Command::Optimize => {
+                    if let Err(e) = self.optimize().await {
+                        error!("failed to optimize archives, reason {e}");
                     }
                 }
             }
@@ -307,13 +1313,43 @@ pub fn channel() -> (DownloadSender, DownloadReceiver) {
     tokio::sync::mpsc::unbounded_channel::<Command>()
 }
 
-pub fn start<C, D, M, P>(
+/// Send `command` to the worker every `secs` seconds, never if 0
+fn send_every(tx: &DownloadSender, secs: u64, command: fn() -> Command) {
+    if secs == 0 {
+        return;
+    }
+
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(secs));
+        // first tick completes immediately, leave startup to the queue
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if tx.send(command()).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn start<C, D, M, S, P>(
     dir: P,
     chapter_repo: C,
     manga_repo: M,
     download_repo: D,
+    source_repo: S,
     ext: ExtensionManager,
     notifier: Notification<UserRepositoryImpl>,
+    layout: PathTemplate,
+    format: DownloadFormatConfig,
+    storage: Storage,
+    concurrency: DownloadConcurrencyConfig,
+    bandwidth: &DownloadBandwidthConfig,
+    verify_interval: u64,
+    compact_interval: u64,
+    optimize: Option<ArchiveOptimizeConfig>,
     download_sender: DownloadSender,
     download_receiver: DownloadReceiver,
 ) -> JoinHandle<()>
@@ -321,15 +1357,29 @@ where
     C: ChapterRepository + 'static,
     D: DownloadRepository + 'static,
     M: MangaRepository + 'static,
+    S: SourceRepository + 'static,
     P: AsRef<Path>,
 {
+    send_every(&download_sender, verify_interval, || Command::Verify);
+    send_every(&download_sender, compact_interval, || Command::Compact);
+    if let Some(optimize) = &optimize {
+        send_every(&download_sender, optimize.interval, || Command::Optimize);
+    }
+
     let download_worker = DownloadWorker::new(
         dir,
         chapter_repo,
         manga_repo,
         download_repo,
+        source_repo,
         ext,
         notifier,
+        layout,
+        format,
+        storage,
+        concurrency,
+        bandwidth,
+        optimize,
         download_sender,
         download_receiver,
     );
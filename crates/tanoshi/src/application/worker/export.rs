@@ -0,0 +1,158 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use chrono::{NaiveDateTime, Utc};
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+use crate::domain::services::export::ExportError;
+
+/// how long a finished export is kept for its user to download
+const EXPORT_TTL_SECS: i64 = 3600;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum ExportStatus {
+    Running,
+    Completed { filename: String },
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportJob {
+    pub user_id: i64,
+    pub status: ExportStatus,
+    pub created_at: NaiveDateTime,
+    path: PathBuf,
+}
+
+impl ExportJob {
+    fn is_expired(&self, now: NaiveDateTime) -> bool {
+        (now - self.created_at).num_seconds() >= EXPORT_TTL_SECS
+    }
+}
+
+/// Exports too large to build within a request, written to `cache_path/exports`
+/// in the background while the client polls for them
+#[derive(Clone)]
+pub struct ExportJobs {
+    dir: PathBuf,
+    next_id: Arc<AtomicU64>,
+    jobs: Arc<RwLock<HashMap<u64, ExportJob>>>,
+}
+
+impl ExportJobs {
+    pub fn new<P: AsRef<Path>>(cache_path: P) -> Self {
+        Self {
+            dir: cache_path.as_ref().join("exports"),
+            next_id: Arc::new(AtomicU64::new(1)),
+            jobs: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    async fn prune(&self) {
+        let now = Utc::now().naive_utc();
+        let mut jobs = self.jobs.write().await;
+        let expired: Vec<u64> = jobs
+            .iter()
+            .filter(|(_, job)| job.is_expired(now) && !matches!(job.status, ExportStatus::Running))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in expired {
+            if let Some(job) = jobs.remove(&id) {
+                let _ = tokio::fs::remove_file(&job.path).await;
+            }
+        }
+    }
+
+    /// Run `export` in the background with a file named `{id}.{extension}` to write to,
+    /// `export` returns the file name the export is downloaded as
+    pub async fn spawn<F, Fut>(
+        &self,
+        user_id: i64,
+        extension: &str,
+        export: F,
+    ) -> Result<u64, ExportError>
+    where
+        F: FnOnce(BufWriter<std::fs::File>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<String, ExportError>> + Send + 'static,
+    {
+        self.prune().await;
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let path = self.dir.join(format!("{id}.{extension}"));
+        let file = BufWriter::new(std::fs::File::create(&path)?);
+
+        self.jobs.write().await.insert(
+            id,
+            ExportJob {
+                user_id,
+                status: ExportStatus::Running,
+                created_at: Utc::now().naive_utc(),
+                path: path.clone(),
+            },
+        );
+
+        let jobs = self.jobs.clone();
+        tokio::spawn(async move {
+            let status = match export(file).await {
+                Ok(filename) => ExportStatus::Completed { filename },
+                Err(e) => {
+                    error!("export {id} failed: {e}");
+                    let _ = tokio::fs::remove_file(&path).await;
+                    ExportStatus::Failed {
+                        error: e.to_string(),
+                    }
+                }
+            };
+
+            if let Some(job) = jobs.write().await.get_mut(&id) {
+                job.status = status;
+            }
+        });
+
+        Ok(id)
+    }
+
+    /// Job `id` if it was started by `user_id`
+    pub async fn get(&self, id: u64, user_id: i64) -> Option<ExportJob> {
+        self.jobs
+            .read()
+            .await
+            .get(&id)
+            .filter(|job| job.user_id == user_id)
+            .cloned()
+    }
+
+    /// Path and file name of every completed job of `user_id`
+    pub async fn completed(&self, user_id: i64) -> Vec<(PathBuf, String)> {
+        self.jobs
+            .read()
+            .await
+            .values()
+            .filter(|job| job.user_id == user_id)
+            .filter_map(|job| match &job.status {
+                ExportStatus::Completed { filename } => Some((job.path.clone(), filename.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Path and file name of job `id` once it's completed
+    pub async fn file(&self, id: u64, user_id: i64) -> Option<(PathBuf, String)> {
+        let job = self.get(id, user_id).await?;
+        match job.status {
+            ExportStatus::Completed { filename } => Some((job.path, filename)),
+            _ => None,
+        }
+    }
+}
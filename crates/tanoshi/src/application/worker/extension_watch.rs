@@ -0,0 +1,99 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use tokio::{task::JoinHandle, time};
+
+use crate::domain::repositories::source::SourceRepository;
+
+const POLL_PERIOD: time::Duration = time::Duration::from_secs(2);
+
+struct ExtensionWatchWorker<S>
+where
+    S: SourceRepository + 'static,
+{
+    dir: PathBuf,
+    source_repo: S,
+}
+
+impl<S> ExtensionWatchWorker<S>
+where
+    S: SourceRepository + 'static,
+{
+    /// Modified time of every extension library in the directory, keyed by file stem
+    async fn scan(&self) -> Result<HashMap<String, SystemTime>, anyhow::Error> {
+        let mut files = HashMap::new();
+        let mut read_dir = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(tanoshi_vm::PLUGIN_EXTENSION) {
+                continue;
+            }
+            // uploads in progress are named with a leading dot
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) if !name.starts_with('.') => name.to_string(),
+                _ => continue,
+            };
+
+            files.insert(name, entry.metadata().await?.modified()?);
+        }
+
+        Ok(files)
+    }
+
+    async fn run(self) {
+        let mut loaded = match self.scan().await {
+            Ok(files) => files,
+            Err(e) => {
+                error!("failed to watch {}: {e}", self.dir.display());
+                return;
+            }
+        };
+        let mut previous = loaded.clone();
+
+        let mut interval = time::interval(POLL_PERIOD);
+        loop {
+            interval.tick().await;
+
+            let current = match self.scan().await {
+                Ok(files) => files,
+                Err(e) => {
+                    error!("failed to scan {}: {e}", self.dir.display());
+                    continue;
+                }
+            };
+
+            for (name, modified) in current.iter() {
+                // wait until a file stops changing for a whole period, so a build
+                // still writing it isn't loaded half way
+                if loaded.get(name) == Some(modified) || previous.get(name) != Some(modified) {
+                    continue;
+                }
+
+                match self.source_repo.reload_source_file(name).await {
+                    Ok(source_id) => info!("reloaded {name} as source {source_id}"),
+                    Err(e) => error!("failed to reload {name}: {e}"),
+                }
+                loaded.insert(name.clone(), *modified);
+            }
+
+            previous = current;
+        }
+    }
+}
+
+/// Reload extensions in `dir` whenever their library file changes
+pub fn start<P, S>(dir: P, source_repo: S) -> JoinHandle<()>
+where
+    P: AsRef<Path>,
+    S: SourceRepository + 'static,
+{
+    let worker = ExtensionWatchWorker {
+        dir: PathBuf::new().join(dir),
+        source_repo,
+    };
+
+    tokio::spawn(worker.run())
+}
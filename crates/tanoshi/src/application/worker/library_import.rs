@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use tanoshi_vm::extension::ExtensionManager;
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+};
+
+use crate::{
+    domain::{
+        entities::inbox::InboxKind,
+        repositories::{
+            chapter::ChapterRepository, history::HistoryRepository, library::LibraryRepository,
+            manga::MangaRepository,
+        },
+        services::{chapter::ChapterService, manga::MangaService},
+    },
+    infrastructure::{
+        domain::repositories::user::UserRepositoryImpl,
+        notification::Notification,
+        remote_library::{self, RemoteSeries, RemoteServer},
+    },
+};
+
+pub type ImportSender = UnboundedSender<ImportJob>;
+type ImportReceiver = UnboundedReceiver<ImportJob>;
+
+/// Import the library of an account on a Komga or Kavita server into a user's library
+pub struct ImportJob {
+    pub user_id: i64,
+    pub server: RemoteServer,
+    pub url: String,
+    pub username: String,
+    pub password: String,
+    /// installed source serving the same server, series are matched by title
+    pub source_id: i64,
+}
+
+#[derive(Debug, Default)]
+struct ImportSummary {
+    imported: usize,
+    unmatched: Vec<String>,
+}
+
+struct LibraryImportWorker<C, H, L, M>
+where
+    C: ChapterRepository + 'static,
+    H: HistoryRepository + 'static,
+    L: LibraryRepository + 'static,
+    M: MangaRepository + 'static,
+{
+    manga_svc: MangaService<M>,
+    chapter_svc: ChapterService<C>,
+    history_repo: H,
+    library_repo: L,
+    notifier: Notification<UserRepositoryImpl>,
+    rx: ImportReceiver,
+}
+
+impl<C, H, L, M> LibraryImportWorker<C, H, L, M>
+where
+    C: ChapterRepository + 'static,
+    H: HistoryRepository + 'static,
+    L: LibraryRepository + 'static,
+    M: MangaRepository + 'static,
+{
+    /// Category id by name, missing ones are created
+    async fn category_ids(
+        &self,
+        user_id: i64,
+        categories: &mut HashMap<String, i64>,
+        names: &[String],
+    ) -> Result<Vec<i64>, anyhow::Error> {
+        let mut ids = vec![];
+        for name in names {
+            let id = match categories.get(name) {
+                Some(id) => *id,
+                None => {
+                    let category = self
+                        .library_repo
+                        .create_category(user_id, None, name, None)
+                        .await?;
+                    let id = category
+                        .id
+                        .ok_or_else(|| anyhow::anyhow!("category {name} has no id"))?;
+                    categories.insert(name.clone(), id);
+                    id
+                }
+            };
+            ids.push(id);
+        }
+
+        Ok(ids)
+    }
+
+    /// Returns false if the source has no manga with the same title
+    async fn import_series(
+        &self,
+        job: &ImportJob,
+        categories: &mut HashMap<String, i64>,
+        series: &RemoteSeries,
+    ) -> Result<bool, anyhow::Error> {
+        let found = self
+            .manga_svc
+            .fetch_source_manga(job.source_id, 1, Some(series.title.clone()), None)
+            .await?
+            .into_iter()
+            .find(|manga| manga.title.trim().eq_ignore_ascii_case(series.title.trim()));
+        let manga = match found {
+            Some(manga) => {
+                self.manga_svc
+                    .fetch_manga_by_source_path(job.source_id, &manga.path)
+                    .await?
+            }
+            None => return Ok(false),
+        };
+
+        let category_ids = self
+            .category_ids(job.user_id, categories, &series.collections)
+            .await?;
+        let in_library = self
+            .library_repo
+            .get_users_by_manga_id(manga.id)
+            .await?
+            .iter()
+            .any(|user| user.id == job.user_id);
+        if in_library {
+            // collections are added to the categories the user already put the manga in
+            let mut merged = self
+                .library_repo
+                .get_category_ids_by_manga_id(job.user_id, manga.id)
+                .await?;
+            for category_id in category_ids {
+                if !merged.contains(&category_id) {
+                    merged.push(category_id);
+                }
+            }
+            self.library_repo
+                .set_manga_categories(job.user_id, manga.id, &merged)
+                .await?;
+        } else {
+            self.library_repo
+                .insert_manga_to_library(job.user_id, manga.id, &category_ids)
+                .await?;
+        }
+
+        let chapters = self
+            .chapter_svc
+            .fetch_chapters_by_manga_id(job.source_id, &manga.path, manga.id, false, None)
+            .await?;
+
+        let mut completed = vec![];
+        for remote in series.chapters.iter() {
+            let chapter = match chapters
+                .iter()
+                .find(|chapter| (chapter.number - remote.number).abs() < f64::EPSILON)
+            {
+                Some(chapter) => chapter,
+                None => continue,
+            };
+
+            if remote.completed {
+                completed.push(chapter.id);
+            } else {
+                self.history_repo
+                    .insert_history_chapter(job.user_id, chapter.id, remote.page, false)
+                    .await?;
+            }
+        }
+
+        if !completed.is_empty() {
+            self.history_repo
+                .insert_history_chapters_as_completed(job.user_id, &completed)
+                .await?;
+        }
+
+        Ok(true)
+    }
+
+    async fn import(&self, job: &ImportJob) -> Result<ImportSummary, anyhow::Error> {
+        let library =
+            remote_library::fetch_library(job.server, &job.url, &job.username, &job.password)
+                .await?;
+        info!(
+            "importing {} series from {:?} for user {}",
+            library.len(),
+            job.server,
+            job.user_id
+        );
+
+        let mut categories: HashMap<String, i64> = self
+            .library_repo
+            .get_categories_by_user_id(job.user_id)
+            .await?
+            .into_iter()
+            // collections are created as top level categories, nested ones may share their name
+            .filter(|category| category.parent_id.is_none())
+            .filter_map(|category| category.id.map(|id| (category.name, id)))
+            .collect();
+
+        let mut summary = ImportSummary::default();
+        for series in library.iter() {
+            match self.import_series(job, &mut categories, series).await {
+                Ok(true) => summary.imported += 1,
+                Ok(false) => summary.unmatched.push(series.title.clone()),
+                Err(e) => {
+                    error!("failed to import {}: {e}", series.title);
+                    summary.unmatched.push(series.title.clone());
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+
+    async fn run(mut self) {
+        while let Some(job) = self.rx.recv().await {
+            let message = match self.import(&job).await {
+                Ok(summary) if summary.unmatched.is_empty() => {
+                    format!("Imported {} series", summary.imported)
+                }
+                Ok(summary) => format!(
+                    "Imported {} series, not found in source: {}",
+                    summary.imported,
+                    summary.unmatched.join(", ")
+                ),
+                Err(e) => {
+                    error!("failed to import library from {}: {e}", job.url);
+                    format!("Failed to import library from {}: {e}", job.url)
+                }
+            };
+
+            if let Err(e) = self
+                .notifier
+                .send_all_to_user(
+                    job.user_id,
+                    InboxKind::Alert,
+                    Some("Library Import".to_string()),
+                    &message,
+                )
+                .await
+            {
+                error!("failed to send import result: {e}");
+            }
+        }
+    }
+}
+
+pub fn channel() -> (ImportSender, ImportReceiver) {
+    tokio::sync::mpsc::unbounded_channel::<ImportJob>()
+}
+
+pub fn start<C, H, L, M>(
+    chapter_repo: C,
+    history_repo: H,
+    library_repo: L,
+    manga_repo: M,
+    ext: ExtensionManager,
+    notifier: Notification<UserRepositoryImpl>,
+    rx: ImportReceiver,
+) -> JoinHandle<()>
+where
+    C: ChapterRepository + 'static,
+    H: HistoryRepository + 'static,
+    L: LibraryRepository + 'static,
+    M: MangaRepository + 'static,
+{
+    let worker = LibraryImportWorker {
+        manga_svc: MangaService::new(manga_repo, ext.clone()),
+        chapter_svc: ChapterService::new(chapter_repo, ext),
+        history_repo,
+        library_repo,
+        notifier,
+        rx,
+    };
+
+    tokio::spawn(worker.run())
+}
@@ -1,2 +1,10 @@
+pub mod automation;
+pub mod download_ahead;
+pub mod download_cleanup;
 pub mod downloads;
+pub mod export;
+pub mod extension_watch;
+pub mod library_import;
+pub mod prefetch;
+pub mod telemetry;
 pub mod updates;
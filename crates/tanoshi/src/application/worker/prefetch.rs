@@ -0,0 +1,215 @@
+use std::collections::{HashMap, HashSet};
+
+use futures::StreamExt;
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+};
+
+use crate::{
+    domain::{
+        repositories::{
+            chapter::ChapterRepository, image::ImageRepository, image_cache::ImageCacheRepository,
+            source::SourceRepository, user::UserRepository,
+        },
+        services::{
+            chapter::ChapterService,
+            image::{ImageService, ImageVariant, DATA_SAVER_QUALITY, DATA_SAVER_WIDTH},
+        },
+    },
+    infrastructure::{config::PrefetchConfig, storage},
+};
+
+pub type PrefetchSender = UnboundedSender<Command>;
+type PrefetchReceiver = UnboundedReceiver<Command>;
+
+/// chapters remembered before forgetting all of them, they're only looked up again
+const MAX_REMEMBERED: usize = 1024;
+
+#[derive(Debug)]
+pub enum Command {
+    /// A user is on page `page` of a chapter, counted from 0
+    PageRead {
+        user_id: i64,
+        chapter_id: i64,
+        page: i64,
+    },
+}
+
+struct PrefetchWorker<C, S, U, IC, IR>
+where
+    C: ChapterRepository + Clone + 'static,
+    S: SourceRepository + 'static,
+    U: UserRepository + 'static,
+    IC: ImageCacheRepository + Clone + Send + Sync + 'static,
+    IR: ImageRepository + Clone + 'static,
+{
+    config: Option<PrefetchConfig>,
+    secret: String,
+    chapter_svc: ChapterService<C>,
+    image_svc: ImageService<IC, IR>,
+    source_repo: S,
+    user_repo: U,
+    rx: PrefetchReceiver,
+    /// pages of chapters being read
+    page_counts: HashMap<i64, usize>,
+    /// chapters already fetched, and whether it was for a user saving data
+    prefetched: HashSet<(i64, bool)>,
+}
+
+impl<C, S, U, IC, IR> PrefetchWorker<C, S, U, IC, IR>
+where
+    C: ChapterRepository + Clone + 'static,
+    S: SourceRepository + 'static,
+    U: UserRepository + 'static,
+    IC: ImageCacheRepository + Clone + Send + Sync + 'static,
+    IR: ImageRepository + Clone + 'static,
+{
+    async fn page_read(
+        &mut self,
+        config: &PrefetchConfig,
+        user_id: i64,
+        chapter_id: i64,
+        page: i64,
+    ) -> Result<(), anyhow::Error> {
+        let chapter = self.chapter_svc.fetch_chapter_by_id(chapter_id).await?;
+        let next_id = match chapter.next {
+            Some(next_id) => next_id,
+            None => return Ok(()),
+        };
+        let data_saver = self.user_repo.get_user_by_id(user_id).await?.data_saver;
+        if self.prefetched.contains(&(next_id, data_saver)) {
+            return Ok(());
+        }
+
+        let session = self
+            .source_repo
+            .get_credential(user_id, chapter.source_id)
+            .await?
+            .map(|credential| credential.session);
+        let chapter_svc = self.chapter_svc.with_session(session);
+
+        let page_count = match self.page_counts.get(&chapter_id) {
+            Some(page_count) => *page_count,
+            None => {
+                let page_count = chapter_svc.fetch_chapter_pages(&chapter).await?.len();
+                if self.page_counts.len() >= MAX_REMEMBERED {
+                    self.page_counts.clear();
+                }
+                self.page_counts.insert(chapter_id, page_count);
+                page_count
+            }
+        };
+        if ((page + 1) as f64) < page_count as f64 * config.threshold {
+            return Ok(());
+        }
+
+        if self.prefetched.len() >= MAX_REMEMBERED {
+            self.prefetched.clear();
+        }
+        self.prefetched.insert((next_id, data_saver));
+
+        let next = self.chapter_svc.fetch_chapter_by_id(next_id).await?;
+        // pages on disk are read fast enough already
+        if matches!(next.downloaded_path.as_ref(), Some(path) if !storage::is_remote(path)) {
+            return Ok(());
+        }
+
+        let pages = chapter_svc.fetch_chapter_pages(&next).await?;
+        let variant = if data_saver {
+            ImageVariant {
+                width: Some(DATA_SAVER_WIDTH),
+                quality: Some(DATA_SAVER_QUALITY),
+                ..Default::default()
+            }
+        } else {
+            ImageVariant::default()
+        };
+        debug!("prefetching {} pages of chapter {next_id}", pages.len());
+
+        let image_svc = self.image_svc.clone();
+        let secret = self.secret.clone();
+        let concurrency = config.concurrency.max(1);
+        tokio::spawn(async move {
+            futures::stream::iter(pages)
+                .for_each_concurrent(concurrency, |page| {
+                    let image_svc = &image_svc;
+                    let secret = &secret;
+                    async move {
+                        let encrypted_url = match image_svc.encrypt_image_url(secret, &page) {
+                            Ok(encrypted_url) => encrypted_url,
+                            Err(e) => {
+                                debug!("failed to prefetch {page}: {e}");
+                                return;
+                            }
+                        };
+                        if let Err(e) = image_svc
+                            .fetch_image_variant(secret, &encrypted_url, None, variant)
+                            .await
+                        {
+                            debug!("failed to prefetch {page}: {e}");
+                        }
+                    }
+                })
+                .await;
+        });
+
+        Ok(())
+    }
+
+    async fn run(mut self) {
+        while let Some(cmd) = self.rx.recv().await {
+            let config = match self.config.clone() {
+                Some(config) => config,
+                None => continue,
+            };
+
+            match cmd {
+                Command::PageRead {
+                    user_id,
+                    chapter_id,
+                    page,
+                } => {
+                    if let Err(e) = self.page_read(&config, user_id, chapter_id, page).await {
+                        error!("failed to prefetch after chapter {chapter_id}: {e}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn channel() -> (PrefetchSender, PrefetchReceiver) {
+    tokio::sync::mpsc::unbounded_channel::<Command>()
+}
+
+pub fn start<C, S, U, IC, IR>(
+    config: Option<PrefetchConfig>,
+    secret: &str,
+    chapter_svc: ChapterService<C>,
+    image_svc: ImageService<IC, IR>,
+    source_repo: S,
+    user_repo: U,
+    rx: PrefetchReceiver,
+) -> JoinHandle<()>
+where
+    C: ChapterRepository + Clone + 'static,
+    S: SourceRepository + 'static,
+    U: UserRepository + 'static,
+    IC: ImageCacheRepository + Clone + Send + Sync + 'static,
+    IR: ImageRepository + Clone + 'static,
+{
+    let worker = PrefetchWorker {
+        config,
+        secret: secret.to_string(),
+        chapter_svc,
+        image_svc,
+        source_repo,
+        user_repo,
+        rx,
+        page_counts: HashMap::new(),
+        prefetched: HashSet::new(),
+    };
+
+    tokio::spawn(worker.run())
+}
@@ -0,0 +1,76 @@
+use tanoshi_vm::extension::ExtensionManager;
+use tokio::{task::JoinHandle, time};
+
+use crate::{
+    domain::{
+        repositories::{library::LibraryRepository, setting::SettingRepository},
+        services::setting::SettingService,
+    },
+    infrastructure::{config::Config, telemetry},
+};
+
+struct TelemetryWorker<L, S>
+where
+    L: LibraryRepository + 'static,
+    S: SettingRepository + 'static,
+{
+    config: Config,
+    library_repo: L,
+    setting_svc: SettingService<S>,
+    extensions: ExtensionManager,
+}
+
+impl<L, S> TelemetryWorker<L, S>
+where
+    L: LibraryRepository + 'static,
+    S: SettingRepository + 'static,
+{
+    async fn report(&self, url: &str) -> Result<(), anyhow::Error> {
+        if !self.setting_svc.get_telemetry_enabled().await? {
+            debug!("telemetry is not enabled");
+            return Ok(());
+        }
+
+        let installed_sources = self.extensions.list().await?.len();
+        let library_size = self.library_repo.get_library_manga_count().await?;
+        let payload = telemetry::collect(&self.config, installed_sources, library_size);
+
+        telemetry::send(url, &payload).await
+    }
+
+    async fn run(self) {
+        let url = match self.config.telemetry_url.clone() {
+            Some(url) => url,
+            None => return,
+        };
+
+        let mut interval = time::interval(time::Duration::from_secs(86400));
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.report(&url).await {
+                error!("failed to send telemetry: {e}");
+            }
+        }
+    }
+}
+
+pub fn start<L, S>(
+    config: Config,
+    library_repo: L,
+    setting_svc: SettingService<S>,
+    extensions: ExtensionManager,
+) -> JoinHandle<()>
+where
+    L: LibraryRepository + 'static,
+    S: SettingRepository + 'static,
+{
+    let worker = TelemetryWorker {
+        config,
+        library_repo,
+        setting_svc,
+        extensions,
+    };
+
+    tokio::spawn(worker.run())
+}
@@ -1,10 +1,11 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
     str::FromStr,
+    sync::Mutex,
 };
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use futures::StreamExt;
 use rayon::prelude::*;
 use serde::Deserialize;
@@ -15,17 +16,35 @@ use tanoshi_vm::extension::ExtensionManager;
 use crate::{
     application::worker::downloads::Command as DownloadCommand,
     domain::{
-        entities::chapter::Chapter,
-        repositories::{chapter::ChapterRepository, library::LibraryRepository},
+        entities::{chapter::Chapter, library::AutoDownload, source::SourceHealth},
+        repositories::{
+            chapter::ChapterRepository, library::LibraryRepository, source::SourceRepository,
+        },
+        services::{
+            chapter::{reconcile_chapters, superseded_chapter_ids},
+            image::ImageService,
+        },
+    },
+    infrastructure::{
+        domain::repositories::{
+            image::ImageRepositoryImpl, image_cache::ImageCacheRepositoryImpl,
+            user::UserRepositoryImpl,
+        },
+        notification::{summarize_error, wants_chapter_notification, AlertThrottle, Notification},
+        repository_index::RepositoryIndex,
     },
-    infrastructure::{domain::repositories::user::UserRepositoryImpl, notification::Notification},
 };
 use tokio::{
     task::JoinHandle,
     time::{self, Instant},
 };
 
-use super::downloads::DownloadSender;
+use super::{
+    automation::{AutomationSender, Event as AutomationEvent},
+    downloads::DownloadSender,
+};
+
+const SOURCE_ALERT_COOLDOWN: time::Duration = time::Duration::from_secs(6 * 3600);
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct SourceInfo {
@@ -37,37 +56,47 @@ pub struct SourceInfo {
     pub nsfw: bool,
 }
 
-struct UpdatesWorker<C, L>
+struct UpdatesWorker<C, L, S>
 where
     C: ChapterRepository + 'static,
     L: LibraryRepository + 'static,
+    S: SourceRepository + 'static,
 {
     period: u64,
-    client: reqwest::Client,
+    repository_index: RepositoryIndex,
     library_repo: L,
     chapter_repo: C,
+    source_repo: S,
     extensions: ExtensionManager,
     auto_download_chapters: bool,
     download_tx: DownloadSender,
+    automation_tx: AutomationSender,
     notifier: Notification<UserRepositoryImpl>,
+    image_svc: ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>,
     extension_repository: String,
     cache_path: PathBuf,
+    source_alerts: Mutex<AlertThrottle>,
 }
 
-impl<C, L> UpdatesWorker<C, L>
+impl<C, L, S> UpdatesWorker<C, L, S>
 where
     C: ChapterRepository + 'static,
     L: LibraryRepository + 'static,
+    S: SourceRepository + 'static,
 {
     fn new<P: AsRef<Path>>(
         period: u64,
         library_repo: L,
         chapter_repo: C,
+        source_repo: S,
         extensions: ExtensionManager,
         download_tx: DownloadSender,
+        automation_tx: AutomationSender,
         auto_download_chapters: bool,
         notifier: Notification<UserRepositoryImpl>,
+        image_svc: ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>,
         extension_repository: String,
+        repository_index: RepositoryIndex,
         cache_path: P,
     ) -> Self {
         #[cfg(not(debug_assertions))]
@@ -80,15 +109,19 @@ where
 
         Self {
             period,
-            client: reqwest::Client::new(),
+            repository_index,
             library_repo,
             chapter_repo,
+            source_repo,
             extensions,
             auto_download_chapters,
             download_tx,
+            automation_tx,
             notifier,
+            image_svc,
             extension_repository,
             cache_path: PathBuf::new().join(cache_path),
+            source_alerts: Mutex::new(AlertThrottle::new(SOURCE_ALERT_COOLDOWN)),
         }
     }
 
@@ -110,48 +143,80 @@ where
             });
         });
 
+        let mut health: HashMap<i64, SourceHealth> = self
+            .source_repo
+            .get_all_source_health()
+            .await?
+            .into_iter()
+            .map(|health| (health.source_id, health))
+            .collect();
+        let now = Utc::now().naive_utc();
+        let mut skipped_sources: HashSet<i64> = health
+            .values()
+            .filter(|health| health.is_disabled() || health.is_cooling_down(now))
+            .map(|health| health.source_id)
+            .collect();
+
         while let Some(Ok(manga)) = rx.recv().await {
+            if skipped_sources.contains(&manga.source_id) {
+                debug!("Skipping updates: {}, source is failing", manga.title);
+                continue;
+            }
+
             debug!("Checking updates: {}", manga.title);
 
+            let session = match self
+                .source_repo
+                .get_library_session(manga.source_id, manga.id)
+                .await
+            {
+                Ok(session) => session,
+                Err(e) => {
+                    error!("failed to get session for {}: {e}", manga.title);
+                    None
+                }
+            };
             let chapters: Vec<Chapter> = match self
                 .extensions
+                .with_session(session)
                 .get_chapters(manga.source_id, manga.path.clone())
                 .await
             {
-                Ok(chapters) => chapters
-                    .into_par_iter()
-                    .map(|ch| {
-                        let mut c: Chapter = ch.into();
-                        c.manga_id = manga.id;
-                        c
-                    })
-                    .collect(),
+                Ok(chapters) => {
+                    self.record_source_result(&mut health, manga.source_id, None)
+                        .await;
+
+                    chapters
+                        .into_par_iter()
+                        .map(|ch| {
+                            let mut c: Chapter = ch.into();
+                            c.manga_id = manga.id;
+                            c
+                        })
+                        .collect()
+                }
                 Err(e) => {
                     error!("error fetch new chapters, reason: {}", e);
+                    if self
+                        .record_source_result(&mut health, manga.source_id, Some(&e))
+                        .await
+                    {
+                        skipped_sources.insert(manga.source_id);
+                    }
+                    self.notify_source_failure(manga.source_id, &manga.title, &e)
+                        .await;
                     continue;
                 }
             };
 
-            self.chapter_repo.insert_chapters(&chapters).await?;
-
-            let chapter_paths: Vec<String> = chapters.into_par_iter().map(|c| c.path).collect();
-
-            if !chapter_paths.is_empty() {
-                let chapters_to_delete: Vec<i64> = self
-                    .chapter_repo
-                    .get_chapters_not_in_source(manga.source_id, manga.id, &chapter_paths)
-                    .await?
-                    .iter()
-                    .map(|c| c.id)
-                    .collect();
-
-                if !chapters_to_delete.is_empty() {
-                    self.chapter_repo
-                        .delete_chapter_by_ids(&chapters_to_delete)
-                        .await?;
-                }
+            if let Err(e) = self.image_svc.store_cover(manga.id, &manga.cover_url).await {
+                error!("failed to store cover of {}: {e}", manga.title);
             }
 
+            let reconciliation =
+                reconcile_chapters(&self.chapter_repo, manga.source_id, manga.id, &chapters)
+                    .await?;
+
             let last_uploaded_chapter = manga
                 .last_uploaded_at
                 .unwrap_or_else(|| NaiveDateTime::from_timestamp(0, 0));
@@ -162,6 +227,13 @@ where
                 .await?
                 .into_par_iter()
                 .filter(|chapter| chapter.uploaded > last_uploaded_chapter)
+                .filter(|chapter| {
+                    // chapters that only changed path upstream are not new to the user
+                    !reconciliation
+                        .migrated
+                        .iter()
+                        .any(|(_, to)| *to == chapter.id)
+                })
                 .collect();
 
             if chapters.is_empty() {
@@ -170,10 +242,17 @@ where
                 info!("{} has {} new chapters", manga.title, chapters.len());
             }
 
+            let source_name = self
+                .extensions
+                .get_source_info(manga.source_id)
+                .map(|source| source.name)
+                .unwrap_or_default();
+
+            let auto_download_ids = self.auto_download_chapter_ids(manga.id, &chapters).await;
+
             for chapter in chapters {
                 #[cfg(feature = "desktop")]
-                self.notifier
-                    .send_desktop_notification(Some(manga.title.clone()), &chapter.title)?;
+                let mut desktop_notified = false;
 
                 let users = self
                     .library_repo
@@ -182,9 +261,62 @@ where
                     .unwrap_or_default();
 
                 for user in users {
+                    // private categories are never unlocked outside a session,
+                    // their manga don't run automation rules either
+                    if self
+                        .library_repo
+                        .get_hidden_manga_ids(user.id, &[])
+                        .await
+                        .unwrap_or_default()
+                        .contains(&manga.id)
+                    {
+                        continue;
+                    }
+
+                    // one notification for the machine, whichever user sees the manga
+                    #[cfg(feature = "desktop")]
+                    if !desktop_notified {
+                        self.notifier.send_chapter_desktop_notification(
+                            &source_name,
+                            &manga.title,
+                            &chapter.title,
+                            chapter.id,
+                        )?;
+                        desktop_notified = true;
+                    }
+
+                    let _ = self.automation_tx.send(AutomationEvent::NewChapter {
+                        user_id: user.id,
+                        manga_id: manga.id,
+                        chapter_id: chapter.id,
+                    });
+
+                    let category_ids = self
+                        .library_repo
+                        .get_category_ids_by_manga_id(user.id, manga.id)
+                        .await
+                        .unwrap_or_default();
+
+                    if user.notification_digest_interval.is_some() {
+                        if !wants_chapter_notification(&user, &category_ids) {
+                            continue;
+                        }
+
+                        if let Err(e) = self
+                            .notifier
+                            .queue_chapter_digest(user.id, chapter.id)
+                            .await
+                        {
+                            error!("failed to queue chapter digest for user {}: {e}", user.id);
+                        }
+                        continue;
+                    }
+
                     self.notifier
                         .send_chapter_notification(
                             user.id,
+                            &category_ids,
+                            &source_name,
                             &manga.title,
                             &chapter.title,
                             chapter.id,
@@ -192,7 +324,7 @@ where
                         .await?;
                 }
 
-                if self.auto_download_chapters {
+                if auto_download_ids.contains(&chapter.id) {
                     info!("add chapter to download queue");
                     self.download_tx
                         .send(DownloadCommand::InsertIntoQueueBySourcePath(
@@ -209,15 +341,155 @@ where
         Ok(())
     }
 
-    async fn check_extension_update(&self) -> Result<(), anyhow::Error> {
-        let url = format!("{}/index.json", self.extension_repository);
+    /// New chapters of a manga to queue for download, the most recent ones first
+    /// when the library entries or their categories limit how many. Releases of other
+    /// groups are left out when a group preferred by a user released the same number
+    async fn auto_download_chapter_ids(&self, manga_id: i64, chapters: &[Chapter]) -> HashSet<i64> {
+        let auto_download = if self.auto_download_chapters {
+            Some(AutoDownload {
+                enabled: true,
+                limit: None,
+            })
+        } else {
+            match self
+                .library_repo
+                .get_auto_download_settings_by_manga_id(manga_id)
+                .await
+            {
+                Ok(settings) => AutoDownload::merge(&settings),
+                Err(e) => {
+                    error!("failed to get auto download settings of manga {manga_id}: {e}");
+                    None
+                }
+            }
+        };
+
+        let limit = match auto_download {
+            Some(auto_download) => auto_download.limit.map(|limit| limit as usize),
+            None => return HashSet::new(),
+        };
+
+        let superseded = match self
+            .library_repo
+            .get_preferred_groups_by_manga_id(manga_id)
+            .await
+        {
+            Ok(groups) if !groups.is_empty() => self
+                .chapter_repo
+                .get_chapters_by_manga_id(manga_id, None, None, false)
+                .await
+                .map(|all_chapters| superseded_chapter_ids(&all_chapters, &groups))
+                .unwrap_or_default(),
+            Ok(_) => HashSet::new(),
+            Err(e) => {
+                error!("failed to get preferred groups of manga {manga_id}: {e}");
+                HashSet::new()
+            }
+        };
+
+        let mut newest: Vec<&Chapter> = chapters
+            .iter()
+            .filter(|chapter| !superseded.contains(&chapter.id))
+            .collect();
+        newest.sort_by(|a, b| {
+            b.sort_key
+                .partial_cmp(&a.sort_key)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| b.uploaded.cmp(&a.uploaded))
+        });
+
+        newest
+            .into_iter()
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|chapter| chapter.id)
+            .collect()
+    }
+
+    /// Returns true when the source should be skipped for the rest of the run,
+    /// either the circuit breaker tripped or the source got disabled
+    async fn record_source_result(
+        &self,
+        health: &mut HashMap<i64, SourceHealth>,
+        source_id: i64,
+        error: Option<&anyhow::Error>,
+    ) -> bool {
+        let now = Utc::now().naive_utc();
+        let source_health = health
+            .entry(source_id)
+            .or_insert_with(|| SourceHealth::new(source_id));
+        match error {
+            Some(e) => source_health.record_failure(now, &summarize_error(e)),
+            None => source_health.record_success(now),
+        }
+
+        let disable = source_health.should_disable(now);
+        if disable {
+            source_health.disabled_at = Some(now);
+        }
+
+        if let Err(e) = self.source_repo.save_source_health(source_health).await {
+            error!("failed to save health of source {source_id}: {e}");
+        }
+
+        if disable {
+            self.notify_source_disabled(source_health).await;
+        }
 
+        source_health.is_disabled() || source_health.is_circuit_open()
+    }
+
+    async fn notify_source_disabled(&self, health: &SourceHealth) {
+        let source_name = self
+            .extensions
+            .get_source_info(health.source_id)
+            .map(|source| source.name)
+            .unwrap_or_else(|_| health.source_id.to_string());
+        let message = format!(
+            "{source_name} disabled after {} failures in a row, re-enable it in source settings\n{}",
+            health.consecutive_failures,
+            health.last_error.as_deref().unwrap_or_default()
+        );
+        if let Err(e) = self
+            .notifier
+            .send_all_to_admins(Some("Source Disabled".to_string()), &message)
+            .await
+        {
+            error!("failed to send source disabled to admin, {e}");
+        }
+    }
+
+    async fn notify_source_failure(&self, source_id: i64, manga_title: &str, e: &anyhow::Error) {
+        if !self
+            .source_alerts
+            .lock()
+            .unwrap()
+            .allow(&source_id.to_string())
+        {
+            return;
+        }
+
+        let source_name = self
+            .extensions
+            .get_source_info(source_id)
+            .map(|source| source.name)
+            .unwrap_or_else(|_| source_id.to_string());
+        let message = format!(
+            "{source_name} failed to refresh {manga_title}\n{}",
+            summarize_error(e)
+        );
+        if let Err(e) = self
+            .notifier
+            .send_all_to_admins(Some("Update Failed".to_string()), &message)
+            .await
+        {
+            error!("failed to send update failure to admin, {e}");
+        }
+    }
+
+    async fn check_extension_update(&self) -> Result<(), anyhow::Error> {
         let available_sources_map = self
-            .client
-            .get(&url)
-            .send()
-            .await?
-            .json::<Vec<SourceInfo>>()
+            .repository_index
+            .get::<Vec<SourceInfo>>(&self.extension_repository)
             .await?
             .into_par_iter()
             .map(|source| (source.id, source))
@@ -295,6 +567,10 @@ where
     async fn clear_cache(&self) -> Result<(), anyhow::Error> {
         let mut read_dir = tokio::fs::read_dir(&self.cache_path).await?;
         while let Ok(Some(entry)) = read_dir.next_entry().await {
+            // source responses spilled to disk expire on their own
+            if entry.file_type().await?.is_dir() {
+                continue;
+            }
             if let Some(created) = entry
                 .metadata()
                 .await?
@@ -323,6 +599,7 @@ where
         let mut chapter_update_interval = time::interval(time::Duration::from_secs(period));
         let mut server_update_interval = time::interval(time::Duration::from_secs(86400));
         let mut clear_cache_interval = time::interval(time::Duration::from_secs(3 * 86400));
+        let mut digest_interval = time::interval(time::Duration::from_secs(60));
 
         loop {
             tokio::select! {
@@ -352,6 +629,11 @@ where
                         error!("failed check extension update: {e}")
                     }
                 }
+                _ = digest_interval.tick() => {
+                    if let Err(e) = self.notifier.send_due_chapter_digests().await {
+                        error!("failed to send chapter digests: {e}")
+                    }
+                }
                 _ = clear_cache_interval.tick() => {
                     if let Err(e) = self.clear_cache().await {
                         error!("failed clear cache: {e}")
@@ -362,31 +644,40 @@ where
     }
 }
 
-pub fn start<C, L, P>(
+pub fn start<C, L, S, P>(
     period: u64,
     library_repo: L,
     chapter_repo: C,
+    source_repo: S,
     extensions: ExtensionManager,
     download_tx: DownloadSender,
+    automation_tx: AutomationSender,
     auto_download_chapters: bool,
     notifier: Notification<UserRepositoryImpl>,
+    image_svc: ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>,
     extension_repository: String,
+    repository_index: RepositoryIndex,
     cache_path: P,
 ) -> JoinHandle<()>
 where
     C: ChapterRepository + 'static,
     L: LibraryRepository + 'static,
+    S: SourceRepository + 'static,
     P: AsRef<Path>,
 {
     let worker = UpdatesWorker::new(
         period,
         library_repo,
         chapter_repo,
+        source_repo,
         extensions,
         download_tx,
+        automation_tx,
         auto_download_chapters,
         notifier,
+        image_svc,
         extension_repository,
+        repository_index,
         cache_path,
     );
 
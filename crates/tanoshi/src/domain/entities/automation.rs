@@ -0,0 +1,73 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutomationEvent {
+    NewChapter,
+    AddedToLibrary,
+    ChapterRead,
+}
+
+impl Display for AutomationEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AutomationEvent::NewChapter => write!(f, "new_chapter"),
+            AutomationEvent::AddedToLibrary => write!(f, "added_to_library"),
+            AutomationEvent::ChapterRead => write!(f, "chapter_read"),
+        }
+    }
+}
+
+impl FromStr for AutomationEvent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "new_chapter" => Ok(AutomationEvent::NewChapter),
+            "added_to_library" => Ok(AutomationEvent::AddedToLibrary),
+            "chapter_read" => Ok(AutomationEvent::ChapterRead),
+            _ => Err(anyhow::anyhow!("unknown automation event {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyChannel {
+    Telegram,
+    Pushover,
+    Gotify,
+}
+
+/// Stored as json in `automation_rule.action`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AutomationAction {
+    /// Queue the chapter, or every chapter of the manga for `AddedToLibrary`,
+    /// or the next chapter for `ChapterRead`
+    Download,
+    /// Replace manga categories, `None` moves it to default category
+    MoveToCategory {
+        category_id: Option<i64>,
+    },
+    Notify {
+        channel: NotifyChannel,
+    },
+    Webhook {
+        url: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct AutomationRule {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub event: AutomationEvent,
+    pub action: AutomationAction,
+    pub enabled: bool,
+    pub created_at: NaiveDateTime,
+}
@@ -1,4 +1,41 @@
 use chrono::{NaiveDateTime, Utc};
+use fancy_regex::Regex;
+use once_cell::sync::Lazy;
+
+/// "Ch. 10.5", "Vol.2 Chapter 3" or "Episode 4", the volume is never picked up
+static CHAPTER_NUMBER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(?:\bch(?:apter|ap)?|\bep(?:isode)?)\.?\s*(\d+(?:[.,]\d+)?)").unwrap()
+});
+/// "#12", only used when there's no chapter number, "The #1 Hero Ch. 3" is chapter 3
+static HASH_NUMBER: Lazy<Regex> = Lazy::new(|| Regex::new(r"#\s*(\d+(?:[.,]\d+)?)").unwrap());
+/// title that is only a number, e.g. "12"
+static BARE_NUMBER: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*(\d+(?:[.,]\d+)?)\s*$").unwrap());
+static EXTRA: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\b(?:extras?|omake|side\s*stor(?:y|ies)|specials?|bonus|one[\s-]?shot)\b")
+        .unwrap()
+});
+
+/// Sort keys are parsed from each source's own titles, two chapters are the same one when
+/// their keys are this close. Below the gap between extras, see `normalize_chapters`
+pub const SORT_KEY_TOLERANCE: f64 = 0.0005;
+
+/// Chapter number written in `title`
+pub fn parse_chapter_number(title: &str) -> Option<f64> {
+    [&CHAPTER_NUMBER, &HASH_NUMBER, &BARE_NUMBER]
+        .iter()
+        .find_map(|re| {
+            re.captures(title)
+                .ok()
+                .flatten()
+                .and_then(|caps| caps.get(1))
+                .and_then(|number| number.as_str().replace(',', ".").parse().ok())
+        })
+}
+
+/// Extras, omake, side stories and oneshots, numbered or not
+pub fn is_extra_title(title: &str) -> bool {
+    EXTRA.is_match(title).unwrap_or(false)
+}
 
 #[derive(Debug, Clone)]
 pub struct Chapter {
@@ -8,7 +45,12 @@ pub struct Chapter {
     pub title: String,
     pub path: String,
     pub number: f64,
+    /// normalized chapter number chapters are ordered by, see `normalize_chapters`
+    pub sort_key: f64,
     pub scanlator: String,
+    /// scanlation groups, `scanlator` as a single group for sources not reporting them
+    pub groups: Vec<String>,
+    pub language: Option<String>,
     pub uploaded: NaiveDateTime,
     pub date_added: NaiveDateTime,
     pub downloaded_path: Option<String>,
@@ -16,8 +58,32 @@ pub struct Chapter {
     pub prev: Option<i64>,
 }
 
+impl Chapter {
+    pub fn has_group(&self, group: &str) -> bool {
+        self.groups.iter().any(|g| g.eq_ignore_ascii_case(group))
+    }
+
+    /// Chapters without a language are in the source language and always match
+    pub fn is_language(&self, language: &str) -> bool {
+        self.language
+            .as_deref()
+            .map(|lang| lang.eq_ignore_ascii_case(language))
+            .unwrap_or(true)
+    }
+}
+
 impl From<tanoshi_lib::models::ChapterInfo> for Chapter {
     fn from(ch: tanoshi_lib::models::ChapterInfo) -> Self {
+        let groups = if ch.groups.is_empty() {
+            ch.scanlator
+                .iter()
+                .map(|scanlator| scanlator.trim().to_string())
+                .filter(|scanlator| !scanlator.is_empty())
+                .collect()
+        } else {
+            ch.groups
+        };
+
         Self {
             id: 0,
             source_id: ch.source_id,
@@ -25,7 +91,10 @@ impl From<tanoshi_lib::models::ChapterInfo> for Chapter {
             title: ch.title,
             path: ch.path,
             number: ch.number,
+            sort_key: ch.number,
             scanlator: ch.scanlator.unwrap_or_default(),
+            groups,
+            language: ch.language.map(|language| language.to_lowercase()),
             uploaded: NaiveDateTime::from_timestamp(ch.uploaded, 0),
             date_added: Utc::now().naive_utc(),
             downloaded_path: None,
@@ -34,3 +103,35 @@ impl From<tanoshi_lib::models::ChapterInfo> for Chapter {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_chapter_number() {
+        assert_eq!(parse_chapter_number("Ch. 10.5"), Some(10.5));
+        assert_eq!(parse_chapter_number("Vol.2 Chapter 3"), Some(3.0));
+        assert_eq!(parse_chapter_number("Vol.2 Ch.3: The Return"), Some(3.0));
+        assert_eq!(parse_chapter_number("Episode 4"), Some(4.0));
+        assert_eq!(parse_chapter_number("chapter 7,5"), Some(7.5));
+        assert_eq!(parse_chapter_number("#12"), Some(12.0));
+        assert_eq!(parse_chapter_number("The #1 Hero Ch. 3"), Some(3.0));
+        assert_eq!(parse_chapter_number(" 12 "), Some(12.0));
+        assert_eq!(parse_chapter_number("Oneshot"), None);
+        assert_eq!(parse_chapter_number("Volume 2"), None);
+    }
+
+    #[test]
+    fn test_is_extra_title() {
+        assert!(is_extra_title("Extra"));
+        assert!(is_extra_title("Vol.3 Extras"));
+        assert!(is_extra_title("Omake 2"));
+        assert!(is_extra_title("Side Story: Summer"));
+        assert!(is_extra_title("Oneshot"));
+        assert!(is_extra_title("One-shot"));
+        assert!(is_extra_title("Special Chapter"));
+        assert!(!is_extra_title("Ch. 10.5"));
+        assert!(!is_extra_title("Extraordinary Ch. 4"));
+    }
+}
@@ -1,4 +1,5 @@
 use chrono::NaiveDateTime;
+use serde::Serialize;
 
 #[derive(Debug, Clone)]
 pub struct DownloadQueue {
@@ -9,10 +10,15 @@ pub struct DownloadQueue {
     pub manga_title: String,
     pub chapter_id: i64,
     pub chapter_title: String,
+    pub chapter_number: f64,
+    /// first category of the manga by name, only read back from the queue
+    pub category: Option<String>,
     pub rank: i64,
     pub url: String,
     pub priority: i64,
     pub date_added: NaiveDateTime,
+    /// failed attempts at downloading this page
+    pub attempts: i64,
 }
 
 #[derive(Debug, Clone)]
@@ -26,6 +32,13 @@ pub struct DownloadQueueEntry {
     pub downloaded: i64,
     pub total: i64,
     pub priority: i64,
+    /// skipped by the worker until resumed
+    pub paused: bool,
+    /// gave up after too many attempts, skipped until retried
+    pub failed: bool,
+    /// most failed attempts of any page
+    pub attempts: i64,
+    pub last_error: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -41,3 +54,60 @@ pub struct DownloadedChapter {
     pub date_added: NaiveDateTime,
     pub downloaded_path: Option<String>,
 }
+
+/// Downloaded chapter archive, `read` when everyone with the manga in library completed it
+#[derive(Debug, Clone)]
+pub struct DownloadedChapterFile {
+    pub chapter_id: i64,
+    pub manga_id: i64,
+    pub manga_title: String,
+    pub chapter_title: String,
+    pub number: f64,
+    pub path: String,
+    pub read: bool,
+    /// pages the archive was completed with, unknown for older downloads
+    pub page_count: Option<i64>,
+    /// bytes recorded when the download completed
+    pub size: Option<i64>,
+}
+
+/// Disk space taken by the downloaded chapters of a manga
+#[derive(Debug, Clone, Serialize)]
+pub struct MangaDownloadUsage {
+    pub manga_id: i64,
+    pub manga_title: String,
+    pub chapters: i64,
+    /// in bytes
+    pub size: i64,
+}
+
+/// Which downloads cleanup removes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RetentionPolicy {
+    pub delete_after_read: bool,
+    /// counted from the highest chapter number down
+    pub keep_per_manga: Option<usize>,
+    /// in bytes
+    pub max_disk_usage: Option<u64>,
+}
+
+impl RetentionPolicy {
+    pub fn is_empty(&self) -> bool {
+        !self.delete_after_read && self.keep_per_manga.is_none() && self.max_disk_usage.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanupReason {
+    Read,
+    PerMangaLimit,
+    DiskQuota,
+}
+
+/// Download removed by cleanup and the policy removing it
+#[derive(Debug, Clone)]
+pub struct CleanupCandidate {
+    pub file: DownloadedChapterFile,
+    pub size: u64,
+    pub reason: CleanupReason,
+}
@@ -11,3 +11,43 @@ pub struct HistoryChapter {
     pub last_page_read: i64,
     pub is_complete: bool,
 }
+
+#[derive(Debug, Clone)]
+pub struct ChapterProgress {
+    pub chapter_id: i64,
+    pub number: f64,
+    pub last_page_read: Option<i64>,
+    pub is_complete: bool,
+    pub read_at: Option<NaiveDateTime>,
+}
+
+/// How far into a manga a user got, `chapters` ordered by number
+#[derive(Debug, Clone)]
+pub struct DropOff {
+    pub manga_id: i64,
+    pub chapters: Vec<ChapterProgress>,
+}
+
+impl DropOff {
+    pub fn completed_chapters(&self) -> usize {
+        self.chapters.iter().filter(|ch| ch.is_complete).count()
+    }
+
+    /// Highest chapter the user opened
+    pub fn last_read_chapter(&self) -> Option<&ChapterProgress> {
+        self.chapters.iter().rev().find(|ch| ch.read_at.is_some())
+    }
+
+    pub fn last_read_at(&self) -> Option<NaiveDateTime> {
+        self.chapters.iter().filter_map(|ch| ch.read_at).max()
+    }
+
+    /// Started, has unread chapters, and untouched since `before`
+    pub fn is_stalled(&self, before: NaiveDateTime) -> bool {
+        self.completed_chapters() < self.chapters.len()
+            && self
+                .last_read_at()
+                .map(|read_at| read_at < before)
+                .unwrap_or(false)
+    }
+}
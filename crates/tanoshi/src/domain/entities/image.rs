@@ -2,11 +2,12 @@ use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvI
 use anyhow::anyhow;
 use bytes::Bytes;
 use fancy_regex::Regex;
+use futures::stream::BoxStream;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 
-use crate::infrastructure::local::SUPPORTED_FILES;
+use crate::infrastructure::{local::SUPPORTED_FILES, storage};
 
 // create an alias for convenience
 type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
@@ -16,20 +17,35 @@ pub enum ImageUri {
     Remote(String),
     File(String),
     Archive(String, String),
+    /// page of an archive kept in remote storage, e.g. `s3://bucket/manga/1.cbz` and `001.jpg`
+    Stored(String, String),
+    /// cover of a local manga, served resized instead of at the size of its page
+    Thumbnail(Box<ImageUri>),
 }
 
+const THUMBNAIL_PREFIX: &str = "thumbnail:";
+
 impl TryFrom<&str> for ImageUri {
     type Error = anyhow::Error;
 
     fn try_from(uri: &str) -> Result<Self, Self::Error> {
-        let uri = if uri.starts_with("http") {
+        let uri = if let Some(uri) = uri.strip_prefix(THUMBNAIL_PREFIX) {
+            Self::Thumbnail(Box::new(Self::try_from(uri)?))
+        } else if uri.starts_with("http") {
             Self::Remote(uri.to_string())
+        } else if storage::is_remote(uri) {
+            let (archive, filename) = uri
+                .split_once(".cbz/")
+                .ok_or_else(|| anyhow!("invalid stored uri"))?;
+
+            Self::Stored(format!("{archive}.cbz"), filename.to_string())
         } else if !uri.is_empty() {
             let path = std::path::PathBuf::from(uri);
-            if path.is_file() {
+            // folders are chapters downloaded as loose pages
+            if path.is_file() || path.is_dir() {
                 Self::File(uri.to_string())
             } else {
-                let regex = format!(r#"\.({})[\/|\\]"#, SUPPORTED_FILES.iter().join("|"));
+                let regex = format!(r#"(?i)\.({})[\/|\\]"#, SUPPORTED_FILES.iter().join("|"));
                 let re = Regex::new(&regex)?;
 
                 if let Some(matches) = re.find(uri).ok().flatten() {
@@ -90,7 +106,10 @@ impl ToString for ImageUri {
         match self {
             ImageUri::Remote(url) => url.to_owned(),
             ImageUri::File(path) => path.to_owned(),
-            ImageUri::Archive(archive, filename) => format!("{archive}/{filename}"),
+            ImageUri::Archive(archive, filename) | ImageUri::Stored(archive, filename) => {
+                format!("{archive}/{filename}")
+            }
+            ImageUri::Thumbnail(uri) => format!("{THUMBNAIL_PREFIX}{}", uri.to_string()),
         }
     }
 }
@@ -100,3 +119,49 @@ pub struct Image {
     pub content_type: String,
     pub data: Bytes,
 }
+
+/// An image sent on while it's still being read from its source
+pub struct ImageStream {
+    pub content_type: String,
+    pub content_length: Option<u64>,
+    /// `Content-Range` of the source when only part of the image is sent
+    pub content_range: Option<String>,
+    pub body: BoxStream<'static, Result<Bytes, std::io::Error>>,
+}
+
+/// Requests made to an image host since startup
+#[derive(Debug, Clone, Default)]
+pub struct ImageHostStats {
+    pub domain: String,
+    pub requests: u64,
+    /// requests failing or answered with an error status
+    pub errors: u64,
+    /// bytes received from the host
+    pub bytes: u64,
+    /// milliseconds until the host answered, summed over every request
+    pub total_ms: u64,
+    pub slowest_ms: u64,
+}
+
+impl ImageHostStats {
+    pub fn average_ms(&self) -> u64 {
+        self.total_ms.checked_div(self.requests).unwrap_or_default()
+    }
+}
+
+/// Responses of the image endpoint since startup
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImageServedStats {
+    pub requests: u64,
+    pub bytes: u64,
+}
+
+/// Lookups of the image cache since startup and what it takes up on disk
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImageCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// bytes of cached images, 0 when they're kept in a bucket
+    pub size: u64,
+    pub max_size: Option<u64>,
+}
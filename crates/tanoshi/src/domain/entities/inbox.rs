@@ -0,0 +1,59 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::NaiveDateTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InboxKind {
+    Chapter,
+    Download,
+    Announcement,
+    Alert,
+}
+
+impl Display for InboxKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InboxKind::Chapter => write!(f, "chapter"),
+            InboxKind::Download => write!(f, "download"),
+            InboxKind::Announcement => write!(f, "announcement"),
+            InboxKind::Alert => write!(f, "alert"),
+        }
+    }
+}
+
+impl FromStr for InboxKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chapter" => Ok(InboxKind::Chapter),
+            "download" => Ok(InboxKind::Download),
+            "announcement" => Ok(InboxKind::Announcement),
+            "alert" => Ok(InboxKind::Alert),
+            _ => Err(anyhow::anyhow!("unknown notification kind {s}")),
+        }
+    }
+}
+
+/// New chapter waiting to be sent in the next digest of a user
+#[derive(Debug, Clone)]
+pub struct ChapterDigestEntry {
+    pub id: i64,
+    pub user_id: i64,
+    pub manga_title: String,
+    pub chapter_title: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// A notification kept in the app, whether or not it was also pushed to a provider
+#[derive(Debug, Clone)]
+pub struct InboxNotification {
+    pub id: i64,
+    pub user_id: i64,
+    pub kind: InboxKind,
+    pub title: Option<String>,
+    pub body: String,
+    pub url: Option<String>,
+    pub is_read: bool,
+    pub created_at: NaiveDateTime,
+}
@@ -1,20 +1,149 @@
 use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone)]
 pub struct Category {
     pub id: Option<i64>,
+    /// category this is nested in, `None` for top level categories
+    pub parent_id: Option<i64>,
+    /// sort index among the user's categories
+    pub position: i64,
+    /// manga added to library without picking categories go here
+    pub is_default: bool,
     pub name: String,
+    /// hidden until unlocked for the session
+    pub is_private: bool,
+    /// argon2 hash, private category without pin is unlocked with account password
+    pub pin: Option<String>,
+    pub auto_download: AutoDownload,
+    /// smart category filled with the manga matching these instead of manga added to it
+    pub rules: Option<SmartRules>,
 }
 
 impl Default for Category {
     fn default() -> Self {
         Self {
             id: None,
+            parent_id: None,
+            position: 0,
+            is_default: false,
             name: "Default".to_string(),
+            is_private: false,
+            pin: None,
+            auto_download: AutoDownload::default(),
+            rules: None,
         }
     }
 }
 
+/// Label a user puts on manga in their library, independent of categories
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+}
+
+/// Ordered list of manga a user put together, the manga don't have to be in library
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadingList {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    /// manga in the list
+    pub count: i64,
+}
+
+/// Stored as json in `user_category.rules`, a manga has to match every rule that is set
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmartRules {
+    /// any of these sources
+    #[serde(default)]
+    pub source_ids: Vec<i64>,
+    /// any of these genres, case insensitive
+    #[serde(default)]
+    pub genres: Vec<String>,
+    /// status as given by the source, case insensitive
+    #[serde(default)]
+    pub status: Option<String>,
+    /// has chapters not read to the end yet
+    #[serde(default)]
+    pub unread: bool,
+    /// last read more than this many days ago, or never read
+    #[serde(default)]
+    pub last_read_days: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LibrarySort {
+    Title,
+    LastRead,
+    /// upload date of the most recent chapter
+    LatestChapter,
+    UnreadCount,
+    /// when the manga was added to library
+    DateAdded,
+}
+
+impl Default for LibrarySort {
+    fn default() -> Self {
+        Self::Title
+    }
+}
+
+/// Order and filters of a library listing, evaluated by the database
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LibraryQuery {
+    pub sort: LibrarySort,
+    pub descending: bool,
+    /// has chapters not read to the end yet
+    pub unread_only: bool,
+    /// has downloaded chapters
+    pub downloaded_only: bool,
+    /// any of these sources, empty matches every source
+    pub source_ids: Vec<i64>,
+    /// status as given by the source, case insensitive
+    pub status: Option<String>,
+}
+
+/// Download new chapters found by library updates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AutoDownload {
+    pub enabled: bool,
+    /// only download this many of the most recent new chapters, `None` downloads every one
+    pub limit: Option<i64>,
+}
+
+impl AutoDownload {
+    /// Combine settings of everyone following a manga, the most generous one wins
+    pub fn merge(settings: &[AutoDownload]) -> Option<AutoDownload> {
+        let enabled: Vec<&AutoDownload> = settings.iter().filter(|s| s.enabled).collect();
+        if enabled.is_empty() {
+            return None;
+        }
+
+        let limit = if enabled.iter().any(|s| s.limit.is_none()) {
+            None
+        } else {
+            enabled.iter().filter_map(|s| s.limit).max()
+        };
+
+        Some(AutoDownload {
+            enabled: true,
+            limit,
+        })
+    }
+}
+
+/// Library entry that keeps its next `count` unread chapters downloaded
+#[derive(Debug, Clone)]
+pub struct DownloadAhead {
+    pub user_id: i64,
+    pub manga_id: i64,
+    pub count: i64,
+    /// scanlation group the user prefers for the manga
+    pub preferred_group: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct LibraryUpdate {
     pub manga_id: i64,
@@ -24,3 +153,24 @@ pub struct LibraryUpdate {
     pub chapter_title: String,
     pub uploaded: NaiveDateTime,
 }
+
+/// Manga by the time it was added to a library
+#[derive(Debug, Clone, Serialize)]
+pub struct LibraryAddition {
+    pub manga_id: i64,
+    pub manga_title: String,
+    pub cover_url: String,
+    pub date_added: NaiveDateTime,
+}
+
+/// Chapter of a library manga by the time it was first fetched from the source
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterAddition {
+    pub manga_id: i64,
+    pub chapter_id: i64,
+    pub manga_title: String,
+    pub cover_url: String,
+    pub chapter_title: String,
+    pub uploaded: NaiveDateTime,
+    pub date_added: NaiveDateTime,
+}
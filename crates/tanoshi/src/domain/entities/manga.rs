@@ -6,6 +6,7 @@ pub struct Manga {
     pub id: i64,
     pub source_id: i64,
     pub title: String,
+    pub alt_titles: Vec<String>,
     pub author: Vec<String>,
     pub genre: Vec<String>,
     pub status: Option<String>,
@@ -22,6 +23,7 @@ impl Default for Manga {
             id: 0,
             source_id: 0,
             title: "".to_string(),
+            alt_titles: vec![],
             author: vec![],
             genre: vec![],
             status: None,
@@ -40,6 +42,7 @@ impl From<tanoshi_lib::models::MangaInfo> for Manga {
             id: 0,
             source_id: m.source_id,
             title: m.title,
+            alt_titles: m.alt_titles,
             author: m.author,
             genre: m.genre,
             status: m.status,
@@ -52,4 +55,46 @@ impl From<tanoshi_lib::models::MangaInfo> for Manga {
     }
 }
 
+/// Metadata a user set over the one of the source, `None` keeps what the source has
+#[derive(Debug, Clone, Default)]
+pub struct MangaOverride {
+    pub title: Option<String>,
+    pub author: Option<Vec<String>>,
+    pub genre: Option<Vec<String>>,
+    pub status: Option<String>,
+    pub description: Option<String>,
+}
+
+impl MangaOverride {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.author.is_none()
+            && self.genre.is_none()
+            && self.status.is_none()
+            && self.description.is_none()
+    }
+
+    pub fn apply(&self, manga: &mut Manga) {
+        if let Some(title) = self.title.as_ref() {
+            // the source's title is still searched for
+            let source_title = std::mem::replace(&mut manga.title, title.clone());
+            if !manga.alt_titles.contains(&source_title) {
+                manga.alt_titles.push(source_title);
+            }
+        }
+        if let Some(author) = self.author.as_ref() {
+            manga.author = author.clone();
+        }
+        if let Some(genre) = self.genre.as_ref() {
+            manga.genre = genre.clone();
+        }
+        if let Some(status) = self.status.as_ref() {
+            manga.status = Some(status.clone());
+        }
+        if let Some(description) = self.description.as_ref() {
+            manga.description = Some(description.clone());
+        }
+    }
+}
+
 pub type InputList = Vec<Input>;
@@ -1,7 +1,9 @@
+pub mod automation;
 pub mod chapter;
 pub mod download;
 pub mod history;
 pub mod image;
+pub mod inbox;
 pub mod library;
 pub mod manga;
 pub mod source;
@@ -1,3 +1,12 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use super::user::User;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Source {
     pub id: i64,
     pub name: String,
@@ -7,6 +16,74 @@ pub struct Source {
     pub lib_version: String,
     pub icon: String,
     pub has_update: bool,
+    /// `None` when the source supports every language or it's unknown
+    pub languages: Option<Vec<String>>,
+    /// pinned by the requesting user, listed first and searched by default
+    pub pinned: bool,
+    pub capabilities: SourceCapabilities,
+    pub nsfw: bool,
+}
+
+/// What the source supports, see [`tanoshi_lib::models::Capabilities`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceCapabilities {
+    pub latest: bool,
+    pub search_filters: bool,
+    pub page_count: bool,
+    pub requires_login: bool,
+}
+
+impl Default for SourceCapabilities {
+    fn default() -> Self {
+        tanoshi_lib::models::Capabilities::default().into()
+    }
+}
+
+impl From<tanoshi_lib::models::Capabilities> for SourceCapabilities {
+    fn from(capabilities: tanoshi_lib::models::Capabilities) -> Self {
+        Self {
+            latest: capabilities.latest,
+            search_filters: capabilities.search_filters,
+            page_count: capabilities.page_count,
+            requires_login: capabilities.login,
+        }
+    }
+}
+
+impl Source {
+    pub fn supports_language(&self, language: &str) -> bool {
+        match self.languages.as_ref() {
+            Some(languages) => languages
+                .iter()
+                .any(|lang| lang.eq_ignore_ascii_case(language)),
+            None => true,
+        }
+    }
+
+    /// Whether `user` has the source hidden by their content filter
+    pub fn is_hidden_for(&self, user: &User) -> bool {
+        self.nsfw && user.hide_nsfw
+    }
+
+    /// Whether any of `languages` is supported, `None` enables every language
+    pub fn supports_any_language(&self, languages: Option<&[String]>) -> bool {
+        match languages {
+            Some(languages) => languages
+                .iter()
+                .any(|language| self.supports_language(language)),
+            None => true,
+        }
+    }
+}
+
+/// Language codes of a source, `None` when it supports every language
+pub fn language_codes(lang: tanoshi_lib::models::Lang) -> Option<Vec<String>> {
+    match lang {
+        tanoshi_lib::models::Lang::All => None,
+        tanoshi_lib::models::Lang::Single(lang) => Some(vec![lang]),
+        tanoshi_lib::models::Lang::Multi(langs) => Some(langs),
+    }
 }
 
 impl From<tanoshi_lib::models::SourceInfo> for Source {
@@ -20,6 +97,203 @@ impl From<tanoshi_lib::models::SourceInfo> for Source {
             lib_version: "".to_string(),
             icon: s.icon.to_string(),
             has_update: false,
+            languages: language_codes(s.languages),
+            pinned: false,
+            capabilities: s.capabilities.into(),
+            nsfw: s.nsfw,
+        }
+    }
+}
+
+/// Source account of a user, `session` is stored encrypted
+#[derive(Debug, Clone)]
+pub struct SourceCredential {
+    pub user_id: i64,
+    pub source_id: i64,
+    pub username: String,
+    pub session: String,
+    pub updated_at: NaiveDateTime,
+}
+
+/// Headers sent in place of the default ones on requests to a source or an image domain,
+/// for sites blocking the default client
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeaderOverride {
+    pub user_agent: Option<String>,
+    /// extra headers by name
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl HeaderOverride {
+    pub fn is_empty(&self) -> bool {
+        self.user_agent.is_none() && self.headers.is_empty()
+    }
+
+    /// Every header to set, `user_agent` as `User-Agent`
+    pub fn to_headers(&self) -> HashMap<String, String> {
+        let mut headers: HashMap<String, String> = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        if let Some(user_agent) = self.user_agent.as_ref() {
+            headers.retain(|name, _| !name.eq_ignore_ascii_case("user-agent"));
+            headers.insert("User-Agent".to_string(), user_agent.clone());
+        }
+
+        headers
+    }
+}
+
+/// Header override of requests to `domain` and its subdomains, e.g. the cdn a source
+/// serves images from
+#[derive(Debug, Clone)]
+pub struct DomainHeaderOverride {
+    pub domain: String,
+    pub header_override: HeaderOverride,
+}
+
+/// consecutive failures after which the rest of an update run skips the source
+pub const CIRCUIT_BREAKER_THRESHOLD: i64 = 5;
+/// minutes after the last failure an open circuit also skips the source on following runs
+pub const CIRCUIT_BREAKER_COOLDOWN_MINUTES: i64 = 30;
+/// consecutive failures, and how long they have to last, before a source is disabled
+pub const AUTO_DISABLE_THRESHOLD: i64 = 10;
+pub const AUTO_DISABLE_AFTER_DAYS: i64 = 3;
+/// weight of the latest result in the moving error rate
+const ERROR_RATE_WEIGHT: f64 = 0.1;
+
+/// Results of requests to a source during update runs
+#[derive(Debug, Clone, Default)]
+pub struct SourceHealth {
+    pub source_id: i64,
+    /// exponential moving average, 0 never fails and 1 always fails
+    pub error_rate: f64,
+    pub consecutive_failures: i64,
+    pub last_error: Option<String>,
+    pub last_success_at: Option<NaiveDateTime>,
+    pub last_failure_at: Option<NaiveDateTime>,
+    /// first failure of the current streak
+    pub failing_since: Option<NaiveDateTime>,
+    pub disabled_at: Option<NaiveDateTime>,
+    /// automatic restarts of the loaded extension after a crash, not persisted
+    pub restarts: u32,
+    /// crashed and refusing calls until restarted
+    pub restarting: bool,
+    pub last_crash: Option<String>,
+}
+
+impl SourceHealth {
+    pub fn new(source_id: i64) -> Self {
+        Self {
+            source_id,
+            ..Default::default()
+        }
+    }
+
+    pub fn record_success(&mut self, now: NaiveDateTime) {
+        self.error_rate *= 1.0 - ERROR_RATE_WEIGHT;
+        self.consecutive_failures = 0;
+        self.last_success_at = Some(now);
+        self.failing_since = None;
+    }
+
+    pub fn record_failure(&mut self, now: NaiveDateTime, error: &str) {
+        self.error_rate = self.error_rate * (1.0 - ERROR_RATE_WEIGHT) + ERROR_RATE_WEIGHT;
+        self.consecutive_failures += 1;
+        self.last_error = Some(error.to_string());
+        self.last_failure_at = Some(now);
+        self.failing_since.get_or_insert(now);
+    }
+
+    pub fn is_circuit_open(&self) -> bool {
+        self.consecutive_failures >= CIRCUIT_BREAKER_THRESHOLD
+    }
+
+    /// When the source gets tried again, `None` while the circuit is closed
+    pub fn circuit_open_until(&self) -> Option<NaiveDateTime> {
+        if !self.is_circuit_open() {
+            return None;
         }
+
+        self.last_failure_at
+            .map(|at| at + chrono::Duration::minutes(CIRCUIT_BREAKER_COOLDOWN_MINUTES))
     }
+
+    /// Circuit open and the cooldown not over yet, afterwards a single request decides
+    /// whether it closes or stays open for another cooldown
+    pub fn is_cooling_down(&self, now: NaiveDateTime) -> bool {
+        self.circuit_open_until()
+            .map(|until| now < until)
+            .unwrap_or(false)
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled_at.is_some()
+    }
+
+    /// From 0 to 100, the error rate halved again while the circuit is open
+    pub fn score(&self) -> i64 {
+        if self.is_disabled() {
+            return 0;
+        }
+
+        let mut score = (1.0 - self.error_rate) * 100.0;
+        if self.is_circuit_open() {
+            score /= 2.0;
+        }
+
+        score.round() as i64
+    }
+
+    /// Failing long enough that retrying on every update run is pointless
+    pub fn should_disable(&self, now: NaiveDateTime) -> bool {
+        !self.is_disabled()
+            && self.consecutive_failures >= AUTO_DISABLE_THRESHOLD
+            && self
+                .failing_since
+                .map(|since| now - since >= chrono::Duration::days(AUTO_DISABLE_AFTER_DAYS))
+                .unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceStatus {
+    Ok,
+    /// answered without error but returned nothing, usually a selector broken by a site change
+    Empty,
+    Error,
+    Timeout,
+}
+
+/// Result of probing a source with its first popular page
+#[derive(Debug, Clone)]
+pub struct SourceDiagnostic {
+    pub source_id: i64,
+    pub name: String,
+    pub version: String,
+    pub status: SourceStatus,
+    pub latency: std::time::Duration,
+    pub manga_count: usize,
+    pub error: Option<String>,
+}
+
+/// Severity of a record logged by an extension, ordered from most to least severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceLogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceLog {
+    pub time: NaiveDateTime,
+    pub level: SourceLogLevel,
+    pub target: String,
+    pub message: String,
 }
@@ -1,5 +1,41 @@
+use std::{fmt::Display, str::FromStr};
+
 use chrono::NaiveDateTime;
 
+/// Listing shown when a source is opened without a query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatalogueSort {
+    Popular,
+    Latest,
+}
+
+impl Default for CatalogueSort {
+    fn default() -> Self {
+        CatalogueSort::Popular
+    }
+}
+
+impl Display for CatalogueSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CatalogueSort::Popular => write!(f, "popular"),
+            CatalogueSort::Latest => write!(f, "latest"),
+        }
+    }
+}
+
+impl FromStr for CatalogueSort {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "popular" => Ok(CatalogueSort::Popular),
+            "latest" => Ok(CatalogueSort::Latest),
+            _ => Err(anyhow::anyhow!("unknown catalogue sort {s}")),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct User {
     pub id: i64,
@@ -11,6 +47,33 @@ pub struct User {
     pub telegram_chat_id: Option<i64>,
     pub pushover_user_key: Option<String>,
     pub gotify_token: Option<String>,
+    pub notification_digest_interval: Option<i64>,
+    /// categories to notify new chapters for, `0` is the uncategorized default
+    /// category and `None` means every category
+    pub notify_chapter_category_ids: Option<Vec<i64>>,
+    pub notify_download_failures: bool,
+    pub notify_announcements: bool,
+    pub pushover_device: Option<String>,
+    pub pushover_sound: Option<String>,
+    /// pushover priority for new chapter notifications, -2 to 1
+    pub pushover_chapter_priority: i64,
+    /// pushover priority for download failures and admin alerts, -2 to 1
+    pub pushover_alert_priority: i64,
+    pub catalogue_sort: CatalogueSort,
+    /// only list sources supporting this language, `None` shows every source
+    pub catalogue_language: Option<String>,
+    /// drop manga already in library from source listings
+    pub catalogue_hide_library: bool,
+    /// languages shown in catalogues and global search, `None` enables every language
+    pub enabled_languages: Option<Vec<String>>,
+    /// hide sources flagged nsfw from source listings and catalogues
+    pub hide_nsfw: bool,
+    /// address chapters are emailed to, e.g. a send to kindle address
+    pub device_email: Option<String>,
+    /// chapter pages are served shrunk and at a lower quality
+    pub data_saver: bool,
+    /// chapters listed in any of these languages, `None` lists every language
+    pub chapter_languages: Option<Vec<String>>,
 }
 
 impl Default for User {
@@ -25,6 +88,22 @@ impl Default for User {
             telegram_chat_id: None,
             pushover_user_key: None,
             gotify_token: None,
+            notification_digest_interval: None,
+            notify_chapter_category_ids: None,
+            notify_download_failures: true,
+            notify_announcements: true,
+            pushover_device: None,
+            pushover_sound: None,
+            pushover_chapter_priority: 0,
+            pushover_alert_priority: 0,
+            catalogue_sort: CatalogueSort::Popular,
+            catalogue_language: None,
+            catalogue_hide_library: false,
+            enabled_languages: None,
+            hide_nsfw: false,
+            device_email: None,
+            data_saver: false,
+            chapter_languages: None,
         }
     }
 }
@@ -0,0 +1,59 @@
+use async_trait::async_trait;
+use thiserror::Error;
+
+use crate::domain::entities::automation::{AutomationAction, AutomationEvent, AutomationRule};
+
+#[derive(Debug, Error)]
+pub enum AutomationRepositoryError {
+    #[error("database return error: {0}")]
+    DbError(#[from] sqlx::Error),
+    #[error("invalid rule: {0}")]
+    InvalidRule(String),
+}
+
+#[async_trait]
+pub trait AutomationRepository: Send + Sync {
+    async fn get_rules_by_user_id(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<AutomationRule>, AutomationRepositoryError>;
+
+    async fn get_enabled_rules_by_event(
+        &self,
+        user_id: i64,
+        event: AutomationEvent,
+    ) -> Result<Vec<AutomationRule>, AutomationRepositoryError>;
+
+    async fn get_rule_by_id(
+        &self,
+        user_id: i64,
+        id: i64,
+    ) -> Result<AutomationRule, AutomationRepositoryError>;
+
+    async fn insert_rule(
+        &self,
+        user_id: i64,
+        name: &str,
+        event: AutomationEvent,
+        action: &AutomationAction,
+        enabled: bool,
+    ) -> Result<i64, AutomationRepositoryError>;
+
+    async fn update_rule(
+        &self,
+        user_id: i64,
+        id: i64,
+        name: &str,
+        event: AutomationEvent,
+        action: &AutomationAction,
+        enabled: bool,
+    ) -> Result<(), AutomationRepositoryError>;
+
+    async fn delete_rule(&self, user_id: i64, id: i64) -> Result<(), AutomationRepositoryError>;
+
+    async fn is_user_category(
+        &self,
+        user_id: i64,
+        category_id: i64,
+    ) -> Result<bool, AutomationRepositoryError>;
+}
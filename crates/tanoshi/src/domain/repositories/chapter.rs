@@ -24,6 +24,19 @@ pub trait ChapterRepository: Send + Sync {
         path: &str,
     ) -> Result<Chapter, ChapterRepositoryError>;
 
+    /// Chapters of manga linked to `manga_id` at `sort_key`, in order of link priority
+    async fn get_chapters_from_linked_manga(
+        &self,
+        manga_id: i64,
+        sort_key: f64,
+    ) -> Result<Vec<Chapter>, ChapterRepositoryError>;
+
+    /// Chapters of manga linked to the manga of `chapter_ids` at the same sort key
+    async fn get_linked_chapter_ids(
+        &self,
+        chapter_ids: &[i64],
+    ) -> Result<Vec<i64>, ChapterRepositoryError>;
+
     async fn get_chapters_by_manga_id(
         &self,
         manga_id: i64,
@@ -45,4 +58,16 @@ pub trait ChapterRepository: Send + Sync {
         manga_id: i64,
         paths: &[String],
     ) -> Result<Vec<Chapter>, ChapterRepositoryError>;
+
+    /// Chapters among `chapter_ids` any user has read history of
+    async fn get_chapter_ids_with_history(
+        &self,
+        chapter_ids: &[i64],
+    ) -> Result<Vec<i64>, ChapterRepositoryError>;
+
+    async fn migrate_chapter_state(
+        &self,
+        from_chapter_id: i64,
+        to_chapter_id: i64,
+    ) -> Result<(), ChapterRepositoryError>;
 }
@@ -2,7 +2,9 @@ use async_trait::async_trait;
 
 use thiserror::Error;
 
-use crate::domain::entities::download::{DownloadQueue, DownloadQueueEntry, DownloadedChapter};
+use crate::domain::entities::download::{
+    DownloadQueue, DownloadQueueEntry, DownloadedChapter, DownloadedChapterFile, MangaDownloadUsage,
+};
 
 #[derive(Debug, Error)]
 pub enum DownloadRepositoryError {
@@ -49,14 +51,33 @@ pub trait DownloadRepository: Send + Sync {
         path: Option<String>,
     ) -> Result<(), DownloadRepositoryError>;
 
+    /// Remember how many pages the chapter has from its queue, before the queue is deleted
+    async fn update_chapter_downloaded_page_count(
+        &self,
+        chapter_id: i64,
+    ) -> Result<(), DownloadRepositoryError>;
+
+    async fn update_chapter_downloaded_size(
+        &self,
+        chapter_id: i64,
+        size: i64,
+    ) -> Result<(), DownloadRepositoryError>;
+
+    /// Recorded size of downloaded chapters summed by manga, largest first
+    async fn get_download_usage(&self) -> Result<Vec<MangaDownloadUsage>, DownloadRepositoryError>;
+
     async fn insert_download_queue(
         &self,
         items: &[DownloadQueue],
     ) -> Result<(), DownloadRepositoryError>;
 
-    async fn get_single_download_queue(
+    /// Up to `limit` pages to download next in queue order, taking at most the first
+    /// `pages_per_chapter` remaining pages of each chapter
+    async fn get_next_download_queue(
         &self,
-    ) -> Result<Option<DownloadQueue>, DownloadRepositoryError>;
+        pages_per_chapter: i64,
+        limit: i64,
+    ) -> Result<Vec<DownloadQueue>, DownloadRepositoryError>;
 
     async fn get_single_chapter_download_status(
         &self,
@@ -72,6 +93,13 @@ pub trait DownloadRepository: Send + Sync {
         &self,
     ) -> Result<Option<i64>, DownloadRepositoryError>;
 
+    /// Earliest time a page waiting out its backoff can be retried, as a unix timestamp
+    async fn get_next_download_retry_at(&self) -> Result<Option<i64>, DownloadRepositoryError>;
+
+    async fn get_download_queue_first_priority(
+        &self,
+    ) -> Result<Option<i64>, DownloadRepositoryError>;
+
     async fn get_download_queue(&self) -> Result<Vec<DownloadQueueEntry>, DownloadRepositoryError>;
 
     async fn delete_single_chapter_download_queue(
@@ -89,4 +117,52 @@ pub trait DownloadRepository: Send + Sync {
         chapter_id: i64,
         priority: i64,
     ) -> Result<(), DownloadRepositoryError>;
+
+    /// Put `chapter_ids` first in that order, the rest of the queue keeps its order after them
+    async fn reorder_download_queue(
+        &self,
+        chapter_ids: &[i64],
+    ) -> Result<(), DownloadRepositoryError>;
+
+    async fn update_download_queue_paused(
+        &self,
+        chapter_id: i64,
+        paused: bool,
+    ) -> Result<(), DownloadRepositoryError>;
+
+    /// Count a failed attempt at a page, skipping it until `retry_at` unix timestamp
+    async fn record_download_queue_failure(
+        &self,
+        id: i64,
+        error: &str,
+        retry_at: i64,
+    ) -> Result<(), DownloadRepositoryError>;
+
+    /// Returns false when the chapter was already marked failed
+    async fn mark_chapter_download_failed(
+        &self,
+        chapter_id: i64,
+    ) -> Result<bool, DownloadRepositoryError>;
+
+    /// Queue failed chapters again with their attempts reset, returns chapters affected
+    async fn reset_failed_download_queue(&self) -> Result<u64, DownloadRepositoryError>;
+
+    /// Every downloaded chapter, grouped by manga with the most recent chapter first
+    async fn get_downloaded_chapter_files(
+        &self,
+    ) -> Result<Vec<DownloadedChapterFile>, DownloadRepositoryError>;
+
+    /// Every chapter of local sources listed so far, `path` is where it is in its folder
+    async fn get_local_chapter_files(
+        &self,
+    ) -> Result<Vec<DownloadedChapterFile>, DownloadRepositoryError>;
+
+    /// Chapters of `manga_ids` neither downloaded nor queued, oldest first, only ones
+    /// `user_id` hasn't completed when `unread_only`
+    async fn get_chapter_ids_to_download(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+        unread_only: bool,
+    ) -> Result<Vec<i64>, DownloadRepositoryError>;
 }
@@ -1,10 +1,11 @@
 use std::collections::HashMap;
 
 use async_trait::async_trait;
+use chrono::NaiveDateTime;
 
 use thiserror::Error;
 
-use crate::domain::entities::history::HistoryChapter;
+use crate::domain::entities::history::{ChapterProgress, HistoryChapter};
 
 #[derive(Debug, Error)]
 pub enum HistoryRepositoryError {
@@ -17,6 +18,7 @@ pub trait HistoryRepository: Send + Sync {
     async fn get_first_history_chapters(
         &self,
         user_id: i64,
+        hidden_manga_ids: &[i64],
         after_timestamp: i64,
         before_timestamp: i64,
         first: i32,
@@ -25,6 +27,7 @@ pub trait HistoryRepository: Send + Sync {
     async fn get_last_history_chapters(
         &self,
         user_id: i64,
+        hidden_manga_ids: &[i64],
         after_timestamp: i64,
         before_timestamp: i64,
         last: i32,
@@ -33,6 +36,7 @@ pub trait HistoryRepository: Send + Sync {
     async fn get_history_chapters(
         &self,
         user_id: i64,
+        hidden_manga_ids: &[i64],
         after_timestamp: i64,
         before_timestamp: i64,
     ) -> Result<Vec<HistoryChapter>, HistoryRepositoryError>;
@@ -69,6 +73,20 @@ pub trait HistoryRepository: Send + Sync {
         chapter_ids: &[i64],
     ) -> Result<(), HistoryRepositoryError>;
 
+    /// Complete every chapter of `manga_ids` in one statement, returns chapters changed
+    async fn insert_manga_chapters_as_completed(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+    ) -> Result<u64, HistoryRepositoryError>;
+
+    /// Forget every chapter of `manga_ids` in one statement, returns chapters changed
+    async fn delete_manga_chapters_from_history(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+    ) -> Result<u64, HistoryRepositoryError>;
+
     async fn get_unread_chapters_by_manga_ids(
         &self,
         user_id: i64,
@@ -80,4 +98,16 @@ pub trait HistoryRepository: Send + Sync {
         user_id: i64,
         manga_id: i64,
     ) -> Result<Option<i64>, HistoryRepositoryError>;
+
+    async fn get_chapter_progress_by_manga_id(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Vec<ChapterProgress>, HistoryRepositoryError>;
+
+    async fn get_stalled_manga_ids(
+        &self,
+        user_id: i64,
+        before: NaiveDateTime,
+    ) -> Result<Vec<i64>, HistoryRepositoryError>;
 }
@@ -4,12 +4,14 @@ use async_trait::async_trait;
 
 use thiserror::Error;
 
-use crate::domain::entities::image::Image;
+use crate::domain::entities::image::{Image, ImageHostStats, ImageStream};
 
 #[derive(Debug, Error)]
 pub enum ImageRepositoryError {
     #[error("error request image: {0}")]
     RequestError(#[from] reqwest::Error),
+    #[error("requested range not satisfiable")]
+    RangeNotSatisfiable,
     #[error("other error: {0}")]
     Other(String),
 }
@@ -21,6 +23,18 @@ pub trait ImageRepository: Send + Sync {
         url: &str,
         referer: Option<&String>,
     ) -> Result<Image, ImageRepositoryError>;
+    /// Like `fetch_image_from_url` without reading the body, `range` is a `Range` header
+    /// passed on to the source
+    async fn stream_image_from_url(
+        &self,
+        url: &str,
+        referer: Option<&String>,
+        range: Option<&str>,
+    ) -> Result<ImageStream, ImageRepositoryError>;
+    /// Send `referer` on later requests to the host of `url`
+    fn set_referer(&self, url: &str, referer: &str);
+    /// Requests made to every remote host since startup
+    fn host_stats(&self) -> Vec<ImageHostStats>;
     async fn fetch_image_from_file<P>(&self, path: P) -> Result<Image, ImageRepositoryError>
     where
         P: AsRef<Path> + std::marker::Send;
@@ -31,4 +45,9 @@ pub trait ImageRepository: Send + Sync {
     ) -> Result<Image, ImageRepositoryError>
     where
         P: AsRef<Path> + std::marker::Send;
+    async fn fetch_image_from_stored_archive(
+        &self,
+        location: &str,
+        filename: &str,
+    ) -> Result<Image, ImageRepositoryError>;
 }
@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use thiserror::Error;
 
-use crate::domain::entities::image::Image;
+use crate::domain::entities::image::{Image, ImageCacheStats};
 
 #[derive(Debug, Error)]
 pub enum ImageCacheRepositoryError {
@@ -18,4 +18,6 @@ pub trait ImageCacheRepository {
     async fn set(&self, key: &str, image: &Image) -> Result<(), ImageCacheRepositoryError>;
 
     async fn get(&self, key: &str) -> Result<Image, ImageCacheRepositoryError>;
+
+    async fn stats(&self) -> ImageCacheStats;
 }
@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+use thiserror::Error;
+
+use crate::domain::entities::inbox::{ChapterDigestEntry, InboxKind, InboxNotification};
+
+#[derive(Debug, Error)]
+pub enum InboxRepositoryError {
+    #[error("database return error: {0}")]
+    DbError(#[from] sqlx::Error),
+}
+
+#[async_trait]
+pub trait InboxRepository: Send + Sync {
+    async fn insert_notification(
+        &self,
+        user_id: i64,
+        kind: InboxKind,
+        title: Option<&str>,
+        body: &str,
+        url: Option<&str>,
+    ) -> Result<i64, InboxRepositoryError>;
+
+    #[allow(clippy::too_many_arguments)]
+    async fn get_first_notifications(
+        &self,
+        user_id: i64,
+        unread_only: bool,
+        after_timestamp: i64,
+        after_id: i64,
+        before_timestamp: i64,
+        before_id: i64,
+        first: i32,
+    ) -> Result<Vec<InboxNotification>, InboxRepositoryError>;
+
+    #[allow(clippy::too_many_arguments)]
+    async fn get_last_notifications(
+        &self,
+        user_id: i64,
+        unread_only: bool,
+        after_timestamp: i64,
+        after_id: i64,
+        before_timestamp: i64,
+        before_id: i64,
+        last: i32,
+    ) -> Result<Vec<InboxNotification>, InboxRepositoryError>;
+
+    async fn get_notifications(
+        &self,
+        user_id: i64,
+        unread_only: bool,
+        after_timestamp: i64,
+        after_id: i64,
+        before_timestamp: i64,
+        before_id: i64,
+    ) -> Result<Vec<InboxNotification>, InboxRepositoryError>;
+
+    async fn get_unread_count(&self, user_id: i64) -> Result<i64, InboxRepositoryError>;
+
+    async fn mark_as_read(&self, user_id: i64, ids: &[i64]) -> Result<u64, InboxRepositoryError>;
+
+    async fn mark_all_as_read(&self, user_id: i64) -> Result<u64, InboxRepositoryError>;
+
+    async fn insert_digest_entry(
+        &self,
+        user_id: i64,
+        chapter_id: i64,
+    ) -> Result<i64, InboxRepositoryError>;
+
+    /// Chapters waiting for a digest of every user, oldest first
+    async fn get_digest_entries(&self) -> Result<Vec<ChapterDigestEntry>, InboxRepositoryError>;
+
+    async fn delete_digest_entries(&self, ids: &[i64]) -> Result<u64, InboxRepositoryError>;
+}
@@ -6,7 +6,10 @@ use futures::Stream;
 use thiserror::Error;
 
 use crate::domain::entities::{
-    library::{Category, LibraryUpdate},
+    library::{
+        AutoDownload, Category, ChapterAddition, DownloadAhead, LibraryAddition, LibraryQuery,
+        LibraryUpdate, ReadingList, SmartRules, Tag,
+    },
     manga::Manga,
     user::User,
 };
@@ -15,6 +18,8 @@ use crate::domain::entities::{
 pub enum LibraryRepositoryError {
     #[error("database error: {0}")]
     DbError(#[from] sqlx::Error),
+    #[error("manga is not in library")]
+    NotInLibrary,
 }
 
 #[async_trait]
@@ -29,7 +34,9 @@ pub trait LibraryRepository: Clone + Send + Sync {
     async fn create_category(
         &self,
         user_id: i64,
+        parent_id: Option<i64>,
         name: &str,
+        rules: Option<&SmartRules>,
     ) -> Result<Category, LibraryRepositoryError>;
 
     async fn rename_category(
@@ -40,24 +47,182 @@ pub trait LibraryRepository: Clone + Send + Sync {
 
     async fn delete_category(&self, id: i64) -> Result<(), LibraryRepositoryError>;
 
+    async fn set_category_privacy(
+        &self,
+        user_id: i64,
+        id: i64,
+        is_private: bool,
+        pin: Option<&str>,
+    ) -> Result<Category, LibraryRepositoryError>;
+
+    /// `None` turns a smart category back into a manual one
+    async fn set_category_rules(
+        &self,
+        user_id: i64,
+        id: i64,
+        rules: Option<&SmartRules>,
+    ) -> Result<Category, LibraryRepositoryError>;
+
+    /// `None` moves the category to the top level
+    async fn set_category_parent(
+        &self,
+        user_id: i64,
+        id: i64,
+        parent_id: Option<i64>,
+    ) -> Result<Category, LibraryRepositoryError>;
+
+    /// `ids` get the position of their index, categories not in it are left as is
+    async fn set_category_positions(
+        &self,
+        user_id: i64,
+        ids: &[i64],
+    ) -> Result<(), LibraryRepositoryError>;
+
+    /// `None` unsets the default category
+    async fn set_default_category(
+        &self,
+        user_id: i64,
+        id: Option<i64>,
+    ) -> Result<(), LibraryRepositoryError>;
+
+    async fn get_tags_by_user_id(&self, user_id: i64) -> Result<Vec<Tag>, LibraryRepositoryError>;
+
+    async fn create_tag(&self, user_id: i64, name: &str) -> Result<Tag, LibraryRepositoryError>;
+
+    async fn rename_tag(
+        &self,
+        user_id: i64,
+        id: i64,
+        name: &str,
+    ) -> Result<Tag, LibraryRepositoryError>;
+
+    async fn delete_tag(&self, user_id: i64, id: i64) -> Result<(), LibraryRepositoryError>;
+
+    /// Tags of every manga in the library of `user_id`, by manga id
+    async fn get_library_tags(
+        &self,
+        user_id: i64,
+    ) -> Result<HashMap<i64, Vec<Tag>>, LibraryRepositoryError>;
+
+    async fn get_tags_by_manga_id(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Vec<Tag>, LibraryRepositoryError>;
+
+    /// Replace tags of a library manga, tags of other users are ignored
+    async fn set_manga_tags(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        tag_ids: &[i64],
+    ) -> Result<(), LibraryRepositoryError>;
+
+    async fn get_reading_lists(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<ReadingList>, LibraryRepositoryError>;
+
+    async fn get_reading_list(
+        &self,
+        user_id: i64,
+        id: i64,
+    ) -> Result<ReadingList, LibraryRepositoryError>;
+
+    /// Reading lists of `user_id` with `manga_id` in them
+    async fn get_reading_lists_by_manga_id(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Vec<ReadingList>, LibraryRepositoryError>;
+
+    async fn create_reading_list(
+        &self,
+        user_id: i64,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<ReadingList, LibraryRepositoryError>;
+
+    async fn update_reading_list(
+        &self,
+        user_id: i64,
+        id: i64,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<ReadingList, LibraryRepositoryError>;
+
+    async fn delete_reading_list(
+        &self,
+        user_id: i64,
+        id: i64,
+    ) -> Result<(), LibraryRepositoryError>;
+
+    /// Manga of a reading list in their order
+    async fn get_manga_from_reading_list(
+        &self,
+        user_id: i64,
+        id: i64,
+    ) -> Result<Vec<Manga>, LibraryRepositoryError>;
+
+    /// Replace manga of a reading list, in the order of `manga_ids`
+    async fn set_reading_list_manga(
+        &self,
+        user_id: i64,
+        id: i64,
+        manga_ids: &[i64],
+    ) -> Result<(), LibraryRepositoryError>;
+
+    /// Manga in a private category, or nested in one, not in `unlocked_category_ids`
+    async fn get_hidden_manga_ids(
+        &self,
+        user_id: i64,
+        unlocked_category_ids: &[i64],
+    ) -> Result<Vec<i64>, LibraryRepositoryError>;
+
+    /// Manga count of every category including its nested categories, smart categories are
+    /// evaluated
     async fn get_category_count(
         &self,
         user_id: i64,
     ) -> Result<HashMap<Option<i64>, i64>, LibraryRepositoryError>;
 
+    /// Unread chapters of the manga in every category including its nested categories
+    async fn get_category_unread_count(
+        &self,
+        user_id: i64,
+    ) -> Result<HashMap<Option<i64>, i64>, LibraryRepositoryError>;
+
     async fn get_users_by_manga_id(
         &self,
         manga_id: i64,
     ) -> Result<Vec<User>, LibraryRepositoryError>;
 
+    async fn get_category_ids_by_manga_id(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Vec<i64>, LibraryRepositoryError>;
+
+    async fn get_library_manga_count(&self) -> Result<i64, LibraryRepositoryError>;
+
     async fn get_manga_from_all_users_library(
         &self,
     ) -> Pin<Box<dyn Stream<Item = Result<Manga, LibraryRepositoryError>>>>;
 
+    /// Manga in the category or any category nested in it, `None` for manga without category
     async fn get_manga_from_library_by_category_id(
         &self,
         user_id: i64,
         category_id: Option<i64>,
+        query: &LibraryQuery,
+    ) -> Result<Vec<Manga>, LibraryRepositoryError>;
+
+    /// Library manga matching `rules`, evaluated on every call
+    async fn get_manga_from_library_by_rules(
+        &self,
+        user_id: i64,
+        rules: &SmartRules,
+        query: &LibraryQuery,
     ) -> Result<Vec<Manga>, LibraryRepositoryError>;
 
     async fn get_manga_from_library(
@@ -72,15 +237,63 @@ pub trait LibraryRepository: Clone + Send + Sync {
         category_ids: &[i64],
     ) -> Result<(), LibraryRepositoryError>;
 
+    async fn set_manga_categories(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        category_ids: &[i64],
+    ) -> Result<(), LibraryRepositoryError>;
+
     async fn delete_manga_from_library(
         &self,
         user_id: i64,
         manga_id: i64,
     ) -> Result<(), LibraryRepositoryError>;
 
+    /// Replace categories of every one of `manga_ids` in library in one transaction, smart
+    /// categories and categories of other users are ignored, returns library entries changed
+    async fn set_many_manga_categories(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+        category_ids: &[i64],
+    ) -> Result<u64, LibraryRepositoryError>;
+
+    /// Returns library entries removed
+    async fn delete_many_manga_from_library(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+    ) -> Result<u64, LibraryRepositoryError>;
+
+    /// Pairs of manga not to be reported as duplicates, the lower id first
+    async fn get_ignored_duplicates(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<(i64, i64)>, LibraryRepositoryError>;
+
+    /// Ignore every pair of `manga_ids` in library as duplicates
+    async fn ignore_duplicates(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+    ) -> Result<(), LibraryRepositoryError>;
+
+    /// Copy categories, tags, trackers, reading lists and history of chapters with the same
+    /// number from `duplicate_ids` to `manga_id`, adding it to library if needed, then remove
+    /// them from library, returns manga removed
+    async fn merge_library_manga(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        duplicate_ids: &[i64],
+    ) -> Result<u64, LibraryRepositoryError>;
+
+    #[allow(clippy::too_many_arguments)]
     async fn get_first_library_updates(
         &self,
         user_id: i64,
+        unlocked_category_ids: &[i64],
         after_timestamp: i64,
         after_id: i64,
         before_timestamp: i64,
@@ -88,9 +301,11 @@ pub trait LibraryRepository: Clone + Send + Sync {
         first: i32,
     ) -> Result<Vec<LibraryUpdate>, LibraryRepositoryError>;
 
+    #[allow(clippy::too_many_arguments)]
     async fn get_last_library_updates(
         &self,
         user_id: i64,
+        unlocked_category_ids: &[i64],
         after_timestamp: i64,
         after_id: i64,
         before_timestamp: i64,
@@ -101,9 +316,118 @@ pub trait LibraryRepository: Clone + Send + Sync {
     async fn get_library_updates(
         &self,
         user_id: i64,
+        unlocked_category_ids: &[i64],
         after_timestamp: i64,
         after_id: i64,
         before_timestamp: i64,
         before_id: i64,
     ) -> Result<Vec<LibraryUpdate>, LibraryRepositoryError>;
+
+    /// Manga added to the library between the cursors, newest first. With `last` the oldest
+    /// ones of the range are taken instead of the newest
+    #[allow(clippy::too_many_arguments)]
+    async fn get_library_additions(
+        &self,
+        user_id: i64,
+        unlocked_category_ids: &[i64],
+        after_timestamp: i64,
+        after_id: i64,
+        before_timestamp: i64,
+        before_id: i64,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Vec<LibraryAddition>, LibraryRepositoryError>;
+
+    /// Chapters fetched for library manga after they were added, between the cursors and
+    /// newest first. With `last` the oldest ones of the range are taken instead of the newest
+    #[allow(clippy::too_many_arguments)]
+    async fn get_chapter_additions(
+        &self,
+        user_id: i64,
+        unlocked_category_ids: &[i64],
+        after_timestamp: i64,
+        after_id: i64,
+        before_timestamp: i64,
+        before_id: i64,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Vec<ChapterAddition>, LibraryRepositoryError>;
+
+    async fn get_download_ahead(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<i64, LibraryRepositoryError>;
+
+    async fn set_download_ahead(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        count: i64,
+    ) -> Result<(), LibraryRepositoryError>;
+
+    async fn get_preferred_group(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Option<String>, LibraryRepositoryError>;
+
+    /// `None` clears the preferred scanlation group
+    async fn set_preferred_group(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        group: Option<&str>,
+    ) -> Result<(), LibraryRepositoryError>;
+
+    async fn get_chapter_languages(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Option<Vec<String>>, LibraryRepositoryError>;
+
+    /// `None` falls back to the chapter languages of the user
+    async fn set_chapter_languages(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        languages: Option<&[String]>,
+    ) -> Result<(), LibraryRepositoryError>;
+
+    /// Scanlation groups preferred by any user having the manga in library
+    async fn get_preferred_groups_by_manga_id(
+        &self,
+        manga_id: i64,
+    ) -> Result<Vec<String>, LibraryRepositoryError>;
+
+    async fn get_download_ahead_entries(
+        &self,
+    ) -> Result<Vec<DownloadAhead>, LibraryRepositoryError>;
+
+    async fn get_auto_download(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<AutoDownload, LibraryRepositoryError>;
+
+    async fn set_auto_download(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        auto_download: AutoDownload,
+    ) -> Result<(), LibraryRepositoryError>;
+
+    async fn set_category_auto_download(
+        &self,
+        user_id: i64,
+        category_id: i64,
+        auto_download: AutoDownload,
+    ) -> Result<Category, LibraryRepositoryError>;
+
+    /// Auto download settings of every user having the manga in library,
+    /// from the library entry and from each of its categories
+    async fn get_auto_download_settings_by_manga_id(
+        &self,
+        manga_id: i64,
+    ) -> Result<Vec<AutoDownload>, LibraryRepositoryError>;
 }
@@ -1,4 +1,6 @@
-use crate::domain::entities::manga::Manga;
+use std::collections::HashMap;
+
+use crate::domain::entities::manga::{Manga, MangaOverride};
 use async_trait::async_trait;
 use thiserror::Error;
 
@@ -18,4 +20,40 @@ pub trait MangaRepository: Send + Sync {
         path: &str,
     ) -> Result<Manga, MangaRepositoryError>;
     async fn insert_manga(&self, manga: &mut Manga) -> Result<(), MangaRepositoryError>;
+    /// Other manga of the series of `manga_id`, preferred first
+    async fn get_linked_manga(&self, manga_id: i64) -> Result<Vec<Manga>, MangaRepositoryError>;
+    /// Every manga of the series of `manga_id` including itself, preferred first
+    async fn get_series_manga(&self, manga_id: i64) -> Result<Vec<Manga>, MangaRepositoryError>;
+    /// Join the series of `manga_id` and `linked_manga_id` into one
+    async fn insert_manga_link(
+        &self,
+        manga_id: i64,
+        linked_manga_id: i64,
+    ) -> Result<(), MangaRepositoryError>;
+    /// Remove `linked_manga_id` from the series of `manga_id`
+    async fn delete_manga_link(
+        &self,
+        manga_id: i64,
+        linked_manga_id: i64,
+    ) -> Result<(), MangaRepositoryError>;
+    /// Prefer manga of a series in the order of `manga_ids`
+    async fn set_manga_link_priority(&self, manga_ids: &[i64]) -> Result<(), MangaRepositoryError>;
+    async fn get_manga_overrides(
+        &self,
+        manga_ids: &[i64],
+    ) -> Result<HashMap<i64, MangaOverride>, MangaRepositoryError>;
+    /// Replace the override of `manga_id`, an empty one removes it
+    async fn set_manga_override(
+        &self,
+        manga_id: i64,
+        manga_override: &MangaOverride,
+    ) -> Result<(), MangaRepositoryError>;
+    async fn get_user_alt_titles(&self, manga_id: i64)
+        -> Result<Vec<String>, MangaRepositoryError>;
+    /// Replace the other titles a user set for `manga_id`, the ones of the source are kept
+    async fn set_user_alt_titles(
+        &self,
+        manga_id: i64,
+        titles: &[String],
+    ) -> Result<(), MangaRepositoryError>;
 }
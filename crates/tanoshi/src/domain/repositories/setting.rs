@@ -0,0 +1,19 @@
+use async_trait::async_trait;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SettingRepositoryError {
+    #[error("database return error: {0}")]
+    DbError(#[from] sqlx::Error),
+}
+
+#[async_trait]
+pub trait SettingRepository: Send + Sync {
+    async fn get_setting(&self, key: &str) -> Result<Option<String>, SettingRepositoryError>;
+
+    async fn set_setting(
+        &self,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<(), SettingRepositoryError>;
+}
@@ -1,23 +1,51 @@
 use async_trait::async_trait;
 
+use tanoshi_lib::prelude::Input;
+use tanoshi_vm::prelude::LimitError;
 use thiserror::Error;
 
-use crate::domain::entities::source::Source;
+use crate::{
+    domain::entities::{
+        manga::Manga,
+        source::{
+            DomainHeaderOverride, HeaderOverride, Source, SourceCredential, SourceHealth,
+            SourceLog, SourceLogLevel,
+        },
+    },
+    infrastructure::repository_index::RepositoryIndexError,
+};
 
 #[derive(Debug, Error)]
 pub enum SourceRepositoryError {
     #[error("extension return error: {0}")]
-    ExtensionError(#[from] anyhow::Error),
+    ExtensionError(anyhow::Error),
+    #[error("extension limit exceeded: {0}")]
+    LimitExceeded(#[from] LimitError),
     #[error("version return error: {0}")]
     VersionError(#[from] tanoshi_lib::error::Error),
     #[error("request return error: {0}")]
     RequestError(#[from] reqwest::Error),
+    #[error("extension repository error: {0}")]
+    RepositoryError(#[from] RepositoryIndexError),
+    #[error("database error: {0}")]
+    DbError(#[from] sqlx::Error),
+    #[error("preferences error: {0}")]
+    PreferencesError(#[from] serde_json::Error),
     #[error("source not found")]
     NotFound,
     #[error("other error: {0}")]
     Other(String),
 }
 
+impl From<anyhow::Error> for SourceRepositoryError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<LimitError>() {
+            Ok(e) => Self::LimitExceeded(e),
+            Err(e) => Self::ExtensionError(e),
+        }
+    }
+}
+
 #[async_trait]
 pub trait SourceRepository: Send + Sync {
     async fn installed_sources(&self) -> Result<Vec<Source>, SourceRepositoryError>;
@@ -33,5 +61,173 @@ pub trait SourceRepository: Send + Sync {
 
     async fn update_source(&self, repo_url: &str, id: i64) -> Result<(), SourceRepositoryError>;
 
+    /// Install a source package not published in the repository, returns the source id
+    async fn install_source_from_file(&self, contents: &[u8])
+        -> Result<i64, SourceRepositoryError>;
+
+    /// Load the source from its library file again and apply its stored settings
+    async fn reload_source(&self, id: i64) -> Result<(), SourceRepositoryError>;
+
+    /// Like `reload_source` by library file name, returns the source id
+    async fn reload_source_file(&self, name: &str) -> Result<i64, SourceRepositoryError>;
+
     async fn uninstall_source(&self, id: i64) -> Result<(), SourceRepositoryError>;
+
+    /// Recent records logged by the extension at `level` or more severe, oldest first
+    async fn get_logs(
+        &self,
+        id: i64,
+        level: SourceLogLevel,
+    ) -> Result<Vec<SourceLog>, SourceRepositoryError>;
+
+    /// Filters `search_manga` of the source accepts
+    async fn get_filters(&self, id: i64) -> Result<Vec<Input>, SourceRepositoryError>;
+
+    async fn get_preferences(&self, id: i64) -> Result<Vec<Input>, SourceRepositoryError>;
+
+    async fn set_preferences(
+        &self,
+        id: i64,
+        preferences: Vec<Input>,
+    ) -> Result<(), SourceRepositoryError>;
+
+    /// Apply stored preferences, proxy, rate limit and header overrides to every loaded extension
+    async fn restore_preferences(&self) -> Result<(), SourceRepositoryError>;
+
+    /// Log in with the extension, returns the session to store
+    async fn login(
+        &self,
+        id: i64,
+        username: &str,
+        password: &str,
+    ) -> Result<String, SourceRepositoryError>;
+
+    async fn get_credential(
+        &self,
+        user_id: i64,
+        source_id: i64,
+    ) -> Result<Option<SourceCredential>, SourceRepositoryError>;
+
+    /// Session of a user with `manga_id` in their library, for calls made on behalf of
+    /// every user like library refresh and downloads. The most recent login is used
+    async fn get_library_session(
+        &self,
+        source_id: i64,
+        manga_id: i64,
+    ) -> Result<Option<String>, SourceRepositoryError>;
+
+    async fn save_credential(
+        &self,
+        user_id: i64,
+        source_id: i64,
+        username: &str,
+        session: &str,
+    ) -> Result<(), SourceRepositoryError>;
+
+    async fn delete_credential(
+        &self,
+        user_id: i64,
+        source_id: i64,
+    ) -> Result<(), SourceRepositoryError>;
+
+    /// Sources pinned by the user, oldest pin first
+    async fn get_pinned_source_ids(&self, user_id: i64) -> Result<Vec<i64>, SourceRepositoryError>;
+
+    async fn pin_source(&self, user_id: i64, source_id: i64) -> Result<(), SourceRepositoryError>;
+
+    async fn unpin_source(&self, user_id: i64, source_id: i64)
+        -> Result<(), SourceRepositoryError>;
+
+    /// Filters the user last browsed the source with, `None` if never saved
+    async fn get_saved_filters(
+        &self,
+        user_id: i64,
+        source_id: i64,
+    ) -> Result<Option<Vec<Input>>, SourceRepositoryError>;
+
+    async fn save_filters(
+        &self,
+        user_id: i64,
+        source_id: i64,
+        filters: &[Input],
+    ) -> Result<(), SourceRepositoryError>;
+
+    async fn delete_saved_filters(
+        &self,
+        user_id: i64,
+        source_id: i64,
+    ) -> Result<(), SourceRepositoryError>;
+
+    /// Health of a source, fresh if nothing has been recorded yet
+    async fn get_source_health(
+        &self,
+        source_id: i64,
+    ) -> Result<SourceHealth, SourceRepositoryError>;
+
+    async fn get_all_source_health(&self) -> Result<Vec<SourceHealth>, SourceRepositoryError>;
+
+    async fn save_source_health(&self, health: &SourceHealth) -> Result<(), SourceRepositoryError>;
+
+    /// Proxy set for the source, `None` when it uses the default
+    async fn get_proxy(&self, id: i64) -> Result<Option<String>, SourceRepositoryError>;
+
+    async fn set_proxy(&self, id: i64, proxy: Option<&str>) -> Result<(), SourceRepositoryError>;
+
+    async fn get_header_override(
+        &self,
+        id: i64,
+    ) -> Result<Option<HeaderOverride>, SourceRepositoryError>;
+
+    /// `None` sends the headers of the extension again
+    async fn set_header_override(
+        &self,
+        id: i64,
+        header_override: Option<&HeaderOverride>,
+    ) -> Result<(), SourceRepositoryError>;
+
+    async fn get_domain_header_overrides(
+        &self,
+    ) -> Result<Vec<DomainHeaderOverride>, SourceRepositoryError>;
+
+    async fn set_domain_header_override(
+        &self,
+        domain: &str,
+        header_override: Option<&HeaderOverride>,
+    ) -> Result<(), SourceRepositoryError>;
+
+    /// Requests per minute set for the source, `None` when it uses the default
+    async fn get_rate_limit(&self, id: i64) -> Result<Option<u32>, SourceRepositoryError>;
+
+    async fn set_rate_limit(
+        &self,
+        id: i64,
+        requests_per_minute: Option<u32>,
+    ) -> Result<(), SourceRepositoryError>;
+
+    /// Popular manga of the source, cached responses are reused unless `refresh`
+    async fn get_popular_manga(
+        &self,
+        id: i64,
+        page: i64,
+        session: Option<String>,
+        refresh: bool,
+    ) -> Result<Vec<Manga>, SourceRepositoryError>;
+
+    async fn get_latest_manga(
+        &self,
+        id: i64,
+        page: i64,
+        session: Option<String>,
+        refresh: bool,
+    ) -> Result<Vec<Manga>, SourceRepositoryError>;
+
+    async fn search_manga(
+        &self,
+        id: i64,
+        page: i64,
+        query: Option<String>,
+        filters: Option<Vec<Input>>,
+        session: Option<String>,
+        refresh: bool,
+    ) -> Result<Vec<Manga>, SourceRepositoryError>;
 }
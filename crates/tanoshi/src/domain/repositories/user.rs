@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use thiserror::Error;
 
-use crate::domain::entities::user::User;
+use crate::domain::entities::user::{CatalogueSort, User};
 
 #[derive(Debug, Error)]
 pub enum UserRepositoryError {
@@ -35,5 +35,58 @@ pub trait UserRepository: Send + Sync {
 
     async fn get_user_by_username(&self, username: String) -> Result<User, UserRepositoryError>;
 
+    async fn get_user_by_telegram_chat_id(&self, chat_id: i64)
+        -> Result<User, UserRepositoryError>;
+
     async fn update_user_setting(&self, user: &User) -> Result<u64, UserRepositoryError>;
+
+    async fn update_notification_digest_interval(
+        &self,
+        id: i64,
+        interval: Option<i64>,
+    ) -> Result<u64, UserRepositoryError>;
+
+    async fn update_notification_preferences(
+        &self,
+        id: i64,
+        chapter_category_ids: Option<&[i64]>,
+        download_failures: bool,
+        announcements: bool,
+    ) -> Result<u64, UserRepositoryError>;
+
+    async fn update_catalogue_preferences(
+        &self,
+        id: i64,
+        sort: CatalogueSort,
+        language: Option<&str>,
+        hide_library: bool,
+    ) -> Result<u64, UserRepositoryError>;
+
+    async fn update_enabled_languages(
+        &self,
+        id: i64,
+        languages: Option<&[String]>,
+    ) -> Result<u64, UserRepositoryError>;
+
+    async fn update_hide_nsfw(&self, id: i64, hide: bool) -> Result<u64, UserRepositoryError>;
+
+    async fn update_device_email(
+        &self,
+        id: i64,
+        email: Option<&str>,
+    ) -> Result<u64, UserRepositoryError>;
+
+    async fn update_data_saver(&self, id: i64, enabled: bool) -> Result<u64, UserRepositoryError>;
+
+    async fn get_category_lock_generation(&self, id: i64) -> Result<i64, UserRepositoryError>;
+
+    /// Returns the new generation
+    async fn increment_category_lock_generation(&self, id: i64)
+        -> Result<i64, UserRepositoryError>;
+
+    async fn update_chapter_languages(
+        &self,
+        id: i64,
+        languages: Option<&[String]>,
+    ) -> Result<u64, UserRepositoryError>;
 }
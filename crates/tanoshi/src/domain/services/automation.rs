@@ -0,0 +1,119 @@
+use crate::domain::{
+    entities::automation::{AutomationAction, AutomationEvent, AutomationRule},
+    repositories::automation::{AutomationRepository, AutomationRepositoryError},
+};
+
+use reqwest::Url;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AutomationError {
+    #[error("rule name cannot be empty")]
+    EmptyName,
+    #[error("invalid webhook url: {0}")]
+    InvalidWebhookUrl(String),
+    #[error("category not found")]
+    CategoryNotFound,
+    #[error("repository error: {0}")]
+    RepositoryError(#[from] AutomationRepositoryError),
+}
+
+#[derive(Clone)]
+pub struct AutomationService<R>
+where
+    R: AutomationRepository,
+{
+    repo: R,
+}
+
+impl<R> AutomationService<R>
+where
+    R: AutomationRepository,
+{
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    async fn validate(
+        &self,
+        user_id: i64,
+        name: &str,
+        action: &AutomationAction,
+    ) -> Result<(), AutomationError> {
+        if name.trim().is_empty() {
+            return Err(AutomationError::EmptyName);
+        }
+
+        if let AutomationAction::Webhook { url } = action {
+            let parsed =
+                Url::parse(url).map_err(|e| AutomationError::InvalidWebhookUrl(e.to_string()))?;
+            if !matches!(parsed.scheme(), "http" | "https") {
+                return Err(AutomationError::InvalidWebhookUrl(format!(
+                    "unsupported scheme {}",
+                    parsed.scheme()
+                )));
+            }
+        }
+
+        // rules only move manga into categories of their owner
+        if let AutomationAction::MoveToCategory {
+            category_id: Some(category_id),
+        } = action
+        {
+            if !self.repo.is_user_category(user_id, *category_id).await? {
+                return Err(AutomationError::CategoryNotFound);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_rules_by_user_id(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<AutomationRule>, AutomationError> {
+        Ok(self.repo.get_rules_by_user_id(user_id).await?)
+    }
+
+    pub async fn create_rule(
+        &self,
+        user_id: i64,
+        name: &str,
+        event: AutomationEvent,
+        action: AutomationAction,
+        enabled: bool,
+    ) -> Result<AutomationRule, AutomationError> {
+        self.validate(user_id, name, &action).await?;
+
+        let id = self
+            .repo
+            .insert_rule(user_id, name.trim(), event, &action, enabled)
+            .await?;
+
+        Ok(self.repo.get_rule_by_id(user_id, id).await?)
+    }
+
+    pub async fn update_rule(
+        &self,
+        user_id: i64,
+        id: i64,
+        name: &str,
+        event: AutomationEvent,
+        action: AutomationAction,
+        enabled: bool,
+    ) -> Result<AutomationRule, AutomationError> {
+        self.validate(user_id, name, &action).await?;
+
+        self.repo
+            .update_rule(user_id, id, name.trim(), event, &action, enabled)
+            .await?;
+
+        Ok(self.repo.get_rule_by_id(user_id, id).await?)
+    }
+
+    pub async fn delete_rule(&self, user_id: i64, id: i64) -> Result<(), AutomationError> {
+        self.repo.delete_rule(user_id, id).await?;
+
+        Ok(())
+    }
+}
@@ -1,11 +1,18 @@
-use std::path::PathBuf;
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, HashMap, HashSet},
+    path::PathBuf,
+};
 
 use crate::{
     domain::{
-        entities::chapter::Chapter,
+        entities::chapter::{is_extra_title, parse_chapter_number, Chapter, SORT_KEY_TOLERANCE},
         repositories::chapter::{ChapterRepository, ChapterRepositoryError},
     },
-    infrastructure::local,
+    infrastructure::{
+        local,
+        storage::{self, Storage},
+    },
 };
 
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
@@ -27,12 +34,292 @@ impl From<JoinError> for ChapterError {
     }
 }
 
+/// Gap between the sort keys of extras following the same chapter
+const EXTRA_SORT_STEP: f64 = 0.001;
+
+/// Set `sort_key` of chapters listed by a source to the number in their title, or the number
+/// the source gave. Extras and chapters with neither come right after the chapter before them
+/// in reading order. A release repeating the number of one listed before it, by the same groups
+/// and in the same language, is dropped. Order of `chapters` is kept
+pub fn normalize_chapters(mut chapters: Vec<Chapter>) -> Vec<Chapter> {
+    let numbers: Vec<Option<f64>> = chapters
+        .iter()
+        .map(|c| {
+            if is_extra_title(&c.title) {
+                return None;
+            }
+            parse_chapter_number(&c.title)
+                .or_else(|| (c.number.is_finite() && c.number > 0.0).then(|| c.number))
+        })
+        .collect();
+
+    // sources list the newest chapter first unless their numbers say otherwise
+    let known: Vec<f64> = numbers.iter().flatten().copied().collect();
+    let ascending =
+        matches!((known.first(), known.last()), (Some(first), Some(last)) if first < last);
+    let mut reading_order: Vec<usize> = (0..chapters.len()).collect();
+    if !ascending {
+        reading_order.reverse();
+    }
+
+    let mut previous = 0.0;
+    let mut extras = 0;
+    for index in reading_order {
+        match numbers[index] {
+            Some(number) => {
+                previous = number;
+                extras = 0;
+                chapters[index].sort_key = number;
+            }
+            None => {
+                extras += 1;
+                chapters[index].sort_key = previous + EXTRA_SORT_STEP * extras as f64;
+            }
+        }
+    }
+
+    let mut releases = HashSet::new();
+    chapters
+        .into_iter()
+        .zip(numbers)
+        .filter(|(c, number)| {
+            number.is_none()
+                || releases.insert((
+                    c.sort_key.to_bits(),
+                    c.scanlator.clone(),
+                    c.groups.clone(),
+                    c.language.clone(),
+                ))
+        })
+        .map(|(c, _)| c)
+        .collect()
+}
+
+/// Outcome of replacing stored chapters of a manga with chapters from source
+#[derive(Debug, Default)]
+pub struct ChapterReconciliation {
+    /// stale chapter id and the chapter its history and download moved to
+    pub migrated: Vec<(i64, i64)>,
+    /// stale chapters with no match and nothing to lose, removed
+    pub removed: Vec<i64>,
+    /// stale chapters with no match kept because they have read history or a download,
+    /// a source listing only part of its chapters doesn't wipe progress
+    pub kept: Vec<i64>,
+}
+
+/// Store `source_chapters` for a manga, then remove chapters the source no longer returns.
+/// Chapters are normalized first, see `normalize_chapters`, so duplicates the source lists
+/// are removed too. Chapters are matched by source path first, a removed chapter with the
+/// same sort key hands over its read history and download to the new one so they are not
+/// lost. Unmatched chapters holding history or a download are kept.
+pub async fn reconcile_chapters<R: ChapterRepository>(
+    repo: &R,
+    source_id: i64,
+    manga_id: i64,
+    source_chapters: &[Chapter],
+) -> Result<ChapterReconciliation, ChapterRepositoryError> {
+    let mut report = ChapterReconciliation::default();
+    if source_chapters.is_empty() {
+        return Ok(report);
+    }
+
+    let source_chapters = normalize_chapters(source_chapters.to_vec());
+    repo.insert_chapters(&source_chapters).await?;
+
+    let paths: Vec<String> = source_chapters.iter().map(|c| c.path.clone()).collect();
+    let stale = repo
+        .get_chapters_not_in_source(source_id, manga_id, &paths)
+        .await?;
+    if stale.is_empty() {
+        return Ok(report);
+    }
+
+    let mut candidates: Vec<Chapter> = repo
+        .get_chapters_by_manga_id(manga_id, None, None, false)
+        .await?
+        .into_iter()
+        .filter(|c| paths.contains(&c.path))
+        .collect();
+
+    for chapter in stale.iter() {
+        let same_number = |c: &Chapter| (c.sort_key - chapter.sort_key).abs() < SORT_KEY_TOLERANCE;
+        let same_release = |c: &Chapter| {
+            c.scanlator == chapter.scanlator
+                && c.groups == chapter.groups
+                && c.language == chapter.language
+        };
+        let found = candidates
+            .iter()
+            .position(|c| same_number(c) && same_release(c))
+            .or_else(|| candidates.iter().position(same_number));
+
+        if let Some(index) = found {
+            let target = candidates.remove(index);
+            repo.migrate_chapter_state(chapter.id, target.id).await?;
+            report.migrated.push((chapter.id, target.id));
+        } else {
+            report.removed.push(chapter.id);
+        }
+    }
+
+    let unmatched = std::mem::take(&mut report.removed);
+    if !unmatched.is_empty() {
+        let read = repo.get_chapter_ids_with_history(&unmatched).await?;
+        let (kept, removed): (Vec<i64>, Vec<i64>) = unmatched.into_iter().partition(|id| {
+            read.contains(id)
+                || stale
+                    .iter()
+                    .any(|c| c.id == *id && c.downloaded_path.is_some())
+        });
+        report.kept = kept;
+        report.removed = removed;
+    }
+
+    let stale_ids: Vec<i64> = report
+        .migrated
+        .iter()
+        .map(|(from_chapter_id, _)| *from_chapter_id)
+        .chain(report.removed.iter().copied())
+        .collect();
+    if !stale_ids.is_empty() {
+        repo.delete_chapter_by_ids(&stale_ids).await?;
+    }
+
+    if !report.migrated.is_empty() || !report.removed.is_empty() || !report.kept.is_empty() {
+        info!(
+            "manga {manga_id}: migrated {} chapters, removed {} chapters, kept {} chapters",
+            report.migrated.len(),
+            report.removed.len(),
+            report.kept.len()
+        );
+    }
+
+    Ok(report)
+}
+
+/// Chapters in `language` released by `group`, `None` matches any
+pub fn filter_chapters(
+    chapters: Vec<Chapter>,
+    language: Option<&str>,
+    group: Option<&str>,
+) -> Vec<Chapter> {
+    chapters
+        .into_iter()
+        .filter(|c| {
+            language
+                .map(|language| c.is_language(language))
+                .unwrap_or(true)
+        })
+        .filter(|c| group.map(|group| c.has_group(group)).unwrap_or(true))
+        .collect()
+}
+
+/// One release per chapter number, from `preferred_group` when it released one, otherwise
+/// from the group releasing most chapters of the manga. Order of `chapters` is kept
+pub fn dedupe_chapters(chapters: Vec<Chapter>, preferred_group: Option<&str>) -> Vec<Chapter> {
+    let mut releases: HashMap<String, usize> = HashMap::new();
+    for group in chapters.iter().flat_map(|c| c.groups.iter()) {
+        *releases.entry(group.to_lowercase()).or_default() += 1;
+    }
+    let rank = |c: &Chapter| {
+        let preferred = preferred_group
+            .map(|group| c.has_group(group))
+            .unwrap_or(false);
+        let releases = c
+            .groups
+            .iter()
+            .filter_map(|group| releases.get(&group.to_lowercase()))
+            .max()
+            .copied()
+            .unwrap_or(0);
+        (preferred, releases)
+    };
+
+    let mut best: HashMap<u64, usize> = HashMap::new();
+    for (index, chapter) in chapters.iter().enumerate() {
+        match best.entry(chapter.sort_key.to_bits()) {
+            Entry::Vacant(entry) => {
+                entry.insert(index);
+            }
+            Entry::Occupied(mut entry) => {
+                if rank(chapter) > rank(&chapters[*entry.get()]) {
+                    entry.insert(index);
+                }
+            }
+        }
+    }
+
+    let keep: HashSet<usize> = best.into_values().collect();
+    chapters
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| keep.contains(index))
+        .map(|(_, chapter)| chapter)
+        .collect()
+}
+
+/// Chapters not released by any of `groups` while one of them released the same number
+pub fn superseded_chapter_ids(chapters: &[Chapter], groups: &[String]) -> HashSet<i64> {
+    let preferred = |c: &Chapter| groups.iter().any(|group| c.has_group(group));
+    let released: HashSet<u64> = chapters
+        .iter()
+        .filter(|c| preferred(c))
+        .map(|c| c.sort_key.to_bits())
+        .collect();
+
+    chapters
+        .iter()
+        .filter(|c| !preferred(c) && released.contains(&c.sort_key.to_bits()))
+        .map(|c| c.id)
+        .collect()
+}
+
+/// One chapter number of a series from the first manga of `series` having it, `series` is
+/// the chapters of every manga in order of preference. Ordered by number descending, with
+/// next and prev leading to the merged chapters
+pub fn merge_series_chapters(series: Vec<Vec<Chapter>>) -> Vec<Chapter> {
+    let mut sources: HashMap<u64, i64> = HashMap::new();
+    for chapter in series.iter().flatten() {
+        sources
+            .entry(chapter.sort_key.to_bits())
+            .or_insert(chapter.manga_id);
+    }
+
+    let mut chapters: Vec<Chapter> = series
+        .into_iter()
+        .flatten()
+        .filter(|c| sources.get(&c.sort_key.to_bits()) == Some(&c.manga_id))
+        .collect();
+    chapters.sort_by(|a, b| {
+        b.sort_key
+            .partial_cmp(&a.sort_key)
+            .unwrap_or(Ordering::Equal)
+    });
+
+    for i in 0..chapters.len() {
+        let sort_key = chapters[i].sort_key;
+        chapters[i].next = chapters[..i]
+            .iter()
+            .rev()
+            .find(|c| c.sort_key > sort_key)
+            .map(|c| c.id);
+        chapters[i].prev = chapters[i + 1..]
+            .iter()
+            .find(|c| c.sort_key < sort_key)
+            .map(|c| c.id);
+    }
+
+    chapters
+}
+
+#[derive(Clone)]
 pub struct ChapterService<R>
 where
     R: ChapterRepository,
 {
     repo: R,
     extension_manager: ExtensionManager,
+    storage: Storage,
 }
 
 impl<R> ChapterService<R>
@@ -43,6 +330,24 @@ where
         Self {
             repo,
             extension_manager,
+            storage: Storage::default(),
+        }
+    }
+
+    /// Service reading downloaded chapters from `storage`
+    pub fn with_storage(self, storage: Storage) -> Self {
+        Self { storage, ..self }
+    }
+
+    /// Service making source calls with a logged in user's session
+    pub fn with_session(&self, session: Option<String>) -> Self
+    where
+        R: Clone,
+    {
+        Self {
+            repo: self.repo.clone(),
+            extension_manager: self.extension_manager.with_session(session),
+            storage: self.storage.clone(),
         }
     }
 
@@ -52,12 +357,49 @@ where
         Ok(chapter)
     }
 
+    pub async fn fetch_chapter_by_source_path(
+        &self,
+        source_id: i64,
+        path: &str,
+    ) -> Result<Chapter, ChapterError> {
+        let chapter = self
+            .repo
+            .get_chapter_by_source_id_path(source_id, path)
+            .await?;
+
+        Ok(chapter)
+    }
+
+    /// Replace stored chapters of a manga with the ones from source, see `reconcile_chapters`
+    pub async fn refresh_chapters(
+        &self,
+        source_id: i64,
+        path: &str,
+        manga_id: i64,
+    ) -> Result<ChapterReconciliation, ChapterError> {
+        let source_chapters: Vec<Chapter> = self
+            .extension_manager
+            .get_chapters(source_id, path.to_string())
+            .await?
+            .into_par_iter()
+            .map(|c| {
+                let mut c: Chapter = c.into();
+                c.manga_id = manga_id;
+                c
+            })
+            .collect();
+
+        Ok(reconcile_chapters(&self.repo, source_id, manga_id, &source_chapters).await?)
+    }
+
+    /// Chapters in any of `languages` or without a language, `None` returns every language
     pub async fn fetch_chapters_by_manga_id(
         &self,
         source_id: i64,
         path: &str,
         manga_id: i64,
         refresh: bool,
+        languages: Option<&[String]>,
     ) -> Result<Vec<Chapter>, ChapterError> {
         let mut chapters = self
             .repo
@@ -66,21 +408,7 @@ where
             .unwrap_or_default();
 
         if refresh || chapters.is_empty() {
-            let source_chapters: Vec<Chapter> = self
-                .extension_manager
-                .get_chapters(source_id, path.to_string())
-                .await?
-                .into_par_iter()
-                .map(|c| {
-                    let mut c: Chapter = c.into();
-                    c.manga_id = manga_id;
-                    c
-                })
-                .collect();
-
-            if !source_chapters.is_empty() {
-                self.repo.insert_chapters(&source_chapters).await?;
-            }
+            self.refresh_chapters(source_id, path, manga_id).await?;
 
             chapters = self
                 .repo
@@ -89,28 +417,90 @@ where
                 .unwrap_or_default();
         }
 
+        if let Some(languages) = languages {
+            chapters.retain(|c| languages.iter().any(|language| c.is_language(language)));
+        }
+
         Ok(chapters)
     }
 
     pub async fn fetch_chapter_pages(
         &self,
-        source_id: i64,
-        path: &str,
-        downloaded_path: &Option<String>,
+        chapter: &Chapter,
     ) -> Result<Vec<String>, ChapterError> {
-        let pages = if let Some(downloaded_path) =
-            downloaded_path.as_ref().map(|p| PathBuf::new().join(p))
+        if let Some(downloaded_path) = chapter.downloaded_path.as_ref() {
+            return self.fetch_downloaded_pages(downloaded_path).await;
+        }
+
+        let err = match self
+            .extension_manager
+            .get_pages(chapter.source_id, chapter.path.clone())
+            .await
         {
-            tokio::task::spawn_blocking(move || {
-                local::get_pages_from_archive(downloaded_path.as_path())
-            })
-            .await??
-        } else {
-            self.extension_manager
-                .get_pages(source_id, path.to_string())
-                .await?
+            Ok(pages) => return Ok(pages),
+            Err(e) => e,
         };
 
+        let alternates = self
+            .repo
+            .get_chapters_from_linked_manga(chapter.manga_id, chapter.sort_key)
+            .await
+            .unwrap_or_default();
+        if alternates.is_empty() {
+            return Err(err.into());
+        }
+
+        error!(
+            "failed to fetch pages for chapter {} from source {}: {err}, trying {} linked chapters",
+            chapter.id,
+            chapter.source_id,
+            alternates.len()
+        );
+
+        let fetch_alternates = alternates.into_iter().map(|alternate| {
+            Box::pin(async move {
+                let pages = if let Some(downloaded_path) = alternate.downloaded_path.as_ref() {
+                    self.fetch_downloaded_pages(downloaded_path).await?
+                } else {
+                    self.extension_manager
+                        .get_pages(alternate.source_id, alternate.path.clone())
+                        .await?
+                };
+
+                if pages.is_empty() {
+                    return Err(ChapterError::Other(anyhow::anyhow!("no pages")));
+                }
+
+                Ok::<_, ChapterError>((alternate, pages))
+            })
+        });
+
+        match futures::future::select_ok(fetch_alternates).await {
+            Ok(((alternate, pages), _)) => {
+                info!(
+                    "chapter {} fallback to chapter {} from source {}",
+                    chapter.id, alternate.id, alternate.source_id
+                );
+                Ok(pages)
+            }
+            Err(_) => Err(err.into()),
+        }
+    }
+
+    async fn fetch_downloaded_pages(
+        &self,
+        downloaded_path: &str,
+    ) -> Result<Vec<String>, ChapterError> {
+        if storage::is_remote(downloaded_path) {
+            return Ok(self.storage.list_archive_pages(downloaded_path).await?);
+        }
+
+        let downloaded_path = PathBuf::new().join(downloaded_path);
+        let pages = tokio::task::spawn_blocking(move || {
+            local::get_pages_from_path(downloaded_path.as_path())
+        })
+        .await??;
+
         Ok(pages)
     }
 
@@ -120,3 +510,90 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+
+    fn chapter(title: &str, number: f64, scanlator: &str) -> Chapter {
+        Chapter {
+            id: 0,
+            source_id: 1,
+            manga_id: 1,
+            title: title.to_string(),
+            path: format!("/{title}/{scanlator}"),
+            number,
+            sort_key: number,
+            scanlator: scanlator.to_string(),
+            groups: vec![scanlator.to_string()],
+            language: None,
+            uploaded: NaiveDateTime::from_timestamp(0, 0),
+            date_added: NaiveDateTime::from_timestamp(0, 0),
+            downloaded_path: None,
+            next: None,
+            prev: None,
+        }
+    }
+
+    fn sort_keys(chapters: &[Chapter]) -> Vec<f64> {
+        chapters.iter().map(|c| c.sort_key).collect()
+    }
+
+    #[test]
+    fn test_normalize_chapters_newest_first() {
+        let chapters = normalize_chapters(vec![
+            chapter("Vol.1 Chapter 3", 0.0, "a"),
+            chapter("Extra", 0.0, "a"),
+            chapter("Ch. 2.5", 0.0, "a"),
+            chapter("Ch. 2", 0.0, "a"),
+            chapter("Ch. 1", 0.0, "a"),
+        ]);
+
+        assert_eq!(
+            sort_keys(&chapters),
+            vec![3.0, 2.5 + EXTRA_SORT_STEP, 2.5, 2.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn test_normalize_chapters_ascending() {
+        let chapters = normalize_chapters(vec![
+            chapter("Ch. 1", 0.0, "a"),
+            chapter("Omake", 0.0, "a"),
+            chapter("Side Story", 0.0, "a"),
+            chapter("Ch. 2", 0.0, "a"),
+        ]);
+
+        assert_eq!(
+            sort_keys(&chapters),
+            vec![1.0, 1.0 + EXTRA_SORT_STEP, 1.0 + 2.0 * EXTRA_SORT_STEP, 2.0]
+        );
+    }
+
+    #[test]
+    fn test_normalize_chapters_source_number_and_oneshot() {
+        let chapters = normalize_chapters(vec![chapter("Prologue", 5.0, "a")]);
+        assert_eq!(sort_keys(&chapters), vec![5.0]);
+
+        let chapters = normalize_chapters(vec![chapter("Oneshot", 1.0, "a")]);
+        assert_eq!(sort_keys(&chapters), vec![EXTRA_SORT_STEP]);
+    }
+
+    #[test]
+    fn test_normalize_chapters_drops_duplicates() {
+        let chapters = normalize_chapters(vec![
+            chapter("Ch. 2", 2.0, "a"),
+            chapter("Chapter 2", 2.0, "a"),
+            chapter("Ch. 2", 2.0, "b"),
+            chapter("Extra", 0.0, "a"),
+            chapter("Extra", 0.0, "a"),
+            chapter("Ch. 1", 1.0, "a"),
+        ]);
+
+        let titles: Vec<&str> = chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, vec!["Ch. 2", "Ch. 2", "Extra", "Extra", "Ch. 1"]);
+        assert_eq!(chapters[1].scanlator, "b");
+    }
+}
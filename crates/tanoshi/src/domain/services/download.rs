@@ -1,11 +1,21 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 use crate::{
     application::worker::downloads::{Command as DownloadCommand, DownloadSender},
     domain::{
-        entities::download::{DownloadQueueEntry, DownloadedChapter},
+        entities::download::{
+            CleanupCandidate, CleanupReason, DownloadQueueEntry, DownloadedChapter,
+            MangaDownloadUsage, RetentionPolicy,
+        },
         repositories::download::{DownloadRepository, DownloadRepositoryError},
     },
+    infrastructure::{
+        page_store,
+        storage::{self, Storage},
+    },
 };
 
 use thiserror::Error;
@@ -18,12 +28,131 @@ pub enum DownloadError {
     OtherError(#[from] anyhow::Error),
 }
 
+/// Bytes a downloaded chapter takes up and when it was last written to
+async fn download_usage(path: &str) -> (u64, Option<SystemTime>) {
+    let metadata = match tokio::fs::metadata(path).await {
+        Ok(metadata) => metadata,
+        Err(_) => return (0, None),
+    };
+    if !metadata.is_dir() {
+        return (metadata.len(), metadata.modified().ok());
+    }
+
+    let mut size = 0;
+    let mut modified = metadata.modified().ok();
+    if let Ok(mut entries) = tokio::fs::read_dir(path).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if let Ok(metadata) = entry.metadata().await {
+                size += metadata.len();
+                modified = modified.max(metadata.modified().ok());
+            }
+        }
+    }
+
+    (size, modified)
+}
+
+/// Downloads `policy` removes: read chapters, then chapters past the per manga limit,
+/// then the least recently written archives until the rest fits the disk quota
+pub async fn plan_cleanup<R: DownloadRepository>(
+    repo: &R,
+    policy: &RetentionPolicy,
+) -> Result<Vec<CleanupCandidate>, DownloadError> {
+    if policy.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut candidates = vec![];
+    let mut kept = vec![];
+    let mut manga_id = None;
+    let mut kept_in_manga = 0;
+    for file in repo.get_downloaded_chapter_files().await? {
+        if manga_id != Some(file.manga_id) {
+            manga_id = Some(file.manga_id);
+            kept_in_manga = 0;
+        }
+
+        // objects in a bucket can't be measured cheaply and a manifest is only a list of pages,
+        // the size recorded on download is used for both
+        let (size, modified) = download_usage(&file.path).await;
+        let size =
+            if storage::is_remote(&file.path) || page_store::is_manifest(Path::new(&file.path)) {
+                file.size.unwrap_or_default().max(0) as u64
+            } else {
+                size
+            };
+
+        let reason = if policy.delete_after_read && file.read {
+            Some(CleanupReason::Read)
+        } else if policy
+            .keep_per_manga
+            .map(|keep| kept_in_manga >= keep)
+            .unwrap_or(false)
+        {
+            Some(CleanupReason::PerMangaLimit)
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => candidates.push(CleanupCandidate { file, size, reason }),
+            None => {
+                kept_in_manga += 1;
+                kept.push((file, size, modified));
+            }
+        }
+    }
+
+    if let Some(max_disk_usage) = policy.max_disk_usage {
+        let mut usage: u64 = kept.iter().map(|(_, size, _)| size).sum();
+        kept.sort_by_key(|(_, _, modified)| *modified);
+        for (file, size, _) in kept {
+            if usage <= max_disk_usage {
+                break;
+            }
+            usage -= size;
+            candidates.push(CleanupCandidate {
+                file,
+                size,
+                reason: CleanupReason::DiskQuota,
+            });
+        }
+    }
+
+    Ok(candidates)
+}
+
+/// Delete the archives of `candidates`, returns how many were removed
+pub async fn apply_cleanup<R: DownloadRepository>(
+    repo: &R,
+    storage: &Storage,
+    candidates: &[CleanupCandidate],
+) -> Result<usize, DownloadError> {
+    let mut removed = 0;
+    for candidate in candidates {
+        if let Err(e) = storage.remove(&candidate.file.path).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                error!("error removing file: {e}");
+                continue;
+            }
+        }
+
+        repo.update_chapter_downloaded_path(candidate.file.chapter_id, None)
+            .await?;
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
+#[derive(Clone)]
 pub struct DownloadService<R>
 where
     R: DownloadRepository,
 {
     repo: R,
     download_sender: DownloadSender,
+    storage: Storage,
 }
 
 impl<R> DownloadService<R>
@@ -34,9 +163,15 @@ where
         Self {
             repo,
             download_sender,
+            storage: Storage::default(),
         }
     }
 
+    /// Service removing downloads from `storage` instead of disk
+    pub fn with_storage(self, storage: Storage) -> Self {
+        Self { storage, ..self }
+    }
+
     pub async fn get_downloaded_chapters(
         &self,
         after_timestamp: i64,
@@ -101,6 +236,45 @@ where
         Ok(())
     }
 
+    /// Check downloaded archives in the background, broken chapters are queued again
+    pub fn verify_downloads(&self) -> Result<(), DownloadError> {
+        self.download_sender
+            .send(DownloadCommand::Verify)
+            .map_err(|_| {
+                DownloadError::OtherError(anyhow::anyhow!("failed to send verify command"))
+            })?;
+
+        Ok(())
+    }
+
+    /// Move downloads into the page store and remove unused pages in the background
+    pub fn compact_downloads(&self) -> Result<(), DownloadError> {
+        self.download_sender
+            .send(DownloadCommand::Compact)
+            .map_err(|_| {
+                DownloadError::OtherError(anyhow::anyhow!("failed to send compact command"))
+            })?;
+
+        Ok(())
+    }
+
+    /// Re-encode the pages of archives in the background when it's configured
+    pub fn optimize_downloads(&self) -> Result<(), DownloadError> {
+        self.download_sender
+            .send(DownloadCommand::Optimize)
+            .map_err(|_| {
+                DownloadError::OtherError(anyhow::anyhow!("failed to send optimize command"))
+            })?;
+
+        Ok(())
+    }
+
+    pub async fn get_download_usage(&self) -> Result<Vec<MangaDownloadUsage>, DownloadError> {
+        let usage = self.repo.get_download_usage().await?;
+
+        Ok(usage)
+    }
+
     pub async fn get_download_queue(&self) -> Result<Vec<DownloadQueueEntry>, DownloadError> {
         let queue = self.repo.get_download_queue().await?;
 
@@ -125,6 +299,26 @@ where
         Ok(())
     }
 
+    /// Queue the chapters of `manga_ids` that aren't downloaded or queued yet,
+    /// returns chapters queued
+    pub async fn download_manga_chapters(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+        unread_only: bool,
+    ) -> Result<u64, DownloadError> {
+        let chapter_ids = self
+            .repo
+            .get_chapter_ids_to_download(user_id, manga_ids, unread_only)
+            .await?;
+        let count = chapter_ids.len() as u64;
+        if count > 0 {
+            self.download_chapters(chapter_ids).await?;
+        }
+
+        Ok(count)
+    }
+
     pub async fn update_chapter_priority(
         &self,
         chapter_id: i64,
@@ -137,6 +331,13 @@ where
         Ok(())
     }
 
+    /// Move `chapter_ids` to the front of the queue in that order
+    pub async fn reorder_queue(&self, chapter_ids: Vec<i64>) -> Result<(), DownloadError> {
+        self.repo.reorder_download_queue(&chapter_ids).await?;
+
+        Ok(())
+    }
+
     pub async fn remove_chapters_from_queue(
         &self,
         chapter_ids: Vec<i64>,
@@ -150,13 +351,68 @@ where
         Ok(())
     }
 
+    /// Keep chapters in queue without the worker downloading them
+    pub async fn pause_chapters(&self, chapter_ids: Vec<i64>) -> Result<(), DownloadError> {
+        for chapter_id in chapter_ids {
+            self.repo
+                .update_download_queue_paused(chapter_id, true)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn resume_chapters(&self, chapter_ids: Vec<i64>) -> Result<(), DownloadError> {
+        for chapter_id in chapter_ids {
+            self.repo
+                .update_download_queue_paused(chapter_id, false)
+                .await?;
+        }
+
+        // worker stops once every remaining entry is paused
+        self.download_sender
+            .send(DownloadCommand::Download)
+            .map_err(|_| {
+                DownloadError::OtherError(anyhow::anyhow!("failed to send download command"))
+            })?;
+
+        Ok(())
+    }
+
+    /// Queue chapters that ran out of attempts again, returns how many
+    pub async fn retry_failed_downloads(&self) -> Result<u64, DownloadError> {
+        let chapters = self.repo.reset_failed_download_queue().await?;
+
+        self.download_sender
+            .send(DownloadCommand::Download)
+            .map_err(|_| {
+                DownloadError::OtherError(anyhow::anyhow!("failed to send download command"))
+            })?;
+
+        Ok(chapters)
+    }
+
+    /// Downloads cleanup would remove under `policy`, nothing is deleted
+    pub async fn plan_cleanup(
+        &self,
+        policy: &RetentionPolicy,
+    ) -> Result<Vec<CleanupCandidate>, DownloadError> {
+        plan_cleanup(&self.repo, policy).await
+    }
+
+    pub async fn cleanup(&self, policy: &RetentionPolicy) -> Result<usize, DownloadError> {
+        let candidates = plan_cleanup(&self.repo, policy).await?;
+
+        apply_cleanup(&self.repo, &self.storage, &candidates).await
+    }
+
     pub async fn remove_downloaded_chapters(
         &self,
         chapter_ids: Vec<i64>,
     ) -> Result<(), DownloadError> {
         for chapter_id in chapter_ids {
             if let Ok(downloaded_path) = self.repo.get_chapter_downloaded_path(chapter_id).await {
-                if let Err(e) = tokio::fs::remove_file(&downloaded_path).await {
+                if let Err(e) = self.storage.remove(&downloaded_path).await {
                     error!("error removing file: {e}");
                 }
             }
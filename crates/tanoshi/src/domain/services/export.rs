@@ -0,0 +1,329 @@
+use std::{
+    cmp::Ordering,
+    convert::TryFrom,
+    io::{Cursor, Seek, Write},
+};
+
+use anyhow::anyhow;
+use tanoshi_vm::prelude::ExtensionManager;
+use thiserror::Error;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    domain::{
+        entities::{
+            chapter::Chapter,
+            image::{Image, ImageUri},
+            manga::Manga,
+        },
+        repositories::{
+            chapter::ChapterRepository,
+            image::{ImageRepository, ImageRepositoryError},
+            library::{LibraryRepository, LibraryRepositoryError},
+            manga::{MangaRepository, MangaRepositoryError},
+        },
+        services::chapter::{ChapterError, ChapterService},
+    },
+    infrastructure::{
+        comic_info::ComicInfo,
+        epub::{EpubMetadata, EpubWriter},
+        path_template::sanitize_component,
+        pdf::PdfWriter,
+    },
+};
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("chapter error: {0}")]
+    ChapterError(#[from] ChapterError),
+    #[error("manga error: {0}")]
+    MangaError(#[from] MangaRepositoryError),
+    #[error("image error: {0}")]
+    ImageError(#[from] ImageRepositoryError),
+    #[error("library error: {0}")]
+    LibraryError(#[from] LibraryRepositoryError),
+    #[error("other error: {0}")]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<zip::result::ZipError> for ExportError {
+    fn from(e: zip::result::ZipError) -> Self {
+        Self::Other(e.into())
+    }
+}
+
+impl From<std::io::Error> for ExportError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Other(e.into())
+    }
+}
+
+/// A chapter packed into a single file
+pub struct ExportedFile {
+    pub filename: String,
+    pub content_type: &'static str,
+    pub data: Vec<u8>,
+}
+
+/// Extension of a page in an export, by its content type then the name it was fetched as
+fn page_extension(image: &Image, uri: &str) -> String {
+    let extension = match image.content_type.as_str() {
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        "image/webp" => "webp",
+        "image/gif" => "gif",
+        "image/avif" => "avif",
+        _ => {
+            return std::path::Path::new(uri.split(&['?', '#'][..]).next().unwrap_or_default())
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| "jpg".to_string())
+        }
+    };
+
+    extension.to_string()
+}
+
+#[derive(Clone)]
+pub struct ExportService<C, M, R, L>
+where
+    C: ChapterRepository,
+    M: MangaRepository,
+    R: ImageRepository,
+    L: LibraryRepository,
+{
+    chapter_svc: ChapterService<C>,
+    manga_repo: M,
+    image_repo: R,
+    library_repo: L,
+    extension_manager: ExtensionManager,
+}
+
+impl<C, M, R, L> ExportService<C, M, R, L>
+where
+    C: ChapterRepository,
+    M: MangaRepository,
+    R: ImageRepository,
+    L: LibraryRepository,
+{
+    pub fn new(
+        chapter_svc: ChapterService<C>,
+        manga_repo: M,
+        image_repo: R,
+        library_repo: L,
+        extension_manager: ExtensionManager,
+    ) -> Self {
+        Self {
+            chapter_svc,
+            manga_repo,
+            image_repo,
+            library_repo,
+            extension_manager,
+        }
+    }
+
+    /// Names of the tags `user_id` put on `manga_id`, exported with the genres
+    async fn tag_names(&self, user_id: i64, manga_id: i64) -> Result<Vec<String>, ExportError> {
+        Ok(self
+            .library_repo
+            .get_tags_by_manga_id(user_id, manga_id)
+            .await?
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect())
+    }
+
+    async fn fetch_page(&self, page: &str, referer: Option<&String>) -> Result<Image, ExportError> {
+        let image = match ImageUri::try_from(page)? {
+            ImageUri::Remote(url) => self.image_repo.fetch_image_from_url(&url, referer).await?,
+            ImageUri::File(path) => self.image_repo.fetch_image_from_file(&path).await?,
+            ImageUri::Archive(archive, filename) => {
+                self.image_repo
+                    .fetch_image_from_archive(&archive, &filename)
+                    .await?
+            }
+            ImageUri::Stored(location, filename) => {
+                self.image_repo
+                    .fetch_image_from_stored_archive(&location, &filename)
+                    .await?
+            }
+            ImageUri::Thumbnail(_) => return Err(anyhow!("{page} is not a page").into()),
+        };
+
+        Ok(image)
+    }
+
+    /// Pack a chapter into a cbz with a `ComicInfo.xml`, from its download if there is one,
+    /// otherwise every page is fetched from the source
+    pub async fn export_cbz(
+        &self,
+        user_id: i64,
+        chapter_id: i64,
+    ) -> Result<ExportedFile, ExportError> {
+        let chapter = self.chapter_svc.fetch_chapter_by_id(chapter_id).await?;
+        let manga = self.manga_repo.get_manga_by_id(chapter.manga_id).await?;
+        let pages = self.chapter_svc.fetch_chapter_pages(&chapter).await?;
+
+        let referer = self
+            .extension_manager
+            .get_source_info(chapter.source_id)
+            .ok()
+            .map(|source| source.url);
+
+        let width = pages.len().to_string().len().max(3);
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        for (i, page) in pages.iter().enumerate() {
+            let image = self.fetch_page(page, referer.as_ref()).await?;
+            let filename = format!("{:0width$}.{}", i + 1, page_extension(&image, page));
+            writer.start_file(
+                filename,
+                FileOptions::default().compression_method(CompressionMethod::Stored),
+            )?;
+            writer.write_all(&image.data)?;
+        }
+
+        let mut comic_info = ComicInfo::new(&manga, &chapter, pages.len());
+        comic_info.tags = self.tag_names(user_id, manga.id).await?;
+        writer.start_file("ComicInfo.xml", Default::default())?;
+        writer.write_all(comic_info.to_xml().as_bytes())?;
+        let data = writer.finish()?.into_inner();
+
+        Ok(ExportedFile {
+            filename: format!(
+                "{}.cbz",
+                sanitize_component(&format!("{} - {}", manga.title, chapter.title), "_")
+            ),
+            content_type: "application/vnd.comicbook+zip",
+            data,
+        })
+    }
+
+    /// Chapters of a single manga in chapter order whatever order they're given in,
+    /// with the manga and the referer to fetch their pages with
+    async fn fetch_chapters(
+        &self,
+        chapter_ids: &[i64],
+    ) -> Result<(Manga, Vec<Chapter>, Option<String>), ExportError> {
+        let mut chapters: Vec<Chapter> = vec![];
+        for chapter_id in chapter_ids {
+            if chapters.iter().any(|chapter| chapter.id == *chapter_id) {
+                continue;
+            }
+            chapters.push(self.chapter_svc.fetch_chapter_by_id(*chapter_id).await?);
+        }
+        chapters.sort_by(|a, b| {
+            a.sort_key
+                .partial_cmp(&b.sort_key)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let first = chapters
+            .first()
+            .ok_or_else(|| anyhow!("no chapters to export"))?;
+        if chapters
+            .iter()
+            .any(|chapter| chapter.manga_id != first.manga_id)
+        {
+            return Err(anyhow!("chapters are from more than one manga").into());
+        }
+
+        let manga = self.manga_repo.get_manga_by_id(first.manga_id).await?;
+        let referer = self
+            .extension_manager
+            .get_source_info(first.source_id)
+            .ok()
+            .map(|source| source.url);
+
+        Ok((manga, chapters, referer))
+    }
+
+    /// Title of an export of `chapters`
+    fn export_title(manga: &Manga, chapters: &[Chapter], volume: Option<f64>) -> String {
+        match (volume, chapters) {
+            (Some(volume), _) => format!("{} - Vol. {volume}", manga.title),
+            (None, [chapter]) => format!("{} - {}", manga.title, chapter.title),
+            (None, [first, .., last]) => {
+                format!("{} - {}-{}", manga.title, first.number, last.number)
+            }
+            (None, []) => manga.title.clone(),
+        }
+    }
+
+    /// Render chapters of a manga into one pdf written to `writer`, in chapter order
+    /// whatever order they're given in, returns the file name with the writer
+    pub async fn export_pdf<W: Write>(
+        &self,
+        chapter_ids: &[i64],
+        rtl: bool,
+        writer: W,
+    ) -> Result<(String, W), ExportError> {
+        let (manga, chapters, referer) = self.fetch_chapters(chapter_ids).await?;
+
+        let mut pdf = PdfWriter::new(writer)?;
+        for chapter in chapters.iter() {
+            for page in self.chapter_svc.fetch_chapter_pages(chapter).await? {
+                let image = self.fetch_page(&page, referer.as_ref()).await?;
+                pdf.add_page(&image.data)?;
+            }
+        }
+        if pdf.page_count() == 0 {
+            return Err(anyhow!("chapters have no pages").into());
+        }
+
+        Ok((
+            format!(
+                "{}.pdf",
+                sanitize_component(&Self::export_title(&manga, &chapters, None), "_")
+            ),
+            pdf.finish(rtl)?,
+        ))
+    }
+
+    /// Render chapters of a manga into one fixed layout epub written to `writer`,
+    /// `volume` names the export and sets its position in the series, tags `user_id` put on
+    /// the manga become subjects
+    pub async fn export_epub<W: Write + Seek>(
+        &self,
+        user_id: i64,
+        chapter_ids: &[i64],
+        volume: Option<f64>,
+        rtl: bool,
+        writer: W,
+    ) -> Result<(String, W), ExportError> {
+        let (manga, chapters, referer) = self.fetch_chapters(chapter_ids).await?;
+
+        let mut epub = EpubWriter::new(writer)?;
+        for chapter in chapters.iter() {
+            epub.begin_chapter(&chapter.title);
+            for page in self.chapter_svc.fetch_chapter_pages(chapter).await? {
+                let image = self.fetch_page(&page, referer.as_ref()).await?;
+                epub.add_page(&image.data)?;
+            }
+        }
+        if epub.page_count() == 0 {
+            return Err(anyhow!("chapters have no pages").into());
+        }
+
+        let title = Self::export_title(&manga, &chapters, volume);
+        let chapter_ids: Vec<String> = chapters.iter().map(|c| c.id.to_string()).collect();
+        let mut metadata = EpubMetadata::new(
+            &manga,
+            format!("urn:tanoshi:{}:{}", manga.id, chapter_ids.join(",")),
+            title.clone(),
+        );
+        metadata.series_index = volume.or_else(|| match chapters.as_slice() {
+            [chapter] => Some(chapter.number),
+            _ => None,
+        });
+        metadata
+            .subjects
+            .extend(self.tag_names(user_id, manga.id).await?);
+        metadata.language = chapters.iter().find_map(|c| c.language.clone());
+        metadata.rtl = rtl;
+
+        Ok((
+            format!("{}.epub", sanitize_component(&title, "_")),
+            epub.finish(&metadata)?,
+        ))
+    }
+}
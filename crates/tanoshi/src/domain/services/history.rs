@@ -1,7 +1,13 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
 use thiserror::Error;
 
 use crate::domain::{
-    entities::{chapter::Chapter, history::HistoryChapter},
+    entities::{
+        chapter::Chapter,
+        history::{DropOff, HistoryChapter},
+    },
     repositories::{
         chapter::{ChapterRepository, ChapterRepositoryError},
         history::{HistoryRepository, HistoryRepositoryError},
@@ -34,9 +40,11 @@ where
         Self { chapter_repo, repo }
     }
 
+    /// Recently read manga, without `hidden_manga_ids`
     pub async fn get_history_chapters(
         &self,
         user_id: i64,
+        hidden_manga_ids: &[i64],
         after_timestamp: i64,
         before_timestamp: i64,
         first: Option<usize>,
@@ -46,6 +54,7 @@ where
             self.repo
                 .get_first_history_chapters(
                     user_id,
+                    hidden_manga_ids,
                     after_timestamp,
                     before_timestamp,
                     first as i32,
@@ -53,17 +62,38 @@ where
                 .await?
         } else if let Some(last) = last {
             self.repo
-                .get_last_history_chapters(user_id, after_timestamp, before_timestamp, last as i32)
+                .get_last_history_chapters(
+                    user_id,
+                    hidden_manga_ids,
+                    after_timestamp,
+                    before_timestamp,
+                    last as i32,
+                )
                 .await?
         } else {
             self.repo
-                .get_history_chapters(user_id, after_timestamp, before_timestamp)
+                .get_history_chapters(user_id, hidden_manga_ids, after_timestamp, before_timestamp)
                 .await?
         };
 
         Ok(histories)
     }
 
+    /// `chapter_ids` with the chapters of linked manga sharing their number, so the
+    /// progress of a series is the same whichever source it's read from
+    async fn with_linked_chapters(
+        &self,
+        mut chapter_ids: Vec<i64>,
+    ) -> Result<Vec<i64>, HistoryError> {
+        let linked = self
+            .chapter_repo
+            .get_linked_chapter_ids(&chapter_ids)
+            .await?;
+        chapter_ids.extend(linked);
+
+        Ok(chapter_ids)
+    }
+
     pub async fn insert_chapter_to_history(
         &self,
         user_id: i64,
@@ -75,6 +105,17 @@ where
             .insert_history_chapter(user_id, chapter_id, page, is_complete)
             .await?;
 
+        // pages differ between sources, only completion is shared
+        if is_complete {
+            let linked = self
+                .chapter_repo
+                .get_linked_chapter_ids(&[chapter_id])
+                .await?;
+            self.repo
+                .insert_history_chapters_as_completed(user_id, &linked)
+                .await?;
+        }
+
         Ok(())
     }
 
@@ -83,6 +124,7 @@ where
         user_id: i64,
         chapter_ids: Vec<i64>,
     ) -> Result<(), HistoryError> {
+        let chapter_ids = self.with_linked_chapters(chapter_ids).await?;
         self.repo
             .insert_history_chapters_as_completed(user_id, &chapter_ids)
             .await?;
@@ -95,6 +137,7 @@ where
         user_id: i64,
         chapter_ids: Vec<i64>,
     ) -> Result<(), HistoryError> {
+        let chapter_ids = self.with_linked_chapters(chapter_ids).await?;
         self.repo
             .delete_chapters_from_history(user_id, &chapter_ids)
             .await?;
@@ -102,6 +145,64 @@ where
         Ok(())
     }
 
+    /// Complete every chapter of `manga_ids`, returns chapters that weren't completed yet
+    pub async fn mark_manga_as_read(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+    ) -> Result<u64, HistoryError> {
+        Ok(self
+            .repo
+            .insert_manga_chapters_as_completed(user_id, manga_ids)
+            .await?)
+    }
+
+    /// Forget reading progress of every chapter of `manga_ids`, returns chapters forgotten
+    pub async fn mark_manga_as_unread(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+    ) -> Result<u64, HistoryError> {
+        Ok(self
+            .repo
+            .delete_manga_chapters_from_history(user_id, manga_ids)
+            .await?)
+    }
+
+    pub async fn get_unread_chapters_by_manga_ids(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+    ) -> Result<HashMap<i64, i64>, HistoryError> {
+        let unread = self
+            .repo
+            .get_unread_chapters_by_manga_ids(user_id, manga_ids)
+            .await?;
+
+        Ok(unread)
+    }
+
+    pub async fn get_drop_off(&self, user_id: i64, manga_id: i64) -> Result<DropOff, HistoryError> {
+        let chapters = self
+            .repo
+            .get_chapter_progress_by_manga_id(user_id, manga_id)
+            .await?;
+
+        Ok(DropOff { manga_id, chapters })
+    }
+
+    /// Library manga with unread chapters that weren't read for `days`
+    pub async fn get_stalled_manga_ids(
+        &self,
+        user_id: i64,
+        days: i64,
+    ) -> Result<Vec<i64>, HistoryError> {
+        let before = Utc::now().naive_utc() - Duration::days(days);
+        let manga_ids = self.repo.get_stalled_manga_ids(user_id, before).await?;
+
+        Ok(manga_ids)
+    }
+
     pub async fn get_next_chapter(
         &self,
         user_id: i64,
@@ -1,13 +1,46 @@
-use crate::domain::{
-    entities::image::{Image, ImageUri},
-    repositories::{
-        image::{ImageRepository, ImageRepositoryError},
-        image_cache::{ImageCacheRepository, ImageCacheRepositoryError},
+use crate::{
+    domain::{
+        entities::image::{
+            Image, ImageCacheStats, ImageHostStats, ImageServedStats, ImageStream, ImageUri,
+        },
+        repositories::{
+            image::{ImageRepository, ImageRepositoryError},
+            image_cache::{ImageCacheRepository, ImageCacheRepositoryError},
+        },
+    },
+    infrastructure::{config::OptimizeFormat, covers::CoverStore, local, optimize, pdf},
+};
+use futures::{stream::BoxStream, StreamExt};
+use image::{imageops::FilterType, DynamicImage, GenericImageView, ImageOutputFormat, RgbImage};
+use sha2::{Digest, Sha256};
+use std::{
+    convert::TryFrom,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
     },
 };
-use std::convert::TryFrom;
 use thiserror::Error;
 
+/// Size of each page thumbnail in a chapter sprite
+pub const SPRITE_TILE_WIDTH: u32 = 80;
+pub const SPRITE_TILE_HEIGHT: u32 = 120;
+/// Thumbnails per row, page `n` is at column `n % SPRITE_COLUMNS` and row `n / SPRITE_COLUMNS`
+pub const SPRITE_COLUMNS: u32 = 10;
+/// Bounds of local cover thumbnails, twice the size covers are shown at in the library
+pub const COVER_WIDTH: u32 = 320;
+pub const COVER_HEIGHT: u32 = 480;
+/// Bounds and quality chapter pages are served at to users saving data
+pub const DATA_SAVER_WIDTH: u32 = 1080;
+pub const DATA_SAVER_QUALITY: u8 = 60;
+/// quality of re-encoded images unless asked for another
+const VARIANT_QUALITY: u8 = 85;
+/// Streamed images are cached when they're at most this many bytes, larger ones are fetched
+/// again every time rather than held in memory
+const STREAM_CACHE_LIMIT: usize = 16 * 1024 * 1024;
+
 #[derive(Debug, Error)]
 pub enum ImageError {
     #[error("error request image")]
@@ -16,10 +49,54 @@ pub enum ImageError {
     RepositoryError(#[from] ImageRepositoryError),
     #[error("cache error: {0}")]
     CacheError(#[from] ImageCacheRepositoryError),
+    #[error("sprite only available for downloaded chapter")]
+    NotDownloaded,
     #[error("other error: {0}")]
     Other(#[from] anyhow::Error),
 }
 
+/// Bounds, quality and format an image is re-encoded with, it's served as it is when all
+/// are empty
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImageVariant {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub quality: Option<u8>,
+    /// jpeg when empty
+    pub format: Option<OptimizeFormat>,
+}
+
+impl ImageVariant {
+    fn is_empty(&self) -> bool {
+        !self.is_resized() && self.quality.is_none() && self.format.is_none()
+    }
+
+    fn is_resized(&self) -> bool {
+        self.width.is_some() || self.height.is_some()
+    }
+}
+
+/// An image read whole, or streamed from a remote source
+pub enum ImageBody {
+    Buffered(Image),
+    Streamed(ImageStream),
+}
+
+/// Body of a streamed image collected on its way to the client, cached once it's complete
+struct Tee<C> {
+    body: BoxStream<'static, Result<bytes::Bytes, std::io::Error>>,
+    buf: Option<Vec<u8>>,
+    cache_repo: C,
+    key: String,
+    content_type: String,
+}
+
+/// Only these are transcoded when an image isn't resized, the others are animated or
+/// already in a small format
+fn is_transcodable(content_type: &str) -> bool {
+    matches!(content_type, "image/jpeg" | "image/jpg" | "image/png")
+}
+
 #[derive(Clone)]
 pub struct ImageService<C, R>
 where
@@ -28,6 +105,9 @@ where
 {
     repo: R,
     cache_repo: C,
+    covers: Option<CoverStore>,
+    served_requests: Arc<AtomicU64>,
+    served_bytes: Arc<AtomicU64>,
 }
 
 impl<C, R> ImageService<C, R>
@@ -36,7 +116,21 @@ where
     R: ImageRepository,
 {
     pub fn new(repo: R, cache_repo: C) -> Self {
-        Self { repo, cache_repo }
+        Self {
+            repo,
+            cache_repo,
+            covers: None,
+            served_requests: Arc::new(AtomicU64::new(0)),
+            served_bytes: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Service keeping covers of library manga in `covers`
+    pub fn with_cover_store(self, covers: CoverStore) -> Self {
+        Self {
+            covers: Some(covers),
+            ..self
+        }
     }
 
     pub async fn fetch_image(
@@ -52,6 +146,15 @@ where
         let uri = ImageUri::from_encrypted(secret, encrypted_url)
             .map_err(|e| ImageError::Other(anyhow::anyhow!("{e}")))?;
 
+        self.fetch_uri(encrypted_url, uri, referer).await
+    }
+
+    async fn fetch_uri(
+        &self,
+        encrypted_url: &str,
+        uri: ImageUri,
+        referer: Option<&String>,
+    ) -> Result<Image, ImageError> {
         let image = match uri {
             ImageUri::Remote(url) => {
                 let image = self.repo.fetch_image_from_url(&url, referer).await?;
@@ -62,14 +165,284 @@ where
                 image
             }
             ImageUri::File(path) => self.repo.fetch_image_from_file(&path).await?,
+            ImageUri::Archive(archive, filename) => {
+                let image = self
+                    .repo
+                    .fetch_image_from_archive(&archive, &filename)
+                    .await?;
+                // a pdf is parsed whole to render any of its pages
+                if pdf::is_pdf(Path::new(&archive)) {
+                    if let Err(e) = self.cache_repo.set(encrypted_url, &image).await {
+                        error!("error cache image {encrypted_url}: {e}");
+                    }
+                }
+
+                image
+            }
+            ImageUri::Stored(location, filename) => {
+                let image = self
+                    .repo
+                    .fetch_image_from_stored_archive(&location, &filename)
+                    .await?;
+                // every page read from a bucket would otherwise fetch the whole archive again
+                if let Err(e) = self.cache_repo.set(encrypted_url, &image).await {
+                    error!("error cache image {encrypted_url}: {e}");
+                }
+
+                image
+            }
+            ImageUri::Thumbnail(uri) => self.fetch_thumbnail(*uri).await?,
+        };
+
+        Ok(image)
+    }
+
+    /// Like [`Self::fetch_image_variant`], except remote images not cached yet are streamed
+    /// from their source. `range` is the `Range` header of the request, passed on to the
+    /// source, only whole images are cached
+    pub async fn fetch_image_body(
+        &self,
+        secret: &str,
+        encrypted_url: &str,
+        referer: Option<&String>,
+        variant: ImageVariant,
+        range: Option<&str>,
+    ) -> Result<ImageBody, ImageError>
+    where
+        C: Clone + Send + Sync + 'static,
+    {
+        if !variant.is_empty() {
+            return self
+                .fetch_image_variant(secret, encrypted_url, referer, variant)
+                .await
+                .map(ImageBody::Buffered);
+        }
+
+        if let Ok(image) = self.cache_repo.get(encrypted_url).await {
+            return Ok(ImageBody::Buffered(image));
+        }
+
+        let url = match ImageUri::from_encrypted(secret, encrypted_url)
+            .map_err(|e| ImageError::Other(anyhow::anyhow!("{e}")))?
+        {
+            ImageUri::Remote(url) => url,
+            uri => {
+                return self
+                    .fetch_uri(encrypted_url, uri, referer)
+                    .await
+                    .map(ImageBody::Buffered)
+            }
+        };
+
+        let stream = self
+            .repo
+            .stream_image_from_url(&url, referer, range)
+            .await?;
+        let too_large = stream
+            .content_length
+            .map(|length| length > STREAM_CACHE_LIMIT as u64)
+            .unwrap_or(false);
+        if range.is_some() || stream.content_range.is_some() || too_large {
+            return Ok(ImageBody::Streamed(stream));
+        }
+
+        let tee = Tee {
+            buf: Some(Vec::with_capacity(
+                stream.content_length.unwrap_or_default() as usize,
+            )),
+            cache_repo: self.cache_repo.clone(),
+            key: encrypted_url.to_string(),
+            content_type: stream.content_type.clone(),
+            body: stream.body,
+        };
+        let body = futures::stream::unfold(tee, |mut tee| async move {
+            match tee.body.next().await {
+                Some(Ok(chunk)) => {
+                    if let Some(buf) = tee.buf.as_mut() {
+                        buf.extend_from_slice(&chunk);
+                    }
+                    if tee.buf.as_ref().map(Vec::len).unwrap_or_default() > STREAM_CACHE_LIMIT {
+                        tee.buf = None;
+                    }
+                    Some((Ok(chunk), tee))
+                }
+                Some(Err(e)) => {
+                    tee.buf = None;
+                    Some((Err(e), tee))
+                }
+                None => {
+                    if let Some(buf) = tee.buf.take() {
+                        let image = Image {
+                            content_type: tee.content_type.clone(),
+                            data: buf.into(),
+                        };
+                        if let Err(e) = tee.cache_repo.set(&tee.key, &image).await {
+                            error!("error cache image {}: {e}", tee.key);
+                        }
+                    }
+                    None
+                }
+            }
+        })
+        .boxed();
+
+        Ok(ImageBody::Streamed(ImageStream { body, ..stream }))
+    }
+
+    /// Like [`Self::fetch_image`], shrunk to fit `variant` and encoded in its format,
+    /// every variant is kept in `variants/` of the cache. An image only transcoded stays as
+    /// it is when it wouldn't get smaller
+    pub async fn fetch_image_variant(
+        &self,
+        secret: &str,
+        encrypted_url: &str,
+        referer: Option<&String>,
+        variant: ImageVariant,
+    ) -> Result<Image, ImageError> {
+        if variant.is_empty() {
+            return self.fetch_image(secret, encrypted_url, referer).await;
+        }
+
+        let width = variant.width.filter(|width| *width > 0).unwrap_or(u32::MAX);
+        let height = variant
+            .height
+            .filter(|height| *height > 0)
+            .unwrap_or(u32::MAX);
+        let quality = variant.quality.unwrap_or(VARIANT_QUALITY).clamp(1, 100);
+        let format = variant.format;
+        let key = format!(
+            "variants/{}",
+            hex::encode(Sha256::digest(format!(
+                "{encrypted_url}:{width}:{height}:{quality}:{}",
+                format.map(|format| format.extension()).unwrap_or("jpeg")
+            )))
+        );
+        if let Ok(image) = self.cache_repo.get(&key).await {
+            return Ok(image);
+        }
+
+        let image = self.fetch_image(secret, encrypted_url, referer).await?;
+        let transcoded_only = !variant.is_resized() && format.is_some();
+        if transcoded_only && !is_transcodable(&image.content_type) {
+            return Ok(image);
+        }
+
+        let data = image.data.clone();
+        let encoded = match tokio::task::spawn_blocking(move || {
+            encode_variant(&data, width, height, quality, format)
+        })
+        .await
+        .map_err(|e| ImageError::Other(e.into()))?
+        {
+            Ok(data) if transcoded_only && data.len() >= image.data.len() => image,
+            Ok(data) => Image {
+                content_type: format
+                    .map(|format| format.content_type())
+                    .unwrap_or("image/jpeg")
+                    .to_string(),
+                data: data.into(),
+            },
+            Err(e) => {
+                debug!("serve {encrypted_url} as it is: {e}");
+                return Ok(image);
+            }
+        };
+
+        if let Err(e) = self.cache_repo.set(&key, &encoded).await {
+            error!("error cache image variant {encrypted_url}: {e}");
+        }
+
+        Ok(encoded)
+    }
+
+    /// Cover of a local manga resized to at most `COVER_WIDTH` by `COVER_HEIGHT`, kept in
+    /// `covers/` of the cache until the file it's from changes
+    async fn fetch_thumbnail(&self, uri: ImageUri) -> Result<Image, ImageError> {
+        let path = match &uri {
+            ImageUri::File(path) | ImageUri::Archive(path, _) => path.clone(),
+            _ => return Err(ImageError::Other(anyhow::anyhow!("not a local image"))),
+        };
+        let modified = tokio::fs::metadata(&path)
+            .await
+            .ok()
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|modified| modified.as_secs())
+            .unwrap_or_default();
+        let key = format!(
+            "covers/{}",
+            hex::encode(Sha256::digest(format!("{}:{modified}", uri.to_string())))
+        );
+        if let Ok(image) = self.cache_repo.get(&key).await {
+            return Ok(image);
+        }
+
+        let image = match uri {
             ImageUri::Archive(archive, filename) => {
                 self.repo
                     .fetch_image_from_archive(&archive, &filename)
                     .await?
             }
+            _ => self.repo.fetch_image_from_file(&path).await?,
+        };
+        let data = image.data.clone();
+        let thumbnail = match tokio::task::spawn_blocking(move || {
+            encode_variant(&data, COVER_WIDTH, COVER_HEIGHT, VARIANT_QUALITY, None)
+        })
+        .await
+        .map_err(|e| ImageError::Other(e.into()))?
+        {
+            Ok(data) => Image {
+                content_type: "image/jpeg".to_string(),
+                data: data.into(),
+            },
+            // still a cover at full size
+            Err(e) => {
+                debug!("serve cover {path} as it is: {e}");
+                return Ok(image);
+            }
         };
 
-        Ok(image)
+        if let Err(e) = self.cache_repo.set(&key, &thumbnail).await {
+            error!("error cache cover {path}: {e}");
+        }
+
+        Ok(thumbnail)
+    }
+
+    pub async fn cache_stats(&self) -> ImageCacheStats {
+        self.cache_repo.stats().await
+    }
+
+    /// Count a response of the image endpoint, its bytes are counted as they're sent
+    pub fn record_served_request(&self) {
+        self.served_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_served_bytes(&self, bytes: u64) {
+        self.served_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn served_stats(&self) -> ImageServedStats {
+        ImageServedStats {
+            requests: self.served_requests.load(Ordering::Relaxed),
+            bytes: self.served_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Requests to every remote image host, the slowest on average first
+    pub fn host_stats(&self) -> Vec<ImageHostStats> {
+        let mut hosts = self.repo.host_stats();
+        hosts.sort_by_key(|host| std::cmp::Reverse(host.average_ms()));
+
+        hosts
+    }
+
+    /// Fetch remote `pages` with `referer` from now on, e.g. the url of their source
+    pub fn set_pages_referer(&self, pages: &[String], referer: &str) {
+        for page in pages.iter().filter(|page| page.starts_with("http")) {
+            self.repo.set_referer(page, referer);
+        }
     }
 
     pub fn encrypt_image_url(&self, secret: &str, url: &str) -> Result<String, ImageError> {
@@ -77,4 +450,177 @@ where
 
         Ok(image_uri.into_encrypted(secret)?)
     }
+
+    /// Like [`Self::encrypt_image_url`], covers from local files are served as thumbnails
+    pub fn encrypt_cover_url(&self, secret: &str, url: &str) -> Result<String, ImageError> {
+        let image_uri = match ImageUri::try_from(url)? {
+            uri @ (ImageUri::File(_) | ImageUri::Archive(..)) => ImageUri::Thumbnail(Box::new(uri)),
+            uri => uri,
+        };
+
+        Ok(image_uri.into_encrypted(secret)?)
+    }
+
+    /// Like [`Self::encrypt_cover_url`], the stored cover of `manga_id` is used over `url`
+    /// when there's one
+    pub fn encrypt_manga_cover_url(
+        &self,
+        secret: &str,
+        manga_id: i64,
+        url: &str,
+    ) -> Result<String, ImageError> {
+        match self
+            .covers
+            .as_ref()
+            .and_then(|covers| covers.path(manga_id))
+        {
+            Some(path) => self.encrypt_cover_url(secret, &path.display().to_string()),
+            None => self.encrypt_cover_url(secret, url),
+        }
+    }
+
+    /// Download the cover of `manga_id` from `cover_url` into the cover store, replacing the
+    /// stored one. Covers of local manga are on disk already and left alone
+    pub async fn store_cover(&self, manga_id: i64, cover_url: &str) -> Result<(), ImageError> {
+        let covers = match self.covers.as_ref() {
+            Some(covers) => covers,
+            None => return Ok(()),
+        };
+        let url = match ImageUri::try_from(cover_url)? {
+            ImageUri::Remote(url) => url,
+            _ => return Ok(()),
+        };
+
+        let image = self.repo.fetch_image_from_url(&url, None).await?;
+        let content_type = image
+            .content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim();
+        // an error page would replace a cover that still works
+        if !content_type.starts_with("image/") {
+            return Err(ImageError::Other(anyhow::anyhow!(
+                "{url} is {content_type}, not an image"
+            )));
+        }
+        covers.save(manga_id, content_type, &image.data).await?;
+
+        Ok(())
+    }
+
+    /// Thumbnails of every page of a downloaded chapter in a single jpeg,
+    /// `encrypted_path` is the encrypted path of the chapter archive or folder
+    pub async fn fetch_chapter_sprite(
+        &self,
+        secret: &str,
+        encrypted_path: &str,
+    ) -> Result<Image, ImageError> {
+        let key = format!("sprite_{encrypted_path}");
+        if let Ok(image) = self.cache_repo.get(&key).await {
+            return Ok(image);
+        }
+
+        let chapter_path = match ImageUri::from_encrypted(secret, encrypted_path)
+            .map_err(|e| ImageError::Other(anyhow::anyhow!("{e}")))?
+        {
+            ImageUri::File(path) => PathBuf::from(path),
+            _ => return Err(ImageError::NotDownloaded),
+        };
+
+        let pages =
+            tokio::task::spawn_blocking(move || local::get_pages_from_path(chapter_path.as_path()))
+                .await
+                .map_err(|e| ImageError::Other(e.into()))??;
+
+        let mut images = vec![];
+        for page in pages.iter() {
+            match ImageUri::try_from(page.as_str()) {
+                Ok(ImageUri::Archive(archive, filename)) => images.push(
+                    self.repo
+                        .fetch_image_from_archive(&archive, &filename)
+                        .await?,
+                ),
+                Ok(ImageUri::File(path)) => {
+                    images.push(self.repo.fetch_image_from_file(&path).await?)
+                }
+                _ => {}
+            }
+        }
+
+        let data = tokio::task::spawn_blocking(move || compose_sprite(&images))
+            .await
+            .map_err(|e| ImageError::Other(e.into()))??;
+
+        let image = Image {
+            content_type: "image/jpeg".to_string(),
+            data: data.into(),
+        };
+        if let Err(e) = self.cache_repo.set(&key, &image).await {
+            error!("error cache sprite {encrypted_path}: {e}");
+        }
+
+        Ok(image)
+    }
+}
+
+/// Shrink `data` to fit within `width` by `height` keeping its aspect ratio and encode it
+/// as `format`, or jpeg without one. Images that already fit keep their size
+fn encode_variant(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    quality: u8,
+    format: Option<OptimizeFormat>,
+) -> Result<Vec<u8>, anyhow::Error> {
+    let image = image::load_from_memory(data)?;
+    let (image_width, image_height) = image.dimensions();
+    let image = if image_width > width || image_height > height {
+        image.resize(width, height, FilterType::Triangle)
+    } else {
+        image
+    };
+
+    if let Some(format) = format {
+        return optimize::encode(&image, format, quality);
+    }
+
+    let mut buf = Cursor::new(vec![]);
+    DynamicImage::ImageRgb8(image.to_rgb8())
+        .write_to(&mut buf, ImageOutputFormat::Jpeg(quality))?;
+
+    Ok(buf.into_inner())
+}
+
+fn compose_sprite(pages: &[Image]) -> Result<Vec<u8>, anyhow::Error> {
+    let count = pages.len().max(1) as u32;
+    let columns = count.min(SPRITE_COLUMNS);
+    let rows = (count + SPRITE_COLUMNS - 1) / SPRITE_COLUMNS;
+
+    let mut sprite = RgbImage::new(columns * SPRITE_TILE_WIDTH, rows * SPRITE_TILE_HEIGHT);
+    for (i, page) in pages.iter().enumerate() {
+        // an undecodable page is left blank so following tiles keep their position
+        let thumbnail = match image::load_from_memory(&page.data) {
+            Ok(image) => image
+                .resize_to_fill(SPRITE_TILE_WIDTH, SPRITE_TILE_HEIGHT, FilterType::Triangle)
+                .to_rgb8(),
+            Err(e) => {
+                debug!("skip page {i} in sprite: {e}");
+                continue;
+            }
+        };
+
+        let i = i as u32;
+        image::imageops::replace(
+            &mut sprite,
+            &thumbnail,
+            ((i % SPRITE_COLUMNS) * SPRITE_TILE_WIDTH) as i64,
+            ((i / SPRITE_COLUMNS) * SPRITE_TILE_HEIGHT) as i64,
+        );
+    }
+
+    let mut buf = Cursor::new(vec![]);
+    DynamicImage::ImageRgb8(sprite).write_to(&mut buf, ImageOutputFormat::Jpeg(75))?;
+
+    Ok(buf.into_inner())
 }
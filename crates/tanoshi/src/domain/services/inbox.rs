@@ -0,0 +1,94 @@
+use crate::domain::{
+    entities::inbox::InboxNotification,
+    repositories::inbox::{InboxRepository, InboxRepositoryError},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum InboxError {
+    #[error("repository error: {0}")]
+    RepositoryError(#[from] InboxRepositoryError),
+}
+
+#[derive(Clone)]
+pub struct InboxService<R>
+where
+    R: InboxRepository,
+{
+    repo: R,
+}
+
+impl<R> InboxService<R>
+where
+    R: InboxRepository,
+{
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_notifications(
+        &self,
+        user_id: i64,
+        unread_only: bool,
+        after_timestamp: i64,
+        after_id: i64,
+        before_timestamp: i64,
+        before_id: i64,
+        first: Option<usize>,
+        last: Option<usize>,
+    ) -> Result<Vec<InboxNotification>, InboxError> {
+        let notifications = if let Some(first) = first {
+            self.repo
+                .get_first_notifications(
+                    user_id,
+                    unread_only,
+                    after_timestamp,
+                    after_id,
+                    before_timestamp,
+                    before_id,
+                    first as i32,
+                )
+                .await?
+        } else if let Some(last) = last {
+            self.repo
+                .get_last_notifications(
+                    user_id,
+                    unread_only,
+                    after_timestamp,
+                    after_id,
+                    before_timestamp,
+                    before_id,
+                    last as i32,
+                )
+                .await?
+        } else {
+            self.repo
+                .get_notifications(
+                    user_id,
+                    unread_only,
+                    after_timestamp,
+                    after_id,
+                    before_timestamp,
+                    before_id,
+                )
+                .await?
+        };
+
+        Ok(notifications)
+    }
+
+    pub async fn get_unread_count(&self, user_id: i64) -> Result<i64, InboxError> {
+        Ok(self.repo.get_unread_count(user_id).await?)
+    }
+
+    /// Mark `ids` as read, or every notification of the user when `ids` is `None`
+    pub async fn mark_as_read(&self, user_id: i64, ids: Option<&[i64]>) -> Result<u64, InboxError> {
+        let rows_affected = match ids {
+            Some(ids) => self.repo.mark_as_read(user_id, ids).await?,
+            None => self.repo.mark_all_as_read(user_id).await?,
+        };
+
+        Ok(rows_affected)
+    }
+}
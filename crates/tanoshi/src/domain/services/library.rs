@@ -1,15 +1,241 @@
+use std::collections::{HashMap, HashSet};
+
 use crate::domain::{
     entities::{
-        library::{Category, LibraryUpdate},
+        library::{
+            AutoDownload, Category, ChapterAddition, LibraryAddition, LibraryQuery, LibraryUpdate,
+            ReadingList, SmartRules, Tag,
+        },
         manga::Manga,
     },
     repositories::library::{LibraryRepository, LibraryRepositoryError},
+    services::user::normalize_languages,
 };
 
+use rand::RngCore;
 use thiserror::Error;
 
+/// upper bound of chapters kept downloaded ahead per manga
+const MAX_DOWNLOAD_AHEAD: i64 = 50;
+
+fn validate_auto_download(auto_download: &AutoDownload) -> Result<(), LibraryError> {
+    if auto_download.limit.map(|limit| limit < 1).unwrap_or(false) {
+        return Err(LibraryError::InvalidAutoDownloadLimit);
+    }
+
+    Ok(())
+}
+
+/// `ids` with `inserted` moved or added at `position`, or at the end when `None` or past it
+fn insert_at(mut ids: Vec<i64>, inserted: &[i64], position: Option<i64>) -> Vec<i64> {
+    let mut unique = Vec::with_capacity(inserted.len());
+    for id in inserted {
+        if !unique.contains(id) {
+            unique.push(*id);
+        }
+    }
+
+    ids.retain(|id| !unique.contains(id));
+    let position = position
+        .map(|position| (position.max(0) as usize).min(ids.len()))
+        .unwrap_or_else(|| ids.len());
+    ids.splice(position..position, unique);
+
+    ids
+}
+
+/// Letters and digits of `title` in lowercase, sources differ in punctuation and spacing
+fn normalize_title(title: &str) -> String {
+    title
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn bigrams(title: &str) -> Vec<(char, char)> {
+    let chars: Vec<char> = title.chars().collect();
+    let mut bigrams: Vec<(char, char)> = chars.windows(2).map(|w| (w[0], w[1])).collect();
+    bigrams.sort_unstable();
+    bigrams
+}
+
+/// Similarity of two titles from 0 to 1, ignoring case, punctuation and spacing
+pub fn title_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize_title(a), normalize_title(b));
+    if !a.is_empty() && a == b {
+        return 1.0;
+    }
+
+    dice(&bigrams(&a), &bigrams(&b))
+}
+
+/// Dice coefficient of two sorted bigram lists, from 0 to 1
+fn dice(a: &[(char, char)], b: &[(char, char)]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    let (mut i, mut j, mut common) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                common += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+
+    (2 * common) as f64 / (a.len() + b.len()) as f64
+}
+
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Normalized title with its bigrams
+type Title = (String, Vec<(char, char)>);
+
+fn is_similar(a: &Title, b: &Title, threshold: f64) -> bool {
+    a.0 == b.0 || {
+        let (len_a, len_b) = (a.1.len(), b.1.len());
+        // titles too different in length can't reach the threshold
+        (2 * len_a.min(len_b)) as f64 / (len_a + len_b).max(1) as f64 >= threshold
+            && dice(&a.1, &b.1) >= threshold
+    }
+}
+
+/// Groups of `manga` from different sources with titles or alternative titles at least
+/// `threshold` similar, pairs in `ignored` aren't grouped together
+fn group_duplicates(
+    manga: Vec<Manga>,
+    ignored: &HashSet<(i64, i64)>,
+    threshold: f64,
+) -> Vec<Vec<Manga>> {
+    let titles: Vec<Vec<Title>> = manga
+        .iter()
+        .map(|m| {
+            std::iter::once(&m.title)
+                .chain(m.alt_titles.iter())
+                .map(|title| normalize_title(title))
+                .filter(|title| !title.is_empty())
+                .map(|title| {
+                    let bigrams = bigrams(&title);
+                    (title, bigrams)
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut parents: Vec<usize> = (0..manga.len()).collect();
+    for i in 0..manga.len() {
+        for j in i + 1..manga.len() {
+            let (a, b) = (&manga[i], &manga[j]);
+            if a.source_id == b.source_id || ignored.contains(&(a.id.min(b.id), a.id.max(b.id))) {
+                continue;
+            }
+
+            let similar = titles[i]
+                .iter()
+                .any(|a| titles[j].iter().any(|b| is_similar(a, b, threshold)));
+            if similar {
+                let (root_i, root_j) = (find(&mut parents, i), find(&mut parents, j));
+                parents[root_j] = root_i;
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<Manga>> = HashMap::new();
+    for (i, m) in manga.into_iter().enumerate() {
+        let root = find(&mut parents, i);
+        groups.entry(root).or_default().push(m);
+    }
+
+    let mut groups: Vec<Vec<Manga>> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect();
+    groups.sort_by(|a, b| a[0].title.cmp(&b[0].title));
+
+    groups
+}
+
+/// Categories ordered so each one is followed by the categories nested in it
+fn nest_categories(categories: &[Category]) -> Vec<Category> {
+    fn push_nested(categories: &[Category], parent_id: Option<i64>, nested: &mut Vec<Category>) {
+        for category in categories.iter().filter(|c| c.parent_id == parent_id) {
+            nested.push(category.clone());
+            push_nested(categories, category.id, nested);
+        }
+    }
+
+    let mut nested = Vec::with_capacity(categories.len());
+    push_nested(categories, None, &mut nested);
+    nested
+}
+
+/// Category `id` followed by the categories it is nested in, closest first
+fn category_ancestors(categories: &[Category], id: i64) -> Vec<Category> {
+    let mut ancestors: Vec<Category> = vec![];
+    let mut next = Some(id);
+    while let Some(id) = next {
+        match categories.iter().find(|c| c.id == Some(id)) {
+            Some(category) if ancestors.len() < categories.len() => {
+                next = category.parent_id;
+                ancestors.push(category.clone());
+            }
+            _ => break,
+        }
+    }
+
+    ancestors
+}
+
+fn validate_rules(rules: &SmartRules) -> Result<(), LibraryError> {
+    if rules.last_read_days.map(|days| days < 1).unwrap_or(false) {
+        return Err(LibraryError::InvalidLastReadDays);
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Error)]
 pub enum LibraryError {
+    #[error("download ahead must be between 0 and 50")]
+    InvalidDownloadAhead,
+    #[error("auto download limit must be at least 1")]
+    InvalidAutoDownloadLimit,
+    #[error("last read days must be at least 1")]
+    InvalidLastReadDays,
+    #[error("similarity threshold must be between 0 and 1")]
+    InvalidThreshold,
+    #[error("tag name can't be empty")]
+    EmptyTagName,
+    #[error("reading list name can't be empty")]
+    EmptyReadingListName,
+    #[error("manga is not in reading list")]
+    NotInReadingList,
+    #[error("category not found")]
+    CategoryNotFound,
+    #[error("smart categories can't be nested")]
+    NestedSmartCategory,
+    #[error("category can't be nested in itself or its subcategories")]
+    InvalidParentCategory,
+    #[error("smart categories can't be the default category")]
+    SmartDefaultCategory,
+    #[error("pin must be 4 to 32 characters")]
+    InvalidPin,
+    #[error("incorrect pin")]
+    WrongPin,
+    #[error("other error: {0}")]
+    Other(String),
     #[error("repository error: {0}")]
     RepositoryError(#[from] LibraryRepositoryError),
 }
@@ -37,7 +263,7 @@ where
 
         let others = self.repo.get_categories_by_user_id(user_id).await?;
 
-        categories.extend_from_slice(&others);
+        categories.extend(nest_categories(&others));
 
         Ok(categories)
     }
@@ -46,10 +272,7 @@ where
         let category = if let Some(id) = id {
             self.repo.get_category_by_id(id).await?
         } else {
-            Category {
-                id: None,
-                name: "Default".to_string(),
-            }
+            Category::default()
         };
 
         Ok(category)
@@ -58,9 +281,24 @@ where
     pub async fn create_category(
         &self,
         user_id: i64,
+        parent_id: Option<i64>,
         name: &str,
+        rules: Option<SmartRules>,
     ) -> Result<Category, LibraryError> {
-        let category = self.repo.create_category(user_id, name).await?;
+        if let Some(rules) = rules.as_ref() {
+            validate_rules(rules)?;
+        }
+        if let Some(parent_id) = parent_id {
+            let parent = self.get_user_category(user_id, parent_id).await?;
+            if rules.is_some() || parent.rules.is_some() {
+                return Err(LibraryError::NestedSmartCategory);
+            }
+        }
+
+        let category = self
+            .repo
+            .create_category(user_id, parent_id, name, rules.as_ref())
+            .await?;
 
         Ok(category)
     }
@@ -77,25 +315,467 @@ where
         Ok(())
     }
 
-    pub async fn get_manga_from_library_by_category_id(
+    /// Category owned by `user_id`, default category is never private
+    pub async fn get_user_category(&self, user_id: i64, id: i64) -> Result<Category, LibraryError> {
+        self.repo
+            .get_categories_by_user_id(user_id)
+            .await?
+            .into_iter()
+            .find(|category| category.id == Some(id))
+            .ok_or(LibraryError::CategoryNotFound)
+    }
+
+    /// Category `id` owned by `user_id` followed by the categories it is nested in, closest first
+    pub async fn get_category_ancestors(
         &self,
         user_id: i64,
-        category_id: Option<i64>,
+        id: i64,
+    ) -> Result<Vec<Category>, LibraryError> {
+        let categories = self.repo.get_categories_by_user_id(user_id).await?;
+        let ancestors = category_ancestors(&categories, id);
+        if ancestors.is_empty() {
+            return Err(LibraryError::CategoryNotFound);
+        }
+
+        Ok(ancestors)
+    }
+
+    /// Nest category `id` in `parent_id`, `None` moves it to the top level
+    pub async fn set_category_parent(
+        &self,
+        user_id: i64,
+        id: i64,
+        parent_id: Option<i64>,
+    ) -> Result<Category, LibraryError> {
+        let categories = self.repo.get_categories_by_user_id(user_id).await?;
+        let category = categories
+            .iter()
+            .find(|category| category.id == Some(id))
+            .ok_or(LibraryError::CategoryNotFound)?;
+
+        if let Some(parent_id) = parent_id {
+            let ancestors = category_ancestors(&categories, parent_id);
+            let parent = ancestors.first().ok_or(LibraryError::CategoryNotFound)?;
+            if ancestors.iter().any(|ancestor| ancestor.id == Some(id)) {
+                return Err(LibraryError::InvalidParentCategory);
+            }
+            if category.rules.is_some() || parent.rules.is_some() {
+                return Err(LibraryError::NestedSmartCategory);
+            }
+        }
+
+        Ok(self
+            .repo
+            .set_category_parent(user_id, id, parent_id)
+            .await?)
+    }
+
+    /// Put `ids` first in the given order, the rest of the categories keep their order after them
+    pub async fn reorder_categories(
+        &self,
+        user_id: i64,
+        ids: &[i64],
+    ) -> Result<Vec<Category>, LibraryError> {
+        let categories = self.repo.get_categories_by_user_id(user_id).await?;
+        let mut ordered_ids = vec![];
+        for id in ids {
+            if !categories.iter().any(|category| category.id == Some(*id)) {
+                return Err(LibraryError::CategoryNotFound);
+            }
+            if !ordered_ids.contains(id) {
+                ordered_ids.push(*id);
+            }
+        }
+        ordered_ids.extend(
+            categories
+                .iter()
+                .filter_map(|category| category.id)
+                .filter(|id| !ids.contains(id)),
+        );
+
+        self.repo
+            .set_category_positions(user_id, &ordered_ids)
+            .await?;
+
+        self.get_categories_by_user_id(user_id).await
+    }
+
+    /// `None` unsets the default category, manga added without categories are left uncategorized
+    pub async fn set_default_category(
+        &self,
+        user_id: i64,
+        id: Option<i64>,
+    ) -> Result<(), LibraryError> {
+        if let Some(id) = id {
+            if self.get_user_category(user_id, id).await?.rules.is_some() {
+                return Err(LibraryError::SmartDefaultCategory);
+            }
+        }
+
+        self.repo.set_default_category(user_id, id).await?;
+
+        Ok(())
+    }
+
+    /// `pin` of `Some("")` removes the pin, `None` keeps the current one
+    pub async fn set_category_privacy(
+        &self,
+        user_id: i64,
+        id: i64,
+        is_private: bool,
+        pin: Option<&str>,
+    ) -> Result<Category, LibraryError> {
+        let category = self.get_user_category(user_id, id).await?;
+
+        let pin = match pin.map(|pin| pin.trim()) {
+            Some("") => None,
+            Some(pin) => {
+                if !(4..=32).contains(&pin.len()) {
+                    return Err(LibraryError::InvalidPin);
+                }
+
+                let mut salt: [u8; 32] = [0; 32];
+                rand::thread_rng().fill_bytes(&mut salt);
+
+                let config = argon2::Config::default();
+                Some(
+                    argon2::hash_encoded(pin.as_bytes(), &salt, &config)
+                        .map_err(|e| LibraryError::Other(format!("{e}")))?,
+                )
+            }
+            None => category.pin,
+        };
+
+        Ok(self
+            .repo
+            .set_category_privacy(user_id, id, is_private, pin.as_deref())
+            .await?)
+    }
+
+    /// `None` turns a smart category back into a manual one, manga are added to it by hand again
+    pub async fn set_category_rules(
+        &self,
+        user_id: i64,
+        id: i64,
+        rules: Option<SmartRules>,
+    ) -> Result<Category, LibraryError> {
+        if let Some(rules) = rules.as_ref() {
+            validate_rules(rules)?;
+
+            let categories = self.repo.get_categories_by_user_id(user_id).await?;
+            let category = categories
+                .iter()
+                .find(|category| category.id == Some(id))
+                .ok_or(LibraryError::CategoryNotFound)?;
+            if category.parent_id.is_some() || categories.iter().any(|c| c.parent_id == Some(id)) {
+                return Err(LibraryError::NestedSmartCategory);
+            }
+        } else {
+            self.get_user_category(user_id, id).await?;
+        }
+
+        Ok(self
+            .repo
+            .set_category_rules(user_id, id, rules.as_ref())
+            .await?)
+    }
+
+    pub fn verify_category_pin(&self, category: &Category, pin: &str) -> Result<(), LibraryError> {
+        let hash = category.pin.as_ref().ok_or(LibraryError::WrongPin)?;
+
+        if !argon2::verify_encoded(hash, pin.trim().as_bytes())
+            .map_err(|e| LibraryError::Other(format!("{e}")))?
+        {
+            return Err(LibraryError::WrongPin);
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_tags_by_user_id(&self, user_id: i64) -> Result<Vec<Tag>, LibraryError> {
+        Ok(self.repo.get_tags_by_user_id(user_id).await?)
+    }
+
+    pub async fn create_tag(&self, user_id: i64, name: &str) -> Result<Tag, LibraryError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(LibraryError::EmptyTagName);
+        }
+
+        Ok(self.repo.create_tag(user_id, name).await?)
+    }
+
+    pub async fn rename_tag(&self, user_id: i64, id: i64, name: &str) -> Result<Tag, LibraryError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(LibraryError::EmptyTagName);
+        }
+
+        Ok(self.repo.rename_tag(user_id, id, name).await?)
+    }
+
+    pub async fn delete_tag(&self, user_id: i64, id: i64) -> Result<(), LibraryError> {
+        Ok(self.repo.delete_tag(user_id, id).await?)
+    }
+
+    pub async fn get_library_tags(
+        &self,
+        user_id: i64,
+    ) -> Result<HashMap<i64, Vec<Tag>>, LibraryError> {
+        Ok(self.repo.get_library_tags(user_id).await?)
+    }
+
+    pub async fn get_tags_by_manga_id(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Vec<Tag>, LibraryError> {
+        Ok(self.repo.get_tags_by_manga_id(user_id, manga_id).await?)
+    }
+
+    pub async fn set_manga_tags(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        tag_ids: &[i64],
+    ) -> Result<Vec<Tag>, LibraryError> {
+        self.repo.set_manga_tags(user_id, manga_id, tag_ids).await?;
+
+        Ok(self.repo.get_tags_by_manga_id(user_id, manga_id).await?)
+    }
+
+    pub async fn get_reading_lists(&self, user_id: i64) -> Result<Vec<ReadingList>, LibraryError> {
+        Ok(self.repo.get_reading_lists(user_id).await?)
+    }
+
+    pub async fn get_reading_list(
+        &self,
+        user_id: i64,
+        id: i64,
+    ) -> Result<ReadingList, LibraryError> {
+        Ok(self.repo.get_reading_list(user_id, id).await?)
+    }
+
+    pub async fn get_reading_lists_by_manga_id(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Vec<ReadingList>, LibraryError> {
+        Ok(self
+            .repo
+            .get_reading_lists_by_manga_id(user_id, manga_id)
+            .await?)
+    }
+
+    pub async fn create_reading_list(
+        &self,
+        user_id: i64,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<ReadingList, LibraryError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(LibraryError::EmptyReadingListName);
+        }
+        let description = description
+            .map(str::trim)
+            .filter(|description| !description.is_empty());
+
+        Ok(self
+            .repo
+            .create_reading_list(user_id, name, description)
+            .await?)
+    }
+
+    pub async fn update_reading_list(
+        &self,
+        user_id: i64,
+        id: i64,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<ReadingList, LibraryError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(LibraryError::EmptyReadingListName);
+        }
+        let description = description
+            .map(str::trim)
+            .filter(|description| !description.is_empty());
+
+        Ok(self
+            .repo
+            .update_reading_list(user_id, id, name, description)
+            .await?)
+    }
+
+    pub async fn delete_reading_list(&self, user_id: i64, id: i64) -> Result<(), LibraryError> {
+        Ok(self.repo.delete_reading_list(user_id, id).await?)
+    }
+
+    pub async fn get_manga_from_reading_list(
+        &self,
+        user_id: i64,
+        id: i64,
     ) -> Result<Vec<Manga>, LibraryError> {
-        let manga = self
+        Ok(self.repo.get_manga_from_reading_list(user_id, id).await?)
+    }
+
+    async fn get_reading_list_manga_ids(
+        &self,
+        user_id: i64,
+        id: i64,
+    ) -> Result<Vec<i64>, LibraryError> {
+        Ok(self
             .repo
-            .get_manga_from_library_by_category_id(user_id, category_id)
+            .get_manga_from_reading_list(user_id, id)
+            .await?
+            .into_iter()
+            .map(|m| m.id)
+            .collect())
+    }
+
+    /// Add `manga_ids` to a reading list at `position` counted from 0, or at its end when
+    /// `None`, manga already in it are moved there
+    pub async fn add_to_reading_list(
+        &self,
+        user_id: i64,
+        id: i64,
+        manga_ids: &[i64],
+        position: Option<i64>,
+    ) -> Result<ReadingList, LibraryError> {
+        let current = self.get_reading_list_manga_ids(user_id, id).await?;
+        let manga_ids = insert_at(current, manga_ids, position);
+        self.repo
+            .set_reading_list_manga(user_id, id, &manga_ids)
+            .await?;
+
+        Ok(self.repo.get_reading_list(user_id, id).await?)
+    }
+
+    pub async fn remove_from_reading_list(
+        &self,
+        user_id: i64,
+        id: i64,
+        manga_ids: &[i64],
+    ) -> Result<ReadingList, LibraryError> {
+        let mut current = self.get_reading_list_manga_ids(user_id, id).await?;
+        current.retain(|manga_id| !manga_ids.contains(manga_id));
+        self.repo
+            .set_reading_list_manga(user_id, id, &current)
+            .await?;
+
+        Ok(self.repo.get_reading_list(user_id, id).await?)
+    }
+
+    /// Move a manga of a reading list to `position` counted from 0
+    pub async fn move_in_reading_list(
+        &self,
+        user_id: i64,
+        id: i64,
+        manga_id: i64,
+        position: i64,
+    ) -> Result<ReadingList, LibraryError> {
+        let current = self.get_reading_list_manga_ids(user_id, id).await?;
+        if !current.contains(&manga_id) {
+            return Err(LibraryError::NotInReadingList);
+        }
+        let manga_ids = insert_at(current, &[manga_id], Some(position));
+        self.repo
+            .set_reading_list_manga(user_id, id, &manga_ids)
+            .await?;
+
+        Ok(self.repo.get_reading_list(user_id, id).await?)
+    }
+
+    /// Replace manga of a reading list with `manga_ids` in their order
+    pub async fn set_reading_list_manga(
+        &self,
+        user_id: i64,
+        id: i64,
+        manga_ids: &[i64],
+    ) -> Result<ReadingList, LibraryError> {
+        let manga_ids = insert_at(vec![], manga_ids, None);
+        self.repo
+            .set_reading_list_manga(user_id, id, &manga_ids)
             .await?;
 
+        Ok(self.repo.get_reading_list(user_id, id).await?)
+    }
+
+    pub async fn get_hidden_manga_ids(
+        &self,
+        user_id: i64,
+        unlocked_category_ids: &[i64],
+    ) -> Result<HashSet<i64>, LibraryError> {
+        Ok(self
+            .repo
+            .get_hidden_manga_ids(user_id, unlocked_category_ids)
+            .await?
+            .into_iter()
+            .collect())
+    }
+
+    pub async fn get_manga_from_library_by_category_id(
+        &self,
+        user_id: i64,
+        category_id: Option<i64>,
+        query: &LibraryQuery,
+    ) -> Result<Vec<Manga>, LibraryError> {
+        let rules = match category_id {
+            Some(id) => self.repo.get_category_by_id(id).await?.rules,
+            None => None,
+        };
+
+        let manga = match rules {
+            Some(rules) => {
+                self.repo
+                    .get_manga_from_library_by_rules(user_id, &rules, query)
+                    .await?
+            }
+            None => {
+                self.repo
+                    .get_manga_from_library_by_category_id(user_id, category_id, query)
+                    .await?
+            }
+        };
+
+        Ok(manga)
+    }
+
+    pub async fn get_manga_from_library(&self, user_id: i64) -> Result<Vec<Manga>, LibraryError> {
+        let manga = self.repo.get_manga_from_library(user_id).await?;
+
         Ok(manga)
     }
 
+    pub async fn get_library_manga_count(&self) -> Result<i64, LibraryError> {
+        Ok(self.repo.get_library_manga_count().await?)
+    }
+
+    /// `None` adds the manga to the default category of the user, if there is one
     pub async fn insert_manga_to_library(
         &self,
         user_id: i64,
         manga_id: i64,
-        category_ids: Vec<i64>,
+        category_ids: Option<Vec<i64>>,
     ) -> Result<(), LibraryError> {
+        let categories = self.repo.get_categories_by_user_id(user_id).await?;
+        let mut category_ids = category_ids.unwrap_or_else(|| {
+            categories
+                .iter()
+                .filter(|category| category.is_default)
+                .filter_map(|category| category.id)
+                .collect()
+        });
+
+        // smart categories pick their manga themselves
+        let smart_category_ids: HashSet<i64> = categories
+            .into_iter()
+            .filter(|category| category.rules.is_some())
+            .filter_map(|category| category.id)
+            .collect();
+        category_ids.retain(|id| !smart_category_ids.contains(id));
+
         self.repo
             .insert_manga_to_library(user_id, manga_id, &category_ids)
             .await?;
@@ -115,9 +795,93 @@ where
         Ok(())
     }
 
+    /// Move every one of `manga_ids` to `category_ids`, empty moves them to the default
+    /// category, returns manga moved
+    pub async fn move_manga_to_categories(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+        category_ids: &[i64],
+    ) -> Result<u64, LibraryError> {
+        Ok(self
+            .repo
+            .set_many_manga_categories(user_id, manga_ids, category_ids)
+            .await?)
+    }
+
+    /// Returns manga removed
+    pub async fn delete_many_manga_from_library(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+    ) -> Result<u64, LibraryError> {
+        Ok(self
+            .repo
+            .delete_many_manga_from_library(user_id, manga_ids)
+            .await?)
+    }
+
+    /// Manga in library from different sources with titles at least `threshold` similar,
+    /// grouped, except the ones ignored together
+    pub async fn get_duplicates(
+        &self,
+        user_id: i64,
+        threshold: f64,
+    ) -> Result<Vec<Vec<Manga>>, LibraryError> {
+        if !(0.0..=1.0).contains(&threshold) {
+            return Err(LibraryError::InvalidThreshold);
+        }
+
+        let manga = self.repo.get_manga_from_library(user_id).await?;
+        let ignored: HashSet<(i64, i64)> = self
+            .repo
+            .get_ignored_duplicates(user_id)
+            .await?
+            .into_iter()
+            .collect();
+
+        Ok(group_duplicates(manga, &ignored, threshold))
+    }
+
+    /// Stop reporting `manga_ids` as duplicates of each other
+    pub async fn ignore_duplicates(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+    ) -> Result<(), LibraryError> {
+        self.repo.ignore_duplicates(user_id, manga_ids).await?;
+
+        Ok(())
+    }
+
+    /// Keep `manga_id` in library with what the user had on `duplicate_ids`, returns
+    /// duplicates removed. Migrating to another source is merging into its manga
+    pub async fn merge_duplicates(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        duplicate_ids: &[i64],
+    ) -> Result<u64, LibraryError> {
+        let duplicate_ids: Vec<i64> = duplicate_ids
+            .iter()
+            .copied()
+            .filter(|id| *id != manga_id)
+            .collect();
+        if duplicate_ids.is_empty() {
+            return Ok(0);
+        }
+
+        Ok(self
+            .repo
+            .merge_library_manga(user_id, manga_id, &duplicate_ids)
+            .await?)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_library_recent_updates(
         &self,
         user_id: i64,
+        unlocked_category_ids: &[i64],
         after_timestamp: i64,
         after_id: i64,
         before_timestamp: i64,
@@ -129,6 +893,7 @@ where
             self.repo
                 .get_first_library_updates(
                     user_id,
+                    unlocked_category_ids,
                     after_timestamp,
                     after_id,
                     before_timestamp,
@@ -140,6 +905,7 @@ where
             self.repo
                 .get_last_library_updates(
                     user_id,
+                    unlocked_category_ids,
                     after_timestamp,
                     after_id,
                     before_timestamp,
@@ -151,6 +917,7 @@ where
             self.repo
                 .get_library_updates(
                     user_id,
+                    unlocked_category_ids,
                     after_timestamp,
                     after_id,
                     before_timestamp,
@@ -161,4 +928,174 @@ where
 
         Ok(updates)
     }
+
+    /// Manga most recently added to the library, see [`LibraryRepository::get_library_additions`]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_library_additions(
+        &self,
+        user_id: i64,
+        unlocked_category_ids: &[i64],
+        after_timestamp: i64,
+        after_id: i64,
+        before_timestamp: i64,
+        before_id: i64,
+        first: Option<usize>,
+        last: Option<usize>,
+    ) -> Result<Vec<LibraryAddition>, LibraryError> {
+        Ok(self
+            .repo
+            .get_library_additions(
+                user_id,
+                unlocked_category_ids,
+                after_timestamp,
+                after_id,
+                before_timestamp,
+                before_id,
+                first.map(|first| first as i32),
+                last.map(|last| last as i32),
+            )
+            .await?)
+    }
+
+    /// Chapters most recently found for library manga, see
+    /// [`LibraryRepository::get_chapter_additions`]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_chapter_additions(
+        &self,
+        user_id: i64,
+        unlocked_category_ids: &[i64],
+        after_timestamp: i64,
+        after_id: i64,
+        before_timestamp: i64,
+        before_id: i64,
+        first: Option<usize>,
+        last: Option<usize>,
+    ) -> Result<Vec<ChapterAddition>, LibraryError> {
+        Ok(self
+            .repo
+            .get_chapter_additions(
+                user_id,
+                unlocked_category_ids,
+                after_timestamp,
+                after_id,
+                before_timestamp,
+                before_id,
+                first.map(|first| first as i32),
+                last.map(|last| last as i32),
+            )
+            .await?)
+    }
+
+    pub async fn get_download_ahead(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<i64, LibraryError> {
+        Ok(self.repo.get_download_ahead(user_id, manga_id).await?)
+    }
+
+    pub async fn set_download_ahead(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        count: i64,
+    ) -> Result<(), LibraryError> {
+        if !(0..=MAX_DOWNLOAD_AHEAD).contains(&count) {
+            return Err(LibraryError::InvalidDownloadAhead);
+        }
+
+        self.repo
+            .set_download_ahead(user_id, manga_id, count)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_preferred_group(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Option<String>, LibraryError> {
+        Ok(self.repo.get_preferred_group(user_id, manga_id).await?)
+    }
+
+    /// Empty or `None` clears the preferred scanlation group
+    pub async fn set_preferred_group(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        group: Option<&str>,
+    ) -> Result<(), LibraryError> {
+        let group = group
+            .map(|group| group.trim())
+            .filter(|group| !group.is_empty());
+
+        self.repo
+            .set_preferred_group(user_id, manga_id, group)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_chapter_languages(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Option<Vec<String>>, LibraryError> {
+        Ok(self.repo.get_chapter_languages(user_id, manga_id).await?)
+    }
+
+    /// Chapter languages of the manga, empty or `None` uses the ones of the user
+    pub async fn set_chapter_languages(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        languages: Option<Vec<String>>,
+    ) -> Result<(), LibraryError> {
+        let languages = normalize_languages(languages);
+
+        self.repo
+            .set_chapter_languages(user_id, manga_id, languages.as_deref())
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_auto_download(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<AutoDownload, LibraryError> {
+        Ok(self.repo.get_auto_download(user_id, manga_id).await?)
+    }
+
+    pub async fn set_auto_download(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        auto_download: AutoDownload,
+    ) -> Result<(), LibraryError> {
+        validate_auto_download(&auto_download)?;
+
+        self.repo
+            .set_auto_download(user_id, manga_id, auto_download)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_category_auto_download(
+        &self,
+        user_id: i64,
+        category_id: i64,
+        auto_download: AutoDownload,
+    ) -> Result<Category, LibraryError> {
+        validate_auto_download(&auto_download)?;
+        self.get_user_category(user_id, category_id).await?;
+
+        Ok(self
+            .repo
+            .set_category_auto_download(user_id, category_id, auto_download)
+            .await?)
+    }
 }
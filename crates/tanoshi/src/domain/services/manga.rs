@@ -4,12 +4,14 @@ use tanoshi_vm::prelude::ExtensionManager;
 use thiserror::Error;
 
 use crate::domain::{
-    entities::manga::{InputList, Manga},
+    entities::manga::{InputList, Manga, MangaOverride},
     repositories::manga::{MangaRepository, MangaRepositoryError},
 };
 
 #[derive(Debug, Error)]
 pub enum MangaError {
+    #[error("no installed source match url {0}")]
+    UnsupportedUrl(String),
     #[error("other error: {0}")]
     Other(#[from] anyhow::Error),
 }
@@ -22,6 +24,23 @@ impl From<MangaRepositoryError> for MangaError {
     }
 }
 
+fn non_blank(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn non_blank_list(values: Option<Vec<String>>) -> Option<Vec<String>> {
+    values
+        .map(|values| {
+            values
+                .into_iter()
+                .filter_map(|value| non_blank(Some(value)))
+                .collect::<Vec<_>>()
+        })
+        .filter(|values| !values.is_empty())
+}
+
 pub struct MangaService<R>
 where
     R: MangaRepository,
@@ -38,6 +57,17 @@ where
         Self { repo, sources }
     }
 
+    /// Service making source calls with a logged in user's session
+    pub fn with_session(&self, session: Option<String>) -> Self
+    where
+        R: Clone,
+    {
+        Self {
+            repo: self.repo.clone(),
+            sources: self.sources.with_session(session),
+        }
+    }
+
     pub async fn fetch_source_popular_manga(
         &self,
         source_id: i64,
@@ -107,7 +137,39 @@ where
             manga
         };
 
-        Ok(manga)
+        self.with_override(manga).await
+    }
+
+    /// Find installed source whose url has the same host as `url`
+    /// and return the source id with the remaining path and query
+    pub async fn resolve_source_path(&self, url: &str) -> Result<(i64, String), MangaError> {
+        let unsupported = || MangaError::UnsupportedUrl(url.to_string());
+        let url = reqwest::Url::parse(url.trim()).map_err(|_| unsupported())?;
+        let host = url.host_str().map(|host| host.trim_start_matches("www."));
+
+        let source = self
+            .sources
+            .list()
+            .await?
+            .into_iter()
+            .find(|source| {
+                reqwest::Url::parse(&source.url)
+                    .ok()
+                    .and_then(|source_url| {
+                        source_url
+                            .host_str()
+                            .map(|source_host| Some(source_host.trim_start_matches("www.")) == host)
+                    })
+                    .unwrap_or(false)
+            })
+            .ok_or_else(unsupported)?;
+
+        let mut path = url.path().to_string();
+        if let Some(query) = url.query() {
+            path = format!("{path}?{query}");
+        }
+
+        Ok((source.id, path))
     }
 
     pub async fn fetch_manga_by_id(&self, id: i64, refresh: bool) -> Result<Manga, MangaError> {
@@ -123,6 +185,124 @@ where
             manga = self.repo.get_manga_by_id(id).await?;
         }
 
+        self.with_override(manga).await
+    }
+
+    /// `manga` with the metadata users set over the one of their source
+    pub async fn with_overrides(&self, mut manga: Vec<Manga>) -> Result<Vec<Manga>, MangaError> {
+        let manga_ids: Vec<i64> = manga.iter().map(|m| m.id).collect();
+        let overrides = self.repo.get_manga_overrides(&manga_ids).await?;
+        for m in manga.iter_mut() {
+            if let Some(manga_override) = overrides.get(&m.id) {
+                manga_override.apply(m);
+            }
+        }
+
+        Ok(manga)
+    }
+
+    async fn with_override(&self, mut manga: Manga) -> Result<Manga, MangaError> {
+        if let Some(manga_override) = self
+            .repo
+            .get_manga_overrides(&[manga.id])
+            .await?
+            .get(&manga.id)
+        {
+            manga_override.apply(&mut manga);
+        }
+
+        Ok(manga)
+    }
+
+    pub async fn fetch_manga_override(&self, manga_id: i64) -> Result<MangaOverride, MangaError> {
+        Ok(self
+            .repo
+            .get_manga_overrides(&[manga_id])
+            .await?
+            .remove(&manga_id)
+            .unwrap_or_default())
+    }
+
+    /// Replace the metadata set over the source's, blank values keep the source's, kept
+    /// apart from `manga` so refreshing from the source doesn't overwrite it
+    pub async fn set_manga_override(
+        &self,
+        manga_id: i64,
+        manga_override: MangaOverride,
+    ) -> Result<Manga, MangaError> {
+        let manga_override = MangaOverride {
+            title: non_blank(manga_override.title),
+            author: non_blank_list(manga_override.author),
+            genre: non_blank_list(manga_override.genre),
+            status: non_blank(manga_override.status),
+            description: non_blank(manga_override.description),
+        };
+        self.repo
+            .set_manga_override(manga_id, &manga_override)
+            .await?;
+
+        self.fetch_manga_by_id(manga_id, false).await
+    }
+
+    pub async fn fetch_user_alt_titles(&self, manga_id: i64) -> Result<Vec<String>, MangaError> {
+        Ok(self.repo.get_user_alt_titles(manga_id).await?)
+    }
+
+    /// Replace the other titles set by users, the ones from the source are kept
+    pub async fn set_alt_titles(
+        &self,
+        manga_id: i64,
+        titles: Vec<String>,
+    ) -> Result<Manga, MangaError> {
+        let mut titles = non_blank_list(Some(titles)).unwrap_or_default();
+        titles.sort();
+        titles.dedup();
+        self.repo.set_user_alt_titles(manga_id, &titles).await?;
+
+        self.fetch_manga_by_id(manga_id, false).await
+    }
+
+    pub async fn fetch_linked_manga(&self, manga_id: i64) -> Result<Vec<Manga>, MangaError> {
+        let manga = self.repo.get_linked_manga(manga_id).await?;
+
         Ok(manga)
     }
+
+    /// Every manga of the series of `manga_id` including itself, preferred first
+    pub async fn fetch_series(&self, manga_id: i64) -> Result<Vec<Manga>, MangaError> {
+        let manga = self.repo.get_series_manga(manga_id).await?;
+
+        Ok(manga)
+    }
+
+    /// Prefer manga of a series in the order of `manga_ids`
+    pub async fn set_series_order(&self, manga_ids: &[i64]) -> Result<(), MangaError> {
+        self.repo.set_manga_link_priority(manga_ids).await?;
+
+        Ok(())
+    }
+
+    pub async fn link_manga(&self, manga_id: i64, linked_manga_id: i64) -> Result<(), MangaError> {
+        if manga_id == linked_manga_id {
+            return Err(MangaError::Other(anyhow!("cannot link manga to itself")));
+        }
+
+        self.repo
+            .insert_manga_link(manga_id, linked_manga_id)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn unlink_manga(
+        &self,
+        manga_id: i64,
+        linked_manga_id: i64,
+    ) -> Result<(), MangaError> {
+        self.repo
+            .delete_manga_link(manga_id, linked_manga_id)
+            .await?;
+
+        Ok(())
+    }
 }
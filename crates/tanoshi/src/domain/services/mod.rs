@@ -1,9 +1,13 @@
+pub mod automation;
 pub mod chapter;
 pub mod download;
+pub mod export;
 pub mod history;
 pub mod image;
+pub mod inbox;
 pub mod library;
 pub mod manga;
+pub mod setting;
 pub mod source;
 pub mod tracker;
 pub mod user;
@@ -0,0 +1,61 @@
+use crate::domain::repositories::setting::{SettingRepository, SettingRepositoryError};
+
+use thiserror::Error;
+
+const ANNOUNCEMENT_KEY: &str = "announcement";
+const TELEMETRY_KEY: &str = "telemetry_enabled";
+
+#[derive(Debug, Error)]
+pub enum SettingError {
+    #[error("repository error: {0}")]
+    RepositoryError(#[from] SettingRepositoryError),
+}
+
+#[derive(Clone)]
+pub struct SettingService<R>
+where
+    R: SettingRepository,
+{
+    repo: R,
+}
+
+impl<R> SettingService<R>
+where
+    R: SettingRepository,
+{
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    pub async fn get_announcement(&self) -> Result<Option<String>, SettingError> {
+        let announcement = self
+            .repo
+            .get_setting(ANNOUNCEMENT_KEY)
+            .await?
+            .filter(|announcement| !announcement.trim().is_empty());
+
+        Ok(announcement)
+    }
+
+    pub async fn set_announcement(&self, announcement: Option<&str>) -> Result<(), SettingError> {
+        self.repo
+            .set_setting(ANNOUNCEMENT_KEY, announcement)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_telemetry_enabled(&self) -> Result<bool, SettingError> {
+        let enabled = self.repo.get_setting(TELEMETRY_KEY).await?;
+
+        Ok(enabled.as_deref() == Some("true"))
+    }
+
+    pub async fn set_telemetry_enabled(&self, enabled: bool) -> Result<(), SettingError> {
+        self.repo
+            .set_setting(TELEMETRY_KEY, enabled.then(|| "true"))
+            .await?;
+
+        Ok(())
+    }
+}
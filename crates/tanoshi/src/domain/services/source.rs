@@ -1,13 +1,25 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, str::FromStr, time::Instant};
 
-use crate::domain::{
-    entities::source::Source,
-    repositories::source::{SourceRepository, SourceRepositoryError},
+use crate::{
+    domain::{
+        entities::{
+            manga::Manga,
+            source::{
+                DomainHeaderOverride, HeaderOverride, Source, SourceCredential, SourceDiagnostic,
+                SourceHealth, SourceLog, SourceLogLevel, SourceStatus,
+            },
+        },
+        repositories::source::{SourceRepository, SourceRepositoryError},
+    },
+    infrastructure::{headers, proxy},
 };
 
-use tanoshi_lib::prelude::Version;
+use tanoshi_lib::prelude::{Input, Version};
 use thiserror::Error;
 
+/// how long a diagnostic probe waits for a source before reporting it as timed out
+const PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
 #[derive(Debug, Error)]
 pub enum SourceError {
     #[error("extension return error: {0}")]
@@ -67,6 +79,30 @@ where
         Ok(sources)
     }
 
+    /// Installed sources with the user's pins marked, pinned sources come first in pin order
+    pub async fn get_installed_sources_for_user(
+        &self,
+        user_id: i64,
+        repo_url: &str,
+        check_update: bool,
+    ) -> Result<Vec<Source>, SourceError> {
+        let mut sources = self.get_installed_sources(repo_url, check_update).await?;
+
+        let pinned_ids = self.repo.get_pinned_source_ids(user_id).await?;
+        for source in sources.iter_mut() {
+            source.pinned = pinned_ids.contains(&source.id);
+        }
+        // stable sort keeps the rest ordered by id
+        sources.sort_by_key(|source| {
+            pinned_ids
+                .iter()
+                .position(|id| *id == source.id)
+                .unwrap_or(pinned_ids.len())
+        });
+
+        Ok(sources)
+    }
+
     pub async fn get_available_sources(&self, repo_url: &str) -> Result<Vec<Source>, SourceError> {
         let sources = self.repo.available_sources(repo_url, true).await?;
 
@@ -91,9 +127,412 @@ where
         Ok(())
     }
 
+    /// Returns the id of the installed source
+    pub async fn install_source_from_file(&self, contents: &[u8]) -> Result<i64, SourceError> {
+        Ok(self.repo.install_source_from_file(contents).await?)
+    }
+
+    /// Install the source package at `path` on the server
+    pub async fn install_source_from_path(&self, path: &str) -> Result<i64, SourceError> {
+        let contents = tokio::fs::read(path)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to read {path}: {e}"))?;
+
+        self.install_source_from_file(&contents).await
+    }
+
+    pub async fn reload_source(&self, id: i64) -> Result<(), SourceError> {
+        self.repo.reload_source(id).await?;
+
+        Ok(())
+    }
+
     pub async fn uninstall_source(&self, id: i64) -> Result<(), SourceError> {
         self.repo.uninstall_source(id).await?;
 
         Ok(())
     }
+
+    pub async fn get_logs(
+        &self,
+        id: i64,
+        level: SourceLogLevel,
+    ) -> Result<Vec<SourceLog>, SourceError> {
+        Ok(self.repo.get_logs(id, level).await?)
+    }
+
+    pub async fn get_filters(&self, id: i64) -> Result<Vec<Input>, SourceError> {
+        Ok(self.repo.get_filters(id).await?)
+    }
+
+    pub async fn get_preferences(&self, id: i64) -> Result<Vec<Input>, SourceError> {
+        Ok(self.repo.get_preferences(id).await?)
+    }
+
+    pub async fn set_preferences(
+        &self,
+        id: i64,
+        preferences: Vec<Input>,
+    ) -> Result<(), SourceError> {
+        self.repo.set_preferences(id, preferences).await?;
+
+        Ok(())
+    }
+
+    pub async fn restore_preferences(&self) -> Result<(), SourceError> {
+        self.repo.restore_preferences().await?;
+
+        Ok(())
+    }
+
+    pub async fn login_to_source(
+        &self,
+        user_id: i64,
+        source_id: i64,
+        username: &str,
+        password: &str,
+    ) -> Result<(), SourceError> {
+        let username = username.trim();
+        if username.is_empty() || password.is_empty() {
+            return Err(SourceError::Other(anyhow::anyhow!(
+                "username and password are required"
+            )));
+        }
+
+        let session = self.repo.login(source_id, username, password).await?;
+        self.repo
+            .save_credential(user_id, source_id, username, &session)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn logout_from_source(
+        &self,
+        user_id: i64,
+        source_id: i64,
+    ) -> Result<(), SourceError> {
+        self.repo.delete_credential(user_id, source_id).await?;
+
+        Ok(())
+    }
+
+    pub async fn pin_source(&self, user_id: i64, source_id: i64) -> Result<(), SourceError> {
+        // only installed sources can be pinned
+        self.repo.get_source_by_id(source_id).await?;
+        self.repo.pin_source(user_id, source_id).await?;
+
+        Ok(())
+    }
+
+    pub async fn unpin_source(&self, user_id: i64, source_id: i64) -> Result<(), SourceError> {
+        self.repo.unpin_source(user_id, source_id).await?;
+
+        Ok(())
+    }
+
+    pub async fn get_saved_filters(
+        &self,
+        user_id: i64,
+        source_id: i64,
+    ) -> Result<Option<Vec<Input>>, SourceError> {
+        Ok(self.repo.get_saved_filters(user_id, source_id).await?)
+    }
+
+    /// An empty list resets the source back to its default filters
+    pub async fn save_filters(
+        &self,
+        user_id: i64,
+        source_id: i64,
+        filters: Vec<Input>,
+    ) -> Result<(), SourceError> {
+        if filters.is_empty() {
+            self.repo.delete_saved_filters(user_id, source_id).await?;
+        } else {
+            // only installed sources have filters to restore
+            self.repo.get_source_by_id(source_id).await?;
+            self.repo.save_filters(user_id, source_id, &filters).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_credential(
+        &self,
+        user_id: i64,
+        source_id: i64,
+    ) -> Result<Option<SourceCredential>, SourceError> {
+        Ok(self.repo.get_credential(user_id, source_id).await?)
+    }
+
+    /// Session to make calls to `source_id` with on behalf of `user_id`
+    pub async fn get_session(
+        &self,
+        user_id: i64,
+        source_id: i64,
+    ) -> Result<Option<String>, SourceError> {
+        Ok(self
+            .repo
+            .get_credential(user_id, source_id)
+            .await?
+            .map(|credential| credential.session))
+    }
+
+    async fn user_session(
+        &self,
+        user_id: Option<i64>,
+        source_id: i64,
+    ) -> Result<Option<String>, SourceError> {
+        match user_id {
+            Some(user_id) => self.get_session(user_id, source_id).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Popular manga with the session of `user_id` if logged in to the source,
+    /// a recent response is reused unless `refresh`
+    pub async fn fetch_popular_manga(
+        &self,
+        user_id: Option<i64>,
+        source_id: i64,
+        page: i64,
+        refresh: bool,
+    ) -> Result<Vec<Manga>, SourceError> {
+        let session = self.user_session(user_id, source_id).await?;
+
+        Ok(self
+            .repo
+            .get_popular_manga(source_id, page, session, refresh)
+            .await?)
+    }
+
+    pub async fn fetch_latest_manga(
+        &self,
+        user_id: Option<i64>,
+        source_id: i64,
+        page: i64,
+        refresh: bool,
+    ) -> Result<Vec<Manga>, SourceError> {
+        let session = self.user_session(user_id, source_id).await?;
+
+        Ok(self
+            .repo
+            .get_latest_manga(source_id, page, session, refresh)
+            .await?)
+    }
+
+    pub async fn search_manga(
+        &self,
+        user_id: Option<i64>,
+        source_id: i64,
+        page: i64,
+        query: Option<String>,
+        filters: Option<Vec<Input>>,
+        refresh: bool,
+    ) -> Result<Vec<Manga>, SourceError> {
+        let session = self.user_session(user_id, source_id).await?;
+
+        Ok(self
+            .repo
+            .search_manga(source_id, page, query, filters, session, refresh)
+            .await?)
+    }
+
+    async fn probe(&self, source: Source) -> SourceDiagnostic {
+        let started = Instant::now();
+        let result = tokio::time::timeout(
+            PROBE_TIMEOUT,
+            self.repo.get_popular_manga(source.id, 1, None, true),
+        )
+        .await;
+        let latency = started.elapsed();
+
+        let (status, manga_count, error) = match result {
+            Ok(Ok(manga)) if manga.is_empty() => (SourceStatus::Empty, 0, None),
+            Ok(Ok(manga)) => (SourceStatus::Ok, manga.len(), None),
+            Ok(Err(e)) => (SourceStatus::Error, 0, Some(e.to_string())),
+            Err(_) => (
+                SourceStatus::Timeout,
+                0,
+                Some(format!("no response after {}s", PROBE_TIMEOUT.as_secs())),
+            ),
+        };
+
+        SourceDiagnostic {
+            source_id: source.id,
+            name: source.name,
+            version: source.version,
+            status,
+            latency,
+            manga_count,
+            error,
+        }
+    }
+
+    /// Fetch the first popular page of every installed source concurrently,
+    /// bypassing the response cache
+    pub async fn diagnose_sources(&self) -> Result<Vec<SourceDiagnostic>, SourceError> {
+        let sources = self.repo.installed_sources().await?;
+
+        Ok(futures::future::join_all(sources.into_iter().map(|source| self.probe(source))).await)
+    }
+
+    pub async fn get_source_health(&self, source_id: i64) -> Result<SourceHealth, SourceError> {
+        Ok(self.repo.get_source_health(source_id).await?)
+    }
+
+    /// Health of every installed source, fresh for the ones never checked yet
+    pub async fn get_all_source_health(&self) -> Result<Vec<SourceHealth>, SourceError> {
+        let mut health: HashMap<i64, SourceHealth> = self
+            .repo
+            .get_all_source_health()
+            .await?
+            .into_iter()
+            .map(|health| (health.source_id, health))
+            .collect();
+
+        let mut all_health = vec![];
+        for source in self.repo.installed_sources().await? {
+            all_health.push(match health.remove(&source.id) {
+                Some(health) => health,
+                None => self.repo.get_source_health(source.id).await?,
+            });
+        }
+
+        Ok(all_health)
+    }
+
+    /// Disabled sources are skipped by update runs, re-enabling starts a fresh failure streak
+    pub async fn set_source_enabled(
+        &self,
+        source_id: i64,
+        enabled: bool,
+    ) -> Result<SourceHealth, SourceError> {
+        let mut health = self.repo.get_source_health(source_id).await?;
+        if enabled {
+            health.disabled_at = None;
+            health.consecutive_failures = 0;
+            health.failing_since = None;
+        } else if health.disabled_at.is_none() {
+            health.disabled_at = Some(chrono::Utc::now().naive_utc());
+        }
+
+        self.repo.save_source_health(&health).await?;
+
+        Ok(health)
+    }
+
+    pub async fn get_proxy(&self, source_id: i64) -> Result<Option<String>, SourceError> {
+        Ok(self.repo.get_proxy(source_id).await?)
+    }
+
+    /// `None` or an empty proxy falls back to the default proxy from config
+    pub async fn set_proxy(&self, source_id: i64, proxy: Option<&str>) -> Result<(), SourceError> {
+        let proxy = proxy.map(str::trim).filter(|proxy| !proxy.is_empty());
+        if let Some(proxy) = proxy {
+            proxy::validate(proxy)?;
+        }
+
+        self.repo.set_proxy(source_id, proxy).await?;
+
+        Ok(())
+    }
+
+    pub async fn get_header_override(
+        &self,
+        source_id: i64,
+    ) -> Result<Option<HeaderOverride>, SourceError> {
+        Ok(self.repo.get_header_override(source_id).await?)
+    }
+
+    /// An override without any header removes it
+    pub async fn set_header_override(
+        &self,
+        source_id: i64,
+        header_override: HeaderOverride,
+    ) -> Result<(), SourceError> {
+        let header_override = normalize_header_override(header_override)?;
+        self.repo
+            .set_header_override(source_id, header_override.as_ref())
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_domain_header_overrides(
+        &self,
+    ) -> Result<Vec<DomainHeaderOverride>, SourceError> {
+        Ok(self.repo.get_domain_header_overrides().await?)
+    }
+
+    /// `domain` may be given as a url, an override without any header removes it
+    pub async fn set_domain_header_override(
+        &self,
+        domain: &str,
+        header_override: HeaderOverride,
+    ) -> Result<String, SourceError> {
+        let domain = normalize_domain(domain)
+            .ok_or_else(|| SourceError::Other(anyhow::anyhow!("invalid domain {domain}")))?;
+        let header_override = normalize_header_override(header_override)?;
+        self.repo
+            .set_domain_header_override(&domain, header_override.as_ref())
+            .await?;
+
+        Ok(domain)
+    }
+
+    pub async fn get_rate_limit(&self, source_id: i64) -> Result<Option<u32>, SourceError> {
+        Ok(self.repo.get_rate_limit(source_id).await?)
+    }
+
+    /// `None` falls back to the default rate limit from config, 0 is unlimited
+    pub async fn set_rate_limit(
+        &self,
+        source_id: i64,
+        requests_per_minute: Option<u32>,
+    ) -> Result<(), SourceError> {
+        self.repo
+            .set_rate_limit(source_id, requests_per_minute)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Trimmed override without blank headers, `None` when nothing is left
+fn normalize_header_override(
+    header_override: HeaderOverride,
+) -> Result<Option<HeaderOverride>, SourceError> {
+    let header_override = HeaderOverride {
+        user_agent: header_override
+            .user_agent
+            .map(|user_agent| user_agent.trim().to_string())
+            .filter(|user_agent| !user_agent.is_empty()),
+        headers: header_override
+            .headers
+            .into_iter()
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .filter(|(name, _)| !name.is_empty())
+            .collect(),
+    };
+    if header_override.is_empty() {
+        return Ok(None);
+    }
+    headers::validate(&header_override)?;
+
+    Ok(Some(header_override))
+}
+
+fn normalize_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim().to_lowercase();
+    let host = match reqwest::Url::parse(&domain) {
+        Ok(url) => url.host_str()?.to_string(),
+        Err(_) => domain.trim_end_matches('/').to_string(),
+    };
+    let host = host.trim_start_matches("www.");
+    if host.is_empty() || host.contains(|c: char| c.is_whitespace() || c == '/') {
+        return None;
+    }
+
+    Some(host.to_string())
 }
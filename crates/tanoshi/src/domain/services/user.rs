@@ -1,8 +1,14 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
 use rand::RngCore;
 use thiserror::Error;
 
 use crate::domain::{
-    entities::user::User,
+    entities::user::{CatalogueSort, User},
     repositories::user::{UserRepository, UserRepositoryError},
 };
 
@@ -16,18 +22,49 @@ pub enum UserError {
     Forbidden,
     #[error("insufficient password length")]
     InsufficientPasswordLength,
+    #[error("invalid or expired pairing code")]
+    InvalidPairingCode,
+    #[error("too many pairing attempts, try again later")]
+    TooManyPairingAttempts,
+    #[error("too many unlock attempts, try again later")]
+    TooManyUnlockAttempts,
     #[error("repository error: {0}")]
     RepositoryError(#[from] UserRepositoryError),
     #[error("other: {0}")]
     Other(String),
 }
 
+const TELEGRAM_PAIRING_CODE_TTL: Duration = Duration::from_secs(600);
+const TELEGRAM_PAIRING_MAX_ATTEMPTS: u32 = 5;
+const TELEGRAM_PAIRING_LOCKOUT: Duration = Duration::from_secs(900);
+const CATEGORY_UNLOCK_MAX_ATTEMPTS: u32 = 5;
+const CATEGORY_UNLOCK_LOCKOUT: Duration = Duration::from_secs(900);
+
+/// Trimmed, lowercase and sorted languages, `None` when none are left
+pub fn normalize_languages(languages: Option<Vec<String>>) -> Option<Vec<String>> {
+    languages
+        .map(|languages| {
+            let mut languages: Vec<String> = languages
+                .iter()
+                .map(|language| language.trim().to_lowercase())
+                .filter(|language| !language.is_empty())
+                .collect();
+            languages.sort();
+            languages.dedup();
+            languages
+        })
+        .filter(|languages| !languages.is_empty())
+}
+
 #[derive(Clone)]
 pub struct UserService<R>
 where
     R: UserRepository,
 {
     repo: R,
+    telegram_pairing_codes: Arc<Mutex<HashMap<String, (i64, Instant)>>>,
+    telegram_pairing_attempts: Arc<Mutex<HashMap<i64, (u32, Instant)>>>,
+    category_unlock_attempts: Arc<Mutex<HashMap<i64, (u32, Instant)>>>,
 }
 
 impl<R> UserService<R>
@@ -35,7 +72,12 @@ where
     R: UserRepository,
 {
     pub fn new(repo: R) -> Self {
-        Self { repo }
+        Self {
+            repo,
+            telegram_pairing_codes: Arc::new(Mutex::new(HashMap::new())),
+            telegram_pairing_attempts: Arc::new(Mutex::new(HashMap::new())),
+            category_unlock_attempts: Arc::new(Mutex::new(HashMap::new())),
+        }
     }
 
     pub async fn create_user(
@@ -79,6 +121,42 @@ where
         Ok(())
     }
 
+    /// User is locked out for a while after too many wrong pins or passwords
+    pub fn check_category_unlock_attempts(&self, user_id: i64) -> Result<(), UserError> {
+        let mut attempts = self.category_unlock_attempts.lock().unwrap();
+        attempts.retain(|_, (_, last_attempt)| last_attempt.elapsed() < CATEGORY_UNLOCK_LOCKOUT);
+        let failed = attempts.get(&user_id).map(|(count, _)| *count).unwrap_or(0);
+        if failed >= CATEGORY_UNLOCK_MAX_ATTEMPTS {
+            return Err(UserError::TooManyUnlockAttempts);
+        }
+
+        Ok(())
+    }
+
+    pub fn record_category_unlock_attempt(&self, user_id: i64, unlocked: bool) {
+        let mut attempts = self.category_unlock_attempts.lock().unwrap();
+        if unlocked {
+            attempts.remove(&user_id);
+        } else {
+            let (count, last_attempt) = attempts.entry(user_id).or_insert((0, Instant::now()));
+            *count += 1;
+            *last_attempt = Instant::now();
+        }
+    }
+
+    /// Tokens unlock categories only while issued with the current generation
+    pub async fn get_category_lock_generation(&self, user_id: i64) -> Result<i64, UserError> {
+        Ok(self.repo.get_category_lock_generation(user_id).await?)
+    }
+
+    /// Lock private categories again on every token of the user, returns the new generation
+    pub async fn lock_categories(&self, user_id: i64) -> Result<i64, UserError> {
+        Ok(self
+            .repo
+            .increment_category_lock_generation(user_id)
+            .await?)
+    }
+
     pub async fn change_password(
         &self,
         user_id: i64,
@@ -111,26 +189,218 @@ where
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn update_profile(
         &self,
         user_id: i64,
         telegram_chat_id: Option<i64>,
         pushover_user_key: Option<String>,
         gotify_token: Option<String>,
+        pushover_device: Option<String>,
+        pushover_sound: Option<String>,
+        pushover_chapter_priority: i64,
+        pushover_alert_priority: i64,
     ) -> Result<(), UserError> {
         debug!("update_profile");
 
+        // emergency priority (2) needs retry and expire parameters, not supported
+        for priority in [pushover_chapter_priority, pushover_alert_priority] {
+            if !(-2..=1).contains(&priority) {
+                return Err(UserError::Other(format!(
+                    "pushover priority {priority} must be between -2 and 1"
+                )));
+            }
+        }
+
         let mut user = self.repo.get_user_by_id(user_id).await?;
 
         user.telegram_chat_id = telegram_chat_id;
         user.pushover_user_key = pushover_user_key;
         user.gotify_token = gotify_token;
+        user.pushover_device = pushover_device.filter(|device| !device.trim().is_empty());
+        user.pushover_sound = pushover_sound.filter(|sound| !sound.trim().is_empty());
+        user.pushover_chapter_priority = pushover_chapter_priority;
+        user.pushover_alert_priority = pushover_alert_priority;
 
         self.repo.update_user_setting(&user).await?;
 
         Ok(())
     }
 
+    pub async fn update_notification_digest_interval(
+        &self,
+        user_id: i64,
+        interval: Option<i64>,
+    ) -> Result<(), UserError> {
+        self.repo
+            .update_notification_digest_interval(user_id, interval)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_notification_preferences(
+        &self,
+        user_id: i64,
+        chapter_category_ids: Option<Vec<i64>>,
+        download_failures: bool,
+        announcements: bool,
+    ) -> Result<(), UserError> {
+        self.repo
+            .update_notification_preferences(
+                user_id,
+                chapter_category_ids.as_deref(),
+                download_failures,
+                announcements,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_catalogue_preferences(
+        &self,
+        user_id: i64,
+        sort: CatalogueSort,
+        language: Option<String>,
+        hide_library: bool,
+    ) -> Result<(), UserError> {
+        let language = language
+            .map(|language| language.trim().to_lowercase())
+            .filter(|language| !language.is_empty());
+
+        self.repo
+            .update_catalogue_preferences(user_id, sort, language.as_deref(), hide_library)
+            .await?;
+
+        Ok(())
+    }
+
+    /// An empty list is stored as `None` so no language ends up hiding every source
+    pub async fn update_enabled_languages(
+        &self,
+        user_id: i64,
+        languages: Option<Vec<String>>,
+    ) -> Result<(), UserError> {
+        let languages = normalize_languages(languages);
+
+        self.repo
+            .update_enabled_languages(user_id, languages.as_deref())
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_hide_nsfw(&self, user_id: i64, hide: bool) -> Result<(), UserError> {
+        self.repo.update_hide_nsfw(user_id, hide).await?;
+
+        Ok(())
+    }
+
+    /// Set the address chapters are sent to, empty unsets it
+    pub async fn update_device_email(
+        &self,
+        user_id: i64,
+        email: Option<String>,
+    ) -> Result<(), UserError> {
+        let email = email
+            .map(|email| email.trim().to_string())
+            .filter(|email| !email.is_empty());
+        if let Some(email) = email.as_ref() {
+            if email.contains(char::is_whitespace) || !email.contains('@') {
+                return Err(UserError::Other(format!("invalid email address {email}")));
+            }
+        }
+
+        self.repo
+            .update_device_email(user_id, email.as_deref())
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_data_saver(&self, user_id: i64, enabled: bool) -> Result<(), UserError> {
+        self.repo.update_data_saver(user_id, enabled).await?;
+
+        Ok(())
+    }
+
+    /// Chapter languages listed for manga without their own, empty lists every language
+    pub async fn update_chapter_languages(
+        &self,
+        user_id: i64,
+        languages: Option<Vec<String>>,
+    ) -> Result<(), UserError> {
+        let languages = normalize_languages(languages);
+
+        self.repo
+            .update_chapter_languages(user_id, languages.as_deref())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Create short lived code the user sends to telegram bot to link the chat
+    pub fn create_telegram_pairing_code(&self, user_id: i64) -> String {
+        let mut codes = self.telegram_pairing_codes.lock().unwrap();
+        codes.retain(|_, (id, created_at)| {
+            *id != user_id && created_at.elapsed() < TELEGRAM_PAIRING_CODE_TTL
+        });
+
+        // 128 bits, url safe so it also fits a t.me/<bot>?start= deep link
+        let mut bytes = [0_u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let code = base64::encode_config(bytes, base64::URL_SAFE_NO_PAD);
+        codes.insert(code.clone(), (user_id, Instant::now()));
+
+        code
+    }
+
+    /// Chat is locked out for a while after too many wrong codes
+    pub async fn pair_telegram_chat(&self, code: &str, chat_id: i64) -> Result<User, UserError> {
+        {
+            let mut attempts = self.telegram_pairing_attempts.lock().unwrap();
+            attempts
+                .retain(|_, (_, last_attempt)| last_attempt.elapsed() < TELEGRAM_PAIRING_LOCKOUT);
+            let failed = attempts.get(&chat_id).map(|(count, _)| *count).unwrap_or(0);
+            if failed >= TELEGRAM_PAIRING_MAX_ATTEMPTS {
+                return Err(UserError::TooManyPairingAttempts);
+            }
+        }
+
+        let user_id = self
+            .telegram_pairing_codes
+            .lock()
+            .unwrap()
+            .remove(code)
+            .filter(|(_, created_at)| created_at.elapsed() < TELEGRAM_PAIRING_CODE_TTL)
+            .map(|(user_id, _)| user_id);
+        let mut attempts = self.telegram_pairing_attempts.lock().unwrap();
+        let user_id = match user_id {
+            Some(user_id) => {
+                attempts.remove(&chat_id);
+                user_id
+            }
+            None => {
+                let (count, last_attempt) = attempts.entry(chat_id).or_insert((0, Instant::now()));
+                *count += 1;
+                *last_attempt = Instant::now();
+                return Err(UserError::InvalidPairingCode);
+            }
+        };
+        drop(attempts);
+
+        let mut user = self.repo.get_user_by_id(user_id).await?;
+        user.telegram_chat_id = Some(chat_id);
+        self.repo.update_user_setting(&user).await?;
+
+        Ok(user)
+    }
+
+    pub async fn fetch_user_by_telegram_chat_id(&self, chat_id: i64) -> Result<User, UserError> {
+        Ok(self.repo.get_user_by_telegram_chat_id(chat_id).await?)
+    }
+
     pub async fn fetch_all_users(&self) -> Result<Vec<User>, UserError> {
         Ok(self.repo.get_users().await?)
     }
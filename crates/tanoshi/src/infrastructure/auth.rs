@@ -8,6 +8,12 @@ pub struct Claims {
     pub username: String,
     pub is_admin: bool,
     pub exp: usize,
+    /// private categories unlocked for this token
+    #[serde(default)]
+    pub unlocked_categories: Vec<i64>,
+    /// category lock generation of the user when categories were unlocked
+    #[serde(default)]
+    pub lock_generation: i64,
 }
 
 pub fn decode_jwt(secret: &str, token: &str) -> Result<Claims> {
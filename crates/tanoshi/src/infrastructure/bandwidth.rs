@@ -0,0 +1,41 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Mutex;
+
+/// Spaces out transfers so together they average at most `rate` bytes per second,
+/// clones share the same budget
+#[derive(Debug, Clone)]
+pub struct BandwidthLimiter {
+    rate: u64,
+    /// when everything consumed so far has been paid off
+    next_free: Arc<Mutex<Instant>>,
+}
+
+impl BandwidthLimiter {
+    /// `rate` in bytes per second, 0 is unlimited
+    pub fn new(rate: u64) -> Self {
+        Self {
+            rate,
+            next_free: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Wait until `bytes` more can be transferred without exceeding the rate
+    pub async fn consume(&self, bytes: usize) {
+        if self.rate == 0 || bytes == 0 {
+            return;
+        }
+
+        let start = {
+            let mut next_free = self.next_free.lock().await;
+            let start = (*next_free).max(Instant::now());
+            *next_free = start + Duration::from_secs_f64(bytes as f64 / self.rate as f64);
+            start
+        };
+
+        tokio::time::sleep_until(start.into()).await;
+    }
+}
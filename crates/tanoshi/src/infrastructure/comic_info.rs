@@ -0,0 +1,291 @@
+use chrono::Datelike;
+
+use crate::domain::entities::{chapter::Chapter, manga::Manga};
+
+/// Escape text for xml content and attribute values
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // not allowed in xml 1.0 at all
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// `ComicInfo.xml` metadata read by komga, kavita and most comic readers,
+/// see https://anansi-project.github.io/docs/comicinfo/schemas/v2.0
+pub struct ComicInfo {
+    pub series: String,
+    pub title: String,
+    pub number: f64,
+    pub summary: Option<String>,
+    pub writer: Vec<String>,
+    pub genre: Vec<String>,
+    /// tags the exporting user put on the manga
+    pub tags: Vec<String>,
+    pub scan_information: String,
+    pub language: Option<String>,
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub page_count: usize,
+}
+
+impl ComicInfo {
+    pub fn new(manga: &Manga, chapter: &Chapter, page_count: usize) -> Self {
+        Self {
+            series: manga.title.clone(),
+            title: chapter.title.clone(),
+            number: chapter.number,
+            summary: manga.description.clone(),
+            writer: manga.author.clone(),
+            genre: manga.genre.clone(),
+            tags: vec![],
+            scan_information: chapter.scanlator.clone(),
+            language: chapter.language.clone(),
+            year: chapter.uploaded.year(),
+            month: chapter.uploaded.month(),
+            day: chapter.uploaded.day(),
+            page_count,
+        }
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut fields = vec![
+            ("Series", self.series.clone()),
+            ("Title", self.title.clone()),
+            ("Number", self.number.to_string()),
+        ];
+        if let Some(summary) = self.summary.as_ref() {
+            fields.push(("Summary", summary.clone()));
+        }
+        if !self.writer.is_empty() {
+            fields.push(("Writer", self.writer.join(", ")));
+        }
+        if !self.genre.is_empty() {
+            fields.push(("Genre", self.genre.join(", ")));
+        }
+        if !self.tags.is_empty() {
+            fields.push(("Tags", self.tags.join(", ")));
+        }
+        if !self.scan_information.is_empty() {
+            fields.push(("ScanInformation", self.scan_information.clone()));
+        }
+        if let Some(language) = self.language.as_ref() {
+            fields.push(("LanguageISO", language.clone()));
+        }
+        fields.extend([
+            ("Year", self.year.to_string()),
+            ("Month", self.month.to_string()),
+            ("Day", self.day.to_string()),
+            ("PageCount", self.page_count.to_string()),
+            ("Manga", "Yes".to_string()),
+        ]);
+
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<ComicInfo xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\">\n",
+        );
+        for (name, value) in fields {
+            xml.push_str(&format!("  <{name}>{}</{name}>\n", escape(&value)));
+        }
+        xml.push_str("</ComicInfo>\n");
+
+        xml
+    }
+}
+
+/// Character an xml entity such as `amp` or `#x41` stands for
+fn entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let code = match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => name.strip_prefix('#')?.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// Reverse of [`escape`], unknown entities are kept as they are
+pub fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match rest
+            .find(';')
+            .and_then(|end| entity(&rest[1..end]).map(|c| (c, end)))
+        {
+            Some((c, end)) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+
+    unescaped
+}
+
+/// Text of the first `<name>` element, `None` if it's missing or empty
+fn element(xml: &str, name: &str) -> Option<String> {
+    let start = xml.find(&format!("<{name}>"))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{name}>"))?;
+    let value = xml[start..end].trim();
+    let value = match value
+        .strip_prefix("<![CDATA[")
+        .and_then(|value| value.strip_suffix("]]>"))
+    {
+        Some(cdata) => cdata.trim().to_string(),
+        None => unescape(value),
+    };
+
+    (!value.is_empty()).then(|| value)
+}
+
+fn list(value: Option<String>) -> Vec<String> {
+    value
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// What could be read from a `ComicInfo.xml` written by another tool, e.g. komga or mylar,
+/// every field is optional there
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedComicInfo {
+    pub series: Option<String>,
+    pub title: Option<String>,
+    pub volume: Option<f64>,
+    pub number: Option<f64>,
+    pub summary: Option<String>,
+    pub writer: Vec<String>,
+    pub genre: Vec<String>,
+    pub scan_information: Option<String>,
+    pub language: Option<String>,
+}
+
+impl ParsedComicInfo {
+    pub fn parse(xml: &str) -> Self {
+        Self {
+            series: element(xml, "Series"),
+            title: element(xml, "Title"),
+            // -1 is what the schema defaults an unknown volume to
+            volume: element(xml, "Volume")
+                .and_then(|volume| volume.parse().ok())
+                .filter(|volume| *volume >= 0.0),
+            number: element(xml, "Number").and_then(|number| number.parse().ok()),
+            summary: element(xml, "Summary"),
+            writer: list(element(xml, "Writer")),
+            genre: list(element(xml, "Genre")),
+            scan_information: element(xml, "ScanInformation"),
+            language: element(xml, "LanguageISO"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let xml = r#"<?xml version="1.0"?>
+<ComicInfo>
+  <Series>Tom &amp; Jerry</Series>
+  <Title><![CDATA[The <Beginning>]]></Title>
+  <Volume>-1</Volume>
+  <Number>10.5</Number>
+  <Summary>  </Summary>
+  <Writer>Alice, Bob ,</Writer>
+  <Genre>Action</Genre>
+  <LanguageISO>en</LanguageISO>
+</ComicInfo>"#;
+
+        assert_eq!(
+            ParsedComicInfo::parse(xml),
+            ParsedComicInfo {
+                series: Some("Tom & Jerry".to_string()),
+                title: Some("The <Beginning>".to_string()),
+                volume: None,
+                number: Some(10.5),
+                summary: None,
+                writer: vec!["Alice".to_string(), "Bob".to_string()],
+                genre: vec!["Action".to_string()],
+                scan_information: None,
+                language: Some("en".to_string()),
+            }
+        );
+        assert_eq!(ParsedComicInfo::parse(""), ParsedComicInfo::default());
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("a &lt;b&gt; &quot;c&apos;"), "a <b> \"c'");
+        assert_eq!(unescape("&#65;&#x42;"), "AB");
+        assert_eq!(unescape("R&D &unknown; &"), "R&D &unknown; &");
+    }
+
+    #[test]
+    fn test_to_xml_parses_back() {
+        let info = ComicInfo {
+            series: "Tom & Jerry".to_string(),
+            title: "Ch. 2 <Part \"1\">".to_string(),
+            number: 2.0,
+            summary: Some("cat\u{1}mouse".to_string()),
+            writer: vec!["Alice".to_string(), "Bob".to_string()],
+            genre: vec![],
+            tags: vec!["favorite".to_string()],
+            scan_information: "Group".to_string(),
+            language: None,
+            year: 2022,
+            month: 7,
+            day: 19,
+            page_count: 20,
+        };
+
+        let xml = info.to_xml();
+        assert!(xml.contains("<PageCount>20</PageCount>"));
+        assert!(xml.contains("<Tags>favorite</Tags>"));
+        assert_eq!(
+            ParsedComicInfo::parse(&xml),
+            ParsedComicInfo {
+                series: Some("Tom & Jerry".to_string()),
+                title: Some("Ch. 2 <Part \"1\">".to_string()),
+                volume: None,
+                number: Some(2.0),
+                summary: Some("catmouse".to_string()),
+                writer: vec!["Alice".to_string(), "Bob".to_string()],
+                genre: vec![],
+                scan_information: Some("Group".to_string()),
+                language: None,
+            }
+        );
+    }
+}
@@ -1,8 +1,13 @@
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use serde::{Deserialize, Serialize};
+use std::io::Write;
 use std::path::Path;
-use std::{iter, path::PathBuf};
+use std::{collections::HashMap, iter, path::PathBuf, time::Duration};
+use tanoshi_notifier::smtp::{SmtpOptions, SmtpSecurity};
+use tanoshi_vm::prelude::{Limits, RetryPolicy};
+
+use crate::{domain::entities::download::RetentionPolicy, infrastructure::proxy::ProxyRule};
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct TelegramConfig {
@@ -20,6 +25,57 @@ pub struct GotifyConfig {
     pub base_url: String,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurityConfig {
+    Tls,
+    StartTls,
+    None,
+}
+
+impl Default for SmtpSecurityConfig {
+    fn default() -> Self {
+        Self::StartTls
+    }
+}
+
+/// Mail server chapters are sent to e-readers through
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// defaults to the port of `security`
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurityConfig,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// e.g. `Tanoshi <tanoshi@example.com>`, has to be an approved sender on kindle
+    pub from: String,
+    /// bytes an attachment may be, larger exports are not sent
+    #[serde(default = "default_smtp_max_attachment_size")]
+    pub max_attachment_size: u64,
+}
+
+impl From<&SmtpConfig> for SmtpOptions {
+    fn from(config: &SmtpConfig) -> Self {
+        Self {
+            host: config.host.clone(),
+            port: config.port,
+            security: match config.security {
+                SmtpSecurityConfig::Tls => SmtpSecurity::Tls,
+                SmtpSecurityConfig::StartTls => SmtpSecurity::StartTls,
+                SmtpSecurityConfig::None => SmtpSecurity::None,
+            },
+            username: config.username.clone(),
+            password: config.password.clone(),
+            from: config.from.clone(),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct MyAnimeListConfig {
     pub client_id: String,
@@ -32,10 +88,427 @@ pub struct AniListConfig {
     pub client_secret: String,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NotificationTemplateConfig {
+    #[serde(default = "default_chapter_title_template")]
+    pub chapter_title: String,
+    #[serde(default = "default_chapter_body_template")]
+    pub chapter_body: String,
+    #[serde(default = "default_admin_body_template")]
+    pub admin_body: String,
+    /// `{{count}}` and `{{url}}` of the updates page, `{{chapters}}` is the list grouped by manga
+    #[serde(default = "default_digest_title_template")]
+    pub digest_title: String,
+    #[serde(default = "default_digest_body_template")]
+    pub digest_body: String,
+}
+
+impl Default for NotificationTemplateConfig {
+    fn default() -> Self {
+        Self {
+            chapter_title: default_chapter_title_template(),
+            chapter_body: default_chapter_body_template(),
+            admin_body: default_admin_body_template(),
+            digest_title: default_digest_title_template(),
+            digest_body: default_digest_body_template(),
+        }
+    }
+}
+
+/// Bounds on every call to an extension
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExtensionLimitsConfig {
+    /// seconds a call is waited for
+    #[serde(default = "default_extension_timeout")]
+    pub timeout: u64,
+    /// seconds of cpu time a call may use
+    #[serde(default = "default_extension_cpu_time")]
+    pub cpu_time: u64,
+    /// items a call may return, e.g. manga of a page or pages of a chapter
+    #[serde(default = "default_extension_max_items")]
+    pub max_items: usize,
+    /// megabytes of memory a WebAssembly extension may use, native extensions can't be bounded
+    #[serde(default = "default_extension_memory")]
+    pub memory: usize,
+}
+
+impl Default for ExtensionLimitsConfig {
+    fn default() -> Self {
+        Self {
+            timeout: default_extension_timeout(),
+            cpu_time: default_extension_cpu_time(),
+            max_items: default_extension_max_items(),
+            memory: default_extension_memory(),
+        }
+    }
+}
+
+impl From<&ExtensionLimitsConfig> for Limits {
+    fn from(config: &ExtensionLimitsConfig) -> Self {
+        Self {
+            timeout: Duration::from_secs(config.timeout),
+            cpu_time: Duration::from_secs(config.cpu_time),
+            max_items: config.max_items,
+            memory: config.memory * 1024 * 1024,
+        }
+    }
+}
+
+/// Retries of failed extension calls, except login
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SourceRetryConfig {
+    /// calls made in total, 1 disables retrying
+    #[serde(default = "default_source_retry_attempts")]
+    pub attempts: u32,
+    /// milliseconds before the first retry, doubled for every following one
+    #[serde(default = "default_source_retry_initial_backoff")]
+    pub initial_backoff: u64,
+    /// milliseconds waited at most between retries
+    #[serde(default = "default_source_retry_max_backoff")]
+    pub max_backoff: u64,
+}
+
+impl Default for SourceRetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: default_source_retry_attempts(),
+            initial_backoff: default_source_retry_initial_backoff(),
+            max_backoff: default_source_retry_max_backoff(),
+        }
+    }
+}
+
+impl From<&SourceRetryConfig> for RetryPolicy {
+    fn from(config: &SourceRetryConfig) -> Self {
+        Self {
+            attempts: config.attempts.max(1),
+            initial_backoff: Duration::from_millis(config.initial_backoff),
+            max_backoff: Duration::from_millis(config.max_backoff),
+        }
+    }
+}
+
+/// Pages the download worker fetches at the same time
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DownloadConcurrencyConfig {
+    /// chapters downloaded at the same time
+    #[serde(default = "default_download_concurrency")]
+    pub chapters: usize,
+    /// pages of one chapter fetched at the same time
+    #[serde(default = "default_download_concurrency")]
+    pub pages: usize,
+    /// pages of one source fetched at the same time, across chapters
+    #[serde(default = "default_download_concurrency")]
+    pub per_source: usize,
+    /// `per_source` by source id, for sources that tolerate more or less
+    #[serde(default)]
+    pub sources: HashMap<i64, usize>,
+}
+
+impl Default for DownloadConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            chapters: default_download_concurrency(),
+            pages: default_download_concurrency(),
+            per_source: default_download_concurrency(),
+            sources: HashMap::new(),
+        }
+    }
+}
+
+impl DownloadConcurrencyConfig {
+    /// Pages of `source_id` fetched at the same time, at least 1
+    pub fn source_limit(&self, source_id: i64) -> usize {
+        self.sources
+            .get(&source_id)
+            .copied()
+            .unwrap_or(self.per_source)
+            .max(1)
+    }
+}
+
+/// Periodic removal of downloaded chapters, every policy is off by default
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DownloadRetentionConfig {
+    /// remove chapters everyone with the manga in library has read
+    #[serde(default)]
+    pub delete_after_read: bool,
+    /// most recent chapters kept downloaded per manga
+    #[serde(default)]
+    pub keep_per_manga: Option<usize>,
+    /// bytes every download together may take up, least recently downloaded go first
+    #[serde(default)]
+    pub max_disk_usage: Option<u64>,
+    /// seconds between cleanups
+    #[serde(default = "default_download_retention_interval")]
+    pub interval: u64,
+}
+
+impl Default for DownloadRetentionConfig {
+    fn default() -> Self {
+        Self {
+            delete_after_read: false,
+            keep_per_manga: None,
+            max_disk_usage: None,
+            interval: default_download_retention_interval(),
+        }
+    }
+}
+
+impl From<&DownloadRetentionConfig> for RetentionPolicy {
+    fn from(config: &DownloadRetentionConfig) -> Self {
+        Self {
+            delete_after_read: config.delete_after_read,
+            keep_per_manga: config.keep_per_manga,
+            max_disk_usage: config.max_disk_usage,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadFormat {
+    Cbz,
+    /// a folder of page images per chapter
+    Folder,
+    /// pages kept once by content in `download_path/.pages`, chapters are manifests of them
+    Dedup,
+}
+
+impl Default for DownloadFormat {
+    fn default() -> Self {
+        Self::Cbz
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DownloadFormatConfig {
+    #[serde(default)]
+    pub format: DownloadFormat,
+    /// by category name, a manga in several categories uses the first by name
+    #[serde(default)]
+    pub categories: HashMap<String, DownloadFormat>,
+}
+
+impl DownloadFormatConfig {
+    pub fn for_category(&self, category: Option<&str>) -> DownloadFormat {
+        category
+            .and_then(|category| self.categories.get(category))
+            .copied()
+            .unwrap_or(self.format)
+    }
+}
+
+/// Naming of downloaded chapters, see `PathTemplate` for the tokens
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DownloadLayoutConfig {
+    /// relative to download_path, `/` separates directories and `.cbz` is appended to archives,
+    /// e.g. `{category}/{manga}/{manga} Ch. {number:3}` for komga or kavita
+    #[serde(default = "default_download_template")]
+    pub template: String,
+    /// put in place of characters file names can't have
+    #[serde(default)]
+    pub replacement: String,
+    /// characters each path component is cut to, 0 is unlimited
+    #[serde(default)]
+    pub max_length: usize,
+}
+
+impl Default for DownloadLayoutConfig {
+    fn default() -> Self {
+        Self {
+            template: default_download_template(),
+            replacement: String::new(),
+            max_length: 0,
+        }
+    }
+}
+
+/// Download speed limits in bytes per second, unset or 0 is unlimited
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DownloadBandwidthConfig {
+    /// shared by every download
+    #[serde(default)]
+    pub limit: Option<u64>,
+    /// by source id, applied on top of `limit`
+    #[serde(default)]
+    pub sources: HashMap<i64, u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptimizeFormat {
+    Webp,
+    /// smaller than webp at the same quality but far slower to encode
+    Avif,
+}
+
+impl Default for OptimizeFormat {
+    fn default() -> Self {
+        Self::Webp
+    }
+}
+
+impl OptimizeFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OptimizeFormat::Webp => "webp",
+            OptimizeFormat::Avif => "avif",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            OptimizeFormat::Webp => "image/webp",
+            OptimizeFormat::Avif => "image/avif",
+        }
+    }
+}
+
+/// Re-encoding the pages of cbz archives to take less space
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ArchiveOptimizeConfig {
+    #[serde(default)]
+    pub format: OptimizeFormat,
+    /// 1 to 100, pages that don't come out smaller are kept as they are
+    #[serde(default = "default_optimize_quality")]
+    pub quality: u8,
+    /// keep the original of every rewritten archive next to it as `{name}.bak`
+    #[serde(default = "default_optimize_keep_backups")]
+    pub keep_backups: bool,
+    /// rewrite archives in local folders too, not only downloads
+    #[serde(default)]
+    pub include_local: bool,
+    /// seconds between runs, 0 only runs when asked to
+    #[serde(default)]
+    pub interval: u64,
+}
+
+/// Serving jpeg and png images in a smaller format to clients that accept it
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ImageTranscodeConfig {
+    /// by preference, the first the client accepts is used
+    #[serde(default = "default_transcode_formats")]
+    pub formats: Vec<OptimizeFormat>,
+    /// 1 to 100, images that don't come out smaller are served as they are
+    #[serde(default = "default_optimize_quality")]
+    pub quality: u8,
+}
+
+/// Warming the image cache with the next chapter while one is being read
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PrefetchConfig {
+    /// part of a chapter read before the next one is fetched, from 0 to 1
+    #[serde(default = "default_prefetch_threshold")]
+    pub threshold: f64,
+    /// pages fetched at the same time
+    #[serde(default = "default_prefetch_concurrency")]
+    pub concurrency: usize,
+}
+
+/// Headers sent on image requests to `domain` and its subdomains
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ProxyRuleConfig {
+    pub domain: String,
+    #[serde(default)]
+    pub referer: Option<String>,
+    /// sent as the `Cookie` header, e.g. `a=1; b=2`
+    #[serde(default)]
+    pub cookies: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+}
+
+impl From<&ProxyRuleConfig> for ProxyRule {
+    fn from(config: &ProxyRuleConfig) -> Self {
+        Self {
+            domain: config.domain.trim_start_matches("www.").to_lowercase(),
+            referer: config.referer.clone(),
+            cookies: config.cookies.clone(),
+            user_agent: config.user_agent.clone(),
+        }
+    }
+}
+
+/// S3 compatible bucket, e.g. AWS, MinIO or Backblaze B2
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct S3Config {
+    /// e.g. `https://s3.us-west-002.backblazeb2.com` or `http://localhost:9000`
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// address the bucket as `endpoint/bucket` instead of `bucket.endpoint`, as MinIO expects
+    #[serde(default)]
+    pub path_style: bool,
+    /// prepended to every key in the bucket
+    #[serde(default)]
+    pub prefix: String,
+}
+
+/// Where downloaded chapters and cached images are kept
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StorageConfig {
+    /// `download_path` and `cache_path` on disk
+    Local,
+    /// chapters are still downloaded to `download_path` then uploaded once complete
+    S3(S3Config),
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self::Local
+    }
+}
+
+/// How series in a local folder are split into chapters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LocalLayout {
+    /// folders holding archives or folders instead of images are volumes of chapters,
+    /// a series folder of loose images is a single chapter
+    Auto,
+    /// every archive and folder in a series is a chapter
+    Chapters,
+    /// every folder in a series is a volume of chapters
+    Volumes,
+    /// every archive and folder in the local folder is a series of a single chapter
+    Oneshots,
+}
+
+impl Default for LocalLayout {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+/// id of the first local source, lower ids are for extensions
+pub const LOCAL_SOURCE_ID: i64 = 10000;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LocalFolder {
     pub name: String,
     pub path: String,
+    /// source id, by default from the position in the list. Set it to keep the library
+    /// of a folder when folders before it are added or removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    #[serde(default)]
+    pub layout: LocalLayout,
+    /// regex finding the chapter number in a file or folder name, in its first group
+    /// if it has any
+    #[serde(default)]
+    pub chapter_pattern: Option<String>,
+    /// regex finding the volume number in a volume folder name
+    #[serde(default)]
+    pub volume_pattern: Option<String>,
+    /// regex of names of files and folders to leave out of the scan, e.g. `^(@eaDir|\.)`
+    /// for nas metadata and hidden files
+    #[serde(default)]
+    pub exclude: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -45,6 +518,50 @@ pub enum LocalFolders {
     Multiple(Vec<LocalFolder>),
 }
 
+impl LocalFolders {
+    /// Every local folder with the id of the source it's served as
+    pub fn sources(&self) -> Result<Vec<(i64, LocalFolder)>, anyhow::Error> {
+        let folders = match self {
+            LocalFolders::Single(path) => {
+                return Ok(vec![(
+                    LOCAL_SOURCE_ID,
+                    LocalFolder {
+                        name: "Local".to_string(),
+                        path: path.clone(),
+                        id: None,
+                        layout: LocalLayout::default(),
+                        chapter_pattern: None,
+                        volume_pattern: None,
+                        exclude: None,
+                    },
+                )])
+            }
+            LocalFolders::Multiple(folders) => folders,
+        };
+
+        let mut sources: Vec<(i64, LocalFolder)> = vec![];
+        for (index, folder) in folders.iter().enumerate() {
+            let id = folder.id.unwrap_or(LOCAL_SOURCE_ID + index as i64);
+            if id < LOCAL_SOURCE_ID {
+                return Err(anyhow::anyhow!(
+                    "id of local folder {} is below {LOCAL_SOURCE_ID}",
+                    folder.name
+                ));
+            }
+            if let Some((_, other)) = sources.iter().find(|(other_id, _)| *other_id == id) {
+                return Err(anyhow::anyhow!(
+                    "local folders {} and {} have the same id {id}",
+                    other.name,
+                    folder.name
+                ));
+            }
+            sources.push((id, folder.clone()));
+        }
+
+        Ok(sources)
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Config {
     #[serde(skip)]
@@ -71,15 +588,88 @@ pub struct Config {
     pub local_path: LocalFolders,
     #[serde(default = "default_download_path")]
     pub download_path: String,
+    #[serde(default)]
+    pub download_concurrency: DownloadConcurrencyConfig,
+    #[serde(default)]
+    pub download_bandwidth: DownloadBandwidthConfig,
+    #[serde(default)]
+    pub download_retention: DownloadRetentionConfig,
+    #[serde(default)]
+    pub download_layout: DownloadLayoutConfig,
+    #[serde(default)]
+    pub download_format: DownloadFormatConfig,
+    /// seconds between checks of downloaded archives, 0 only checks when asked to
+    #[serde(default)]
+    pub download_verify_interval: u64,
+    /// seconds between moving downloads into the page store when the format is `dedup` and
+    /// removing pages no chapter uses anymore, 0 only compacts when asked to
+    #[serde(default)]
+    pub download_compact_interval: u64,
+    /// re-encode the pages of archives to reclaim space, off unless set
+    #[serde(default)]
+    pub archive_optimize: Option<ArchiveOptimizeConfig>,
+    #[serde(default)]
+    pub storage: StorageConfig,
     #[serde(default = "default_cache_path")]
     pub cache_path: String,
+    /// covers of library manga are kept here, refreshed by library updates
+    #[serde(default = "default_cover_path")]
+    pub cover_path: String,
+    /// bytes cached images may take up in `cache_path`, least recently used are removed
+    /// past it, unlimited if not set
+    #[serde(default)]
+    pub image_cache_max_size: Option<u64>,
+    /// transcode images on the fly by the `Accept` header of requests, off unless set
+    #[serde(default)]
+    pub image_transcode: Option<ImageTranscodeConfig>,
+    /// fetch the pages of the next chapter once a user is far enough into one, off unless set
+    #[serde(default)]
+    pub prefetch: Option<PrefetchConfig>,
     #[serde(default)]
     pub enable_playground: bool,
+    /// serve `download_path` and finished exports read only over webdav at `/dav`
+    #[serde(default)]
+    pub enable_webdav: bool,
+    /// reload extensions in `plugin_path` whenever their file changes
+    #[serde(default)]
+    pub extension_dev_mode: bool,
     pub telegram: Option<TelegramConfig>,
     pub pushover: Option<PushoverConfig>,
     pub gotify: Option<GotifyConfig>,
+    #[serde(default)]
+    pub smtp: Option<SmtpConfig>,
     pub myanimelist: Option<MyAnimeListConfig>,
     pub anilist: Option<AniListConfig>,
+    #[serde(default)]
+    pub notification_template: NotificationTemplateConfig,
+    /// endpoint for anonymous usage stats, only used after admin opt-in
+    #[serde(default)]
+    pub telemetry_url: Option<String>,
+    /// proxy for sources without their own, e.g. `socks5://127.0.0.1:1080`
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// referer, cookies and user agent sent on image requests by domain
+    #[serde(default)]
+    pub proxy_rules: Vec<ProxyRuleConfig>,
+    /// requests per minute to each source without its own limit, unlimited if not set
+    #[serde(default)]
+    pub source_rate_limit: Option<u32>,
+    /// seconds popular, latest and search results of a source are reused, 0 disables
+    #[serde(default = "default_source_cache_ttl")]
+    pub source_cache_ttl: u64,
+    /// keep source results evicted from memory in `cache_path` until they expire
+    #[serde(default)]
+    pub source_cache_spill: bool,
+    #[serde(default)]
+    pub extension_limits: ExtensionLimitsConfig,
+    #[serde(default)]
+    pub source_retry: SourceRetryConfig,
+    /// extensions loaded at the same time on startup
+    #[serde(default = "default_extension_load_concurrency")]
+    pub extension_load_concurrency: usize,
+    /// seconds the extension repository index is reused before revalidating it
+    #[serde(default = "default_extension_index_ttl")]
+    pub extension_index_ttl: u64,
 }
 
 impl Default for Config {
@@ -97,13 +687,40 @@ impl Default for Config {
             plugin_path: default_plugin_path(),
             local_path: default_local_folders(),
             download_path: default_download_path(),
+            download_concurrency: DownloadConcurrencyConfig::default(),
+            download_bandwidth: DownloadBandwidthConfig::default(),
+            download_retention: DownloadRetentionConfig::default(),
+            download_layout: DownloadLayoutConfig::default(),
+            download_format: DownloadFormatConfig::default(),
+            download_verify_interval: 0,
+            download_compact_interval: 0,
+            archive_optimize: None,
+            storage: StorageConfig::default(),
             cache_path: default_cache_path(),
+            cover_path: default_cover_path(),
+            image_cache_max_size: None,
+            image_transcode: None,
+            prefetch: None,
             enable_playground: false,
+            enable_webdav: false,
+            extension_dev_mode: false,
             telegram: None,
             pushover: None,
             gotify: None,
+            smtp: None,
             myanimelist: None,
             anilist: None,
+            notification_template: NotificationTemplateConfig::default(),
+            telemetry_url: None,
+            proxy: None,
+            proxy_rules: vec![],
+            source_rate_limit: None,
+            source_cache_ttl: default_source_cache_ttl(),
+            source_cache_spill: false,
+            extension_limits: ExtensionLimitsConfig::default(),
+            source_retry: SourceRetryConfig::default(),
+            extension_load_concurrency: default_extension_load_concurrency(),
+            extension_index_ttl: default_extension_index_ttl(),
         }
     }
 }
@@ -115,6 +732,26 @@ fn tanoshi_home() -> PathBuf {
     }
 }
 
+fn default_chapter_title_template() -> String {
+    "{{manga_title}}".to_string()
+}
+
+fn default_chapter_body_template() -> String {
+    "{{chapter_title}}".to_string()
+}
+
+fn default_admin_body_template() -> String {
+    "{{message}}".to_string()
+}
+
+fn default_digest_title_template() -> String {
+    "{{count}} new chapters".to_string()
+}
+
+fn default_digest_body_template() -> String {
+    "{{chapters}}\n\n{{url}}".to_string()
+}
+
 fn default_port() -> u16 {
     80
 }
@@ -176,6 +813,88 @@ fn default_download_path() -> String {
     path.display().to_string()
 }
 
+fn default_download_template() -> String {
+    "{source}/{manga}/{chapter}".to_string()
+}
+
+fn default_smtp_max_attachment_size() -> u64 {
+    // kindle's limit is 50MB but most mail servers refuse more than 25MB
+    25 * 1024 * 1024
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_download_retention_interval() -> u64 {
+    3600
+}
+
+fn default_optimize_quality() -> u8 {
+    80
+}
+
+fn default_transcode_formats() -> Vec<OptimizeFormat> {
+    // avif takes too long to encode while a page is being waited for
+    vec![OptimizeFormat::Webp]
+}
+
+fn default_optimize_keep_backups() -> bool {
+    true
+}
+
+fn default_prefetch_threshold() -> f64 {
+    0.75
+}
+
+fn default_prefetch_concurrency() -> usize {
+    2
+}
+
+fn default_download_concurrency() -> usize {
+    1
+}
+
+fn default_extension_load_concurrency() -> usize {
+    4
+}
+
+fn default_extension_index_ttl() -> u64 {
+    600
+}
+
+fn default_extension_timeout() -> u64 {
+    60
+}
+
+fn default_extension_cpu_time() -> u64 {
+    30
+}
+
+fn default_extension_max_items() -> usize {
+    5000
+}
+
+fn default_extension_memory() -> usize {
+    256
+}
+
+fn default_source_retry_attempts() -> u32 {
+    3
+}
+
+fn default_source_retry_initial_backoff() -> u64 {
+    1000
+}
+
+fn default_source_retry_max_backoff() -> u64 {
+    30000
+}
+
+fn default_source_cache_ttl() -> u64 {
+    300
+}
+
 fn default_cache_path() -> String {
     let path = tanoshi_home().join("cache");
     if !path.exists() {
@@ -184,6 +903,10 @@ fn default_cache_path() -> String {
     path.display().to_string()
 }
 
+fn default_cover_path() -> String {
+    tanoshi_home().join("covers").display().to_string()
+}
+
 impl Config {
     pub fn open<P: AsRef<Path>>(path: Option<P>) -> Result<Config, anyhow::Error> {
         let config_path = match path {
@@ -194,8 +917,20 @@ impl Config {
         match std::fs::File::open(config_path.clone()) {
             Ok(file) => {
                 info!("Open config from {:?}", config_path);
-                let mut cfg: Self = serde_yaml::from_reader(file)?;
+                let value: serde_yaml::Value = serde_yaml::from_reader(file)?;
+                let has_secret = value.get("secret").is_some();
+                let mut cfg: Self = serde_yaml::from_value(value)?;
                 cfg.path = config_path;
+                // a secret generated on every start changes every encrypted image url,
+                // so browsers never get to reuse what they cached
+                if !has_secret {
+                    match cfg.append_secret() {
+                        Ok(_) => info!("Write generated secret to {:?}", cfg.path),
+                        Err(e) => warn!(
+                            "failed to save generated secret, image urls change on restart: {e}"
+                        ),
+                    }
+                }
                 Ok(cfg)
             }
             Err(_) => {
@@ -215,4 +950,21 @@ impl Config {
 
         Ok(())
     }
+
+    /// Add only the `secret` key to an existing config file, so comments, ordering
+    /// and unset defaults the user left out are kept as they are
+    fn append_secret(&self) -> Result<(), anyhow::Error> {
+        let content = std::fs::read_to_string(&self.path)?;
+        let separator = if content.is_empty() || content.ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        };
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&self.path)?;
+        // generated secrets are alphanumeric, nothing to quote
+        write!(file, "{separator}secret: {}\n", self.secret)?;
+
+        Ok(())
+    }
 }
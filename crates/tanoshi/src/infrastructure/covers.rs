@@ -0,0 +1,57 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+/// extensions a stored cover can have, one per manga
+const COVER_EXTENSIONS: &[&str] = &["jpg", "png", "webp", "gif", "avif"];
+
+/// Covers of library manga kept as `{manga_id}.{extension}`, so they're still shown when
+/// their source is uninstalled or offline
+#[derive(Debug, Clone)]
+pub struct CoverStore {
+    dir: PathBuf,
+}
+
+impl CoverStore {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    fn file(&self, manga_id: i64, extension: &str) -> PathBuf {
+        self.dir.join(format!("{manga_id}.{extension}"))
+    }
+
+    /// Stored cover of `manga_id`, if any
+    pub fn path(&self, manga_id: i64) -> Option<PathBuf> {
+        COVER_EXTENSIONS
+            .iter()
+            .map(|extension| self.file(manga_id, extension))
+            .find(|path| path.is_file())
+    }
+
+    /// Store `data` as the cover of `manga_id`, replacing the previous one
+    pub async fn save(&self, manga_id: i64, content_type: &str, data: &[u8]) -> Result<PathBuf> {
+        let extension = match content_type {
+            "image/png" => "png",
+            "image/webp" => "webp",
+            "image/gif" => "gif",
+            "image/avif" => "avif",
+            _ => "jpg",
+        };
+        let path = self.file(manga_id, extension);
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let mut part_path = path.as_os_str().to_owned();
+        part_path.push(".part");
+        tokio::fs::write(&part_path, data).await?;
+        tokio::fs::rename(part_path, &path).await?;
+
+        for other in COVER_EXTENSIONS.iter().filter(|other| **other != extension) {
+            let _ = tokio::fs::remove_file(self.file(manga_id, other)).await;
+        }
+
+        Ok(path)
+    }
+}
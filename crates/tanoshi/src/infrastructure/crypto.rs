@@ -0,0 +1,76 @@
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use anyhow::{anyhow, Result};
+use rand::RngCore;
+
+type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+const IV_LEN: usize = 16;
+
+/// Encrypt `plaintext` with the server secret, random iv is prepended to the ciphertext
+pub fn encrypt(secret: &str, plaintext: &str) -> Result<String> {
+    let mut iv = [0_u8; IV_LEN];
+    rand::thread_rng().fill_bytes(&mut iv);
+
+    let pos = plaintext.len();
+    let mut buffer = vec![0_u8; pos + IV_LEN];
+    buffer[..pos].copy_from_slice(plaintext.as_bytes());
+
+    let ciphertext = Aes128CbcEnc::new_from_slices(secret.as_bytes(), &iv)
+        .map_err(|e| anyhow!("invalid secret: {e}"))?
+        .encrypt_padded_mut::<Pkcs7>(&mut buffer, pos)
+        .map_err(|e| anyhow!("error encrypt: {e}"))?;
+
+    let mut data = iv.to_vec();
+    data.extend_from_slice(ciphertext);
+
+    Ok(base64::encode_config(data, base64::URL_SAFE_NO_PAD))
+}
+
+pub fn decrypt(secret: &str, encoded: &str) -> Result<String> {
+    let mut data = base64::decode_config(encoded, base64::URL_SAFE_NO_PAD)?;
+    if data.len() < IV_LEN {
+        return Err(anyhow!("ciphertext too short"));
+    }
+
+    let (iv, ciphertext) = data.split_at_mut(IV_LEN);
+    let plaintext = Aes128CbcDec::new_from_slices(secret.as_bytes(), iv)
+        .map_err(|e| anyhow!("invalid secret: {e}"))?
+        .decrypt_padded_mut::<Pkcs7>(ciphertext)
+        .map_err(|e| anyhow!("error decrypt: {e}"))?;
+
+    Ok(String::from_utf8(plaintext.to_vec())?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef";
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        for plaintext in ["", "session", "a session longer than a single block"] {
+            let encrypted = encrypt(SECRET, plaintext).unwrap();
+            assert_ne!(encrypted, plaintext);
+            assert_eq!(decrypt(SECRET, &encrypted).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_random_iv() {
+        assert_ne!(
+            encrypt(SECRET, "session").unwrap(),
+            encrypt(SECRET, "session").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decrypt_invalid() {
+        let encrypted = encrypt(SECRET, "session").unwrap();
+        assert!(decrypt("fedcba9876543210", &encrypted).map_or(true, |p| p != "session"));
+        assert!(decrypt(SECRET, "short").is_err());
+        assert!(decrypt(SECRET, "not base64!").is_err());
+        assert!(encrypt("short secret", "session").is_err());
+    }
+}
@@ -0,0 +1,208 @@
+use async_trait::async_trait;
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+
+use crate::{
+    domain::{
+        entities::automation::{AutomationAction, AutomationEvent, AutomationRule},
+        repositories::automation::{AutomationRepository, AutomationRepositoryError},
+    },
+    infrastructure::database::Pool,
+};
+
+#[derive(Clone)]
+pub struct AutomationRepositoryImpl {
+    pool: Pool,
+}
+
+impl AutomationRepositoryImpl {
+    pub fn new<P: Into<Pool>>(pool: P) -> Self {
+        Self { pool: pool.into() }
+    }
+}
+
+fn row_to_rule(row: &SqliteRow) -> Result<AutomationRule, AutomationRepositoryError> {
+    let event: String = row.get(3);
+    let action: String = row.get(4);
+
+    Ok(AutomationRule {
+        id: row.get(0),
+        user_id: row.get(1),
+        name: row.get(2),
+        event: event
+            .parse()
+            .map_err(|e: anyhow::Error| AutomationRepositoryError::InvalidRule(e.to_string()))?,
+        action: serde_json::from_str(&action)
+            .map_err(|e| AutomationRepositoryError::InvalidRule(e.to_string()))?,
+        enabled: row.get(5),
+        created_at: row.get(6),
+    })
+}
+
+fn rows_to_rules(rows: Vec<SqliteRow>) -> Vec<AutomationRule> {
+    rows.iter()
+        .filter_map(|row| match row_to_rule(row) {
+            Ok(rule) => Some(rule),
+            Err(e) => {
+                error!("skipping automation rule: {e}");
+                None
+            }
+        })
+        .collect()
+}
+
+fn serialize_action(action: &AutomationAction) -> Result<String, AutomationRepositoryError> {
+    serde_json::to_string(action).map_err(|e| AutomationRepositoryError::InvalidRule(e.to_string()))
+}
+
+#[async_trait]
+impl AutomationRepository for AutomationRepositoryImpl {
+    async fn get_rules_by_user_id(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<AutomationRule>, AutomationRepositoryError> {
+        let rows = sqlx::query(
+            r#"SELECT
+                id,
+                user_id,
+                name,
+                event,
+                action,
+                enabled,
+                created_at
+            FROM automation_rule
+            WHERE user_id = ?
+            ORDER BY id"#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(rows_to_rules(rows))
+    }
+
+    async fn get_enabled_rules_by_event(
+        &self,
+        user_id: i64,
+        event: AutomationEvent,
+    ) -> Result<Vec<AutomationRule>, AutomationRepositoryError> {
+        let rows = sqlx::query(
+            r#"SELECT
+                id,
+                user_id,
+                name,
+                event,
+                action,
+                enabled,
+                created_at
+            FROM automation_rule
+            WHERE user_id = ? AND event = ? AND enabled = true
+            ORDER BY id"#,
+        )
+        .bind(user_id)
+        .bind(event.to_string())
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(rows_to_rules(rows))
+    }
+
+    async fn get_rule_by_id(
+        &self,
+        user_id: i64,
+        id: i64,
+    ) -> Result<AutomationRule, AutomationRepositoryError> {
+        let row = sqlx::query(
+            r#"SELECT
+                id,
+                user_id,
+                name,
+                event,
+                action,
+                enabled,
+                created_at
+            FROM automation_rule
+            WHERE user_id = ? AND id = ?"#,
+        )
+        .bind(user_id)
+        .bind(id)
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
+
+        row_to_rule(&row)
+    }
+
+    async fn insert_rule(
+        &self,
+        user_id: i64,
+        name: &str,
+        event: AutomationEvent,
+        action: &AutomationAction,
+        enabled: bool,
+    ) -> Result<i64, AutomationRepositoryError> {
+        let id = sqlx::query(
+            r#"INSERT INTO automation_rule(user_id, name, event, action, enabled)
+            VALUES (?, ?, ?, ?, ?)"#,
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(event.to_string())
+        .bind(serialize_action(action)?)
+        .bind(enabled)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .last_insert_rowid();
+
+        Ok(id)
+    }
+
+    async fn update_rule(
+        &self,
+        user_id: i64,
+        id: i64,
+        name: &str,
+        event: AutomationEvent,
+        action: &AutomationAction,
+        enabled: bool,
+    ) -> Result<(), AutomationRepositoryError> {
+        sqlx::query(
+            r#"UPDATE automation_rule
+            SET name = ?, event = ?, action = ?, enabled = ?
+            WHERE user_id = ? AND id = ?"#,
+        )
+        .bind(name)
+        .bind(event.to_string())
+        .bind(serialize_action(action)?)
+        .bind(enabled)
+        .bind(user_id)
+        .bind(id)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_rule(&self, user_id: i64, id: i64) -> Result<(), AutomationRepositoryError> {
+        sqlx::query("DELETE FROM automation_rule WHERE user_id = ? AND id = ?")
+            .bind(user_id)
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn is_user_category(
+        &self,
+        user_id: i64,
+        category_id: i64,
+    ) -> Result<bool, AutomationRepositoryError> {
+        let row =
+            sqlx::query("SELECT EXISTS(SELECT 1 FROM user_category WHERE id = ? AND user_id = ?)")
+                .bind(category_id)
+                .bind(user_id)
+                .fetch_one(&self.pool as &SqlitePool)
+                .await?;
+
+        Ok(row.get(0))
+    }
+}
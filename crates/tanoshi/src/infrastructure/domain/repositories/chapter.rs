@@ -1,16 +1,23 @@
 use async_trait::async_trait;
 use chrono::Utc;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use sqlx::{Row, SqlitePool};
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
 
 use crate::{
     domain::{
-        entities::chapter::Chapter,
+        entities::chapter::{Chapter, SORT_KEY_TOLERANCE},
         repositories::chapter::{ChapterRepository, ChapterRepositoryError},
     },
     infrastructure::database::Pool,
 };
 
+/// Scanlation groups stored as a json array in column `index`
+fn groups_from_row(row: &SqliteRow, index: usize) -> Vec<String> {
+    row.get::<Option<String>, _>(index)
+        .and_then(|groups| serde_json::from_str(&groups).ok())
+        .unwrap_or_default()
+}
+
 #[derive(Clone)]
 pub struct ChapterRepositoryImpl {
     pool: Pool,
@@ -30,7 +37,7 @@ impl ChapterRepository for ChapterRepositoryImpl {
         }
 
         let mut values = vec![];
-        values.resize(chapters.len(), "(?, ?, ?, ?, ?, ?, ?, ?)");
+        values.resize(chapters.len(), "(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)");
 
         let query_str = format!(
             r#"INSERT INTO chapter(
@@ -39,16 +46,21 @@ impl ChapterRepository for ChapterRepositoryImpl {
             title,
             path,
             number,
+            sort_key,
             scanlator,
+            scanlation_groups,
+            language,
             uploaded,
             date_added
         ) VALUES {} ON CONFLICT(source_id, path) DO UPDATE SET
             manga_id=excluded.manga_id,
             title=excluded.title,
             number=excluded.number,
+            sort_key=excluded.sort_key,
             scanlator=excluded.scanlator,
-            uploaded=excluded.uploaded,
-            date_added=excluded.date_added
+            scanlation_groups=excluded.scanlation_groups,
+            language=excluded.language,
+            uploaded=excluded.uploaded
         "#,
             values.join(",")
         );
@@ -61,7 +73,10 @@ impl ChapterRepository for ChapterRepositoryImpl {
                 .bind(&chapter.title)
                 .bind(&chapter.path)
                 .bind(chapter.number)
+                .bind(chapter.sort_key)
                 .bind(&chapter.scanlator)
+                .bind(serde_json::to_string(&chapter.groups).unwrap_or_else(|_| "[]".to_string()))
+                .bind(&chapter.language)
                 .bind(chapter.uploaded)
                 .bind(Utc::now().naive_utc());
         }
@@ -75,8 +90,8 @@ impl ChapterRepository for ChapterRepositoryImpl {
         let row = sqlx::query(
             r#"SELECT 
                         chapter.*,
-                        (SELECT c.id FROM chapter c WHERE c.manga_id = chapter.manga_id AND c.number > chapter.number ORDER BY c.number ASC LIMIT 1) next,
-                        (SELECT c.id FROM chapter c WHERE c.manga_id = chapter.manga_id AND c.number < chapter.number ORDER BY c.number DESC LIMIT 1) prev
+                        (SELECT c.id FROM chapter c WHERE c.manga_id = chapter.manga_id AND c.sort_key > chapter.sort_key ORDER BY c.sort_key ASC LIMIT 1) next,
+                        (SELECT c.id FROM chapter c WHERE c.manga_id = chapter.manga_id AND c.sort_key < chapter.sort_key ORDER BY c.sort_key DESC LIMIT 1) prev
                     FROM chapter WHERE id = ?"#,
         )
         .bind(id)
@@ -94,8 +109,11 @@ impl ChapterRepository for ChapterRepositoryImpl {
             uploaded: row.get(7),
             date_added: row.get(8),
             downloaded_path: row.get(9),
-            next: row.get(10),
-            prev: row.get(11),
+            groups: groups_from_row(&row, 10),
+            language: row.get(11),
+            sort_key: row.get(14),
+            next: row.get(15),
+            prev: row.get(16),
         })
     }
 
@@ -107,8 +125,8 @@ impl ChapterRepository for ChapterRepositoryImpl {
         let row = sqlx::query(
             r#"SELECT 
                         chapter.*,
-                        (SELECT c.id FROM chapter c WHERE c.manga_id = chapter.manga_id AND c.number > chapter.number ORDER BY c.number ASC LIMIT 1) next,
-                        (SELECT c.id FROM chapter c WHERE c.manga_id = chapter.manga_id AND c.number < chapter.number ORDER BY c.number DESC LIMIT 1) prev
+                        (SELECT c.id FROM chapter c WHERE c.manga_id = chapter.manga_id AND c.sort_key > chapter.sort_key ORDER BY c.sort_key ASC LIMIT 1) next,
+                        (SELECT c.id FROM chapter c WHERE c.manga_id = chapter.manga_id AND c.sort_key < chapter.sort_key ORDER BY c.sort_key DESC LIMIT 1) prev
                     FROM chapter WHERE source_id = ? AND path = ?"#,
         )
         .bind(source_id)
@@ -127,11 +145,75 @@ impl ChapterRepository for ChapterRepositoryImpl {
             uploaded: row.get(7),
             date_added: row.get(8),
             downloaded_path: row.get(9),
-            next: row.get(10),
-            prev: row.get(11),
+            groups: groups_from_row(&row, 10),
+            language: row.get(11),
+            sort_key: row.get(14),
+            next: row.get(15),
+            prev: row.get(16),
         })
     }
 
+    async fn get_chapters_from_linked_manga(
+        &self,
+        manga_id: i64,
+        sort_key: f64,
+    ) -> Result<Vec<Chapter>, ChapterRepositoryError> {
+        let query_str = format!(
+            r#"SELECT chapter.* FROM manga_link
+            JOIN chapter ON chapter.manga_id = manga_link.linked_manga_id
+            WHERE manga_link.manga_id = ? AND ABS(chapter.sort_key - ?) < {SORT_KEY_TOLERANCE}
+            ORDER BY manga_link.priority"#
+        );
+        let chapters = sqlx::query(&query_str)
+            .bind(manga_id)
+            .bind(sort_key)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| Chapter {
+                id: row.get(0),
+                source_id: row.get(1),
+                manga_id: row.get(2),
+                title: row.get(3),
+                path: row.get(4),
+                number: row.get(5),
+                scanlator: row.get(6),
+                uploaded: row.get(7),
+                date_added: row.get(8),
+                downloaded_path: row.get(9),
+                groups: groups_from_row(&row, 10),
+                language: row.get(11),
+                sort_key: row.get(14),
+                next: None,
+                prev: None,
+            })
+            .collect();
+
+        Ok(chapters)
+    }
+
+    async fn get_linked_chapter_ids(
+        &self,
+        chapter_ids: &[i64],
+    ) -> Result<Vec<i64>, ChapterRepositoryError> {
+        let query_str = format!(
+            r#"SELECT DISTINCT linked.id FROM chapter
+            JOIN manga_link ON manga_link.manga_id = chapter.manga_id
+            JOIN chapter linked ON linked.manga_id = manga_link.linked_manga_id
+                AND ABS(linked.sort_key - chapter.sort_key) < {SORT_KEY_TOLERANCE}
+            WHERE chapter.id IN (SELECT value FROM json_each(?))"#
+        );
+        let chapter_ids = sqlx::query(&query_str)
+            .bind(serde_json::to_string(chapter_ids).unwrap_or_default())
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+
+        Ok(chapter_ids)
+    }
+
     async fn get_chapters_by_manga_id(
         &self,
         manga_id: i64,
@@ -142,14 +224,14 @@ impl ChapterRepository for ChapterRepositoryImpl {
         let limit = limit
             .map(|limit| format!("LIMIT {limit}"))
             .unwrap_or_else(|| "".to_string());
-        let order_by = order_by.unwrap_or("number");
+        let order_by = order_by.unwrap_or("sort_key");
         let order = if asc { "ASC" } else { "DESC" };
 
         let query_str = format!(
             r#"SELECT
                         chapter.*,
-                        (SELECT c.id FROM chapter c WHERE c.manga_id = chapter.manga_id AND c.number > chapter.number ORDER BY c.number ASC LIMIT 1) next,
-                        (SELECT c.id FROM chapter c WHERE c.manga_id = chapter.manga_id AND c.number < chapter.number ORDER BY c.number DESC LIMIT 1) prev
+                        (SELECT c.id FROM chapter c WHERE c.manga_id = chapter.manga_id AND c.sort_key > chapter.sort_key ORDER BY c.sort_key ASC LIMIT 1) next,
+                        (SELECT c.id FROM chapter c WHERE c.manga_id = chapter.manga_id AND c.sort_key < chapter.sort_key ORDER BY c.sort_key DESC LIMIT 1) prev
                     FROM chapter WHERE manga_id = ? ORDER BY {order_by} {order} {limit}"#,
         );
         let chapters = sqlx::query(&query_str)
@@ -168,8 +250,11 @@ impl ChapterRepository for ChapterRepositoryImpl {
                 uploaded: row.get(7),
                 date_added: row.get(8),
                 downloaded_path: row.get(9),
-                next: row.get(10),
-                prev: row.get(11),
+                groups: groups_from_row(&row, 10),
+                language: row.get(11),
+                sort_key: row.get(14),
+                next: row.get(15),
+                prev: row.get(16),
             })
             .collect();
 
@@ -249,6 +334,9 @@ impl ChapterRepository for ChapterRepositoryImpl {
                 uploaded: row.get(7),
                 date_added: row.get(8),
                 downloaded_path: row.get(9),
+                groups: groups_from_row(&row, 10),
+                language: row.get(11),
+                sort_key: row.get(14),
                 next: None,
                 prev: None,
             })
@@ -256,4 +344,60 @@ impl ChapterRepository for ChapterRepositoryImpl {
 
         Ok(chapters)
     }
+
+    async fn get_chapter_ids_with_history(
+        &self,
+        chapter_ids: &[i64],
+    ) -> Result<Vec<i64>, ChapterRepositoryError> {
+        let chapter_ids = sqlx::query(
+            r#"SELECT DISTINCT chapter_id FROM user_history
+            WHERE chapter_id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(serde_json::to_string(chapter_ids).unwrap_or_default())
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+        Ok(chapter_ids)
+    }
+
+    async fn migrate_chapter_state(
+        &self,
+        from_chapter_id: i64,
+        to_chapter_id: i64,
+    ) -> Result<(), ChapterRepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        // keep existing history of the new chapter if user already read it
+        sqlx::query(r#"UPDATE OR IGNORE user_history SET chapter_id = ? WHERE chapter_id = ?"#)
+            .bind(to_chapter_id)
+            .bind(from_chapter_id)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query(
+            r#"UPDATE chapter
+                SET (downloaded_path, downloaded_page_count, downloaded_size) = (
+                    SELECT downloaded_path, downloaded_page_count, downloaded_size
+                    FROM chapter WHERE id = ?
+                )
+                WHERE id = ? AND downloaded_path IS NULL"#,
+        )
+        .bind(from_chapter_id)
+        .bind(to_chapter_id)
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(r#"UPDATE download_queue SET chapter_id = ? WHERE chapter_id = ?"#)
+            .bind(to_chapter_id)
+            .bind(from_chapter_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
 }
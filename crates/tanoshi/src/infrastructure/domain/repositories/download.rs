@@ -4,10 +4,13 @@ use sqlx::{Row, SqlitePool};
 
 use crate::{
     domain::{
-        entities::download::{DownloadQueue, DownloadQueueEntry, DownloadedChapter},
+        entities::download::{
+            DownloadQueue, DownloadQueueEntry, DownloadedChapter, DownloadedChapterFile,
+            MangaDownloadUsage,
+        },
         repositories::download::{DownloadRepository, DownloadRepositoryError},
     },
-    infrastructure::database::Pool,
+    infrastructure::{config::LOCAL_SOURCE_ID, database::Pool},
 };
 
 #[derive(Clone)]
@@ -173,8 +176,46 @@ impl DownloadRepository for DownloadRepositoryImpl {
         chapter_id: i64,
         path: Option<String>,
     ) -> Result<(), DownloadRepositoryError> {
-        sqlx::query(r#"UPDATE chapter SET downloaded_path = ? WHERE id = ?"#)
-            .bind(path)
+        // size is recorded again once a download completes
+        sqlx::query(
+            r#"UPDATE chapter
+            SET downloaded_path = ?,
+            downloaded_size = CASE WHEN ? IS NULL THEN NULL ELSE downloaded_size END
+            WHERE id = ?"#,
+        )
+        .bind(&path)
+        .bind(&path)
+        .bind(chapter_id)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn update_chapter_downloaded_page_count(
+        &self,
+        chapter_id: i64,
+    ) -> Result<(), DownloadRepositoryError> {
+        sqlx::query(
+            r#"UPDATE chapter
+            SET downloaded_page_count = (SELECT COUNT(1) FROM download_queue WHERE chapter_id = ?)
+            WHERE id = ?"#,
+        )
+        .bind(chapter_id)
+        .bind(chapter_id)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn update_chapter_downloaded_size(
+        &self,
+        chapter_id: i64,
+        size: i64,
+    ) -> Result<(), DownloadRepositoryError> {
+        sqlx::query(r#"UPDATE chapter SET downloaded_size = ? WHERE id = ?"#)
+            .bind(size)
             .bind(chapter_id)
             .execute(&self.pool as &SqlitePool)
             .await?;
@@ -182,6 +223,33 @@ impl DownloadRepository for DownloadRepositoryImpl {
         Ok(())
     }
 
+    async fn get_download_usage(&self) -> Result<Vec<MangaDownloadUsage>, DownloadRepositoryError> {
+        let usage = sqlx::query(
+            r#"SELECT
+                manga.id,
+                manga.title,
+                COUNT(chapter.id),
+                COALESCE(SUM(chapter.downloaded_size), 0) AS size
+            FROM chapter
+            JOIN manga ON manga.id = chapter.manga_id
+            WHERE chapter.downloaded_path IS NOT NULL
+            GROUP BY manga.id
+            ORDER BY size DESC"#,
+        )
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| MangaDownloadUsage {
+            manga_id: row.get(0),
+            manga_title: row.get(1),
+            chapters: row.get(2),
+            size: row.get(3),
+        })
+        .collect();
+
+        Ok(usage)
+    }
+
     async fn insert_download_queue(
         &self,
         items: &[DownloadQueue],
@@ -229,29 +297,50 @@ impl DownloadRepository for DownloadRepositoryImpl {
         Ok(())
     }
 
-    async fn get_single_download_queue(
+    async fn get_next_download_queue(
         &self,
-    ) -> Result<Option<DownloadQueue>, DownloadRepositoryError> {
+        pages_per_chapter: i64,
+        limit: i64,
+    ) -> Result<Vec<DownloadQueue>, DownloadRepositoryError> {
         let data = sqlx::query(
             r#"SELECT 
-                    id,
-                    source_id,
-                    source_name,
-                    manga_id,
-                    manga_title,
-                    chapter_id,
-                    chapter_title,
-                    rank,
-                    url,
-                    priority,
-                    date_added 
-                FROM download_queue
-                WHERE downloaded IS NOT true
-                ORDER BY priority ASC, date_added ASC, chapter_id ASC, rank ASC
-                LIMIT 1"#,
+                    q.id,
+                    q.source_id,
+                    q.source_name,
+                    q.manga_id,
+                    q.manga_title,
+                    q.chapter_id,
+                    q.chapter_title,
+                    q.rank,
+                    q.url,
+                    q.priority,
+                    q.date_added,
+                    q.attempts,
+                    COALESCE(chapter.number, 0),
+                    (
+                        SELECT user_category.name FROM user_library
+                        JOIN library_category ON library_category.library_id = user_library.id
+                        JOIN user_category ON user_category.id = library_category.category_id
+                        WHERE user_library.manga_id = q.manga_id
+                        ORDER BY user_category.name
+                        LIMIT 1
+                    )
+                FROM (
+                    SELECT *, ROW_NUMBER() OVER (PARTITION BY chapter_id ORDER BY rank) AS chapter_rank
+                    FROM download_queue
+                    WHERE downloaded IS NOT true AND paused IS NOT true AND failed IS NOT true
+                    AND (retry_at IS NULL OR retry_at <= CAST(strftime('%s', 'now') AS INTEGER))
+                ) q
+                LEFT JOIN chapter ON chapter.id = q.chapter_id
+                WHERE q.chapter_rank <= ?
+                ORDER BY q.priority ASC, q.date_added ASC, q.chapter_id ASC, q.rank ASC
+                LIMIT ?"#,
         )
-        .fetch_optional(&self.pool as &SqlitePool)
+        .bind(pages_per_chapter)
+        .bind(limit)
+        .fetch_all(&self.pool as &SqlitePool)
         .await?
+        .iter()
         .map(|row| DownloadQueue {
             id: row.get(0),
             source_id: row.get(1),
@@ -264,7 +353,11 @@ impl DownloadRepository for DownloadRepositoryImpl {
             url: row.get(8),
             priority: row.get(9),
             date_added: row.get(10),
-        });
+            attempts: row.get(11),
+            chapter_number: row.get(12),
+            category: row.get(13),
+        })
+        .collect();
 
         Ok(data)
     }
@@ -308,6 +401,30 @@ impl DownloadRepository for DownloadRepositoryImpl {
         Ok(data)
     }
 
+    async fn get_next_download_retry_at(&self) -> Result<Option<i64>, DownloadRepositoryError> {
+        let data = sqlx::query(
+            r#"SELECT MIN(retry_at) FROM download_queue
+            WHERE downloaded IS NOT true AND paused IS NOT true AND failed IS NOT true
+            AND retry_at > CAST(strftime('%s', 'now') AS INTEGER)"#,
+        )
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?
+        .and_then(|row| row.try_get(0).ok());
+
+        Ok(data)
+    }
+
+    async fn get_download_queue_first_priority(
+        &self,
+    ) -> Result<Option<i64>, DownloadRepositoryError> {
+        let data = sqlx::query(r#"SELECT MIN(priority) FROM download_queue"#)
+            .fetch_optional(&self.pool as &SqlitePool)
+            .await?
+            .and_then(|row| row.try_get(0).ok());
+
+        Ok(data)
+    }
+
     async fn get_download_queue(&self) -> Result<Vec<DownloadQueueEntry>, DownloadRepositoryError> {
         let data = sqlx::query(
             r#"
@@ -320,7 +437,11 @@ impl DownloadRepository for DownloadRepositoryImpl {
                 dq.chapter_title, 
                 SUM(dq.downloaded),
                 COUNT(1),
-                dq.priority
+                dq.priority,
+                MAX(dq.paused),
+                MAX(dq.failed),
+                MAX(dq.attempts),
+                MAX(dq.last_error)
             FROM download_queue dq
             GROUP BY dq.chapter_id
             ORDER BY dq.priority ASC, dq.date_added ASC, dq.chapter_id ASC"#,
@@ -338,6 +459,10 @@ impl DownloadRepository for DownloadRepositoryImpl {
             downloaded: row.get(6),
             total: row.get(7),
             priority: row.get(8),
+            paused: row.get(9),
+            failed: row.get(10),
+            attempts: row.get(11),
+            last_error: row.get(12),
         })
         .collect();
 
@@ -401,4 +526,233 @@ impl DownloadRepository for DownloadRepositoryImpl {
 
         Ok(())
     }
+
+    async fn reorder_download_queue(
+        &self,
+        chapter_ids: &[i64],
+    ) -> Result<(), DownloadRepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        let queued: Vec<i64> = sqlx::query(
+            r#"SELECT chapter_id FROM download_queue
+            GROUP BY chapter_id
+            ORDER BY MIN(priority), MIN(date_added), chapter_id"#,
+        )
+        .fetch_all(&mut tx)
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+        let mut order: Vec<i64> = vec![];
+        for chapter_id in chapter_ids.iter().chain(queued.iter()) {
+            if queued.contains(chapter_id) && !order.contains(chapter_id) {
+                order.push(*chapter_id);
+            }
+        }
+
+        for (priority, chapter_id) in order.iter().enumerate() {
+            sqlx::query(r#"UPDATE download_queue SET priority = ? WHERE chapter_id = ?"#)
+                .bind(priority as i64)
+                .bind(chapter_id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn update_download_queue_paused(
+        &self,
+        chapter_id: i64,
+        paused: bool,
+    ) -> Result<(), DownloadRepositoryError> {
+        sqlx::query(r#"UPDATE download_queue SET paused = ? WHERE chapter_id = ?"#)
+            .bind(paused)
+            .bind(chapter_id)
+            .execute(&self.pool as &SqlitePool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn record_download_queue_failure(
+        &self,
+        id: i64,
+        error: &str,
+        retry_at: i64,
+    ) -> Result<(), DownloadRepositoryError> {
+        sqlx::query(
+            r#"UPDATE download_queue
+            SET attempts = attempts + 1, last_error = ?, retry_at = ?
+            WHERE id = ?"#,
+        )
+        .bind(error)
+        .bind(retry_at)
+        .bind(id)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn mark_chapter_download_failed(
+        &self,
+        chapter_id: i64,
+    ) -> Result<bool, DownloadRepositoryError> {
+        let res = sqlx::query(
+            r#"UPDATE download_queue SET failed = true WHERE chapter_id = ? AND failed = false"#,
+        )
+        .bind(chapter_id)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn reset_failed_download_queue(&self) -> Result<u64, DownloadRepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        let chapters: i64 = sqlx::query(
+            r#"SELECT COUNT(DISTINCT chapter_id) FROM download_queue WHERE failed = true"#,
+        )
+        .fetch_one(&mut tx)
+        .await?
+        .get(0);
+
+        sqlx::query(
+            r#"UPDATE download_queue
+            SET failed = false, attempts = 0, last_error = NULL, retry_at = NULL
+            WHERE chapter_id IN (SELECT chapter_id FROM download_queue WHERE failed = true)"#,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(chapters as u64)
+    }
+
+    async fn get_downloaded_chapter_files(
+        &self,
+    ) -> Result<Vec<DownloadedChapterFile>, DownloadRepositoryError> {
+        let files = sqlx::query(
+            r#"SELECT
+                chapter.id,
+                chapter.manga_id,
+                manga.title,
+                chapter.title,
+                chapter.number,
+                chapter.downloaded_path,
+                chapter.downloaded_page_count,
+                chapter.downloaded_size,
+                EXISTS (
+                    SELECT 1 FROM user_history
+                    WHERE user_history.chapter_id = chapter.id AND user_history.is_complete
+                ) AND NOT EXISTS (
+                    SELECT 1 FROM user_library
+                    WHERE user_library.manga_id = chapter.manga_id
+                    AND NOT EXISTS (
+                        SELECT 1 FROM user_history
+                        WHERE user_history.user_id = user_library.user_id
+                        AND user_history.chapter_id = chapter.id
+                        AND user_history.is_complete
+                    )
+                )
+            FROM chapter
+            JOIN manga ON manga.id = chapter.manga_id
+            WHERE chapter.downloaded_path IS NOT NULL
+            ORDER BY chapter.manga_id, chapter.sort_key DESC"#,
+        )
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| DownloadedChapterFile {
+            chapter_id: row.get(0),
+            manga_id: row.get(1),
+            manga_title: row.get(2),
+            chapter_title: row.get(3),
+            number: row.get(4),
+            path: row.get(5),
+            page_count: row.get(6),
+            size: row.get(7),
+            read: row.get(8),
+        })
+        .collect();
+
+        Ok(files)
+    }
+
+    async fn get_local_chapter_files(
+        &self,
+    ) -> Result<Vec<DownloadedChapterFile>, DownloadRepositoryError> {
+        let files = sqlx::query(
+            r#"SELECT
+                chapter.id,
+                chapter.manga_id,
+                manga.title,
+                chapter.title,
+                chapter.number,
+                chapter.path
+            FROM chapter
+            JOIN manga ON manga.id = chapter.manga_id
+            WHERE manga.source_id >= ?
+            ORDER BY chapter.manga_id, chapter.sort_key DESC"#,
+        )
+        .bind(LOCAL_SOURCE_ID)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| DownloadedChapterFile {
+            chapter_id: row.get(0),
+            manga_id: row.get(1),
+            manga_title: row.get(2),
+            chapter_title: row.get(3),
+            number: row.get(4),
+            path: row.get(5),
+            page_count: None,
+            size: None,
+            read: false,
+        })
+        .collect();
+
+        Ok(files)
+    }
+
+    async fn get_chapter_ids_to_download(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+        unread_only: bool,
+    ) -> Result<Vec<i64>, DownloadRepositoryError> {
+        // sources from 10000 up are local, there's nothing to download
+        let chapter_ids = sqlx::query(
+            r#"SELECT chapter.id FROM chapter
+            WHERE chapter.manga_id IN (SELECT value FROM json_each(?))
+            AND chapter.source_id < 10000
+            AND chapter.downloaded_path IS NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM download_queue WHERE download_queue.chapter_id = chapter.id
+            )
+            AND (NOT ? OR NOT EXISTS (
+                SELECT 1 FROM user_history
+                WHERE user_history.user_id = ?
+                AND user_history.chapter_id = chapter.id
+                AND user_history.is_complete
+            ))
+            ORDER BY chapter.manga_id, chapter.sort_key"#,
+        )
+        .bind(serde_json::to_string(manga_ids).unwrap_or_default())
+        .bind(unread_only)
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+        Ok(chapter_ids)
+    }
 }
@@ -1,13 +1,13 @@
 use std::collections::HashMap;
 
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use sqlx::{Row, SqlitePool};
 
 use crate::{
     domain::{
-        entities::history::HistoryChapter,
+        entities::history::{ChapterProgress, HistoryChapter},
         repositories::history::{HistoryRepository, HistoryRepositoryError},
     },
     infrastructure::database::Pool,
@@ -29,6 +29,7 @@ impl HistoryRepository for HistoryRepositoryImpl {
     async fn get_first_history_chapters(
         &self,
         user_id: i64,
+        hidden_manga_ids: &[i64],
         after_timestamp: i64,
         before_timestamp: i64,
         first: i32,
@@ -49,6 +50,7 @@ impl HistoryRepository for HistoryRepositoryImpl {
             user_history.user_id = ? AND
             chapter.id = user_history.chapter_id
         JOIN manga ON manga.id = chapter.manga_id
+            AND manga.id NOT IN (SELECT value FROM json_each(?))
         GROUP BY manga.id
         HAVING
             read_at < datetime(?, 'unixepoch') AND
@@ -57,6 +59,7 @@ impl HistoryRepository for HistoryRepositoryImpl {
         LIMIT ?"#,
         )
         .bind(user_id)
+        .bind(serde_json::to_string(hidden_manga_ids).unwrap_or_default())
         .bind(after_timestamp)
         .bind(before_timestamp)
         .bind(first)
@@ -81,6 +84,7 @@ impl HistoryRepository for HistoryRepositoryImpl {
     async fn get_last_history_chapters(
         &self,
         user_id: i64,
+        hidden_manga_ids: &[i64],
         after_timestamp: i64,
         before_timestamp: i64,
         last: i32,
@@ -102,6 +106,7 @@ impl HistoryRepository for HistoryRepositoryImpl {
                 user_history.user_id = ? AND
                 chapter.id = user_history.chapter_id
             JOIN manga ON manga.id = chapter.manga_id
+                AND manga.id NOT IN (SELECT value FROM json_each(?))
             GROUP BY manga.id
             HAVING
                 read_at < datetime(?, 'unixepoch') AND
@@ -110,6 +115,7 @@ impl HistoryRepository for HistoryRepositoryImpl {
             LIMIT ?) c ORDER BY c.read_at DESC, c.id DESC"#,
         )
         .bind(user_id)
+        .bind(serde_json::to_string(hidden_manga_ids).unwrap_or_default())
         .bind(after_timestamp)
         .bind(before_timestamp)
         .bind(last)
@@ -134,6 +140,7 @@ impl HistoryRepository for HistoryRepositoryImpl {
     async fn get_history_chapters(
         &self,
         user_id: i64,
+        hidden_manga_ids: &[i64],
         after_timestamp: i64,
         before_timestamp: i64,
     ) -> Result<Vec<HistoryChapter>, HistoryRepositoryError> {
@@ -153,6 +160,7 @@ impl HistoryRepository for HistoryRepositoryImpl {
             user_history.user_id = ? AND
             chapter.id = user_history.chapter_id
         JOIN manga ON manga.id = chapter.manga_id
+            AND manga.id NOT IN (SELECT value FROM json_each(?))
         GROUP BY manga.id
         HAVING
             read_at < datetime(?, 'unixepoch') AND
@@ -160,6 +168,7 @@ impl HistoryRepository for HistoryRepositoryImpl {
         ORDER BY user_history.read_at DESC, manga.id DESC"#,
         )
         .bind(user_id)
+        .bind(serde_json::to_string(hidden_manga_ids).unwrap_or_default())
         .bind(after_timestamp)
         .bind(before_timestamp)
         .fetch_all(&self.pool as &SqlitePool)
@@ -316,26 +325,22 @@ impl HistoryRepository for HistoryRepositoryImpl {
             return Ok(());
         }
 
-        let query_str = format!(
+        // ids as one json array, thousands of them would be past the limit of bound variables
+        sqlx::query(
             r#"
             INSERT INTO user_history(user_id, chapter_id, last_page, read_at, is_complete)
-            VALUES {}
+            SELECT ?, value, 0, ?, true FROM json_each(?) WHERE true
             ON CONFLICT(user_id, chapter_id)
             DO UPDATE SET
                 last_page = excluded.last_page,
                 read_at = excluded.read_at,
                 is_complete = excluded.is_complete"#,
-            vec!["(?, ?, 0, ?, true)"; chapter_ids.len()].join(",")
-        );
-
-        let mut query = sqlx::query(&query_str);
-
-        let now = Utc::now().naive_utc();
-        for chapter_id in chapter_ids.iter() {
-            query = query.bind(user_id).bind(chapter_id).bind(now);
-        }
-
-        query.execute(&self.pool as &SqlitePool).await?;
+        )
+        .bind(user_id)
+        .bind(Utc::now().naive_utc())
+        .bind(serde_json::to_string(chapter_ids).unwrap_or_default())
+        .execute(&self.pool as &SqlitePool)
+        .await?;
 
         Ok(())
     }
@@ -349,23 +354,77 @@ impl HistoryRepository for HistoryRepositoryImpl {
             return Ok(());
         }
 
-        let mut values = vec![];
-        values.resize(chapter_ids.len(), "?");
+        sqlx::query(
+            r#"DELETE FROM user_history
+            WHERE user_id = ? AND chapter_id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(user_id)
+        .bind(serde_json::to_string(chapter_ids).unwrap_or_default())
+        .execute(&self.pool as &SqlitePool)
+        .await?;
 
-        let query_str = format!(
-            r#"DELETE FROM user_history WHERE user_id = ? AND chapter_id IN ({})"#,
-            values.join(",")
-        );
+        Ok(())
+    }
 
-        let mut query = sqlx::query(&query_str).bind(user_id);
+    async fn insert_manga_chapters_as_completed(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+    ) -> Result<u64, HistoryRepositoryError> {
+        if manga_ids.is_empty() {
+            return Ok(0);
+        }
 
-        for chapter_id in chapter_ids.iter() {
-            query = query.bind(chapter_id);
+        let rows_affected = sqlx::query(
+            r#"
+            INSERT INTO user_history(user_id, chapter_id, last_page, read_at, is_complete)
+            SELECT ?, chapter.id, 0, ?, true FROM chapter
+            WHERE chapter.manga_id IN (SELECT value FROM json_each(?))
+            AND NOT EXISTS (
+                SELECT 1 FROM user_history
+                WHERE user_history.user_id = ?
+                AND user_history.chapter_id = chapter.id
+                AND user_history.is_complete
+            )
+            ON CONFLICT(user_id, chapter_id)
+            DO UPDATE SET
+                last_page = excluded.last_page,
+                read_at = excluded.read_at,
+                is_complete = excluded.is_complete"#,
+        )
+        .bind(user_id)
+        .bind(Utc::now().naive_utc())
+        .bind(serde_json::to_string(manga_ids).unwrap_or_default())
+        .bind(user_id)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn delete_manga_chapters_from_history(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+    ) -> Result<u64, HistoryRepositoryError> {
+        if manga_ids.is_empty() {
+            return Ok(0);
         }
 
-        query.execute(&self.pool as &SqlitePool).await?;
+        let rows_affected = sqlx::query(
+            r#"DELETE FROM user_history
+            WHERE user_id = ? AND chapter_id IN (
+                SELECT id FROM chapter WHERE manga_id IN (SELECT value FROM json_each(?))
+            )"#,
+        )
+        .bind(user_id)
+        .bind(serde_json::to_string(manga_ids).unwrap_or_default())
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
 
-        Ok(())
+        Ok(rows_affected)
     }
 
     async fn get_unread_chapters_by_manga_ids(
@@ -416,7 +475,7 @@ impl HistoryRepository for HistoryRepositoryImpl {
                 SELECT
                     chapter_id,
                     is_complete,
-                    number as chapter_number
+                    sort_key as chapter_sort_key
                 FROM
                     user_history
                     INNER JOIN chapter on chapter.id = user_history.chapter_id
@@ -438,7 +497,7 @@ impl HistoryRepository for HistoryRepositoryImpl {
                     manga_id = ?
                     AND user_history.is_complete IS NOT true
                 ORDER BY
-                    chapter.number ASC
+                    chapter.sort_key ASC
                 LIMIT
                     1
             ), resume_chapter AS (
@@ -453,11 +512,11 @@ impl HistoryRepository for HistoryRepositoryImpl {
                                     LEFT JOIN user_history ON user_history.chapter_id = chapter.id
                                     AND user_history.user_id = ?
                                 WHERE
-                                    chapter.number > chapter_number
+                                    chapter.sort_key > chapter_sort_key
                                     AND manga_id = ?
                                     AND user_history.is_complete IS NOT true
                                 ORDER BY
-                                    number ASC
+                                    sort_key ASC
                                 LIMIT
                                     1
                             )
@@ -494,4 +553,74 @@ impl HistoryRepository for HistoryRepositoryImpl {
 
         Ok(chapter_id)
     }
+
+    async fn get_chapter_progress_by_manga_id(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Vec<ChapterProgress>, HistoryRepositoryError> {
+        let progress = sqlx::query(
+            r#"SELECT
+                chapter.id,
+                chapter.number,
+                user_history.last_page,
+                IFNULL(user_history.is_complete, false),
+                user_history.read_at
+            FROM chapter
+            LEFT JOIN user_history ON
+                user_history.user_id = ? AND
+                user_history.chapter_id = chapter.id
+            WHERE chapter.manga_id = ?
+            ORDER BY chapter.sort_key ASC"#,
+        )
+        .bind(user_id)
+        .bind(manga_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .into_par_iter()
+        .map(|row| ChapterProgress {
+            chapter_id: row.get(0),
+            number: row.get(1),
+            last_page_read: row.get(2),
+            is_complete: row.get(3),
+            read_at: row.get(4),
+        })
+        .collect();
+
+        Ok(progress)
+    }
+
+    async fn get_stalled_manga_ids(
+        &self,
+        user_id: i64,
+        before: NaiveDateTime,
+    ) -> Result<Vec<i64>, HistoryRepositoryError> {
+        let manga_ids = sqlx::query(
+            r#"SELECT chapter.manga_id
+            FROM user_history
+            INNER JOIN chapter ON chapter.id = user_history.chapter_id
+            INNER JOIN user_library ON
+                user_library.user_id = user_history.user_id AND
+                user_library.manga_id = chapter.manga_id
+            WHERE user_history.user_id = ?
+            GROUP BY chapter.manga_id
+            HAVING MAX(user_history.read_at) < ? AND EXISTS (
+                SELECT 1 FROM chapter c
+                LEFT JOIN user_history uh ON
+                    uh.user_id = ? AND
+                    uh.chapter_id = c.id
+                WHERE c.manga_id = chapter.manga_id AND uh.is_complete IS NOT true
+            )"#,
+        )
+        .bind(user_id)
+        .bind(before)
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+        Ok(manga_ids)
+    }
 }
@@ -1,32 +1,97 @@
-use std::path::Path;
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
 
-use http::{HeaderMap, HeaderValue};
+use futures::{StreamExt, TryStreamExt};
+use http::{HeaderMap, HeaderValue, StatusCode};
+use reqwest::{RequestBuilder, Response};
 
-use crate::domain::{
-    entities::image::Image,
-    repositories::image::{ImageRepository, ImageRepositoryError},
+use crate::{
+    domain::{
+        entities::image::{Image, ImageHostStats, ImageStream},
+        repositories::image::{ImageRepository, ImageRepositoryError},
+    },
+    infrastructure::{
+        headers::HeaderTable,
+        pdf,
+        proxy::{host_of, ProxyTable},
+        storage::Storage,
+    },
 };
 
+/// Requests to each remote host by domain
+#[derive(Default, Clone)]
+struct HostStatsTable(Arc<Mutex<HashMap<String, ImageHostStats>>>);
+
+impl HostStatsTable {
+    fn update<F: FnOnce(&mut ImageHostStats)>(&self, url: &str, f: F) {
+        let domain = match host_of(url) {
+            Some(domain) => domain,
+            None => return,
+        };
+        if let Ok(mut hosts) = self.0.lock() {
+            f(hosts
+                .entry(domain.clone())
+                .or_insert_with(|| ImageHostStats {
+                    domain,
+                    ..Default::default()
+                }));
+        }
+    }
+
+    /// Count a request to the host of `url` answered after `elapsed`
+    fn record(&self, url: &str, elapsed: Duration, ok: bool) {
+        let elapsed = elapsed.as_millis() as u64;
+        self.update(url, |stats| {
+            stats.requests += 1;
+            stats.total_ms += elapsed;
+            stats.slowest_ms = stats.slowest_ms.max(elapsed);
+            if !ok {
+                stats.errors += 1;
+            }
+        });
+    }
+
+    fn add_bytes(&self, url: &str, bytes: u64) {
+        self.update(url, |stats| stats.bytes += bytes);
+    }
+
+    fn snapshot(&self) -> Vec<ImageHostStats> {
+        self.0
+            .lock()
+            .map(|hosts| hosts.values().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct ImageRepositoryImpl {
-    client: reqwest::Client,
+    proxies: ProxyTable,
+    headers: HeaderTable,
+    storage: Storage,
+    stats: HostStatsTable,
 }
 
 impl ImageRepositoryImpl {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(proxies: ProxyTable, headers: HeaderTable, storage: Storage) -> Self {
+        Self {
+            proxies,
+            headers,
+            storage,
+            stats: HostStatsTable::default(),
+        }
     }
-}
 
-#[async_trait]
-impl ImageRepository for ImageRepositoryImpl {
-    async fn fetch_image_from_url(
+    fn request(
         &self,
         url: &str,
         referer: Option<&String>,
-    ) -> Result<Image, ImageRepositoryError> {
+    ) -> Result<RequestBuilder, ImageRepositoryError> {
         debug!("get image from {}", url);
         if url.is_empty() {
             return Err(ImageRepositoryError::Other(
@@ -34,27 +99,119 @@ impl ImageRepository for ImageRepositoryImpl {
             ));
         }
 
+        // clients don't have to pass the referer, the proxy rules know it
+        let referer = referer.cloned().or_else(|| self.proxies.referer(url));
+        let referer = referer.as_ref();
+
         let mut headers = HeaderMap::new();
 
         if let Some(referer) = referer.and_then(|r| r.parse::<HeaderValue>().ok()) {
             headers.insert("Referer", referer);
         }
+        headers.extend(self.proxies.headers(url));
+        headers.extend(self.headers.resolve(url, referer.map(|r| r.as_str())));
 
-        let source_res = self.client.get(url).headers(headers).send().await?;
+        let client = self
+            .proxies
+            .client(self.proxies.resolve(url, referer.map(|r| r.as_str())))
+            .map_err(|e| ImageRepositoryError::Other(format!("{e}")))?;
 
-        let content_type = source_res
-            .headers()
-            .get("content-type")
-            .ok_or_else(|| ImageRepositoryError::Other("not a string".to_string()))?
-            .to_str()
-            .map_err(|_| ImageRepositoryError::Other("no content type".to_string()))?
-            .to_string();
+        Ok(client.get(url).headers(headers))
+    }
+}
+
+fn content_type(res: &Response) -> Result<String, ImageRepositoryError> {
+    Ok(res
+        .headers()
+        .get("content-type")
+        .ok_or_else(|| ImageRepositoryError::Other("not a string".to_string()))?
+        .to_str()
+        .map_err(|_| ImageRepositoryError::Other("no content type".to_string()))?
+        .to_string())
+}
+
+#[async_trait]
+impl ImageRepository for ImageRepositoryImpl {
+    async fn fetch_image_from_url(
+        &self,
+        url: &str,
+        referer: Option<&String>,
+    ) -> Result<Image, ImageRepositoryError> {
+        let start = Instant::now();
+        let source_res = match self.request(url, referer)?.send().await {
+            Ok(source_res) => source_res,
+            Err(e) => {
+                self.stats.record(url, start.elapsed(), false);
+                return Err(e.into());
+            }
+        };
+        self.stats
+            .record(url, start.elapsed(), source_res.status().is_success());
 
+        let content_type = content_type(&source_res)?;
         let data = source_res.bytes().await?;
+        self.stats.add_bytes(url, data.len() as u64);
 
         Ok(Image { content_type, data })
     }
 
+    async fn stream_image_from_url(
+        &self,
+        url: &str,
+        referer: Option<&String>,
+        range: Option<&str>,
+    ) -> Result<ImageStream, ImageRepositoryError> {
+        let mut req = self.request(url, referer)?;
+        if let Some(range) = range {
+            req = req.header("Range", range);
+        }
+        let start = Instant::now();
+        let source_res = match req.send().await {
+            Ok(source_res) => source_res,
+            Err(e) => {
+                self.stats.record(url, start.elapsed(), false);
+                return Err(e.into());
+            }
+        };
+        self.stats
+            .record(url, start.elapsed(), source_res.status().is_success());
+        if source_res.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Err(ImageRepositoryError::RangeNotSatisfiable);
+        }
+
+        let content_type = content_type(&source_res)?;
+        let content_range = if source_res.status() == StatusCode::PARTIAL_CONTENT {
+            source_res
+                .headers()
+                .get("content-range")
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        } else {
+            None
+        };
+
+        let stats = self.stats.clone();
+        let url = url.to_string();
+        Ok(ImageStream {
+            content_type,
+            content_length: source_res.content_length(),
+            content_range,
+            body: source_res
+                .bytes_stream()
+                .inspect_ok(move |chunk| stats.add_bytes(&url, chunk.len() as u64))
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+                .boxed(),
+        })
+    }
+
+    fn set_referer(&self, url: &str, referer: &str) {
+        self.proxies.set_referer(url, referer);
+    }
+
+    fn host_stats(&self) -> Vec<ImageHostStats> {
+        self.stats.snapshot()
+    }
+
     async fn fetch_image_from_file<P>(&self, path: P) -> Result<Image, ImageRepositoryError>
     where
         P: AsRef<Path> + std::marker::Send,
@@ -85,10 +242,16 @@ impl ImageRepository for ImageRepositoryImpl {
             .first_or_octet_stream()
             .to_string();
 
+        let is_pdf = pdf::is_pdf(archive.as_ref());
+        let path = archive.as_ref().to_path_buf();
         let source = std::fs::File::open(archive)
             .map_err(|e| ImageRepositoryError::Other(format!("{e}")))?;
         let (content_type, data) =
             tokio::task::spawn_blocking(move || -> Result<(String, Vec<u8>), anyhow::Error> {
+                if is_pdf {
+                    return Ok((content_type, pdf::read_page(&path, &filename)?));
+                }
+
                 let mut buf: Vec<u8> = vec![];
                 compress_tools::uncompress_archive_file(source, &mut buf, &filename)?;
 
@@ -103,4 +266,24 @@ impl ImageRepository for ImageRepositoryImpl {
             data: data.into(),
         })
     }
+
+    async fn fetch_image_from_stored_archive(
+        &self,
+        location: &str,
+        filename: &str,
+    ) -> Result<Image, ImageRepositoryError> {
+        let content_type = mime_guess::from_path(filename)
+            .first_or_octet_stream()
+            .to_string();
+        let data = self
+            .storage
+            .read_archive_page(location, filename)
+            .await
+            .map_err(|e| ImageRepositoryError::Other(format!("{e}")))?;
+
+        Ok(Image {
+            content_type,
+            data: data.into(),
+        })
+    }
 }
@@ -1,22 +1,162 @@
 use async_trait::async_trait;
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+use tokio::sync::Mutex;
 
-use crate::domain::{
-    entities::image::Image,
-    repositories::image_cache::{ImageCacheRepository, ImageCacheRepositoryError},
+use crate::{
+    domain::{
+        entities::image::{Image, ImageCacheStats},
+        repositories::image_cache::{ImageCacheRepository, ImageCacheRepositoryError},
+    },
+    infrastructure::storage::Storage,
 };
 
+/// directories of `path` holding cached images, other directories belong to other caches
+const CACHE_DIRS: &[&str] = &["covers", "variants"];
+
+/// Size and last use of every cached file, the order is kept in memory so after a
+/// restart files count as used when they were written
+#[derive(Default)]
+struct Usage {
+    loaded: bool,
+    size: u64,
+    clock: u64,
+    entries: HashMap<String, (u64, u64)>,
+}
+
+impl Usage {
+    fn insert(&mut self, key: &str, size: u64) {
+        self.clock += 1;
+        if let Some((old, _)) = self.entries.insert(key.to_string(), (size, self.clock)) {
+            self.size -= old;
+        }
+        self.size += size;
+    }
+
+    fn touch(&mut self, key: &str) {
+        self.clock += 1;
+        if let Some((_, used)) = self.entries.get_mut(key) {
+            *used = self.clock;
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((size, _)) = self.entries.remove(key) {
+            self.size -= size;
+        }
+    }
+}
+
+async fn cached_files(dir: &Path, prefix: &str, files: &mut Vec<(String, u64, SystemTime)>) {
+    let mut read_dir = match tokio::fs::read_dir(dir).await {
+        Ok(read_dir) => read_dir,
+        Err(_) => return,
+    };
+    while let Ok(Some(entry)) = read_dir.next_entry().await {
+        let metadata = match entry.metadata().await {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => continue,
+        };
+        files.push((
+            format!("{prefix}{}", entry.file_name().to_string_lossy()),
+            metadata.len(),
+            metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        ));
+    }
+}
+
 #[derive(Clone)]
 pub struct ImageCacheRepositoryImpl {
     path: PathBuf,
+    storage: Storage,
+    max_size: Option<u64>,
+    usage: Arc<Mutex<Usage>>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
 }
 
 impl ImageCacheRepositoryImpl {
     pub fn new<P: AsRef<Path>>(path: P) -> Self {
         Self {
             path: PathBuf::new().join(path),
+            storage: Storage::default(),
+            max_size: None,
+            usage: Arc::new(Mutex::new(Usage::default())),
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
         }
     }
+
+    /// Cache kept in the bucket of `storage` when it's remote, instead of `path`
+    pub fn with_storage(self, storage: Storage) -> Self {
+        Self { storage, ..self }
+    }
+
+    /// Bytes cached images may take up on disk, least recently used are removed past it
+    pub fn with_max_size(self, max_size: Option<u64>) -> Self {
+        Self { max_size, ..self }
+    }
+
+    async fn load(&self, usage: &mut Usage) {
+        if usage.loaded {
+            return;
+        }
+
+        let mut files = vec![];
+        cached_files(&self.path, "", &mut files).await;
+        for dir in CACHE_DIRS {
+            cached_files(&self.path.join(dir), &format!("{dir}/"), &mut files).await;
+        }
+        files.sort_by_key(|(_, _, modified)| *modified);
+        for (key, size, _) in files {
+            usage.insert(&key, size);
+        }
+        usage.loaded = true;
+    }
+
+    /// Record `key` as just written, then remove the least recently used images until the
+    /// cache is a tenth below `max_size` so it isn't trimmed on every write
+    async fn record(&self, key: &str, size: u64) {
+        let mut usage = self.usage.lock().await;
+        self.load(&mut usage).await;
+        usage.insert(key, size);
+
+        let max_size = match self.max_size {
+            Some(max_size) if usage.size > max_size => max_size,
+            _ => return,
+        };
+
+        let mut entries: Vec<(String, u64)> = usage
+            .entries
+            .iter()
+            .map(|(key, (_, used))| (key.clone(), *used))
+            .collect();
+        entries.sort_by_key(|(_, used)| *used);
+
+        let target = max_size - max_size / 10;
+        let mut evicted = 0;
+        for (key, _) in entries {
+            if usage.size <= target {
+                break;
+            }
+            if let Err(e) = tokio::fs::remove_file(self.path.join(&key)).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    error!("failed to evict cached image {key}: {e}");
+                    continue;
+                }
+            }
+            usage.remove(&key);
+            evicted += 1;
+        }
+        debug!("evicted {evicted} cached images");
+    }
 }
 
 #[async_trait]
@@ -26,7 +166,16 @@ impl ImageCacheRepository for ImageCacheRepositoryImpl {
 
         let encoded = bincode::serialize(&image)?;
 
+        if let Some(res) = self.storage.put_cache(key, encoded.clone()).await {
+            return res.map_err(|e| ImageCacheRepositoryError::Other(format!("{e}")));
+        }
+
+        // keys can be in a folder of their own, e.g. `covers/`
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
         tokio::fs::write(&path, &encoded).await?;
+        self.record(key, encoded.len() as u64).await;
 
         Ok(())
     }
@@ -34,10 +183,40 @@ impl ImageCacheRepository for ImageCacheRepositoryImpl {
     async fn get(&self, key: &str) -> Result<Image, ImageCacheRepositoryError> {
         let path = self.path.join(key);
 
-        let encoded = tokio::fs::read(path).await?;
+        let encoded = match self.storage.get_cache(key).await {
+            Some(res) => res
+                .map_err(|e| ImageCacheRepositoryError::Other(format!("{e}")))
+                .map(|data| data.to_vec()),
+            None => tokio::fs::read(path)
+                .await
+                .map_err(ImageCacheRepositoryError::from),
+        };
+        let encoded = match encoded {
+            Ok(encoded) => encoded,
+            Err(e) => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                return Err(e);
+            }
+        };
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        self.usage.lock().await.touch(key);
 
         let decoded = bincode::deserialize(&encoded)?;
 
         Ok(decoded)
     }
+
+    async fn stats(&self) -> ImageCacheStats {
+        let mut usage = self.usage.lock().await;
+        if !self.storage.is_remote() {
+            self.load(&mut usage).await;
+        }
+
+        ImageCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            size: usage.size,
+            max_size: self.max_size,
+        }
+    }
 }
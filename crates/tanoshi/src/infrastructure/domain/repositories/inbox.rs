@@ -0,0 +1,282 @@
+use async_trait::async_trait;
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+
+use crate::{
+    domain::{
+        entities::inbox::{ChapterDigestEntry, InboxKind, InboxNotification},
+        repositories::inbox::{InboxRepository, InboxRepositoryError},
+    },
+    infrastructure::database::Pool,
+};
+
+#[derive(Clone)]
+pub struct InboxRepositoryImpl {
+    pool: Pool,
+}
+
+impl InboxRepositoryImpl {
+    pub fn new<P: Into<Pool>>(pool: P) -> Self {
+        Self { pool: pool.into() }
+    }
+}
+
+fn rows_to_notifications(rows: Vec<SqliteRow>) -> Vec<InboxNotification> {
+    rows.iter()
+        .filter_map(|row| {
+            let kind: String = row.get(2);
+            match kind.parse() {
+                Ok(kind) => Some(InboxNotification {
+                    id: row.get(0),
+                    user_id: row.get(1),
+                    kind,
+                    title: row.get(3),
+                    body: row.get(4),
+                    url: row.get(5),
+                    is_read: row.get(6),
+                    created_at: row.get(7),
+                }),
+                Err(e) => {
+                    error!("skipping notification: {e}");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[async_trait]
+impl InboxRepository for InboxRepositoryImpl {
+    async fn insert_notification(
+        &self,
+        user_id: i64,
+        kind: InboxKind,
+        title: Option<&str>,
+        body: &str,
+        url: Option<&str>,
+    ) -> Result<i64, InboxRepositoryError> {
+        let row_id = sqlx::query(
+            r#"INSERT INTO notifications(user_id, kind, title, body, url)
+            VALUES (?, ?, ?, ?, ?)"#,
+        )
+        .bind(user_id)
+        .bind(kind.to_string())
+        .bind(title)
+        .bind(body)
+        .bind(url)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .last_insert_rowid();
+
+        Ok(row_id)
+    }
+
+    async fn get_first_notifications(
+        &self,
+        user_id: i64,
+        unread_only: bool,
+        after_timestamp: i64,
+        after_id: i64,
+        before_timestamp: i64,
+        before_id: i64,
+        first: i32,
+    ) -> Result<Vec<InboxNotification>, InboxRepositoryError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, user_id, kind, title, body, url, is_read, created_at
+            FROM notifications
+            WHERE
+                user_id = ? AND
+                (? = false OR is_read = false) AND
+                (created_at, id) < (datetime(?, 'unixepoch'), ?) AND
+                (created_at, id) > (datetime(?, 'unixepoch'), ?)
+            ORDER BY created_at DESC, id DESC
+            LIMIT ?"#,
+        )
+        .bind(user_id)
+        .bind(unread_only)
+        .bind(after_timestamp)
+        .bind(after_id)
+        .bind(before_timestamp)
+        .bind(before_id)
+        .bind(first)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(rows_to_notifications(rows))
+    }
+
+    async fn get_last_notifications(
+        &self,
+        user_id: i64,
+        unread_only: bool,
+        after_timestamp: i64,
+        after_id: i64,
+        before_timestamp: i64,
+        before_id: i64,
+        last: i32,
+    ) -> Result<Vec<InboxNotification>, InboxRepositoryError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM (
+                SELECT id, user_id, kind, title, body, url, is_read, created_at
+                FROM notifications
+                WHERE
+                    user_id = ? AND
+                    (? = false OR is_read = false) AND
+                    (created_at, id) < (datetime(?, 'unixepoch'), ?) AND
+                    (created_at, id) > (datetime(?, 'unixepoch'), ?)
+                ORDER BY created_at ASC, id ASC
+                LIMIT ?) n
+            ORDER BY n.created_at DESC, n.id DESC"#,
+        )
+        .bind(user_id)
+        .bind(unread_only)
+        .bind(after_timestamp)
+        .bind(after_id)
+        .bind(before_timestamp)
+        .bind(before_id)
+        .bind(last)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(rows_to_notifications(rows))
+    }
+
+    async fn get_notifications(
+        &self,
+        user_id: i64,
+        unread_only: bool,
+        after_timestamp: i64,
+        after_id: i64,
+        before_timestamp: i64,
+        before_id: i64,
+    ) -> Result<Vec<InboxNotification>, InboxRepositoryError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, user_id, kind, title, body, url, is_read, created_at
+            FROM notifications
+            WHERE
+                user_id = ? AND
+                (? = false OR is_read = false) AND
+                (created_at, id) < (datetime(?, 'unixepoch'), ?) AND
+                (created_at, id) > (datetime(?, 'unixepoch'), ?)
+            ORDER BY created_at DESC, id DESC"#,
+        )
+        .bind(user_id)
+        .bind(unread_only)
+        .bind(after_timestamp)
+        .bind(after_id)
+        .bind(before_timestamp)
+        .bind(before_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(rows_to_notifications(rows))
+    }
+
+    async fn get_unread_count(&self, user_id: i64) -> Result<i64, InboxRepositoryError> {
+        let row = sqlx::query(
+            r#"SELECT COUNT(1) FROM notifications WHERE user_id = ? AND is_read = false"#,
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(row.get(0))
+    }
+
+    async fn mark_as_read(&self, user_id: i64, ids: &[i64]) -> Result<u64, InboxRepositoryError> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut values = vec![];
+        values.resize(ids.len(), "?");
+
+        let query_str = format!(
+            r#"UPDATE notifications SET is_read = true WHERE user_id = ? AND id IN ({})"#,
+            values.join(",")
+        );
+
+        let mut query = sqlx::query(&query_str).bind(user_id);
+
+        for id in ids.iter() {
+            query = query.bind(id);
+        }
+
+        let rows_affected = query
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn mark_all_as_read(&self, user_id: i64) -> Result<u64, InboxRepositoryError> {
+        let rows_affected = sqlx::query(
+            r#"UPDATE notifications SET is_read = true WHERE user_id = ? AND is_read = false"#,
+        )
+        .bind(user_id)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn insert_digest_entry(
+        &self,
+        user_id: i64,
+        chapter_id: i64,
+    ) -> Result<i64, InboxRepositoryError> {
+        let row_id =
+            sqlx::query(r#"INSERT INTO chapter_digest(user_id, chapter_id) VALUES (?, ?)"#)
+                .bind(user_id)
+                .bind(chapter_id)
+                .execute(&self.pool as &SqlitePool)
+                .await?
+                .last_insert_rowid();
+
+        Ok(row_id)
+    }
+
+    async fn get_digest_entries(&self) -> Result<Vec<ChapterDigestEntry>, InboxRepositoryError> {
+        let entries = sqlx::query(
+            r#"SELECT
+                chapter_digest.id,
+                chapter_digest.user_id,
+                manga.title,
+                chapter.title,
+                chapter_digest.created_at
+            FROM chapter_digest
+            JOIN chapter ON chapter.id = chapter_digest.chapter_id
+            JOIN manga ON manga.id = chapter.manga_id
+            ORDER BY chapter_digest.created_at, chapter_digest.id"#,
+        )
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| ChapterDigestEntry {
+            id: row.get(0),
+            user_id: row.get(1),
+            manga_title: row.get(2),
+            chapter_title: row.get(3),
+            created_at: row.get(4),
+        })
+        .collect();
+
+        Ok(entries)
+    }
+
+    async fn delete_digest_entries(&self, ids: &[i64]) -> Result<u64, InboxRepositoryError> {
+        let rows_affected = sqlx::query(
+            r#"DELETE FROM chapter_digest WHERE id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(serde_json::to_string(ids).unwrap_or_default())
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected)
+    }
+}
@@ -3,12 +3,19 @@ use std::{collections::HashMap, pin::Pin};
 use async_trait::async_trait;
 use futures::{Stream, StreamExt};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use sqlx::{Row, SqlitePool};
+use sqlx::{
+    query::Query,
+    sqlite::{SqliteArguments, SqliteRow},
+    Row, Sqlite, SqlitePool,
+};
 
 use crate::{
     domain::{
         entities::{
-            library::{Category, LibraryUpdate},
+            library::{
+                AutoDownload, Category, ChapterAddition, DownloadAhead, LibraryAddition,
+                LibraryQuery, LibrarySort, LibraryUpdate, ReadingList, SmartRules, Tag,
+            },
             manga::Manga,
             user::User,
         },
@@ -17,6 +24,128 @@ use crate::{
     infrastructure::database::Pool,
 };
 
+/// Filters of a [`LibraryQuery`] on `manga` joined with `user_library`, bound by [`bind_query`]
+const QUERY_FILTERS: &str = r#"
+    (NOT ? OR EXISTS (
+        SELECT 1 FROM chapter
+        WHERE chapter.manga_id = manga.id
+        AND NOT EXISTS (
+            SELECT 1 FROM user_history
+            WHERE user_history.user_id = user_library.user_id
+            AND user_history.chapter_id = chapter.id
+            AND user_history.is_complete
+        )
+    ))
+    AND (NOT ? OR EXISTS (
+        SELECT 1 FROM chapter
+        WHERE chapter.manga_id = manga.id AND chapter.downloaded_path IS NOT NULL
+    ))
+    AND (? IS NULL OR manga.source_id IN (SELECT value FROM json_each(?)))
+    AND (? IS NULL OR lower(manga.status) = lower(?))"#;
+
+/// Recursive `category_ancestor` table pairing every category of a user with itself and each
+/// category it is nested in, binds the user id
+const CATEGORY_ANCESTORS: &str = r#"
+    category_ancestor(category_id, ancestor_id) AS (
+        SELECT id, id FROM user_category WHERE user_id = ?
+        UNION
+        SELECT category_ancestor.category_id, user_category.parent_id FROM category_ancestor
+        JOIN user_category ON user_category.id = category_ancestor.ancestor_id
+        WHERE user_category.parent_id IS NOT NULL
+    )"#;
+
+/// Excludes `user_library` entries in a private category that isn't unlocked, or in one
+/// nested in it, needs [`CATEGORY_ANCESTORS`] and binds the unlocked category ids
+const HIDDEN_LIBRARY_FILTER: &str = r#"
+    NOT EXISTS (
+        SELECT 1 FROM library_category
+        JOIN category_ancestor ON category_ancestor.category_id = library_category.category_id
+        JOIN user_category ON user_category.id = category_ancestor.ancestor_id
+        WHERE library_category.library_id = user_library.id
+        AND user_category.is_private
+        AND user_category.id NOT IN (SELECT value FROM json_each(?))
+    )"#;
+
+fn bind_query<'q>(
+    sql: Query<'q, Sqlite, SqliteArguments<'q>>,
+    query: &LibraryQuery,
+) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+    let source_ids = if query.source_ids.is_empty() {
+        None
+    } else {
+        serde_json::to_string(&query.source_ids).ok()
+    };
+
+    sql.bind(query.unread_only)
+        .bind(query.downloaded_only)
+        .bind(source_ids.clone())
+        .bind(source_ids)
+        .bind(query.status.clone())
+        .bind(query.status.clone())
+}
+
+/// `ORDER BY` of a [`LibraryQuery`], ties are ordered by title
+fn order_by(query: &LibraryQuery) -> String {
+    let column = match query.sort {
+        LibrarySort::Title => {
+            "COALESCE((SELECT title FROM manga_override WHERE manga_id = manga.id), manga.title)"
+        }
+        LibrarySort::LastRead => {
+            r#"(SELECT MAX(user_history.read_at) FROM user_history
+            JOIN chapter ON chapter.id = user_history.chapter_id
+            WHERE user_history.user_id = user_library.user_id AND chapter.manga_id = manga.id)"#
+        }
+        LibrarySort::LatestChapter => {
+            "(SELECT MAX(chapter.uploaded) FROM chapter WHERE chapter.manga_id = manga.id)"
+        }
+        LibrarySort::UnreadCount => {
+            r#"(SELECT COUNT(1) FROM chapter
+            WHERE chapter.manga_id = manga.id
+            AND NOT EXISTS (
+                SELECT 1 FROM user_history
+                WHERE user_history.user_id = user_library.user_id
+                AND user_history.chapter_id = chapter.id
+                AND user_history.is_complete
+            ))"#
+        }
+        // library entries are numbered in the order they were added
+        LibrarySort::DateAdded => "user_library.id",
+    };
+    let direction = if query.descending { "DESC" } else { "ASC" };
+
+    format!("{column} {direction}, manga.title")
+}
+
+/// Direction and `LIMIT` of the additions feeds, `last` reads the range from its oldest end
+fn addition_order(first: Option<i32>, last: Option<i32>) -> (&'static str, String) {
+    match (first, last) {
+        (Some(first), _) => ("DESC", format!("LIMIT {first}")),
+        (None, Some(last)) => ("ASC", format!("LIMIT {last}")),
+        (None, None) => ("DESC", "".to_string()),
+    }
+}
+
+/// Maps a `user_category` row selected as `id, name, is_private, pin, auto_download,
+/// auto_download_limit, rules, parent_id, position, is_default`
+fn category_from_row(row: &SqliteRow) -> Category {
+    Category {
+        id: row.get(0),
+        parent_id: row.get(7),
+        position: row.get(8),
+        is_default: row.get(9),
+        name: row.get(1),
+        is_private: row.get(2),
+        pin: row.get(3),
+        auto_download: AutoDownload {
+            enabled: row.get(4),
+            limit: row.get(5),
+        },
+        rules: row
+            .get::<Option<String>, _>(6)
+            .and_then(|rules| serde_json::from_str(&rules).ok()),
+    }
+}
+
 #[derive(Clone)]
 pub struct LibraryRepositoryImpl {
     pool: Pool,
@@ -37,19 +166,24 @@ impl LibraryRepository for LibraryRepositoryImpl {
         let categories = sqlx::query(
             r#"SELECT
                 id,
-                name
+                name,
+                is_private,
+                pin,
+                auto_download,
+                auto_download_limit,
+                rules,
+                parent_id,
+                position,
+                is_default
             FROM user_category
             WHERE user_id = ?
-            ORDER BY name"#,
+            ORDER BY position, name"#,
         )
         .bind(user_id)
         .fetch_all(&self.pool as &SqlitePool)
         .await?
         .into_par_iter()
-        .map(|row| Category {
-            id: row.get(0),
-            name: row.get(1),
-        })
+        .map(|row| category_from_row(&row))
         .collect();
 
         Ok(categories)
@@ -59,7 +193,15 @@ impl LibraryRepository for LibraryRepositoryImpl {
         let row = sqlx::query(
             r#"SELECT
                     id,
-                    name
+                    name,
+                    is_private,
+                    pin,
+                    auto_download,
+                    auto_download_limit,
+                    rules,
+                    parent_id,
+                    position,
+                    is_default
                 FROM user_category
                 WHERE id = ?"#,
         )
@@ -67,29 +209,30 @@ impl LibraryRepository for LibraryRepositoryImpl {
         .fetch_one(&self.pool as &SqlitePool)
         .await?;
 
-        Ok(Category {
-            id: row.get(0),
-            name: row.get(1),
-        })
+        Ok(category_from_row(&row))
     }
 
     async fn create_category(
         &self,
         user_id: i64,
+        parent_id: Option<i64>,
         name: &str,
+        rules: Option<&SmartRules>,
     ) -> Result<Category, LibraryRepositoryError> {
         let row = sqlx::query(
-            "INSERT INTO user_category (user_id, name) VALUES (?, ?) RETURNING id, name",
+            r#"INSERT INTO user_category (user_id, parent_id, name, rules, position)
+            VALUES (?, ?, ?, ?, (SELECT COALESCE(MAX(position) + 1, 0) FROM user_category WHERE user_id = ?))
+            RETURNING id, name, is_private, pin, auto_download, auto_download_limit, rules, parent_id, position, is_default"#,
         )
         .bind(user_id)
+        .bind(parent_id)
         .bind(name)
+        .bind(rules.and_then(|rules| serde_json::to_string(rules).ok()))
+        .bind(user_id)
         .fetch_one(&self.pool as &SqlitePool)
         .await?;
 
-        Ok(Category {
-            id: row.get(0),
-            name: row.get(1),
-        })
+        Ok(category_from_row(&row))
     }
 
     async fn rename_category(
@@ -97,16 +240,15 @@ impl LibraryRepository for LibraryRepositoryImpl {
         id: i64,
         name: &str,
     ) -> Result<Category, LibraryRepositoryError> {
-        let row = sqlx::query("UPDATE user_category SET name = ? WHERE id = ? RETURNING id, name")
-            .bind(name)
-            .bind(id)
-            .fetch_one(&self.pool as &SqlitePool)
-            .await?;
+        let row = sqlx::query(
+            "UPDATE user_category SET name = ? WHERE id = ? RETURNING id, name, is_private, pin, auto_download, auto_download_limit, rules, parent_id, position, is_default",
+        )
+        .bind(name)
+        .bind(id)
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
 
-        Ok(Category {
-            id: row.get(0),
-            name: row.get(1),
-        })
+        Ok(category_from_row(&row))
     }
 
     async fn delete_category(&self, id: i64) -> Result<(), LibraryRepositoryError> {
@@ -118,212 +260,1253 @@ impl LibraryRepository for LibraryRepositoryImpl {
         Ok(())
     }
 
-    async fn get_category_count(
+    async fn set_category_privacy(
         &self,
         user_id: i64,
-    ) -> Result<HashMap<Option<i64>, i64>, LibraryRepositoryError> {
-        let data = sqlx::query(
-            "SELECT user_category.id, COUNT(1) FROM manga
-        INNER JOIN user_library ON user_library.user_id = ? AND manga.id = user_library.manga_id
-        LEFT JOIN library_category ON user_library.id = library_category.library_id
-        LEFT JOIN user_category ON library_category.category_id = user_category.id
-        GROUP BY user_category.id",
+        id: i64,
+        is_private: bool,
+        pin: Option<&str>,
+    ) -> Result<Category, LibraryRepositoryError> {
+        let row = sqlx::query(
+            r#"UPDATE user_category SET is_private = ?, pin = ?
+            WHERE id = ? AND user_id = ?
+            RETURNING id, name, is_private, pin, auto_download, auto_download_limit, rules, parent_id, position, is_default"#,
         )
+        .bind(is_private)
+        .bind(pin)
+        .bind(id)
         .bind(user_id)
-        .fetch_all(&self.pool as &SqlitePool)
-        .await?
-        .into_par_iter()
-        .map(|row| (row.get(0), row.get(1)))
-        .collect();
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
 
-        Ok(data)
+        Ok(category_from_row(&row))
     }
 
-    async fn get_users_by_manga_id(
+    async fn set_category_rules(
         &self,
-        manga_id: i64,
-    ) -> Result<Vec<User>, LibraryRepositoryError> {
-        let users = sqlx::query(
-            r#"SELECT user.* FROM user_library
-                    JOIN user ON user_library.user_id = user.id
-                    WHERE user_library.manga_id = ?"#,
+        user_id: i64,
+        id: i64,
+        rules: Option<&SmartRules>,
+    ) -> Result<Category, LibraryRepositoryError> {
+        let row = sqlx::query(
+            r#"UPDATE user_category SET rules = ?
+            WHERE id = ? AND user_id = ?
+            RETURNING id, name, is_private, pin, auto_download, auto_download_limit, rules, parent_id, position, is_default"#,
         )
-        .bind(manga_id)
-        .fetch_all(&self.pool as &SqlitePool)
-        .await?
-        .into_par_iter()
-        .map(|row| User {
-            id: row.get(0),
-            username: row.get(1),
-            password: "".to_string(),
-            is_admin: row.get(3),
-            created_at: row.get(4),
-            updated_at: row.get(5),
-            telegram_chat_id: row.get(6),
-            pushover_user_key: row.get(7),
-            gotify_token: row.get(8),
-        })
-        .collect();
+        .bind(rules.and_then(|rules| serde_json::to_string(rules).ok()))
+        .bind(id)
+        .bind(user_id)
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
 
-        Ok(users)
+        Ok(category_from_row(&row))
     }
 
-    async fn get_manga_from_all_users_library(
+    async fn set_category_parent(
         &self,
-    ) -> Pin<Box<dyn Stream<Item = Result<Manga, LibraryRepositoryError>>>> {
-        let stream = sqlx::query(
-            r#"SELECT DISTINCT manga.*, MAX(chapter.uploaded) as last_uploaded FROM manga
-                    JOIN user_library ON manga.id = user_library.manga_id
-                    JOIN chapter ON manga.id = chapter.manga_id
-                    GROUP by manga.id"#,
+        user_id: i64,
+        id: i64,
+        parent_id: Option<i64>,
+    ) -> Result<Category, LibraryRepositoryError> {
+        let row = sqlx::query(
+            r#"UPDATE user_category SET parent_id = ?
+            WHERE id = ? AND user_id = ?
+            RETURNING id, name, is_private, pin, auto_download, auto_download_limit, rules, parent_id, position, is_default"#,
         )
-        .fetch(&self.pool as &SqlitePool)
-        .map(|row| {
-            row.map(|row| Manga {
+        .bind(parent_id)
+        .bind(id)
+        .bind(user_id)
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(category_from_row(&row))
+    }
+
+    async fn set_category_positions(
+        &self,
+        user_id: i64,
+        ids: &[i64],
+    ) -> Result<(), LibraryRepositoryError> {
+        sqlx::query(
+            r#"UPDATE user_category
+            SET position = (SELECT key FROM json_each(?) WHERE value = user_category.id)
+            WHERE user_id = ? AND id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(serde_json::to_string(ids).unwrap_or_default())
+        .bind(user_id)
+        .bind(serde_json::to_string(ids).unwrap_or_default())
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn set_default_category(
+        &self,
+        user_id: i64,
+        id: Option<i64>,
+    ) -> Result<(), LibraryRepositoryError> {
+        sqlx::query("UPDATE user_category SET is_default = id IS ? WHERE user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool as &SqlitePool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_tags_by_user_id(&self, user_id: i64) -> Result<Vec<Tag>, LibraryRepositoryError> {
+        let tags = sqlx::query("SELECT id, name FROM user_tag WHERE user_id = ? ORDER BY name")
+            .bind(user_id)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_iter()
+            .map(|row| Tag {
                 id: row.get(0),
-                source_id: row.get(1),
-                title: row.get(2),
-                author: serde_json::from_str(row.get::<String, _>(3).as_str()).unwrap_or_default(),
-                genre: serde_json::from_str(row.get::<String, _>(4).as_str()).unwrap_or_default(),
-                status: row.get(5),
-                description: row.get(6),
-                path: row.get(7),
-                cover_url: row.get(8),
-                date_added: row.get(9),
-                last_uploaded_at: row.get(10),
+                name: row.get(1),
             })
-            .map_err(LibraryRepositoryError::DbError)
-        })
-        .boxed();
+            .collect();
 
-        stream
+        Ok(tags)
     }
 
-    async fn get_manga_from_library(
+    async fn create_tag(&self, user_id: i64, name: &str) -> Result<Tag, LibraryRepositoryError> {
+        let row =
+            sqlx::query("INSERT INTO user_tag (user_id, name) VALUES (?, ?) RETURNING id, name")
+                .bind(user_id)
+                .bind(name)
+                .fetch_one(&self.pool as &SqlitePool)
+                .await?;
+
+        Ok(Tag {
+            id: row.get(0),
+            name: row.get(1),
+        })
+    }
+
+    async fn rename_tag(
         &self,
         user_id: i64,
-    ) -> Result<Vec<Manga>, LibraryRepositoryError> {
-        let manga = sqlx::query(
-            r#"SELECT manga.*, library_category.category_id FROM manga
-            INNER JOIN user_library ON user_library.user_id = ? AND manga.id = user_library.manga_id
-            LEFT JOIN library_category ON user_library.id = library_category.library_id
-            ORDER BY title"#,
+        id: i64,
+        name: &str,
+    ) -> Result<Tag, LibraryRepositoryError> {
+        let row = sqlx::query(
+            "UPDATE user_tag SET name = ? WHERE id = ? AND user_id = ? RETURNING id, name",
         )
+        .bind(name)
+        .bind(id)
         .bind(user_id)
-        .fetch_all(&self.pool as &SqlitePool)
-        .await?
-        .into_par_iter()
-        .map(|row| Manga {
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(Tag {
             id: row.get(0),
-            source_id: row.get(1),
-            title: row.get(2),
-            author: serde_json::from_str(row.get::<String, _>(3).as_str()).unwrap_or_default(),
-            genre: serde_json::from_str(row.get::<String, _>(4).as_str()).unwrap_or_default(),
-            status: row.get(5),
-            description: row.get(6),
-            path: row.get(7),
-            cover_url: row.get(8),
-            date_added: row.get(9),
-            last_uploaded_at: None,
+            name: row.get(1),
         })
-        .collect();
+    }
 
-        Ok(manga)
+    async fn delete_tag(&self, user_id: i64, id: i64) -> Result<(), LibraryRepositoryError> {
+        sqlx::query("DELETE FROM user_tag WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool as &SqlitePool)
+            .await?;
+
+        Ok(())
     }
 
-    async fn get_manga_from_library_by_category_id(
+    async fn get_library_tags(
         &self,
         user_id: i64,
-        category_id: Option<i64>,
-    ) -> Result<Vec<Manga>, LibraryRepositoryError> {
-        let manga = sqlx::query(
-            r#"SELECT manga.*, library_category.category_id FROM manga
-            INNER JOIN user_library ON user_library.user_id = ? AND manga.id = user_library.manga_id
-            LEFT JOIN library_category ON user_library.id = library_category.library_id
-            WHERE category_id IS ?
-            ORDER BY title"#,
+    ) -> Result<HashMap<i64, Vec<Tag>>, LibraryRepositoryError> {
+        let rows = sqlx::query(
+            r#"SELECT user_library.manga_id, user_tag.id, user_tag.name FROM user_library
+            JOIN library_tag ON library_tag.library_id = user_library.id
+            JOIN user_tag ON user_tag.id = library_tag.tag_id
+            WHERE user_library.user_id = ?
+            ORDER BY user_tag.name"#,
         )
         .bind(user_id)
-        .bind(category_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?;
+
+        let mut tags: HashMap<i64, Vec<Tag>> = HashMap::new();
+        for row in rows {
+            tags.entry(row.get(0)).or_default().push(Tag {
+                id: row.get(1),
+                name: row.get(2),
+            });
+        }
+
+        Ok(tags)
+    }
+
+    async fn get_tags_by_manga_id(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Vec<Tag>, LibraryRepositoryError> {
+        let tags = sqlx::query(
+            r#"SELECT user_tag.id, user_tag.name FROM user_library
+            JOIN library_tag ON library_tag.library_id = user_library.id
+            JOIN user_tag ON user_tag.id = library_tag.tag_id
+            WHERE user_library.user_id = ? AND user_library.manga_id = ?
+            ORDER BY user_tag.name"#,
+        )
+        .bind(user_id)
+        .bind(manga_id)
         .fetch_all(&self.pool as &SqlitePool)
         .await?
-        .into_par_iter()
-        .map(|row| Manga {
+        .into_iter()
+        .map(|row| Tag {
             id: row.get(0),
-            source_id: row.get(1),
-            title: row.get(2),
-            author: serde_json::from_str(row.get::<String, _>(3).as_str()).unwrap_or_default(),
-            genre: serde_json::from_str(row.get::<String, _>(4).as_str()).unwrap_or_default(),
-            status: row.get(5),
-            description: row.get(6),
-            path: row.get(7),
-            cover_url: row.get(8),
-            date_added: row.get(9),
-            last_uploaded_at: None,
+            name: row.get(1),
         })
         .collect();
 
-        Ok(manga)
+        Ok(tags)
     }
 
-    async fn insert_manga_to_library(
+    async fn set_manga_tags(
         &self,
         user_id: i64,
         manga_id: i64,
-        category_ids: &[i64],
+        tag_ids: &[i64],
     ) -> Result<(), LibraryRepositoryError> {
         let mut tx = self.pool.begin().await?;
 
-        let library_id = sqlx::query("INSERT INTO user_library(user_id, manga_id) VALUES (?, ?)")
-            .bind(user_id)
-            .bind(manga_id)
-            .execute(&mut tx)
-            .await
-            .map(|res| res.last_insert_rowid())?;
+        let library_id: i64 =
+            sqlx::query("SELECT id FROM user_library WHERE user_id = ? AND manga_id = ?")
+                .bind(user_id)
+                .bind(manga_id)
+                .fetch_optional(&mut tx)
+                .await?
+                .ok_or(LibraryRepositoryError::NotInLibrary)?
+                .get(0);
 
-        if !category_ids.is_empty() {
-            let query_str = format!(
-                "INSERT INTO library_category(library_id, category_id) VALUES {}",
-                vec!["(?,?)".to_string(); category_ids.len()].join(",")
-            );
+        sqlx::query("DELETE FROM library_tag WHERE library_id = ?")
+            .bind(library_id)
+            .execute(&mut tx)
+            .await?;
 
-            let mut query = sqlx::query(&query_str);
-            for category_id in category_ids {
-                query = query.bind(library_id).bind(category_id);
-            }
-            query.execute(&mut tx).await?;
-        }
+        sqlx::query(
+            r#"INSERT INTO library_tag(library_id, tag_id)
+            SELECT ?, id FROM user_tag
+            WHERE user_id = ? AND id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(library_id)
+        .bind(user_id)
+        .bind(serde_json::to_string(tag_ids).unwrap_or_default())
+        .execute(&mut tx)
+        .await?;
 
         tx.commit().await?;
 
         Ok(())
     }
 
-    async fn delete_manga_from_library(
+    async fn get_reading_lists(
         &self,
         user_id: i64,
-        manga_id: i64,
-    ) -> Result<(), LibraryRepositoryError> {
-        sqlx::query("DELETE FROM user_library WHERE user_id = ? AND manga_id = ?")
-            .bind(user_id)
-            .bind(manga_id)
-            .execute(&self.pool as &SqlitePool)
-            .await?;
-
+    ) -> Result<Vec<ReadingList>, LibraryRepositoryError> {
+        let reading_lists = sqlx::query(
+            r#"SELECT id, name, description,
+                (SELECT COUNT(1) FROM reading_list_manga WHERE reading_list_id = reading_list.id)
+            FROM reading_list
+            WHERE user_id = ?
+            ORDER BY name"#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .into_iter()
+        .map(|row| ReadingList {
+            id: row.get(0),
+            name: row.get(1),
+            description: row.get(2),
+            count: row.get(3),
+        })
+        .collect();
+
+        Ok(reading_lists)
+    }
+
+    async fn get_reading_list(
+        &self,
+        user_id: i64,
+        id: i64,
+    ) -> Result<ReadingList, LibraryRepositoryError> {
+        let row = sqlx::query(
+            r#"SELECT id, name, description,
+                (SELECT COUNT(1) FROM reading_list_manga WHERE reading_list_id = reading_list.id)
+            FROM reading_list
+            WHERE id = ? AND user_id = ?"#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(ReadingList {
+            id: row.get(0),
+            name: row.get(1),
+            description: row.get(2),
+            count: row.get(3),
+        })
+    }
+
+    async fn get_reading_lists_by_manga_id(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Vec<ReadingList>, LibraryRepositoryError> {
+        let reading_lists = sqlx::query(
+            r#"SELECT id, name, description,
+                (SELECT COUNT(1) FROM reading_list_manga WHERE reading_list_id = reading_list.id)
+            FROM reading_list
+            WHERE user_id = ?
+            AND id IN (SELECT reading_list_id FROM reading_list_manga WHERE manga_id = ?)
+            ORDER BY name"#,
+        )
+        .bind(user_id)
+        .bind(manga_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .into_iter()
+        .map(|row| ReadingList {
+            id: row.get(0),
+            name: row.get(1),
+            description: row.get(2),
+            count: row.get(3),
+        })
+        .collect();
+
+        Ok(reading_lists)
+    }
+
+    async fn create_reading_list(
+        &self,
+        user_id: i64,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<ReadingList, LibraryRepositoryError> {
+        let row = sqlx::query(
+            r#"INSERT INTO reading_list (user_id, name, description) VALUES (?, ?, ?)
+            RETURNING id, name, description"#,
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(description)
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(ReadingList {
+            id: row.get(0),
+            name: row.get(1),
+            description: row.get(2),
+            count: 0,
+        })
+    }
+
+    async fn update_reading_list(
+        &self,
+        user_id: i64,
+        id: i64,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<ReadingList, LibraryRepositoryError> {
+        sqlx::query(
+            "UPDATE reading_list SET name = ?, description = ? WHERE id = ? AND user_id = ?",
+        )
+        .bind(name)
+        .bind(description)
+        .bind(id)
+        .bind(user_id)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        self.get_reading_list(user_id, id).await
+    }
+
+    async fn delete_reading_list(
+        &self,
+        user_id: i64,
+        id: i64,
+    ) -> Result<(), LibraryRepositoryError> {
+        sqlx::query("DELETE FROM reading_list WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool as &SqlitePool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_manga_from_reading_list(
+        &self,
+        user_id: i64,
+        id: i64,
+    ) -> Result<Vec<Manga>, LibraryRepositoryError> {
+        let manga = sqlx::query(
+            r#"SELECT manga.*,
+            (SELECT json_group_array(title) FROM manga_alt_title alt WHERE alt.manga_id = manga.id)
+            FROM reading_list_manga
+            JOIN reading_list ON reading_list.id = reading_list_manga.reading_list_id
+            JOIN manga ON manga.id = reading_list_manga.manga_id
+            WHERE reading_list.id = ? AND reading_list.user_id = ?
+            ORDER BY reading_list_manga.position"#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| Manga {
+            id: row.get(0),
+            source_id: row.get(1),
+            title: row.get(2),
+            author: serde_json::from_str(row.get::<String, _>(3).as_str()).unwrap_or_default(),
+            genre: serde_json::from_str(row.get::<String, _>(4).as_str()).unwrap_or_default(),
+            status: row.get(5),
+            description: row.get(6),
+            path: row.get(7),
+            cover_url: row.get(8),
+            date_added: row.get(9),
+            last_uploaded_at: None,
+            alt_titles: serde_json::from_str(row.get::<String, _>(10).as_str()).unwrap_or_default(),
+        })
+        .collect();
+
+        Ok(manga)
+    }
+
+    async fn set_reading_list_manga(
+        &self,
+        user_id: i64,
+        id: i64,
+        manga_ids: &[i64],
+    ) -> Result<(), LibraryRepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        // fails for reading lists of other users
+        sqlx::query("SELECT id FROM reading_list WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .fetch_one(&mut tx)
+            .await?;
+
+        sqlx::query("DELETE FROM reading_list_manga WHERE reading_list_id = ?")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query(
+            r#"INSERT INTO reading_list_manga(reading_list_id, manga_id, position)
+            SELECT ?, value, key FROM json_each(?)
+            WHERE value IN (SELECT id FROM manga)
+            ON CONFLICT DO NOTHING"#,
+        )
+        .bind(id)
+        .bind(serde_json::to_string(manga_ids).unwrap_or_default())
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
         Ok(())
     }
 
+    async fn get_hidden_manga_ids(
+        &self,
+        user_id: i64,
+        unlocked_category_ids: &[i64],
+    ) -> Result<Vec<i64>, LibraryRepositoryError> {
+        // manga in a category nested in a locked one is hidden too
+        let sql = format!(
+            r#"WITH RECURSIVE {CATEGORY_ANCESTORS}
+            SELECT DISTINCT user_library.manga_id FROM user_library
+            JOIN library_category ON library_category.library_id = user_library.id
+            JOIN category_ancestor ON category_ancestor.category_id = library_category.category_id
+            JOIN user_category ON user_category.id = category_ancestor.ancestor_id
+            WHERE user_library.user_id = ?
+            AND user_category.is_private
+            AND user_category.id NOT IN (SELECT value FROM json_each(?))"#
+        );
+
+        let manga_ids = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(user_id)
+            .bind(serde_json::to_string(unlocked_category_ids).unwrap_or_default())
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_iter()
+            .map(|row| row.get(0))
+            .collect();
+
+        Ok(manga_ids)
+    }
+
+    async fn get_category_count(
+        &self,
+        user_id: i64,
+    ) -> Result<HashMap<Option<i64>, i64>, LibraryRepositoryError> {
+        // manga in a nested category are counted in each of its parents too, once per parent
+        let sql = format!(
+            r#"WITH RECURSIVE {CATEGORY_ANCESTORS}
+            SELECT NULL, COUNT(1) FROM user_library
+            WHERE user_library.user_id = ? AND NOT EXISTS (
+                SELECT 1 FROM library_category WHERE library_category.library_id = user_library.id
+            )
+            UNION ALL
+            SELECT category_ancestor.ancestor_id, COUNT(DISTINCT user_library.id) FROM user_library
+            JOIN library_category ON library_category.library_id = user_library.id
+            JOIN category_ancestor ON category_ancestor.category_id = library_category.category_id
+            WHERE user_library.user_id = ?
+            GROUP BY category_ancestor.ancestor_id"#
+        );
+
+        let mut data: HashMap<Option<i64>, i64> = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(user_id)
+            .bind(user_id)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+
+        for category in self.get_categories_by_user_id(user_id).await? {
+            if let Some(rules) = category.rules.as_ref() {
+                let count = self
+                    .get_manga_from_library_by_rules(user_id, rules, &LibraryQuery::default())
+                    .await?
+                    .len();
+                data.insert(category.id, count as i64);
+            }
+        }
+
+        Ok(data)
+    }
+
+    async fn get_category_unread_count(
+        &self,
+        user_id: i64,
+    ) -> Result<HashMap<Option<i64>, i64>, LibraryRepositoryError> {
+        let sql = format!(
+            r#"WITH RECURSIVE {CATEGORY_ANCESTORS},
+            unread(library_id, count) AS (
+                SELECT user_library.id, (
+                    SELECT COUNT(1) FROM chapter
+                    WHERE chapter.manga_id = user_library.manga_id
+                    AND NOT EXISTS (
+                        SELECT 1 FROM user_history
+                        WHERE user_history.user_id = user_library.user_id
+                        AND user_history.chapter_id = chapter.id
+                        AND user_history.is_complete
+                    )
+                )
+                FROM user_library WHERE user_library.user_id = ?
+            )
+            SELECT NULL, COALESCE(SUM(unread.count), 0) FROM unread
+            WHERE NOT EXISTS (
+                SELECT 1 FROM library_category WHERE library_category.library_id = unread.library_id
+            )
+            UNION ALL
+            SELECT category_id, SUM(count) FROM (
+                SELECT DISTINCT category_ancestor.ancestor_id AS category_id, unread.library_id, unread.count
+                FROM unread
+                JOIN library_category ON library_category.library_id = unread.library_id
+                JOIN category_ancestor ON category_ancestor.category_id = library_category.category_id
+            )
+            GROUP BY category_id"#
+        );
+
+        let mut data: HashMap<Option<i64>, i64> = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(user_id)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+
+        for category in self.get_categories_by_user_id(user_id).await? {
+            if let Some(rules) = category.rules.as_ref() {
+                let manga_ids: Vec<i64> = self
+                    .get_manga_from_library_by_rules(user_id, rules, &LibraryQuery::default())
+                    .await?
+                    .into_iter()
+                    .map(|manga| manga.id)
+                    .collect();
+
+                let row = sqlx::query(
+                    r#"SELECT COUNT(1) FROM chapter
+                    WHERE chapter.manga_id IN (SELECT value FROM json_each(?))
+                    AND NOT EXISTS (
+                        SELECT 1 FROM user_history
+                        WHERE user_history.user_id = ?
+                        AND user_history.chapter_id = chapter.id
+                        AND user_history.is_complete
+                    )"#,
+                )
+                .bind(serde_json::to_string(&manga_ids).unwrap_or_default())
+                .bind(user_id)
+                .fetch_one(&self.pool as &SqlitePool)
+                .await?;
+
+                data.insert(category.id, row.get(0));
+            }
+        }
+
+        Ok(data)
+    }
+
+    async fn get_users_by_manga_id(
+        &self,
+        manga_id: i64,
+    ) -> Result<Vec<User>, LibraryRepositoryError> {
+        let users = sqlx::query(
+            r#"SELECT user.* FROM user_library
+                    JOIN user ON user_library.user_id = user.id
+                    WHERE user_library.manga_id = ?"#,
+        )
+        .bind(manga_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .into_par_iter()
+        .map(|row| User {
+            id: row.get(0),
+            username: row.get(1),
+            password: "".to_string(),
+            is_admin: row.get(3),
+            created_at: row.get(4),
+            updated_at: row.get(5),
+            telegram_chat_id: row.get(6),
+            pushover_user_key: row.get(7),
+            gotify_token: row.get(8),
+            notification_digest_interval: row.get(9),
+            notify_chapter_category_ids: row
+                .get::<Option<String>, _>(10)
+                .and_then(|ids| serde_json::from_str(&ids).ok()),
+            notify_download_failures: row.get(11),
+            notify_announcements: row.get(12),
+            pushover_device: row.get(13),
+            pushover_sound: row.get(14),
+            pushover_chapter_priority: row.get(15),
+            pushover_alert_priority: row.get(16),
+            catalogue_sort: row.get::<String, _>(17).parse().unwrap_or_default(),
+            catalogue_language: row.get(18),
+            catalogue_hide_library: row.get(19),
+            enabled_languages: row
+                .get::<Option<String>, _>(20)
+                .and_then(|languages| serde_json::from_str(&languages).ok()),
+            hide_nsfw: row.get(21),
+            device_email: row.get(22),
+            data_saver: row.get(23),
+            chapter_languages: row
+                .get::<Option<String>, _>(24)
+                .and_then(|languages| serde_json::from_str(&languages).ok()),
+        })
+        .collect();
+
+        Ok(users)
+    }
+
+    async fn get_category_ids_by_manga_id(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Vec<i64>, LibraryRepositoryError> {
+        let category_ids = sqlx::query(
+            r#"SELECT library_category.category_id FROM user_library
+                    JOIN library_category ON user_library.id = library_category.library_id
+                    WHERE user_library.user_id = ? AND user_library.manga_id = ?"#,
+        )
+        .bind(user_id)
+        .bind(manga_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .into_iter()
+        .map(|row| row.get(0))
+        .collect();
+
+        Ok(category_ids)
+    }
+
+    async fn get_library_manga_count(&self) -> Result<i64, LibraryRepositoryError> {
+        let row = sqlx::query(r#"SELECT COUNT(DISTINCT manga_id) FROM user_library"#)
+            .fetch_one(&self.pool as &SqlitePool)
+            .await?;
+
+        Ok(row.get(0))
+    }
+
+    async fn get_manga_from_all_users_library(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = Result<Manga, LibraryRepositoryError>>>> {
+        let stream = sqlx::query(
+            r#"SELECT DISTINCT manga.*, MAX(chapter.uploaded) as last_uploaded,
+                (SELECT json_group_array(title) FROM manga_alt_title alt WHERE alt.manga_id = manga.id)
+                FROM manga
+                    JOIN user_library ON manga.id = user_library.manga_id
+                    JOIN chapter ON manga.id = chapter.manga_id
+                    GROUP by manga.id"#,
+        )
+        .fetch(&self.pool as &SqlitePool)
+        .map(|row| {
+            row.map(|row| Manga {
+                id: row.get(0),
+                source_id: row.get(1),
+                title: row.get(2),
+                author: serde_json::from_str(row.get::<String, _>(3).as_str()).unwrap_or_default(),
+                genre: serde_json::from_str(row.get::<String, _>(4).as_str()).unwrap_or_default(),
+                status: row.get(5),
+                description: row.get(6),
+                path: row.get(7),
+                cover_url: row.get(8),
+                date_added: row.get(9),
+                last_uploaded_at: row.get(10),
+                alt_titles: serde_json::from_str(row.get::<String, _>(11).as_str()).unwrap_or_default(),
+            })
+            .map_err(LibraryRepositoryError::DbError)
+        })
+        .boxed();
+
+        stream
+    }
+
+    async fn get_manga_from_library(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<Manga>, LibraryRepositoryError> {
+        let manga = sqlx::query(
+            r#"SELECT manga.*, library_category.category_id,
+            (SELECT json_group_array(title) FROM manga_alt_title alt WHERE alt.manga_id = manga.id)
+            FROM manga
+            INNER JOIN user_library ON user_library.user_id = ? AND manga.id = user_library.manga_id
+            LEFT JOIN library_category ON user_library.id = library_category.library_id
+            ORDER BY title"#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .into_par_iter()
+        .map(|row| Manga {
+            id: row.get(0),
+            source_id: row.get(1),
+            title: row.get(2),
+            author: serde_json::from_str(row.get::<String, _>(3).as_str()).unwrap_or_default(),
+            genre: serde_json::from_str(row.get::<String, _>(4).as_str()).unwrap_or_default(),
+            status: row.get(5),
+            description: row.get(6),
+            path: row.get(7),
+            cover_url: row.get(8),
+            date_added: row.get(9),
+            last_uploaded_at: None,
+            alt_titles: serde_json::from_str(row.get::<String, _>(11).as_str()).unwrap_or_default(),
+        })
+        .collect();
+
+        Ok(manga)
+    }
+
+    async fn get_manga_from_library_by_category_id(
+        &self,
+        user_id: i64,
+        category_id: Option<i64>,
+        query: &LibraryQuery,
+    ) -> Result<Vec<Manga>, LibraryRepositoryError> {
+        // a category includes the manga of every category nested in it
+        let sql = format!(
+            r#"WITH RECURSIVE category_tree(id) AS (
+                SELECT ?
+                UNION
+                SELECT user_category.id FROM user_category
+                JOIN category_tree ON user_category.parent_id = category_tree.id
+            )
+            SELECT manga.*,
+            (SELECT json_group_array(title) FROM manga_alt_title alt WHERE alt.manga_id = manga.id)
+            FROM manga
+            INNER JOIN user_library ON user_library.user_id = ? AND manga.id = user_library.manga_id
+            WHERE CASE WHEN ? IS NULL
+                THEN NOT EXISTS (
+                    SELECT 1 FROM library_category
+                    WHERE library_category.library_id = user_library.id
+                )
+                ELSE EXISTS (
+                    SELECT 1 FROM library_category
+                    WHERE library_category.library_id = user_library.id
+                    AND library_category.category_id IN (SELECT id FROM category_tree)
+                )
+            END
+            AND {QUERY_FILTERS}
+            ORDER BY {}"#,
+            order_by(query)
+        );
+
+        let sql = sqlx::query(&sql)
+            .bind(category_id)
+            .bind(user_id)
+            .bind(category_id);
+        let manga = bind_query(sql, query)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| Manga {
+                id: row.get(0),
+                source_id: row.get(1),
+                title: row.get(2),
+                author: serde_json::from_str(row.get::<String, _>(3).as_str()).unwrap_or_default(),
+                genre: serde_json::from_str(row.get::<String, _>(4).as_str()).unwrap_or_default(),
+                status: row.get(5),
+                description: row.get(6),
+                path: row.get(7),
+                cover_url: row.get(8),
+                date_added: row.get(9),
+                last_uploaded_at: None,
+                alt_titles: serde_json::from_str(row.get::<String, _>(10).as_str())
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        Ok(manga)
+    }
+
+    async fn get_manga_from_library_by_rules(
+        &self,
+        user_id: i64,
+        rules: &SmartRules,
+        query: &LibraryQuery,
+    ) -> Result<Vec<Manga>, LibraryRepositoryError> {
+        let source_ids = if rules.source_ids.is_empty() {
+            None
+        } else {
+            serde_json::to_string(&rules.source_ids).ok()
+        };
+        let genres = if rules.genres.is_empty() {
+            None
+        } else {
+            serde_json::to_string(&rules.genres).ok()
+        };
+
+        let sql = format!(
+            r#"SELECT manga.*,
+            (SELECT json_group_array(title) FROM manga_alt_title alt WHERE alt.manga_id = manga.id)
+            FROM manga
+            INNER JOIN user_library ON user_library.user_id = ? AND manga.id = user_library.manga_id
+            WHERE
+                (? IS NULL OR manga.source_id IN (SELECT value FROM json_each(?)))
+                AND (? IS NULL OR EXISTS (
+                    SELECT 1 FROM json_each(manga.genre)
+                    WHERE lower(json_each.value) IN (SELECT lower(value) FROM json_each(?))
+                ))
+                AND (? IS NULL OR lower(manga.status) = lower(?))
+                AND (NOT ? OR EXISTS (
+                    SELECT 1 FROM chapter
+                    WHERE chapter.manga_id = manga.id
+                    AND NOT EXISTS (
+                        SELECT 1 FROM user_history
+                        WHERE user_history.user_id = user_library.user_id
+                        AND user_history.chapter_id = chapter.id
+                        AND user_history.is_complete
+                    )
+                ))
+                AND (? IS NULL OR NOT EXISTS (
+                    SELECT 1 FROM user_history
+                    JOIN chapter ON chapter.id = user_history.chapter_id
+                    WHERE user_history.user_id = user_library.user_id
+                    AND chapter.manga_id = manga.id
+                    AND user_history.read_at > datetime('now', '-' || ? || ' days')
+                ))
+                AND {QUERY_FILTERS}
+            ORDER BY {}"#,
+            order_by(query)
+        );
+
+        let sql = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(&source_ids)
+            .bind(&source_ids)
+            .bind(&genres)
+            .bind(&genres)
+            .bind(&rules.status)
+            .bind(&rules.status)
+            .bind(rules.unread)
+            .bind(rules.last_read_days)
+            .bind(rules.last_read_days);
+        let manga = bind_query(sql, query)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| Manga {
+                id: row.get(0),
+                source_id: row.get(1),
+                title: row.get(2),
+                author: serde_json::from_str(row.get::<String, _>(3).as_str()).unwrap_or_default(),
+                genre: serde_json::from_str(row.get::<String, _>(4).as_str()).unwrap_or_default(),
+                status: row.get(5),
+                description: row.get(6),
+                path: row.get(7),
+                cover_url: row.get(8),
+                date_added: row.get(9),
+                last_uploaded_at: None,
+                alt_titles: serde_json::from_str(row.get::<String, _>(10).as_str())
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        Ok(manga)
+    }
+
+    async fn insert_manga_to_library(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        category_ids: &[i64],
+    ) -> Result<(), LibraryRepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        let library_id = sqlx::query(
+            "INSERT INTO user_library(user_id, manga_id, date_added) VALUES (?, ?, datetime('now'))",
+        )
+        .bind(user_id)
+        .bind(manga_id)
+        .execute(&mut tx)
+        .await
+        .map(|res| res.last_insert_rowid())?;
+
+        if !category_ids.is_empty() {
+            let query_str = format!(
+                "INSERT INTO library_category(library_id, category_id) VALUES {}",
+                vec!["(?,?)".to_string(); category_ids.len()].join(",")
+            );
+
+            let mut query = sqlx::query(&query_str);
+            for category_id in category_ids {
+                query = query.bind(library_id).bind(category_id);
+            }
+            query.execute(&mut tx).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn set_manga_categories(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        category_ids: &[i64],
+    ) -> Result<(), LibraryRepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        let library_id: i64 =
+            sqlx::query("SELECT id FROM user_library WHERE user_id = ? AND manga_id = ?")
+                .bind(user_id)
+                .bind(manga_id)
+                .fetch_one(&mut tx)
+                .await?
+                .get(0);
+
+        sqlx::query("DELETE FROM library_category WHERE library_id = ?")
+            .bind(library_id)
+            .execute(&mut tx)
+            .await?;
+
+        if !category_ids.is_empty() {
+            let query_str = format!(
+                "INSERT INTO library_category(library_id, category_id) VALUES {}",
+                vec!["(?,?)".to_string(); category_ids.len()].join(",")
+            );
+
+            let mut query = sqlx::query(&query_str);
+            for category_id in category_ids {
+                query = query.bind(library_id).bind(category_id);
+            }
+            query.execute(&mut tx).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn delete_manga_from_library(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<(), LibraryRepositoryError> {
+        sqlx::query("DELETE FROM user_library WHERE user_id = ? AND manga_id = ?")
+            .bind(user_id)
+            .bind(manga_id)
+            .execute(&self.pool as &SqlitePool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_many_manga_categories(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+        category_ids: &[i64],
+    ) -> Result<u64, LibraryRepositoryError> {
+        let manga_ids = serde_json::to_string(manga_ids).unwrap_or_default();
+        let mut tx = self.pool.begin().await?;
+
+        let entries: i64 = sqlx::query(
+            r#"SELECT COUNT(1) FROM user_library
+            WHERE user_id = ? AND manga_id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(user_id)
+        .bind(&manga_ids)
+        .fetch_one(&mut tx)
+        .await?
+        .get(0);
+
+        sqlx::query(
+            r#"DELETE FROM library_category WHERE library_id IN (
+                SELECT id FROM user_library
+                WHERE user_id = ? AND manga_id IN (SELECT value FROM json_each(?))
+            )"#,
+        )
+        .bind(user_id)
+        .bind(&manga_ids)
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"INSERT INTO library_category(library_id, category_id)
+            SELECT user_library.id, user_category.id FROM user_library
+            JOIN user_category ON user_category.user_id = user_library.user_id
+            WHERE user_library.user_id = ?
+            AND user_library.manga_id IN (SELECT value FROM json_each(?))
+            AND user_category.id IN (SELECT value FROM json_each(?))
+            AND user_category.rules IS NULL"#,
+        )
+        .bind(user_id)
+        .bind(&manga_ids)
+        .bind(serde_json::to_string(category_ids).unwrap_or_default())
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(entries as u64)
+    }
+
+    async fn delete_many_manga_from_library(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+    ) -> Result<u64, LibraryRepositoryError> {
+        let rows_affected = sqlx::query(
+            r#"DELETE FROM user_library
+            WHERE user_id = ? AND manga_id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(user_id)
+        .bind(serde_json::to_string(manga_ids).unwrap_or_default())
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn get_ignored_duplicates(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<(i64, i64)>, LibraryRepositoryError> {
+        let pairs = sqlx::query(
+            r#"SELECT manga_id, other_id FROM user_duplicate_ignore WHERE user_id = ?"#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect();
+
+        Ok(pairs)
+    }
+
+    async fn ignore_duplicates(
+        &self,
+        user_id: i64,
+        manga_ids: &[i64],
+    ) -> Result<(), LibraryRepositoryError> {
+        let manga_ids = serde_json::to_string(manga_ids).unwrap_or_default();
+
+        sqlx::query(
+            r#"INSERT OR IGNORE INTO user_duplicate_ignore(user_id, manga_id, other_id)
+            SELECT ?, a.value, b.value FROM json_each(?) a
+            JOIN json_each(?) b ON a.value < b.value
+            WHERE a.value IN (SELECT manga_id FROM user_library WHERE user_id = ?)
+            AND b.value IN (SELECT manga_id FROM user_library WHERE user_id = ?)"#,
+        )
+        .bind(user_id)
+        .bind(&manga_ids)
+        .bind(&manga_ids)
+        .bind(user_id)
+        .bind(user_id)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn merge_library_manga(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        duplicate_ids: &[i64],
+    ) -> Result<u64, LibraryRepositoryError> {
+        let duplicate_ids = serde_json::to_string(duplicate_ids).unwrap_or_default();
+
+        let mut tx = self.pool.begin().await?;
+
+        let in_library: i64 = sqlx::query(
+            r#"SELECT COUNT(1) FROM user_library
+            WHERE user_id = ? AND manga_id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(user_id)
+        .bind(&duplicate_ids)
+        .fetch_one(&mut tx)
+        .await?
+        .get(0);
+        if in_library == 0 {
+            return Err(LibraryRepositoryError::NotInLibrary);
+        }
+
+        // the merged entry keeps when the first of the duplicates was added
+        sqlx::query(
+            r#"INSERT OR IGNORE INTO user_library(user_id, manga_id, date_added)
+            SELECT ?, ?, MIN(date_added) FROM user_library
+            WHERE user_id = ? AND manga_id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(user_id)
+        .bind(manga_id)
+        .bind(user_id)
+        .bind(&duplicate_ids)
+        .execute(&mut tx)
+        .await?;
+        let library_id: i64 =
+            sqlx::query(r#"SELECT id FROM user_library WHERE user_id = ? AND manga_id = ?"#)
+                .bind(user_id)
+                .bind(manga_id)
+                .fetch_one(&mut tx)
+                .await?
+                .get(0);
+
+        sqlx::query(
+            r#"INSERT OR IGNORE INTO library_category(library_id, category_id)
+            SELECT ?, library_category.category_id FROM library_category
+            JOIN user_library ON user_library.id = library_category.library_id
+            WHERE user_library.user_id = ?
+            AND user_library.manga_id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(library_id)
+        .bind(user_id)
+        .bind(&duplicate_ids)
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"INSERT OR IGNORE INTO library_tag(library_id, tag_id)
+            SELECT ?, library_tag.tag_id FROM library_tag
+            JOIN user_library ON user_library.id = library_tag.library_id
+            WHERE user_library.user_id = ?
+            AND user_library.manga_id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(library_id)
+        .bind(user_id)
+        .bind(&duplicate_ids)
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"INSERT OR IGNORE INTO tracker_manga(user_id, manga_id, tracker, tracker_manga_id)
+            SELECT user_id, ?, tracker, tracker_manga_id FROM tracker_manga
+            WHERE user_id = ? AND manga_id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(manga_id)
+        .bind(user_id)
+        .bind(&duplicate_ids)
+        .execute(&mut tx)
+        .await?;
+
+        // chapters of different sources are only matched by their number
+        sqlx::query(
+            r#"INSERT INTO user_history(user_id, chapter_id, last_page, read_at, is_complete)
+            SELECT user_history.user_id, kept.id, user_history.last_page,
+                user_history.read_at, user_history.is_complete
+            FROM user_history
+            JOIN chapter ON chapter.id = user_history.chapter_id
+            JOIN chapter kept ON kept.manga_id = ? AND kept.number = chapter.number
+            WHERE user_history.user_id = ?
+            AND chapter.manga_id IN (SELECT value FROM json_each(?))
+            ON CONFLICT(user_id, chapter_id)
+            DO UPDATE SET
+                last_page = CASE WHEN excluded.read_at > read_at THEN excluded.last_page ELSE last_page END,
+                read_at = MAX(read_at, excluded.read_at),
+                is_complete = is_complete OR excluded.is_complete"#,
+        )
+        .bind(manga_id)
+        .bind(user_id)
+        .bind(&duplicate_ids)
+        .execute(&mut tx)
+        .await?;
+
+        // the kept manga takes the place of a duplicate in reading lists it isn't in yet
+        sqlx::query(
+            r#"UPDATE OR IGNORE reading_list_manga SET manga_id = ?
+            WHERE manga_id IN (SELECT value FROM json_each(?))
+            AND reading_list_id IN (SELECT id FROM reading_list WHERE user_id = ?)"#,
+        )
+        .bind(manga_id)
+        .bind(&duplicate_ids)
+        .bind(user_id)
+        .execute(&mut tx)
+        .await?;
+        sqlx::query(
+            r#"DELETE FROM reading_list_manga
+            WHERE manga_id IN (SELECT value FROM json_each(?))
+            AND reading_list_id IN (SELECT id FROM reading_list WHERE user_id = ?)"#,
+        )
+        .bind(&duplicate_ids)
+        .bind(user_id)
+        .execute(&mut tx)
+        .await?;
+
+        let rows_affected = sqlx::query(
+            r#"DELETE FROM user_library
+            WHERE user_id = ? AND manga_id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(user_id)
+        .bind(&duplicate_ids)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+
+        Ok(rows_affected)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn get_first_library_updates(
         &self,
         user_id: i64,
+        unlocked_category_ids: &[i64],
         after_timestamp: i64,
         after_id: i64,
         before_timestamp: i64,
         before_id: i64,
         first: i32,
     ) -> Result<Vec<LibraryUpdate>, LibraryRepositoryError> {
-        let chapters = sqlx::query(
-            r#"
+        let sql = format!(
+            r#"WITH RECURSIVE {CATEGORY_ANCESTORS}
         SELECT
             manga.id,
             chapter.id,
@@ -336,45 +1519,52 @@ impl LibraryRepository for LibraryRepositoryImpl {
         JOIN user_library ON
             user_library.manga_id = manga.id
             AND user_library.user_id = ?
+            AND {HIDDEN_LIBRARY_FILTER}
         WHERE
             (uploaded, chapter.id) < (datetime(?, 'unixepoch'), ?) AND
             (uploaded, chapter.id) > (datetime(?, 'unixepoch'), ?)
-        ORDER BY chapter.uploaded DESC, chapter.number DESC
-        LIMIT ?"#,
-        )
-        .bind(user_id)
-        .bind(after_timestamp)
-        .bind(after_id)
-        .bind(before_timestamp)
-        .bind(before_id)
-        .bind(first)
-        .fetch_all(&self.pool as &SqlitePool)
-        .await?
-        .into_par_iter()
-        .map(|row| LibraryUpdate {
-            manga_id: row.get(0),
-            chapter_id: row.get(1),
-            manga_title: row.get(2),
-            cover_url: row.get(3),
-            chapter_title: row.get(4),
-            uploaded: row.get(5),
-        })
-        .collect();
+        ORDER BY chapter.uploaded DESC, chapter.sort_key DESC
+        LIMIT ?"#
+        );
+
+        let chapters = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(user_id)
+            .bind(serde_json::to_string(unlocked_category_ids).unwrap_or_default())
+            .bind(after_timestamp)
+            .bind(after_id)
+            .bind(before_timestamp)
+            .bind(before_id)
+            .bind(first)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| LibraryUpdate {
+                manga_id: row.get(0),
+                chapter_id: row.get(1),
+                manga_title: row.get(2),
+                cover_url: row.get(3),
+                chapter_title: row.get(4),
+                uploaded: row.get(5),
+            })
+            .collect();
 
         Ok(chapters)
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn get_last_library_updates(
         &self,
         user_id: i64,
+        unlocked_category_ids: &[i64],
         after_timestamp: i64,
         after_id: i64,
         before_timestamp: i64,
         before_id: i64,
         last: i32,
     ) -> Result<Vec<LibraryUpdate>, LibraryRepositoryError> {
-        let chapters = sqlx::query(
-            r#"
+        let sql = format!(
+            r#"WITH RECURSIVE {CATEGORY_ANCESTORS}
         SELECT * FROM (
             SELECT
                 manga.id,
@@ -383,37 +1573,42 @@ impl LibraryRepository for LibraryRepositoryImpl {
                 manga.cover_url,
                 chapter.title,
                 chapter.uploaded,
-                chapter.number
+                chapter.sort_key
             FROM chapter
             JOIN manga ON manga.id = chapter.manga_id
             JOIN user_library ON
                 user_library.manga_id = manga.id
                 AND user_library.user_id = ?
+                AND {HIDDEN_LIBRARY_FILTER}
             WHERE
                 (uploaded, chapter.id) < (datetime(?, 'unixepoch'), ?) AND
                 (uploaded, chapter.id) > (datetime(?, 'unixepoch'), ?)
-            ORDER BY chapter.uploaded ASC, chapter.number DESC
+            ORDER BY chapter.uploaded ASC, chapter.sort_key DESC
             LIMIT ?) c
-        ORDER BY c.uploaded DESC, c.number DESC"#,
-        )
-        .bind(user_id)
-        .bind(after_timestamp)
-        .bind(after_id)
-        .bind(before_timestamp)
-        .bind(before_id)
-        .bind(last)
-        .fetch_all(&self.pool as &SqlitePool)
-        .await?
-        .into_par_iter()
-        .map(|row| LibraryUpdate {
-            manga_id: row.get(0),
-            chapter_id: row.get(1),
-            manga_title: row.get(2),
-            cover_url: row.get(3),
-            chapter_title: row.get(4),
-            uploaded: row.get(5),
-        })
-        .collect();
+        ORDER BY c.uploaded DESC, c.sort_key DESC"#
+        );
+
+        let chapters = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(user_id)
+            .bind(serde_json::to_string(unlocked_category_ids).unwrap_or_default())
+            .bind(after_timestamp)
+            .bind(after_id)
+            .bind(before_timestamp)
+            .bind(before_id)
+            .bind(last)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| LibraryUpdate {
+                manga_id: row.get(0),
+                chapter_id: row.get(1),
+                manga_title: row.get(2),
+                cover_url: row.get(3),
+                chapter_title: row.get(4),
+                uploaded: row.get(5),
+            })
+            .collect();
 
         Ok(chapters)
     }
@@ -421,13 +1616,14 @@ impl LibraryRepository for LibraryRepositoryImpl {
     async fn get_library_updates(
         &self,
         user_id: i64,
+        unlocked_category_ids: &[i64],
         after_timestamp: i64,
         after_id: i64,
         before_timestamp: i64,
         before_id: i64,
     ) -> Result<Vec<LibraryUpdate>, LibraryRepositoryError> {
-        let chapters = sqlx::query(
-            r#"
+        let sql = format!(
+            r#"WITH RECURSIVE {CATEGORY_ANCESTORS}
         SELECT
             manga.id,
             chapter.id,
@@ -440,29 +1636,413 @@ impl LibraryRepository for LibraryRepositoryImpl {
         JOIN user_library ON
             user_library.manga_id = manga.id
             AND user_library.user_id = ?
+            AND {HIDDEN_LIBRARY_FILTER}
         WHERE
             (uploaded, chapter.id) < (datetime(?, 'unixepoch'), ?) AND
             (uploaded, chapter.id) > (datetime(?, 'unixepoch'), ?)
-        ORDER BY chapter.uploaded DESC, chapter.number DESC"#,
+        ORDER BY chapter.uploaded DESC, chapter.sort_key DESC"#
+        );
+
+        let chapters = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(user_id)
+            .bind(serde_json::to_string(unlocked_category_ids).unwrap_or_default())
+            .bind(after_timestamp)
+            .bind(after_id)
+            .bind(before_timestamp)
+            .bind(before_id)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| LibraryUpdate {
+                manga_id: row.get(0),
+                chapter_id: row.get(1),
+                manga_title: row.get(2),
+                cover_url: row.get(3),
+                chapter_title: row.get(4),
+                uploaded: row.get(5),
+            })
+            .collect();
+
+        Ok(chapters)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn get_library_additions(
+        &self,
+        user_id: i64,
+        unlocked_category_ids: &[i64],
+        after_timestamp: i64,
+        after_id: i64,
+        before_timestamp: i64,
+        before_id: i64,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Vec<LibraryAddition>, LibraryRepositoryError> {
+        let (order, limit) = addition_order(first, last);
+        let query_str = format!(
+            r#"WITH RECURSIVE {CATEGORY_ANCESTORS}
+        SELECT
+            manga.id,
+            manga.title,
+            manga.cover_url,
+            user_library.date_added
+        FROM user_library
+        JOIN manga ON manga.id = user_library.manga_id
+        WHERE
+            user_library.user_id = ?
+            AND {HIDDEN_LIBRARY_FILTER}
+            AND (user_library.date_added, manga.id) < (datetime(?, 'unixepoch'), ?)
+            AND (user_library.date_added, manga.id) > (datetime(?, 'unixepoch'), ?)
+        ORDER BY user_library.date_added {order}, manga.id {order}
+        {limit}"#
+        );
+
+        let mut additions: Vec<LibraryAddition> = sqlx::query(&query_str)
+            .bind(user_id)
+            .bind(user_id)
+            .bind(serde_json::to_string(unlocked_category_ids).unwrap_or_default())
+            .bind(after_timestamp)
+            .bind(after_id)
+            .bind(before_timestamp)
+            .bind(before_id)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| LibraryAddition {
+                manga_id: row.get(0),
+                manga_title: row.get(1),
+                cover_url: row.get(2),
+                date_added: row.get(3),
+            })
+            .collect();
+        if order == "ASC" {
+            additions.reverse();
+        }
+
+        Ok(additions)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn get_chapter_additions(
+        &self,
+        user_id: i64,
+        unlocked_category_ids: &[i64],
+        after_timestamp: i64,
+        after_id: i64,
+        before_timestamp: i64,
+        before_id: i64,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Vec<ChapterAddition>, LibraryRepositoryError> {
+        let (order, limit) = addition_order(first, last);
+        // chapter.date_added is stored with fractions of a second, cursors are whole seconds
+        let query_str = format!(
+            r#"WITH RECURSIVE {CATEGORY_ANCESTORS}
+        SELECT
+            manga.id,
+            chapter.id,
+            manga.title,
+            manga.cover_url,
+            chapter.title,
+            chapter.uploaded,
+            chapter.date_added
+        FROM chapter
+        JOIN manga ON manga.id = chapter.manga_id
+        JOIN user_library ON
+            user_library.manga_id = manga.id
+            AND user_library.user_id = ?
+            AND {HIDDEN_LIBRARY_FILTER}
+        WHERE
+            datetime(chapter.date_added) > user_library.date_added
+            AND (datetime(chapter.date_added), chapter.id) < (datetime(?, 'unixepoch'), ?)
+            AND (datetime(chapter.date_added), chapter.id) > (datetime(?, 'unixepoch'), ?)
+        ORDER BY datetime(chapter.date_added) {order}, chapter.id {order}
+        {limit}"#
+        );
+
+        let mut additions: Vec<ChapterAddition> = sqlx::query(&query_str)
+            .bind(user_id)
+            .bind(user_id)
+            .bind(serde_json::to_string(unlocked_category_ids).unwrap_or_default())
+            .bind(after_timestamp)
+            .bind(after_id)
+            .bind(before_timestamp)
+            .bind(before_id)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| ChapterAddition {
+                manga_id: row.get(0),
+                chapter_id: row.get(1),
+                manga_title: row.get(2),
+                cover_url: row.get(3),
+                chapter_title: row.get(4),
+                uploaded: row.get(5),
+                date_added: row.get(6),
+            })
+            .collect();
+        if order == "ASC" {
+            additions.reverse();
+        }
+
+        Ok(additions)
+    }
+
+    async fn get_download_ahead(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<i64, LibraryRepositoryError> {
+        let count = sqlx::query(
+            r#"SELECT download_ahead FROM user_library WHERE user_id = ? AND manga_id = ?"#,
+        )
+        .bind(user_id)
+        .bind(manga_id)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?
+        .map(|row| row.get(0))
+        .unwrap_or(0);
+
+        Ok(count)
+    }
+
+    async fn set_download_ahead(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        count: i64,
+    ) -> Result<(), LibraryRepositoryError> {
+        let rows_affected = sqlx::query(
+            r#"UPDATE user_library SET download_ahead = ? WHERE user_id = ? AND manga_id = ?"#,
+        )
+        .bind(count)
+        .bind(user_id)
+        .bind(manga_id)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
+
+        if rows_affected == 0 {
+            return Err(LibraryRepositoryError::NotInLibrary);
+        }
+
+        Ok(())
+    }
+
+    async fn get_preferred_group(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Option<String>, LibraryRepositoryError> {
+        let group = sqlx::query(
+            r#"SELECT preferred_group FROM user_library WHERE user_id = ? AND manga_id = ?"#,
+        )
+        .bind(user_id)
+        .bind(manga_id)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?
+        .and_then(|row| row.get(0));
+
+        Ok(group)
+    }
+
+    async fn set_preferred_group(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        group: Option<&str>,
+    ) -> Result<(), LibraryRepositoryError> {
+        let rows_affected = sqlx::query(
+            r#"UPDATE user_library SET preferred_group = ? WHERE user_id = ? AND manga_id = ?"#,
+        )
+        .bind(group)
+        .bind(user_id)
+        .bind(manga_id)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
+
+        if rows_affected == 0 {
+            return Err(LibraryRepositoryError::NotInLibrary);
+        }
+
+        Ok(())
+    }
+
+    async fn get_chapter_languages(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<Option<Vec<String>>, LibraryRepositoryError> {
+        let languages = sqlx::query(
+            r#"SELECT chapter_languages FROM user_library WHERE user_id = ? AND manga_id = ?"#,
+        )
+        .bind(user_id)
+        .bind(manga_id)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?
+        .and_then(|row| row.get::<Option<String>, _>(0))
+        .and_then(|languages| serde_json::from_str(&languages).ok());
+
+        Ok(languages)
+    }
+
+    async fn set_chapter_languages(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        languages: Option<&[String]>,
+    ) -> Result<(), LibraryRepositoryError> {
+        let rows_affected = sqlx::query(
+            r#"UPDATE user_library SET chapter_languages = ? WHERE user_id = ? AND manga_id = ?"#,
         )
+        .bind(languages.and_then(|languages| serde_json::to_string(languages).ok()))
         .bind(user_id)
-        .bind(after_timestamp)
-        .bind(after_id)
-        .bind(before_timestamp)
-        .bind(before_id)
+        .bind(manga_id)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
+
+        if rows_affected == 0 {
+            return Err(LibraryRepositoryError::NotInLibrary);
+        }
+
+        Ok(())
+    }
+
+    async fn get_preferred_groups_by_manga_id(
+        &self,
+        manga_id: i64,
+    ) -> Result<Vec<String>, LibraryRepositoryError> {
+        let groups = sqlx::query(
+            r#"SELECT DISTINCT preferred_group FROM user_library
+            WHERE manga_id = ? AND preferred_group IS NOT NULL"#,
+        )
+        .bind(manga_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .into_iter()
+        .map(|row| row.get(0))
+        .collect();
+
+        Ok(groups)
+    }
+
+    async fn get_download_ahead_entries(
+        &self,
+    ) -> Result<Vec<DownloadAhead>, LibraryRepositoryError> {
+        let entries = sqlx::query(
+            r#"SELECT user_id, manga_id, download_ahead, preferred_group FROM user_library
+                WHERE download_ahead > 0"#,
+        )
         .fetch_all(&self.pool as &SqlitePool)
         .await?
         .into_par_iter()
-        .map(|row| LibraryUpdate {
-            manga_id: row.get(0),
-            chapter_id: row.get(1),
-            manga_title: row.get(2),
-            cover_url: row.get(3),
-            chapter_title: row.get(4),
-            uploaded: row.get(5),
+        .map(|row| DownloadAhead {
+            user_id: row.get(0),
+            manga_id: row.get(1),
+            count: row.get(2),
+            preferred_group: row.get(3),
         })
         .collect();
 
-        Ok(chapters)
+        Ok(entries)
+    }
+
+    async fn get_auto_download(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<AutoDownload, LibraryRepositoryError> {
+        let auto_download = sqlx::query(
+            r#"SELECT auto_download, auto_download_limit FROM user_library
+            WHERE user_id = ? AND manga_id = ?"#,
+        )
+        .bind(user_id)
+        .bind(manga_id)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?
+        .map(|row| AutoDownload {
+            enabled: row.get(0),
+            limit: row.get(1),
+        })
+        .unwrap_or_default();
+
+        Ok(auto_download)
+    }
+
+    async fn set_auto_download(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        auto_download: AutoDownload,
+    ) -> Result<(), LibraryRepositoryError> {
+        let rows_affected = sqlx::query(
+            r#"UPDATE user_library SET auto_download = ?, auto_download_limit = ?
+            WHERE user_id = ? AND manga_id = ?"#,
+        )
+        .bind(auto_download.enabled)
+        .bind(auto_download.limit)
+        .bind(user_id)
+        .bind(manga_id)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
+
+        if rows_affected == 0 {
+            return Err(LibraryRepositoryError::NotInLibrary);
+        }
+
+        Ok(())
+    }
+
+    async fn set_category_auto_download(
+        &self,
+        user_id: i64,
+        category_id: i64,
+        auto_download: AutoDownload,
+    ) -> Result<Category, LibraryRepositoryError> {
+        let row = sqlx::query(
+            r#"UPDATE user_category SET auto_download = ?, auto_download_limit = ?
+            WHERE id = ? AND user_id = ?
+            RETURNING id, name, is_private, pin, auto_download, auto_download_limit, rules, parent_id, position, is_default"#,
+        )
+        .bind(auto_download.enabled)
+        .bind(auto_download.limit)
+        .bind(category_id)
+        .bind(user_id)
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(category_from_row(&row))
+    }
+
+    async fn get_auto_download_settings_by_manga_id(
+        &self,
+        manga_id: i64,
+    ) -> Result<Vec<AutoDownload>, LibraryRepositoryError> {
+        let settings = sqlx::query(
+            r#"SELECT auto_download, auto_download_limit FROM user_library
+            WHERE manga_id = ?
+            UNION ALL
+            SELECT user_category.auto_download, user_category.auto_download_limit
+            FROM user_library
+            JOIN library_category ON library_category.library_id = user_library.id
+            JOIN user_category ON user_category.id = library_category.category_id
+            WHERE user_library.manga_id = ?"#,
+        )
+        .bind(manga_id)
+        .bind(manga_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| AutoDownload {
+            enabled: row.get(0),
+            limit: row.get(1),
+        })
+        .collect();
+
+        Ok(settings)
     }
 }
@@ -1,6 +1,8 @@
+use std::collections::HashMap;
+
 use crate::{
     domain::{
-        entities::manga::Manga,
+        entities::manga::{Manga, MangaOverride},
         repositories::manga::{MangaRepository, MangaRepositoryError},
     },
     infrastructure::database::Pool,
@@ -23,10 +25,14 @@ impl MangaRepositoryImpl {
 #[async_trait]
 impl MangaRepository for MangaRepositoryImpl {
     async fn get_manga_by_id(&self, id: i64) -> Result<Manga, MangaRepositoryError> {
-        let row = sqlx::query(r#"SELECT * FROM manga WHERE id = ?"#)
-            .bind(id)
-            .fetch_one(&self.pool as &SqlitePool)
-            .await?;
+        let row = sqlx::query(
+            r#"SELECT manga.*,
+            (SELECT json_group_array(title) FROM manga_alt_title alt WHERE alt.manga_id = manga.id)
+            FROM manga WHERE id = ?"#,
+        )
+        .bind(id)
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
 
         Ok(Manga {
             id: row.get(0),
@@ -40,12 +46,15 @@ impl MangaRepository for MangaRepositoryImpl {
             cover_url: row.get(8),
             date_added: row.get(9),
             last_uploaded_at: None,
+            alt_titles: serde_json::from_str(row.get::<String, _>(10).as_str()).unwrap_or_default(),
         })
     }
 
     async fn get_manga_by_ids(&self, ids: &[i64]) -> Result<Vec<Manga>, MangaRepositoryError> {
         let query_str = format!(
-            r#"SELECT * FROM manga WHERE id IN ({})"#,
+            r#"SELECT manga.*,
+            (SELECT json_group_array(title) FROM manga_alt_title alt WHERE alt.manga_id = manga.id)
+            FROM manga WHERE id IN ({})"#,
             vec!["?"; ids.len()].join(",")
         );
         let mut query = sqlx::query(&query_str);
@@ -68,6 +77,8 @@ impl MangaRepository for MangaRepositoryImpl {
                 cover_url: row.get(8),
                 date_added: row.get(9),
                 last_uploaded_at: None,
+                alt_titles: serde_json::from_str(row.get::<String, _>(10).as_str())
+                    .unwrap_or_default(),
             })
             .collect();
 
@@ -79,11 +90,15 @@ impl MangaRepository for MangaRepositoryImpl {
         source_id: i64,
         path: &str,
     ) -> Result<Manga, MangaRepositoryError> {
-        let row = sqlx::query(r#"SELECT * FROM manga WHERE source_id = ? AND path = ?"#)
-            .bind(source_id)
-            .bind(path)
-            .fetch_one(&self.pool as &SqlitePool)
-            .await?;
+        let row = sqlx::query(
+            r#"SELECT manga.*,
+            (SELECT json_group_array(title) FROM manga_alt_title alt WHERE alt.manga_id = manga.id)
+            FROM manga WHERE source_id = ? AND path = ?"#,
+        )
+        .bind(source_id)
+        .bind(path)
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
 
         Ok(Manga {
             id: row.get(0),
@@ -97,6 +112,7 @@ impl MangaRepository for MangaRepositoryImpl {
             cover_url: row.get(8),
             date_added: row.get(9),
             last_uploaded_at: None,
+            alt_titles: serde_json::from_str(row.get::<String, _>(10).as_str()).unwrap_or_default(),
         })
     }
 
@@ -142,6 +158,298 @@ impl MangaRepository for MangaRepositoryImpl {
             manga.id = row_id;
         }
 
+        // manga listed in a search often come without them, keep the ones of the details
+        if !manga.alt_titles.is_empty() {
+            let mut tx = self.pool.begin().await?;
+
+            sqlx::query(
+                r#"DELETE FROM manga_alt_title
+                WHERE manga_id = (SELECT id FROM manga WHERE source_id = ? AND path = ?)
+                AND NOT from_user"#,
+            )
+            .bind(manga.source_id)
+            .bind(&manga.path)
+            .execute(&mut tx)
+            .await?;
+
+            sqlx::query(
+                r#"INSERT OR IGNORE INTO manga_alt_title(manga_id, title, from_user)
+                SELECT manga.id, json_each.value, false FROM manga, json_each(?)
+                WHERE manga.source_id = ? AND manga.path = ?"#,
+            )
+            .bind(serde_json::to_string(&manga.alt_titles).unwrap_or_default())
+            .bind(manga.source_id)
+            .bind(&manga.path)
+            .execute(&mut tx)
+            .await?;
+
+            tx.commit().await?;
+        }
+
+        Ok(())
+    }
+    async fn get_linked_manga(&self, manga_id: i64) -> Result<Vec<Manga>, MangaRepositoryError> {
+        let manga = sqlx::query(
+            r#"SELECT manga.*,
+            (SELECT json_group_array(title) FROM manga_alt_title alt WHERE alt.manga_id = manga.id)
+            FROM manga_link
+            JOIN manga ON manga.id = manga_link.linked_manga_id
+            WHERE manga_link.manga_id = ?
+            ORDER BY manga_link.priority, manga.id"#,
+        )
+        .bind(manga_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| Manga {
+            id: row.get(0),
+            source_id: row.get(1),
+            title: row.get(2),
+            author: serde_json::from_str(row.get::<String, _>(3).as_str()).unwrap_or_default(),
+            genre: serde_json::from_str(row.get::<String, _>(4).as_str()).unwrap_or_default(),
+            status: row.get(5),
+            description: row.get(6),
+            path: row.get(7),
+            cover_url: row.get(8),
+            date_added: row.get(9),
+            last_uploaded_at: None,
+            alt_titles: serde_json::from_str(row.get::<String, _>(10).as_str()).unwrap_or_default(),
+        })
+        .collect();
+
+        Ok(manga)
+    }
+
+    async fn get_series_manga(&self, manga_id: i64) -> Result<Vec<Manga>, MangaRepositoryError> {
+        // every link to a manga has the same priority, ties keep `manga_id` first
+        let manga = sqlx::query(
+            r#"SELECT manga.*,
+            (SELECT json_group_array(title) FROM manga_alt_title alt WHERE alt.manga_id = manga.id)
+            FROM manga
+            WHERE manga.id = ?
+            OR manga.id IN (SELECT linked_manga_id FROM manga_link WHERE manga_id = ?)
+            ORDER BY
+                COALESCE((SELECT MIN(priority) FROM manga_link WHERE linked_manga_id = manga.id), 0),
+                manga.id <> ?,
+                manga.id"#,
+        )
+        .bind(manga_id)
+        .bind(manga_id)
+        .bind(manga_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| Manga {
+            id: row.get(0),
+            source_id: row.get(1),
+            title: row.get(2),
+            author: serde_json::from_str(row.get::<String, _>(3).as_str()).unwrap_or_default(),
+            genre: serde_json::from_str(row.get::<String, _>(4).as_str()).unwrap_or_default(),
+            status: row.get(5),
+            description: row.get(6),
+            path: row.get(7),
+            cover_url: row.get(8),
+            date_added: row.get(9),
+            last_uploaded_at: None,
+            alt_titles: serde_json::from_str(row.get::<String, _>(10).as_str()).unwrap_or_default(),
+        })
+        .collect();
+
+        Ok(manga)
+    }
+
+    async fn insert_manga_link(
+        &self,
+        manga_id: i64,
+        linked_manga_id: i64,
+    ) -> Result<(), MangaRepositoryError> {
+        sqlx::query(
+            r#"WITH series(id) AS (
+                SELECT ? UNION SELECT ?
+                UNION SELECT linked_manga_id FROM manga_link WHERE manga_id IN (?, ?)
+            )
+            INSERT INTO manga_link(manga_id, linked_manga_id, priority)
+            SELECT a.id, b.id,
+                COALESCE((SELECT MIN(priority) FROM manga_link WHERE linked_manga_id = b.id), 0)
+            FROM series a JOIN series b ON a.id <> b.id
+            WHERE true
+            ON CONFLICT DO NOTHING"#,
+        )
+        .bind(manga_id)
+        .bind(linked_manga_id)
+        .bind(manga_id)
+        .bind(linked_manga_id)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_manga_link(
+        &self,
+        manga_id: i64,
+        linked_manga_id: i64,
+    ) -> Result<(), MangaRepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        let linked =
+            sqlx::query(r#"SELECT 1 FROM manga_link WHERE manga_id = ? AND linked_manga_id = ?"#)
+                .bind(manga_id)
+                .bind(linked_manga_id)
+                .fetch_optional(&mut tx)
+                .await?
+                .is_some();
+
+        if linked {
+            sqlx::query(r#"DELETE FROM manga_link WHERE manga_id = ? OR linked_manga_id = ?"#)
+                .bind(linked_manga_id)
+                .bind(linked_manga_id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn set_manga_link_priority(&self, manga_ids: &[i64]) -> Result<(), MangaRepositoryError> {
+        let manga_ids = serde_json::to_string(manga_ids).unwrap_or_default();
+
+        sqlx::query(
+            r#"UPDATE manga_link
+            SET priority = (SELECT key FROM json_each(?) WHERE value = manga_link.linked_manga_id)
+            WHERE linked_manga_id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(&manga_ids)
+        .bind(&manga_ids)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_manga_overrides(
+        &self,
+        manga_ids: &[i64],
+    ) -> Result<HashMap<i64, MangaOverride>, MangaRepositoryError> {
+        let overrides = sqlx::query(
+            r#"SELECT manga_id, title, author, genre, status, description FROM manga_override
+            WHERE manga_id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(serde_json::to_string(manga_ids).unwrap_or_default())
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| {
+            (
+                row.get(0),
+                MangaOverride {
+                    title: row.get(1),
+                    author: row
+                        .get::<Option<String>, _>(2)
+                        .and_then(|author| serde_json::from_str(&author).ok()),
+                    genre: row
+                        .get::<Option<String>, _>(3)
+                        .and_then(|genre| serde_json::from_str(&genre).ok()),
+                    status: row.get(4),
+                    description: row.get(5),
+                },
+            )
+        })
+        .collect();
+
+        Ok(overrides)
+    }
+
+    async fn set_manga_override(
+        &self,
+        manga_id: i64,
+        manga_override: &MangaOverride,
+    ) -> Result<(), MangaRepositoryError> {
+        if manga_override.is_empty() {
+            sqlx::query(r#"DELETE FROM manga_override WHERE manga_id = ?"#)
+                .bind(manga_id)
+                .execute(&self.pool as &SqlitePool)
+                .await?;
+
+            return Ok(());
+        }
+
+        sqlx::query(
+            r#"INSERT INTO manga_override(manga_id, title, author, genre, status, description)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(manga_id) DO UPDATE SET
+                title = excluded.title,
+                author = excluded.author,
+                genre = excluded.genre,
+                status = excluded.status,
+                description = excluded.description"#,
+        )
+        .bind(manga_id)
+        .bind(&manga_override.title)
+        .bind(
+            manga_override
+                .author
+                .as_ref()
+                .and_then(|author| serde_json::to_string(author).ok()),
+        )
+        .bind(
+            manga_override
+                .genre
+                .as_ref()
+                .and_then(|genre| serde_json::to_string(genre).ok()),
+        )
+        .bind(&manga_override.status)
+        .bind(&manga_override.description)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_user_alt_titles(
+        &self,
+        manga_id: i64,
+    ) -> Result<Vec<String>, MangaRepositoryError> {
+        let titles = sqlx::query(
+            r#"SELECT title FROM manga_alt_title WHERE manga_id = ? AND from_user ORDER BY title"#,
+        )
+        .bind(manga_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+        Ok(titles)
+    }
+
+    async fn set_user_alt_titles(
+        &self,
+        manga_id: i64,
+        titles: &[String],
+    ) -> Result<(), MangaRepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(r#"DELETE FROM manga_alt_title WHERE manga_id = ? AND from_user"#)
+            .bind(manga_id)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query(
+            r#"INSERT INTO manga_alt_title(manga_id, title, from_user)
+            SELECT ?, value, true FROM json_each(?)
+            WHERE true
+            ON CONFLICT(manga_id, title) DO UPDATE SET from_user = true"#,
+        )
+        .bind(manga_id)
+        .bind(serde_json::to_string(titles).unwrap_or_default())
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
         Ok(())
     }
 }
@@ -1,10 +1,13 @@
+pub mod automation;
 pub mod chapter;
 pub mod download;
 pub mod history;
 pub mod image;
 pub mod image_cache;
+pub mod inbox;
 pub mod library;
 pub mod manga;
+pub mod setting;
 pub mod source;
 pub mod tracker;
 pub mod user;
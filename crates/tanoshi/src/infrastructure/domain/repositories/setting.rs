@@ -0,0 +1,48 @@
+use async_trait::async_trait;
+use sqlx::{Row, SqlitePool};
+
+use crate::{
+    domain::repositories::setting::{SettingRepository, SettingRepositoryError},
+    infrastructure::database::Pool,
+};
+
+#[derive(Clone)]
+pub struct SettingRepositoryImpl {
+    pool: Pool,
+}
+
+impl SettingRepositoryImpl {
+    pub fn new<P: Into<Pool>>(pool: P) -> Self {
+        Self { pool: pool.into() }
+    }
+}
+
+#[async_trait]
+impl SettingRepository for SettingRepositoryImpl {
+    async fn get_setting(&self, key: &str) -> Result<Option<String>, SettingRepositoryError> {
+        let value = sqlx::query(r#"SELECT value FROM setting WHERE key = ?"#)
+            .bind(key)
+            .fetch_optional(&self.pool as &SqlitePool)
+            .await?
+            .and_then(|row| row.get(0));
+
+        Ok(value)
+    }
+
+    async fn set_setting(
+        &self,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<(), SettingRepositoryError> {
+        sqlx::query(
+            r#"INSERT INTO setting(key, value) VALUES (?, ?)
+            ON CONFLICT(key) DO UPDATE SET value = excluded.value"#,
+        )
+        .bind(key)
+        .bind(value)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+}
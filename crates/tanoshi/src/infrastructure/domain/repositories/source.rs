@@ -1,13 +1,30 @@
-use std::str::FromStr;
+use std::{collections::HashMap, future::Future, str::FromStr};
 
 use async_trait::async_trait;
 use serde::Deserialize;
-use tanoshi_lib::prelude::Version;
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+use tanoshi_lib::prelude::{Input, MangaInfo, Version};
 use tanoshi_vm::prelude::ExtensionManager;
 
-use crate::domain::{
-    entities::source::Source,
-    repositories::source::{SourceRepository, SourceRepositoryError},
+use crate::{
+    domain::{
+        entities::{
+            manga::Manga,
+            source::{
+                language_codes, DomainHeaderOverride, HeaderOverride, Source, SourceCredential,
+                SourceHealth, SourceLog, SourceLogLevel,
+            },
+        },
+        repositories::source::{SourceRepository, SourceRepositoryError},
+    },
+    infrastructure::{
+        crypto,
+        database::Pool,
+        headers::HeaderTable,
+        proxy::ProxyTable,
+        repository_index::RepositoryIndex,
+        source_cache::{self, SourceCache},
+    },
 };
 
 #[derive(Deserialize)]
@@ -19,18 +36,229 @@ pub struct SourceDto {
     pub rustc_version: String,
     pub lib_version: String,
     pub icon: String,
+    /// null when the source supports every language
+    #[serde(default)]
+    pub languages: Option<tanoshi_lib::models::Lang>,
+    #[serde(default)]
+    pub capabilities: tanoshi_lib::models::Capabilities,
+    #[serde(default)]
+    pub nsfw: bool,
+    /// builds keyed by platform, e.g. `linux-aarch64`, older index doesn't have this
+    #[serde(default)]
+    pub artifacts: HashMap<String, SourceArtifactDto>,
+}
+
+#[derive(Deserialize)]
+pub struct SourceArtifactDto {
+    /// relative to repository url
+    pub path: String,
+    pub rustc_version: String,
+    pub lib_version: String,
+}
+
+impl SourceDto {
+    /// Build for the running platform, `None` when the index predates per platform artifacts
+    fn artifact(&self) -> Result<Option<&SourceArtifactDto>, SourceRepositoryError> {
+        if self.artifacts.is_empty() {
+            return Ok(None);
+        }
+
+        let platform = tanoshi_vm::platform();
+        self.artifacts.get(&platform).map(Some).ok_or_else(|| {
+            SourceRepositoryError::Other(format!("{} has no build for {platform}", self.name))
+        })
+    }
+
+    fn is_compatible(&self) -> Result<(), SourceRepositoryError> {
+        let (rustc_version, lib_version) = match self.artifact()? {
+            Some(artifact) => (&artifact.rustc_version, &artifact.lib_version),
+            None => (&self.rustc_version, &self.lib_version),
+        };
+
+        if rustc_version != tanoshi_lib::RUSTC_VERSION || lib_version != tanoshi_lib::LIB_VERSION {
+            return Err(SourceRepositoryError::Other(
+                "Incompatible version, update tanoshi server".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn row_to_source_health(row: &SqliteRow) -> SourceHealth {
+    SourceHealth {
+        source_id: row.get(0),
+        error_rate: row.get(1),
+        consecutive_failures: row.get(2),
+        last_error: row.get(3),
+        last_success_at: row.get(4),
+        last_failure_at: row.get(5),
+        failing_since: row.get(6),
+        disabled_at: row.get(7),
+        ..Default::default()
+    }
 }
 
 #[derive(Clone)]
 pub struct SourceRepositoryImpl {
+    pool: Pool,
     extension_manager: ExtensionManager,
+    /// encrypts stored source sessions
+    secret: String,
+    proxies: ProxyTable,
+    headers: HeaderTable,
+    /// popular, latest and search responses
+    cache: SourceCache,
+    index: RepositoryIndex,
 }
 
 impl SourceRepositoryImpl {
-    pub fn new(ext: ExtensionManager) -> Self {
+    pub fn new<P: Into<Pool>>(
+        pool: P,
+        ext: ExtensionManager,
+        secret: &str,
+        proxies: ProxyTable,
+        headers: HeaderTable,
+        cache: SourceCache,
+        index: RepositoryIndex,
+    ) -> Self {
         Self {
+            pool: pool.into(),
             extension_manager: ext,
+            secret: secret.to_string(),
+            proxies,
+            headers,
+            cache,
+            index,
+        }
+    }
+
+    /// Health with the crash state of the loaded extension, which lives only in memory
+    fn with_crash_state(&self, mut health: SourceHealth) -> SourceHealth {
+        if let Some(state) = self.extension_manager.crash_state(health.source_id) {
+            health.restarts = state.restarts;
+            health.restarting = state.restarting;
+            health.last_crash = state.last_crash;
         }
+
+        health
+    }
+
+    /// Serve `key` from cache unless `refresh`, otherwise call `fetch` and cache its result
+    async fn cached<F, Fut>(
+        &self,
+        key: String,
+        refresh: bool,
+        fetch: F,
+    ) -> Result<Vec<Manga>, SourceRepositoryError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<Vec<MangaInfo>>>,
+    {
+        if !refresh {
+            if let Some(cached) = self.cache.get(&key).await {
+                match serde_json::from_str::<Vec<MangaInfo>>(&cached) {
+                    Ok(manga) => return Ok(manga.into_iter().map(Manga::from).collect()),
+                    Err(e) => error!("invalid cached source response {key}: {e}"),
+                }
+            }
+        }
+
+        let manga = fetch().await?;
+        self.cache.set(key, serde_json::to_string(&manga)?).await;
+
+        Ok(manga.into_iter().map(Manga::from).collect())
+    }
+
+    async fn stored_preferences(
+        &self,
+        id: i64,
+    ) -> Result<Option<Vec<Input>>, SourceRepositoryError> {
+        let preferences: Option<String> =
+            sqlx::query(r#"SELECT preferences FROM source_preference WHERE source_id = ?"#)
+                .bind(id)
+                .fetch_optional(&self.pool as &SqlitePool)
+                .await?
+                .map(|row| row.get(0));
+
+        match preferences {
+            Some(preferences) => Ok(Some(serde_json::from_str(&preferences)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Extension and image fetches of the source use its own proxy or the default one
+    async fn apply_proxy(
+        &self,
+        id: i64,
+        proxy: Option<String>,
+    ) -> Result<(), SourceRepositoryError> {
+        let source = self.extension_manager.get_source_info(id)?;
+        self.proxies.set(&source.url, proxy.as_deref());
+        self.extension_manager
+            .set_proxy(id, proxy.or_else(|| self.proxies.default_proxy()))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Header override is sent by the extension and on image fetches for the source
+    async fn apply_header_override(
+        &self,
+        id: i64,
+        header_override: Option<HeaderOverride>,
+    ) -> Result<(), SourceRepositoryError> {
+        let source = self.extension_manager.get_source_info(id)?;
+        let headers = header_override
+            .as_ref()
+            .map(HeaderOverride::to_headers)
+            .unwrap_or_default();
+        self.headers.set_source(&source.url, header_override);
+        self.extension_manager
+            .set_header_overrides(id, headers)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Freshly loaded extension starts with its defaults
+    async fn apply_stored_settings(&self, id: i64) -> Result<(), SourceRepositoryError> {
+        self.cache.invalidate(id).await;
+
+        if let Some(preferences) = self.stored_preferences(id).await? {
+            self.extension_manager
+                .set_preferences(id, preferences)
+                .await?;
+        }
+
+        self.apply_proxy(id, self.get_proxy(id).await?).await?;
+        self.extension_manager
+            .set_rate_limit(id, self.get_rate_limit(id).await?)?;
+        self.apply_header_override(id, self.get_header_override(id).await?)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn install_from_index(
+        &self,
+        repo_url: &str,
+        source: &SourceDto,
+    ) -> Result<(), SourceRepositoryError> {
+        match source.artifact()? {
+            Some(artifact) => {
+                self.extension_manager
+                    .install_from_url(&format!("{repo_url}/{}", artifact.path), &source.name)
+                    .await?
+            }
+            None => {
+                self.extension_manager
+                    .install(repo_url, &source.name)
+                    .await?
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -55,10 +283,7 @@ impl SourceRepository for SourceRepositoryImpl {
         repo_url: &str,
         filter_installed: bool,
     ) -> Result<Vec<Source>, SourceRepositoryError> {
-        let source_indexes: Vec<SourceDto> = reqwest::get(&format!("{repo_url}/index.json"))
-            .await?
-            .json()
-            .await?;
+        let source_indexes: Vec<SourceDto> = self.index.get(repo_url).await?;
 
         let mut sources: Vec<Source> = vec![];
         for index in source_indexes {
@@ -66,15 +291,28 @@ impl SourceRepository for SourceRepositoryImpl {
                 continue;
             }
 
+            // hide sources that can't be installed on this platform
+            let (rustc_version, lib_version) = match index.artifact() {
+                Ok(Some(artifact)) => {
+                    (artifact.rustc_version.clone(), artifact.lib_version.clone())
+                }
+                Ok(None) => (index.rustc_version, index.lib_version),
+                Err(_) => continue,
+            };
+
             sources.push(Source {
                 id: index.id,
                 name: index.name,
                 url: index.url,
                 version: index.version,
-                rustc_version: index.rustc_version,
-                lib_version: index.lib_version,
+                rustc_version,
+                lib_version,
                 icon: index.icon,
                 has_update: false,
+                languages: index.languages.and_then(language_codes),
+                pinned: false,
+                capabilities: index.capabilities.into(),
+                nsfw: index.nsfw,
             });
         }
 
@@ -93,27 +331,17 @@ impl SourceRepository for SourceRepositoryImpl {
             ));
         }
 
-        let source_indexes: Vec<SourceDto> = reqwest::get(format!("{repo_url}/index.json"))
-            .await?
-            .json()
-            .await?;
+        let source_indexes: Vec<SourceDto> = self.index.get(repo_url).await?;
 
         let source = source_indexes
             .iter()
             .find(|index| index.id == id)
             .ok_or(SourceRepositoryError::NotFound)?;
 
-        if source.rustc_version != tanoshi_lib::RUSTC_VERSION
-            || source.lib_version != tanoshi_lib::LIB_VERSION
-        {
-            return Err(SourceRepositoryError::Other(
-                "Incompatible version, update tanoshi server".to_string(),
-            ));
-        }
+        source.is_compatible()?;
 
-        self.extension_manager
-            .install(repo_url, &source.name)
-            .await?;
+        self.install_from_index(repo_url, source).await?;
+        self.apply_stored_settings(id).await?;
 
         Ok(())
     }
@@ -121,10 +349,7 @@ impl SourceRepository for SourceRepositoryImpl {
     async fn update_source(&self, repo_url: &str, id: i64) -> Result<(), SourceRepositoryError> {
         let installed_source = self.extension_manager.get_source_info(id)?;
 
-        let source_indexes: Vec<SourceDto> = reqwest::get(format!("{repo_url}/index.json"))
-            .await?
-            .json()
-            .await?;
+        let source_indexes: Vec<SourceDto> = self.index.get(repo_url).await?;
         let source = source_indexes
             .iter()
             .find(|index| index.id == id)
@@ -134,25 +359,638 @@ impl SourceRepository for SourceRepositoryImpl {
             return Err(SourceRepositoryError::Other("No new version".to_string()));
         }
 
-        if source.rustc_version != tanoshi_lib::RUSTC_VERSION
-            || source.lib_version != tanoshi_lib::LIB_VERSION
-        {
-            return Err(SourceRepositoryError::Other(
-                "Incompatible version, update tanoshi server".to_string(),
-            ));
-        }
+        source.is_compatible()?;
 
         self.extension_manager.remove(id).await?;
-        self.extension_manager
-            .install(repo_url, &source.name)
-            .await?;
+        self.install_from_index(repo_url, source).await?;
+        self.apply_stored_settings(id).await?;
+
+        Ok(())
+    }
+
+    async fn install_source_from_file(
+        &self,
+        contents: &[u8],
+    ) -> Result<i64, SourceRepositoryError> {
+        let id = self.extension_manager.install_from_bytes(contents).await?;
+        self.apply_stored_settings(id).await?;
+
+        Ok(id)
+    }
+
+    async fn reload_source(&self, id: i64) -> Result<(), SourceRepositoryError> {
+        self.extension_manager.reload(id).await?;
+        self.apply_stored_settings(id).await?;
 
         Ok(())
     }
 
+    async fn reload_source_file(&self, name: &str) -> Result<i64, SourceRepositoryError> {
+        let id = self.extension_manager.reload_file(name).await?;
+        self.apply_stored_settings(id).await?;
+
+        Ok(id)
+    }
+
     async fn uninstall_source(&self, id: i64) -> Result<(), SourceRepositoryError> {
         self.extension_manager.remove(id).await?;
+        self.cache.invalidate(id).await;
+
+        Ok(())
+    }
+
+    async fn get_logs(
+        &self,
+        id: i64,
+        level: SourceLogLevel,
+    ) -> Result<Vec<SourceLog>, SourceRepositoryError> {
+        let level = match level {
+            SourceLogLevel::Error => log::LevelFilter::Error,
+            SourceLogLevel::Warn => log::LevelFilter::Warn,
+            SourceLogLevel::Info => log::LevelFilter::Info,
+            SourceLogLevel::Debug => log::LevelFilter::Debug,
+        };
+
+        let logs = tanoshi_vm::prelude::source_logs(id, level)
+            .into_iter()
+            .map(|entry| SourceLog {
+                time: chrono::DateTime::<chrono::Utc>::from(entry.time).naive_utc(),
+                level: match entry.level {
+                    log::Level::Error => SourceLogLevel::Error,
+                    log::Level::Warn => SourceLogLevel::Warn,
+                    log::Level::Info => SourceLogLevel::Info,
+                    log::Level::Debug | log::Level::Trace => SourceLogLevel::Debug,
+                },
+                target: entry.target,
+                message: entry.message,
+            })
+            .collect();
+
+        Ok(logs)
+    }
+
+    async fn get_filters(&self, id: i64) -> Result<Vec<Input>, SourceRepositoryError> {
+        Ok(self.extension_manager.filter_list(id)?)
+    }
+
+    async fn get_preferences(&self, id: i64) -> Result<Vec<Input>, SourceRepositoryError> {
+        Ok(self.extension_manager.get_preferences(id)?)
+    }
+
+    async fn set_preferences(
+        &self,
+        id: i64,
+        preferences: Vec<Input>,
+    ) -> Result<(), SourceRepositoryError> {
+        self.extension_manager
+            .set_preferences(id, preferences.clone())
+            .await?;
+
+        sqlx::query(
+            r#"INSERT INTO source_preference(source_id, preferences) VALUES (?, ?)
+            ON CONFLICT(source_id) DO UPDATE SET
+                preferences = excluded.preferences,
+                updated_at = CURRENT_TIMESTAMP"#,
+        )
+        .bind(id)
+        .bind(serde_json::to_string(&preferences)?)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        self.cache.invalidate(id).await;
+
+        Ok(())
+    }
+
+    async fn restore_preferences(&self) -> Result<(), SourceRepositoryError> {
+        for domain_override in self.get_domain_header_overrides().await? {
+            self.headers.set_domain(
+                &domain_override.domain,
+                Some(domain_override.header_override),
+            );
+        }
+
+        for source in self.extension_manager.list().await? {
+            if let Err(e) = self.apply_stored_settings(source.id).await {
+                error!("failed to restore preferences of {}: {e}", source.name);
+            }
+        }
 
         Ok(())
     }
+
+    async fn login(
+        &self,
+        id: i64,
+        username: &str,
+        password: &str,
+    ) -> Result<String, SourceRepositoryError> {
+        Ok(self
+            .extension_manager
+            .login(id, username.to_string(), password.to_string())
+            .await?)
+    }
+
+    async fn get_credential(
+        &self,
+        user_id: i64,
+        source_id: i64,
+    ) -> Result<Option<SourceCredential>, SourceRepositoryError> {
+        let row = sqlx::query(
+            r#"SELECT user_id, source_id, username, session, updated_at FROM source_credential
+            WHERE user_id = ? AND source_id = ?"#,
+        )
+        .bind(user_id)
+        .bind(source_id)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?;
+
+        let credential = match row {
+            Some(row) => {
+                let session: String = row.get(3);
+                Some(SourceCredential {
+                    user_id: row.get(0),
+                    source_id: row.get(1),
+                    username: row.get(2),
+                    session: crypto::decrypt(&self.secret, &session)
+                        .map_err(|e| SourceRepositoryError::Other(format!("{e}")))?,
+                    updated_at: row.get(4),
+                })
+            }
+            None => None,
+        };
+
+        Ok(credential)
+    }
+
+    async fn get_library_session(
+        &self,
+        source_id: i64,
+        manga_id: i64,
+    ) -> Result<Option<String>, SourceRepositoryError> {
+        let row = sqlx::query(
+            r#"SELECT source_credential.session FROM source_credential
+            JOIN user_library ON user_library.user_id = source_credential.user_id
+            WHERE source_credential.source_id = ? AND user_library.manga_id = ?
+            ORDER BY source_credential.updated_at DESC
+            LIMIT 1"#,
+        )
+        .bind(source_id)
+        .bind(manga_id)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?;
+
+        row.map(|row| {
+            let session: String = row.get(0);
+            crypto::decrypt(&self.secret, &session)
+                .map_err(|e| SourceRepositoryError::Other(format!("{e}")))
+        })
+        .transpose()
+    }
+
+    async fn save_credential(
+        &self,
+        user_id: i64,
+        source_id: i64,
+        username: &str,
+        session: &str,
+    ) -> Result<(), SourceRepositoryError> {
+        sqlx::query(
+            r#"INSERT INTO source_credential(user_id, source_id, username, session)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(user_id, source_id) DO UPDATE SET
+                username = excluded.username,
+                session = excluded.session,
+                updated_at = CURRENT_TIMESTAMP"#,
+        )
+        .bind(user_id)
+        .bind(source_id)
+        .bind(username)
+        .bind(
+            crypto::encrypt(&self.secret, session)
+                .map_err(|e| SourceRepositoryError::Other(format!("{e}")))?,
+        )
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_credential(
+        &self,
+        user_id: i64,
+        source_id: i64,
+    ) -> Result<(), SourceRepositoryError> {
+        sqlx::query(r#"DELETE FROM source_credential WHERE user_id = ? AND source_id = ?"#)
+            .bind(user_id)
+            .bind(source_id)
+            .execute(&self.pool as &SqlitePool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_pinned_source_ids(&self, user_id: i64) -> Result<Vec<i64>, SourceRepositoryError> {
+        let source_ids = sqlx::query(
+            r#"SELECT source_id FROM source_pin WHERE user_id = ? ORDER BY created_at, id"#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+        Ok(source_ids)
+    }
+
+    async fn pin_source(&self, user_id: i64, source_id: i64) -> Result<(), SourceRepositoryError> {
+        sqlx::query(
+            r#"INSERT INTO source_pin(user_id, source_id) VALUES (?, ?)
+            ON CONFLICT(user_id, source_id) DO NOTHING"#,
+        )
+        .bind(user_id)
+        .bind(source_id)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn unpin_source(
+        &self,
+        user_id: i64,
+        source_id: i64,
+    ) -> Result<(), SourceRepositoryError> {
+        sqlx::query(r#"DELETE FROM source_pin WHERE user_id = ? AND source_id = ?"#)
+            .bind(user_id)
+            .bind(source_id)
+            .execute(&self.pool as &SqlitePool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_saved_filters(
+        &self,
+        user_id: i64,
+        source_id: i64,
+    ) -> Result<Option<Vec<Input>>, SourceRepositoryError> {
+        let row =
+            sqlx::query(r#"SELECT filters FROM source_filter WHERE user_id = ? AND source_id = ?"#)
+                .bind(user_id)
+                .bind(source_id)
+                .fetch_optional(&self.pool as &SqlitePool)
+                .await?;
+
+        let filters = row
+            .map(|row| serde_json::from_str(&row.get::<String, _>(0)))
+            .transpose()?;
+
+        Ok(filters)
+    }
+
+    async fn save_filters(
+        &self,
+        user_id: i64,
+        source_id: i64,
+        filters: &[Input],
+    ) -> Result<(), SourceRepositoryError> {
+        sqlx::query(
+            r#"INSERT INTO source_filter(user_id, source_id, filters) VALUES (?, ?, ?)
+            ON CONFLICT(user_id, source_id) DO UPDATE SET
+                filters = excluded.filters,
+                updated_at = CURRENT_TIMESTAMP"#,
+        )
+        .bind(user_id)
+        .bind(source_id)
+        .bind(serde_json::to_string(filters)?)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_saved_filters(
+        &self,
+        user_id: i64,
+        source_id: i64,
+    ) -> Result<(), SourceRepositoryError> {
+        sqlx::query(r#"DELETE FROM source_filter WHERE user_id = ? AND source_id = ?"#)
+            .bind(user_id)
+            .bind(source_id)
+            .execute(&self.pool as &SqlitePool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_source_health(
+        &self,
+        source_id: i64,
+    ) -> Result<SourceHealth, SourceRepositoryError> {
+        let row = sqlx::query(
+            r#"SELECT source_id, error_rate, consecutive_failures, last_error, last_success_at,
+            last_failure_at, failing_since, disabled_at FROM source_health WHERE source_id = ?"#,
+        )
+        .bind(source_id)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?;
+
+        let health = row
+            .map(|row| row_to_source_health(&row))
+            .unwrap_or_else(|| SourceHealth::new(source_id));
+
+        Ok(self.with_crash_state(health))
+    }
+
+    async fn get_all_source_health(&self) -> Result<Vec<SourceHealth>, SourceRepositoryError> {
+        let health = sqlx::query(
+            r#"SELECT source_id, error_rate, consecutive_failures, last_error, last_success_at,
+            last_failure_at, failing_since, disabled_at FROM source_health"#,
+        )
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| self.with_crash_state(row_to_source_health(row)))
+        .collect();
+
+        Ok(health)
+    }
+
+    async fn save_source_health(&self, health: &SourceHealth) -> Result<(), SourceRepositoryError> {
+        sqlx::query(
+            r#"INSERT INTO source_health(
+                source_id,
+                error_rate,
+                consecutive_failures,
+                last_error,
+                last_success_at,
+                last_failure_at,
+                failing_since,
+                disabled_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(source_id) DO UPDATE SET
+                error_rate = excluded.error_rate,
+                consecutive_failures = excluded.consecutive_failures,
+                last_error = excluded.last_error,
+                last_success_at = excluded.last_success_at,
+                last_failure_at = excluded.last_failure_at,
+                failing_since = excluded.failing_since,
+                disabled_at = excluded.disabled_at"#,
+        )
+        .bind(health.source_id)
+        .bind(health.error_rate)
+        .bind(health.consecutive_failures)
+        .bind(health.last_error.as_ref())
+        .bind(health.last_success_at)
+        .bind(health.last_failure_at)
+        .bind(health.failing_since)
+        .bind(health.disabled_at)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_proxy(&self, id: i64) -> Result<Option<String>, SourceRepositoryError> {
+        let proxy = sqlx::query(r#"SELECT url FROM source_proxy WHERE source_id = ?"#)
+            .bind(id)
+            .fetch_optional(&self.pool as &SqlitePool)
+            .await?
+            .map(|row| row.get(0));
+
+        Ok(proxy)
+    }
+
+    async fn set_proxy(&self, id: i64, proxy: Option<&str>) -> Result<(), SourceRepositoryError> {
+        match proxy {
+            Some(proxy) => {
+                sqlx::query(
+                    r#"INSERT INTO source_proxy(source_id, url) VALUES (?, ?)
+                    ON CONFLICT(source_id) DO UPDATE SET
+                        url = excluded.url,
+                        updated_at = CURRENT_TIMESTAMP"#,
+                )
+                .bind(id)
+                .bind(proxy)
+                .execute(&self.pool as &SqlitePool)
+                .await?;
+            }
+            None => {
+                sqlx::query(r#"DELETE FROM source_proxy WHERE source_id = ?"#)
+                    .bind(id)
+                    .execute(&self.pool as &SqlitePool)
+                    .await?;
+            }
+        }
+
+        self.apply_proxy(id, proxy.map(|proxy| proxy.to_string()))
+            .await
+    }
+
+    async fn get_header_override(
+        &self,
+        id: i64,
+    ) -> Result<Option<HeaderOverride>, SourceRepositoryError> {
+        let row =
+            sqlx::query(r#"SELECT user_agent, headers FROM source_header WHERE source_id = ?"#)
+                .bind(id)
+                .fetch_optional(&self.pool as &SqlitePool)
+                .await?;
+
+        match row {
+            Some(row) => Ok(Some(HeaderOverride {
+                user_agent: row.get(0),
+                headers: serde_json::from_str(&row.get::<String, _>(1))?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    async fn set_header_override(
+        &self,
+        id: i64,
+        header_override: Option<&HeaderOverride>,
+    ) -> Result<(), SourceRepositoryError> {
+        match header_override {
+            Some(header_override) => {
+                sqlx::query(
+                    r#"INSERT INTO source_header(source_id, user_agent, headers) VALUES (?, ?, ?)
+                    ON CONFLICT(source_id) DO UPDATE SET
+                        user_agent = excluded.user_agent,
+                        headers = excluded.headers,
+                        updated_at = CURRENT_TIMESTAMP"#,
+                )
+                .bind(id)
+                .bind(header_override.user_agent.as_deref())
+                .bind(serde_json::to_string(&header_override.headers)?)
+                .execute(&self.pool as &SqlitePool)
+                .await?;
+            }
+            None => {
+                sqlx::query(r#"DELETE FROM source_header WHERE source_id = ?"#)
+                    .bind(id)
+                    .execute(&self.pool as &SqlitePool)
+                    .await?;
+            }
+        }
+
+        self.apply_header_override(id, header_override.cloned())
+            .await
+    }
+
+    async fn get_domain_header_overrides(
+        &self,
+    ) -> Result<Vec<DomainHeaderOverride>, SourceRepositoryError> {
+        let rows =
+            sqlx::query(r#"SELECT domain, user_agent, headers FROM domain_header ORDER BY domain"#)
+                .fetch_all(&self.pool as &SqlitePool)
+                .await?;
+
+        let mut overrides = vec![];
+        for row in rows {
+            overrides.push(DomainHeaderOverride {
+                domain: row.get(0),
+                header_override: HeaderOverride {
+                    user_agent: row.get(1),
+                    headers: serde_json::from_str(&row.get::<String, _>(2))?,
+                },
+            });
+        }
+
+        Ok(overrides)
+    }
+
+    async fn set_domain_header_override(
+        &self,
+        domain: &str,
+        header_override: Option<&HeaderOverride>,
+    ) -> Result<(), SourceRepositoryError> {
+        match header_override {
+            Some(header_override) => {
+                sqlx::query(
+                    r#"INSERT INTO domain_header(domain, user_agent, headers) VALUES (?, ?, ?)
+                    ON CONFLICT(domain) DO UPDATE SET
+                        user_agent = excluded.user_agent,
+                        headers = excluded.headers,
+                        updated_at = CURRENT_TIMESTAMP"#,
+                )
+                .bind(domain)
+                .bind(header_override.user_agent.as_deref())
+                .bind(serde_json::to_string(&header_override.headers)?)
+                .execute(&self.pool as &SqlitePool)
+                .await?;
+            }
+            None => {
+                sqlx::query(r#"DELETE FROM domain_header WHERE domain = ?"#)
+                    .bind(domain)
+                    .execute(&self.pool as &SqlitePool)
+                    .await?;
+            }
+        }
+
+        self.headers.set_domain(domain, header_override.cloned());
+
+        Ok(())
+    }
+
+    async fn get_rate_limit(&self, id: i64) -> Result<Option<u32>, SourceRepositoryError> {
+        let requests_per_minute =
+            sqlx::query(r#"SELECT requests_per_minute FROM source_rate_limit WHERE source_id = ?"#)
+                .bind(id)
+                .fetch_optional(&self.pool as &SqlitePool)
+                .await?
+                .map(|row| row.get(0));
+
+        Ok(requests_per_minute)
+    }
+
+    async fn set_rate_limit(
+        &self,
+        id: i64,
+        requests_per_minute: Option<u32>,
+    ) -> Result<(), SourceRepositoryError> {
+        match requests_per_minute {
+            Some(requests_per_minute) => {
+                sqlx::query(
+                    r#"INSERT INTO source_rate_limit(source_id, requests_per_minute) VALUES (?, ?)
+                    ON CONFLICT(source_id) DO UPDATE SET
+                        requests_per_minute = excluded.requests_per_minute,
+                        updated_at = CURRENT_TIMESTAMP"#,
+                )
+                .bind(id)
+                .bind(requests_per_minute)
+                .execute(&self.pool as &SqlitePool)
+                .await?;
+            }
+            None => {
+                sqlx::query(r#"DELETE FROM source_rate_limit WHERE source_id = ?"#)
+                    .bind(id)
+                    .execute(&self.pool as &SqlitePool)
+                    .await?;
+            }
+        }
+
+        self.extension_manager
+            .set_rate_limit(id, requests_per_minute)?;
+
+        Ok(())
+    }
+
+    async fn get_popular_manga(
+        &self,
+        id: i64,
+        page: i64,
+        session: Option<String>,
+        refresh: bool,
+    ) -> Result<Vec<Manga>, SourceRepositoryError> {
+        let key = source_cache::key(id, ("popular", page), session.as_deref());
+        let ext = self.extension_manager.with_session(session);
+
+        self.cached(key, refresh, || async move {
+            ext.get_popular_manga(id, page).await
+        })
+        .await
+    }
+
+    async fn get_latest_manga(
+        &self,
+        id: i64,
+        page: i64,
+        session: Option<String>,
+        refresh: bool,
+    ) -> Result<Vec<Manga>, SourceRepositoryError> {
+        let key = source_cache::key(id, ("latest", page), session.as_deref());
+        let ext = self.extension_manager.with_session(session);
+
+        self.cached(key, refresh, || async move {
+            ext.get_latest_manga(id, page).await
+        })
+        .await
+    }
+
+    async fn search_manga(
+        &self,
+        id: i64,
+        page: i64,
+        query: Option<String>,
+        filters: Option<Vec<Input>>,
+        session: Option<String>,
+        refresh: bool,
+    ) -> Result<Vec<Manga>, SourceRepositoryError> {
+        let request = (
+            "search",
+            page,
+            query.clone(),
+            filters.as_ref().map(serde_json::to_string).transpose()?,
+        );
+        let key = source_cache::key(id, request, session.as_deref());
+        let ext = self.extension_manager.with_session(session);
+
+        self.cached(key, refresh, || async move {
+            ext.search_manga(id, page, query, filters).await
+        })
+        .await
+    }
 }
@@ -1,6 +1,6 @@
 use crate::{
     domain::{
-        entities::user::User,
+        entities::user::{CatalogueSort, User},
         repositories::user::{UserRepository, UserRepositoryError},
     },
     infrastructure::database::Pool,
@@ -91,6 +91,28 @@ impl UserRepository for UserRepositoryImpl {
                 telegram_chat_id: row.get(6),
                 pushover_user_key: row.get(7),
                 gotify_token: row.get(8),
+                notification_digest_interval: row.get(9),
+                notify_chapter_category_ids: row
+                    .get::<Option<String>, _>(10)
+                    .and_then(|ids| serde_json::from_str(&ids).ok()),
+                notify_download_failures: row.get(11),
+                notify_announcements: row.get(12),
+                pushover_device: row.get(13),
+                pushover_sound: row.get(14),
+                pushover_chapter_priority: row.get(15),
+                pushover_alert_priority: row.get(16),
+                catalogue_sort: row.get::<String, _>(17).parse().unwrap_or_default(),
+                catalogue_language: row.get(18),
+                catalogue_hide_library: row.get(19),
+                enabled_languages: row
+                    .get::<Option<String>, _>(20)
+                    .and_then(|languages| serde_json::from_str(&languages).ok()),
+                hide_nsfw: row.get(21),
+                device_email: row.get(22),
+                data_saver: row.get(23),
+                chapter_languages: row
+                    .get::<Option<String>, _>(24)
+                    .and_then(|languages| serde_json::from_str(&languages).ok()),
             })
             .collect();
 
@@ -121,6 +143,28 @@ impl UserRepository for UserRepositoryImpl {
                 telegram_chat_id: row.get(6),
                 pushover_user_key: row.get(7),
                 gotify_token: row.get(8),
+                notification_digest_interval: row.get(9),
+                notify_chapter_category_ids: row
+                    .get::<Option<String>, _>(10)
+                    .and_then(|ids| serde_json::from_str(&ids).ok()),
+                notify_download_failures: row.get(11),
+                notify_announcements: row.get(12),
+                pushover_device: row.get(13),
+                pushover_sound: row.get(14),
+                pushover_chapter_priority: row.get(15),
+                pushover_alert_priority: row.get(16),
+                catalogue_sort: row.get::<String, _>(17).parse().unwrap_or_default(),
+                catalogue_language: row.get(18),
+                catalogue_hide_library: row.get(19),
+                enabled_languages: row
+                    .get::<Option<String>, _>(20)
+                    .and_then(|languages| serde_json::from_str(&languages).ok()),
+                hide_nsfw: row.get(21),
+                device_email: row.get(22),
+                data_saver: row.get(23),
+                chapter_languages: row
+                    .get::<Option<String>, _>(24)
+                    .and_then(|languages| serde_json::from_str(&languages).ok()),
             });
         }
         Ok(users)
@@ -142,6 +186,28 @@ impl UserRepository for UserRepositoryImpl {
             telegram_chat_id: row.get(6),
             pushover_user_key: row.get(7),
             gotify_token: row.get(8),
+            notification_digest_interval: row.get(9),
+            notify_chapter_category_ids: row
+                .get::<Option<String>, _>(10)
+                .and_then(|ids| serde_json::from_str(&ids).ok()),
+            notify_download_failures: row.get(11),
+            notify_announcements: row.get(12),
+            pushover_device: row.get(13),
+            pushover_sound: row.get(14),
+            pushover_chapter_priority: row.get(15),
+            pushover_alert_priority: row.get(16),
+            catalogue_sort: row.get::<String, _>(17).parse().unwrap_or_default(),
+            catalogue_language: row.get(18),
+            catalogue_hide_library: row.get(19),
+            enabled_languages: row
+                .get::<Option<String>, _>(20)
+                .and_then(|languages| serde_json::from_str(&languages).ok()),
+            hide_nsfw: row.get(21),
+            device_email: row.get(22),
+            data_saver: row.get(23),
+            chapter_languages: row
+                .get::<Option<String>, _>(24)
+                .and_then(|languages| serde_json::from_str(&languages).ok()),
         })
     }
 
@@ -161,6 +227,72 @@ impl UserRepository for UserRepositoryImpl {
             telegram_chat_id: row.get(6),
             pushover_user_key: row.get(7),
             gotify_token: row.get(8),
+            notification_digest_interval: row.get(9),
+            notify_chapter_category_ids: row
+                .get::<Option<String>, _>(10)
+                .and_then(|ids| serde_json::from_str(&ids).ok()),
+            notify_download_failures: row.get(11),
+            notify_announcements: row.get(12),
+            pushover_device: row.get(13),
+            pushover_sound: row.get(14),
+            pushover_chapter_priority: row.get(15),
+            pushover_alert_priority: row.get(16),
+            catalogue_sort: row.get::<String, _>(17).parse().unwrap_or_default(),
+            catalogue_language: row.get(18),
+            catalogue_hide_library: row.get(19),
+            enabled_languages: row
+                .get::<Option<String>, _>(20)
+                .and_then(|languages| serde_json::from_str(&languages).ok()),
+            hide_nsfw: row.get(21),
+            device_email: row.get(22),
+            data_saver: row.get(23),
+            chapter_languages: row
+                .get::<Option<String>, _>(24)
+                .and_then(|languages| serde_json::from_str(&languages).ok()),
+        })
+    }
+
+    async fn get_user_by_telegram_chat_id(
+        &self,
+        chat_id: i64,
+    ) -> Result<User, UserRepositoryError> {
+        let row = sqlx::query(r#"SELECT * FROM user WHERE telegram_chat_id = ?"#)
+            .bind(chat_id)
+            .fetch_one(&self.pool as &SqlitePool)
+            .await?;
+
+        Ok(User {
+            id: row.get(0),
+            username: row.get(1),
+            password: row.get(2),
+            is_admin: row.get(3),
+            created_at: row.get(4),
+            updated_at: row.get(5),
+            telegram_chat_id: row.get(6),
+            pushover_user_key: row.get(7),
+            gotify_token: row.get(8),
+            notification_digest_interval: row.get(9),
+            notify_chapter_category_ids: row
+                .get::<Option<String>, _>(10)
+                .and_then(|ids| serde_json::from_str(&ids).ok()),
+            notify_download_failures: row.get(11),
+            notify_announcements: row.get(12),
+            pushover_device: row.get(13),
+            pushover_sound: row.get(14),
+            pushover_chapter_priority: row.get(15),
+            pushover_alert_priority: row.get(16),
+            catalogue_sort: row.get::<String, _>(17).parse().unwrap_or_default(),
+            catalogue_language: row.get(18),
+            catalogue_hide_library: row.get(19),
+            enabled_languages: row
+                .get::<Option<String>, _>(20)
+                .and_then(|languages| serde_json::from_str(&languages).ok()),
+            hide_nsfw: row.get(21),
+            device_email: row.get(22),
+            data_saver: row.get(23),
+            chapter_languages: row
+                .get::<Option<String>, _>(24)
+                .and_then(|languages| serde_json::from_str(&languages).ok()),
         })
     }
 
@@ -171,9 +303,17 @@ impl UserRepository for UserRepositoryImpl {
         column_to_update.push("telegram_chat_id = ?");
         column_to_update.push("pushover_user_key = ?");
         column_to_update.push("gotify_token = ?");
+        column_to_update.push("pushover_device = ?");
+        column_to_update.push("pushover_sound = ?");
+        column_to_update.push("pushover_chapter_priority = ?");
+        column_to_update.push("pushover_alert_priority = ?");
         arguments.add(user.telegram_chat_id);
         arguments.add(user.pushover_user_key.clone());
         arguments.add(user.gotify_token.clone());
+        arguments.add(user.pushover_device.clone());
+        arguments.add(user.pushover_sound.clone());
+        arguments.add(user.pushover_chapter_priority);
+        arguments.add(user.pushover_alert_priority);
         arguments.add(user.id);
 
         if column_to_update.is_empty() {
@@ -194,4 +334,178 @@ impl UserRepository for UserRepositoryImpl {
 
         Ok(rows_affected)
     }
+
+    async fn update_notification_digest_interval(
+        &self,
+        id: i64,
+        interval: Option<i64>,
+    ) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(
+            r#"UPDATE user
+                SET notification_digest_interval = ?
+                WHERE id = ?"#,
+        )
+        .bind(interval)
+        .bind(id)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn update_notification_preferences(
+        &self,
+        id: i64,
+        chapter_category_ids: Option<&[i64]>,
+        download_failures: bool,
+        announcements: bool,
+    ) -> Result<u64, UserRepositoryError> {
+        let chapter_category_ids = chapter_category_ids
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| UserRepositoryError::Other(e.into()))?;
+
+        let rows_affected = sqlx::query(
+            r#"UPDATE user
+                SET notify_chapter_category_ids = ?,
+                notify_download_failures = ?,
+                notify_announcements = ?
+                WHERE id = ?"#,
+        )
+        .bind(chapter_category_ids)
+        .bind(download_failures)
+        .bind(announcements)
+        .bind(id)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn update_catalogue_preferences(
+        &self,
+        id: i64,
+        sort: CatalogueSort,
+        language: Option<&str>,
+        hide_library: bool,
+    ) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(
+            r#"UPDATE user
+                SET catalogue_sort = ?,
+                catalogue_language = ?,
+                catalogue_hide_library = ?
+                WHERE id = ?"#,
+        )
+        .bind(sort.to_string())
+        .bind(language)
+        .bind(hide_library)
+        .bind(id)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn update_enabled_languages(
+        &self,
+        id: i64,
+        languages: Option<&[String]>,
+    ) -> Result<u64, UserRepositoryError> {
+        let languages = languages
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| UserRepositoryError::Other(e.into()))?;
+
+        let rows_affected = sqlx::query(r#"UPDATE user SET enabled_languages = ? WHERE id = ?"#)
+            .bind(languages)
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn update_hide_nsfw(&self, id: i64, hide: bool) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(r#"UPDATE user SET hide_nsfw = ? WHERE id = ?"#)
+            .bind(hide)
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn update_device_email(
+        &self,
+        id: i64,
+        email: Option<&str>,
+    ) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(r#"UPDATE user SET device_email = ? WHERE id = ?"#)
+            .bind(email)
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn update_data_saver(&self, id: i64, enabled: bool) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(r#"UPDATE user SET data_saver = ? WHERE id = ?"#)
+            .bind(enabled)
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn get_category_lock_generation(&self, id: i64) -> Result<i64, UserRepositoryError> {
+        let row = sqlx::query(r#"SELECT category_lock_generation FROM user WHERE id = ?"#)
+            .bind(id)
+            .fetch_one(&self.pool as &SqlitePool)
+            .await?;
+
+        Ok(row.get(0))
+    }
+
+    async fn increment_category_lock_generation(
+        &self,
+        id: i64,
+    ) -> Result<i64, UserRepositoryError> {
+        sqlx::query(
+            r#"UPDATE user SET category_lock_generation = category_lock_generation + 1 WHERE id = ?"#,
+        )
+        .bind(id)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        self.get_category_lock_generation(id).await
+    }
+
+    async fn update_chapter_languages(
+        &self,
+        id: i64,
+        languages: Option<&[String]>,
+    ) -> Result<u64, UserRepositoryError> {
+        let languages = languages
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| UserRepositoryError::Other(e.into()))?;
+
+        let rows_affected = sqlx::query(r#"UPDATE user SET chapter_languages = ? WHERE id = ?"#)
+            .bind(languages)
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
 }
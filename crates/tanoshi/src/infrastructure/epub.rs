@@ -0,0 +1,453 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Cursor, Read, Seek, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use super::comic_info::{escape, unescape};
+use crate::domain::entities::manga::Manga;
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+/// Book level metadata of an epub, from the manga it's exported from
+pub struct EpubMetadata {
+    pub identifier: String,
+    pub title: String,
+    pub series: String,
+    /// position in the series, the volume or chapter number
+    pub series_index: Option<f64>,
+    pub authors: Vec<String>,
+    pub description: Option<String>,
+    pub subjects: Vec<String>,
+    pub language: Option<String>,
+    pub rtl: bool,
+}
+
+impl EpubMetadata {
+    pub fn new(manga: &Manga, identifier: String, title: String) -> Self {
+        Self {
+            identifier,
+            title,
+            series: manga.title.clone(),
+            series_index: None,
+            authors: manga.author.clone(),
+            description: manga.description.clone(),
+            subjects: manga.genre.clone(),
+            language: None,
+            rtl: false,
+        }
+    }
+}
+
+struct EpubPage {
+    id: String,
+    image: String,
+    media_type: &'static str,
+}
+
+/// Writes a fixed layout epub, every page an image filling the viewport,
+/// to `writer` as pages are added
+pub struct EpubWriter<W: Write + Seek> {
+    zip: ZipWriter<W>,
+    pages: Vec<EpubPage>,
+    /// table of contents, titles with the index of the page they start at
+    toc: Vec<(String, usize)>,
+}
+
+impl<W: Write + Seek> EpubWriter<W> {
+    pub fn new(writer: W) -> Result<Self> {
+        let mut zip = ZipWriter::new(writer);
+        // must be the first entry and uncompressed for readers to sniff the format
+        zip.start_file(
+            "mimetype",
+            FileOptions::default().compression_method(CompressionMethod::Stored),
+        )?;
+        zip.write_all(b"application/epub+zip")?;
+        zip.start_file("META-INF/container.xml", FileOptions::default())?;
+        zip.write_all(CONTAINER_XML.as_bytes())?;
+
+        Ok(Self {
+            zip,
+            pages: vec![],
+            toc: vec![],
+        })
+    }
+
+    /// Start a chapter titled `title` at the next page added
+    pub fn begin_chapter(&mut self, title: &str) {
+        self.toc.push((title.to_string(), self.pages.len()));
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Add a page showing `data`, only gif, jpeg, png and svg are core media types
+    /// so anything else is converted to jpeg
+    pub fn add_page(&mut self, data: &[u8]) -> Result<()> {
+        let format = image::guess_format(data)?;
+        let converted;
+        let (data, extension, media_type) = match format {
+            image::ImageFormat::Jpeg => (data, "jpg", "image/jpeg"),
+            image::ImageFormat::Png => (data, "png", "image/png"),
+            image::ImageFormat::Gif => (data, "gif", "image/gif"),
+            _ => {
+                let mut buf = Cursor::new(vec![]);
+                image::load_from_memory(data)?
+                    .into_rgb8()
+                    .write_to(&mut buf, image::ImageOutputFormat::Jpeg(90))?;
+                converted = buf.into_inner();
+                (converted.as_slice(), "jpg", "image/jpeg")
+            }
+        };
+        let (width, height) = image::io::Reader::new(Cursor::new(data))
+            .with_guessed_format()?
+            .into_dimensions()?;
+
+        let n = self.pages.len() + 1;
+        let id = format!("page{n:04}");
+        let image = format!("images/{id}.{extension}");
+
+        self.zip.start_file(
+            format!("OEBPS/{image}"),
+            FileOptions::default().compression_method(CompressionMethod::Stored),
+        )?;
+        self.zip.write_all(data)?;
+
+        self.zip
+            .start_file(format!("OEBPS/{id}.xhtml"), FileOptions::default())?;
+        self.zip.write_all(
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head>
+  <title>{n}</title>
+  <meta name="viewport" content="width={width}, height={height}"/>
+  <style>html, body {{ margin: 0; padding: 0; }} img {{ width: {width}px; height: {height}px; }}</style>
+</head>
+<body>
+  <img src="{image}" alt="{n}"/>
+</body>
+</html>
+"#
+            )
+            .as_bytes(),
+        )?;
+
+        self.pages.push(EpubPage {
+            id,
+            image,
+            media_type,
+        });
+
+        Ok(())
+    }
+
+    /// Write the package document and navigation, returns the writer
+    pub fn finish(mut self, metadata: &EpubMetadata) -> Result<W> {
+        let mut meta = vec![
+            format!(
+                "<dc:identifier id=\"book-id\">{}</dc:identifier>",
+                escape(&metadata.identifier)
+            ),
+            format!("<dc:title>{}</dc:title>", escape(&metadata.title)),
+            format!(
+                "<dc:language>{}</dc:language>",
+                escape(metadata.language.as_deref().unwrap_or("und"))
+            ),
+            format!(
+                "<meta property=\"dcterms:modified\">{}</meta>",
+                Utc::now().format("%Y-%m-%dT%H:%M:%SZ")
+            ),
+            "<meta property=\"rendition:layout\">pre-paginated</meta>".to_string(),
+            "<meta property=\"rendition:spread\">landscape</meta>".to_string(),
+            format!(
+                "<meta property=\"belongs-to-collection\" id=\"series\">{}</meta>",
+                escape(&metadata.series)
+            ),
+            "<meta refines=\"#series\" property=\"collection-type\">series</meta>".to_string(),
+            // calibre and readers based on it only know their own series metadata
+            format!(
+                "<meta name=\"calibre:series\" content=\"{}\"/>",
+                escape(&metadata.series)
+            ),
+        ];
+        if let Some(index) = metadata.series_index {
+            meta.push(format!(
+                "<meta refines=\"#series\" property=\"group-position\">{index}</meta>"
+            ));
+            meta.push(format!(
+                "<meta name=\"calibre:series_index\" content=\"{index}\"/>"
+            ));
+        }
+        for author in metadata.authors.iter() {
+            meta.push(format!("<dc:creator>{}</dc:creator>", escape(author)));
+        }
+        if let Some(description) = metadata.description.as_ref() {
+            meta.push(format!(
+                "<dc:description>{}</dc:description>",
+                escape(description)
+            ));
+        }
+        for subject in metadata.subjects.iter() {
+            meta.push(format!("<dc:subject>{}</dc:subject>", escape(subject)));
+        }
+
+        let mut manifest = vec![
+            "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>".to_string(),
+        ];
+        let mut spine = vec![];
+        for (i, page) in self.pages.iter().enumerate() {
+            let cover = if i == 0 {
+                " properties=\"cover-image\""
+            } else {
+                ""
+            };
+            manifest.push(format!(
+                "<item id=\"{id}-image\" href=\"{}\" media-type=\"{}\"{cover}/>",
+                page.image,
+                page.media_type,
+                id = page.id
+            ));
+            manifest.push(format!(
+                "<item id=\"{id}\" href=\"{id}.xhtml\" media-type=\"application/xhtml+xml\"/>",
+                id = page.id
+            ));
+            spine.push(format!("<itemref idref=\"{}\"/>", page.id));
+        }
+
+        let indent = "\n    ";
+        self.zip
+            .start_file("OEBPS/content.opf", FileOptions::default())?;
+        self.zip.write_all(
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" prefix="rendition: http://www.idpf.org/vocab/rendition/#">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    {}
+  </metadata>
+  <manifest>
+    {}
+  </manifest>
+  <spine page-progression-direction="{}">
+    {}
+  </spine>
+</package>
+"#,
+                meta.join(indent),
+                manifest.join(indent),
+                if metadata.rtl { "rtl" } else { "ltr" },
+                spine.join(indent),
+            )
+            .as_bytes(),
+        )?;
+
+        let toc: Vec<String> = self
+            .toc
+            .iter()
+            .filter_map(|(title, index)| {
+                let page = self.pages.get(*index)?;
+                Some(format!(
+                    "<li><a href=\"{}.xhtml\">{}</a></li>",
+                    page.id,
+                    escape(title)
+                ))
+            })
+            .collect();
+        self.zip
+            .start_file("OEBPS/nav.xhtml", FileOptions::default())?;
+        self.zip.write_all(
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head>
+  <title>{title}</title>
+</head>
+<body>
+  <nav epub:type="toc">
+    <ol>
+      {toc}
+    </ol>
+  </nav>
+</body>
+</html>
+"#,
+                title = escape(&metadata.title),
+                toc = toc.join("\n      "),
+            )
+            .as_bytes(),
+        )?;
+
+        Ok(self.zip.finish()?)
+    }
+}
+
+pub fn is_epub(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("epub"))
+        .unwrap_or(false)
+}
+
+/// Attributes of every `<name ...>` tag in `xml`, whatever namespace prefix it has
+fn tags<'a>(xml: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    xml.split('<').skip(1).filter_map(move |tag| {
+        let tag = tag.split('>').next()?;
+        let end = tag
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(tag.len());
+        let tag_name = &tag[..end];
+        let local_name = tag_name.rsplit(':').next().unwrap_or(tag_name);
+        (local_name == name).then(|| &tag[end..])
+    })
+}
+
+/// Value of attribute `name` in the attributes of a tag
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let pattern = format!("{name}=");
+    let mut rest = attributes;
+    while let Some(start) = rest.find(&pattern) {
+        let preceded_by_space = rest[..start]
+            .chars()
+            .last()
+            .map(char::is_whitespace)
+            .unwrap_or(true);
+        rest = &rest[start + pattern.len()..];
+        if !preceded_by_space {
+            continue;
+        }
+
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = rest[1..].split(quote).next()?;
+        return Some(unescape(value));
+    }
+
+    None
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = value
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Path in the archive that `href` in the document at `base` points to
+fn resolve_href(base: &str, href: &str) -> String {
+    let href = percent_decode(href.split('#').next().unwrap_or_default());
+    let mut segments: Vec<&str> = match base.rsplit_once('/') {
+        Some((dir, _)) => dir.split('/').collect(),
+        None => vec![],
+    };
+    for segment in href.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    segments.join("/")
+}
+
+fn read_entry<R: Read + Seek>(zip: &mut ZipArchive<R>, name: &str) -> Result<String> {
+    let mut content = String::new();
+    zip.by_name(name)?.read_to_string(&mut content)?;
+
+    Ok(content)
+}
+
+/// Images of an epub in reading order, as paths in the archive. Every document of
+/// the spine is taken as a page showing its first image, like the fixed layout comics
+/// tanoshi exports, falling back to every image of the book for anything else
+pub fn list_pages(path: &Path) -> Result<Vec<String>> {
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+
+    let container = read_entry(&mut zip, "META-INF/container.xml")?;
+    let package_path = tags(&container, "rootfile")
+        .find_map(|rootfile| attribute(rootfile, "full-path"))
+        .ok_or_else(|| anyhow!("epub without a package document"))?;
+    let package = read_entry(&mut zip, &package_path)?;
+
+    let mut items = HashMap::new();
+    let mut images = vec![];
+    for item in tags(&package, "item") {
+        let (id, href, media_type) = match (
+            attribute(item, "id"),
+            attribute(item, "href"),
+            attribute(item, "media-type"),
+        ) {
+            (Some(id), Some(href), Some(media_type)) => (id, href, media_type),
+            _ => continue,
+        };
+        let href = resolve_href(&package_path, &href);
+        if media_type.starts_with("image/") {
+            images.push(href.clone());
+        }
+        items.insert(id, (href, media_type));
+    }
+
+    let mut pages = vec![];
+    for itemref in tags(&package, "itemref") {
+        let (href, media_type) = match attribute(itemref, "idref").and_then(|id| items.get(&id)) {
+            Some(item) => item,
+            None => continue,
+        };
+        if media_type.starts_with("image/") {
+            pages.push(href.clone());
+            continue;
+        }
+
+        let document = match read_entry(&mut zip, href) {
+            Ok(document) => document,
+            Err(_) => continue,
+        };
+        let image = tags(&document, "img")
+            .find_map(|img| attribute(img, "src"))
+            .or_else(|| {
+                tags(&document, "image").find_map(|image| {
+                    attribute(image, "xlink:href").or_else(|| attribute(image, "href"))
+                })
+            });
+        if let Some(image) = image {
+            pages.push(resolve_href(href, &image));
+        }
+    }
+
+    if pages.is_empty() {
+        pages = images;
+    }
+    // consecutive documents showing the same image are a single page
+    pages.dedup();
+
+    Ok(pages)
+}
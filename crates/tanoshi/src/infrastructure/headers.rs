@@ -0,0 +1,112 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{anyhow, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::{domain::entities::source::HeaderOverride, infrastructure::proxy::host_of};
+
+pub fn validate(header_override: &HeaderOverride) -> Result<()> {
+    for (name, value) in header_override.to_headers() {
+        HeaderName::from_bytes(name.as_bytes()).map_err(|_| anyhow!("invalid header {name}"))?;
+        HeaderValue::from_str(&value).map_err(|_| anyhow!("invalid value of header {name}"))?;
+    }
+
+    Ok(())
+}
+
+fn find<'a>(
+    overrides: &'a HashMap<String, HeaderOverride>,
+    host: &str,
+) -> Option<&'a HeaderOverride> {
+    overrides.iter().find_map(|(domain, header_override)| {
+        (host == domain || host.ends_with(&format!(".{domain}"))).then(|| header_override)
+    })
+}
+
+/// Header overrides of sources, keyed by the host of the source url, and of domains set
+/// on their own, applied to requests tanoshi makes on behalf of a source such as images
+#[derive(Clone, Default)]
+pub struct HeaderTable {
+    sources: Arc<RwLock<HashMap<String, HeaderOverride>>>,
+    domains: Arc<RwLock<HashMap<String, HeaderOverride>>>,
+}
+
+impl HeaderTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send `header_override` on requests to the host of `source_url`, `None` removes it
+    pub fn set_source(&self, source_url: &str, header_override: Option<HeaderOverride>) {
+        if let Some(host) = host_of(source_url) {
+            Self::set(&self.sources, host, header_override);
+        }
+    }
+
+    /// Send `header_override` on requests to `domain`, `None` removes it
+    pub fn set_domain(&self, domain: &str, header_override: Option<HeaderOverride>) {
+        Self::set(&self.domains, domain.to_string(), header_override);
+    }
+
+    fn set(
+        table: &RwLock<HashMap<String, HeaderOverride>>,
+        host: String,
+        header_override: Option<HeaderOverride>,
+    ) {
+        if let Ok(mut table) = table.write() {
+            match header_override.filter(|header_override| !header_override.is_empty()) {
+                Some(header_override) => table.insert(host, header_override),
+                None => table.remove(&host),
+            };
+        }
+    }
+
+    /// Headers for a request to `url`, a domain override wins over the one of the source,
+    /// which is also matched against the referer as images are often on a cdn
+    pub fn resolve(&self, url: &str, referer: Option<&str>) -> HeaderMap {
+        let host = host_of(url);
+        let referer_host = referer.and_then(host_of);
+
+        let header_override = self
+            .domains
+            .read()
+            .ok()
+            .and_then(|domains| {
+                host.as_deref()
+                    .and_then(|host| find(&domains, host))
+                    .cloned()
+            })
+            .or_else(|| {
+                let sources = self.sources.read().ok()?;
+                host.as_deref()
+                    .and_then(|host| find(&sources, host))
+                    .or_else(|| {
+                        referer_host
+                            .as_deref()
+                            .and_then(|host| find(&sources, host))
+                    })
+                    .cloned()
+            });
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in header_override
+            .map(|header_override| header_override.to_headers())
+            .unwrap_or_default()
+        {
+            match (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(&value),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    headers.insert(name, value);
+                }
+                _ => warn!("invalid header override {name}"),
+            }
+        }
+
+        headers
+    }
+}
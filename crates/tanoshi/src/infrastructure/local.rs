@@ -1,4 +1,5 @@
 use std::{
+    cmp::Ordering,
     collections::HashMap,
     fs::{DirEntry, ReadDir},
     path::{Path, PathBuf},
@@ -9,19 +10,46 @@ use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use fancy_regex::Regex;
 use mime_guess::mime;
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use tanoshi_lib::prelude::{ChapterInfo, Extension, Input, Lang, MangaInfo, SourceInfo};
+use tanoshi_lib::prelude::{
+    Capabilities, ChapterInfo, Extension, Input, Lang, MangaInfo, SourceInfo,
+};
+
+use crate::infrastructure::{
+    comic_info::ParsedComicInfo,
+    config::{LocalFolder, LocalLayout},
+    epub,
+    page_store::{self, Manifest},
+    pdf,
+};
 
-// list of supported files, other archive may works but no tested
+// list of supported files, other archive may works but no tested.
+// rar and 7z are read by the libarchive bundled with compress-tools, which extracts
+// a single page by reading through the archive up to it instead of unpacking all of it.
+// pdf pages are rendered from the image on each of them, epub pages are the images
+// its documents show
 pub static SUPPORTED_FILES: phf::Set<&'static str> = phf::phf_set! {
     "cbz",
     "cbr",
-    "cb7"
+    "rar",
+    "cb7",
+    "7z",
+    "pdf",
+    "epub"
 };
 
+/// Whether `path` is an archive of a supported format, whatever the case of its extension
+pub fn is_supported_archive(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| SUPPORTED_FILES.contains(&ext.to_string_lossy().to_lowercase()))
+        .unwrap_or(false)
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub struct LocalMangaInfo {
     pub title: Option<String>,
+    pub alt_titles: Option<Vec<String>>,
     pub author: Option<Vec<String>>,
     pub genre: Option<Vec<String>>,
     pub status: Option<String>,
@@ -29,27 +57,223 @@ pub struct LocalMangaInfo {
     pub cover_path: Option<String>,
 }
 
+static CHAPTER_NUMBER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(?<=v)(\d+)|(?<=volume)\s*(\d+)|(?<=vol)\s*(\d+)|(?<=\s)(\d+)|(\d+)").unwrap()
+});
+static VOLUME_NUMBER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)(?:\bvol(?:ume)?\.?\s*|\bv)(\d+(?:\.\d+)?)").unwrap());
+
+/// Number `re` finds in `name`, from its first group that matched or else the whole match
+fn find_number(re: &Regex, name: &str) -> Option<Result<f64, std::num::ParseFloatError>> {
+    let captures = re.captures(name).ok()??;
+    let number = captures
+        .iter()
+        .skip(1)
+        .flatten()
+        .next()
+        .or_else(|| captures.get(0))?;
+
+    Some(number.as_str().trim().parse())
+}
+
+/// A folder of chapters in a series
+struct Volume {
+    name: String,
+    number: Option<f64>,
+}
+
 pub struct Local {
     id: i64,
     name: String,
     path: PathBuf,
+    layout: LocalLayout,
+    chapter_pattern: Option<Regex>,
+    volume_pattern: Option<Regex>,
+    exclude: Option<Regex>,
 }
 
 impl Local {
     pub fn new<P: AsRef<Path>>(id: i64, name: String, path: P) -> Self {
         let path = PathBuf::new().join(path);
-        Self { id, name, path }
+        Self {
+            id,
+            name,
+            path,
+            layout: LocalLayout::default(),
+            chapter_pattern: None,
+            volume_pattern: None,
+            exclude: None,
+        }
+    }
+
+    /// Local source of a configured folder, with its layout and patterns
+    pub fn from_folder(id: i64, folder: &LocalFolder) -> Result<Self> {
+        let compile = |pattern: &Option<String>| -> Result<Option<Regex>> {
+            pattern
+                .as_deref()
+                .map(Regex::new)
+                .transpose()
+                .map_err(|e| anyhow!("invalid pattern of local folder {}: {e}", folder.name))
+        };
+
+        Ok(Self::new(id, folder.name.clone(), &folder.path)
+            .with_layout(folder.layout)
+            .with_chapter_pattern(compile(&folder.chapter_pattern)?)
+            .with_volume_pattern(compile(&folder.volume_pattern)?)
+            .with_exclude(compile(&folder.exclude)?))
+    }
+
+    pub fn with_layout(self, layout: LocalLayout) -> Self {
+        Self { layout, ..self }
+    }
+
+    pub fn with_chapter_pattern(self, chapter_pattern: Option<Regex>) -> Self {
+        Self {
+            chapter_pattern,
+            ..self
+        }
+    }
+
+    pub fn with_volume_pattern(self, volume_pattern: Option<Regex>) -> Self {
+        Self {
+            volume_pattern,
+            ..self
+        }
+    }
+
+    pub fn with_exclude(self, exclude: Option<Regex>) -> Self {
+        Self { exclude, ..self }
+    }
+
+    fn is_excluded(&self, entry: &DirEntry) -> bool {
+        self.exclude
+            .as_ref()
+            .map(|exclude| {
+                exclude
+                    .is_match(&entry.file_name().to_string_lossy())
+                    .unwrap_or(false)
+            })
+            .unwrap_or(false)
+    }
+
+    /// Whether `path`, a folder in a series, holds chapters rather than pages
+    fn is_volume(&self, path: &Path) -> bool {
+        if !path.is_dir() {
+            return false;
+        }
+
+        match self.layout {
+            LocalLayout::Auto => !contains_images(path) && contains_supported_entries(path),
+            LocalLayout::Volumes => true,
+            LocalLayout::Chapters | LocalLayout::Oneshots => false,
+        }
+    }
+
+    /// Chapters in the folder of a series, along with the number of the volume they're in
+    fn find_chapters(
+        &self,
+        path: &Path,
+        volume: Option<&Volume>,
+    ) -> Vec<(Option<f64>, ChapterInfo)> {
+        let entries = match std::fs::read_dir(path) {
+            Ok(read_dir) => read_dir.filter_map(filter_supported_files_and_folders),
+            Err(_) => return vec![],
+        };
+
+        let mut chapters = vec![];
+        for entry in entries.filter(|entry| !self.is_excluded(entry)) {
+            let path = entry.path();
+            if volume.is_none() && self.is_volume(&path) {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let number = find_number(
+                    self.volume_pattern.as_ref().unwrap_or(&VOLUME_NUMBER),
+                    &name,
+                )
+                .and_then(Result::ok);
+                chapters.extend(self.find_chapters(&path, Some(&Volume { name, number })));
+            } else if let Some(chapter) = self.map_entry_to_chapter(&path, volume) {
+                chapters.push((volume.and_then(|volume| volume.number), chapter));
+            }
+        }
+
+        chapters
+    }
+
+    fn map_entry_to_chapter(&self, path: &Path, volume: Option<&Volume>) -> Option<ChapterInfo> {
+        let modified = match path
+            .metadata()
+            .ok()
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        {
+            Some(modified) => modified.as_secs(),
+            None => {
+                return None;
+            }
+        };
+        let file_name = path.file_stem()?.to_string_lossy().to_string();
+        let number = match find_number(
+            self.chapter_pattern.as_ref().unwrap_or(&CHAPTER_NUMBER),
+            &file_name,
+        ) {
+            Some(number) => number.unwrap_or(0_f64),
+            None => 10000_f64,
+        };
+
+        // ComicInfo.xml knows better than the file name
+        let info = find_comic_info(path).unwrap_or_default();
+        let number = info.number.unwrap_or(number);
+        let title = match (info.title, info.volume, volume) {
+            (Some(title), _, _) => title,
+            (None, Some(volume), _) => format!("Vol. {volume} Ch. {number}"),
+            (None, None, Some(volume)) => format!("{} - {file_name}", volume.name),
+            (None, None, None) => file_name,
+        };
+
+        Some(ChapterInfo {
+            source_id: self.id,
+            title,
+            path: format!("{}", path.display()),
+            number,
+            scanlator: info.scan_information,
+            groups: vec![],
+            language: info.language,
+            uploaded: modified as i64,
+        })
     }
 }
 fn default_cover_url() -> String {
     "/images/cover-placeholder.jpg".to_string()
 }
 
+pub fn is_image(path: &Path) -> bool {
+    mime_guess::from_path(path)
+        .first()
+        .map(|m| m.type_() == mime::IMAGE)
+        .unwrap_or(false)
+}
+
+fn contains_images(path: &Path) -> bool {
+    path.read_dir()
+        .map(|mut entries| {
+            entries.any(|entry| {
+                entry
+                    .map(|entry| entry.path().is_file() && is_image(&entry.path()))
+                    .unwrap_or(false)
+            })
+        })
+        .unwrap_or(false)
+}
+
+fn contains_supported_entries(path: &Path) -> bool {
+    path.read_dir()
+        .map(|mut entries| entries.any(|entry| filter_supported_files_and_folders(entry).is_some()))
+        .unwrap_or(false)
+}
+
 fn filter_supported_files_and_folders(entry: Result<DirEntry, std::io::Error>) -> Option<DirEntry> {
     let entry = entry.ok()?;
-    if entry.path().is_dir()
-        || SUPPORTED_FILES.contains(&entry.path().extension()?.to_string_lossy().to_string())
-    {
+    if entry.path().is_dir() || is_supported_archive(&entry.path()) {
         Some(entry)
     } else {
         None
@@ -58,6 +282,20 @@ fn filter_supported_files_and_folders(entry: Result<DirEntry, std::io::Error>) -
 
 // find first image from an archvie
 fn find_cover_from_archive(path: &Path) -> String {
+    if pdf::is_pdf(path) {
+        return pdf::list_pages(path)
+            .ok()
+            .and_then(|pages| pages.into_iter().next())
+            .map(|page| path.join(page).display().to_string())
+            .unwrap_or_else(default_cover_url);
+    }
+    if epub::is_epub(path) {
+        return get_pages_from_epub(path)
+            .ok()
+            .and_then(|pages| pages.into_iter().next())
+            .unwrap_or_else(default_cover_url);
+    }
+
     let source = match std::fs::File::open(path) {
         Ok(file) => file,
         Err(e) => {
@@ -66,24 +304,17 @@ fn find_cover_from_archive(path: &Path) -> String {
         }
     };
 
-    let mut cover_url = default_cover_url();
-    if let Ok(files) = compress_tools::list_archive_files(source) {
-        for file in files {
-            let file = PathBuf::from(&file);
-            let res = mime_guess::from_path(&file);
-            debug!("{} {:?}", file.display(), res.first());
-            if res
-                .first()
-                .map(|m| m.type_() == mime::IMAGE)
-                .unwrap_or(false)
-            {
-                cover_url = path.join(file).display().to_string();
-                break;
-            }
-        }
-    }
-
-    cover_url
+    // rar archives aren't necessarily listed in page order
+    compress_tools::list_archive_files(source)
+        .ok()
+        .and_then(|files| {
+            files
+                .into_iter()
+                .filter(|file| is_image(Path::new(file)))
+                .min_by(|a, b| human_sort::compare(a, b))
+        })
+        .map(|file| path.join(file).display().to_string())
+        .unwrap_or_else(default_cover_url)
 }
 
 // find first image from a directory
@@ -91,7 +322,7 @@ fn find_cover_from_dir(path: &Path) -> String {
     path.read_dir()
         .ok()
         .map(sort_dir)
-        .and_then(|dir| dir.into_iter().next())
+        .and_then(|dir| dir.into_iter().find(|entry| is_image(&entry.path())))
         .map(|entry| entry.path().display().to_string())
         .unwrap_or_else(default_cover_url)
 }
@@ -108,11 +339,47 @@ fn find_details_from_archive(path: &Path) -> Option<Vec<u8>> {
     None
 }
 
-// find first image from a directory
+// find details from a directory
 fn find_details_from_dir(path: &Path) -> Option<Vec<u8>> {
     std::fs::read(path.join("details.json")).ok()
 }
 
+// find ComicInfo.xml from an archive
+fn find_comic_info_from_archive(path: &Path) -> Option<Vec<u8>> {
+    let source = std::fs::File::open(path).ok()?;
+    let mut data = vec![];
+    compress_tools::uncompress_archive_file(source, &mut data, "ComicInfo.xml").ok()?;
+
+    Some(data)
+}
+
+fn find_comic_info(path: &Path) -> Option<ParsedComicInfo> {
+    let data = if path.is_dir() {
+        std::fs::read(path.join("ComicInfo.xml")).ok()?
+    } else if path.is_file() {
+        find_comic_info_from_archive(path)?
+    } else {
+        return None;
+    };
+
+    Some(ParsedComicInfo::parse(&String::from_utf8_lossy(&data)))
+}
+
+/// `ComicInfo.xml` describing a manga, its own or else the one of its first chapter
+fn find_series_comic_info(path: &Path) -> Option<ParsedComicInfo> {
+    if let Some(info) = find_comic_info(path) {
+        return Some(info);
+    }
+
+    let first = path
+        .read_dir()
+        .ok()?
+        .filter_map(filter_supported_files_and_folders)
+        .map(|entry| entry.path())
+        .min_by(|a, b| human_sort::compare(&a.display().to_string(), &b.display().to_string()))?;
+    find_comic_info(&first)
+}
+
 fn sort_dir(dir: ReadDir) -> Vec<DirEntry> {
     sort_read_dir_with_reverse(dir, false)
 }
@@ -140,6 +407,10 @@ fn find_cover_url(entry: &Path) -> String {
     if entry.is_file() {
         return find_cover_from_archive(entry);
     }
+    // a series of loose images is its own single chapter
+    if contains_images(entry) {
+        return find_cover_from_dir(entry);
+    }
 
     let entry_read_dir = match entry.read_dir() {
         Ok(entry_read_dir) => entry_read_dir,
@@ -158,7 +429,10 @@ fn find_cover_url(entry: &Path) -> String {
         }
     };
 
-    if path.is_dir() {
+    if path.is_dir() && !contains_images(&path) {
+        // a volume, its cover is the one of its first chapter
+        find_cover_url(&path)
+    } else if path.is_dir() {
         find_cover_from_dir(&path)
     } else if path.is_file() {
         find_cover_from_archive(&path)
@@ -178,17 +452,19 @@ fn find_details(path: &Path) -> Option<Vec<u8>> {
 }
 
 pub fn get_pages_from_archive(path: &Path) -> Result<Vec<String>, anyhow::Error> {
+    if pdf::is_pdf(path) {
+        return Ok(pdf::list_pages(path)?
+            .into_iter()
+            .map(|p| path.join(p).display().to_string())
+            .collect());
+    }
+
     let source = std::fs::File::open(path)?;
     match compress_tools::list_archive_files(source) {
         Ok(files) => {
             let pages = files
                 .into_iter()
-                .filter(|p| {
-                    mime_guess::from_path(&p)
-                        .first()
-                        .map(|m| m.type_() == mime::IMAGE)
-                        .unwrap_or(false)
-                })
+                .filter(|p| is_image(Path::new(p)))
                 .map(|p| path.join(p).display().to_string())
                 .collect();
             Ok(pages)
@@ -197,50 +473,47 @@ pub fn get_pages_from_archive(path: &Path) -> Result<Vec<String>, anyhow::Error>
     }
 }
 
+fn get_pages_from_epub(path: &Path) -> Result<Vec<String>, anyhow::Error> {
+    Ok(epub::list_pages(path)?
+        .into_iter()
+        .map(|p| path.join(p).display().to_string())
+        .collect())
+}
+
 fn get_pages_from_dir(path: &Path) -> Result<Vec<String>, anyhow::Error> {
     let pages = path
         .read_dir()?
         .into_iter()
         .filter_map(Result::ok)
-        .filter_map(|f| (f.path().is_file()).then(|| f.path().display().to_string()))
+        .filter_map(|f| {
+            (f.path().is_file() && is_image(&f.path())).then(|| f.path().display().to_string())
+        })
         .collect();
     Ok(pages)
 }
 
-fn map_entry_to_chapter(source_id: i64, path: &Path) -> Option<ChapterInfo> {
-    let modified = match path
-        .metadata()
-        .ok()
-        .and_then(|metadata| metadata.modified().ok())
-        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
-    {
-        Some(modified) => modified.as_secs(),
-        None => {
-            return None;
-        }
-    };
-    let number_re = match Regex::new(
-        r"(?i)(?<=v)(\d+)|(?<=volume)\s*(\d+)|(?<=vol)\s*(\d+)|(?<=\s)(\d+)|(\d+)",
-    ) {
-        Ok(re) => re,
-        Err(_) => {
-            return None;
-        }
-    };
-    let file_name = path.file_stem()?.to_string_lossy().to_string();
-    let number = match number_re.find(&file_name).ok().and_then(|m| m) {
-        Some(mat) => mat.as_str().parse().unwrap_or(0_f64),
-        None => 10000_f64,
+/// Pages of a chapter stored either as an archive or a folder of images, in reading order
+pub fn get_pages_from_path(path: &Path) -> Result<Vec<String>, anyhow::Error> {
+    // manifests are already in reading order, their page paths are hashes
+    if page_store::is_manifest(path) {
+        return Ok(Manifest::open(path)?.page_paths());
+    }
+    // epubs are in the order of their spine, whatever their images are named
+    if epub::is_epub(path) {
+        return get_pages_from_epub(path);
+    }
+
+    let mut pages = if path.is_dir() {
+        get_pages_from_dir(path)?
+    } else if path.is_file() {
+        get_pages_from_archive(path)?
+    } else {
+        return Err(anyhow!("filename neither file or dir"));
     };
 
-    Some(ChapterInfo {
-        source_id,
-        title: file_name,
-        path: format!("{}", path.display()),
-        number,
-        scanlator: None,
-        uploaded: modified as i64,
-    })
+    pages.sort_by(|a, b| human_sort::compare(a, b));
+
+    Ok(pages)
 }
 
 #[async_trait]
@@ -254,6 +527,13 @@ impl Extension for Local {
             icon: "/icons/192.png",
             languages: Lang::All,
             nsfw: false,
+            // listed in directory order whatever is asked
+            capabilities: Capabilities {
+                latest: false,
+                search_filters: false,
+                page_count: true,
+                login: false,
+            },
         }
     }
 
@@ -298,10 +578,11 @@ impl Extension for Local {
             }
         };
 
-        let mut data: Box<dyn Iterator<Item = _>> = Box::new(
+        let mut data: Box<dyn Iterator<Item = _> + '_> = Box::new(
             read_dir
                 .into_iter()
-                .filter_map(filter_supported_files_and_folders),
+                .filter_map(filter_supported_files_and_folders)
+                .filter(move |entry| !self.is_excluded(entry)),
         );
 
         if let Some(keyword) = query {
@@ -324,6 +605,7 @@ impl Extension for Local {
                     .file_stem()
                     .map(|s| s.to_string_lossy().to_string())
                     .unwrap_or_else(|| "".to_string()),
+                alt_titles: vec![],
                 author: vec![],
                 genre: vec![],
                 status: None,
@@ -349,6 +631,7 @@ impl Extension for Local {
         let mut manga = MangaInfo {
             source_id: id,
             title: title.clone(),
+            alt_titles: vec![],
             author: vec![],
             genre: vec![],
             status: Some("".to_string()),
@@ -357,12 +640,31 @@ impl Extension for Local {
             cover_url,
         };
 
+        if let Some(info) = find_series_comic_info(&path) {
+            if let Some(series) = info.series {
+                manga.title = series;
+            }
+            if !info.writer.is_empty() {
+                manga.author = info.writer;
+            }
+            if !info.genre.is_empty() {
+                manga.genre = info.genre;
+            }
+            if let Some(summary) = info.summary {
+                manga.description = Some(summary);
+            }
+        }
+
+        // details.json is written for tanoshi so it wins over ComicInfo.xml
         if let Some(info) = find_details(&path)
             .and_then(|object| serde_json::from_slice::<LocalMangaInfo>(&object).ok())
         {
             if let Some(title) = info.title {
                 manga.title = title;
             }
+            if let Some(alt_titles) = info.alt_titles {
+                manga.alt_titles = alt_titles;
+            }
             if let Some(cover_path) = info.cover_path {
                 manga.cover_url = path.join(cover_path).display().to_string();
             }
@@ -381,52 +683,39 @@ impl Extension for Local {
     }
 
     fn get_chapters(&self, path: String) -> Result<Vec<ChapterInfo>> {
-        let source_id = self.id;
         let path = PathBuf::from(path);
-        if path.is_file() {
-            if let Some(data) = map_entry_to_chapter(source_id, &path) {
+        if path.is_file()
+            || self.layout == LocalLayout::Oneshots
+            || (self.layout == LocalLayout::Auto
+                && contains_images(&path)
+                && !contains_supported_entries(&path))
+        {
+            if let Some(data) = self.map_entry_to_chapter(&path, None) {
                 return Ok(vec![data]);
             }
         }
 
-        let read_dir = match std::fs::read_dir(&path) {
-            Ok(read_dir) => read_dir,
-            Err(e) => {
-                return Err(anyhow!("{}", e));
-            }
-        };
-
-        let mut data: Vec<ChapterInfo> = read_dir
-            .into_iter()
-            .filter_map(filter_supported_files_and_folders)
-            .filter_map(|entry| map_entry_to_chapter(source_id, &entry.path()))
-            .collect();
+        if let Err(e) = std::fs::read_dir(&path) {
+            return Err(anyhow!("{}", e));
+        }
 
-        data.sort_by(|a, b| a.number.partial_cmp(&b.number).unwrap());
+        let mut data = self.find_chapters(&path, None);
+        // chapters in volumes come first, in the order of their volumes
+        data.sort_by(|(a_volume, a), (b_volume, b)| {
+            let a_volume = a_volume.unwrap_or(f64::MAX);
+            let b_volume = b_volume.unwrap_or(f64::MAX);
+            a_volume
+                .partial_cmp(&b_volume)
+                .unwrap_or(Ordering::Equal)
+                .then(a.number.partial_cmp(&b.number).unwrap_or(Ordering::Equal))
+        });
         data.reverse();
 
-        Ok(data)
+        Ok(data.into_iter().map(|(_, chapter)| chapter).collect())
     }
 
     fn get_pages(&self, filename: String) -> Result<Vec<String>> {
-        let path = PathBuf::from(filename);
-        let mut pages = if path.is_dir() {
-            match get_pages_from_dir(&path) {
-                Ok(pages) => pages,
-                Err(e) => return Err(anyhow!("{}", e)),
-            }
-        } else if path.is_file() {
-            match get_pages_from_archive(&path) {
-                Ok(pages) => pages,
-                Err(e) => return Err(anyhow!("{}", e)),
-            }
-        } else {
-            return Err(anyhow!("filename neither file or dir"));
-        };
-
-        pages.sort_by(|a, b| human_sort::compare(a, b));
-
-        Ok(pages)
+        get_pages_from_path(&PathBuf::from(filename))
     }
 }
 
@@ -877,4 +1166,68 @@ mod test {
             }
         }
     }
+
+    fn number(re: &Regex, name: &str) -> Option<f64> {
+        find_number(re, name).and_then(Result::ok)
+    }
+
+    #[test]
+    fn test_volume_number() {
+        assert_eq!(number(&VOLUME_NUMBER, "Vol. 2"), Some(2.0));
+        assert_eq!(number(&VOLUME_NUMBER, "Volume 1.5"), Some(1.5));
+        assert_eq!(number(&VOLUME_NUMBER, "Series v03"), Some(3.0));
+        assert_eq!(number(&VOLUME_NUMBER, "Extras"), None);
+    }
+
+    #[test]
+    fn test_chapter_number() {
+        assert_eq!(
+            number(&CHAPTER_NUMBER, "Space_Adventures_004__c2c__diff_ver"),
+            Some(4.0)
+        );
+        assert_eq!(number(&CHAPTER_NUMBER, "no number"), None);
+    }
+
+    #[test]
+    fn test_custom_number_pattern() {
+        // first group that matched, or the whole match without groups
+        let pattern = Regex::new(r"c(\d+)|ch\.?\s*(\d+)").unwrap();
+        assert_eq!(number(&pattern, "Series v01 c012"), Some(12.0));
+        assert_eq!(number(&pattern, "Series v01 ch. 7"), Some(7.0));
+        let pattern = Regex::new(r"\d+$").unwrap();
+        assert_eq!(number(&pattern, "Series 2 - 15"), Some(15.0));
+    }
+
+    #[test]
+    fn test_from_folder_invalid_pattern() {
+        let folder = LocalFolder {
+            name: "Local".to_string(),
+            path: "../../test/data/manga".to_string(),
+            id: None,
+            layout: LocalLayout::Auto,
+            chapter_pattern: Some("(".to_string()),
+            volume_pattern: None,
+            exclude: None,
+        };
+        assert!(Local::from_folder(10000, &folder).is_err());
+    }
+
+    #[test]
+    fn test_is_volume() {
+        let series = Path::new("../../test/data/manga").join("Space Adventures");
+        let folder = series.join("Space_Adventures_004__c2c__diff_ver");
+        let archive = series.join("Space_Adventures_001__c2c__diff_ver.cbz");
+        let local = |layout| {
+            Local::new(1, "Local".to_string(), "../../test/data/manga").with_layout(layout)
+        };
+
+        // a folder of images is a chapter unless every folder is a volume
+        assert!(!local(LocalLayout::Auto).is_volume(&folder));
+        assert!(local(LocalLayout::Volumes).is_volume(&folder));
+        assert!(!local(LocalLayout::Chapters).is_volume(&folder));
+        assert!(!local(LocalLayout::Oneshots).is_volume(&folder));
+        // a folder of archives is a volume
+        assert!(local(LocalLayout::Auto).is_volume(&series));
+        assert!(!local(LocalLayout::Volumes).is_volume(&archive));
+    }
 }
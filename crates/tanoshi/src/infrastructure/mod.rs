@@ -1,6 +1,22 @@
 pub mod auth;
+pub mod bandwidth;
+pub mod comic_info;
 pub mod config;
+pub mod covers;
+pub mod crypto;
 pub mod database;
 pub mod domain;
+pub mod epub;
+pub mod headers;
 pub mod local;
 pub mod notification;
+pub mod optimize;
+pub mod page_store;
+pub mod path_template;
+pub mod pdf;
+pub mod proxy;
+pub mod remote_library;
+pub mod repository_index;
+pub mod source_cache;
+pub mod storage;
+pub mod telemetry;
@@ -1,15 +1,40 @@
-use crate::domain::repositories::user::UserRepository;
-use tanoshi_notifier::{gotify::Gotify, pushover::Pushover, telegram::Telegram, Notifier};
+use crate::{
+    domain::{
+        entities::{
+            automation::NotifyChannel,
+            inbox::{ChapterDigestEntry, InboxKind},
+            user::User,
+        },
+        repositories::{inbox::InboxRepository, user::UserRepository},
+    },
+    infrastructure::{
+        config::NotificationTemplateConfig, domain::repositories::inbox::InboxRepositoryImpl,
+    },
+};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tanoshi_notifier::{
+    gotify::Gotify,
+    pushover::{Pushover, PushoverOptions},
+    smtp::Smtp,
+    telegram::Telegram,
+    Notifier,
+};
 
 pub struct Builder<R>
 where
     R: UserRepository,
 {
     user_repo: R,
+    inbox: Option<InboxRepositoryImpl>,
     pushover: Option<Pushover>,
     telegram: Option<Telegram>,
     gotify: Option<Gotify>,
+    smtp: Option<Smtp>,
     base_url: Option<String>,
+    templates: NotificationTemplateConfig,
 }
 
 impl<R> Builder<R>
@@ -19,10 +44,20 @@ where
     pub fn new(user_repo: R) -> Self {
         Self {
             user_repo,
+            inbox: None,
             pushover: None,
             telegram: None,
             gotify: None,
+            smtp: None,
             base_url: None,
+            templates: NotificationTemplateConfig::default(),
+        }
+    }
+
+    pub fn inbox(self, inbox: InboxRepositoryImpl) -> Self {
+        Self {
+            inbox: Some(inbox),
+            ..self
         }
     }
 
@@ -47,6 +82,13 @@ where
         }
     }
 
+    pub fn smtp(self, smtp: Smtp) -> Self {
+        Self {
+            smtp: Some(smtp),
+            ..self
+        }
+    }
+
     pub fn base_url(self, base_url: String) -> Self {
         Self {
             base_url: Some(base_url),
@@ -54,13 +96,20 @@ where
         }
     }
 
+    pub fn templates(self, templates: NotificationTemplateConfig) -> Self {
+        Self { templates, ..self }
+    }
+
     pub fn finish(self) -> Notification<R> {
         Notification {
             user_repo: self.user_repo,
+            inbox: self.inbox,
             telegram: self.telegram,
             pushover: self.pushover,
             gotify: self.gotify,
+            smtp: self.smtp,
             base_url: self.base_url,
+            templates: self.templates,
         }
     }
 }
@@ -71,26 +120,176 @@ where
     R: UserRepository,
 {
     user_repo: R,
+    inbox: Option<InboxRepositoryImpl>,
     pushover: Option<Pushover>,
     telegram: Option<Telegram>,
     base_url: Option<String>,
     gotify: Option<Gotify>,
+    smtp: Option<Smtp>,
+    templates: NotificationTemplateConfig,
+}
+
+/// Replace every `{{name}}` placeholder in `template` with its value from `vars`,
+/// unknown placeholders are rendered as empty string.
+pub fn render_template(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        match rest[start + 2..].find("}}") {
+            Some(end) => {
+                let key = rest[start + 2..start + 2 + end].trim();
+                if let Some((_, value)) = vars.iter().find(|(name, _)| *name == key) {
+                    rendered.push_str(value);
+                }
+                rest = &rest[start + 2 + end + 2..];
+            }
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    rendered.push_str(rest);
+
+    rendered
+}
+
+/// Drops repeated alerts for the same key until `cooldown` has passed,
+/// so a broken source doesn't flood admins every retry.
+pub struct AlertThrottle {
+    cooldown: Duration,
+    last_sent: HashMap<String, Instant>,
+}
+
+impl AlertThrottle {
+    pub fn new(cooldown: Duration) -> Self {
+        Self {
+            cooldown,
+            last_sent: HashMap::new(),
+        }
+    }
+
+    pub fn allow(&mut self, key: &str) -> bool {
+        match self.last_sent.get(key) {
+            Some(last_sent) if last_sent.elapsed() < self.cooldown => false,
+            _ => {
+                self.last_sent.insert(key.to_string(), Instant::now());
+                true
+            }
+        }
+    }
+}
+
+/// First line of an error, capped so provider limits aren't hit
+pub fn summarize_error(e: &anyhow::Error) -> String {
+    let summary = e.to_string();
+    let summary = summary.lines().next().unwrap_or_default();
+    if summary.chars().count() > 200 {
+        format!("{}...", summary.chars().take(200).collect::<String>())
+    } else {
+        summary.to_string()
+    }
+}
+
+/// Picks which of the user's pushover priorities applies to a message
+#[derive(Debug, Clone, Copy)]
+enum Urgency {
+    Normal,
+    Chapter,
+    Alert,
+}
+
+impl From<InboxKind> for Urgency {
+    fn from(kind: InboxKind) -> Self {
+        match kind {
+            InboxKind::Chapter => Urgency::Chapter,
+            InboxKind::Download | InboxKind::Alert => Urgency::Alert,
+            InboxKind::Announcement => Urgency::Normal,
+        }
+    }
+}
+
+fn pushover_options(user: &User, urgency: Urgency) -> PushoverOptions {
+    PushoverOptions {
+        priority: match urgency {
+            Urgency::Normal => 0,
+            Urgency::Chapter => user.pushover_chapter_priority,
+            Urgency::Alert => user.pushover_alert_priority,
+        },
+        sound: user.pushover_sound.clone(),
+        device: user.pushover_device.clone(),
+    }
+}
+
+/// Whether `user` wants new chapter notifications for a manga in `category_ids`,
+/// an empty `category_ids` means the manga is in the default category.
+pub fn wants_chapter_notification(user: &User, category_ids: &[i64]) -> bool {
+    match user.notify_chapter_category_ids.as_ref() {
+        None => true,
+        Some(allowed) if category_ids.is_empty() => allowed.contains(&0),
+        Some(allowed) => category_ids.iter().any(|id| allowed.contains(id)),
+    }
 }
 
 impl<R> Notification<R>
 where
     R: UserRepository,
 {
+    /// Keep a copy in the user's inbox, failure is only logged so providers still get it
+    async fn store_in_inbox(
+        &self,
+        user_id: i64,
+        kind: InboxKind,
+        title: Option<&str>,
+        body: &str,
+        url: Option<&str>,
+    ) {
+        if let Some(inbox) = self.inbox.as_ref() {
+            if let Err(e) = inbox
+                .insert_notification(user_id, kind, title, body, url)
+                .await
+            {
+                error!("failed to store notification for user {user_id}: {e}");
+            }
+        }
+    }
+
+    pub async fn add_to_admins_inbox(
+        &self,
+        kind: InboxKind,
+        title: Option<&str>,
+        body: &str,
+        url: Option<&str>,
+    ) -> Result<(), anyhow::Error> {
+        let admins = self.user_repo.get_admins().await?;
+        for user in admins {
+            self.store_in_inbox(user.id, kind, title, body, url).await;
+        }
+
+        Ok(())
+    }
+
     pub async fn send_all_to_user(
         &self,
         user_id: i64,
+        kind: InboxKind,
         title: Option<String>,
         body: &str,
     ) -> Result<(), anyhow::Error> {
+        self.store_in_inbox(user_id, kind, title.as_deref(), body, None)
+            .await;
+
+        let urgency = Urgency::from(kind);
         let user = self.user_repo.get_user_by_id(user_id).await?;
-        if let Some(user_key) = user.pushover_user_key {
+        if let Some(user_key) = user.pushover_user_key.as_deref() {
             let _ = self
-                .send_message_to_pushover(&user_key, title.clone(), body)
+                .send_message_to_pushover_with_options(
+                    user_key,
+                    title.clone(),
+                    body,
+                    &pushover_options(&user, urgency),
+                )
                 .await;
         }
         if let Some(chat_id) = user.telegram_chat_id {
@@ -105,66 +304,178 @@ where
         Ok(())
     }
 
+    pub async fn send_to_user_channel(
+        &self,
+        user_id: i64,
+        channel: NotifyChannel,
+        title: Option<String>,
+        body: &str,
+    ) -> Result<(), anyhow::Error> {
+        let user = self.user_repo.get_user_by_id(user_id).await?;
+        match channel {
+            NotifyChannel::Telegram => {
+                let chat_id = user
+                    .telegram_chat_id
+                    .ok_or_else(|| anyhow::anyhow!("telegram chat id is not set"))?;
+                let message = match title {
+                    Some(title) => format!("<b>{title}</b>\n{body}"),
+                    None => body.to_string(),
+                };
+                self.send_message_to_telegram(chat_id, &message).await
+            }
+            NotifyChannel::Pushover => {
+                let user_key = user
+                    .pushover_user_key
+                    .as_deref()
+                    .ok_or_else(|| anyhow::anyhow!("pushover user key is not set"))?;
+                self.send_message_to_pushover_with_options(
+                    user_key,
+                    title,
+                    body,
+                    &pushover_options(&user, Urgency::Normal),
+                )
+                .await
+            }
+            NotifyChannel::Gotify => {
+                let token = user
+                    .gotify_token
+                    .ok_or_else(|| anyhow::anyhow!("gotify token is not set"))?;
+                self.send_message_to_gotify(&token, title, body).await
+            }
+        }
+    }
+
     pub async fn send_all_to_admins(
         &self,
         title: Option<String>,
         body: &str,
     ) -> Result<(), anyhow::Error> {
+        let body = render_template(
+            &self.templates.admin_body,
+            &[
+                ("title", title.as_deref().unwrap_or_default()),
+                ("message", body),
+                ("url", self.base_url.as_deref().unwrap_or_default()),
+            ],
+        );
+
         let admins = self.user_repo.get_admins().await?;
         for user in admins {
-            let _ = self.send_all_to_user(user.id, title.clone(), body).await;
+            let _ = self
+                .send_all_to_user(user.id, InboxKind::Alert, title.clone(), &body)
+                .await;
+        }
+
+        Ok(())
+    }
+
+    pub async fn send_download_failure_to_admins(
+        &self,
+        title: Option<String>,
+        body: &str,
+    ) -> Result<(), anyhow::Error> {
+        let admins = self.user_repo.get_admins().await?;
+        for user in admins.iter().filter(|user| user.notify_download_failures) {
+            let _ = self
+                .send_all_to_user(user.id, InboxKind::Download, title.clone(), body)
+                .await;
         }
 
         Ok(())
     }
 
+    pub async fn send_announcement(&self, message: &str) -> Result<(), anyhow::Error> {
+        let users = self.user_repo.get_users().await?;
+        for user in users.iter().filter(|user| user.notify_announcements) {
+            let _ = self
+                .send_all_to_user(
+                    user.id,
+                    InboxKind::Announcement,
+                    Some("Announcement".to_string()),
+                    message,
+                )
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// Title, body and link of a new chapter message, rendered with the chapter templates
+    fn render_chapter_message(
+        &self,
+        source_name: &str,
+        manga_title: &str,
+        chapter_title: &str,
+        chapter_id: i64,
+    ) -> (String, String, Option<String>) {
+        let url = self
+            .base_url
+            .as_ref()
+            .map(|base_url| format!("{base_url}/chapter/{chapter_id}"));
+
+        let vars = [
+            ("manga_title", manga_title),
+            ("chapter_title", chapter_title),
+            ("source", source_name),
+            ("url", url.as_deref().unwrap_or_default()),
+        ];
+        let title = render_template(&self.templates.chapter_title, &vars);
+        let body = render_template(&self.templates.chapter_body, &vars);
+
+        (title, body, url)
+    }
+
     pub async fn send_chapter_notification(
         &self,
         user_id: i64,
+        category_ids: &[i64],
+        source_name: &str,
         manga_title: &str,
         chapter_title: &str,
         chapter_id: i64,
     ) -> Result<(), anyhow::Error> {
         let user = self.user_repo.get_user_by_id(user_id).await?;
+        if !wants_chapter_notification(&user, category_ids) {
+            return Ok(());
+        }
 
-        let url = self
-            .base_url
-            .as_ref()
-            .map(|base_url| format!("{base_url}/chapter/{chapter_id}"));
+        let (title, body, url) =
+            self.render_chapter_message(source_name, manga_title, chapter_title, chapter_id);
 
-        if let Some((user_key, pushover)) = user.pushover_user_key.zip(self.pushover.as_ref()) {
-            if let Some(url) = &url {
-                pushover
-                    .send_notification_with_title_and_url(
-                        &user_key,
-                        manga_title,
-                        chapter_title,
-                        url,
-                        "Read",
-                    )
-                    .await?;
-            } else {
-                pushover
-                    .send_notification_with_title(&user_key, manga_title, chapter_title)
-                    .await?;
-            }
+        self.store_in_inbox(
+            user_id,
+            InboxKind::Chapter,
+            Some(&title),
+            &body,
+            url.as_deref(),
+        )
+        .await;
+
+        if let Some((user_key, pushover)) = user
+            .pushover_user_key
+            .as_deref()
+            .zip(self.pushover.as_ref())
+        {
+            pushover
+                .send_notification_with_options(
+                    user_key,
+                    Some(&title),
+                    &body,
+                    url.as_deref().map(|url| (url, "Read")),
+                    &pushover_options(&user, Urgency::Chapter),
+                )
+                .await?;
         }
 
         if let Some((chat_id, telegram)) = user.telegram_chat_id.zip(self.telegram.as_ref()) {
             let chat_id = &format!("{chat_id}");
             if let Some(url) = &url {
                 telegram
-                    .send_notification_with_title_and_url(
-                        chat_id,
-                        manga_title,
-                        chapter_title,
-                        url,
-                        "Read",
-                    )
+                    .send_notification_with_title_and_url(chat_id, &title, &body, url, "Read")
                     .await?;
             } else {
                 telegram
-                    .send_notification_with_title(chat_id, manga_title, chapter_title)
+                    .send_notification_with_title(chat_id, &title, &body)
                     .await?;
             }
         }
@@ -172,17 +483,11 @@ where
         if let Some((token, gotify)) = user.gotify_token.zip(self.gotify.as_ref()) {
             if let Some(url) = &url {
                 gotify
-                    .send_notification_with_title_and_url(
-                        &token,
-                        manga_title,
-                        chapter_title,
-                        url,
-                        "Read",
-                    )
+                    .send_notification_with_title_and_url(&token, &title, &body, url, "Read")
                     .await?;
             } else {
                 gotify
-                    .send_notification_with_title(&token, manga_title, chapter_title)
+                    .send_notification_with_title(&token, &title, &body)
                     .await?;
             }
         }
@@ -190,6 +495,121 @@ where
         Ok(())
     }
 
+    /// Keep a new chapter for the next digest of the user, stored so a restart doesn't drop it
+    pub async fn queue_chapter_digest(
+        &self,
+        user_id: i64,
+        chapter_id: i64,
+    ) -> Result<(), anyhow::Error> {
+        self.inbox
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("inbox not set"))?
+            .insert_digest_entry(user_id, chapter_id)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Send digests of users whose oldest queued chapter waited their digest interval,
+    /// or right away for users who since turned digests off
+    pub async fn send_due_chapter_digests(&self) -> Result<(), anyhow::Error> {
+        let inbox = match self.inbox.as_ref() {
+            Some(inbox) => inbox,
+            None => return Ok(()),
+        };
+
+        let mut queued: Vec<(i64, Vec<ChapterDigestEntry>)> = vec![];
+        for entry in inbox.get_digest_entries().await? {
+            match queued
+                .iter_mut()
+                .find(|(user_id, _)| *user_id == entry.user_id)
+            {
+                Some((_, entries)) => entries.push(entry),
+                None => queued.push((entry.user_id, vec![entry])),
+            }
+        }
+
+        let now = chrono::Utc::now().naive_utc();
+        for (user_id, entries) in queued {
+            let interval = match self.user_repo.get_user_by_id(user_id).await {
+                Ok(user) => user.notification_digest_interval.unwrap_or(0),
+                Err(e) => {
+                    error!("failed to get user {user_id} for chapter digest: {e}");
+                    continue;
+                }
+            };
+            if (now - entries[0].created_at).num_seconds() < interval {
+                continue;
+            }
+
+            // left queued for the next attempt when it can't be sent
+            if let Err(e) = self.send_chapter_digest(user_id, &entries).await {
+                error!("failed to send chapter digest to user {user_id}: {e}");
+                continue;
+            }
+            let ids: Vec<i64> = entries.iter().map(|entry| entry.id).collect();
+            inbox.delete_digest_entries(&ids).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Send one message summarizing queued chapters, grouped by manga
+    async fn send_chapter_digest(
+        &self,
+        user_id: i64,
+        entries: &[ChapterDigestEntry],
+    ) -> Result<(), anyhow::Error> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut grouped: Vec<(&str, Vec<&str>)> = vec![];
+        for entry in entries {
+            match grouped
+                .iter_mut()
+                .find(|(manga_title, _)| *manga_title == entry.manga_title)
+            {
+                Some((_, chapters)) => chapters.push(&entry.chapter_title),
+                None => grouped.push((&entry.manga_title, vec![&entry.chapter_title])),
+            }
+        }
+
+        let chapters = grouped
+            .iter()
+            .map(|(manga_title, chapters)| {
+                format!(
+                    "{manga_title}\n{}",
+                    chapters
+                        .iter()
+                        .map(|chapter_title| format!("- {chapter_title}"))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let url = self
+            .base_url
+            .as_ref()
+            .map(|base_url| format!("{base_url}/updates"));
+        let count = entries.len().to_string();
+
+        let vars = [
+            ("count", count.as_str()),
+            ("chapters", chapters.as_str()),
+            ("url", url.as_deref().unwrap_or_default()),
+        ];
+        let title = render_template(&self.templates.digest_title, &vars);
+        // an empty url leaves blank lines behind in the default template
+        let body = render_template(&self.templates.digest_body, &vars)
+            .trim()
+            .to_string();
+
+        self.send_all_to_user(user_id, InboxKind::Chapter, Some(title), &body)
+            .await
+    }
+
     pub async fn send_message_to_telegram(
         &self,
         chat_id: i64,
@@ -224,6 +644,22 @@ where
         Ok(())
     }
 
+    pub async fn send_message_to_pushover_with_options(
+        &self,
+        user_key: &str,
+        title: Option<String>,
+        body: &str,
+        options: &PushoverOptions,
+    ) -> Result<(), anyhow::Error> {
+        self.pushover
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("pushover not set"))?
+            .send_notification_with_options(user_key, title.as_deref(), body, None, options)
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn send_message_to_gotify(
         &self,
         token: &str,
@@ -245,6 +681,43 @@ where
         Ok(())
     }
 
+    /// Email a file to the device address of a user, e.g. a kindle's send to kindle address
+    pub async fn send_file_to_device(
+        &self,
+        user_id: i64,
+        filename: &str,
+        content_type: &str,
+        data: Vec<u8>,
+    ) -> Result<(), anyhow::Error> {
+        let smtp = self
+            .smtp
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("smtp not set"))?;
+        let to = self
+            .user_repo
+            .get_user_by_id(user_id)
+            .await?
+            .device_email
+            .ok_or_else(|| anyhow::anyhow!("device email is not set"))?;
+
+        smtp.send_attachment(&to, filename, filename, content_type, data)
+            .await
+    }
+
+    #[cfg(feature = "desktop")]
+    pub fn send_chapter_desktop_notification(
+        &self,
+        source_name: &str,
+        manga_title: &str,
+        chapter_title: &str,
+        chapter_id: i64,
+    ) -> Result<(), anyhow::Error> {
+        let (title, body, _) =
+            self.render_chapter_message(source_name, manga_title, chapter_title, chapter_id);
+
+        self.send_desktop_notification(Some(title), &body)
+    }
+
     #[cfg(feature = "desktop")]
     pub fn send_desktop_notification(
         &self,
@@ -261,3 +734,25 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let vars = [("manga_title", "One Piece"), ("chapter_title", "Ch. 1000")];
+
+        assert_eq!(
+            render_template("{{manga_title}} - {{ chapter_title }}", &vars),
+            "One Piece - Ch. 1000"
+        );
+        assert_eq!(render_template("no placeholder", &vars), "no placeholder");
+        assert_eq!(render_template("{{unknown}}!", &vars), "!");
+        assert_eq!(
+            render_template("{{manga_title}} {{chapter_title", &vars),
+            "One Piece {{chapter_title"
+        );
+        assert_eq!(render_template("{}{{manga_title}}}", &vars), "{}One Piece}");
+    }
+}
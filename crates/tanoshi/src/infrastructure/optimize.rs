@@ -0,0 +1,157 @@
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use image::{
+    codecs::{
+        avif::AvifEncoder,
+        webp::{WebPEncoder, WebPQuality},
+    },
+    ColorType, DynamicImage, GenericImageView, ImageEncoder,
+};
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use super::{
+    config::{ArchiveOptimizeConfig, OptimizeFormat},
+    local::is_image,
+};
+
+/// 1 is the slowest and smallest, 10 the fastest
+const AVIF_SPEED: u8 = 6;
+
+/// Bytes an archive took before and after its pages were re-encoded
+#[derive(Debug, Clone, Copy)]
+pub struct Optimized {
+    pub before: u64,
+    pub after: u64,
+}
+
+impl Optimized {
+    pub fn saved(&self) -> u64 {
+        self.before.saturating_sub(self.after)
+    }
+}
+
+/// Only zip archives are rewritten, the other formats can be read but not written
+pub fn is_optimizable(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| matches!(extension.to_lowercase().as_str(), "cbz" | "zip"))
+            .unwrap_or(false)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path: OsString = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+/// `image` encoded lossy as `format`, `quality` is from 1 to 100
+pub fn encode(image: &DynamicImage, format: OptimizeFormat, quality: u8) -> Result<Vec<u8>> {
+    let (width, height) = image.dimensions();
+    let (pixels, color) = if image.color().has_alpha() {
+        (image.to_rgba8().into_raw(), ColorType::Rgba8)
+    } else {
+        (image.to_rgb8().into_raw(), ColorType::Rgb8)
+    };
+
+    let mut data = vec![];
+    match format {
+        OptimizeFormat::Webp => {
+            WebPEncoder::new_with_quality(&mut data, WebPQuality::lossy(quality))
+                .write_image(&pixels, width, height, color)?
+        }
+        OptimizeFormat::Avif => AvifEncoder::new_with_speed_quality(&mut data, AVIF_SPEED, quality)
+            .write_image(&pixels, width, height, color)?,
+    }
+
+    Ok(data)
+}
+
+/// Page `i` encoded as `config.format` with its new name, `None` when it's kept as it is
+fn convert_page(
+    zip: &mut ZipArchive<File>,
+    i: usize,
+    names: &HashSet<String>,
+    config: &ArchiveOptimizeConfig,
+) -> Result<Option<(String, Vec<u8>)>> {
+    let mut entry = zip.by_index(i)?;
+    let name = entry.name().to_string();
+    let extension = config.format.extension();
+    if !entry.is_file() || !is_image(Path::new(&name)) {
+        return Ok(None);
+    }
+
+    let target = match name.rsplit_once('.') {
+        Some((_, current)) if current.eq_ignore_ascii_case(extension) => return Ok(None),
+        Some((stem, _)) => format!("{stem}.{extension}"),
+        None => return Ok(None),
+    };
+    // another page already has the name, e.g. both 001.jpg and 001.png
+    if names.contains(&target) {
+        return Ok(None);
+    }
+
+    let mut data = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut data)?;
+    let image = match image::load_from_memory(&data) {
+        Ok(image) => image,
+        Err(_) => return Ok(None),
+    };
+
+    match encode(&image, config.format, config.quality.clamp(1, 100)) {
+        Ok(encoded) if encoded.len() < data.len() => Ok(Some((target, encoded))),
+        Ok(_) => Ok(None),
+        Err(e) => {
+            debug!("failed to encode {name}, reason {e}");
+            Ok(None)
+        }
+    }
+}
+
+/// Rewrite the archive at `path` with its pages re-encoded as `config.format`, the original
+/// is renamed to `{name}.bak` when backups are kept. `None` if nothing got smaller, the
+/// archive is left as it was then
+pub fn optimize_archive(path: &Path, config: &ArchiveOptimizeConfig) -> Result<Option<Optimized>> {
+    let before = path.metadata()?.len();
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+    let names: HashSet<String> = zip.file_names().map(str::to_string).collect();
+
+    let part_path = with_suffix(path, ".part");
+    let mut writer = ZipWriter::new(File::create(&part_path)?);
+    let mut converted = 0;
+    for i in 0..zip.len() {
+        match convert_page(&mut zip, i, &names, config)? {
+            Some((name, data)) => {
+                writer.start_file(
+                    name,
+                    FileOptions::default().compression_method(CompressionMethod::Stored),
+                )?;
+                writer.write_all(&data)?;
+                converted += 1;
+            }
+            None => writer.raw_copy_file(zip.by_index_raw(i)?)?,
+        }
+    }
+    writer.finish()?;
+
+    let after = part_path.metadata()?.len();
+    if converted == 0 || after >= before {
+        std::fs::remove_file(part_path)?;
+        return Ok(None);
+    }
+
+    if config.keep_backups {
+        std::fs::rename(path, with_suffix(path, ".bak"))?;
+    }
+    std::fs::rename(part_path, path)?;
+
+    Ok(Some(Optimized { before, after }))
+}
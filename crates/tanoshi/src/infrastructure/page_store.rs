@@ -0,0 +1,150 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// extension of chapter manifests
+pub const MANIFEST_EXTENSION: &str = "pages";
+/// directory in the download directory pages are kept in
+const STORE_DIR: &str = ".pages";
+
+pub fn is_manifest(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension == MANIFEST_EXTENSION)
+        .unwrap_or(false)
+}
+
+/// Write `contents` so `path` is either complete or untouched
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut part_path = path.as_os_str().to_owned();
+    part_path.push(".part");
+    std::fs::write(&part_path, contents)?;
+    std::fs::rename(part_path, path)?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ManifestPage {
+    /// file name the page was downloaded as
+    pub name: String,
+    /// where the page is in the store, e.g. `ab/ab12...ef.jpg`
+    pub blob: String,
+}
+
+/// Pages of a chapter in reading order, stored once by content in `store`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Manifest {
+    pub store: PathBuf,
+    pub pages: Vec<ManifestPage>,
+}
+
+impl Manifest {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, &serde_json::to_vec(self)?)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.pages.iter().any(|page| page.name == name)
+    }
+
+    pub fn blob_path(&self, page: &ManifestPage) -> PathBuf {
+        self.store.join(&page.blob)
+    }
+
+    /// Paths of every page in reading order
+    pub fn page_paths(&self) -> Vec<String> {
+        self.pages
+            .iter()
+            .map(|page| self.blob_path(page).display().to_string())
+            .collect()
+    }
+}
+
+/// Downloaded pages addressed by their sha256, so a page shared by chapters,
+/// e.g. a credits page or the same release from two sources, is only kept once
+#[derive(Debug, Clone)]
+pub struct PageStore {
+    dir: PathBuf,
+}
+
+impl PageStore {
+    pub fn new<P: AsRef<Path>>(download_path: P) -> Self {
+        Self {
+            dir: download_path.as_ref().join(STORE_DIR),
+        }
+    }
+
+    /// Store a page unless identical content is already there, returns its blob
+    pub fn insert(&self, name: &str, contents: &[u8]) -> Result<String> {
+        let hash = hex::encode(Sha256::digest(contents));
+        let extension = Path::new(name)
+            .extension()
+            .map(|extension| format!(".{}", extension.to_string_lossy().to_lowercase()))
+            .unwrap_or_default();
+        let blob = format!("{}/{hash}{extension}", &hash[..2]);
+
+        let path = self.dir.join(&blob);
+        if !path.is_file() {
+            write_atomic(&path, contents)?;
+        }
+
+        Ok(blob)
+    }
+
+    /// Manifest of `pages`, sorted into reading order by name
+    pub fn manifest(&self, mut pages: Vec<ManifestPage>) -> Manifest {
+        pages.sort_by(|a, b| human_sort::compare(&a.name, &b.name));
+
+        Manifest {
+            store: self.dir.clone(),
+            pages,
+        }
+    }
+
+    /// Remove stored pages not in `referenced`, returns bytes freed
+    pub fn collect_garbage(&self, referenced: &HashSet<String>) -> Result<u64> {
+        let prefixes = match self.dir.read_dir() {
+            Ok(prefixes) => prefixes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut freed = 0;
+        for prefix in prefixes.filter_map(Result::ok) {
+            if !prefix.path().is_dir() {
+                continue;
+            }
+            for entry in prefix.path().read_dir()?.filter_map(Result::ok) {
+                let blob = format!(
+                    "{}/{}",
+                    prefix.file_name().to_string_lossy(),
+                    entry.file_name().to_string_lossy()
+                );
+                if referenced.contains(&blob) {
+                    continue;
+                }
+
+                freed += entry.metadata().map(|m| m.len()).unwrap_or_default();
+                std::fs::remove_file(entry.path())?;
+            }
+            // only succeeds once every page under the prefix is gone
+            let _ = std::fs::remove_dir(prefix.path());
+        }
+
+        Ok(freed)
+    }
+}
@@ -0,0 +1,291 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::infrastructure::config::DownloadLayoutConfig;
+
+/// characters not allowed in file names on at least one platform
+const RESERVED: &[char] = &['\\', '/', ':', '*', '?', '"', '<', '>', '|'];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Source,
+    Category,
+    Manga,
+    Chapter,
+    /// chapter number, integer part zero padded to the width
+    Number(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Token(Token),
+}
+
+/// Values substituted into a template for one chapter
+pub struct PathValues<'a> {
+    pub source: &'a str,
+    /// first category of the manga by name, if it's in any
+    pub category: Option<&'a str>,
+    pub manga: &'a str,
+    /// number and title as shown in the queue, e.g. `12 - The End`
+    pub chapter: &'a str,
+    pub number: f64,
+}
+
+/// Where downloaded chapters go relative to the download directory,
+/// parsed from templates like `{source}/{manga}/{chapter}`
+#[derive(Debug, Clone)]
+pub struct PathTemplate {
+    components: Vec<Vec<Part>>,
+    replacement: String,
+    max_length: usize,
+}
+
+fn parse_token(name: &str) -> Result<Token> {
+    let token = match name.split_once(':') {
+        Some(("number", width)) => Token::Number(
+            width
+                .parse()
+                .map_err(|_| anyhow!("invalid width {width:?} for {{number}}"))?,
+        ),
+        Some(_) => bail!("only {{number}} takes a width, got {{{name}}}"),
+        None => match name {
+            "source" => Token::Source,
+            "category" => Token::Category,
+            "manga" => Token::Manga,
+            "chapter" => Token::Chapter,
+            "number" => Token::Number(0),
+            _ => bail!("unknown token {{{name}}}"),
+        },
+    };
+
+    Ok(token)
+}
+
+fn parse_component(component: &str) -> Result<Vec<Part>> {
+    let mut parts = vec![];
+    let mut rest = component;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            parts.push(Part::Literal(rest[..start].to_string()));
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("unclosed {{ in {component:?}"))?;
+        parts.push(Part::Token(parse_token(&rest[start + 1..start + end])?));
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Literal(rest.to_string()));
+    }
+
+    for part in parts.iter() {
+        if matches!(part, Part::Literal(literal) if literal.contains(RESERVED)) {
+            bail!("{component:?} contains characters file names can't have");
+        }
+    }
+
+    Ok(parts)
+}
+
+/// `value` usable as a file name on any platform, reserved characters become `replacement`
+pub fn sanitize_component(value: &str, replacement: &str) -> String {
+    let value = value
+        .replace(RESERVED, replacement)
+        .replace(char::is_control, "");
+    // windows strips trailing dots and spaces, which would break looking the file up again
+    value.trim().trim_end_matches('.').trim_end().to_string()
+}
+
+fn format_number(number: f64, width: usize) -> String {
+    let number = number.to_string();
+    match number.split_once('.') {
+        Some((integer, fraction)) => format!("{integer:0>width$}.{fraction}"),
+        None => format!("{number:0>width$}"),
+    }
+}
+
+impl PathTemplate {
+    pub fn new(config: &DownloadLayoutConfig) -> Result<Self> {
+        if config.replacement.contains(RESERVED) {
+            bail!(
+                "download replacement {:?} contains reserved characters",
+                config.replacement
+            );
+        }
+
+        let components = config
+            .template
+            .split('/')
+            .filter(|component| !component.trim().is_empty())
+            .map(parse_component)
+            .collect::<Result<Vec<_>>>()?;
+
+        if !components
+            .last()
+            .map(|parts| parts.iter().any(|part| matches!(part, Part::Token(_))))
+            .unwrap_or(false)
+        {
+            bail!(
+                "download template {:?} must end with a file name containing a token",
+                config.template
+            );
+        }
+
+        Ok(Self {
+            components,
+            replacement: config.replacement.clone(),
+            max_length: config.max_length,
+        })
+    }
+
+    fn sanitize(&self, value: &str) -> String {
+        sanitize_component(value, &self.replacement)
+    }
+
+    fn truncate(&self, component: String) -> String {
+        if self.max_length == 0 || component.chars().count() <= self.max_length {
+            return component;
+        }
+
+        component
+            .chars()
+            .take(self.max_length)
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+
+    /// Path of a chapter relative to the download directory with `extension` appended,
+    /// components that render empty (e.g. `{category}` of an uncategorized manga) are left out
+    pub fn render(&self, values: &PathValues, extension: Option<&str>) -> PathBuf {
+        let mut path = PathBuf::new();
+        let last = self.components.len() - 1;
+        for (i, parts) in self.components.iter().enumerate() {
+            let component: String = parts
+                .iter()
+                .map(|part| match part {
+                    Part::Literal(literal) => literal.clone(),
+                    Part::Token(Token::Source) => self.sanitize(values.source),
+                    Part::Token(Token::Category) => {
+                        self.sanitize(values.category.unwrap_or_default())
+                    }
+                    Part::Token(Token::Manga) => self.sanitize(values.manga),
+                    Part::Token(Token::Chapter) => self.sanitize(values.chapter),
+                    Part::Token(Token::Number(width)) => format_number(values.number, *width),
+                })
+                .collect();
+            let component = self.truncate(component.trim().to_string());
+            let is_empty = component.is_empty() || component == "." || component == "..";
+
+            if i == last {
+                let filename = if is_empty {
+                    self.sanitize(values.chapter)
+                } else {
+                    component
+                };
+                match extension {
+                    Some(extension) => path.push(format!("{filename}.{extension}")),
+                    None => path.push(filename),
+                }
+            } else if !is_empty {
+                path.push(component);
+            }
+        }
+
+        path
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    fn template(template: &str, replacement: &str, max_length: usize) -> Result<PathTemplate> {
+        PathTemplate::new(&DownloadLayoutConfig {
+            template: template.to_string(),
+            replacement: replacement.to_string(),
+            max_length,
+        })
+    }
+
+    fn values(number: f64) -> PathValues<'static> {
+        PathValues {
+            source: "MangaDex",
+            category: None,
+            manga: "Re:Zero",
+            chapter: "12 - The End?",
+            number,
+        }
+    }
+
+    #[test]
+    fn test_render_default_template() {
+        let template = PathTemplate::new(&DownloadLayoutConfig::default()).unwrap();
+
+        assert_eq!(
+            template.render(&values(12.0), Some("cbz")),
+            Path::new("MangaDex")
+                .join("ReZero")
+                .join("12 - The End.cbz")
+        );
+        assert_eq!(
+            template.render(&values(12.0), None),
+            Path::new("MangaDex").join("ReZero").join("12 - The End")
+        );
+    }
+
+    #[test]
+    fn test_render_number_and_empty_category() {
+        let template = template("{category}/{manga}/{manga} Ch. {number:3}", "_", 0).unwrap();
+
+        assert_eq!(
+            template.render(&values(12.5), Some("cbz")),
+            Path::new("Re_Zero").join("Re_Zero Ch. 012.5.cbz")
+        );
+        assert_eq!(
+            template.render(
+                &PathValues {
+                    category: Some("Reading"),
+                    ..values(3.0)
+                },
+                Some("cbz")
+            ),
+            Path::new("Reading")
+                .join("Re_Zero")
+                .join("Re_Zero Ch. 003.cbz")
+        );
+    }
+
+    #[test]
+    fn test_render_truncated() {
+        let template = template("{manga}/{chapter}", "", 4).unwrap();
+
+        assert_eq!(
+            template.render(&values(12.0), None),
+            Path::new("ReZe").join("12 -")
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(template("{unknown}", "", 0).is_err());
+        assert!(template("{manga}/{chapter", "", 0).is_err());
+        assert!(template("{manga:2}", "", 0).is_err());
+        assert!(template("{number:x}", "", 0).is_err());
+        assert!(template("{source}/chapter", "", 0).is_err());
+        assert!(template("a:b/{chapter}", "", 0).is_err());
+        assert!(template("{manga}/{chapter}", "/", 0).is_err());
+    }
+
+    #[test]
+    fn test_sanitize_component() {
+        assert_eq!(sanitize_component("a/b\\c", "-"), "a-b-c");
+        assert_eq!(sanitize_component(" Vol. 1... ", ""), "Vol. 1");
+        assert_eq!(sanitize_component("tab\there", ""), "tabhere");
+    }
+}
@@ -0,0 +1,375 @@
+use std::{
+    borrow::Cow,
+    io::{Cursor, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use image::{
+    codecs::jpeg::JpegDecoder, ColorType, DynamicImage, GenericImageView, GrayImage, ImageDecoder,
+    ImageFormat, ImageOutputFormat, RgbImage,
+};
+use lopdf::{Document, Object, ObjectId, Stream};
+
+/// catalog and page tree, written last as they list every page
+const CATALOG_ID: usize = 1;
+const PAGES_ID: usize = 2;
+
+/// A page image as a pdf can embed it, jpegs are kept as they are
+struct PageImage<'a> {
+    data: Cow<'a, [u8]>,
+    width: u32,
+    height: u32,
+    color_space: &'static str,
+}
+
+impl<'a> PageImage<'a> {
+    fn new(data: &'a [u8]) -> Result<Self> {
+        if image::guess_format(data)? == ImageFormat::Jpeg {
+            let decoder = JpegDecoder::new(Cursor::new(data))?;
+            let (width, height) = decoder.dimensions();
+            let color_space = match decoder.color_type() {
+                ColorType::L8 => Some("DeviceGray"),
+                ColorType::Rgb8 => Some("DeviceRGB"),
+                _ => None,
+            };
+            if let Some(color_space) = color_space {
+                return Ok(Self {
+                    data: Cow::Borrowed(data),
+                    width,
+                    height,
+                    color_space,
+                });
+            }
+        }
+
+        let image = image::load_from_memory(data)?;
+        let (width, height) = image.dimensions();
+        let mut buf = Cursor::new(vec![]);
+        image
+            .into_rgb8()
+            .write_to(&mut buf, ImageOutputFormat::Jpeg(90))?;
+
+        Ok(Self {
+            data: Cow::Owned(buf.into_inner()),
+            width,
+            height,
+            color_space: "DeviceRGB",
+        })
+    }
+}
+
+/// Writes a pdf of one image per page to `writer` as pages are added,
+/// so a long export doesn't have to be held in memory
+pub struct PdfWriter<W: Write> {
+    writer: W,
+    written: usize,
+    /// byte offset of every object by id, starting at 1
+    offsets: Vec<usize>,
+    pages: Vec<usize>,
+}
+
+impl<W: Write> PdfWriter<W> {
+    pub fn new(writer: W) -> Result<Self> {
+        let mut pdf = Self {
+            writer,
+            written: 0,
+            offsets: vec![0; PAGES_ID],
+            pages: vec![],
+        };
+        // binary comment so transfers treat the file as binary
+        pdf.write(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n")?;
+
+        Ok(pdf)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.write_all(bytes)?;
+        self.written += bytes.len();
+
+        Ok(())
+    }
+
+    fn reserve(&mut self) -> usize {
+        self.offsets.push(0);
+        self.offsets.len()
+    }
+
+    fn begin(&mut self, id: usize) -> Result<()> {
+        self.offsets[id - 1] = self.written;
+        self.write(format!("{id} 0 obj\n").as_bytes())
+    }
+
+    fn object(&mut self, id: usize, body: &str) -> Result<()> {
+        self.begin(id)?;
+        self.write(format!("{body}\nendobj\n").as_bytes())
+    }
+
+    fn stream(&mut self, id: usize, dictionary: &str, data: &[u8]) -> Result<()> {
+        self.begin(id)?;
+        self.write(format!("<< {dictionary} /Length {} >>\nstream\n", data.len()).as_bytes())?;
+        self.write(data)?;
+        self.write(b"\nendstream\nendobj\n")
+    }
+
+    /// Add a page sized to the image, one pixel to a point
+    pub fn add_page(&mut self, image: &[u8]) -> Result<()> {
+        let image = PageImage::new(image)?;
+        let (width, height) = (image.width, image.height);
+
+        let image_id = self.reserve();
+        self.stream(
+            image_id,
+            &format!(
+                "/Type /XObject /Subtype /Image /Width {width} /Height {height} /ColorSpace /{} /BitsPerComponent 8 /Filter /DCTDecode",
+                image.color_space
+            ),
+            &image.data,
+        )?;
+
+        let content_id = self.reserve();
+        self.stream(
+            content_id,
+            "",
+            format!("q {width} 0 0 {height} 0 0 cm /Im0 Do Q").as_bytes(),
+        )?;
+
+        let page_id = self.reserve();
+        self.object(
+            page_id,
+            &format!(
+                "<< /Type /Page /Parent {PAGES_ID} 0 R /MediaBox [0 0 {width} {height}] /Resources << /XObject << /Im0 {image_id} 0 R >> >> /Contents {content_id} 0 R >>"
+            ),
+        )?;
+        self.pages.push(page_id);
+
+        Ok(())
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Write the page tree and cross references, `rtl` asks viewers to lay out spreads
+    /// right to left
+    pub fn finish(mut self, rtl: bool) -> Result<W> {
+        let kids = self
+            .pages
+            .iter()
+            .map(|id| format!("{id} 0 R"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.object(
+            PAGES_ID,
+            &format!(
+                "<< /Type /Pages /Kids [{kids}] /Count {} >>",
+                self.pages.len()
+            ),
+        )?;
+        self.object(
+            CATALOG_ID,
+            &format!(
+                "<< /Type /Catalog /Pages {PAGES_ID} 0 R /ViewerPreferences << /Direction /{} >> >>",
+                if rtl { "R2L" } else { "L2R" }
+            ),
+        )?;
+
+        let xref = self.written;
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in self.offsets.iter() {
+            table.push_str(&format!("{offset:010} 00000 n \n"));
+        }
+        table.push_str(&format!(
+            "trailer\n<< /Size {} /Root {CATALOG_ID} 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            self.offsets.len() + 1
+        ));
+        self.write(table.as_bytes())?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+pub fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("pdf"))
+        .unwrap_or(false)
+}
+
+/// Follow references until `object` is what they point to
+fn resolve<'a>(doc: &'a Document, mut object: &'a Object) -> Option<&'a Object> {
+    // bounded so a reference cycle in a broken file can't hang
+    for _ in 0..32 {
+        match object {
+            Object::Reference(id) => object = doc.get_object(*id).ok()?,
+            object => return Some(object),
+        }
+    }
+
+    None
+}
+
+fn integer(doc: &Document, stream: &Stream, key: &[u8]) -> Option<i64> {
+    resolve(doc, stream.dict.get(key).ok()?)?.as_i64().ok()
+}
+
+fn filters(stream: &Stream) -> Vec<&[u8]> {
+    match stream.dict.get(b"Filter") {
+        Ok(Object::Name(name)) => vec![name.as_slice()],
+        Ok(Object::Array(names)) => names
+            .iter()
+            .filter_map(|name| name.as_name().ok())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn is_jpeg(stream: &Stream) -> bool {
+    filters(stream) == [b"DCTDecode".as_slice()]
+}
+
+/// The image of a scanned page, the largest one drawn on it. Pages are only ever
+/// rendered from their images, text and vector graphics aren't
+fn page_image(doc: &Document, page_id: ObjectId) -> Option<&Stream> {
+    let (resources, resource_ids) = doc.get_page_resources(page_id);
+    resources
+        .into_iter()
+        .chain(
+            resource_ids
+                .into_iter()
+                .filter_map(|id| doc.get_dictionary(id).ok()),
+        )
+        .filter_map(|resources| {
+            resolve(doc, resources.get(b"XObject").ok()?)?
+                .as_dict()
+                .ok()
+        })
+        .flat_map(|xobjects| xobjects.iter().map(|(_, xobject)| xobject))
+        .filter_map(|xobject| resolve(doc, xobject)?.as_stream().ok())
+        .filter(|stream| {
+            stream
+                .dict
+                .get(b"Subtype")
+                .and_then(Object::as_name)
+                .map(|subtype| subtype == b"Image")
+                .unwrap_or(false)
+        })
+        .max_by_key(|stream| {
+            integer(doc, stream, b"Width").unwrap_or(0)
+                * integer(doc, stream, b"Height").unwrap_or(0)
+        })
+}
+
+/// Number of color components of an image, only device color spaces and icc profiles
+/// based on them are understood
+fn components(doc: &Document, stream: &Stream) -> Option<i64> {
+    let color_space = resolve(doc, stream.dict.get(b"ColorSpace").ok()?)?;
+    let name = match color_space {
+        Object::Array(array) => array.first()?.as_name().ok()?,
+        object => object.as_name().ok()?,
+    };
+    match name {
+        b"DeviceGray" | b"CalGray" => Some(1),
+        b"DeviceRGB" | b"CalRGB" => Some(3),
+        b"DeviceCMYK" => Some(4),
+        b"ICCBased" => {
+            let profile = match color_space {
+                Object::Array(array) => resolve(doc, array.get(1)?)?.as_stream().ok()?,
+                _ => return None,
+            };
+            integer(doc, profile, b"N")
+        }
+        _ => None,
+    }
+}
+
+/// Encode the raw samples of an image as png
+fn encode_samples(doc: &Document, stream: &Stream) -> Result<Vec<u8>> {
+    let width = integer(doc, stream, b"Width").ok_or_else(|| anyhow!("image without width"))?;
+    let height = integer(doc, stream, b"Height").ok_or_else(|| anyhow!("image without height"))?;
+    let (width, height) = (width as u32, height as u32);
+    let bits = integer(doc, stream, b"BitsPerComponent").unwrap_or(8);
+    let components =
+        components(doc, stream).ok_or_else(|| anyhow!("unsupported image color space"))?;
+
+    let samples = if filters(stream).is_empty() {
+        stream.content.clone()
+    } else {
+        stream.decompressed_content()?
+    };
+
+    let image = match (components, bits) {
+        // bilevel scans, rows are padded to whole bytes and a set bit is white
+        (1, 1) => {
+            let stride = (width as usize + 7) / 8;
+            let mut pixels = Vec::with_capacity(width as usize * height as usize);
+            for row in samples.chunks(stride).take(height as usize) {
+                for x in 0..width as usize {
+                    let bit = row.get(x / 8).map(|byte| (byte >> (7 - x % 8)) & 1);
+                    pixels.push(if bit == Some(1) { 255 } else { 0 });
+                }
+            }
+            GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8)
+        }
+        (1, 8) => GrayImage::from_raw(width, height, samples).map(DynamicImage::ImageLuma8),
+        (3, 8) => RgbImage::from_raw(width, height, samples).map(DynamicImage::ImageRgb8),
+        (4, 8) => {
+            let pixels = samples
+                .chunks_exact(4)
+                .flat_map(|cmyk| {
+                    let k = 255 - cmyk[3] as u16;
+                    [0, 1, 2].map(|i| ((255 - cmyk[i] as u16) * k / 255) as u8)
+                })
+                .collect();
+            RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+        }
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("unsupported image of {components} components, {bits} bits each"))?;
+
+    let mut buf = Cursor::new(vec![]);
+    image.write_to(&mut buf, ImageOutputFormat::Png)?;
+
+    Ok(buf.into_inner())
+}
+
+/// File names the pages of a pdf are served as, `0001.jpg` for page 1 if its image
+/// is a jpeg that can be served as it is, `0001.png` otherwise
+pub fn list_pages(path: &Path) -> Result<Vec<String>> {
+    let doc = Document::load(path)?;
+
+    Ok(doc
+        .get_pages()
+        .into_iter()
+        .map(|(number, page_id)| {
+            let extension = match page_image(&doc, page_id) {
+                Some(stream) if is_jpeg(stream) => "jpg",
+                _ => "png",
+            };
+            format!("{number:04}.{extension}")
+        })
+        .collect())
+}
+
+/// Render page `filename`, as listed by [`list_pages`], of the pdf at `path`
+pub fn read_page(path: &Path, filename: &str) -> Result<Vec<u8>> {
+    let number: u32 = Path::new(filename)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.parse().ok())
+        .ok_or_else(|| anyhow!("{filename} is not a pdf page"))?;
+
+    let doc = Document::load(path)?;
+    let page_id = *doc
+        .get_pages()
+        .get(&number)
+        .ok_or_else(|| anyhow!("pdf has no page {number}"))?;
+    let stream =
+        page_image(&doc, page_id).ok_or_else(|| anyhow!("page {number} has no image to render"))?;
+
+    if is_jpeg(stream) {
+        Ok(stream.content.clone())
+    } else {
+        encode_samples(&doc, stream)
+    }
+}
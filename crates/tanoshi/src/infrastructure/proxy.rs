@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{anyhow, Result};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, COOKIE, REFERER, USER_AGENT},
+    Client, Proxy, Url,
+};
+
+const SUPPORTED_SCHEMES: [&str; 4] = ["http", "https", "socks5", "socks5h"];
+
+pub fn validate(proxy: &str) -> Result<()> {
+    let url = Url::parse(proxy)?;
+    if !SUPPORTED_SCHEMES.contains(&url.scheme()) {
+        return Err(anyhow!("unsupported proxy scheme {}", url.scheme()));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn host_of(url: &str) -> Option<String> {
+    Url::parse(url).ok().and_then(|url| {
+        url.host_str()
+            .map(|host| host.trim_start_matches("www.").to_string())
+    })
+}
+
+fn matches_domain(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(&format!(".{domain}"))
+}
+
+/// Headers sent on image requests to `domain` and its subdomains, for hosts that reject
+/// hotlinking without them
+#[derive(Debug, Clone, Default)]
+pub struct ProxyRule {
+    pub domain: String,
+    pub referer: Option<String>,
+    /// value of the `Cookie` header, e.g. `a=1; b=2`
+    pub cookies: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ProxyRule {
+    fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in [
+            (REFERER, self.referer.as_ref()),
+            (COOKIE, self.cookies.as_ref()),
+            (USER_AGENT, self.user_agent.as_ref()),
+        ] {
+            match value.map(|value| HeaderValue::from_str(value)) {
+                Some(Ok(value)) => {
+                    headers.insert(name, value);
+                }
+                Some(Err(_)) => warn!("invalid {name} in proxy rule of {}", self.domain),
+                None => {}
+            }
+        }
+
+        headers
+    }
+}
+
+/// Outbound proxy of each source, keyed by the host of the source url,
+/// so requests made by tanoshi on behalf of a source use the same egress as its extension
+#[derive(Clone, Default)]
+pub struct ProxyTable {
+    default: Option<String>,
+    hosts: Arc<RwLock<HashMap<String, String>>>,
+    clients: Arc<RwLock<HashMap<Option<String>, Client>>>,
+    rules: Arc<Vec<ProxyRule>>,
+    /// referer of image hosts without a rule, the source whose pages were last seen on them
+    referers: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl ProxyTable {
+    pub fn new(default: Option<String>) -> Self {
+        Self {
+            default,
+            ..Default::default()
+        }
+    }
+
+    /// Table applying `rules` to image requests, the first matching rule of a host is used
+    pub fn with_rules(self, rules: Vec<ProxyRule>) -> Self {
+        Self {
+            rules: Arc::new(rules),
+            ..self
+        }
+    }
+
+    pub fn default_proxy(&self) -> Option<String> {
+        self.default.clone()
+    }
+
+    /// Route requests to the host of `source_url` through `proxy`, `None` uses the default
+    pub fn set(&self, source_url: &str, proxy: Option<&str>) {
+        let host = match host_of(source_url) {
+            Some(host) => host,
+            None => return,
+        };
+
+        if let Ok(mut hosts) = self.hosts.write() {
+            match proxy {
+                Some(proxy) => hosts.insert(host, proxy.to_string()),
+                None => hosts.remove(&host),
+            };
+        }
+    }
+
+    /// Proxy for a request to `url`, the referer is checked too as images are often on a cdn
+    pub fn resolve(&self, url: &str, referer: Option<&str>) -> Option<String> {
+        let hosts = self.hosts.read().ok()?;
+        let matches = |host: &String| {
+            hosts.iter().find_map(|(source_host, proxy)| {
+                matches_domain(host, source_host).then(|| proxy.clone())
+            })
+        };
+
+        host_of(url)
+            .and_then(|host| matches(&host))
+            .or_else(|| referer.and_then(host_of).and_then(|host| matches(&host)))
+            .or_else(|| self.default.clone())
+    }
+
+    fn rule(&self, host: &str) -> Option<&ProxyRule> {
+        self.rules
+            .iter()
+            .find(|rule| matches_domain(host, &rule.domain))
+    }
+
+    /// Send `referer` on requests to the host of `url`, unless a rule sets another
+    pub fn set_referer(&self, url: &str, referer: &str) {
+        let host = match host_of(url) {
+            Some(host) => host,
+            None => return,
+        };
+        if self
+            .rule(&host)
+            .and_then(|rule| rule.referer.as_ref())
+            .is_some()
+        {
+            return;
+        }
+
+        if let Ok(mut referers) = self.referers.write() {
+            referers.insert(host, referer.to_string());
+        }
+    }
+
+    /// Referer for a request to `url`, from its rule or the source it was last seen with
+    pub fn referer(&self, url: &str) -> Option<String> {
+        let host = host_of(url)?;
+        self.rule(&host)
+            .and_then(|rule| rule.referer.clone())
+            .or_else(|| self.referers.read().ok()?.get(&host).cloned())
+    }
+
+    /// Headers of the rule matching the host of `url`
+    pub fn headers(&self, url: &str) -> HeaderMap {
+        host_of(url)
+            .and_then(|host| self.rule(&host).map(ProxyRule::headers))
+            .unwrap_or_default()
+    }
+
+    /// Client sending requests through `proxy`, built once per proxy
+    pub fn client(&self, proxy: Option<String>) -> Result<Client> {
+        if let Some(client) = self
+            .clients
+            .read()
+            .ok()
+            .and_then(|clients| clients.get(&proxy).cloned())
+        {
+            return Ok(client);
+        }
+
+        let mut builder = Client::builder();
+        if let Some(proxy) = proxy.as_ref() {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+        let client = builder.build()?;
+
+        if let Ok(mut clients) = self.clients.write() {
+            clients.insert(proxy, client.clone());
+        }
+
+        Ok(client)
+    }
+}
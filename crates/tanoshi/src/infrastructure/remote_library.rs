@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+const KOMGA_PAGE_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteServer {
+    Komga,
+    Kavita,
+}
+
+/// Read state of a chapter on the remote server
+#[derive(Debug, Clone)]
+pub struct RemoteChapter {
+    pub number: f64,
+    pub page: i64,
+    pub completed: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteSeries {
+    pub title: String,
+    /// collections (komga) or collection tags (kavita) the series is in
+    pub collections: Vec<String>,
+    pub chapters: Vec<RemoteChapter>,
+}
+
+/// Series, collections and read progress of the account on a Komga (v1 api)
+/// or Kavita (0.5 api) server
+pub async fn fetch_library(
+    server: RemoteServer,
+    url: &str,
+    username: &str,
+    password: &str,
+) -> Result<Vec<RemoteSeries>> {
+    let url = url.trim_end_matches('/');
+    match server {
+        RemoteServer::Komga => Komga::new(url, username, password).fetch().await,
+        RemoteServer::Kavita => Kavita::login(url, username, password).await?.fetch().await,
+    }
+}
+
+#[derive(Deserialize)]
+struct KomgaPage<T> {
+    content: Vec<T>,
+    last: bool,
+}
+
+#[derive(Deserialize)]
+struct KomgaSeries {
+    id: String,
+    metadata: KomgaSeriesMetadata,
+}
+
+#[derive(Deserialize)]
+struct KomgaSeriesMetadata {
+    title: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KomgaCollection {
+    name: String,
+    series_ids: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KomgaBook {
+    metadata: KomgaBookMetadata,
+    read_progress: Option<KomgaReadProgress>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KomgaBookMetadata {
+    number_sort: f64,
+}
+
+#[derive(Deserialize)]
+struct KomgaReadProgress {
+    page: i64,
+    completed: bool,
+}
+
+struct Komga<'a> {
+    client: Client,
+    url: &'a str,
+    username: &'a str,
+    password: &'a str,
+}
+
+impl<'a> Komga<'a> {
+    fn new(url: &'a str, username: &'a str, password: &'a str) -> Self {
+        Self {
+            client: Client::new(),
+            url,
+            username,
+            password,
+        }
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.client
+            .get(format!("{}{path}", self.url))
+            .basic_auth(self.username, Some(self.password))
+    }
+
+    async fn get_all<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<Vec<T>> {
+        let mut items = vec![];
+        for page in 0.. {
+            let res: KomgaPage<T> = self
+                .get(path)
+                .query(&[("page", page), ("size", KOMGA_PAGE_SIZE)])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            items.extend(res.content);
+            if res.last {
+                break;
+            }
+        }
+
+        Ok(items)
+    }
+
+    async fn fetch(&self) -> Result<Vec<RemoteSeries>> {
+        let mut collections: HashMap<String, Vec<String>> = HashMap::new();
+        for collection in self
+            .get_all::<KomgaCollection>("/api/v1/collections")
+            .await?
+        {
+            for series_id in collection.series_ids {
+                collections
+                    .entry(series_id)
+                    .or_default()
+                    .push(collection.name.clone());
+            }
+        }
+
+        let mut library = vec![];
+        for series in self.get_all::<KomgaSeries>("/api/v1/series").await? {
+            let chapters = self
+                .get_all::<KomgaBook>(&format!("/api/v1/series/{}/books", series.id))
+                .await?
+                .into_iter()
+                .filter_map(|book| {
+                    book.read_progress.map(|progress| RemoteChapter {
+                        number: book.metadata.number_sort,
+                        page: progress.page,
+                        completed: progress.completed,
+                    })
+                })
+                .collect();
+
+            library.push(RemoteSeries {
+                title: series.metadata.title,
+                collections: collections.remove(&series.id).unwrap_or_default(),
+                chapters,
+            });
+        }
+
+        Ok(library)
+    }
+}
+
+#[derive(Serialize)]
+struct KavitaLogin<'a> {
+    username: &'a str,
+    password: &'a str,
+}
+
+#[derive(Deserialize)]
+struct KavitaUser {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct KavitaSeries {
+    id: i64,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct KavitaCollection {
+    id: i64,
+    title: String,
+}
+
+#[derive(Deserialize)]
+struct KavitaVolume {
+    number: i64,
+    chapters: Vec<KavitaChapter>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KavitaChapter {
+    number: String,
+    pages: i64,
+    pages_read: i64,
+}
+
+struct Kavita<'a> {
+    client: Client,
+    url: &'a str,
+    token: String,
+}
+
+impl<'a> Kavita<'a> {
+    async fn login(url: &'a str, username: &str, password: &str) -> Result<Kavita<'a>> {
+        let client = Client::new();
+        let user: KavitaUser = client
+            .post(format!("{url}/api/Account/login"))
+            .json(&KavitaLogin { username, password })
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|e| anyhow!("failed to login to kavita: {e}"))?
+            .json()
+            .await?;
+
+        Ok(Self {
+            client,
+            url,
+            token: user.token,
+        })
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T> {
+        Ok(self
+            .client
+            .get(format!("{}{path}", self.url))
+            .bearer_auth(&self.token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    async fn fetch(&self) -> Result<Vec<RemoteSeries>> {
+        let mut collections: HashMap<i64, Vec<String>> = HashMap::new();
+        for collection in self.get::<Vec<KavitaCollection>>("/api/Collection").await? {
+            let series: Vec<KavitaSeries> = self
+                .get(&format!(
+                    "/api/Series/series-by-collection?collectionId={}",
+                    collection.id
+                ))
+                .await?;
+            for series in series {
+                collections
+                    .entry(series.id)
+                    .or_default()
+                    .push(collection.title.clone());
+            }
+        }
+
+        let all_series: Vec<KavitaSeries> = self
+            .client
+            .post(format!("{}/api/Series/all?libraryId=0", self.url))
+            .bearer_auth(&self.token)
+            .json(&serde_json::json!({}))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let mut library = vec![];
+        for series in all_series {
+            let volumes: Vec<KavitaVolume> = self
+                .get(&format!("/api/Series/volumes?seriesId={}", series.id))
+                .await?;
+
+            let chapters = volumes
+                .into_iter()
+                .flat_map(|volume| {
+                    let volume_number = volume.number;
+                    volume
+                        .chapters
+                        .into_iter()
+                        .map(move |chapter| (volume_number, chapter))
+                })
+                .filter(|(_, chapter)| chapter.pages_read > 0)
+                .map(|(volume_number, chapter)| RemoteChapter {
+                    // volume-only releases have chapter number 0
+                    number: chapter
+                        .number
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|number| *number > 0.0)
+                        .unwrap_or(volume_number as f64),
+                    page: chapter.pages_read,
+                    completed: chapter.pages_read >= chapter.pages,
+                })
+                .collect();
+
+            library.push(RemoteSeries {
+                title: series.name,
+                collections: collections.remove(&series.id).unwrap_or_default(),
+                chapters,
+            });
+        }
+
+        Ok(library)
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use reqwest::{
+    header::{ETAG, IF_NONE_MATCH},
+    StatusCode,
+};
+use serde::de::DeserializeOwned;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RepositoryIndexError {
+    #[error("request return error: {0}")]
+    RequestError(#[from] reqwest::Error),
+    #[error("invalid index: {0}")]
+    InvalidIndex(#[from] serde_json::Error),
+}
+
+struct Entry {
+    fetched_at: Instant,
+    etag: Option<String>,
+    body: Arc<String>,
+}
+
+/// Client of extension repositories `index.json`, an index is reused for `ttl`
+/// then revalidated with its ETag, a stale one is served if the repository is unreachable.
+/// `ttl` of zero revalidates on every read
+#[derive(Clone)]
+pub struct RepositoryIndex {
+    client: reqwest::Client,
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl RepositoryIndex {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            ttl,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Index of `repo_url` deserialized as `T`
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        repo_url: &str,
+    ) -> Result<T, RepositoryIndexError> {
+        let body = self.fetch(repo_url).await?;
+
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    async fn fetch(&self, repo_url: &str) -> Result<Arc<String>, RepositoryIndexError> {
+        let (cached, etag) = match self.entries.lock() {
+            Ok(entries) => match entries.get(repo_url) {
+                Some(entry) if entry.fetched_at.elapsed() < self.ttl => {
+                    return Ok(entry.body.clone());
+                }
+                Some(entry) => (Some(entry.body.clone()), entry.etag.clone()),
+                None => (None, None),
+            },
+            Err(_) => (None, None),
+        };
+
+        let mut req = self.client.get(format!("{repo_url}/index.json"));
+        if let Some(etag) = etag.as_deref() {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+
+        let res = match req.send().await.and_then(|res| res.error_for_status()) {
+            Ok(res) => res,
+            Err(e) => match cached {
+                Some(body) => {
+                    warn!("failed to refresh index of {repo_url}, using stale one: {e}");
+                    return Ok(body);
+                }
+                None => return Err(e.into()),
+            },
+        };
+
+        let (etag, body) = match (res.status(), cached) {
+            (StatusCode::NOT_MODIFIED, Some(body)) => (etag, body),
+            _ => {
+                let etag = res
+                    .headers()
+                    .get(ETAG)
+                    .and_then(|etag| etag.to_str().ok())
+                    .map(str::to_string);
+                (etag, Arc::new(res.text().await?))
+            }
+        };
+
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(
+                repo_url.to_string(),
+                Entry {
+                    fetched_at: Instant::now(),
+                    etag,
+                    body: body.clone(),
+                },
+            );
+        }
+
+        Ok(body)
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// responses kept in memory, the oldest one is evicted (or spilled to disk) past this
+const MEMORY_CAPACITY: usize = 256;
+
+/// Key of a source response, unique per source, request and session
+pub fn key<T: Hash>(source_id: i64, request: T, session: Option<&str>) -> String {
+    let mut hasher = DefaultHasher::new();
+    request.hash(&mut hasher);
+    session.hash(&mut hasher);
+
+    format!("{source_id}_{:x}", hasher.finish())
+}
+
+/// TTL cache of serialized source responses, entries evicted from memory
+/// are written to `spill_dir` if set and served from there until they expire
+#[derive(Clone)]
+pub struct SourceCache {
+    ttl: Duration,
+    spill_dir: Option<PathBuf>,
+    entries: Arc<Mutex<HashMap<String, (SystemTime, Arc<String>)>>>,
+}
+
+impl SourceCache {
+    /// `ttl` of zero disables caching
+    pub fn new(ttl: Duration, spill_dir: Option<PathBuf>) -> Self {
+        if let Some(dir) = spill_dir.as_ref() {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                error!("failed to create source cache dir {}: {e}", dir.display());
+            }
+        }
+
+        Self {
+            ttl,
+            spill_dir,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn spill_path(&self, key: &str) -> Option<PathBuf> {
+        self.spill_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{key}.json")))
+    }
+
+    fn is_fresh(&self, stored_at: SystemTime) -> bool {
+        stored_at
+            .elapsed()
+            .map(|age| age < self.ttl)
+            .unwrap_or(false)
+    }
+
+    pub async fn get(&self, key: &str) -> Option<Arc<String>> {
+        if self.ttl.is_zero() {
+            return None;
+        }
+
+        if let Ok(mut entries) = self.entries.lock() {
+            if let Some((stored_at, value)) = entries.get(key) {
+                if self.is_fresh(*stored_at) {
+                    return Some(value.clone());
+                }
+                entries.remove(key);
+            }
+        }
+
+        // spilled entries start with the unix time they were first stored at
+        let path = self.spill_path(key)?;
+        let content = tokio::fs::read_to_string(&path).await.ok()?;
+        let (stored_at, value) = content.split_once('\n')?;
+        let stored_at = UNIX_EPOCH + Duration::from_secs(stored_at.parse().ok()?);
+        if !self.is_fresh(stored_at) {
+            let _ = tokio::fs::remove_file(&path).await;
+            return None;
+        }
+
+        Some(Arc::new(value.to_string()))
+    }
+
+    pub async fn set(&self, key: String, value: String) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        let evicted = match self.entries.lock() {
+            Ok(mut entries) => {
+                entries.insert(key, (SystemTime::now(), Arc::new(value)));
+                if entries.len() > MEMORY_CAPACITY {
+                    let oldest = entries
+                        .iter()
+                        .min_by_key(|(_, (stored_at, _))| *stored_at)
+                        .map(|(key, _)| key.clone());
+                    oldest.and_then(|key| entries.remove_entry(&key))
+                } else {
+                    None
+                }
+            }
+            Err(_) => None,
+        };
+
+        if let Some((key, (stored_at, value))) = evicted {
+            if !self.is_fresh(stored_at) {
+                return;
+            }
+            if let Some(path) = self.spill_path(&key) {
+                let stored_at = stored_at
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                if let Err(e) = tokio::fs::write(&path, format!("{stored_at}\n{value}")).await {
+                    error!("failed to spill source response {key}: {e}");
+                }
+            }
+        }
+    }
+
+    /// Drop every response of a source, e.g. after its preferences changed
+    pub async fn invalidate(&self, source_id: i64) {
+        let prefix = format!("{source_id}_");
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|key, _| !key.starts_with(&prefix));
+        }
+
+        let dir = match self.spill_dir.as_ref() {
+            Some(dir) => dir,
+            None => return,
+        };
+        if let Ok(mut read_dir) = tokio::fs::read_dir(dir).await {
+            while let Ok(Some(entry)) = read_dir.next_entry().await {
+                if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                    let _ = tokio::fs::remove_file(entry.path()).await;
+                }
+            }
+        }
+    }
+}
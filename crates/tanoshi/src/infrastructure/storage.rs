@@ -0,0 +1,367 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Cursor, Read},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Result};
+use bytes::Bytes;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use mime_guess::mime;
+use reqwest::{Method, Url};
+use sha2::{Digest, Sha256};
+use zip::ZipArchive;
+
+use crate::infrastructure::config::{S3Config, StorageConfig};
+
+const S3_SCHEME: &str = "s3://";
+/// Archives kept in memory after a read, so pages of a chapter being read
+/// don't fetch the whole archive from the bucket again
+const ARCHIVE_CACHE_SIZE: usize = 4;
+const ARCHIVE_CACHE_TTL: Duration = Duration::from_secs(1800);
+
+/// Whether `location` was stored in a remote bucket rather than on disk
+pub fn is_remote(location: &str) -> bool {
+    location.starts_with(S3_SCHEME)
+}
+
+/// Percent encode everything but unreserved characters, as signature v4 expects
+fn uri_encode(value: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    encoded
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// S3 compatible bucket (AWS, MinIO, B2...) signed with signature v4
+#[derive(Debug, Clone)]
+pub struct S3Bucket {
+    client: reqwest::Client,
+    endpoint: Url,
+    config: S3Config,
+    archives: Arc<Mutex<VecDeque<(String, Bytes, Instant)>>>,
+}
+
+impl S3Bucket {
+    pub fn new(config: &S3Config) -> Result<Self> {
+        let endpoint = Url::parse(&config.endpoint)
+            .map_err(|e| anyhow!("invalid s3 endpoint {:?}: {e}", config.endpoint))?;
+        if endpoint.host_str().is_none() {
+            bail!("s3 endpoint {:?} has no host", config.endpoint);
+        }
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            endpoint,
+            config: config.clone(),
+            archives: Arc::new(Mutex::new(VecDeque::with_capacity(ARCHIVE_CACHE_SIZE))),
+        })
+    }
+
+    /// Archive at `location`, from memory when it was read recently
+    async fn archive(&self, location: &str) -> Result<Bytes> {
+        {
+            let mut archives = self.archives.lock().unwrap();
+            archives.retain(|(_, _, read_at)| read_at.elapsed() < ARCHIVE_CACHE_TTL);
+            if let Some((_, data, read_at)) = archives.iter_mut().find(|(l, _, _)| l == location) {
+                *read_at = Instant::now();
+                return Ok(data.clone());
+            }
+        }
+
+        let data = self
+            .request(Method::GET, self.key_of(location)?, vec![])
+            .await?;
+
+        let mut archives = self.archives.lock().unwrap();
+        if archives.len() >= ARCHIVE_CACHE_SIZE {
+            if let Some(oldest) = archives
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (_, _, read_at))| *read_at)
+                .map(|(i, _)| i)
+            {
+                archives.remove(oldest);
+            }
+        }
+        archives.push_back((location.to_string(), data.clone(), Instant::now()));
+
+        Ok(data)
+    }
+
+    fn evict_archive(&self, location: &str) {
+        self.archives
+            .lock()
+            .unwrap()
+            .retain(|(l, _, _)| l != location);
+    }
+
+    fn object_key(&self, key: &str) -> String {
+        let prefix = self.config.prefix.trim_matches('/');
+        if prefix.is_empty() {
+            key.trim_start_matches('/').to_string()
+        } else {
+            format!("{prefix}/{}", key.trim_start_matches('/'))
+        }
+    }
+
+    /// Location stored in the database for `key`
+    fn location(&self, key: &str) -> String {
+        format!("{S3_SCHEME}{}/{}", self.config.bucket, self.object_key(key))
+    }
+
+    fn key_of<'a>(&self, location: &'a str) -> Result<&'a str> {
+        location
+            .strip_prefix(S3_SCHEME)
+            .and_then(|rest| rest.strip_prefix(self.config.bucket.as_str()))
+            .and_then(|rest| rest.strip_prefix('/'))
+            .ok_or_else(|| anyhow!("{location} is not in bucket {}", self.config.bucket))
+    }
+
+    async fn request(&self, method: Method, object_key: &str, body: Vec<u8>) -> Result<Bytes> {
+        let host = self.endpoint.host_str().unwrap_or_default();
+        let host = match (self.config.path_style, self.endpoint.port()) {
+            (true, Some(port)) => format!("{host}:{port}"),
+            (true, None) => host.to_string(),
+            (false, Some(port)) => format!("{}.{host}:{port}", self.config.bucket),
+            (false, None) => format!("{}.{host}", self.config.bucket),
+        };
+        let base_path = self.endpoint.path().trim_end_matches('/');
+        let path = if self.config.path_style {
+            format!(
+                "{base_path}/{}/{}",
+                uri_encode(&self.config.bucket, false),
+                uri_encode(object_key, true)
+            )
+        } else {
+            format!("{base_path}/{}", uri_encode(object_key, true))
+        };
+        let url = format!("{}://{host}{path}", self.endpoint.scheme());
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex::encode(Sha256::digest(&body));
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{method}\n{path}\n\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\n{signed_headers}\n{payload_hash}"
+        );
+        let scope = format!("{date}/{}/s3/aws4_request", self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = hmac_sha256(format!("AWS4{}", self.config.secret_key).as_bytes(), &date);
+        let key = hmac_sha256(&key, &self.config.region);
+        let key = hmac_sha256(&key, "s3");
+        let key = hmac_sha256(&key, "aws4_request");
+        let signature = hex::encode(hmac_sha256(&key, &string_to_sign));
+
+        let res = self
+            .client
+            .request(method, url)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header(
+                "authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+                    self.config.access_key
+                ),
+            )
+            .body(body)
+            .send()
+            .await?;
+
+        let status = res.status();
+        let body = res.bytes().await?;
+        if !status.is_success() {
+            bail!(
+                "s3 returned {status} for {object_key}: {}",
+                String::from_utf8_lossy(&body)
+            );
+        }
+
+        Ok(body)
+    }
+}
+
+/// Where downloaded chapters and cached images are kept, see `storage` in config.
+/// Locations are plain paths on disk, or `s3://bucket/key` for objects in a bucket
+#[derive(Debug, Clone)]
+pub enum Storage {
+    Local,
+    S3(S3Bucket),
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self::Local
+    }
+}
+
+impl Storage {
+    pub fn new(config: &StorageConfig) -> Result<Self> {
+        match config {
+            StorageConfig::Local => Ok(Self::Local),
+            StorageConfig::S3(config) => Ok(Self::S3(S3Bucket::new(config)?)),
+        }
+    }
+
+    pub fn is_remote(&self) -> bool {
+        matches!(self, Self::S3(_))
+    }
+
+    /// Move the finished download at `path` to storage under `key`, returns its new location
+    pub async fn store(&self, path: &Path, key: &str) -> Result<String> {
+        match self {
+            Self::Local => Ok(path.display().to_string()),
+            Self::S3(bucket) => {
+                let data = tokio::fs::read(path).await?;
+                bucket
+                    .request(Method::PUT, &bucket.object_key(key), data)
+                    .await?;
+                tokio::fs::remove_file(path).await?;
+                bucket.evict_archive(&bucket.location(key));
+
+                Ok(bucket.location(key))
+            }
+        }
+    }
+
+    pub async fn read(&self, location: &str) -> Result<Bytes> {
+        match self {
+            Self::S3(bucket) if is_remote(location) => {
+                bucket
+                    .request(Method::GET, bucket.key_of(location)?, vec![])
+                    .await
+            }
+            _ => Ok(tokio::fs::read(location).await?.into()),
+        }
+    }
+
+    /// Remove a downloaded chapter, an archive or folder of pages on disk or an object
+    pub async fn remove(&self, location: &str) -> io::Result<()> {
+        match self {
+            Self::S3(bucket) if is_remote(location) => {
+                bucket.evict_archive(location);
+                let key = bucket
+                    .key_of(location)
+                    .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e.to_string()))?;
+                bucket
+                    .request(Method::DELETE, key, vec![])
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+            }
+            _ if is_remote(location) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("no bucket configured to remove {location}"),
+            )),
+            _ => {
+                if tokio::fs::metadata(location).await?.is_dir() {
+                    tokio::fs::remove_dir_all(location).await
+                } else {
+                    tokio::fs::remove_file(location).await
+                }
+            }
+        }
+    }
+
+    /// Remote archive at `location`, kept in memory for a while as its pages are read one by one
+    async fn read_archive(&self, location: &str) -> Result<Bytes> {
+        match self {
+            Self::S3(bucket) if is_remote(location) => bucket.archive(location).await,
+            _ => self.read(location).await,
+        }
+    }
+
+    /// Image pages of the archive at a remote `location`, as `location/filename`
+    pub async fn list_archive_pages(&self, location: &str) -> Result<Vec<String>> {
+        let data = self.read_archive(location).await?;
+        let location = location.to_string();
+        tokio::task::spawn_blocking(move || {
+            let zip = ZipArchive::new(Cursor::new(data))?;
+            let mut pages: Vec<String> = zip
+                .file_names()
+                .filter(|name| {
+                    mime_guess::from_path(name)
+                        .first()
+                        .map(|m| m.type_() == mime::IMAGE)
+                        .unwrap_or(false)
+                })
+                .map(|name| format!("{location}/{name}"))
+                .collect();
+            pages.sort_by(|a, b| human_sort::compare(a, b));
+
+            Ok(pages)
+        })
+        .await?
+    }
+
+    pub async fn read_archive_page(&self, location: &str, filename: &str) -> Result<Vec<u8>> {
+        let data = self.read_archive(location).await?;
+        let filename = filename.to_string();
+        tokio::task::spawn_blocking(move || {
+            let mut zip = ZipArchive::new(Cursor::new(data))?;
+            let mut page = zip.by_name(&filename)?;
+            let mut buf = Vec::with_capacity(page.size() as usize);
+            page.read_to_end(&mut buf)?;
+
+            Ok(buf)
+        })
+        .await?
+    }
+
+    /// Store cached data under `key` in the bucket, `None` if cache stays on disk
+    pub async fn put_cache(&self, key: &str, data: Vec<u8>) -> Option<Result<()>> {
+        match self {
+            Self::Local => None,
+            Self::S3(bucket) => Some(
+                bucket
+                    .request(
+                        Method::PUT,
+                        &bucket.object_key(&format!("cache/{key}")),
+                        data,
+                    )
+                    .await
+                    .map(|_| ()),
+            ),
+        }
+    }
+
+    /// Cached data under `key` in the bucket, `None` if cache stays on disk
+    pub async fn get_cache(&self, key: &str) -> Option<Result<Bytes>> {
+        match self {
+            Self::Local => None,
+            Self::S3(bucket) => Some(
+                bucket
+                    .request(
+                        Method::GET,
+                        &bucket.object_key(&format!("cache/{key}")),
+                        vec![],
+                    )
+                    .await,
+            ),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use serde::Serialize;
+
+use super::config::Config;
+
+/// Coarse usage stats, nothing here identifies the server or its users
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetryPayload {
+    pub version: String,
+    pub os: String,
+    pub arch: String,
+    pub library_size: String,
+    pub installed_sources: String,
+    pub features: Vec<String>,
+}
+
+fn bucket(count: i64) -> String {
+    match count {
+        i64::MIN..=0 => "0",
+        1..=10 => "1-10",
+        11..=50 => "11-50",
+        51..=200 => "51-200",
+        201..=1000 => "201-1000",
+        _ => "1000+",
+    }
+    .to_string()
+}
+
+pub fn collect(config: &Config, installed_sources: usize, library_size: i64) -> TelemetryPayload {
+    let mut features = vec![];
+    if cfg!(feature = "desktop") {
+        features.push("desktop");
+    }
+    if config.telegram.is_some() {
+        features.push("telegram");
+    }
+    if config.pushover.is_some() {
+        features.push("pushover");
+    }
+    if config.gotify.is_some() {
+        features.push("gotify");
+    }
+    if config.smtp.is_some() {
+        features.push("smtp");
+    }
+    if config.myanimelist.is_some() {
+        features.push("myanimelist");
+    }
+    if config.anilist.is_some() {
+        features.push("anilist");
+    }
+    if config.auto_download_chapters {
+        features.push("auto_download_chapters");
+    }
+
+    TelemetryPayload {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        library_size: bucket(library_size),
+        installed_sources: bucket(installed_sources as i64),
+        features: features.into_iter().map(|f| f.to_string()).collect(),
+    }
+}
+
+pub async fn send(url: &str, payload: &TelemetryPayload) -> Result<(), anyhow::Error> {
+    reqwest::Client::new()
+        .post(url)
+        .header(
+            "User-Agent",
+            format!("Tanoshi/{}", env!("CARGO_PKG_VERSION")).as_str(),
+        )
+        .json(payload)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
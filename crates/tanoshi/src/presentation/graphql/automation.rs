@@ -0,0 +1,246 @@
+use super::notification::NotificationChannel;
+use crate::{
+    domain::{
+        entities::automation::{AutomationAction, NotifyChannel},
+        services::automation::AutomationService,
+    },
+    infrastructure::{auth::Claims, domain::repositories::automation::AutomationRepositoryImpl},
+};
+use async_graphql::{Context, Enum, InputObject, Object, Result, SimpleObject};
+use chrono::NaiveDateTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum AutomationEvent {
+    NewChapter,
+    AddedToLibrary,
+    ChapterRead,
+}
+
+impl From<crate::domain::entities::automation::AutomationEvent> for AutomationEvent {
+    fn from(event: crate::domain::entities::automation::AutomationEvent) -> Self {
+        use crate::domain::entities::automation::AutomationEvent as E;
+        match event {
+            E::NewChapter => Self::NewChapter,
+            E::AddedToLibrary => Self::AddedToLibrary,
+            E::ChapterRead => Self::ChapterRead,
+        }
+    }
+}
+
+impl From<AutomationEvent> for crate::domain::entities::automation::AutomationEvent {
+    fn from(event: AutomationEvent) -> Self {
+        match event {
+            AutomationEvent::NewChapter => Self::NewChapter,
+            AutomationEvent::AddedToLibrary => Self::AddedToLibrary,
+            AutomationEvent::ChapterRead => Self::ChapterRead,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum AutomationActionType {
+    Download,
+    MoveToCategory,
+    Notify,
+    Webhook,
+}
+
+impl From<NotifyChannel> for NotificationChannel {
+    fn from(channel: NotifyChannel) -> Self {
+        match channel {
+            NotifyChannel::Telegram => Self::Telegram,
+            NotifyChannel::Pushover => Self::Pushover,
+            NotifyChannel::Gotify => Self::Gotify,
+        }
+    }
+}
+
+impl From<NotificationChannel> for NotifyChannel {
+    fn from(channel: NotificationChannel) -> Self {
+        match channel {
+            NotificationChannel::Telegram => Self::Telegram,
+            NotificationChannel::Pushover => Self::Pushover,
+            NotificationChannel::Gotify => Self::Gotify,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct AutomationRule {
+    pub id: i64,
+    pub name: String,
+    pub event: AutomationEvent,
+    pub action: AutomationActionType,
+    /// target of MOVE_TO_CATEGORY, null is the default category
+    pub category_id: Option<i64>,
+    /// target of NOTIFY
+    pub channel: Option<NotificationChannel>,
+    /// target of WEBHOOK
+    pub webhook_url: Option<String>,
+    pub enabled: bool,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<crate::domain::entities::automation::AutomationRule> for AutomationRule {
+    fn from(rule: crate::domain::entities::automation::AutomationRule) -> Self {
+        let (action, category_id, channel, webhook_url) = match rule.action {
+            AutomationAction::Download => (AutomationActionType::Download, None, None, None),
+            AutomationAction::MoveToCategory { category_id } => (
+                AutomationActionType::MoveToCategory,
+                category_id,
+                None,
+                None,
+            ),
+            AutomationAction::Notify { channel } => (
+                AutomationActionType::Notify,
+                None,
+                Some(channel.into()),
+                None,
+            ),
+            AutomationAction::Webhook { url } => {
+                (AutomationActionType::Webhook, None, None, Some(url))
+            }
+        };
+
+        Self {
+            id: rule.id,
+            name: rule.name,
+            event: rule.event.into(),
+            action,
+            category_id,
+            channel,
+            webhook_url,
+            enabled: rule.enabled,
+            created_at: rule.created_at,
+        }
+    }
+}
+
+#[derive(InputObject)]
+struct AutomationRuleInput {
+    pub name: String,
+    pub event: AutomationEvent,
+    pub action: AutomationActionType,
+    pub category_id: Option<i64>,
+    pub channel: Option<NotificationChannel>,
+    pub webhook_url: Option<String>,
+    #[graphql(default = true)]
+    pub enabled: bool,
+}
+
+impl AutomationRuleInput {
+    fn action(&self) -> Result<AutomationAction> {
+        let action = match self.action {
+            AutomationActionType::Download => AutomationAction::Download,
+            AutomationActionType::MoveToCategory => AutomationAction::MoveToCategory {
+                category_id: self.category_id,
+            },
+            AutomationActionType::Notify => AutomationAction::Notify {
+                channel: self
+                    .channel
+                    .ok_or("channel is required for notify action")?
+                    .into(),
+            },
+            AutomationActionType::Webhook => AutomationAction::Webhook {
+                url: self
+                    .webhook_url
+                    .clone()
+                    .ok_or("webhook url is required for webhook action")?,
+            },
+        };
+
+        Ok(action)
+    }
+}
+
+#[derive(Default)]
+pub struct AutomationRoot;
+
+#[Object]
+impl AutomationRoot {
+    async fn automation_rules(&self, ctx: &Context<'_>) -> Result<Vec<AutomationRule>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let rules = ctx
+            .data::<AutomationService<AutomationRepositoryImpl>>()?
+            .get_rules_by_user_id(claims.sub)
+            .await?
+            .into_iter()
+            .map(|rule| rule.into())
+            .collect();
+
+        Ok(rules)
+    }
+}
+
+#[derive(Default)]
+pub struct AutomationMutationRoot;
+
+#[Object]
+impl AutomationMutationRoot {
+    async fn create_automation_rule(
+        &self,
+        ctx: &Context<'_>,
+        input: AutomationRuleInput,
+    ) -> Result<AutomationRule> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let rule = ctx
+            .data::<AutomationService<AutomationRepositoryImpl>>()?
+            .create_rule(
+                claims.sub,
+                &input.name,
+                input.event.into(),
+                input.action()?,
+                input.enabled,
+            )
+            .await?;
+
+        Ok(rule.into())
+    }
+
+    async fn update_automation_rule(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "rule id")] id: i64,
+        input: AutomationRuleInput,
+    ) -> Result<AutomationRule> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let rule = ctx
+            .data::<AutomationService<AutomationRepositoryImpl>>()?
+            .update_rule(
+                claims.sub,
+                id,
+                &input.name,
+                input.event.into(),
+                input.action()?,
+                input.enabled,
+            )
+            .await?;
+
+        Ok(rule.into())
+    }
+
+    async fn delete_automation_rule(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "rule id")] id: i64,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<AutomationService<AutomationRepositoryImpl>>()?
+            .delete_rule(claims.sub, id)
+            .await?;
+
+        Ok(1)
+    }
+}
@@ -1,14 +1,150 @@
-use super::{chapter::Chapter, common::InputList, guard::AdminGuard, manga::Manga};
+use super::{
+    chapter::Chapter,
+    common::InputList,
+    guard::AdminGuard,
+    manga::{Manga, MangaOverrideInput},
+};
 
 use crate::{
-    domain::services::{chapter::ChapterService, manga::MangaService},
-    infrastructure::domain::repositories::{
-        chapter::ChapterRepositoryImpl, manga::MangaRepositoryImpl,
+    domain::{
+        entities::user::CatalogueSort,
+        services::{
+            chapter::ChapterService, library::LibraryService, manga::MangaService,
+            source::SourceService, user::UserService,
+        },
+    },
+    infrastructure::{
+        auth::Claims,
+        domain::repositories::{
+            chapter::ChapterRepositoryImpl, library::LibraryRepositoryImpl,
+            manga::MangaRepositoryImpl, source::SourceRepositoryImpl, user::UserRepositoryImpl,
+        },
     },
 };
 
-use async_graphql::{Context, Object, Result};
+use async_graphql::{Context, Object, Result, SimpleObject};
 use rayon::prelude::*;
+use std::collections::HashSet;
+
+#[derive(SimpleObject)]
+pub struct ResolvedUrl {
+    pub manga: Manga,
+    pub chapter_id: Option<i64>,
+}
+
+#[derive(SimpleObject)]
+pub struct ChapterMigration {
+    /// chapter the source no longer lists
+    pub from_chapter_id: i64,
+    /// chapter its history and download moved to
+    pub to_chapter_id: i64,
+}
+
+/// What happened to stored chapters the source no longer lists
+#[derive(SimpleObject)]
+pub struct ChapterReconciliation {
+    pub migrated: Vec<ChapterMigration>,
+    /// removed, none had history or a download
+    pub removed: Vec<i64>,
+    /// kept for their history or download
+    pub kept: Vec<i64>,
+}
+
+impl From<crate::domain::services::chapter::ChapterReconciliation> for ChapterReconciliation {
+    fn from(report: crate::domain::services::chapter::ChapterReconciliation) -> Self {
+        Self {
+            migrated: report
+                .migrated
+                .into_iter()
+                .map(|(from_chapter_id, to_chapter_id)| ChapterMigration {
+                    from_chapter_id,
+                    to_chapter_id,
+                })
+                .collect(),
+            removed: report.removed,
+            kept: report.kept,
+        }
+    }
+}
+
+/// Catalogue preferences of the logged in user, anonymous requests use the defaults
+async fn catalogue_user(ctx: &Context<'_>) -> Result<Option<crate::domain::entities::user::User>> {
+    match ctx.data::<Claims>() {
+        Ok(claims) => Ok(Some(
+            ctx.data::<UserService<UserRepositoryImpl>>()?
+                .fetch_user_by_id(claims.sub)
+                .await?,
+        )),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Refuse browsing a source the logged in user hides with their content filter
+async fn check_content_filter(ctx: &Context<'_>, source_id: i64) -> Result<()> {
+    if let Some(user) = catalogue_user(ctx).await? {
+        if user.hide_nsfw {
+            let source = ctx
+                .data::<SourceService<SourceRepositoryImpl>>()?
+                .get_source_by_id(source_id)
+                .await?;
+            if source.is_hidden_for(&user) {
+                return Err("source hidden by content filter".into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn user_id(ctx: &Context<'_>) -> Option<i64> {
+    ctx.data::<Claims>().ok().map(|claims| claims.sub)
+}
+
+/// Session of the logged in user for `source_id`, if any
+async fn source_session(ctx: &Context<'_>, source_id: i64) -> Result<Option<String>> {
+    match ctx.data::<Claims>() {
+        Ok(claims) => Ok(ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .get_session(claims.sub, source_id)
+            .await?),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Manga service calling `source_id` with the logged in user's session, if any
+async fn source_manga_svc(
+    ctx: &Context<'_>,
+    source_id: i64,
+) -> Result<MangaService<MangaRepositoryImpl>> {
+    let session = source_session(ctx, source_id).await?;
+
+    Ok(ctx
+        .data::<MangaService<MangaRepositoryImpl>>()?
+        .with_session(session))
+}
+
+/// Drop manga already in library if the user chose to hide them
+async fn apply_catalogue_preferences(
+    ctx: &Context<'_>,
+    manga: Vec<crate::domain::entities::manga::Manga>,
+) -> Result<Vec<Manga>> {
+    let library: HashSet<(i64, String)> = match catalogue_user(ctx).await? {
+        Some(user) if user.catalogue_hide_library => ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .get_manga_from_library(user.id)
+            .await?
+            .into_iter()
+            .map(|manga| (manga.source_id, manga.path))
+            .collect(),
+        _ => HashSet::new(),
+    };
+
+    Ok(manga
+        .into_par_iter()
+        .filter(|manga| !library.contains(&(manga.source_id, manga.path.clone())))
+        .map(Manga::from)
+        .collect())
+}
 
 #[derive(Default)]
 pub struct CatalogueRoot;
@@ -20,32 +156,32 @@ impl CatalogueRoot {
         ctx: &Context<'_>,
         #[graphql(desc = "source id")] source_id: i64,
         #[graphql(desc = "page")] page: i64,
+        #[graphql(desc = "bypass cached source response", default)] refresh: bool,
     ) -> Result<Vec<Manga>> {
+        check_content_filter(ctx, source_id).await?;
+
         let fetched_manga = ctx
-            .data::<MangaService<MangaRepositoryImpl>>()?
-            .fetch_source_popular_manga(source_id, page)
-            .await?
-            .into_par_iter()
-            .map(Manga::from)
-            .collect();
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .fetch_popular_manga(user_id(ctx), source_id, page, refresh)
+            .await?;
 
-        Ok(fetched_manga)
+        apply_catalogue_preferences(ctx, fetched_manga).await
     }
     async fn get_latest_manga(
         &self,
         ctx: &Context<'_>,
         #[graphql(desc = "source id")] source_id: i64,
         #[graphql(desc = "page")] page: i64,
+        #[graphql(desc = "bypass cached source response", default)] refresh: bool,
     ) -> Result<Vec<Manga>> {
+        check_content_filter(ctx, source_id).await?;
+
         let fetched_manga = ctx
-            .data::<MangaService<MangaRepositoryImpl>>()?
-            .fetch_source_latest_manga(source_id, page)
-            .await?
-            .into_par_iter()
-            .map(Manga::from)
-            .collect();
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .fetch_latest_manga(user_id(ctx), source_id, page, refresh)
+            .await?;
 
-        Ok(fetched_manga)
+        apply_catalogue_preferences(ctx, fetched_manga).await
     }
 
     async fn browse_source(
@@ -55,16 +191,55 @@ impl CatalogueRoot {
         #[graphql(desc = "page")] page: i64,
         #[graphql(desc = "query")] query: Option<String>,
         #[graphql(desc = "filters")] filters: Option<InputList>,
+        #[graphql(desc = "bypass cached source response", default)] refresh: bool,
     ) -> Result<Vec<Manga>> {
+        check_content_filter(ctx, source_id).await?;
+
         let fetched_manga = ctx
-            .data::<MangaService<MangaRepositoryImpl>>()?
-            .fetch_source_manga(source_id, page, query, filters.map(|filters| filters.0))
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .search_manga(
+                user_id(ctx),
+                source_id,
+                page,
+                query,
+                filters.map(|filters| filters.0),
+                refresh,
+            )
+            .await?;
+
+        apply_catalogue_preferences(ctx, fetched_manga).await
+    }
+
+    /// popular or latest manga depending on the user's catalogue sort
+    async fn get_catalogue_manga(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "source id")] source_id: i64,
+        #[graphql(desc = "page")] page: i64,
+        #[graphql(desc = "bypass cached source response", default)] refresh: bool,
+    ) -> Result<Vec<Manga>> {
+        check_content_filter(ctx, source_id).await?;
+
+        let sort = catalogue_user(ctx)
             .await?
-            .into_par_iter()
-            .map(Manga::from)
-            .collect();
+            .map(|user| user.catalogue_sort)
+            .unwrap_or_default();
+
+        let source_svc = ctx.data::<SourceService<SourceRepositoryImpl>>()?;
+        let fetched_manga = match sort {
+            CatalogueSort::Popular => {
+                source_svc
+                    .fetch_popular_manga(user_id(ctx), source_id, page, refresh)
+                    .await?
+            }
+            CatalogueSort::Latest => {
+                source_svc
+                    .fetch_latest_manga(user_id(ctx), source_id, page, refresh)
+                    .await?
+            }
+        };
 
-        Ok(fetched_manga)
+        apply_catalogue_preferences(ctx, fetched_manga).await
     }
 
     async fn manga_by_source_path(
@@ -73,14 +248,45 @@ impl CatalogueRoot {
         #[graphql(desc = "source id")] source_id: i64,
         #[graphql(desc = "path to manga in source")] path: String,
     ) -> Result<Manga> {
-        let manga = ctx
-            .data::<MangaService<MangaRepositoryImpl>>()?
+        let manga = source_manga_svc(ctx, source_id)
+            .await?
             .fetch_manga_by_source_path(source_id, &path)
             .await?;
 
         Ok(manga.into())
     }
 
+    async fn resolve_url(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "url of manga or chapter in source website")] url: String,
+    ) -> Result<ResolvedUrl> {
+        let manga_svc = ctx.data::<MangaService<MangaRepositoryImpl>>()?;
+        let (source_id, path) = manga_svc.resolve_source_path(&url).await?;
+
+        if let Ok(chapter) = ctx
+            .data::<ChapterService<ChapterRepositoryImpl>>()?
+            .fetch_chapter_by_source_path(source_id, &path)
+            .await
+        {
+            let manga = manga_svc.fetch_manga_by_id(chapter.manga_id, false).await?;
+
+            return Ok(ResolvedUrl {
+                manga: manga.into(),
+                chapter_id: Some(chapter.id),
+            });
+        }
+
+        let manga = manga_svc
+            .fetch_manga_by_source_path(source_id, &path)
+            .await?;
+
+        Ok(ResolvedUrl {
+            manga: manga.into(),
+            chapter_id: None,
+        })
+    }
+
     async fn manga(
         &self,
         ctx: &Context<'_>,
@@ -115,6 +321,121 @@ pub struct CatalogueMutationRoot;
 
 #[Object]
 impl CatalogueMutationRoot {
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn link_manga(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+        #[graphql(desc = "manga id from other source")] linked_manga_id: i64,
+    ) -> Result<bool> {
+        ctx.data::<MangaService<MangaRepositoryImpl>>()?
+            .link_manga(manga_id, linked_manga_id)
+            .await?;
+
+        Ok(true)
+    }
+
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn unlink_manga(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+        #[graphql(desc = "manga id from other source")] linked_manga_id: i64,
+    ) -> Result<bool> {
+        ctx.data::<MangaService<MangaRepositoryImpl>>()?
+            .unlink_manga(manga_id, linked_manga_id)
+            .await?;
+
+        Ok(true)
+    }
+
+    /// fetch chapters of a manga from source, stored chapters the source no longer lists
+    /// hand their history and download over to a chapter with the same number, are kept
+    /// when they have any or are removed
+    async fn refresh_chapters(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+    ) -> Result<ChapterReconciliation> {
+        let _ = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let manga = ctx
+            .data::<MangaService<MangaRepositoryImpl>>()?
+            .fetch_manga_by_id(manga_id, false)
+            .await?;
+        let session = source_session(ctx, manga.source_id).await?;
+        let report = ctx
+            .data::<ChapterService<ChapterRepositoryImpl>>()?
+            .with_session(session)
+            .refresh_chapters(manga.source_id, &manga.path, manga.id)
+            .await?;
+
+        Ok(report.into())
+    }
+
+    /// set metadata over the one of the source, kept when the manga is refreshed
+    async fn set_manga_override(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+        input: MangaOverrideInput,
+    ) -> Result<Manga> {
+        let _ = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let manga = ctx
+            .data::<MangaService<MangaRepositoryImpl>>()?
+            .set_manga_override(manga_id, input.into())
+            .await?
+            .into();
+
+        Ok(manga)
+    }
+
+    /// replace the other titles set by the user, the source's are kept
+    async fn set_alt_titles(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+        #[graphql(desc = "other titles of the manga")] titles: Vec<String>,
+    ) -> Result<Manga> {
+        let _ = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let manga = ctx
+            .data::<MangaService<MangaRepositoryImpl>>()?
+            .set_alt_titles(manga_id, titles)
+            .await?
+            .into();
+
+        Ok(manga)
+    }
+
+    /// order to read chapters of linked manga from, the first preferred
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn set_series_order(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga ids of a series")] manga_ids: Vec<i64>,
+    ) -> Result<bool> {
+        ctx.data::<MangaService<MangaRepositoryImpl>>()?
+            .set_series_order(&manga_ids)
+            .await?;
+
+        Ok(true)
+    }
+}
+
+/// Not part of the schema yet
+#[derive(Default)]
+pub struct ChapterMutationRoot;
+
+#[Object]
+impl ChapterMutationRoot {
     #[graphql(guard = "AdminGuard::new()")]
     async fn remove_chapter(
         &self,
@@ -1,22 +1,100 @@
 use crate::{
-    domain::services::library::LibraryService,
-    infrastructure::{auth::Claims, domain::repositories::library::LibraryRepositoryImpl},
-    presentation::graphql::{loader::UserCategoryId, schema::DatabaseLoader},
+    domain::services::{library::LibraryService, user::UserService},
+    infrastructure::{
+        auth::{self, Claims},
+        config::Config,
+        domain::repositories::{library::LibraryRepositoryImpl, user::UserRepositoryImpl},
+    },
+    presentation::graphql::{
+        library::AutoDownload,
+        loader::{UserCategoryId, UserCategoryUnreadId},
+        schema::DatabaseLoader,
+    },
 };
-use async_graphql::{dataloader::DataLoader, Context, Object, Result};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use async_graphql::{dataloader::DataLoader, Context, InputObject, Object, Result, SimpleObject};
+use std::collections::HashSet;
 
 #[derive(Debug, Clone)]
 pub struct Category {
     id: Option<i64>,
+    parent_id: Option<i64>,
+    is_default: bool,
     name: String,
+    is_private: bool,
+    has_pin: bool,
+    auto_download: AutoDownload,
+    rules: Option<SmartRules>,
+}
+
+/// Rules a manga has to match every one of to be in a smart category
+#[derive(Debug, Clone, SimpleObject)]
+pub struct SmartRules {
+    /// any of these sources, empty matches every source
+    pub source_ids: Vec<i64>,
+    /// any of these genres, empty matches every genre
+    pub genres: Vec<String>,
+    pub status: Option<String>,
+    /// has chapters not read to the end yet
+    pub unread: bool,
+    /// last read more than this many days ago, or never read
+    pub last_read_days: Option<i64>,
+}
+
+impl From<crate::domain::entities::library::SmartRules> for SmartRules {
+    fn from(rules: crate::domain::entities::library::SmartRules) -> Self {
+        Self {
+            source_ids: rules.source_ids,
+            genres: rules.genres,
+            status: rules.status,
+            unread: rules.unread,
+            last_read_days: rules.last_read_days,
+        }
+    }
+}
+
+#[derive(InputObject)]
+struct SmartRulesInput {
+    #[graphql(default)]
+    pub source_ids: Vec<i64>,
+    #[graphql(default)]
+    pub genres: Vec<String>,
+    pub status: Option<String>,
+    #[graphql(default = false)]
+    pub unread: bool,
+    pub last_read_days: Option<i64>,
+}
+
+impl From<SmartRulesInput> for crate::domain::entities::library::SmartRules {
+    fn from(input: SmartRulesInput) -> Self {
+        Self {
+            source_ids: input.source_ids,
+            genres: input
+                .genres
+                .into_iter()
+                .map(|genre| genre.trim().to_string())
+                .filter(|genre| !genre.is_empty())
+                .collect(),
+            status: input
+                .status
+                .map(|status| status.trim().to_string())
+                .filter(|status| !status.is_empty()),
+            unread: input.unread,
+            last_read_days: input.last_read_days,
+        }
+    }
 }
 
 impl Default for Category {
     fn default() -> Self {
         Self {
             id: None,
+            parent_id: None,
+            is_default: false,
             name: "Default".to_string(),
+            is_private: false,
+            has_pin: false,
+            auto_download: crate::domain::entities::library::AutoDownload::default().into(),
+            rules: None,
         }
     }
 }
@@ -24,21 +102,119 @@ impl From<crate::domain::entities::library::Category> for Category {
     fn from(val: crate::domain::entities::library::Category) -> Self {
         Self {
             id: val.id,
+            parent_id: val.parent_id,
+            is_default: val.is_default,
             name: val.name,
+            is_private: val.is_private,
+            has_pin: val.pin.is_some(),
+            auto_download: val.auto_download.into(),
+            rules: val.rules.map(SmartRules::from),
         }
     }
 }
 
+impl Category {
+    fn is_locked(&self, claims: &Claims) -> bool {
+        self.is_private
+            && !self
+                .id
+                .map(|id| claims.unlocked_categories.contains(&id))
+                .unwrap_or(false)
+    }
+}
+
+/// Leave out locked categories and the ones nested in them, parents have to come first
+fn visible_categories(
+    categories: Vec<crate::domain::entities::library::Category>,
+    claims: &Claims,
+    include_locked: bool,
+) -> Vec<Category> {
+    let mut locked_ids = HashSet::new();
+    let mut visible = vec![];
+    for category in categories.into_iter().map(Category::from) {
+        let locked = category.is_locked(claims)
+            || category
+                .parent_id
+                .map(|id| locked_ids.contains(&id))
+                .unwrap_or(false);
+        if locked {
+            locked_ids.extend(category.id);
+        }
+        if include_locked || !locked {
+            visible.push(category);
+        }
+    }
+
+    visible
+}
+
+/// Re-issue the session token with a different set of unlocked categories
+fn reissue_token(
+    ctx: &Context<'_>,
+    claims: &Claims,
+    unlocked_categories: Vec<i64>,
+    lock_generation: i64,
+) -> Result<String> {
+    let claims = Claims {
+        sub: claims.sub,
+        username: claims.username.clone(),
+        is_admin: claims.is_admin,
+        exp: claims.exp,
+        unlocked_categories,
+        lock_generation,
+    };
+
+    Ok(auth::encode_jwt(&ctx.data::<Config>()?.secret, &claims)?)
+}
+
 #[Object]
 impl Category {
     async fn id(&self) -> Option<i64> {
         self.id
     }
 
+    /// category this is nested in, null for top level categories
+    async fn parent_id(&self) -> Option<i64> {
+        self.parent_id
+    }
+
     async fn name(&self) -> String {
         self.name.clone()
     }
 
+    /// manga added to library without picking categories go here
+    async fn is_default(&self) -> bool {
+        self.is_default
+    }
+
+    async fn is_private(&self) -> bool {
+        self.is_private
+    }
+
+    /// unlocked with pin instead of account password
+    async fn has_pin(&self) -> bool {
+        self.has_pin
+    }
+
+    /// new chapters of manga in this category are downloaded by library updates
+    async fn auto_download(&self) -> AutoDownload {
+        self.auto_download
+    }
+
+    /// smart category rules, null for categories manga are added to by hand
+    async fn rules(&self) -> Option<SmartRules> {
+        self.rules.clone()
+    }
+
+    /// private and not unlocked in this session, categories nested in it are hidden with it
+    async fn locked(&self, ctx: &Context<'_>) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(self.is_locked(claims))
+    }
+
     async fn count(&self, ctx: &Context<'_>) -> Result<i64> {
         let claims = ctx
             .data::<Claims>()
@@ -50,6 +226,19 @@ impl Category {
             .await?
             .unwrap_or(0))
     }
+
+    /// unread chapters of the manga in this category and the ones nested in it
+    async fn unread_count(&self, ctx: &Context<'_>) -> Result<i64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<DataLoader<DatabaseLoader>>()?
+            .load_one(UserCategoryUnreadId(claims.sub, self.id))
+            .await?
+            .unwrap_or(0))
+    }
 }
 
 #[derive(Default)]
@@ -57,7 +246,12 @@ pub struct CategoryRoot;
 
 #[Object]
 impl CategoryRoot {
-    async fn get_categories(&self, ctx: &Context<'_>) -> Result<Vec<Category>> {
+    async fn get_categories(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "include private categories not unlocked yet", default = false)]
+        include_locked: bool,
+    ) -> Result<Vec<Category>> {
         let claims = ctx
             .data::<Claims>()
             .map_err(|_| "token not exists, please login")?;
@@ -65,24 +259,29 @@ impl CategoryRoot {
         let categories = ctx
             .data::<LibraryService<LibraryRepositoryImpl>>()?
             .get_categories_by_user_id(claims.sub)
-            .await?
-            .into_par_iter()
-            .map(|cat| cat.into())
-            .collect();
+            .await?;
 
-        Ok(categories)
+        Ok(visible_categories(categories, claims, include_locked))
     }
 
     async fn get_category(&self, ctx: &Context<'_>, id: Option<i64>) -> Result<Category> {
-        let _ = ctx
+        let claims = ctx
             .data::<Claims>()
             .map_err(|_| "token not exists, please login")?;
 
-        let category = ctx
-            .data::<LibraryService<LibraryRepositoryImpl>>()?
-            .get_category_by_id(id)
-            .await?
-            .into();
+        let library_svc = ctx.data::<LibraryService<LibraryRepositoryImpl>>()?;
+        let category: Category = library_svc.get_category_by_id(id).await?.into();
+
+        if let Some(id) = id {
+            let ancestors = library_svc.get_category_ancestors(claims.sub, id).await?;
+            if ancestors
+                .into_iter()
+                .map(Category::from)
+                .any(|category| category.is_locked(claims))
+            {
+                return Err("category is locked".into());
+            }
+        }
 
         Ok(category)
     }
@@ -97,6 +296,8 @@ impl CategoryMutationRoot {
         &self,
         ctx: &Context<'_>,
         #[graphql(desc = "category name")] name: String,
+        #[graphql(desc = "smart category rules, null if manual")] rules: Option<SmartRulesInput>,
+        #[graphql(desc = "category to nest it in, null for top level")] parent_id: Option<i64>,
     ) -> Result<Category> {
         let claims = ctx
             .data::<Claims>()
@@ -104,7 +305,7 @@ impl CategoryMutationRoot {
 
         let category = ctx
             .data::<LibraryService<LibraryRepositoryImpl>>()?
-            .create_category(claims.sub, &name)
+            .create_category(claims.sub, parent_id, &name, rules.map(Into::into))
             .await?
             .into();
 
@@ -130,6 +331,7 @@ impl CategoryMutationRoot {
         Ok(category)
     }
 
+    /// categories nested in it are deleted with it
     async fn delete_category(
         &self,
         ctx: &Context<'_>,
@@ -145,4 +347,178 @@ impl CategoryMutationRoot {
 
         Ok(1)
     }
+
+    async fn set_category_parent(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "category id")] id: i64,
+        #[graphql(desc = "category to nest it in, null moves it to top level")] parent_id: Option<
+            i64,
+        >,
+    ) -> Result<Category> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let category = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .set_category_parent(claims.sub, id, parent_id)
+            .await?
+            .into();
+
+        Ok(category)
+    }
+
+    /// move categories to the front in the given order, the others keep their order after them
+    async fn reorder_categories(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "category ids")] ids: Vec<i64>,
+    ) -> Result<Vec<Category>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let categories = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .reorder_categories(claims.sub, &ids)
+            .await?;
+
+        Ok(visible_categories(categories, claims, false))
+    }
+
+    async fn set_default_category(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "category id, null unsets the default category")] id: Option<i64>,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<LibraryService<LibraryRepositoryImpl>>()?
+            .set_default_category(claims.sub, id)
+            .await?;
+
+        Ok(1)
+    }
+
+    async fn set_category_privacy(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "category id")] id: i64,
+        #[graphql(desc = "hide category and its manga until unlocked")] is_private: bool,
+        #[graphql(desc = "pin to unlock, empty removes it, null keeps the current one")]
+        pin: Option<String>,
+    ) -> Result<Category> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let category = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .set_category_privacy(claims.sub, id, is_private, pin.as_deref())
+            .await?
+            .into();
+
+        Ok(category)
+    }
+
+    async fn set_category_auto_download(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "category id")] id: i64,
+        #[graphql(desc = "download new chapters found by library updates")] enabled: bool,
+        #[graphql(desc = "only the most recent new chapters, null downloads every one")]
+        limit: Option<i64>,
+    ) -> Result<Category> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let category = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .set_category_auto_download(
+                claims.sub,
+                id,
+                crate::domain::entities::library::AutoDownload { enabled, limit },
+            )
+            .await?
+            .into();
+
+        Ok(category)
+    }
+
+    async fn set_category_rules(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "category id")] id: i64,
+        #[graphql(desc = "rules of a smart category, null turns it into a manual one")]
+        rules: Option<SmartRulesInput>,
+    ) -> Result<Category> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let category = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .set_category_rules(claims.sub, id, rules.map(Into::into))
+            .await?
+            .into();
+
+        Ok(category)
+    }
+
+    /// Returns a new token with the category unlocked
+    async fn unlock_category(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "category id")] id: i64,
+        #[graphql(desc = "category pin, or account password when it has no pin")] secret: String,
+    ) -> Result<String> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let library_svc = ctx.data::<LibraryService<LibraryRepositoryImpl>>()?;
+        let user_svc = ctx.data::<UserService<UserRepositoryImpl>>()?;
+        let category = library_svc.get_user_category(claims.sub, id).await?;
+
+        user_svc.check_category_unlock_attempts(claims.sub)?;
+        let verified: Result<()> = if category.pin.is_some() {
+            library_svc
+                .verify_category_pin(&category, &secret)
+                .map_err(Into::into)
+        } else {
+            user_svc
+                .verify_password(&claims.username, &secret)
+                .await
+                .map_err(Into::into)
+        };
+        user_svc.record_category_unlock_attempt(claims.sub, verified.is_ok());
+        verified?;
+
+        let mut unlocked_categories = claims.unlocked_categories.clone();
+        if !unlocked_categories.contains(&id) {
+            unlocked_categories.push(id);
+        }
+        let lock_generation = user_svc.get_category_lock_generation(claims.sub).await?;
+
+        reissue_token(ctx, claims, unlocked_categories, lock_generation)
+    }
+
+    /// Returns a new token with every private category locked again, categories unlocked
+    /// by tokens issued before are locked too
+    async fn lock_categories(&self, ctx: &Context<'_>) -> Result<String> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let lock_generation = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .lock_categories(claims.sub)
+            .await?;
+
+        reissue_token(ctx, claims, vec![], lock_generation)
+    }
 }
@@ -5,20 +5,41 @@ use super::{
     source::Source,
 };
 use crate::{
-    domain::services::{chapter::ChapterService, image::ImageService, source::SourceService},
+    domain::services::{
+        chapter::ChapterService,
+        image::{
+            ImageService, DATA_SAVER_QUALITY, DATA_SAVER_WIDTH, SPRITE_COLUMNS, SPRITE_TILE_HEIGHT,
+            SPRITE_TILE_WIDTH,
+        },
+        source::SourceService,
+        user::UserService,
+    },
     infrastructure::{
         auth::Claims,
         config::Config,
         domain::repositories::{
             chapter::ChapterRepositoryImpl, image::ImageRepositoryImpl,
             image_cache::ImageCacheRepositoryImpl, source::SourceRepositoryImpl,
+            user::UserRepositoryImpl,
         },
+        storage,
     },
     presentation::graphql::schema::DatabaseLoader,
 };
-use async_graphql::{dataloader::DataLoader, Context, Object, Result};
-use chrono::{NaiveDateTime, Utc};
+use async_graphql::{dataloader::DataLoader, Context, Object, Result, SimpleObject};
+use chrono::NaiveDateTime;
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use tanoshi_vm::extension::ExtensionManager;
+
+/// Page thumbnails of a downloaded chapter laid out in a grid,
+/// page `n` is at column `n % columns` and row `n / columns`
+#[derive(Debug, SimpleObject)]
+pub struct ThumbnailSprite {
+    pub url: String,
+    pub tile_width: i32,
+    pub tile_height: i32,
+    pub columns: i32,
+}
 
 /// A type represent chapter, normalized across source
 #[derive(Debug, Clone)]
@@ -29,7 +50,10 @@ pub struct Chapter {
     pub title: String,
     pub path: String,
     pub number: f64,
+    pub sort_key: f64,
     pub scanlator: String,
+    pub groups: Vec<String>,
+    pub language: Option<String>,
     pub uploaded: chrono::NaiveDateTime,
     pub date_added: chrono::NaiveDateTime,
     pub read_progress: Option<ReadProgress>,
@@ -40,21 +64,7 @@ pub struct Chapter {
 
 impl From<tanoshi_lib::models::ChapterInfo> for Chapter {
     fn from(ch: tanoshi_lib::models::ChapterInfo) -> Self {
-        Self {
-            id: 0,
-            source_id: ch.source_id,
-            manga_id: 0,
-            title: ch.title,
-            path: ch.path,
-            number: ch.number,
-            scanlator: ch.scanlator.unwrap_or_default(),
-            uploaded: chrono::NaiveDateTime::from_timestamp(ch.uploaded, 0),
-            date_added: Utc::now().naive_utc(),
-            read_progress: None,
-            downloaded_path: None,
-            next: None,
-            prev: None,
-        }
+        crate::domain::entities::chapter::Chapter::from(ch).into()
     }
 }
 
@@ -67,7 +77,10 @@ impl From<crate::domain::entities::chapter::Chapter> for Chapter {
             title: val.title,
             path: val.path,
             number: val.number,
+            sort_key: val.sort_key,
             scanlator: val.scanlator,
+            groups: val.groups,
+            language: val.language,
             uploaded: val.uploaded,
             date_added: val.date_added,
             read_progress: None,
@@ -87,7 +100,10 @@ impl From<Chapter> for crate::domain::entities::chapter::Chapter {
             title: val.title,
             path: val.path,
             number: val.number,
+            sort_key: val.sort_key,
             scanlator: val.scanlator,
+            groups: val.groups,
+            language: val.language,
             uploaded: val.uploaded,
             date_added: val.date_added,
             downloaded_path: val.downloaded_path,
@@ -115,10 +131,25 @@ impl Chapter {
         self.number
     }
 
+    /// number chapters are ordered by, parsed from the title when it has one
+    async fn sort_key(&self) -> f64 {
+        self.sort_key
+    }
+
     async fn scanlator(&self) -> String {
         self.scanlator.clone()
     }
 
+    /// scanlation groups of this release
+    async fn groups(&self) -> Vec<String> {
+        self.groups.clone()
+    }
+
+    /// language code, null when the chapter is in the source language
+    async fn language(&self) -> Option<String> {
+        self.language.clone()
+    }
+
     async fn prev(&self) -> Option<i64> {
         self.prev
     }
@@ -168,24 +199,71 @@ impl Chapter {
         #[graphql(desc = "fetch from source", default = false)] _fetch: bool,
         #[graphql(desc = "encrypt url", default = true)] encrypt: bool,
     ) -> Result<Vec<String>> {
+        let (session, data_saver) = match ctx.data::<Claims>() {
+            Ok(claims) => (
+                ctx.data::<SourceService<SourceRepositoryImpl>>()?
+                    .get_session(claims.sub, self.source_id)
+                    .await?,
+                ctx.data::<UserService<UserRepositoryImpl>>()?
+                    .fetch_user_by_id(claims.sub)
+                    .await?
+                    .data_saver,
+            ),
+            Err(_) => (None, false),
+        };
+
         let mut pages = ctx
             .data::<ChapterService<ChapterRepositoryImpl>>()?
-            .fetch_chapter_pages(self.source_id, &self.path, &self.downloaded_path)
+            .with_session(session)
+            .fetch_chapter_pages(&self.clone().into())
             .await?;
 
         let image_svc =
             ctx.data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?;
+        // image hosts are told apart from the source they serve by the referer
+        if let Ok(source) = ctx
+            .data::<ExtensionManager>()?
+            .get_source_info(self.source_id)
+        {
+            image_svc.set_pages_referer(&pages, &source.url);
+        }
 
         if encrypt {
             let secret = &ctx.data::<Config>()?.secret;
             pages
                 .par_iter_mut()
                 .for_each(|p| *p = image_svc.encrypt_image_url(secret, p).unwrap());
+            // honored by the image endpoint, urls not encrypted aren't served through it
+            if data_saver {
+                pages.par_iter_mut().for_each(|p| {
+                    *p = format!("{p}?width={DATA_SAVER_WIDTH}&quality={DATA_SAVER_QUALITY}")
+                });
+            }
         }
 
         Ok(pages)
     }
 
+    /// thumbnails of every page in one image, fetched from `/sprite/:url`
+    async fn thumbnail_sprite(&self, ctx: &Context<'_>) -> Result<Option<ThumbnailSprite>> {
+        // sprites are composed from downloads on disk only
+        let downloaded_path = match self.downloaded_path.as_ref() {
+            Some(downloaded_path) if !storage::is_remote(downloaded_path) => downloaded_path,
+            _ => return Ok(None),
+        };
+
+        let url = ctx
+            .data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?
+            .encrypt_image_url(&ctx.data::<Config>()?.secret, downloaded_path)?;
+
+        Ok(Some(ThumbnailSprite {
+            url,
+            tile_width: SPRITE_TILE_WIDTH as _,
+            tile_height: SPRITE_TILE_HEIGHT as _,
+            columns: SPRITE_COLUMNS as _,
+        }))
+    }
+
     async fn downloaded_path(&self) -> Option<String> {
         self.downloaded_path.clone()
     }
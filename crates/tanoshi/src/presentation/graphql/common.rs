@@ -28,6 +28,40 @@ pub struct ReadProgress {
     pub is_complete: bool,
 }
 
+#[derive(Debug, Clone, SimpleObject)]
+pub struct ChapterProgress {
+    pub chapter_id: i64,
+    pub number: f64,
+    pub last_page_read: Option<i64>,
+    pub is_complete: bool,
+    pub read_at: Option<NaiveDateTime>,
+}
+
+impl From<crate::domain::entities::history::ChapterProgress> for ChapterProgress {
+    fn from(progress: crate::domain::entities::history::ChapterProgress) -> Self {
+        Self {
+            chapter_id: progress.chapter_id,
+            number: progress.number,
+            last_page_read: progress.last_page_read,
+            is_complete: progress.is_complete,
+            read_at: progress.read_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct DropOff {
+    pub manga_id: i64,
+    pub total_chapters: i64,
+    pub completed_chapters: i64,
+    /// number of the furthest chapter opened
+    pub last_read_chapter_number: Option<f64>,
+    pub last_read_at: Option<NaiveDateTime>,
+    pub stalled: bool,
+    /// every chapter ordered by number, for rendering a heatmap
+    pub chapters: Vec<ChapterProgress>,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct InputList(pub Vec<Input>);
 
@@ -1,11 +1,13 @@
 use super::{chapter::Chapter, common::Cursor, guard::AdminGuard};
 use crate::{
     domain::services::download::DownloadService,
-    infrastructure::{config::Config, domain::repositories::download::DownloadRepositoryImpl},
+    infrastructure::{
+        auth::Claims, config::Config, domain::repositories::download::DownloadRepositoryImpl,
+    },
 };
 use async_graphql::{
     connection::{query, Connection, Edge, EmptyFields},
-    Context, Error, Object, Result, SimpleObject,
+    Context, Enum, Error, Object, Result, SimpleObject,
 };
 use chrono::Utc;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
@@ -21,6 +23,11 @@ pub struct DownloadQueueEntry {
     pub downloaded: i64,
     pub total: i64,
     pub priority: i64,
+    pub paused: bool,
+    /// gave up after too many attempts, see retryFailedDownloads
+    pub failed: bool,
+    pub attempts: i64,
+    pub last_error: Option<String>,
 }
 
 impl From<crate::domain::entities::download::DownloadQueueEntry> for DownloadQueueEntry {
@@ -35,10 +42,89 @@ impl From<crate::domain::entities::download::DownloadQueueEntry> for DownloadQue
             downloaded: queue.downloaded,
             total: queue.total,
             priority: queue.priority,
+            paused: queue.paused,
+            failed: queue.failed,
+            attempts: queue.attempts,
+            last_error: queue.last_error,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum CleanupReason {
+    Read,
+    PerMangaLimit,
+    DiskQuota,
+}
+
+impl From<crate::domain::entities::download::CleanupReason> for CleanupReason {
+    fn from(reason: crate::domain::entities::download::CleanupReason) -> Self {
+        use crate::domain::entities::download::CleanupReason as R;
+        match reason {
+            R::Read => Self::Read,
+            R::PerMangaLimit => Self::PerMangaLimit,
+            R::DiskQuota => Self::DiskQuota,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct DownloadCleanupCandidate {
+    pub chapter_id: i64,
+    pub manga_id: i64,
+    pub manga_title: String,
+    pub chapter_title: String,
+    /// bytes freed by removing the chapter
+    pub size: i64,
+    pub reason: CleanupReason,
+}
+
+impl From<crate::domain::entities::download::CleanupCandidate> for DownloadCleanupCandidate {
+    fn from(candidate: crate::domain::entities::download::CleanupCandidate) -> Self {
+        Self {
+            chapter_id: candidate.file.chapter_id,
+            manga_id: candidate.file.manga_id,
+            manga_title: candidate.file.manga_title,
+            chapter_title: candidate.file.chapter_title,
+            size: candidate.size as i64,
+            reason: candidate.reason.into(),
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct DownloadCleanupReport {
+    pub chapters: Vec<DownloadCleanupCandidate>,
+    pub total_size: i64,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct MangaDownloadUsage {
+    pub manga_id: i64,
+    pub manga_title: String,
+    pub chapters: i64,
+    pub size: i64,
+}
+
+impl From<crate::domain::entities::download::MangaDownloadUsage> for MangaDownloadUsage {
+    fn from(usage: crate::domain::entities::download::MangaDownloadUsage) -> Self {
+        Self {
+            manga_id: usage.manga_id,
+            manga_title: usage.manga_title,
+            chapters: usage.chapters,
+            size: usage.size,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct DownloadUsage {
+    /// bytes taken by every downloaded chapter
+    pub total_size: i64,
+    /// largest first
+    pub manga: Vec<MangaDownloadUsage>,
+}
+
 #[derive(Default)]
 pub struct DownloadRoot;
 
@@ -67,6 +153,42 @@ impl DownloadRoot {
         Ok(queue)
     }
 
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn download_usage(&self, ctx: &Context<'_>) -> Result<DownloadUsage> {
+        let manga: Vec<MangaDownloadUsage> = ctx
+            .data::<DownloadService<DownloadRepositoryImpl>>()?
+            .get_download_usage()
+            .await?
+            .into_iter()
+            .map(|usage| usage.into())
+            .collect();
+
+        Ok(DownloadUsage {
+            total_size: manga.iter().map(|m| m.size).sum(),
+            manga,
+        })
+    }
+
+    /// chapters the next cleanup would remove under the configured retention policy
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn download_cleanup_report(&self, ctx: &Context<'_>) -> Result<DownloadCleanupReport> {
+        let policy = (&ctx.data::<Config>()?.download_retention).into();
+
+        let chapters: Vec<DownloadCleanupCandidate> = ctx
+            .data::<DownloadService<DownloadRepositoryImpl>>()?
+            .plan_cleanup(&policy)
+            .await?
+            .into_iter()
+            .map(|c| c.into())
+            .collect();
+        let total_size = chapters.iter().map(|c| c.size).sum();
+
+        Ok(DownloadCleanupReport {
+            chapters,
+            total_size,
+        })
+    }
+
     #[graphql(guard = "AdminGuard::new()")]
     async fn get_downloaded_chapters(
         &self,
@@ -139,7 +261,10 @@ impl DownloadRoot {
                             title: e.title,
                             path: e.path,
                             number: e.number,
+                            sort_key: e.number,
                             scanlator: e.scanlator,
+                            groups: vec![],
+                            language: None,
                             uploaded: e.uploaded,
                             date_added: e.date_added,
                             read_progress: None,
@@ -192,6 +317,27 @@ impl DownloadMutationRoot {
         Ok(len)
     }
 
+    /// queue chapters of many manga at once, returns chapters queued
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn download_manga(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga ids")] manga_ids: Vec<i64>,
+        #[graphql(desc = "only chapters not read to the end yet", default = true)]
+        unread_only: bool,
+    ) -> Result<i64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let count = ctx
+            .data::<DownloadService<DownloadRepositoryImpl>>()?
+            .download_manga_chapters(claims.sub, &manga_ids, unread_only)
+            .await?;
+
+        Ok(count as i64)
+    }
+
     #[graphql(guard = "AdminGuard::new()")]
     async fn remove_chapters_from_queue(&self, ctx: &Context<'_>, ids: Vec<i64>) -> Result<i64> {
         let len = ids.len() as i64;
@@ -202,6 +348,83 @@ impl DownloadMutationRoot {
         Ok(len)
     }
 
+    /// keep chapters in queue without downloading them until resumed
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn pause_chapters_in_queue(&self, ctx: &Context<'_>, ids: Vec<i64>) -> Result<i64> {
+        let len = ids.len() as i64;
+        ctx.data::<DownloadService<DownloadRepositoryImpl>>()?
+            .pause_chapters(ids)
+            .await?;
+
+        Ok(len)
+    }
+
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn resume_chapters_in_queue(&self, ctx: &Context<'_>, ids: Vec<i64>) -> Result<i64> {
+        let len = ids.len() as i64;
+        ctx.data::<DownloadService<DownloadRepositoryImpl>>()?
+            .resume_chapters(ids)
+            .await?;
+
+        Ok(len)
+    }
+
+    /// queue chapters that failed too many times again, returns how many
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn retry_failed_downloads(&self, ctx: &Context<'_>) -> Result<i64> {
+        let chapters = ctx
+            .data::<DownloadService<DownloadRepositoryImpl>>()?
+            .retry_failed_downloads()
+            .await?;
+
+        Ok(chapters as i64)
+    }
+
+    /// remove downloads now under the configured retention policy, returns how many
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn run_download_cleanup(&self, ctx: &Context<'_>) -> Result<i64> {
+        let policy = (&ctx.data::<Config>()?.download_retention).into();
+
+        let removed = ctx
+            .data::<DownloadService<DownloadRepositoryImpl>>()?
+            .cleanup(&policy)
+            .await?;
+
+        Ok(removed as i64)
+    }
+
+    /// check downloaded archives in the background, queueing broken chapters again
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn verify_downloads(&self, ctx: &Context<'_>) -> Result<bool> {
+        ctx.data::<DownloadService<DownloadRepositoryImpl>>()?
+            .verify_downloads()?;
+
+        Ok(true)
+    }
+
+    /// move downloads into the page store when the format is dedup and remove pages
+    /// no chapter uses, in the background
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn compact_downloads(&self, ctx: &Context<'_>) -> Result<bool> {
+        ctx.data::<DownloadService<DownloadRepositoryImpl>>()?
+            .compact_downloads()?;
+
+        Ok(true)
+    }
+
+    /// re-encode the pages of downloaded archives, and local ones when configured, in the
+    /// background, admins get how much space every manga saved in their inbox
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn optimize_downloads(&self, ctx: &Context<'_>) -> Result<bool> {
+        if ctx.data::<Config>()?.archive_optimize.is_none() {
+            return Err("archive optimization is not configured".into());
+        }
+        ctx.data::<DownloadService<DownloadRepositoryImpl>>()?
+            .optimize_downloads()?;
+
+        Ok(true)
+    }
+
     #[graphql(guard = "AdminGuard::new()")]
     async fn remove_downloaded_chapters(&self, ctx: &Context<'_>, ids: Vec<i64>) -> Result<i64> {
         let len = ids.len() as i64;
@@ -212,6 +435,16 @@ impl DownloadMutationRoot {
         Ok(len)
     }
 
+    /// download `ids` first in the given order, chapters not listed keep their order after them
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn reorder_download_queue(&self, ctx: &Context<'_>, ids: Vec<i64>) -> Result<bool> {
+        ctx.data::<DownloadService<DownloadRepositoryImpl>>()?
+            .reorder_queue(ids)
+            .await?;
+
+        Ok(true)
+    }
+
     #[graphql(guard = "AdminGuard::new()")]
     async fn update_chapter_priority(
         &self,
@@ -0,0 +1,198 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Seek, SeekFrom},
+};
+
+use anyhow::anyhow;
+use async_graphql::{Context, Enum, Object, Result, SimpleObject};
+use chrono::NaiveDateTime;
+
+use crate::{
+    application::worker::export::{ExportJobs, ExportStatus},
+    domain::services::{
+        export::{ExportError, ExportService},
+        user::UserService,
+    },
+    infrastructure::{
+        auth::Claims,
+        config::Config,
+        domain::repositories::{
+            chapter::ChapterRepositoryImpl, image::ImageRepositoryImpl,
+            library::LibraryRepositoryImpl, manga::MangaRepositoryImpl, user::UserRepositoryImpl,
+        },
+        notification::Notification,
+    },
+};
+
+type ChapterExportService = ExportService<
+    ChapterRepositoryImpl,
+    MangaRepositoryImpl,
+    ImageRepositoryImpl,
+    LibraryRepositoryImpl,
+>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum DeviceFormat {
+    Epub,
+    Pdf,
+}
+
+impl DeviceFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            DeviceFormat::Epub => "epub",
+            DeviceFormat::Pdf => "pdf",
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            DeviceFormat::Epub => "application/epub+zip",
+            DeviceFormat::Pdf => "application/pdf",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum ExportJobStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct ExportJob {
+    pub id: u64,
+    pub status: ExportJobStatus,
+    pub filename: Option<String>,
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl ExportJob {
+    fn new(id: u64, job: crate::application::worker::export::ExportJob) -> Self {
+        let (status, filename, error) = match job.status {
+            ExportStatus::Running => (ExportJobStatus::Running, None, None),
+            ExportStatus::Completed { filename } => {
+                (ExportJobStatus::Completed, Some(filename), None)
+            }
+            ExportStatus::Failed { error } => (ExportJobStatus::Failed, None, Some(error)),
+        };
+
+        Self {
+            id,
+            status,
+            filename,
+            error,
+            created_at: job.created_at,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ExportRoot;
+
+#[Object]
+impl ExportRoot {
+    /// status of an export started by the current user, kept for an hour after it's done
+    async fn export_job(&self, ctx: &Context<'_>, id: u64) -> Result<Option<ExportJob>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<ExportJobs>()?
+            .get(id, claims.sub)
+            .await
+            .map(|job| ExportJob::new(id, job)))
+    }
+}
+
+/// Render a chapter into `file` and email it to the device of `user_id`,
+/// unless it's larger than the mail server takes
+#[allow(clippy::too_many_arguments)]
+async fn send_to_device(
+    svc: ChapterExportService,
+    notifier: Notification<UserRepositoryImpl>,
+    user_id: i64,
+    chapter_id: i64,
+    format: DeviceFormat,
+    rtl: bool,
+    max_size: u64,
+    file: BufWriter<File>,
+) -> Result<String, ExportError> {
+    let (filename, file) = match format {
+        DeviceFormat::Epub => {
+            svc.export_epub(user_id, &[chapter_id], None, rtl, file)
+                .await?
+        }
+        DeviceFormat::Pdf => svc.export_pdf(&[chapter_id], rtl, file).await?,
+    };
+
+    let mut file = file.into_inner().map_err(|e| e.into_error())?;
+    let size = file.metadata()?.len();
+    if size > max_size {
+        return Err(anyhow!(
+            "{filename} is {:.1} MB, more than the {:.1} MB email attachments are limited to",
+            size as f64 / 1048576.0,
+            max_size as f64 / 1048576.0
+        )
+        .into());
+    }
+
+    let mut data = Vec::with_capacity(size as usize);
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut data)?;
+
+    notifier
+        .send_file_to_device(user_id, &filename, format.content_type(), data)
+        .await?;
+
+    Ok(filename)
+}
+
+#[derive(Default)]
+pub struct ExportMutationRoot;
+
+#[Object]
+impl ExportMutationRoot {
+    /// email a chapter to the device email of the current user, returns the id of the export job
+    async fn send_chapter_to_device(
+        &self,
+        ctx: &Context<'_>,
+        chapter_id: i64,
+        #[graphql(default_with = "DeviceFormat::Epub")] format: DeviceFormat,
+        #[graphql(desc = "lay out spreads right to left", default)] rtl: bool,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let max_size = ctx
+            .data::<Config>()?
+            .smtp
+            .as_ref()
+            .ok_or("smtp is not configured")?
+            .max_attachment_size;
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .fetch_user_by_id(claims.sub)
+            .await?
+            .device_email
+            .ok_or("device email is not set in profile")?;
+
+        let svc = ctx.data::<ChapterExportService>()?.clone();
+        let notifier = ctx.data::<Notification<UserRepositoryImpl>>()?.clone();
+        let user_id = claims.sub;
+
+        let id = ctx
+            .data::<ExportJobs>()?
+            .spawn(user_id, format.extension(), move |file| {
+                send_to_device(
+                    svc, notifier, user_id, chapter_id, format, rtl, max_size, file,
+                )
+            })
+            .await?;
+
+        Ok(id)
+    }
+}
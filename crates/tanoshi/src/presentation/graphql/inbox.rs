@@ -0,0 +1,178 @@
+use super::common::Cursor;
+use crate::{
+    domain::services::inbox::InboxService,
+    infrastructure::{auth::Claims, domain::repositories::inbox::InboxRepositoryImpl},
+};
+use async_graphql::{
+    connection::{query, Connection, Edge, EmptyFields},
+    Context, Enum, Error, Object, Result, SimpleObject,
+};
+use chrono::{NaiveDateTime, Utc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum InboxKind {
+    Chapter,
+    Download,
+    Announcement,
+    Alert,
+}
+
+impl From<crate::domain::entities::inbox::InboxKind> for InboxKind {
+    fn from(kind: crate::domain::entities::inbox::InboxKind) -> Self {
+        use crate::domain::entities::inbox::InboxKind as K;
+        match kind {
+            K::Chapter => Self::Chapter,
+            K::Download => Self::Download,
+            K::Announcement => Self::Announcement,
+            K::Alert => Self::Alert,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct InboxNotification {
+    pub id: i64,
+    pub kind: InboxKind,
+    pub title: Option<String>,
+    pub body: String,
+    pub url: Option<String>,
+    pub is_read: bool,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<crate::domain::entities::inbox::InboxNotification> for InboxNotification {
+    fn from(notification: crate::domain::entities::inbox::InboxNotification) -> Self {
+        Self {
+            id: notification.id,
+            kind: notification.kind.into(),
+            title: notification.title,
+            body: notification.body,
+            url: notification.url,
+            is_read: notification.is_read,
+            created_at: notification.created_at,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InboxRoot;
+
+#[Object]
+impl InboxRoot {
+    async fn inbox(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "only return unread notifications", default = false)] unread_only: bool,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<Cursor, InboxNotification, EmptyFields, EmptyFields>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let inbox_svc = ctx.data::<InboxService<InboxRepositoryImpl>>()?;
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<Cursor>, before: Option<Cursor>, first, last| async move {
+                // next second so notifications created just now are included
+                let after_cursor = after.unwrap_or_else(|| Cursor(Utc::now().timestamp() + 1, 0));
+                let before_cursor = before.unwrap_or(Cursor(0, 0));
+
+                let edges = inbox_svc
+                    .get_notifications(
+                        claims.sub,
+                        unread_only,
+                        after_cursor.0,
+                        after_cursor.1,
+                        before_cursor.0,
+                        before_cursor.1,
+                        first,
+                        last,
+                    )
+                    .await?;
+
+                let mut has_previous_page = false;
+                if let Some(e) = edges.first() {
+                    has_previous_page = !inbox_svc
+                        .get_notifications(
+                            claims.sub,
+                            unread_only,
+                            Utc::now().timestamp() + 1,
+                            0,
+                            e.created_at.timestamp(),
+                            e.id,
+                            None,
+                            Some(1),
+                        )
+                        .await?
+                        .is_empty();
+                }
+
+                let mut has_next_page = false;
+                if let Some(e) = edges.last() {
+                    has_next_page = !inbox_svc
+                        .get_notifications(
+                            claims.sub,
+                            unread_only,
+                            e.created_at.timestamp(),
+                            e.id,
+                            0,
+                            0,
+                            Some(1),
+                            None,
+                        )
+                        .await?
+                        .is_empty();
+                }
+
+                let mut connection = Connection::new(has_previous_page, has_next_page);
+                connection.edges.extend(
+                    edges
+                        .into_iter()
+                        .map(|e| Edge::new(Cursor(e.created_at.timestamp(), e.id), e.into())),
+                );
+
+                Ok::<_, Error>(connection)
+            },
+        )
+        .await
+    }
+
+    async fn inbox_unread_count(&self, ctx: &Context<'_>) -> Result<i64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<InboxService<InboxRepositoryImpl>>()?
+            .get_unread_count(claims.sub)
+            .await?)
+    }
+}
+
+#[derive(Default)]
+pub struct InboxMutationRoot;
+
+#[Object]
+impl InboxMutationRoot {
+    async fn mark_inbox_as_read(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "notification ids, null marks every notification")] ids: Option<Vec<i64>>,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<InboxService<InboxRepositoryImpl>>()?
+            .mark_as_read(claims.sub, ids.as_deref())
+            .await?)
+    }
+}
@@ -1,30 +1,114 @@
+use std::collections::HashMap;
+
 use super::{
-    common::Cursor,
+    common::{Cursor, DropOff},
     manga::Manga,
-    recent::{RecentChapter, RecentUpdate},
+    recent::{ChapterAddition, LibraryAddition, RecentChapter, RecentUpdate},
 };
 use crate::{
-    domain::services::{
-        chapter::ChapterService, history::HistoryService, library::LibraryService,
-        tracker::TrackerService,
+    application::worker::{
+        automation::{AutomationSender, Event as AutomationEvent},
+        download_ahead::{Command as DownloadAheadCommand, DownloadAheadSender},
+        library_import::{ImportJob, ImportSender},
+        prefetch::{Command as PrefetchCommand, PrefetchSender},
+    },
+    domain::{
+        entities::library::LibraryQuery,
+        services::{
+            chapter::ChapterService, history::HistoryService, image::ImageService,
+            library::LibraryService, manga::MangaService, tracker::TrackerService,
+        },
     },
     infrastructure::{
         auth::Claims,
         domain::repositories::{
             chapter::ChapterRepositoryImpl, history::HistoryRepositoryImpl,
-            library::LibraryRepositoryImpl, tracker::TrackerRepositoryImpl,
+            image::ImageRepositoryImpl, image_cache::ImageCacheRepositoryImpl,
+            library::LibraryRepositoryImpl, manga::MangaRepositoryImpl,
+            tracker::TrackerRepositoryImpl,
         },
+        remote_library::RemoteServer,
     },
 };
 use async_graphql::{
     connection::{query, Connection, Edge, EmptyFields},
     Error,
 };
-use async_graphql::{Context, Object, Result};
+use async_graphql::{Context, Enum, InputObject, Object, Result, SimpleObject};
 use chrono::Utc;
 
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum ImportServer {
+    Komga,
+    Kavita,
+}
+
+impl From<ImportServer> for RemoteServer {
+    fn from(server: ImportServer) -> Self {
+        match server {
+            ImportServer::Komga => Self::Komga,
+            ImportServer::Kavita => Self::Kavita,
+        }
+    }
+}
+
+#[derive(InputObject)]
+struct ImportLibraryInput {
+    pub server: ImportServer,
+    /// base url of the server, e.g. `http://komga:25600`
+    pub url: String,
+    pub username: String,
+    pub password: String,
+    /// installed source serving the same server, series are matched by title
+    pub source_id: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum LibrarySort {
+    Title,
+    LastRead,
+    LatestChapter,
+    UnreadCount,
+    DateAdded,
+}
+
+impl From<LibrarySort> for crate::domain::entities::library::LibrarySort {
+    fn from(sort: LibrarySort) -> Self {
+        match sort {
+            LibrarySort::Title => Self::Title,
+            LibrarySort::LastRead => Self::LastRead,
+            LibrarySort::LatestChapter => Self::LatestChapter,
+            LibrarySort::UnreadCount => Self::UnreadCount,
+            LibrarySort::DateAdded => Self::DateAdded,
+        }
+    }
+}
+
+/// Manga in library likely to be the same series from different sources
+#[derive(SimpleObject)]
+pub struct DuplicateGroup {
+    pub manga: Vec<Manga>,
+}
+
+/// Download new chapters found by library updates
+#[derive(Debug, Clone, Copy, SimpleObject)]
+pub struct AutoDownload {
+    pub enabled: bool,
+    /// only the most recent new chapters, null downloads every one
+    pub limit: Option<i64>,
+}
+
+impl From<crate::domain::entities::library::AutoDownload> for AutoDownload {
+    fn from(auto_download: crate::domain::entities::library::AutoDownload) -> Self {
+        Self {
+            enabled: auto_download.enabled,
+            limit: auto_download.limit,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct LibraryRoot;
 
@@ -35,22 +119,240 @@ impl LibraryRoot {
         ctx: &Context<'_>,
         #[graphql(desc = "refresh data from source", default = false)] _refresh: bool,
         #[graphql(desc = "category id")] category_id: Option<i64>,
+        #[graphql(desc = "only manga with unread chapters not read for this many days")]
+        stalled_days: Option<i64>,
+        #[graphql(desc = "only manga with every one of these tags")] tag_ids: Option<Vec<i64>>,
+        #[graphql(desc = "only manga with a title or tag containing this, case insensitive")]
+        search: Option<String>,
+        #[graphql(desc = "order of the manga", default_with = "LibrarySort::Title")]
+        sort: LibrarySort,
+        #[graphql(desc = "reverse the order", default = false)] descending: bool,
+        #[graphql(desc = "only manga with unread chapters", default = false)] unread_only: bool,
+        #[graphql(desc = "only manga with downloaded chapters", default = false)]
+        downloaded_only: bool,
+        #[graphql(desc = "only manga from these sources")] source_ids: Option<Vec<i64>>,
+        #[graphql(desc = "only manga with this status, case insensitive")] status: Option<String>,
     ) -> Result<Vec<Manga>> {
         let claims = ctx
             .data::<Claims>()
             .map_err(|_| "token not exists, please login")?;
 
+        let stalled_manga_ids = match stalled_days {
+            Some(days) => Some(
+                ctx.data::<HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>>()?
+                    .get_stalled_manga_ids(claims.sub, days)
+                    .await?,
+            ),
+            None => None,
+        };
+
+        let library_svc = ctx.data::<LibraryService<LibraryRepositoryImpl>>()?;
+
+        if let Some(category_id) = category_id {
+            let locked = library_svc
+                .get_category_ancestors(claims.sub, category_id)
+                .await?
+                .iter()
+                .any(|category| {
+                    category.is_private
+                        && !category
+                            .id
+                            .map(|id| claims.unlocked_categories.contains(&id))
+                            .unwrap_or(false)
+                });
+            if locked {
+                return Err("category is locked".into());
+            }
+        }
+
+        let hidden_manga_ids = library_svc
+            .get_hidden_manga_ids(claims.sub, &claims.unlocked_categories)
+            .await?;
+
+        let tag_ids = tag_ids.unwrap_or_default();
+        let search = search
+            .map(|search| search.trim().to_lowercase())
+            .filter(|search| !search.is_empty());
+        let tags = if tag_ids.is_empty() && search.is_none() {
+            HashMap::new()
+        } else {
+            library_svc.get_library_tags(claims.sub).await?
+        };
+
+        let query = LibraryQuery {
+            sort: sort.into(),
+            descending,
+            unread_only,
+            downloaded_only,
+            source_ids: source_ids.unwrap_or_default(),
+            status: status
+                .map(|status| status.trim().to_string())
+                .filter(|status| !status.is_empty()),
+        };
+
+        let manga = library_svc
+            .get_manga_from_library_by_category_id(claims.sub, category_id, &query)
+            .await?;
         let manga = ctx
-            .data::<LibraryService<LibraryRepositoryImpl>>()?
-            .get_manga_from_library_by_category_id(claims.sub, category_id)
+            .data::<MangaService<MangaRepositoryImpl>>()?
+            .with_overrides(manga)
             .await?
             .into_par_iter()
+            .filter(|m| !hidden_manga_ids.contains(&m.id))
+            .filter(|m| {
+                stalled_manga_ids
+                    .as_ref()
+                    .map(|ids| ids.contains(&m.id))
+                    .unwrap_or(true)
+            })
+            .filter(|m| {
+                let manga_tags = tags.get(&m.id).map(Vec::as_slice).unwrap_or_default();
+                tag_ids
+                    .iter()
+                    .all(|id| manga_tags.iter().any(|tag| tag.id == *id))
+                    && search
+                        .as_ref()
+                        .map(|search| {
+                            m.title.to_lowercase().contains(search)
+                                || m.alt_titles
+                                    .iter()
+                                    .any(|title| title.to_lowercase().contains(search))
+                                || manga_tags
+                                    .iter()
+                                    .any(|tag| tag.name.to_lowercase().contains(search))
+                        })
+                        .unwrap_or(true)
+            })
             .map(|m| m.into())
             .collect();
 
         Ok(manga)
     }
 
+    async fn drop_off(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+        #[graphql(desc = "days without reading before a manga is stalled", default = 30)]
+        stalled_days: i64,
+    ) -> Result<DropOff> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let drop_off = ctx
+            .data::<HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>>()?
+            .get_drop_off(claims.sub, manga_id)
+            .await?;
+
+        Ok(DropOff {
+            manga_id: drop_off.manga_id,
+            total_chapters: drop_off.chapters.len() as i64,
+            completed_chapters: drop_off.completed_chapters() as i64,
+            last_read_chapter_number: drop_off.last_read_chapter().map(|ch| ch.number),
+            last_read_at: drop_off.last_read_at(),
+            stalled: drop_off
+                .is_stalled(Utc::now().naive_utc() - chrono::Duration::days(stalled_days)),
+            chapters: drop_off.chapters.into_iter().map(|ch| ch.into()).collect(),
+        })
+    }
+
+    /// manga in library with similar titles across sources, grouped
+    async fn library_duplicates(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "title similarity from 0 to 1", default = 0.85)] threshold: f64,
+    ) -> Result<Vec<DuplicateGroup>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let library_svc = ctx.data::<LibraryService<LibraryRepositoryImpl>>()?;
+        let hidden_manga_ids = library_svc
+            .get_hidden_manga_ids(claims.sub, &claims.unlocked_categories)
+            .await?;
+
+        let groups = library_svc
+            .get_duplicates(claims.sub, threshold)
+            .await?
+            .into_iter()
+            .map(|group| {
+                group
+                    .into_iter()
+                    .filter(|m| !hidden_manga_ids.contains(&m.id))
+                    .map(|m| m.into())
+                    .collect::<Vec<Manga>>()
+            })
+            .filter(|manga| manga.len() > 1)
+            .map(|manga| DuplicateGroup { manga })
+            .collect();
+
+        Ok(groups)
+    }
+
+    async fn download_ahead(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+    ) -> Result<i64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .get_download_ahead(claims.sub, manga_id)
+            .await?)
+    }
+
+    /// chapter languages set for the manga, null if it uses the ones of the user
+    async fn chapter_languages(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+    ) -> Result<Option<Vec<String>>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .get_chapter_languages(claims.sub, manga_id)
+            .await?)
+    }
+
+    /// scanlation group preferred for the manga, null if not set
+    async fn preferred_group(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+    ) -> Result<Option<String>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .get_preferred_group(claims.sub, manga_id)
+            .await?)
+    }
+
+    async fn auto_download(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+    ) -> Result<AutoDownload> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .get_auto_download(claims.sub, manga_id)
+            .await?
+            .into())
+    }
+
     async fn recent_updates(
         &self,
         ctx: &Context<'_>,
@@ -77,6 +379,7 @@ impl LibraryRoot {
                 let edges = library_svc
                     .get_library_recent_updates(
                         claims.sub,
+                        &claims.unlocked_categories,
                         after_cursor.0,
                         after_cursor.1,
                         before_cursor.0,
@@ -91,6 +394,7 @@ impl LibraryRoot {
                     has_previous_page = !library_svc
                         .get_library_recent_updates(
                             claims.sub,
+                            &claims.unlocked_categories,
                             Utc::now().timestamp(),
                             1,
                             e.uploaded.timestamp(),
@@ -107,6 +411,7 @@ impl LibraryRoot {
                     has_next_page = !library_svc
                         .get_library_recent_updates(
                             claims.sub,
+                            &claims.unlocked_categories,
                             e.uploaded.timestamp(),
                             e.chapter_id,
                             0,
@@ -131,6 +436,174 @@ impl LibraryRoot {
         .await
     }
 
+    /// manga in the order they were added to the library, newest first
+    async fn recently_added_manga(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<Cursor, LibraryAddition, EmptyFields, EmptyFields>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let library_svc = ctx.data::<LibraryService<LibraryRepositoryImpl>>()?;
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<Cursor>, before: Option<Cursor>, first, last| async move {
+                let after_cursor = after.unwrap_or_else(|| Cursor(Utc::now().timestamp(), 1));
+                let before_cursor = before.unwrap_or(Cursor(0, 0));
+
+                let edges = library_svc
+                    .get_library_additions(
+                        claims.sub,
+                        &claims.unlocked_categories,
+                        after_cursor.0,
+                        after_cursor.1,
+                        before_cursor.0,
+                        before_cursor.1,
+                        first,
+                        last,
+                    )
+                    .await?;
+
+                let mut has_previous_page = false;
+                if let Some(e) = edges.first() {
+                    has_previous_page = !library_svc
+                        .get_library_additions(
+                            claims.sub,
+                            &claims.unlocked_categories,
+                            Utc::now().timestamp(),
+                            1,
+                            e.date_added.timestamp(),
+                            e.manga_id,
+                            None,
+                            Some(1),
+                        )
+                        .await?
+                        .is_empty();
+                }
+
+                let mut has_next_page = false;
+                if let Some(e) = edges.last() {
+                    has_next_page = !library_svc
+                        .get_library_additions(
+                            claims.sub,
+                            &claims.unlocked_categories,
+                            e.date_added.timestamp(),
+                            e.manga_id,
+                            0,
+                            0,
+                            Some(1),
+                            None,
+                        )
+                        .await?
+                        .is_empty();
+                }
+
+                let mut connection = Connection::new(has_previous_page, has_next_page);
+                connection.edges.extend(
+                    edges
+                        .into_iter()
+                        .map(|e| Edge::new(Cursor(e.date_added.timestamp(), e.manga_id), e.into())),
+                );
+
+                Ok::<_, Error>(connection)
+            },
+        )
+        .await
+    }
+
+    /// chapters found for library manga after they were added, in the order they were found
+    async fn recently_added_chapters(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Connection<Cursor, ChapterAddition, EmptyFields, EmptyFields>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let library_svc = ctx.data::<LibraryService<LibraryRepositoryImpl>>()?;
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<Cursor>, before: Option<Cursor>, first, last| async move {
+                let after_cursor = after.unwrap_or_else(|| Cursor(Utc::now().timestamp(), 1));
+                let before_cursor = before.unwrap_or(Cursor(0, 0));
+
+                let edges = library_svc
+                    .get_chapter_additions(
+                        claims.sub,
+                        &claims.unlocked_categories,
+                        after_cursor.0,
+                        after_cursor.1,
+                        before_cursor.0,
+                        before_cursor.1,
+                        first,
+                        last,
+                    )
+                    .await?;
+
+                let mut has_previous_page = false;
+                if let Some(e) = edges.first() {
+                    has_previous_page = !library_svc
+                        .get_chapter_additions(
+                            claims.sub,
+                            &claims.unlocked_categories,
+                            Utc::now().timestamp(),
+                            1,
+                            e.date_added.timestamp(),
+                            e.chapter_id,
+                            None,
+                            Some(1),
+                        )
+                        .await?
+                        .is_empty();
+                }
+
+                let mut has_next_page = false;
+                if let Some(e) = edges.last() {
+                    has_next_page = !library_svc
+                        .get_chapter_additions(
+                            claims.sub,
+                            &claims.unlocked_categories,
+                            e.date_added.timestamp(),
+                            e.chapter_id,
+                            0,
+                            0,
+                            Some(1),
+                            None,
+                        )
+                        .await?
+                        .is_empty();
+                }
+
+                let mut connection = Connection::new(has_previous_page, has_next_page);
+                connection.edges.extend(
+                    edges.into_iter().map(|e| {
+                        Edge::new(Cursor(e.date_added.timestamp(), e.chapter_id), e.into())
+                    }),
+                );
+
+                Ok::<_, Error>(connection)
+            },
+        )
+        .await
+    }
+
     async fn recent_chapters(
         &self,
         ctx: &Context<'_>,
@@ -145,6 +618,13 @@ impl LibraryRoot {
 
         let history_svc =
             ctx.data::<HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>>()?;
+        let hidden_manga_ids: Vec<i64> = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .get_hidden_manga_ids(claims.sub, &claims.unlocked_categories)
+            .await?
+            .into_iter()
+            .collect();
+        let hidden_manga_ids = hidden_manga_ids.as_slice();
 
         query(
             after,
@@ -156,7 +636,14 @@ impl LibraryRoot {
                 let before_cursor = before.unwrap_or(Cursor(0, 0));
 
                 let edges = history_svc
-                    .get_history_chapters(claims.sub, after_cursor.0, before_cursor.0, first, last)
+                    .get_history_chapters(
+                        claims.sub,
+                        hidden_manga_ids,
+                        after_cursor.0,
+                        before_cursor.0,
+                        first,
+                        last,
+                    )
                     .await?;
 
                 let mut has_previous_page = false;
@@ -164,6 +651,7 @@ impl LibraryRoot {
                     has_previous_page = !history_svc
                         .get_history_chapters(
                             claims.sub,
+                            hidden_manga_ids,
                             Utc::now().timestamp(),
                             e.read_at.timestamp(),
                             None,
@@ -177,7 +665,14 @@ impl LibraryRoot {
                 let mut has_next_page = false;
                 if let Some(e) = edges.last() {
                     has_next_page = !history_svc
-                        .get_history_chapters(claims.sub, e.read_at.timestamp(), 0, Some(1), None)
+                        .get_history_chapters(
+                            claims.sub,
+                            hidden_manga_ids,
+                            e.read_at.timestamp(),
+                            0,
+                            Some(1),
+                            None,
+                        )
                         .await?
                         .is_empty();
                 }
@@ -201,11 +696,34 @@ pub struct LibraryMutationRoot;
 
 #[Object]
 impl LibraryMutationRoot {
+    /// Import series, collections as categories and read progress from a Komga or Kavita
+    /// account, runs in background and the result is sent to the inbox
+    async fn import_library(&self, ctx: &Context<'_>, input: ImportLibraryInput) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<ImportSender>()?
+            .send(ImportJob {
+                user_id: claims.sub,
+                server: input.server.into(),
+                url: input.url,
+                username: input.username,
+                password: input.password,
+                source_id: input.source_id,
+            })
+            .map_err(|_| "import worker is not running")?;
+
+        Ok(true)
+    }
+
     async fn add_to_library(
         &self,
         ctx: &Context<'_>,
         #[graphql(desc = "manga id")] manga_id: i64,
-        #[graphql(desc = "category ids")] category_ids: Vec<i64>,
+        #[graphql(desc = "category ids, null for the default category")] category_ids: Option<
+            Vec<i64>,
+        >,
     ) -> Result<u64> {
         let claims = ctx
             .data::<Claims>()
@@ -215,6 +733,26 @@ impl LibraryMutationRoot {
             .insert_manga_to_library(claims.sub, manga_id, category_ids)
             .await?;
 
+        let manga = ctx
+            .data::<MangaService<MangaRepositoryImpl>>()?
+            .fetch_manga_by_id(manga_id, false)
+            .await?;
+        let image_svc = ctx
+            .data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?
+            .clone();
+        tokio::spawn(async move {
+            if let Err(e) = image_svc.store_cover(manga.id, &manga.cover_url).await {
+                error!("failed to store cover of {}: {e}", manga.title);
+            }
+        });
+
+        let _ = ctx
+            .data::<AutomationSender>()?
+            .send(AutomationEvent::AddedToLibrary {
+                user_id: claims.sub,
+                manga_id,
+            });
+
         Ok(1)
     }
 
@@ -234,6 +772,159 @@ impl LibraryMutationRoot {
         Ok(1)
     }
 
+    /// remove many manga at once, returns manga removed
+    async fn delete_many_from_library(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga ids")] manga_ids: Vec<i64>,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .delete_many_manga_from_library(claims.sub, &manga_ids)
+            .await?)
+    }
+
+    /// replace categories of many manga in library at once, returns manga moved
+    async fn move_to_categories(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga ids")] manga_ids: Vec<i64>,
+        #[graphql(desc = "category ids, empty for the default category")] category_ids: Vec<i64>,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .move_manga_to_categories(claims.sub, &manga_ids, &category_ids)
+            .await?)
+    }
+
+    /// remove duplicates of a manga, keeping their categories, tags, trackers and progress
+    async fn merge_duplicates(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id to keep")] manga_id: i64,
+        #[graphql(desc = "manga ids to remove")] duplicate_ids: Vec<i64>,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .merge_duplicates(claims.sub, manga_id, &duplicate_ids)
+            .await?)
+    }
+
+    /// stop reporting manga as duplicates of each other
+    async fn ignore_duplicates(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga ids")] manga_ids: Vec<i64>,
+    ) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<LibraryService<LibraryRepositoryImpl>>()?
+            .ignore_duplicates(claims.sub, &manga_ids)
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn set_download_ahead(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+        #[graphql(desc = "unread chapters to keep downloaded, 0 disables")] count: i64,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<LibraryService<LibraryRepositoryImpl>>()?
+            .set_download_ahead(claims.sub, manga_id, count)
+            .await?;
+
+        let _ = ctx
+            .data::<DownloadAheadSender>()?
+            .send(DownloadAheadCommand::Refresh { manga_id });
+
+        Ok(1)
+    }
+
+    async fn set_chapter_languages(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+        #[graphql(desc = "null or empty uses the chapter languages of the user")] languages: Option<
+            Vec<String>,
+        >,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<LibraryService<LibraryRepositoryImpl>>()?
+            .set_chapter_languages(claims.sub, manga_id, languages)
+            .await?;
+
+        Ok(1)
+    }
+
+    /// other groups' releases of a chapter number the group released are hidden by chapter
+    /// dedupe and skipped by downloads
+    async fn set_preferred_group(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+        #[graphql(desc = "scanlation group, null or empty clears it")] group: Option<String>,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<LibraryService<LibraryRepositoryImpl>>()?
+            .set_preferred_group(claims.sub, manga_id, group.as_deref())
+            .await?;
+
+        let _ = ctx
+            .data::<DownloadAheadSender>()?
+            .send(DownloadAheadCommand::Refresh { manga_id });
+
+        Ok(1)
+    }
+
+    async fn set_auto_download(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+        #[graphql(desc = "download new chapters found by library updates")] enabled: bool,
+        #[graphql(desc = "only the most recent new chapters, null downloads every one")]
+        limit: Option<i64>,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<LibraryService<LibraryRepositoryImpl>>()?
+            .set_auto_download(
+                claims.sub,
+                manga_id,
+                crate::domain::entities::library::AutoDownload { enabled, limit },
+            )
+            .await?;
+
+        Ok(1)
+    }
+
     async fn update_page_read_at(
         &self,
         ctx: &Context<'_>,
@@ -249,6 +940,26 @@ impl LibraryMutationRoot {
             .insert_chapter_to_history(claims.sub, chapter_id, page, is_complete)
             .await?;
 
+        let _ = ctx
+            .data::<PrefetchSender>()?
+            .send(PrefetchCommand::PageRead {
+                user_id: claims.sub,
+                chapter_id,
+                page,
+            });
+
+        if is_complete {
+            let _ = ctx
+                .data::<AutomationSender>()?
+                .send(AutomationEvent::ChapterRead {
+                    user_id: claims.sub,
+                    chapter_id,
+                });
+            let _ = ctx
+                .data::<DownloadAheadSender>()?
+                .send(DownloadAheadCommand::ChapterRead { chapter_id });
+        }
+
         let chapter = ctx
             .data::<ChapterService<ChapterRepositoryImpl>>()?
             .fetch_chapter_by_id(chapter_id)
@@ -296,9 +1007,19 @@ impl LibraryMutationRoot {
             .map_err(|_| "token not exists, please login")?;
 
         ctx.data::<HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>>()?
-            .insert_chapters_to_history_as_completed(claims.sub, chapter_ids)
+            .insert_chapters_to_history_as_completed(claims.sub, chapter_ids.clone())
             .await?;
 
+        let automation_tx = ctx.data::<AutomationSender>()?;
+        let download_ahead_tx = ctx.data::<DownloadAheadSender>()?;
+        for chapter_id in chapter_ids {
+            let _ = automation_tx.send(AutomationEvent::ChapterRead {
+                user_id: claims.sub,
+                chapter_id,
+            });
+            let _ = download_ahead_tx.send(DownloadAheadCommand::ChapterRead { chapter_id });
+        }
+
         Ok(1)
     }
 
@@ -317,4 +1038,44 @@ impl LibraryMutationRoot {
 
         Ok(1)
     }
+
+    /// mark every chapter of many manga as read, returns chapters marked
+    async fn mark_manga_as_read(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga ids")] manga_ids: Vec<i64>,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let count = ctx
+            .data::<HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>>()?
+            .mark_manga_as_read(claims.sub, &manga_ids)
+            .await?;
+
+        // chapter read automations are left out, they'd run for every chapter
+        let download_ahead_tx = ctx.data::<DownloadAheadSender>()?;
+        for manga_id in manga_ids {
+            let _ = download_ahead_tx.send(DownloadAheadCommand::Refresh { manga_id });
+        }
+
+        Ok(count)
+    }
+
+    /// mark every chapter of many manga as unread, returns chapters marked
+    async fn mark_manga_as_unread(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga ids")] manga_ids: Vec<i64>,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>>()?
+            .mark_manga_as_unread(claims.sub, &manga_ids)
+            .await?)
+    }
 }
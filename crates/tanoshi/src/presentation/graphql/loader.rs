@@ -1,7 +1,10 @@
 use super::{common::ReadProgress, manga::Manga};
-use crate::domain::repositories::{
-    history::HistoryRepository, library::LibraryRepository, manga::MangaRepository,
-    tracker::TrackerRepository,
+use crate::domain::{
+    entities::library::Tag,
+    repositories::{
+        history::HistoryRepository, library::LibraryRepository, manga::MangaRepository,
+        tracker::TrackerRepository,
+    },
 };
 use async_graphql::{dataloader::Loader, Result};
 use chrono::NaiveDateTime;
@@ -170,6 +173,44 @@ where
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UserMangaTagsId(pub i64, pub i64);
+
+#[async_trait::async_trait]
+impl<H, L, M, T> Loader<UserMangaTagsId> for DatabaseLoader<H, L, M, T>
+where
+    H: HistoryRepository + 'static,
+    L: LibraryRepository + 'static,
+    M: MangaRepository + 'static,
+    T: TrackerRepository + 'static,
+{
+    type Value = Vec<Tag>;
+
+    type Error = Arc<anyhow::Error>;
+
+    async fn load(
+        &self,
+        keys: &[UserMangaTagsId],
+    ) -> Result<HashMap<UserMangaTagsId, Self::Value>, Self::Error> {
+        let user_id = keys
+            .iter()
+            .next()
+            .map(|key| key.0)
+            .ok_or_else(|| anyhow::anyhow!("no user id"))?;
+
+        let res = self
+            .library_repo
+            .get_library_tags(user_id)
+            .await
+            .map_err(|e| Arc::new(anyhow::anyhow!("{e}")))?
+            .into_iter()
+            .map(|(manga_id, tags)| (UserMangaTagsId(user_id, manga_id), tags))
+            .collect();
+
+        Ok(res)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct UserUnreadChaptersId(pub i64, pub i64);
 
@@ -371,3 +412,40 @@ where
         Ok(res)
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UserCategoryUnreadId(pub i64, pub Option<i64>);
+
+#[async_trait::async_trait]
+impl<H, L, M, T> Loader<UserCategoryUnreadId> for DatabaseLoader<H, L, M, T>
+where
+    H: HistoryRepository + 'static,
+    L: LibraryRepository + 'static,
+    M: MangaRepository + 'static,
+    T: TrackerRepository + 'static,
+{
+    type Value = i64;
+
+    type Error = Arc<anyhow::Error>;
+
+    async fn load(
+        &self,
+        keys: &[UserCategoryUnreadId],
+    ) -> Result<HashMap<UserCategoryUnreadId, Self::Value>, Self::Error> {
+        let user_id = keys
+            .iter()
+            .next()
+            .map(|key| key.0)
+            .ok_or_else(|| anyhow::anyhow!("no user id"))?;
+
+        let res = self
+            .library_repo
+            .get_category_unread_count(user_id)
+            .await
+            .map_err(|e| Arc::new(anyhow::anyhow!("{e}")))?
+            .into_par_iter()
+            .map(|(category_id, count)| (UserCategoryUnreadId(user_id, category_id), count))
+            .collect();
+        Ok(res)
+    }
+}
@@ -1,14 +1,22 @@
 use super::{
     chapter::Chapter,
     loader::{
-        UserFavoriteId, UserFavoritePath, UserLastReadId, UserTrackerMangaId, UserUnreadChaptersId,
+        UserFavoriteId, UserFavoritePath, UserLastReadId, UserMangaTagsId, UserTrackerMangaId,
+        UserUnreadChaptersId,
     },
+    reading_lists::ReadingList,
     source::Source,
+    tags::Tag,
 };
 use crate::{
     domain::services::{
-        chapter::ChapterService, history::HistoryService, image::ImageService,
+        chapter::{dedupe_chapters, filter_chapters, merge_series_chapters, ChapterService},
+        history::HistoryService,
+        image::ImageService,
+        library::LibraryService,
+        manga::MangaService,
         source::SourceService,
+        user::UserService,
     },
     infrastructure::{
         auth::Claims,
@@ -16,12 +24,13 @@ use crate::{
         domain::repositories::{
             chapter::ChapterRepositoryImpl, history::HistoryRepositoryImpl,
             image::ImageRepositoryImpl, image_cache::ImageCacheRepositoryImpl,
-            source::SourceRepositoryImpl,
+            library::LibraryRepositoryImpl, manga::MangaRepositoryImpl,
+            source::SourceRepositoryImpl, user::UserRepositoryImpl,
         },
     },
     presentation::graphql::schema::DatabaseLoader,
 };
-use async_graphql::{dataloader::DataLoader, Context, Object, Result, SimpleObject};
+use async_graphql::{dataloader::DataLoader, Context, InputObject, Object, Result, SimpleObject};
 use chrono::NaiveDateTime;
 use rayon::prelude::*;
 use tanoshi_vm::extension::ExtensionManager;
@@ -32,12 +41,56 @@ pub struct Tracker {
     pub tracker_manga_id: Option<String>,
 }
 
+/// Metadata set over the one of the source, null fields keep the source's
+#[derive(Debug, Default, SimpleObject)]
+pub struct MangaOverride {
+    pub title: Option<String>,
+    pub author: Option<Vec<String>>,
+    pub genre: Option<Vec<String>>,
+    pub status: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(InputObject)]
+pub struct MangaOverrideInput {
+    pub title: Option<String>,
+    pub author: Option<Vec<String>>,
+    pub genre: Option<Vec<String>>,
+    pub status: Option<String>,
+    pub description: Option<String>,
+}
+
+impl From<crate::domain::entities::manga::MangaOverride> for MangaOverride {
+    fn from(manga_override: crate::domain::entities::manga::MangaOverride) -> Self {
+        Self {
+            title: manga_override.title,
+            author: manga_override.author,
+            genre: manga_override.genre,
+            status: manga_override.status,
+            description: manga_override.description,
+        }
+    }
+}
+
+impl From<MangaOverrideInput> for crate::domain::entities::manga::MangaOverride {
+    fn from(input: MangaOverrideInput) -> Self {
+        Self {
+            title: input.title,
+            author: input.author,
+            genre: input.genre,
+            status: input.status,
+            description: input.description,
+        }
+    }
+}
+
 /// A type represent manga details, normalized across source
 #[derive(Debug, Clone)]
 pub struct Manga {
     pub id: i64,
     pub source_id: i64,
     pub title: String,
+    pub alt_titles: Vec<String>,
     pub author: Vec<String>,
     pub genre: Vec<String>,
     pub status: Option<String>,
@@ -53,6 +106,7 @@ impl Default for Manga {
             id: Default::default(),
             source_id: Default::default(),
             title: Default::default(),
+            alt_titles: Default::default(),
             author: Default::default(),
             genre: Default::default(),
             status: Default::default(),
@@ -70,6 +124,7 @@ impl From<tanoshi_lib::models::MangaInfo> for Manga {
             id: 0,
             source_id: m.source_id,
             title: m.title,
+            alt_titles: m.alt_titles,
             author: m.author,
             genre: m.genre,
             status: m.status,
@@ -87,6 +142,7 @@ impl From<crate::domain::entities::manga::Manga> for Manga {
             id: val.id,
             source_id: val.source_id,
             title: val.title,
+            alt_titles: val.alt_titles,
             author: val.author,
             genre: val.genre,
             status: val.status,
@@ -108,6 +164,19 @@ impl Manga {
         self.title.clone()
     }
 
+    /// other titles from the source and the user
+    async fn alt_titles(&self) -> Vec<String> {
+        self.alt_titles.clone()
+    }
+
+    /// other titles set by the user
+    async fn user_alt_titles(&self, ctx: &Context<'_>) -> Result<Vec<String>> {
+        Ok(ctx
+            .data::<MangaService<MangaRepositoryImpl>>()?
+            .fetch_user_alt_titles(self.id)
+            .await?)
+    }
+
     async fn author(&self) -> Vec<String> {
         self.author.clone()
     }
@@ -140,7 +209,7 @@ impl Manga {
 
         Ok(ctx
             .data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?
-            .encrypt_image_url(secret, &self.cover_url)?)
+            .encrypt_manga_cover_url(secret, self.id, &self.cover_url)?)
     }
 
     async fn is_favorite(&self, ctx: &Context<'_>) -> Result<bool> {
@@ -183,6 +252,36 @@ impl Manga {
         Ok(loader.load_one(UserLastReadId(user.sub, self.id)).await?)
     }
 
+    /// tags the current user put on this manga
+    async fn tags(&self, ctx: &Context<'_>) -> Result<Vec<Tag>> {
+        let user = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+        let loader = ctx.data::<DataLoader<DatabaseLoader>>()?;
+        Ok(loader
+            .load_one(UserMangaTagsId(user.sub, self.id))
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(Tag::from)
+            .collect())
+    }
+
+    /// reading lists of the current user with this manga in them
+    async fn reading_lists(&self, ctx: &Context<'_>) -> Result<Vec<ReadingList>> {
+        let user = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .get_reading_lists_by_manga_id(user.sub, self.id)
+            .await?
+            .into_iter()
+            .map(ReadingList::from)
+            .collect())
+    }
+
     async fn source(&self, ctx: &Context<'_>) -> Result<Source> {
         let source = ctx
             .data::<SourceService<SourceRepositoryImpl>>()?
@@ -197,11 +296,103 @@ impl Manga {
         &self,
         ctx: &Context<'_>,
         #[graphql(desc = "refresh data from source", default = false)] refresh: bool,
+        #[graphql(
+            desc = "only chapters in this language or in the source language, null for the chapter languages of the manga or the user"
+        )]
+        language: Option<String>,
+        #[graphql(desc = "only chapters released by this scanlation group")] group: Option<String>,
+        #[graphql(
+            desc = "one chapter per number, preferring `preferredGroup` then the group releasing most chapters, null dedupes when the manga has a preferred group"
+        )]
+        dedupe: Option<bool>,
+        #[graphql(
+            desc = "scanlation group kept by dedupe, null for the one the user set for the manga"
+        )]
+        preferred_group: Option<String>,
+        #[graphql(
+            desc = "chapters of linked manga too, each number from the most preferred manga",
+            default = false
+        )]
+        series: bool,
     ) -> Result<Vec<Chapter>> {
-        let chapters = ctx
-            .data::<ChapterService<ChapterRepositoryImpl>>()?
-            .fetch_chapters_by_manga_id(self.source_id, &self.path, self.id, refresh)
-            .await?
+        let chapter_svc = ctx.data::<ChapterService<ChapterRepositoryImpl>>()?;
+        let user_preferred_group = match ctx.data::<Claims>() {
+            Ok(claims) if dedupe != Some(false) && preferred_group.is_none() => {
+                ctx.data::<LibraryService<LibraryRepositoryImpl>>()?
+                    .get_preferred_group(claims.sub, self.id)
+                    .await?
+            }
+            _ => None,
+        };
+        let languages = match ctx.data::<Claims>() {
+            Ok(claims) if language.is_none() => {
+                match ctx
+                    .data::<LibraryService<LibraryRepositoryImpl>>()?
+                    .get_chapter_languages(claims.sub, self.id)
+                    .await?
+                {
+                    Some(languages) => Some(languages),
+                    None => {
+                        ctx.data::<UserService<UserRepositoryImpl>>()?
+                            .fetch_user_by_id(claims.sub)
+                            .await?
+                            .chapter_languages
+                    }
+                }
+            }
+            _ => None,
+        };
+        let dedupe = dedupe.unwrap_or_else(|| user_preferred_group.is_some());
+        let preferred_group = preferred_group.or(user_preferred_group);
+        let filter = |mut chapters| {
+            chapters = filter_chapters(chapters, language.as_deref(), group.as_deref());
+            if dedupe {
+                chapters = dedupe_chapters(chapters, preferred_group.as_deref());
+            }
+            chapters
+        };
+
+        let chapters = if series {
+            let manga = ctx
+                .data::<MangaService<MangaRepositoryImpl>>()?
+                .fetch_series(self.id)
+                .await?;
+            let mut series_chapters = Vec::with_capacity(manga.len());
+            for m in manga {
+                let session = get_session(ctx, m.source_id).await?;
+                match chapter_svc
+                    .with_session(session)
+                    .fetch_chapters_by_manga_id(
+                        m.source_id,
+                        &m.path,
+                        m.id,
+                        refresh,
+                        languages.as_deref(),
+                    )
+                    .await
+                {
+                    Ok(chapters) => series_chapters.push(filter(chapters)),
+                    Err(e) if m.id == self.id => return Err(e.into()),
+                    Err(e) => error!("failed to fetch chapters of linked manga {}: {e}", m.id),
+                }
+            }
+            merge_series_chapters(series_chapters)
+        } else {
+            let session = get_session(ctx, self.source_id).await?;
+            let chapters = chapter_svc
+                .with_session(session)
+                .fetch_chapters_by_manga_id(
+                    self.source_id,
+                    &self.path,
+                    self.id,
+                    refresh,
+                    languages.as_deref(),
+                )
+                .await?;
+            filter(chapters)
+        };
+
+        let chapters = chapters
             .into_par_iter()
             .map(|c| c.into())
             .collect::<Vec<Chapter>>();
@@ -223,6 +414,27 @@ impl Manga {
         Ok(chapter)
     }
 
+    /// metadata set over the one of the source
+    async fn metadata_override(&self, ctx: &Context<'_>) -> Result<MangaOverride> {
+        Ok(ctx
+            .data::<MangaService<MangaRepositoryImpl>>()?
+            .fetch_manga_override(self.id)
+            .await?
+            .into())
+    }
+
+    async fn linked_manga(&self, ctx: &Context<'_>) -> Result<Vec<Manga>> {
+        let manga = ctx
+            .data::<MangaService<MangaRepositoryImpl>>()?
+            .fetch_linked_manga(self.id)
+            .await?
+            .into_par_iter()
+            .map(Manga::from)
+            .collect();
+
+        Ok(manga)
+    }
+
     async fn next_chapter(&self, ctx: &Context<'_>) -> Result<Option<Chapter>> {
         let claims = ctx
             .data::<Claims>()
@@ -256,3 +468,13 @@ impl Manga {
         Ok(data)
     }
 }
+
+async fn get_session(ctx: &Context<'_>, source_id: i64) -> Result<Option<String>> {
+    match ctx.data::<Claims>() {
+        Ok(claims) => Ok(ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .get_session(claims.sub, source_id)
+            .await?),
+        Err(_) => Ok(None),
+    }
+}
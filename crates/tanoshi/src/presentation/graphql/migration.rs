@@ -0,0 +1,227 @@
+use std::{cmp::Ordering, collections::HashSet};
+
+use super::manga::Manga;
+use crate::{
+    domain::services::{
+        chapter::ChapterService,
+        history::HistoryService,
+        image::ImageService,
+        library::{title_similarity, LibraryService},
+        manga::MangaService,
+        source::SourceService,
+        user::UserService,
+    },
+    infrastructure::{
+        auth::Claims,
+        config::Config,
+        domain::repositories::{
+            chapter::ChapterRepositoryImpl, history::HistoryRepositoryImpl,
+            image::ImageRepositoryImpl, image_cache::ImageCacheRepositoryImpl,
+            library::LibraryRepositoryImpl, manga::MangaRepositoryImpl,
+            source::SourceRepositoryImpl, user::UserRepositoryImpl,
+        },
+    },
+};
+use async_graphql::{Context, Object, Result, SimpleObject};
+
+/// Manga of another source a library manga could be migrated to
+#[derive(SimpleObject)]
+pub struct MigrationCandidate {
+    pub manga: Manga,
+    /// title similarity from 0 to 1
+    pub similarity: f64,
+}
+
+/// How the read chapters of a manga match the chapters of the manga it would migrate to
+#[derive(SimpleObject)]
+pub struct MigrationPreview {
+    pub manga: Manga,
+    pub chapter_count: i64,
+    pub read_count: i64,
+    /// read chapters with a chapter of the same number to carry their progress to
+    pub matched_count: i64,
+    /// numbers of read chapters the other source doesn't have
+    pub missing_numbers: Vec<f64>,
+}
+
+fn by_similarity(a: &MigrationCandidate, b: &MigrationCandidate) -> Ordering {
+    b.similarity
+        .partial_cmp(&a.similarity)
+        .unwrap_or(Ordering::Equal)
+}
+
+#[derive(Default)]
+pub struct MigrationRoot;
+
+#[Object]
+impl MigrationRoot {
+    /// search other installed sources for a manga, best matches first
+    async fn migration_candidates(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+        #[graphql(desc = "sources to search, all if null")] source_ids: Option<Vec<i64>>,
+        #[graphql(desc = "results kept per source", default = 3)] limit: i64,
+    ) -> Result<Vec<MigrationCandidate>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let manga = ctx
+            .data::<MangaService<MangaRepositoryImpl>>()?
+            .fetch_manga_by_id(manga_id, false)
+            .await?;
+        let user = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .fetch_user_by_id(claims.sub)
+            .await?;
+
+        let source_svc = ctx.data::<SourceService<SourceRepositoryImpl>>()?;
+        let repo_url = &ctx.data::<Config>()?.extension_repository;
+        let sources = source_svc
+            .get_installed_sources(repo_url, false)
+            .await?
+            .into_iter()
+            .filter(|source| source.id != manga.source_id && !source.is_hidden_for(&user))
+            .filter(|source| {
+                source_ids
+                    .as_ref()
+                    .map(|ids| ids.contains(&source.id))
+                    .unwrap_or(true)
+            });
+
+        let searches = sources.map(|source| {
+            let title = manga.title.clone();
+            async move {
+                match source_svc
+                    .search_manga(Some(claims.sub), source.id, 1, Some(title), None, false)
+                    .await
+                {
+                    Ok(manga) => manga,
+                    Err(e) => {
+                        debug!("failed to search {} for migration: {e}", source.name);
+                        vec![]
+                    }
+                }
+            }
+        });
+
+        let mut candidates = vec![];
+        for results in futures::future::join_all(searches).await {
+            let mut results: Vec<MigrationCandidate> = results
+                .into_iter()
+                .map(|m| MigrationCandidate {
+                    similarity: title_similarity(&manga.title, &m.title),
+                    manga: m.into(),
+                })
+                .collect();
+            results.sort_by(by_similarity);
+            results.truncate(limit.max(0) as usize);
+            candidates.extend(results);
+        }
+        candidates.sort_by(by_similarity);
+
+        Ok(candidates)
+    }
+
+    /// chapters of a manga matched with the chapters of the manga it would migrate to
+    async fn migration_preview(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+        #[graphql(desc = "source id to migrate to")] source_id: i64,
+        #[graphql(desc = "path of the manga in that source")] path: String,
+    ) -> Result<MigrationPreview> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let target = ctx
+            .data::<MangaService<MangaRepositoryImpl>>()?
+            .fetch_manga_by_source_path(source_id, &path)
+            .await?;
+        let chapters = ctx
+            .data::<ChapterService<ChapterRepositoryImpl>>()?
+            .fetch_chapters_by_manga_id(target.source_id, &target.path, target.id, false, None)
+            .await?;
+        let numbers: HashSet<u64> = chapters.iter().map(|c| c.number.to_bits()).collect();
+
+        let read: Vec<f64> = ctx
+            .data::<HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>>()?
+            .get_drop_off(claims.sub, manga_id)
+            .await?
+            .chapters
+            .into_iter()
+            .filter(|c| c.read_at.is_some())
+            .map(|c| c.number)
+            .collect();
+        let matched_count = read
+            .iter()
+            .filter(|number| numbers.contains(&number.to_bits()))
+            .count();
+        let mut missing_numbers: Vec<f64> = read
+            .iter()
+            .copied()
+            .filter(|number| !numbers.contains(&number.to_bits()))
+            .collect();
+        missing_numbers.dedup();
+
+        Ok(MigrationPreview {
+            manga: target.into(),
+            chapter_count: chapters.len() as i64,
+            read_count: read.len() as i64,
+            matched_count: matched_count as i64,
+            missing_numbers,
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct MigrationMutationRoot;
+
+#[Object]
+impl MigrationMutationRoot {
+    /// replace a library manga with the same manga from another source, keeping its
+    /// categories, tags, trackers and progress of chapters with the same number
+    async fn migrate_manga(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+        #[graphql(desc = "source id to migrate to")] source_id: i64,
+        #[graphql(desc = "path of the manga in that source")] path: String,
+    ) -> Result<Manga> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let target = ctx
+            .data::<MangaService<MangaRepositoryImpl>>()?
+            .fetch_manga_by_source_path(source_id, &path)
+            .await?;
+        if target.id == manga_id {
+            return Err("manga is already from this source".into());
+        }
+
+        // progress is carried by chapter number, chapters have to be known first
+        ctx.data::<ChapterService<ChapterRepositoryImpl>>()?
+            .fetch_chapters_by_manga_id(target.source_id, &target.path, target.id, false, None)
+            .await?;
+
+        ctx.data::<LibraryService<LibraryRepositoryImpl>>()?
+            .merge_duplicates(claims.sub, target.id, &[manga_id])
+            .await?;
+
+        let image_svc = ctx
+            .data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?
+            .clone();
+        let (cover_id, cover_url, title) =
+            (target.id, target.cover_url.clone(), target.title.clone());
+        tokio::spawn(async move {
+            if let Err(e) = image_svc.store_cover(cover_id, &cover_url).await {
+                error!("failed to store cover of {title}: {e}");
+            }
+        });
+
+        Ok(target.into())
+    }
+}
@@ -1,21 +1,30 @@
+pub mod automation;
 pub mod catalogue;
 pub mod categories;
 pub mod chapter;
 pub mod common;
 pub mod downloads;
+pub mod export;
 pub mod guard;
+pub mod inbox;
 pub mod library;
 pub mod loader;
 pub mod manga;
+pub mod migration;
 pub mod notification;
+pub mod reading_lists;
 pub mod recent;
 pub mod schema;
 pub mod source;
 pub mod status;
+pub mod tags;
 pub mod tracking;
 pub mod user;
 
-use crate::infrastructure::{auth, config::Config};
+use crate::{
+    domain::services::user::UserService,
+    infrastructure::{auth, config::Config, domain::repositories::user::UserRepositoryImpl},
+};
 use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
 use axum::{
@@ -31,11 +40,22 @@ pub async fn graphql_handler(
     token: Token,
     config: Extension<Config>,
     schema: Extension<TanoshiSchema>,
+    user_svc: Extension<UserService<UserRepositoryImpl>>,
     req: GraphQLRequest,
 ) -> GraphQLResponse {
     let mut req = req.into_inner();
 
-    if let Ok(claims) = auth::decode_jwt(&config.secret, &token.0) {
+    if let Ok(mut claims) = auth::decode_jwt(&config.secret, &token.0) {
+        // categories were locked again since this token unlocked them
+        if !claims.unlocked_categories.is_empty()
+            && user_svc
+                .get_category_lock_generation(claims.sub)
+                .await
+                .map(|generation| generation != claims.lock_generation)
+                .unwrap_or(true)
+        {
+            claims.unlocked_categories.clear();
+        }
         req = req.data(claims);
     }
 
@@ -1,7 +1,18 @@
-use crate::infrastructure::{
-    auth::Claims, domain::repositories::user::UserRepositoryImpl, notification::Notification,
+use crate::{
+    domain::services::user::UserService,
+    infrastructure::{
+        auth::Claims, domain::repositories::user::UserRepositoryImpl, notification::Notification,
+    },
 };
-use async_graphql::{Context, Object, Result};
+use async_graphql::{Context, Enum, Object, Result};
+use tanoshi_notifier::pushover::PushoverOptions;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum NotificationChannel {
+    Telegram,
+    Pushover,
+    Gotify,
+}
 
 #[derive(Default)]
 pub struct NotificationRoot;
@@ -66,3 +77,70 @@ impl NotificationRoot {
         Err("desktop notification only available for desktop version".into())
     }
 }
+
+#[derive(Default)]
+pub struct NotificationMutationRoot;
+
+#[Object]
+impl NotificationMutationRoot {
+    /// Send test message to a channel configured in the user profile,
+    /// error from the provider is returned as is
+    async fn test_notification(
+        &self,
+        ctx: &Context<'_>,
+        channel: NotificationChannel,
+    ) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let user = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .fetch_user_by_id(claims.sub)
+            .await?;
+        let notifier = ctx.data::<Notification<UserRepositoryImpl>>()?;
+
+        let res = match channel {
+            NotificationChannel::Telegram => {
+                let chat_id = user
+                    .telegram_chat_id
+                    .ok_or("telegram chat id is not set in profile")?;
+                notifier
+                    .send_message_to_telegram(chat_id, "Test Notification")
+                    .await
+            }
+            NotificationChannel::Pushover => {
+                let user_key = user
+                    .pushover_user_key
+                    .as_deref()
+                    .ok_or("pushover user key is not set in profile")?;
+                // uses the profile's device and sound so they can be checked
+                let options = PushoverOptions {
+                    priority: 0,
+                    sound: user.pushover_sound.clone(),
+                    device: user.pushover_device.clone(),
+                };
+                notifier
+                    .send_message_to_pushover_with_options(
+                        user_key,
+                        None,
+                        "Test Notification",
+                        &options,
+                    )
+                    .await
+            }
+            NotificationChannel::Gotify => {
+                let token = user
+                    .gotify_token
+                    .ok_or("gotify token is not set in profile")?;
+                notifier
+                    .send_message_to_gotify(&token, None, "Test Notification")
+                    .await
+            }
+        };
+
+        res.map_err(|e| format!("{channel:?}: {e}"))?;
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,257 @@
+use super::manga::Manga;
+use crate::{
+    domain::services::{library::LibraryService, manga::MangaService},
+    infrastructure::{
+        auth::Claims,
+        domain::repositories::{library::LibraryRepositoryImpl, manga::MangaRepositoryImpl},
+    },
+};
+use async_graphql::{Context, Object, Result};
+
+/// Ordered list of manga from any source or category
+#[derive(Debug, Clone)]
+pub struct ReadingList {
+    id: i64,
+    name: String,
+    description: Option<String>,
+    count: i64,
+}
+
+impl From<crate::domain::entities::library::ReadingList> for ReadingList {
+    fn from(reading_list: crate::domain::entities::library::ReadingList) -> Self {
+        Self {
+            id: reading_list.id,
+            name: reading_list.name,
+            description: reading_list.description,
+            count: reading_list.count,
+        }
+    }
+}
+
+#[Object]
+impl ReadingList {
+    async fn id(&self) -> i64 {
+        self.id
+    }
+
+    async fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn description(&self) -> Option<String> {
+        self.description.clone()
+    }
+
+    /// manga in the list, including the ones in locked categories
+    async fn count(&self) -> i64 {
+        self.count
+    }
+
+    /// manga in the order of the list, manga in locked categories are left out
+    async fn manga(&self, ctx: &Context<'_>) -> Result<Vec<Manga>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let library_svc = ctx.data::<LibraryService<LibraryRepositoryImpl>>()?;
+        let hidden_manga_ids = library_svc
+            .get_hidden_manga_ids(claims.sub, &claims.unlocked_categories)
+            .await?;
+        let manga = library_svc
+            .get_manga_from_reading_list(claims.sub, self.id)
+            .await?;
+
+        let manga = ctx
+            .data::<MangaService<MangaRepositoryImpl>>()?
+            .with_overrides(manga)
+            .await?
+            .into_iter()
+            .filter(|m| !hidden_manga_ids.contains(&m.id))
+            .map(Manga::from)
+            .collect();
+
+        Ok(manga)
+    }
+}
+
+#[derive(Default)]
+pub struct ReadingListRoot;
+
+#[Object]
+impl ReadingListRoot {
+    async fn reading_lists(&self, ctx: &Context<'_>) -> Result<Vec<ReadingList>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let reading_lists = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .get_reading_lists(claims.sub)
+            .await?
+            .into_iter()
+            .map(ReadingList::from)
+            .collect();
+
+        Ok(reading_lists)
+    }
+
+    async fn reading_list(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "reading list id")] id: i64,
+    ) -> Result<ReadingList> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let reading_list = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .get_reading_list(claims.sub, id)
+            .await?
+            .into();
+
+        Ok(reading_list)
+    }
+}
+
+#[derive(Default)]
+pub struct ReadingListMutationRoot;
+
+#[Object]
+impl ReadingListMutationRoot {
+    async fn create_reading_list(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "reading list name")] name: String,
+        #[graphql(desc = "reading list description")] description: Option<String>,
+    ) -> Result<ReadingList> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let reading_list = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .create_reading_list(claims.sub, &name, description.as_deref())
+            .await?
+            .into();
+
+        Ok(reading_list)
+    }
+
+    async fn update_reading_list(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "reading list id")] id: i64,
+        #[graphql(desc = "reading list name")] name: String,
+        #[graphql(desc = "description, null or empty removes it")] description: Option<String>,
+    ) -> Result<ReadingList> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let reading_list = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .update_reading_list(claims.sub, id, &name, description.as_deref())
+            .await?
+            .into();
+
+        Ok(reading_list)
+    }
+
+    async fn delete_reading_list(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "reading list id")] id: i64,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<LibraryService<LibraryRepositoryImpl>>()?
+            .delete_reading_list(claims.sub, id)
+            .await?;
+
+        Ok(1)
+    }
+
+    /// add manga to a reading list, manga already in it are moved to the position
+    async fn add_to_reading_list(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "reading list id")] id: i64,
+        #[graphql(desc = "manga ids")] manga_ids: Vec<i64>,
+        #[graphql(desc = "position counted from 0, null adds to the end")] position: Option<i64>,
+    ) -> Result<ReadingList> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let reading_list = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .add_to_reading_list(claims.sub, id, &manga_ids, position)
+            .await?
+            .into();
+
+        Ok(reading_list)
+    }
+
+    async fn remove_from_reading_list(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "reading list id")] id: i64,
+        #[graphql(desc = "manga ids")] manga_ids: Vec<i64>,
+    ) -> Result<ReadingList> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let reading_list = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .remove_from_reading_list(claims.sub, id, &manga_ids)
+            .await?
+            .into();
+
+        Ok(reading_list)
+    }
+
+    /// move a manga of a reading list, the ones after it shift by one
+    async fn move_in_reading_list(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "reading list id")] id: i64,
+        #[graphql(desc = "manga id")] manga_id: i64,
+        #[graphql(desc = "position counted from 0")] position: i64,
+    ) -> Result<ReadingList> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let reading_list = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .move_in_reading_list(claims.sub, id, manga_id, position)
+            .await?
+            .into();
+
+        Ok(reading_list)
+    }
+
+    /// replace manga of a reading list, in the order given
+    async fn set_reading_list_manga(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "reading list id")] id: i64,
+        #[graphql(desc = "manga ids, empty clears the list")] manga_ids: Vec<i64>,
+    ) -> Result<ReadingList> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let reading_list = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .set_reading_list_manga(claims.sub, id, &manga_ids)
+            .await?
+            .into();
+
+        Ok(reading_list)
+    }
+}
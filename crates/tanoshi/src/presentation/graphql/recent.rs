@@ -52,7 +52,7 @@ impl RecentChapter {
 
         let cover_url = ctx
             .data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?
-            .encrypt_image_url(secret, &self.cover_url)?;
+            .encrypt_manga_cover_url(secret, self.manga_id, &self.cover_url)?;
 
         Ok(cover_url)
     }
@@ -111,7 +111,7 @@ impl RecentUpdate {
 
         let cover_url = ctx
             .data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?
-            .encrypt_image_url(secret, &self.cover_url)?;
+            .encrypt_manga_cover_url(secret, self.manga_id, &self.cover_url)?;
 
         Ok(cover_url)
     }
@@ -124,3 +124,110 @@ impl RecentUpdate {
         self.uploaded
     }
 }
+
+pub struct LibraryAddition {
+    pub manga_id: i64,
+    pub manga_title: String,
+    pub cover_url: String,
+    pub date_added: NaiveDateTime,
+}
+
+impl From<crate::domain::entities::library::LibraryAddition> for LibraryAddition {
+    fn from(other: crate::domain::entities::library::LibraryAddition) -> Self {
+        Self {
+            manga_id: other.manga_id,
+            manga_title: other.manga_title,
+            cover_url: other.cover_url,
+            date_added: other.date_added,
+        }
+    }
+}
+
+#[Object]
+impl LibraryAddition {
+    async fn manga_id(&self) -> i64 {
+        self.manga_id
+    }
+
+    async fn manga_title(&self) -> String {
+        self.manga_title.clone()
+    }
+
+    async fn cover_url(&self, ctx: &Context<'_>) -> Result<String> {
+        let secret = &ctx.data::<Config>()?.secret;
+
+        let cover_url = ctx
+            .data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?
+            .encrypt_manga_cover_url(secret, self.manga_id, &self.cover_url)?;
+
+        Ok(cover_url)
+    }
+
+    /// when the manga was added to the library
+    async fn date_added(&self) -> NaiveDateTime {
+        self.date_added
+    }
+}
+
+pub struct ChapterAddition {
+    pub manga_id: i64,
+    pub chapter_id: i64,
+    pub manga_title: String,
+    pub cover_url: String,
+    pub chapter_title: String,
+    pub uploaded: NaiveDateTime,
+    pub date_added: NaiveDateTime,
+}
+
+impl From<crate::domain::entities::library::ChapterAddition> for ChapterAddition {
+    fn from(other: crate::domain::entities::library::ChapterAddition) -> Self {
+        Self {
+            manga_id: other.manga_id,
+            chapter_id: other.chapter_id,
+            manga_title: other.manga_title,
+            cover_url: other.cover_url,
+            chapter_title: other.chapter_title,
+            uploaded: other.uploaded,
+            date_added: other.date_added,
+        }
+    }
+}
+
+#[Object]
+impl ChapterAddition {
+    async fn manga_id(&self) -> i64 {
+        self.manga_id
+    }
+
+    async fn chapter_id(&self) -> i64 {
+        self.chapter_id
+    }
+
+    async fn manga_title(&self) -> String {
+        self.manga_title.clone()
+    }
+
+    async fn cover_url(&self, ctx: &Context<'_>) -> Result<String> {
+        let secret = &ctx.data::<Config>()?.secret;
+
+        let cover_url = ctx
+            .data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?
+            .encrypt_manga_cover_url(secret, self.manga_id, &self.cover_url)?;
+
+        Ok(cover_url)
+    }
+
+    async fn chapter_title(&self) -> String {
+        self.chapter_title.clone()
+    }
+
+    /// upload date given by the source
+    async fn uploaded(&self) -> NaiveDateTime {
+        self.uploaded
+    }
+
+    /// when the chapter was first fetched from the source
+    async fn date_added(&self) -> NaiveDateTime {
+        self.date_added
+    }
+}
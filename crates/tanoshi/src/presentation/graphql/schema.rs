@@ -6,13 +6,19 @@ use crate::infrastructure::domain::repositories::{
 };
 
 use super::{
-    catalogue::CatalogueRoot,
+    automation::{AutomationMutationRoot, AutomationRoot},
+    catalogue::{CatalogueMutationRoot, CatalogueRoot},
     categories::{CategoryMutationRoot, CategoryRoot},
     downloads::{DownloadMutationRoot, DownloadRoot},
+    export::{ExportMutationRoot, ExportRoot},
+    inbox::{InboxMutationRoot, InboxRoot},
     library::{LibraryMutationRoot, LibraryRoot},
-    notification::NotificationRoot,
+    migration::{MigrationMutationRoot, MigrationRoot},
+    notification::{NotificationMutationRoot, NotificationRoot},
+    reading_lists::{ReadingListMutationRoot, ReadingListRoot},
     source::{SourceMutationRoot, SourceRoot},
-    status::StatusRoot,
+    status::{StatusMutationRoot, StatusRoot},
+    tags::{TagMutationRoot, TagRoot},
     tracking::{TrackingMutationRoot, TrackingRoot},
     user::{UserMutationRoot, UserRoot},
 };
@@ -29,21 +35,36 @@ pub struct QueryRoot(
     CatalogueRoot,
     LibraryRoot,
     CategoryRoot,
+    TagRoot,
+    ReadingListRoot,
+    MigrationRoot,
     UserRoot,
     StatusRoot,
     NotificationRoot,
     DownloadRoot,
     TrackingRoot,
+    AutomationRoot,
+    InboxRoot,
+    ExportRoot,
 );
 
 #[derive(MergedObject, Default)]
 pub struct MutationRoot(
+    CatalogueMutationRoot,
     LibraryMutationRoot,
     CategoryMutationRoot,
+    TagMutationRoot,
+    ReadingListMutationRoot,
+    MigrationMutationRoot,
     UserMutationRoot,
     SourceMutationRoot,
     DownloadMutationRoot,
     TrackingMutationRoot,
+    StatusMutationRoot,
+    NotificationMutationRoot,
+    AutomationMutationRoot,
+    InboxMutationRoot,
+    ExportMutationRoot,
 );
 
 pub type DatabaseLoader = crate::presentation::graphql::loader::DatabaseLoader<
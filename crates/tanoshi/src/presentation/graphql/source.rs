@@ -1,14 +1,250 @@
 use super::{common::InputList, guard::AdminGuard};
 use crate::{
-    domain::services::source::SourceService,
+    domain::services::{source::SourceService, user::UserService},
     infrastructure::{
-        auth::Claims, config::Config, domain::repositories::source::SourceRepositoryImpl,
+        auth::Claims,
+        config::Config,
+        domain::repositories::{source::SourceRepositoryImpl, user::UserRepositoryImpl},
     },
 };
-use async_graphql::{Context, Object, Result};
+use async_graphql::{Context, Enum, InputObject, Object, Result, SimpleObject, Upload};
+use chrono::NaiveDateTime;
 use serde::Deserialize;
+use std::io::Read;
 use tanoshi_vm::extension::ExtensionManager;
 
+#[derive(Debug, SimpleObject)]
+pub struct SourceHealth {
+    pub source_id: i64,
+    /// from 0 to 100, based on recent error rate and failure streak
+    pub score: i64,
+    pub error_rate: f64,
+    pub consecutive_failures: i64,
+    /// failing often enough that update runs stop after the first error
+    pub circuit_open: bool,
+    /// update runs skip the source until then, null while the circuit is closed
+    pub circuit_open_until: Option<NaiveDateTime>,
+    pub last_error: Option<String>,
+    pub last_success_at: Option<NaiveDateTime>,
+    pub last_failure_at: Option<NaiveDateTime>,
+    /// disabled sources are skipped by update runs
+    pub disabled_at: Option<NaiveDateTime>,
+    /// automatic restarts after the extension crashed since the server started
+    pub restarts: u32,
+    /// crashed, calls fail until it is restarted
+    pub restarting: bool,
+    pub last_crash: Option<String>,
+}
+
+impl From<crate::domain::entities::source::SourceHealth> for SourceHealth {
+    fn from(health: crate::domain::entities::source::SourceHealth) -> Self {
+        Self {
+            source_id: health.source_id,
+            score: health.score(),
+            error_rate: health.error_rate,
+            consecutive_failures: health.consecutive_failures,
+            circuit_open: health.is_circuit_open(),
+            circuit_open_until: health.circuit_open_until(),
+            last_error: health.last_error,
+            last_success_at: health.last_success_at,
+            last_failure_at: health.last_failure_at,
+            disabled_at: health.disabled_at,
+            restarts: health.restarts,
+            restarting: health.restarting,
+            last_crash: health.last_crash,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum SourceStatus {
+    Ok,
+    Empty,
+    Error,
+    Timeout,
+}
+
+impl From<crate::domain::entities::source::SourceStatus> for SourceStatus {
+    fn from(status: crate::domain::entities::source::SourceStatus) -> Self {
+        use crate::domain::entities::source::SourceStatus as S;
+        match status {
+            S::Ok => Self::Ok,
+            S::Empty => Self::Empty,
+            S::Error => Self::Error,
+            S::Timeout => Self::Timeout,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum SourceLogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl From<SourceLogLevel> for crate::domain::entities::source::SourceLogLevel {
+    fn from(level: SourceLogLevel) -> Self {
+        match level {
+            SourceLogLevel::Error => Self::Error,
+            SourceLogLevel::Warn => Self::Warn,
+            SourceLogLevel::Info => Self::Info,
+            SourceLogLevel::Debug => Self::Debug,
+        }
+    }
+}
+
+impl From<crate::domain::entities::source::SourceLogLevel> for SourceLogLevel {
+    fn from(level: crate::domain::entities::source::SourceLogLevel) -> Self {
+        use crate::domain::entities::source::SourceLogLevel as L;
+        match level {
+            L::Error => Self::Error,
+            L::Warn => Self::Warn,
+            L::Info => Self::Info,
+            L::Debug => Self::Debug,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct SourceLog {
+    pub time: NaiveDateTime,
+    pub level: SourceLogLevel,
+    pub target: String,
+    pub message: String,
+}
+
+impl From<crate::domain::entities::source::SourceLog> for SourceLog {
+    fn from(log: crate::domain::entities::source::SourceLog) -> Self {
+        Self {
+            time: log.time,
+            level: log.level.into(),
+            target: log.target,
+            message: log.message,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct SourceDiagnostic {
+    pub source_id: i64,
+    pub name: String,
+    pub version: String,
+    /// empty means the source answered with no manga, usually broken by a site change
+    pub status: SourceStatus,
+    pub latency_ms: i64,
+    pub manga_count: i64,
+    pub error: Option<String>,
+}
+
+impl From<crate::domain::entities::source::SourceDiagnostic> for SourceDiagnostic {
+    fn from(diagnostic: crate::domain::entities::source::SourceDiagnostic) -> Self {
+        Self {
+            source_id: diagnostic.source_id,
+            name: diagnostic.name,
+            version: diagnostic.version,
+            status: diagnostic.status.into(),
+            latency_ms: diagnostic.latency.as_millis() as i64,
+            manga_count: diagnostic.manga_count as i64,
+            error: diagnostic.error,
+        }
+    }
+}
+
+/// actions a client should hide when unsupported
+#[derive(Debug, SimpleObject)]
+pub struct SourceCapabilities {
+    /// latest manga are listed by update
+    pub latest: bool,
+    /// search applies the source filters
+    pub search_filters: bool,
+    /// page count of a chapter is known before reading it
+    pub page_count: bool,
+    /// browsing needs logging in to the source
+    pub requires_login: bool,
+}
+
+impl From<crate::domain::entities::source::SourceCapabilities> for SourceCapabilities {
+    fn from(capabilities: crate::domain::entities::source::SourceCapabilities) -> Self {
+        Self {
+            latest: capabilities.latest,
+            search_filters: capabilities.search_filters,
+            page_count: capabilities.page_count,
+            requires_login: capabilities.requires_login,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct Header {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(InputObject)]
+pub struct HeaderInput {
+    pub name: String,
+    pub value: String,
+}
+
+fn to_header_override(
+    user_agent: Option<String>,
+    headers: Vec<HeaderInput>,
+) -> crate::domain::entities::source::HeaderOverride {
+    crate::domain::entities::source::HeaderOverride {
+        user_agent,
+        headers: headers
+            .into_iter()
+            .map(|header| (header.name, header.value))
+            .collect(),
+    }
+}
+
+fn to_headers(header_override: &crate::domain::entities::source::HeaderOverride) -> Vec<Header> {
+    header_override
+        .headers
+        .iter()
+        .map(|(name, value)| Header {
+            name: name.clone(),
+            value: value.clone(),
+        })
+        .collect()
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct HeaderOverride {
+    pub user_agent: Option<String>,
+    pub headers: Vec<Header>,
+}
+
+impl From<crate::domain::entities::source::HeaderOverride> for HeaderOverride {
+    fn from(header_override: crate::domain::entities::source::HeaderOverride) -> Self {
+        Self {
+            headers: to_headers(&header_override),
+            user_agent: header_override.user_agent,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct DomainHeaderOverride {
+    /// also applies to its subdomains
+    pub domain: String,
+    pub user_agent: Option<String>,
+    pub headers: Vec<Header>,
+}
+
+impl From<crate::domain::entities::source::DomainHeaderOverride> for DomainHeaderOverride {
+    fn from(domain_override: crate::domain::entities::source::DomainHeaderOverride) -> Self {
+        Self {
+            domain: domain_override.domain,
+            headers: to_headers(&domain_override.header_override),
+            user_agent: domain_override.header_override.user_agent,
+        }
+    }
+}
+
 #[derive(Clone, Deserialize)]
 pub struct Source {
     pub id: i64,
@@ -20,6 +256,14 @@ pub struct Source {
     pub icon: String,
     #[serde(default)]
     pub has_update: bool,
+    #[serde(default)]
+    pub languages: Option<Vec<String>>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub capabilities: crate::domain::entities::source::SourceCapabilities,
+    #[serde(default)]
+    pub nsfw: bool,
 }
 
 impl From<crate::domain::entities::source::Source> for Source {
@@ -33,6 +277,10 @@ impl From<crate::domain::entities::source::Source> for Source {
             lib_version: s.lib_version,
             icon: s.icon,
             has_update: s.has_update,
+            languages: s.languages,
+            pinned: s.pinned,
+            capabilities: s.capabilities,
+            nsfw: s.nsfw,
         }
     }
 }
@@ -63,17 +311,131 @@ impl Source {
         self.has_update
     }
 
+    /// null means every language
+    async fn languages(&self) -> Option<Vec<String>> {
+        self.languages.clone()
+    }
+
+    /// always false outside of installedSources
+    async fn pinned(&self) -> bool {
+        self.pinned
+    }
+
+    async fn capabilities(&self) -> SourceCapabilities {
+        self.capabilities.into()
+    }
+
+    async fn nsfw(&self) -> bool {
+        self.nsfw
+    }
+
     async fn filters(&self, ctx: &Context<'_>) -> Result<InputList> {
         let filters = ctx.data::<ExtensionManager>()?.filter_list(self.id)?;
 
         Ok(InputList(filters))
     }
 
+    /// filters the current user last saved for this source, null when never saved
+    async fn saved_filters(&self, ctx: &Context<'_>) -> Result<Option<InputList>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let filters = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .get_saved_filters(claims.sub, self.id)
+            .await?;
+
+        Ok(filters.map(InputList))
+    }
+
     async fn preferences(&self, ctx: &Context<'_>) -> Result<InputList> {
-        let preferences = ctx.data::<ExtensionManager>()?.get_preferences(self.id)?;
+        let preferences = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .get_preferences(self.id)
+            .await?;
 
         Ok(InputList(preferences))
     }
+
+    /// proxy set for this source, null when it uses the default from config
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn proxy(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+        let proxy = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .get_proxy(self.id)
+            .await?;
+
+        Ok(proxy)
+    }
+
+    /// headers sent in place of the extension's own, null when none are set
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn header_override(&self, ctx: &Context<'_>) -> Result<Option<HeaderOverride>> {
+        let header_override = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .get_header_override(self.id)
+            .await?
+            .map(HeaderOverride::from);
+
+        Ok(header_override)
+    }
+
+    /// requests per minute set for this source, null when it uses the default from config
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn rate_limit(&self, ctx: &Context<'_>) -> Result<Option<u32>> {
+        let rate_limit = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .get_rate_limit(self.id)
+            .await?;
+
+        Ok(rate_limit)
+    }
+
+    /// recent records logged by the extension, oldest first
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn logs(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(
+            desc = "least severe level returned",
+            default_with = "SourceLogLevel::Debug"
+        )]
+        level: SourceLogLevel,
+    ) -> Result<Vec<SourceLog>> {
+        let logs = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .get_logs(self.id, level.into())
+            .await?
+            .into_iter()
+            .map(SourceLog::from)
+            .collect();
+
+        Ok(logs)
+    }
+
+    async fn health(&self, ctx: &Context<'_>) -> Result<SourceHealth> {
+        let health = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .get_source_health(self.id)
+            .await?;
+
+        Ok(health.into())
+    }
+
+    /// username the current user is logged in to this source with
+    async fn logged_in_as(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let credential = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .get_credential(claims.sub, self.id)
+            .await?;
+
+        Ok(credential.map(|credential| credential.username))
+    }
 }
 
 #[derive(Default)]
@@ -85,32 +447,78 @@ impl SourceRoot {
         &self,
         ctx: &Context<'_>,
         check_update: bool,
+        #[graphql(
+            desc = "only return sources supporting the user's catalogue language and enabled languages",
+            default = false
+        )]
+        filter_language: bool,
+        #[graphql(
+            desc = "only return sources pinned by the user, the default global search targets",
+            default = false
+        )]
+        pinned_only: bool,
     ) -> Result<Vec<Source>> {
-        let _ = ctx.data::<Claims>()?;
+        let claims = ctx.data::<Claims>()?;
 
         let repo_url = &ctx.data::<Config>()?.extension_repository;
 
+        let user = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .fetch_user_by_id(claims.sub)
+            .await?;
+        let language = if filter_language {
+            user.catalogue_language
+        } else {
+            None
+        };
+
         let sources = ctx
             .data::<SourceService<SourceRepositoryImpl>>()?
-            .get_installed_sources(repo_url, check_update)
+            .get_installed_sources_for_user(claims.sub, repo_url, check_update)
             .await?
             .into_iter()
+            .filter(|source| !pinned_only || source.pinned)
+            .filter(|source| !source.is_hidden_for(&user))
+            .filter(|source| {
+                language
+                    .as_deref()
+                    .map(|language| source.supports_language(language))
+                    .unwrap_or(true)
+                    && (!filter_language
+                        || source.supports_any_language(user.enabled_languages.as_deref()))
+            })
             .map(Source::from)
             .collect();
 
         Ok(sources)
     }
 
-    async fn available_sources(&self, ctx: &Context<'_>) -> Result<Vec<Source>> {
-        let _ = ctx.data::<Claims>()?;
+    async fn available_sources(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "only return sources supporting this language")] language: Option<String>,
+    ) -> Result<Vec<Source>> {
+        let claims = ctx.data::<Claims>()?;
 
         let repo_url = &ctx.data::<Config>()?.extension_repository;
 
+        let user = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .fetch_user_by_id(claims.sub)
+            .await?;
+
         let sources = ctx
             .data::<SourceService<SourceRepositoryImpl>>()?
             .get_available_sources(repo_url)
             .await?
             .into_iter()
+            .filter(|source| !source.is_hidden_for(&user))
+            .filter(|source| {
+                language
+                    .as_deref()
+                    .map(|language| source.supports_language(language))
+                    .unwrap_or(true)
+            })
             .map(Source::from)
             .collect();
 
@@ -128,6 +536,52 @@ impl SourceRoot {
 
         Ok(source)
     }
+
+    /// fetch the first popular page of every installed source and report how each one responded
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn source_diagnostics(&self, ctx: &Context<'_>) -> Result<Vec<SourceDiagnostic>> {
+        let diagnostics = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .diagnose_sources()
+            .await?
+            .into_iter()
+            .map(SourceDiagnostic::from)
+            .collect();
+
+        Ok(diagnostics)
+    }
+
+    /// headers sent on image fetches from a domain, e.g. the cdn of a source
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn domain_header_overrides(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Vec<DomainHeaderOverride>> {
+        let overrides = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .get_domain_header_overrides()
+            .await?
+            .into_iter()
+            .map(DomainHeaderOverride::from)
+            .collect();
+
+        Ok(overrides)
+    }
+
+    /// recorded health of every installed source, including open circuits
+    async fn source_health(&self, ctx: &Context<'_>) -> Result<Vec<SourceHealth>> {
+        let _ = ctx.data::<Claims>()?;
+
+        let health = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .get_all_source_health()
+            .await?
+            .into_iter()
+            .map(SourceHealth::from)
+            .collect();
+
+        Ok(health)
+    }
 }
 
 #[derive(Default)]
@@ -150,6 +604,41 @@ impl SourceMutationRoot {
         Ok(source_id)
     }
 
+    /// install a source package not published in the repository, returns the source id
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn install_source_from_file(&self, ctx: &Context<'_>, file: Upload) -> Result<i64> {
+        let mut contents = vec![];
+        file.value(ctx)?.content.read_to_end(&mut contents)?;
+
+        let source_id = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .install_source_from_file(&contents)
+            .await?;
+
+        Ok(source_id)
+    }
+
+    /// install the source package at `path` on the server, returns the source id
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn install_source_from_path(&self, ctx: &Context<'_>, path: String) -> Result<i64> {
+        let source_id = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .install_source_from_path(&path)
+            .await?;
+
+        Ok(source_id)
+    }
+
+    /// load the source from its library file again, e.g. after rebuilding it
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn reload_source(&self, ctx: &Context<'_>, source_id: i64) -> Result<i64> {
+        ctx.data::<SourceService<SourceRepositoryImpl>>()?
+            .reload_source(source_id)
+            .await?;
+
+        Ok(source_id)
+    }
+
     #[graphql(guard = "AdminGuard::new()")]
     async fn uninstall_source(&self, ctx: &Context<'_>, source_id: i64) -> Result<i64> {
         ctx.data::<SourceService<SourceRepositoryImpl>>()?
@@ -177,10 +666,162 @@ impl SourceMutationRoot {
         source_id: i64,
         preferences: InputList,
     ) -> Result<i64> {
-        ctx.data::<ExtensionManager>()?
+        ctx.data::<SourceService<SourceRepositoryImpl>>()?
             .set_preferences(source_id, preferences.0)
             .await?;
 
         Ok(source_id)
     }
+
+    /// http, https or socks5 proxy for requests to the source, null to use the default
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn set_source_proxy(
+        &self,
+        ctx: &Context<'_>,
+        source_id: i64,
+        proxy: Option<String>,
+    ) -> Result<i64> {
+        ctx.data::<SourceService<SourceRepositoryImpl>>()?
+            .set_proxy(source_id, proxy.as_deref())
+            .await?;
+
+        Ok(source_id)
+    }
+
+    /// user agent and headers sent on requests to the source and its images, set neither to
+    /// use the extension's own again
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn set_source_headers(
+        &self,
+        ctx: &Context<'_>,
+        source_id: i64,
+        user_agent: Option<String>,
+        #[graphql(default)] headers: Vec<HeaderInput>,
+    ) -> Result<i64> {
+        ctx.data::<SourceService<SourceRepositoryImpl>>()?
+            .set_header_override(source_id, to_header_override(user_agent, headers))
+            .await?;
+
+        Ok(source_id)
+    }
+
+    /// user agent and headers sent on image fetches from `domain` and its subdomains, set
+    /// neither to remove them, returns the domain as stored
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn set_domain_headers(
+        &self,
+        ctx: &Context<'_>,
+        domain: String,
+        user_agent: Option<String>,
+        #[graphql(default)] headers: Vec<HeaderInput>,
+    ) -> Result<String> {
+        let domain = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .set_domain_header_override(&domain, to_header_override(user_agent, headers))
+            .await?;
+
+        Ok(domain)
+    }
+
+    /// requests per minute to the source, 0 for unlimited, null to use the default
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn set_source_rate_limit(
+        &self,
+        ctx: &Context<'_>,
+        source_id: i64,
+        requests_per_minute: Option<u32>,
+    ) -> Result<i64> {
+        ctx.data::<SourceService<SourceRepositoryImpl>>()?
+            .set_rate_limit(source_id, requests_per_minute)
+            .await?;
+
+        Ok(source_id)
+    }
+
+    /// re-enable a source disabled after failing for too long, or disable it manually
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn set_source_enabled(
+        &self,
+        ctx: &Context<'_>,
+        source_id: i64,
+        enabled: bool,
+    ) -> Result<SourceHealth> {
+        let health = ctx
+            .data::<SourceService<SourceRepositoryImpl>>()?
+            .set_source_enabled(source_id, enabled)
+            .await?;
+
+        Ok(health.into())
+    }
+
+    async fn login_to_source(
+        &self,
+        ctx: &Context<'_>,
+        source_id: i64,
+        username: String,
+        password: String,
+    ) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<SourceService<SourceRepositoryImpl>>()?
+            .login_to_source(claims.sub, source_id, &username, &password)
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn logout_from_source(&self, ctx: &Context<'_>, source_id: i64) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<SourceService<SourceRepositoryImpl>>()?
+            .logout_from_source(claims.sub, source_id)
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn pin_source(&self, ctx: &Context<'_>, source_id: i64) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<SourceService<SourceRepositoryImpl>>()?
+            .pin_source(claims.sub, source_id)
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn unpin_source(&self, ctx: &Context<'_>, source_id: i64) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<SourceService<SourceRepositoryImpl>>()?
+            .unpin_source(claims.sub, source_id)
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn save_source_filters(
+        &self,
+        ctx: &Context<'_>,
+        source_id: i64,
+        #[graphql(desc = "empty list resets to the source's default filters")] filters: InputList,
+    ) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<SourceService<SourceRepositoryImpl>>()?
+            .save_filters(claims.sub, source_id, filters.0)
+            .await?;
+
+        Ok(true)
+    }
 }
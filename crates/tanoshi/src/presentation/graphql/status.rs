@@ -1,15 +1,103 @@
 use async_graphql::{Context, Object, Result, SimpleObject};
 
+use super::guard::AdminGuard;
 use crate::{
-    domain::services::user::UserService,
-    infrastructure::{auth::Claims, domain::repositories::user::UserRepositoryImpl},
+    domain::{
+        entities::image,
+        services::{
+            image::ImageService, library::LibraryService, setting::SettingService,
+            user::UserService,
+        },
+    },
+    infrastructure::{
+        auth::Claims,
+        config::Config,
+        domain::repositories::{
+            image::ImageRepositoryImpl, image_cache::ImageCacheRepositoryImpl,
+            library::LibraryRepositoryImpl, setting::SettingRepositoryImpl,
+            user::UserRepositoryImpl,
+        },
+        notification::Notification,
+        telemetry,
+    },
 };
+use tanoshi_vm::extension::ExtensionManager;
 
 #[derive(Debug, SimpleObject)]
 struct Status {
     activated: bool,
     version: String,
     loggedin: bool,
+    /// markdown message set by admin
+    announcement: Option<String>,
+}
+
+#[derive(Debug, SimpleObject)]
+struct Telemetry {
+    enabled: bool,
+    /// where the payload is sent, nothing is sent when empty
+    url: Option<String>,
+    /// json sent once a day when enabled
+    payload: String,
+}
+
+/// Lookups of cached images since the server started
+#[derive(Debug, SimpleObject)]
+struct ImageCacheStats {
+    hits: u64,
+    misses: u64,
+    /// bytes taken on disk, 0 when images are cached in a bucket
+    size: u64,
+    /// bytes the cache is trimmed to, unlimited when empty
+    max_size: Option<u64>,
+}
+
+impl From<image::ImageCacheStats> for ImageCacheStats {
+    fn from(stats: image::ImageCacheStats) -> Self {
+        Self {
+            hits: stats.hits,
+            misses: stats.misses,
+            size: stats.size,
+            max_size: stats.max_size,
+        }
+    }
+}
+
+/// Requests to an image host since the server started
+#[derive(Debug, SimpleObject)]
+struct ImageHostStats {
+    domain: String,
+    requests: u64,
+    /// requests failing or answered with an error status
+    errors: u64,
+    /// bytes received from the host
+    bytes: u64,
+    /// milliseconds until the host answered
+    average_ms: u64,
+    slowest_ms: u64,
+}
+
+impl From<image::ImageHostStats> for ImageHostStats {
+    fn from(stats: image::ImageHostStats) -> Self {
+        Self {
+            average_ms: stats.average_ms(),
+            domain: stats.domain,
+            requests: stats.requests,
+            errors: stats.errors,
+            bytes: stats.bytes,
+            slowest_ms: stats.slowest_ms,
+        }
+    }
+}
+
+/// Images served since the server started
+#[derive(Debug, SimpleObject)]
+struct ImageProxyStats {
+    requests: u64,
+    bytes_served: u64,
+    cache: ImageCacheStats,
+    /// the slowest on average first
+    hosts: Vec<ImageHostStats>,
 }
 
 #[derive(Default)]
@@ -26,11 +114,100 @@ impl StatusRoot {
             .await?
             .is_empty();
         let version = env!("CARGO_PKG_VERSION").to_string();
+        let announcement = ctx
+            .data::<SettingService<SettingRepositoryImpl>>()?
+            .get_announcement()
+            .await?;
 
         Ok(Status {
             activated,
             version,
             loggedin,
+            announcement,
         })
     }
+
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn telemetry(&self, ctx: &Context<'_>) -> Result<Telemetry> {
+        let config = ctx.data::<Config>()?;
+        let enabled = ctx
+            .data::<SettingService<SettingRepositoryImpl>>()?
+            .get_telemetry_enabled()
+            .await?;
+        let installed_sources = ctx.data::<ExtensionManager>()?.list().await?.len();
+        let library_size = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .get_library_manga_count()
+            .await?;
+
+        let payload = telemetry::collect(config, installed_sources, library_size);
+
+        Ok(Telemetry {
+            enabled,
+            url: config.telemetry_url.clone(),
+            payload: serde_json::to_string_pretty(&payload)?,
+        })
+    }
+
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn image_cache_stats(&self, ctx: &Context<'_>) -> Result<ImageCacheStats> {
+        let stats = ctx
+            .data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?
+            .cache_stats()
+            .await;
+
+        Ok(stats.into())
+    }
+
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn image_proxy_stats(&self, ctx: &Context<'_>) -> Result<ImageProxyStats> {
+        let image_svc =
+            ctx.data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?;
+        let served = image_svc.served_stats();
+
+        Ok(ImageProxyStats {
+            requests: served.requests,
+            bytes_served: served.bytes,
+            cache: image_svc.cache_stats().await.into(),
+            hosts: image_svc
+                .host_stats()
+                .into_iter()
+                .map(ImageHostStats::from)
+                .collect(),
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct StatusMutationRoot;
+
+#[Object]
+impl StatusMutationRoot {
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn set_announcement(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "markdown message, empty to remove")] announcement: Option<String>,
+    ) -> Result<bool> {
+        ctx.data::<SettingService<SettingRepositoryImpl>>()?
+            .set_announcement(announcement.as_deref())
+            .await?;
+
+        if let Some(announcement) = announcement.filter(|a| !a.trim().is_empty()) {
+            ctx.data::<Notification<UserRepositoryImpl>>()?
+                .send_announcement(&announcement)
+                .await?;
+        }
+
+        Ok(true)
+    }
+
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn set_telemetry(&self, ctx: &Context<'_>, enabled: bool) -> Result<bool> {
+        ctx.data::<SettingService<SettingRepositoryImpl>>()?
+            .set_telemetry_enabled(enabled)
+            .await?;
+
+        Ok(enabled)
+    }
 }
@@ -0,0 +1,125 @@
+use crate::{
+    domain::services::library::LibraryService,
+    infrastructure::{auth::Claims, domain::repositories::library::LibraryRepositoryImpl},
+};
+use async_graphql::{Context, Object, Result, SimpleObject};
+
+/// Label put on library manga, independent of categories
+#[derive(Debug, Clone, SimpleObject)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+}
+
+impl From<crate::domain::entities::library::Tag> for Tag {
+    fn from(tag: crate::domain::entities::library::Tag) -> Self {
+        Self {
+            id: tag.id,
+            name: tag.name,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TagRoot;
+
+#[Object]
+impl TagRoot {
+    async fn tags(&self, ctx: &Context<'_>) -> Result<Vec<Tag>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let tags = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .get_tags_by_user_id(claims.sub)
+            .await?
+            .into_iter()
+            .map(Tag::from)
+            .collect();
+
+        Ok(tags)
+    }
+}
+
+#[derive(Default)]
+pub struct TagMutationRoot;
+
+#[Object]
+impl TagMutationRoot {
+    async fn create_tag(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "tag name")] name: String,
+    ) -> Result<Tag> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let tag = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .create_tag(claims.sub, &name)
+            .await?
+            .into();
+
+        Ok(tag)
+    }
+
+    async fn rename_tag(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "tag id")] id: i64,
+        #[graphql(desc = "tag name")] name: String,
+    ) -> Result<Tag> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let tag = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .rename_tag(claims.sub, id, &name)
+            .await?
+            .into();
+
+        Ok(tag)
+    }
+
+    /// removes the tag from every manga it's on
+    async fn delete_tag(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "tag id")] id: i64,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<LibraryService<LibraryRepositoryImpl>>()?
+            .delete_tag(claims.sub, id)
+            .await?;
+
+        Ok(1)
+    }
+
+    /// replace tags of a manga in library, returns its tags
+    async fn set_manga_tags(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "manga id")] manga_id: i64,
+        #[graphql(desc = "tag ids, empty removes every tag")] tag_ids: Vec<i64>,
+    ) -> Result<Vec<Tag>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let tags = ctx
+            .data::<LibraryService<LibraryRepositoryImpl>>()?
+            .set_manga_tags(claims.sub, manga_id, &tag_ids)
+            .await?
+            .into_iter()
+            .map(Tag::from)
+            .collect();
+
+        Ok(tags)
+    }
+}
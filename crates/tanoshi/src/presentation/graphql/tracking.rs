@@ -1,11 +1,18 @@
+use std::collections::HashSet;
+
 use async_graphql::{Context, InputObject, Object, Result, SimpleObject};
 use chrono::NaiveDateTime;
 
-use crate::domain::services::tracker::TrackerService;
+use crate::domain::services::{manga::MangaService, tracker::TrackerService};
 use crate::infrastructure::auth::Claims;
-use crate::infrastructure::domain::repositories::tracker::TrackerRepositoryImpl;
+use crate::infrastructure::domain::repositories::{
+    manga::MangaRepositoryImpl, tracker::TrackerRepositoryImpl,
+};
 use tanoshi_tracker::{anilist, myanimelist};
 
+/// other titles of a manga searched on a tracker besides the one asked for
+const MAX_ALT_TITLE_SEARCHES: usize = 3;
+
 #[derive(SimpleObject)]
 pub struct Session {
     pub authorize_url: String,
@@ -156,18 +163,41 @@ impl TrackingRoot {
         ctx: &Context<'_>,
         tracker: String,
         title: String,
+        #[graphql(desc = "manga id to also search its other titles")] manga_id: Option<i64>,
     ) -> Result<Vec<TrackerManga>> {
         let claim = ctx
             .data::<Claims>()
             .map_err(|_| "token not exists, please login")?;
 
-        let manga = ctx
-            .data::<TrackerService<TrackerRepositoryImpl>>()?
-            .search_manga(claim.sub, &tracker, &title)
-            .await?
-            .into_iter()
-            .map(|m| m.into())
-            .collect();
+        let mut titles = vec![title];
+        if let Some(manga_id) = manga_id {
+            let manga = ctx
+                .data::<MangaService<MangaRepositoryImpl>>()?
+                .fetch_manga_by_id(manga_id, false)
+                .await?;
+            for alt_title in std::iter::once(manga.title).chain(manga.alt_titles) {
+                if titles.len() > MAX_ALT_TITLE_SEARCHES {
+                    break;
+                }
+                if !titles.iter().any(|t| t.eq_ignore_ascii_case(&alt_title)) {
+                    titles.push(alt_title);
+                }
+            }
+        }
+
+        let tracker_svc = ctx.data::<TrackerService<TrackerRepositoryImpl>>()?;
+        let mut tracker_manga_ids = HashSet::new();
+        let mut manga = vec![];
+        for title in titles {
+            for m in tracker_svc
+                .search_manga(claim.sub, &tracker, &title)
+                .await?
+            {
+                if tracker_manga_ids.insert(m.tracker_manga_id.clone()) {
+                    manga.push(m.into());
+                }
+            }
+        }
 
         Ok(manga)
     }
@@ -7,9 +7,61 @@ use crate::{
         domain::repositories::{tracker::TrackerRepositoryImpl, user::UserRepositoryImpl},
     },
 };
-use async_graphql::{Context, InputObject, Object, Result};
+use async_graphql::{Context, Enum, InputObject, Object, Result};
 use tanoshi_tracker::{anilist, myanimelist};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum NotificationDigest {
+    Off,
+    Hourly,
+    Daily,
+}
+
+impl From<Option<i64>> for NotificationDigest {
+    fn from(interval: Option<i64>) -> Self {
+        match interval {
+            Some(interval) if interval >= 86400 => Self::Daily,
+            Some(_) => Self::Hourly,
+            None => Self::Off,
+        }
+    }
+}
+
+impl From<NotificationDigest> for Option<i64> {
+    fn from(digest: NotificationDigest) -> Self {
+        match digest {
+            NotificationDigest::Off => None,
+            NotificationDigest::Hourly => Some(3600),
+            NotificationDigest::Daily => Some(86400),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum CatalogueSort {
+    Popular,
+    Latest,
+}
+
+impl From<crate::domain::entities::user::CatalogueSort> for CatalogueSort {
+    fn from(sort: crate::domain::entities::user::CatalogueSort) -> Self {
+        use crate::domain::entities::user::CatalogueSort as S;
+        match sort {
+            S::Popular => Self::Popular,
+            S::Latest => Self::Latest,
+        }
+    }
+}
+
+impl From<CatalogueSort> for crate::domain::entities::user::CatalogueSort {
+    fn from(sort: CatalogueSort) -> Self {
+        match sort {
+            CatalogueSort::Popular => Self::Popular,
+            CatalogueSort::Latest => Self::Latest,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct User {
     pub id: i64,
@@ -19,6 +71,22 @@ pub struct User {
     telegram_chat_id: Option<i64>,
     pushover_user_key: Option<String>,
     gotify_token: Option<String>,
+    notification_digest_interval: Option<i64>,
+    notify_chapter_category_ids: Option<Vec<i64>>,
+    notify_download_failures: bool,
+    notify_announcements: bool,
+    pushover_device: Option<String>,
+    pushover_sound: Option<String>,
+    pushover_chapter_priority: i64,
+    pushover_alert_priority: i64,
+    catalogue_sort: CatalogueSort,
+    catalogue_language: Option<String>,
+    catalogue_hide_library: bool,
+    enabled_languages: Option<Vec<String>>,
+    hide_nsfw: bool,
+    device_email: Option<String>,
+    data_saver: bool,
+    chapter_languages: Option<Vec<String>>,
 }
 
 impl From<crate::domain::entities::user::User> for User {
@@ -31,6 +99,22 @@ impl From<crate::domain::entities::user::User> for User {
             telegram_chat_id: val.telegram_chat_id,
             pushover_user_key: val.pushover_user_key,
             gotify_token: val.gotify_token,
+            notification_digest_interval: val.notification_digest_interval,
+            notify_chapter_category_ids: val.notify_chapter_category_ids,
+            notify_download_failures: val.notify_download_failures,
+            notify_announcements: val.notify_announcements,
+            pushover_device: val.pushover_device,
+            pushover_sound: val.pushover_sound,
+            pushover_chapter_priority: val.pushover_chapter_priority,
+            pushover_alert_priority: val.pushover_alert_priority,
+            catalogue_sort: val.catalogue_sort.into(),
+            catalogue_language: val.catalogue_language,
+            catalogue_hide_library: val.catalogue_hide_library,
+            enabled_languages: val.enabled_languages,
+            hide_nsfw: val.hide_nsfw,
+            device_email: val.device_email,
+            data_saver: val.data_saver,
+            chapter_languages: val.chapter_languages,
         }
     }
 }
@@ -69,10 +153,87 @@ impl User {
         self.pushover_user_key.clone()
     }
 
+    /// pushover device name to target, null sends to all devices
+    async fn pushover_device(&self) -> Option<String> {
+        self.pushover_device.clone()
+    }
+
+    /// pushover sound name, null uses the user's default sound
+    async fn pushover_sound(&self) -> Option<String> {
+        self.pushover_sound.clone()
+    }
+
+    /// pushover priority for new chapters, -2 to 1
+    async fn pushover_chapter_priority(&self) -> i64 {
+        self.pushover_chapter_priority
+    }
+
+    /// pushover priority for download failures and admin alerts, -2 to 1
+    async fn pushover_alert_priority(&self) -> i64 {
+        self.pushover_alert_priority
+    }
+
     async fn gotify_token(&self) -> Option<String> {
         self.gotify_token.clone()
     }
 
+    async fn notification_digest(&self) -> NotificationDigest {
+        self.notification_digest_interval.into()
+    }
+
+    /// categories to notify new chapters for, 0 is the default category, null means all
+    async fn notify_chapter_category_ids(&self) -> Option<Vec<i64>> {
+        self.notify_chapter_category_ids.clone()
+    }
+
+    async fn notify_download_failures(&self) -> bool {
+        self.notify_download_failures
+    }
+
+    async fn notify_announcements(&self) -> bool {
+        self.notify_announcements
+    }
+
+    /// listing used by getCatalogueManga
+    async fn catalogue_sort(&self) -> CatalogueSort {
+        self.catalogue_sort
+    }
+
+    /// installedSources only returns sources supporting this language when set
+    async fn catalogue_language(&self) -> Option<String> {
+        self.catalogue_language.clone()
+    }
+
+    /// manga already in library are removed from source listings
+    async fn catalogue_hide_library(&self) -> bool {
+        self.catalogue_hide_library
+    }
+
+    /// sources not supporting any of these languages are hidden, null enables every language
+    async fn enabled_languages(&self) -> Option<Vec<String>> {
+        self.enabled_languages.clone()
+    }
+
+    /// sources flagged nsfw are hidden from source listings and catalogues
+    async fn hide_nsfw(&self) -> bool {
+        self.hide_nsfw
+    }
+
+    /// address chapters are sent to with sendChapterToDevice
+    async fn device_email(&self) -> Option<String> {
+        self.device_email.clone()
+    }
+
+    /// chapter pages are served shrunk and at a lower quality to save data
+    async fn data_saver(&self) -> bool {
+        self.data_saver
+    }
+
+    /// chapters in other languages are left out of manga without their own, null lists every language
+    async fn chapter_languages(&self) -> Option<Vec<String>> {
+        self.chapter_languages.clone()
+    }
+
     async fn myanimelist_status(&self, ctx: &Context<'_>) -> Result<bool> {
         let user = ctx
             .data::<Claims>()
@@ -103,6 +264,28 @@ struct ProfileInput {
     pub telegram_chat_id: Option<i64>,
     pub pushover_user_key: Option<String>,
     pub gotify_token: Option<String>,
+    pub pushover_device: Option<String>,
+    pub pushover_sound: Option<String>,
+    #[graphql(default)]
+    pub pushover_chapter_priority: i64,
+    #[graphql(default)]
+    pub pushover_alert_priority: i64,
+}
+
+#[derive(InputObject)]
+struct NotificationPreferencesInput {
+    /// categories to notify new chapters for, 0 is the default category, null means all
+    pub chapter_category_ids: Option<Vec<i64>>,
+    pub download_failures: bool,
+    pub announcements: bool,
+}
+
+#[derive(InputObject)]
+struct CataloguePreferencesInput {
+    pub sort: CatalogueSort,
+    /// language code such as `en`, null shows every source
+    pub language: Option<String>,
+    pub hide_library: bool,
 }
 
 #[derive(Default)]
@@ -129,6 +312,8 @@ impl UserRoot {
             username: user.username,
             is_admin: user.is_admin,
             exp: (current_time + std::time::Duration::from_secs(2678400)).as_secs() as usize, // 31 days
+            unlocked_categories: vec![],
+            lock_generation: 0,
         };
         let token = auth::encode_jwt(secret, &claims)?;
 
@@ -212,12 +397,161 @@ impl UserMutationRoot {
                 input.telegram_chat_id,
                 input.pushover_user_key,
                 input.gotify_token,
+                input.pushover_device,
+                input.pushover_sound,
+                input.pushover_chapter_priority,
+                input.pushover_alert_priority,
             )
             .await?;
 
         Ok(1)
     }
 
+    async fn update_notification_digest(
+        &self,
+        ctx: &Context<'_>,
+        digest: NotificationDigest,
+    ) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .update_notification_digest_interval(claims.sub, digest.into())
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn update_notification_preferences(
+        &self,
+        ctx: &Context<'_>,
+        input: NotificationPreferencesInput,
+    ) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .update_notification_preferences(
+                claims.sub,
+                input.chapter_category_ids,
+                input.download_failures,
+                input.announcements,
+            )
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn update_catalogue_preferences(
+        &self,
+        ctx: &Context<'_>,
+        input: CataloguePreferencesInput,
+    ) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .update_catalogue_preferences(
+                claims.sub,
+                input.sort.into(),
+                input.language,
+                input.hide_library,
+            )
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn update_enabled_languages(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "null or empty enables every language")] languages: Option<Vec<String>>,
+    ) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .update_enabled_languages(claims.sub, languages)
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn update_hide_nsfw(&self, ctx: &Context<'_>, hide: bool) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .update_hide_nsfw(claims.sub, hide)
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn update_data_saver(&self, ctx: &Context<'_>, enabled: bool) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .update_data_saver(claims.sub, enabled)
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn update_chapter_languages(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "null or empty lists every language")] languages: Option<Vec<String>>,
+    ) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .update_chapter_languages(claims.sub, languages)
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn update_device_email(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "null or empty unsets it")] email: Option<String>,
+    ) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .update_device_email(claims.sub, email)
+            .await?;
+
+        Ok(true)
+    }
+
+    /// code to send to telegram bot as `/start CODE`, valid for 10 minutes
+    async fn create_telegram_pairing_code(&self, ctx: &Context<'_>) -> Result<String> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        if ctx.data::<Config>()?.telegram.is_none() {
+            return Err("telegram bot is not configured".into());
+        }
+
+        Ok(ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .create_telegram_pairing_code(claims.sub))
+    }
+
     async fn tracker_logout(&self, ctx: &Context<'_>, tracker: String) -> Result<u64> {
         let claims = ctx
             .data::<Claims>()
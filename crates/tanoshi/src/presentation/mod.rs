@@ -2,12 +2,13 @@
 pub mod assets;
 pub mod graphql;
 pub mod rest;
+pub mod telegram;
 pub mod token;
 
 use anyhow::anyhow;
 use axum::{
     extract::Extension,
-    routing::{get, post},
+    routing::{any, get, post, put},
     Router,
 };
 use graphql::schema::TanoshiSchema;
@@ -19,22 +20,43 @@ use self::{
         graphql_handler, graphql_playground,
         schema::{DatabaseLoader, SchemaBuilder},
     },
-    rest::{health::health_check, image::fetch_image},
+    rest::{
+        dav::webdav,
+        download::get_download_usage,
+        export::{
+            download_export, export_chapter_cbz, export_chapter_epub, export_chapter_pdf,
+            get_export, start_epub_export, start_pdf_export,
+        },
+        health::health_check,
+        image::{fetch_chapter_sprite, fetch_image},
+        library::{get_recently_added_chapters, get_recently_added_manga},
+        source::{
+            get_available_sources, get_filters, get_installed_sources, get_logs, get_preferences,
+            set_preferences,
+        },
+        status::server_status,
+    },
 };
 use crate::{
-    application::worker::downloads::DownloadSender,
+    application::worker::{
+        automation::AutomationSender, download_ahead::DownloadAheadSender,
+        downloads::DownloadSender, export::ExportJobs, library_import::ImportSender,
+        prefetch::PrefetchSender,
+    },
     domain::services::{
-        chapter::ChapterService, download::DownloadService, history::HistoryService,
-        image::ImageService, library::LibraryService, manga::MangaService, source::SourceService,
-        tracker::TrackerService, user::UserService,
+        automation::AutomationService, chapter::ChapterService, download::DownloadService,
+        export::ExportService, history::HistoryService, image::ImageService, inbox::InboxService,
+        library::LibraryService, manga::MangaService, setting::SettingService,
+        source::SourceService, tracker::TrackerService, user::UserService,
     },
     infrastructure::{
         config::Config,
         domain::repositories::{
-            chapter::ChapterRepositoryImpl, download::DownloadRepositoryImpl,
-            history::HistoryRepositoryImpl, image::ImageRepositoryImpl,
-            image_cache::ImageCacheRepositoryImpl, library::LibraryRepositoryImpl,
-            manga::MangaRepositoryImpl, source::SourceRepositoryImpl,
+            automation::AutomationRepositoryImpl, chapter::ChapterRepositoryImpl,
+            download::DownloadRepositoryImpl, history::HistoryRepositoryImpl,
+            image::ImageRepositoryImpl, image_cache::ImageCacheRepositoryImpl,
+            inbox::InboxRepositoryImpl, library::LibraryRepositoryImpl, manga::MangaRepositoryImpl,
+            setting::SettingRepositoryImpl, source::SourceRepositoryImpl,
             tracker::TrackerRepositoryImpl, user::UserRepositoryImpl,
         },
         notification::Notification,
@@ -42,6 +64,13 @@ use crate::{
 };
 use tanoshi_vm::extension::ExtensionManager;
 
+type ChapterExportService = ExportService<
+    ChapterRepositoryImpl,
+    MangaRepositoryImpl,
+    ImageRepositoryImpl,
+    LibraryRepositoryImpl,
+>;
+
 #[derive(Default)]
 pub struct ServerBuilder {
     config: Option<Config>,
@@ -54,8 +83,17 @@ pub struct ServerBuilder {
     library_svc: Option<LibraryService<LibraryRepositoryImpl>>,
     history_svc: Option<HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>>,
     download_svc: Option<DownloadService<DownloadRepositoryImpl>>,
+    export_svc: Option<ChapterExportService>,
+    export_jobs: Option<ExportJobs>,
+    setting_svc: Option<SettingService<SettingRepositoryImpl>>,
+    automation_svc: Option<AutomationService<AutomationRepositoryImpl>>,
+    inbox_svc: Option<InboxService<InboxRepositoryImpl>>,
     ext_manager: Option<ExtensionManager>,
     download_tx: Option<DownloadSender>,
+    automation_tx: Option<AutomationSender>,
+    download_ahead_tx: Option<DownloadAheadSender>,
+    prefetch_tx: Option<PrefetchSender>,
+    import_tx: Option<ImportSender>,
     notifier: Option<Notification<UserRepositoryImpl>>,
     loader: Option<DatabaseLoader>,
     enable_playground: bool,
@@ -142,6 +180,44 @@ impl ServerBuilder {
         }
     }
 
+    pub fn with_export_svc(self, export_svc: ChapterExportService) -> Self {
+        Self {
+            export_svc: Some(export_svc),
+            ..self
+        }
+    }
+
+    pub fn with_export_jobs(self, export_jobs: ExportJobs) -> Self {
+        Self {
+            export_jobs: Some(export_jobs),
+            ..self
+        }
+    }
+
+    pub fn with_setting_svc(self, setting_svc: SettingService<SettingRepositoryImpl>) -> Self {
+        Self {
+            setting_svc: Some(setting_svc),
+            ..self
+        }
+    }
+
+    pub fn with_automation_svc(
+        self,
+        automation_svc: AutomationService<AutomationRepositoryImpl>,
+    ) -> Self {
+        Self {
+            automation_svc: Some(automation_svc),
+            ..self
+        }
+    }
+
+    pub fn with_inbox_svc(self, inbox_svc: InboxService<InboxRepositoryImpl>) -> Self {
+        Self {
+            inbox_svc: Some(inbox_svc),
+            ..self
+        }
+    }
+
     pub fn with_ext_manager(self, ext_manager: ExtensionManager) -> Self {
         Self {
             ext_manager: Some(ext_manager),
@@ -156,6 +232,34 @@ impl ServerBuilder {
         }
     }
 
+    pub fn with_automation_tx(self, automation_tx: AutomationSender) -> Self {
+        Self {
+            automation_tx: Some(automation_tx),
+            ..self
+        }
+    }
+
+    pub fn with_download_ahead_tx(self, download_ahead_tx: DownloadAheadSender) -> Self {
+        Self {
+            download_ahead_tx: Some(download_ahead_tx),
+            ..self
+        }
+    }
+
+    pub fn with_prefetch_tx(self, prefetch_tx: PrefetchSender) -> Self {
+        Self {
+            prefetch_tx: Some(prefetch_tx),
+            ..self
+        }
+    }
+
+    pub fn with_import_tx(self, import_tx: ImportSender) -> Self {
+        Self {
+            import_tx: Some(import_tx),
+            ..self
+        }
+    }
+
     pub fn with_notifier(self, notifier: Notification<UserRepositoryImpl>) -> Self {
         Self {
             notifier: Some(notifier),
@@ -200,29 +304,59 @@ impl ServerBuilder {
         let download_svc = self
             .download_svc
             .ok_or_else(|| anyhow!("no download service"))?;
+        let export_svc = self
+            .export_svc
+            .ok_or_else(|| anyhow!("no export service"))?;
+        let export_jobs = self.export_jobs.ok_or_else(|| anyhow!("no export jobs"))?;
+        let setting_svc = self
+            .setting_svc
+            .ok_or_else(|| anyhow!("no setting service"))?;
+        let automation_svc = self
+            .automation_svc
+            .ok_or_else(|| anyhow!("no automation service"))?;
+        let inbox_svc = self.inbox_svc.ok_or_else(|| anyhow!("no inbox service"))?;
         let extension_manager = self
             .ext_manager
             .ok_or_else(|| anyhow!("no extension manager"))?;
         let download_tx = self
             .download_tx
             .ok_or_else(|| anyhow!("no download sender"))?;
+        let automation_tx = self
+            .automation_tx
+            .ok_or_else(|| anyhow!("no automation sender"))?;
+        let download_ahead_tx = self
+            .download_ahead_tx
+            .ok_or_else(|| anyhow!("no download ahead sender"))?;
+        let prefetch_tx = self
+            .prefetch_tx
+            .ok_or_else(|| anyhow!("no prefetch sender"))?;
+        let import_tx = self.import_tx.ok_or_else(|| anyhow!("no import sender"))?;
         let notifier = self.notifier.ok_or_else(|| anyhow!("no notifier"))?;
         let loader = self.loader.ok_or_else(|| anyhow!("no loader"))?;
 
         let schema = SchemaBuilder::new()
             .data(config.clone())
-            .data(user_svc)
+            .data(user_svc.clone())
             .data(tracker_svc)
-            .data(source_svc)
+            .data(source_svc.clone())
             .data(manga_svc)
             .data(chapter_svc)
             .data(image_svc.clone())
-            .data(library_svc)
+            .data(library_svc.clone())
             .data(history_svc)
-            .data(download_svc)
+            .data(download_svc.clone())
+            .data(export_svc.clone())
+            .data(export_jobs.clone())
+            .data(setting_svc.clone())
+            .data(automation_svc)
+            .data(inbox_svc)
             .loader(loader)
             .data(extension_manager)
             .data(download_tx)
+            .data(automation_tx)
+            .data(download_ahead_tx)
+            .data(prefetch_tx)
+            .data(import_tx)
             .data(notifier)
             .build();
 
@@ -231,6 +365,13 @@ impl ServerBuilder {
             config,
             schema,
             image_svc,
+            library_svc,
+            setting_svc,
+            source_svc,
+            user_svc,
+            download_svc,
+            export_svc,
+            export_jobs,
         ))
     }
 }
@@ -240,18 +381,62 @@ pub struct Server {
 }
 
 impl Server {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         enable_playground: bool,
         config: Config,
         schema: TanoshiSchema,
         image_svc: ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>,
+        library_svc: LibraryService<LibraryRepositoryImpl>,
+        setting_svc: SettingService<SettingRepositoryImpl>,
+        source_svc: SourceService<SourceRepositoryImpl>,
+        user_svc: UserService<UserRepositoryImpl>,
+        download_svc: DownloadService<DownloadRepositoryImpl>,
+        export_svc: ChapterExportService,
+        export_jobs: ExportJobs,
     ) -> Self {
         let mut router = Router::new();
 
         router = router
             .route("/health", get(health_check))
+            .route("/api/library/recent/manga", get(get_recently_added_manga))
+            .route(
+                "/api/library/recent/chapters",
+                get(get_recently_added_chapters),
+            )
+            .layer(Extension(library_svc))
             .route("/image/:url", get(fetch_image))
-            .layer(Extension(image_svc));
+            .route("/sprite/:url", get(fetch_chapter_sprite))
+            .layer(Extension(image_svc))
+            .route("/api/status", get(server_status))
+            .layer(Extension(setting_svc))
+            .route("/api/sources", get(get_installed_sources))
+            .route("/api/sources/available", get(get_available_sources))
+            .route("/api/source/:source_id/filters", get(get_filters))
+            .route("/api/source/:source_id/logs", get(get_logs))
+            .route(
+                "/api/source/:source_id/preferences",
+                get(get_preferences).put(set_preferences),
+            )
+            .layer(Extension(source_svc))
+            .route("/api/downloads/usage", get(get_download_usage))
+            .layer(Extension(download_svc))
+            .route("/api/chapter/:chapter_id/cbz", get(export_chapter_cbz))
+            .route("/api/chapter/:chapter_id/pdf", get(export_chapter_pdf))
+            .route("/api/chapter/:chapter_id/epub", get(export_chapter_epub))
+            .route("/api/export/pdf", post(start_pdf_export))
+            .route("/api/export/epub", post(start_epub_export))
+            .route("/api/export/:id", get(get_export))
+            .route("/api/export/:id/file", get(download_export))
+            .layer(Extension(export_svc));
+
+        if config.enable_webdav {
+            router = router
+                .route("/dav", any(webdav))
+                .route("/dav/*path", any(webdav));
+        }
+
+        router = router.layer(Extension(export_jobs));
 
         if enable_playground {
             router = router
@@ -264,6 +449,7 @@ impl Server {
         }
 
         router = router
+            .layer(Extension(user_svc))
             .layer(Extension(config))
             .layer(Extension(schema))
             .layer(
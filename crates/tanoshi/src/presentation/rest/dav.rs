@@ -0,0 +1,390 @@
+use std::{
+    io::ErrorKind,
+    path::{Component, Path as FsPath, PathBuf},
+    time::SystemTime,
+};
+
+use axum::{
+    body::{Body, Bytes, StreamBody},
+    extract::{Extension, Path},
+    http::{HeaderMap, Method, Response, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use headers::{
+    authorization::{Basic, Bearer},
+    Authorization, HeaderMapExt,
+};
+use tokio::io::AsyncReadExt;
+
+use super::export::encode_filename;
+use crate::{
+    application::worker::export::ExportJobs,
+    domain::services::user::UserService,
+    infrastructure::{
+        auth, comic_info::escape, config::Config, domain::repositories::user::UserRepositoryImpl,
+        page_store::is_manifest,
+    },
+};
+
+const ALLOW: &str = "OPTIONS, GET, HEAD, PROPFIND";
+const DOWNLOADS: &str = "downloads";
+const EXPORTS: &str = "exports";
+
+/// Id of the user of a request, webdav clients mostly only do basic auth so either
+/// a token or the password of the account is accepted as the password
+async fn authenticate(
+    headers: &HeaderMap,
+    config: &Config,
+    user_svc: &UserService<UserRepositoryImpl>,
+) -> Option<i64> {
+    if let Some(Authorization(bearer)) = headers.typed_get::<Authorization<Bearer>>() {
+        return auth::decode_jwt(&config.secret, bearer.token())
+            .ok()
+            .map(|claims| claims.sub);
+    }
+
+    let Authorization(basic) = headers.typed_get::<Authorization<Basic>>()?;
+    if let Ok(claims) = auth::decode_jwt(&config.secret, basic.password()) {
+        return Some(claims.sub);
+    }
+    user_svc
+        .verify_password(basic.username(), basic.password())
+        .await
+        .ok()?;
+
+    user_svc
+        .fetch_user_by_username(basic.username())
+        .await
+        .ok()
+        .map(|user| user.id)
+}
+
+/// Segments of a request path, `None` if any would leave the served directory
+fn segments(path: &str) -> Option<Vec<String>> {
+    let mut segments = vec![];
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        let mut components = FsPath::new(segment).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) if !segment.starts_with('.') => {
+                segments.push(segment.to_string())
+            }
+            _ => return None,
+        }
+    }
+
+    Some(segments)
+}
+
+fn href(segments: &[String], collection: bool) -> String {
+    let mut href = "/dav".to_string();
+    for segment in segments {
+        href.push('/');
+        href.push_str(&encode_filename(segment));
+    }
+    if collection {
+        href.push('/');
+    }
+
+    href
+}
+
+fn http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+struct Entry {
+    segments: Vec<String>,
+    collection: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Entry {
+    fn collection(segments: Vec<String>) -> Self {
+        Self {
+            segments,
+            collection: true,
+            size: 0,
+            modified: None,
+        }
+    }
+
+    async fn from_path(segments: Vec<String>, path: &FsPath) -> Option<Self> {
+        let metadata = tokio::fs::metadata(path).await.ok()?;
+
+        Some(Self {
+            segments,
+            collection: metadata.is_dir(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    fn name(&self) -> &str {
+        self.segments.last().map(String::as_str).unwrap_or_default()
+    }
+
+    fn to_xml(&self) -> String {
+        let name = self.name();
+        let mut props = format!("<D:displayname>{}</D:displayname>", escape(name));
+        if self.collection {
+            props.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+        } else {
+            props.push_str(&format!(
+                "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength><D:getcontenttype>{}</D:getcontenttype>",
+                self.size,
+                mime_guess::from_path(name).first_or_octet_stream()
+            ));
+        }
+        if let Some(modified) = self.modified {
+            props.push_str(&format!(
+                "<D:getlastmodified>{}</D:getlastmodified>",
+                http_date(modified)
+            ));
+        }
+
+        format!(
+            "<D:response><D:href>{}</D:href><D:propstat><D:prop>{props}</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>",
+            escape(&href(&self.segments, self.collection))
+        )
+    }
+}
+
+/// Files in a download directory, pages kept in the page store aren't served
+async fn read_dir(segments: &[String], dir: &FsPath) -> Vec<Entry> {
+    let mut entries = vec![];
+    let mut read_dir = match tokio::fs::read_dir(dir).await {
+        Ok(read_dir) => read_dir,
+        Err(_) => return entries,
+    };
+    while let Ok(Some(entry)) = read_dir.next_entry().await {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || is_manifest(&entry.path()) {
+            continue;
+        }
+
+        let mut child = segments.to_vec();
+        child.push(name);
+        if let Some(entry) = Entry::from_path(child, &entry.path()).await {
+            entries.push(entry);
+        }
+    }
+    entries.sort_by(|a, b| human_sort::compare(a.name(), b.name()));
+
+    entries
+}
+
+/// The file of an export or download a path refers to, `None` for collections
+async fn resolve(
+    segments: &[String],
+    config: &Config,
+    jobs: &ExportJobs,
+    user_id: i64,
+) -> Result<Option<PathBuf>, StatusCode> {
+    match segments.split_first() {
+        None => Ok(None),
+        Some((root, rest)) if root == DOWNLOADS => {
+            let path = rest
+                .iter()
+                .fold(PathBuf::from(&config.download_path), |path, segment| {
+                    path.join(segment)
+                });
+            if is_manifest(&path) {
+                return Err(StatusCode::NOT_FOUND);
+            }
+            match tokio::fs::metadata(&path).await {
+                Ok(metadata) if metadata.is_dir() => Ok(None),
+                Ok(_) => Ok(Some(path)),
+                Err(e) if e.kind() == ErrorKind::NotFound => Err(StatusCode::NOT_FOUND),
+                Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+            }
+        }
+        Some((root, [])) if root == EXPORTS => Ok(None),
+        Some((root, [filename])) if root == EXPORTS => jobs
+            .completed(user_id)
+            .await
+            .into_iter()
+            .find(|(_, name)| name == filename)
+            .map(|(path, _)| Some(path))
+            .ok_or(StatusCode::NOT_FOUND),
+        _ => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+async fn propfind(
+    segments: Vec<String>,
+    depth_one: bool,
+    config: &Config,
+    jobs: &ExportJobs,
+    user_id: i64,
+) -> Result<Response<Body>, StatusCode> {
+    let file = resolve(&segments, config, jobs, user_id).await?;
+
+    let root = segments.first().cloned();
+    let mut entries = vec![];
+    match (file, root.as_deref()) {
+        (Some(path), _) => entries.push(
+            Entry::from_path(segments, &path)
+                .await
+                .ok_or(StatusCode::NOT_FOUND)?,
+        ),
+        (None, None) => {
+            entries.push(Entry::collection(vec![]));
+            if depth_one {
+                entries.push(Entry::collection(vec![DOWNLOADS.to_string()]));
+                entries.push(Entry::collection(vec![EXPORTS.to_string()]));
+            }
+        }
+        (None, Some(DOWNLOADS)) => {
+            let dir = segments[1..]
+                .iter()
+                .fold(PathBuf::from(&config.download_path), |path, segment| {
+                    path.join(segment)
+                });
+            if depth_one {
+                entries.extend(read_dir(&segments, &dir).await);
+            }
+            entries.insert(
+                0,
+                Entry::from_path(segments, &dir)
+                    .await
+                    .ok_or(StatusCode::NOT_FOUND)?,
+            );
+        }
+        (None, _) => {
+            if depth_one {
+                for (path, filename) in jobs.completed(user_id).await {
+                    let child = vec![EXPORTS.to_string(), filename];
+                    if let Some(entry) = Entry::from_path(child, &path).await {
+                        entries.push(entry);
+                    }
+                }
+            }
+            entries.insert(0, Entry::collection(segments));
+        }
+    }
+
+    let mut body = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    for entry in entries {
+        body.push_str(&entry.to_xml());
+        body.push('\n');
+    }
+    body.push_str("</D:multistatus>\n");
+
+    Response::builder()
+        .status(StatusCode::MULTI_STATUS)
+        .header("Content-Type", "application/xml; charset=utf-8")
+        .body(Body::from(body))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Read `file` in chunks so large archives aren't held in memory
+fn file_stream(file: tokio::fs::File) -> impl Stream<Item = std::io::Result<Bytes>> {
+    futures::stream::unfold(file, |mut file| async move {
+        let mut buf = vec![0; 64 * 1024];
+        match file.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(Bytes::from(buf)), file))
+            }
+            Err(e) => Some((Err(e), file)),
+        }
+    })
+}
+
+/// Read only webdav of downloaded chapters under `/dav/downloads` and the finished
+/// exports of the user under `/dav/exports`
+pub async fn webdav(
+    method: Method,
+    path: Option<Path<String>>,
+    headers: HeaderMap,
+    Extension(config): Extension<Config>,
+    Extension(user_svc): Extension<UserService<UserRepositoryImpl>>,
+    Extension(jobs): Extension<ExportJobs>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if method == Method::OPTIONS {
+        return Ok(Response::builder()
+            .header("DAV", "1")
+            .header("Allow", ALLOW)
+            .body(Body::empty())
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into_response());
+    }
+
+    let user_id = match authenticate(&headers, &config, &user_svc).await {
+        Some(user_id) => user_id,
+        None => {
+            return Ok(Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .header("WWW-Authenticate", "Basic realm=\"tanoshi\"")
+                .body(Body::empty())
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .into_response())
+        }
+    };
+
+    let path = path.map(|Path(path)| path).unwrap_or_default();
+    let segments = segments(&path).ok_or(StatusCode::NOT_FOUND)?;
+
+    if method.as_str() == "PROPFIND" {
+        let depth_one = headers
+            .get("Depth")
+            .map(|depth| depth.as_bytes() != b"0")
+            .unwrap_or(true);
+        return Ok(propfind(segments, depth_one, &config, &jobs, user_id)
+            .await?
+            .into_response());
+    }
+
+    if method != Method::GET && method != Method::HEAD {
+        return Ok(Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header("Allow", ALLOW)
+            .body(Body::empty())
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into_response());
+    }
+
+    let path = resolve(&segments, &config, &jobs, user_id)
+        .await?
+        .ok_or(StatusCode::METHOD_NOT_ALLOWED)?;
+    let file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let metadata = file
+        .metadata()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let name = segments.last().cloned().unwrap_or_default();
+
+    let mut builder = Response::builder()
+        .header(
+            "Content-Type",
+            mime_guess::from_path(&name)
+                .first_or_octet_stream()
+                .to_string(),
+        )
+        .header("Content-Length", metadata.len());
+    if let Ok(modified) = metadata.modified() {
+        builder = builder.header("Last-Modified", http_date(modified));
+    }
+
+    if method == Method::HEAD {
+        return Ok(builder
+            .body(Body::empty())
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into_response());
+    }
+
+    Ok(builder
+        .body(StreamBody::new(file_stream(file)))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_response())
+}
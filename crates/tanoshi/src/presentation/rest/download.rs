@@ -0,0 +1,35 @@
+use axum::{extract::Extension, http::StatusCode, Json};
+use serde::Serialize;
+
+use super::source::authorize;
+use crate::{
+    domain::{entities::download::MangaDownloadUsage, services::download::DownloadService},
+    infrastructure::{config::Config, domain::repositories::download::DownloadRepositoryImpl},
+    presentation::token::Token,
+};
+
+#[derive(Debug, Serialize)]
+pub struct DownloadUsage {
+    total_size: i64,
+    manga: Vec<MangaDownloadUsage>,
+}
+
+pub async fn get_download_usage(
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(svc): Extension<DownloadService<DownloadRepositoryImpl>>,
+) -> Result<Json<DownloadUsage>, StatusCode> {
+    if !authorize(&config, &token)?.is_admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let manga = svc
+        .get_download_usage()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(DownloadUsage {
+        total_size: manga.iter().map(|m| m.size).sum(),
+        manga,
+    }))
+}
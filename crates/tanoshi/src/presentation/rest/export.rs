@@ -0,0 +1,295 @@
+use std::io::Cursor;
+
+use axum::{
+    body::Body,
+    extract::{Extension, Path, Query},
+    http::{Response, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use super::source::authorize;
+use crate::{
+    application::worker::export::{ExportJobs, ExportStatus},
+    domain::services::export::ExportService,
+    infrastructure::{
+        config::Config,
+        domain::repositories::{
+            chapter::ChapterRepositoryImpl, image::ImageRepositoryImpl,
+            library::LibraryRepositoryImpl, manga::MangaRepositoryImpl,
+        },
+    },
+    presentation::token::Token,
+};
+
+/// Percent encode a file name for `filename*` of `Content-Disposition`
+pub(super) fn encode_filename(filename: &str) -> String {
+    let mut encoded = String::with_capacity(filename.len());
+    for byte in filename.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    encoded
+}
+
+type ChapterExportService = ExportService<
+    ChapterRepositoryImpl,
+    MangaRepositoryImpl,
+    ImageRepositoryImpl,
+    LibraryRepositoryImpl,
+>;
+
+fn attachment(
+    filename: &str,
+    content_type: &str,
+    length: u64,
+    body: Body,
+) -> Result<Response<Body>, StatusCode> {
+    // ascii only fallback for clients not understanding filename*
+    let fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii() && c != '"' { c } else { '_' })
+        .collect();
+
+    Response::builder()
+        .header("Content-Type", content_type)
+        .header("Content-Length", length)
+        .header(
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"{fallback}\"; filename*=UTF-8''{}",
+                encode_filename(filename)
+            ),
+        )
+        .body(body)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn export_chapter_cbz(
+    Path(chapter_id): Path<i64>,
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(svc): Extension<ChapterExportService>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let claims = authorize(&config, &token)?;
+
+    let file = svc.export_cbz(claims.sub, chapter_id).await.map_err(|e| {
+        error!("failed to export chapter {chapter_id}: {e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    attachment(
+        &file.filename,
+        file.content_type,
+        file.data.len() as u64,
+        Body::from(file.data),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LayoutParams {
+    /// lay out spreads right to left
+    #[serde(default)]
+    rtl: bool,
+}
+
+pub async fn export_chapter_pdf(
+    Path(chapter_id): Path<i64>,
+    Query(params): Query<LayoutParams>,
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(svc): Extension<ChapterExportService>,
+) -> Result<impl IntoResponse, StatusCode> {
+    authorize(&config, &token)?;
+
+    let (filename, data) = svc
+        .export_pdf(&[chapter_id], params.rtl, vec![])
+        .await
+        .map_err(|e| {
+            error!("failed to export chapter {chapter_id}: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    attachment(
+        &filename,
+        "application/pdf",
+        data.len() as u64,
+        Body::from(data),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PdfExportRequest {
+    chapters: Vec<i64>,
+    #[serde(default)]
+    rtl: bool,
+}
+
+pub async fn export_chapter_epub(
+    Path(chapter_id): Path<i64>,
+    Query(params): Query<LayoutParams>,
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(svc): Extension<ChapterExportService>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let claims = authorize(&config, &token)?;
+
+    let (filename, data) = svc
+        .export_epub(
+            claims.sub,
+            &[chapter_id],
+            None,
+            params.rtl,
+            Cursor::new(vec![]),
+        )
+        .await
+        .map_err(|e| {
+            error!("failed to export chapter {chapter_id}: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let data = data.into_inner();
+
+    attachment(
+        &filename,
+        "application/epub+zip",
+        data.len() as u64,
+        Body::from(data),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EpubExportRequest {
+    chapters: Vec<i64>,
+    /// volume the chapters make up, for the title and series position
+    volume: Option<f64>,
+    #[serde(default)]
+    rtl: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportJobResponse {
+    id: u64,
+    #[serde(flatten)]
+    status: ExportStatus,
+}
+
+/// Start rendering chapters into a pdf in the background, poll `/api/export/:id` for it
+pub async fn start_pdf_export(
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(svc): Extension<ChapterExportService>,
+    Extension(jobs): Extension<ExportJobs>,
+    Json(request): Json<PdfExportRequest>,
+) -> Result<Json<ExportJobResponse>, StatusCode> {
+    let claims = authorize(&config, &token)?;
+
+    if request.chapters.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let id = jobs
+        .spawn(claims.sub, "pdf", move |file| async move {
+            let (filename, _) = svc.export_pdf(&request.chapters, request.rtl, file).await?;
+            Ok(filename)
+        })
+        .await
+        .map_err(|e| {
+            error!("failed to start export: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(ExportJobResponse {
+        id,
+        status: ExportStatus::Running,
+    }))
+}
+
+/// Start packing chapters into an epub in the background, poll `/api/export/:id` for it
+pub async fn start_epub_export(
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(svc): Extension<ChapterExportService>,
+    Extension(jobs): Extension<ExportJobs>,
+    Json(request): Json<EpubExportRequest>,
+) -> Result<Json<ExportJobResponse>, StatusCode> {
+    let claims = authorize(&config, &token)?;
+
+    if request.chapters.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let user_id = claims.sub;
+    let id = jobs
+        .spawn(user_id, "epub", move |file| async move {
+            let (filename, _) = svc
+                .export_epub(
+                    user_id,
+                    &request.chapters,
+                    request.volume,
+                    request.rtl,
+                    file,
+                )
+                .await?;
+            Ok(filename)
+        })
+        .await
+        .map_err(|e| {
+            error!("failed to start export: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(ExportJobResponse {
+        id,
+        status: ExportStatus::Running,
+    }))
+}
+
+pub async fn get_export(
+    Path(id): Path<u64>,
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(jobs): Extension<ExportJobs>,
+) -> Result<Json<ExportJobResponse>, StatusCode> {
+    let claims = authorize(&config, &token)?;
+
+    let job = jobs
+        .get(id, claims.sub)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(ExportJobResponse {
+        id,
+        status: job.status,
+    }))
+}
+
+pub async fn download_export(
+    Path(id): Path<u64>,
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(jobs): Extension<ExportJobs>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let claims = authorize(&config, &token)?;
+
+    let (path, filename) = jobs
+        .file(id, claims.sub)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let data = tokio::fs::read(&path)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let content_type = mime_guess::from_path(&path).first_or_octet_stream();
+
+    attachment(
+        &filename,
+        content_type.as_ref(),
+        data.len() as u64,
+        Body::from(data),
+    )
+}
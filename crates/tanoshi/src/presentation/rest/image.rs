@@ -1,16 +1,26 @@
 use axum::{
-    body::Body,
+    body::{Body, StreamBody},
     extract::{Extension, Path, Query},
-    http::{Response, StatusCode},
+    http::{
+        header::{ACCEPT, CONTENT_LENGTH, IF_NONE_MATCH, IF_RANGE, RANGE},
+        HeaderMap, Response, StatusCode,
+    },
     response::IntoResponse,
 };
 
+use bytes::Bytes;
+use futures::{stream::BoxStream, StreamExt, TryStreamExt};
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 
 use crate::{
-    domain::services::image::ImageService,
+    domain::{
+        entities::image::{Image, ImageStream},
+        repositories::image::ImageRepositoryError,
+        services::image::{ImageBody, ImageError, ImageService, ImageVariant},
+    },
     infrastructure::{
-        config::Config,
+        config::{Config, OptimizeFormat},
         domain::repositories::{image::ImageRepositoryImpl, image_cache::ImageCacheRepositoryImpl},
     },
 };
@@ -18,23 +28,295 @@ use crate::{
 #[derive(Debug, Deserialize)]
 pub struct Params {
     referer: Option<String>,
+    /// shrink the image to fit, keeping its aspect ratio
+    width: Option<u32>,
+    height: Option<u32>,
+    /// quality the image is re-encoded with, 1 to 100
+    quality: Option<u8>,
+}
+
+/// First of `formats` the client lists in its `Accept` header
+fn negotiate(headers: &HeaderMap, formats: &[OptimizeFormat]) -> Option<OptimizeFormat> {
+    let accept = headers.get(ACCEPT)?.to_str().ok()?;
+    formats.iter().copied().find(|format| {
+        accept
+            .split(',')
+            .filter_map(|media| media.split(';').next())
+            .any(|media| media.trim() == format.content_type())
+    })
+}
+
+/// Strong validator of an image, its contents hashed
+fn etag(image: &Image) -> String {
+    format!("\"{}\"", hex::encode(&Sha256::digest(&image.data)[..16]))
+}
+
+/// Whether the client already has the image tagged `etag`, per its `If-None-Match` header
+fn is_fresh(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    Full,
+    /// first and last byte, inclusive
+    Partial(usize, usize),
+    Unsatisfiable,
+}
+
+/// Bytes of an image of `len` bytes asked for by the `Range` header. Anything but a single
+/// range of bytes is served whole, as is an image changed since the `If-Range` validator
+fn byte_range(headers: &HeaderMap, len: usize, etag: &str) -> ByteRange {
+    let range = match headers.get(RANGE).and_then(|value| value.to_str().ok()) {
+        Some(range) => range,
+        None => return ByteRange::Full,
+    };
+    if let Some(if_range) = headers.get(IF_RANGE) {
+        if if_range.as_bytes() != etag.as_bytes() {
+            return ByteRange::Full;
+        }
+    }
+
+    let (start, end) = match range
+        .strip_prefix("bytes=")
+        .filter(|range| !range.contains(','))
+        .and_then(|range| range.split_once('-'))
+    {
+        Some((start, end)) => (start.trim(), end.trim()),
+        None => return ByteRange::Full,
+    };
+
+    let last = len.saturating_sub(1);
+    let (start, end) = if start.is_empty() {
+        // the last `end` bytes
+        match end.parse::<usize>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(suffix) => (len.saturating_sub(suffix), last),
+            Err(_) => return ByteRange::Full,
+        }
+    } else {
+        match (start.parse::<usize>(), end) {
+            (Ok(start), "") => (start, last),
+            (Ok(start), end) => match end.parse::<usize>() {
+                Ok(end) if start <= end => (start, end.min(last)),
+                _ => return ByteRange::Full,
+            },
+            (Err(_), _) => return ByteRange::Full,
+        }
+    };
+    if start >= len {
+        return ByteRange::Unsatisfiable;
+    }
+
+    ByteRange::Partial(start, end)
+}
+
+/// `image` with its validator and caching headers, without a body if the client's copy is
+/// still fresh and only the bytes asked for when it's a range request
+fn image_response(
+    image: Image,
+    headers: &HeaderMap,
+    vary_accept: bool,
+) -> Result<Response<Body>, StatusCode> {
+    let etag = etag(&image);
+    let mut builder = Response::builder()
+        .header("ETag", &etag)
+        .header("Cache-Control", "public, max-age=864000")
+        .header("Accept-Ranges", "bytes");
+    // the same url is another format for clients accepting other ones
+    if vary_accept {
+        builder = builder.header("Vary", "Accept");
+    }
+
+    if is_fresh(headers, &etag) {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let len = image.data.len();
+    let (builder, data) = match byte_range(headers, len, &etag) {
+        ByteRange::Full => (builder, image.data),
+        ByteRange::Partial(start, end) => (
+            builder
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header("Content-Range", format!("bytes {start}-{end}/{len}")),
+            image.data.slice(start..=end),
+        ),
+        ByteRange::Unsatisfiable => {
+            return builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header("Content-Range", format!("bytes */{len}"))
+                .body(Body::empty())
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    builder
+        .header("Content-Type", image.content_type)
+        .header("Content-Length", data.len())
+        .body(Body::from(data))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// `stream` sent on as it's read, partial when the source answered a range request
+fn stream_response(
+    stream: ImageStream,
+    vary_accept: bool,
+) -> Result<Response<StreamBody<BoxStream<'static, std::io::Result<Bytes>>>>, StatusCode> {
+    let mut builder = Response::builder()
+        .header("Content-Type", stream.content_type)
+        .header("Cache-Control", "public, max-age=864000")
+        .header("Accept-Ranges", "bytes");
+    if vary_accept {
+        builder = builder.header("Vary", "Accept");
+    }
+    if let Some(content_length) = stream.content_length {
+        builder = builder.header("Content-Length", content_length);
+    }
+    if let Some(content_range) = stream.content_range {
+        builder = builder
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header("Content-Range", content_range);
+    }
+
+    builder
+        .body(StreamBody::new(stream.body))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
 }
 
 pub async fn fetch_image(
     Path(encrypted_url): Path<String>,
     Query(params): Query<Params>,
+    headers: HeaderMap,
+    Extension(config): Extension<Config>,
+    Extension(svc): Extension<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    svc.record_served_request();
+    let transcode = config.image_transcode.as_ref().and_then(|transcode| {
+        negotiate(&headers, &transcode.formats).map(|format| (format, transcode.quality))
+    });
+    let variant = ImageVariant {
+        width: params.width,
+        height: params.height,
+        quality: params
+            .quality
+            .or_else(|| transcode.map(|(_, quality)| quality)),
+        format: transcode.map(|(format, _)| format),
+    };
+    let range = headers.get(RANGE).and_then(|range| range.to_str().ok());
+    let body = svc
+        .fetch_image_body(
+            &config.secret,
+            &encrypted_url,
+            params.referer.as_ref(),
+            variant,
+            range,
+        )
+        .await
+        .map_err(|e| match e {
+            ImageError::RepositoryError(ImageRepositoryError::RangeNotSatisfiable) => {
+                StatusCode::RANGE_NOT_SATISFIABLE
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        })?;
+
+    let vary_accept = config.image_transcode.is_some();
+    Ok(match body {
+        ImageBody::Buffered(image) => {
+            let res = image_response(image, &headers, vary_accept)?;
+            // not modified and unsatisfiable responses have no body
+            let len = res
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|len| len.to_str().ok())
+                .and_then(|len| len.parse().ok())
+                .unwrap_or_default();
+            svc.record_served_bytes(len);
+            res.into_response()
+        }
+        ImageBody::Streamed(mut stream) => {
+            let counter = svc.clone();
+            stream.body = stream
+                .body
+                .inspect_ok(move |chunk| counter.record_served_bytes(chunk.len() as u64))
+                .boxed();
+            stream_response(stream, vary_accept)?.into_response()
+        }
+    })
+}
+
+pub async fn fetch_chapter_sprite(
+    Path(encrypted_path): Path<String>,
+    headers: HeaderMap,
     Extension(config): Extension<Config>,
     Extension(svc): Extension<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>,
 ) -> Result<impl IntoResponse, StatusCode> {
     let image = svc
-        .fetch_image(&config.secret, &encrypted_url, params.referer.as_ref())
+        .fetch_chapter_sprite(&config.secret, &encrypted_path)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|e| match e {
+            ImageError::NotDownloaded => StatusCode::NOT_FOUND,
+            e => {
+                error!("failed to generate sprite: {e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
 
-    Ok(Response::builder()
-        .header("Content-Type", image.content_type)
-        .header("Content-Length", image.data.len())
-        .header("Cache-Control", "max-age=864000")
-        .body(Body::from(image.data))
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?)
+    image_response(image, &headers, false)
+}
+
+#[cfg(test)]
+mod test {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn range(range: &str, if_range: Option<&str>) -> ByteRange {
+        let mut headers = HeaderMap::new();
+        headers.insert(RANGE, HeaderValue::from_str(range).unwrap());
+        if let Some(if_range) = if_range {
+            headers.insert(IF_RANGE, HeaderValue::from_str(if_range).unwrap());
+        }
+        byte_range(&headers, 100, "\"etag\"")
+    }
+
+    #[test]
+    fn test_byte_range() {
+        assert_eq!(
+            byte_range(&HeaderMap::new(), 100, "\"etag\""),
+            ByteRange::Full
+        );
+        assert_eq!(range("bytes=0-9", None), ByteRange::Partial(0, 9));
+        assert_eq!(range("bytes=10-", None), ByteRange::Partial(10, 99));
+        assert_eq!(range("bytes=90-200", None), ByteRange::Partial(90, 99));
+        assert_eq!(range("bytes=-10", None), ByteRange::Partial(90, 99));
+        assert_eq!(range("bytes=-200", None), ByteRange::Partial(0, 99));
+    }
+
+    #[test]
+    fn test_byte_range_served_whole() {
+        assert_eq!(range("bytes=0-9,20-29", None), ByteRange::Full);
+        assert_eq!(range("bytes=9-0", None), ByteRange::Full);
+        assert_eq!(range("items=0-9", None), ByteRange::Full);
+        assert_eq!(range("bytes=a-9", None), ByteRange::Full);
+        assert_eq!(range("bytes=0-9", Some("\"other\"")), ByteRange::Full);
+        assert_eq!(
+            range("bytes=0-9", Some("\"etag\"")),
+            ByteRange::Partial(0, 9)
+        );
+    }
+
+    #[test]
+    fn test_byte_range_unsatisfiable() {
+        assert_eq!(range("bytes=100-", None), ByteRange::Unsatisfiable);
+        assert_eq!(range("bytes=-0", None), ByteRange::Unsatisfiable);
+    }
 }
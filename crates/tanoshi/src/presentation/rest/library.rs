@@ -0,0 +1,139 @@
+use async_graphql::connection::CursorType;
+use axum::{
+    extract::{Extension, Query},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::source::authorize;
+use crate::{
+    domain::{
+        entities::library::{ChapterAddition, LibraryAddition},
+        services::{image::ImageService, library::LibraryService},
+    },
+    infrastructure::{
+        config::Config,
+        domain::repositories::{
+            image::ImageRepositoryImpl, image_cache::ImageCacheRepositoryImpl,
+            library::LibraryRepositoryImpl,
+        },
+    },
+    presentation::{graphql::common::Cursor, token::Token},
+};
+
+const DEFAULT_PAGE_SIZE: usize = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct PageParams {
+    /// `next_cursor` of the previous page, the first page when not given
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+impl PageParams {
+    fn cursor(&self) -> Result<Cursor, StatusCode> {
+        match &self.cursor {
+            Some(cursor) => Cursor::decode_cursor(cursor).map_err(|_| StatusCode::BAD_REQUEST),
+            None => Ok(Cursor(Utc::now().timestamp(), 1)),
+        }
+    }
+
+    fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, 100)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    items: Vec<T>,
+    /// null on the last page
+    next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// `items` has one item more than the page when there is a next page
+    fn new(mut items: Vec<T>, limit: usize, cursor: impl Fn(&T) -> Cursor) -> Self {
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(|item| cursor(item).encode_cursor())
+        } else {
+            None
+        };
+
+        Self { items, next_cursor }
+    }
+}
+
+type CoverImageService = ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>;
+
+pub async fn get_recently_added_manga(
+    Query(params): Query<PageParams>,
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(image_svc): Extension<CoverImageService>,
+    Extension(svc): Extension<LibraryService<LibraryRepositoryImpl>>,
+) -> Result<Json<Page<LibraryAddition>>, StatusCode> {
+    let claims = authorize(&config, &token)?;
+    let cursor = params.cursor()?;
+    let limit = params.limit();
+
+    let mut manga = svc
+        .get_library_additions(
+            claims.sub,
+            &claims.unlocked_categories,
+            cursor.0,
+            cursor.1,
+            0,
+            0,
+            Some(limit + 1),
+            None,
+        )
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    for m in manga.iter_mut() {
+        m.cover_url = image_svc
+            .encrypt_manga_cover_url(&config.secret, m.manga_id, &m.cover_url)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
+    Ok(Json(Page::new(manga, limit, |m| {
+        Cursor(m.date_added.timestamp(), m.manga_id)
+    })))
+}
+
+pub async fn get_recently_added_chapters(
+    Query(params): Query<PageParams>,
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(image_svc): Extension<CoverImageService>,
+    Extension(svc): Extension<LibraryService<LibraryRepositoryImpl>>,
+) -> Result<Json<Page<ChapterAddition>>, StatusCode> {
+    let claims = authorize(&config, &token)?;
+    let cursor = params.cursor()?;
+    let limit = params.limit();
+
+    let mut chapters = svc
+        .get_chapter_additions(
+            claims.sub,
+            &claims.unlocked_categories,
+            cursor.0,
+            cursor.1,
+            0,
+            0,
+            Some(limit + 1),
+            None,
+        )
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    for chapter in chapters.iter_mut() {
+        chapter.cover_url = image_svc
+            .encrypt_manga_cover_url(&config.secret, chapter.manga_id, &chapter.cover_url)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
+    Ok(Json(Page::new(chapters, limit, |chapter| {
+        Cursor(chapter.date_added.timestamp(), chapter.chapter_id)
+    })))
+}
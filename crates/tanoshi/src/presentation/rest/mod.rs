@@ -1,2 +1,8 @@
+pub mod dav;
+pub mod download;
+pub mod export;
 pub mod health;
 pub mod image;
+pub mod library;
+pub mod source;
+pub mod status;
@@ -0,0 +1,179 @@
+use axum::{
+    extract::{Extension, Path, Query},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use tanoshi_lib::prelude::Input;
+
+use crate::{
+    domain::{
+        entities::source::{Source, SourceLog, SourceLogLevel},
+        entities::user::User,
+        services::{source::SourceService, user::UserService},
+    },
+    infrastructure::{
+        auth::{self, Claims},
+        config::Config,
+        domain::repositories::{source::SourceRepositoryImpl, user::UserRepositoryImpl},
+    },
+    presentation::token::Token,
+};
+
+pub(super) fn authorize(config: &Config, token: &Token) -> Result<Claims, StatusCode> {
+    auth::decode_jwt(&config.secret, &token.0).map_err(|_| StatusCode::UNAUTHORIZED)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SourceListParams {
+    /// only list sources supporting this language
+    language: Option<String>,
+}
+
+fn filter_language(sources: Vec<Source>, language: Option<&str>) -> Vec<Source> {
+    sources
+        .into_iter()
+        .filter(|source| {
+            language
+                .map(|language| source.supports_language(language))
+                .unwrap_or(true)
+        })
+        .collect()
+}
+
+async fn fetch_user(
+    user_svc: &UserService<UserRepositoryImpl>,
+    claims: &Claims,
+) -> Result<User, StatusCode> {
+    user_svc.fetch_user_by_id(claims.sub).await.map_err(|e| {
+        error!("failed to get user: {e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+pub async fn get_installed_sources(
+    Query(params): Query<SourceListParams>,
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(user_svc): Extension<UserService<UserRepositoryImpl>>,
+) -> Result<Json<Vec<Source>>, StatusCode> {
+    let claims = authorize(&config, &token)?;
+    let user = fetch_user(&user_svc, &claims).await?;
+
+    let sources = svc
+        .get_installed_sources_for_user(claims.sub, &config.extension_repository, false)
+        .await
+        .map_err(|e| {
+            error!("failed to get installed sources: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .into_iter()
+        .filter(|source| !source.is_hidden_for(&user))
+        .collect();
+
+    Ok(Json(filter_language(sources, params.language.as_deref())))
+}
+
+pub async fn get_available_sources(
+    Query(params): Query<SourceListParams>,
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(user_svc): Extension<UserService<UserRepositoryImpl>>,
+) -> Result<Json<Vec<Source>>, StatusCode> {
+    let claims = authorize(&config, &token)?;
+    let user = fetch_user(&user_svc, &claims).await?;
+
+    let sources = svc
+        .get_available_sources(&config.extension_repository)
+        .await
+        .map_err(|e| {
+            error!("failed to get available sources: {e}");
+            StatusCode::BAD_GATEWAY
+        })?
+        .into_iter()
+        .filter(|source| !source.is_hidden_for(&user))
+        .collect();
+
+    Ok(Json(filter_language(sources, params.language.as_deref())))
+}
+
+/// Filter definitions of the source, send back with values set to `search_manga`
+pub async fn get_filters(
+    Path(source_id): Path<i64>,
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(svc): Extension<SourceService<SourceRepositoryImpl>>,
+) -> Result<Json<Vec<Input>>, StatusCode> {
+    authorize(&config, &token)?;
+
+    let filters = svc
+        .get_filters(source_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    Ok(Json(filters))
+}
+
+pub async fn get_preferences(
+    Path(source_id): Path<i64>,
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(svc): Extension<SourceService<SourceRepositoryImpl>>,
+) -> Result<Json<Vec<Input>>, StatusCode> {
+    authorize(&config, &token)?;
+
+    let preferences = svc
+        .get_preferences(source_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    Ok(Json(preferences))
+}
+
+pub async fn set_preferences(
+    Path(source_id): Path<i64>,
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Json(preferences): Json<Vec<Input>>,
+) -> Result<StatusCode, StatusCode> {
+    if !authorize(&config, &token)?.is_admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    svc.set_preferences(source_id, preferences)
+        .await
+        .map_err(|e| {
+            error!("failed to set preferences of source {source_id}: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogParams {
+    /// least severe level returned, defaults to debug
+    level: Option<SourceLogLevel>,
+}
+
+pub async fn get_logs(
+    Path(source_id): Path<i64>,
+    Query(params): Query<LogParams>,
+    token: Token,
+    Extension(config): Extension<Config>,
+    Extension(svc): Extension<SourceService<SourceRepositoryImpl>>,
+) -> Result<Json<Vec<SourceLog>>, StatusCode> {
+    if !authorize(&config, &token)?.is_admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let logs = svc
+        .get_logs(source_id, params.level.unwrap_or(SourceLogLevel::Debug))
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    Ok(Json(logs))
+}
@@ -0,0 +1,27 @@
+use axum::{extract::Extension, http::StatusCode, Json};
+use serde::Serialize;
+
+use crate::{
+    domain::services::setting::SettingService,
+    infrastructure::domain::repositories::setting::SettingRepositoryImpl,
+};
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+    version: String,
+    announcement: Option<String>,
+}
+
+pub async fn server_status(
+    Extension(svc): Extension<SettingService<SettingRepositoryImpl>>,
+) -> Result<Json<Status>, StatusCode> {
+    let announcement = svc
+        .get_announcement()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(Status {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        announcement,
+    }))
+}
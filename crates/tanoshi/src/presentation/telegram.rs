@@ -0,0 +1,238 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::future::join_all;
+use tanoshi_notifier::telegram::TelegramHandler;
+
+use crate::{
+    application::worker::automation::{AutomationSender, Event as AutomationEvent},
+    domain::services::{
+        history::HistoryService, library::LibraryService, manga::MangaService,
+        source::SourceService, user::UserService,
+    },
+    infrastructure::domain::repositories::{
+        chapter::ChapterRepositoryImpl, history::HistoryRepositoryImpl,
+        library::LibraryRepositoryImpl, manga::MangaRepositoryImpl, source::SourceRepositoryImpl,
+        user::UserRepositoryImpl,
+    },
+};
+
+const SEARCH_RESULT_PER_SOURCE: usize = 5;
+const LATEST_CHAPTERS: usize = 10;
+const UNREAD_MANGA: usize = 20;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub struct TelegramBotHandler {
+    base_url: Option<String>,
+    user_svc: UserService<UserRepositoryImpl>,
+    source_svc: SourceService<SourceRepositoryImpl>,
+    manga_svc: MangaService<MangaRepositoryImpl>,
+    library_svc: LibraryService<LibraryRepositoryImpl>,
+    history_svc: HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>,
+    automation_tx: AutomationSender,
+}
+
+impl TelegramBotHandler {
+    pub fn new(
+        base_url: Option<String>,
+        user_svc: UserService<UserRepositoryImpl>,
+        source_svc: SourceService<SourceRepositoryImpl>,
+        manga_svc: MangaService<MangaRepositoryImpl>,
+        library_svc: LibraryService<LibraryRepositoryImpl>,
+        history_svc: HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>,
+        automation_tx: AutomationSender,
+    ) -> Self {
+        Self {
+            base_url,
+            user_svc,
+            source_svc,
+            manga_svc,
+            library_svc,
+            history_svc,
+            automation_tx,
+        }
+    }
+
+    async fn user_id(&self, chat_id: i64) -> Result<i64> {
+        self.user_svc
+            .fetch_user_by_telegram_chat_id(chat_id)
+            .await
+            .map(|user| user.id)
+            .map_err(|_| {
+                anyhow!("this chat is not paired, send /start CODE with the code from tanoshi profile settings")
+            })
+    }
+
+    fn link(&self, path: &str, text: &str) -> String {
+        match self.base_url.as_ref() {
+            Some(base_url) => format!("<a href=\"{base_url}{path}\">{}</a>", escape(text)),
+            None => escape(text),
+        }
+    }
+}
+
+#[async_trait]
+impl TelegramHandler for TelegramBotHandler {
+    async fn pair(&self, chat_id: i64, code: &str) -> Result<String> {
+        let user = self.user_svc.pair_telegram_chat(code, chat_id).await?;
+
+        Ok(format!(
+            "Paired with <b>{}</b>, notifications will be sent to this chat",
+            escape(&user.username)
+        ))
+    }
+
+    async fn search(&self, chat_id: i64, query: &str) -> Result<String> {
+        self.user_id(chat_id).await?;
+        if query.is_empty() {
+            return Ok("Usage: <code>/search QUERY</code>".to_string());
+        }
+
+        let sources = self.source_svc.get_installed_sources("", false).await?;
+        let results = join_all(sources.iter().map(|source| {
+            self.manga_svc
+                .fetch_source_manga(source.id, 1, Some(query.to_string()), None)
+        }))
+        .await;
+
+        let mut reply = vec![];
+        for (source, result) in sources.iter().zip(results) {
+            let manga = match result {
+                Ok(manga) if !manga.is_empty() => manga,
+                _ => continue,
+            };
+
+            let lines: Vec<String> = manga
+                .iter()
+                .take(SEARCH_RESULT_PER_SOURCE)
+                .map(|manga| {
+                    let path = base64::encode_config(&manga.path, base64::URL_SAFE_NO_PAD);
+                    format!(
+                        "- {}",
+                        self.link(&format!("/manga/{}/{path}", source.id), &manga.title)
+                    )
+                })
+                .collect();
+            reply.push(format!(
+                "<b>{}</b>\n{}",
+                escape(&source.name),
+                lines.join("\n")
+            ));
+        }
+
+        if reply.is_empty() {
+            return Ok(format!("No manga found for {}", escape(query)));
+        }
+
+        Ok(reply.join("\n\n"))
+    }
+
+    async fn latest(&self, chat_id: i64) -> Result<String> {
+        let user_id = self.user_id(chat_id).await?;
+
+        let updates = self
+            .library_svc
+            .get_library_recent_updates(
+                user_id,
+                &[],
+                Utc::now().timestamp(),
+                1,
+                0,
+                0,
+                Some(LATEST_CHAPTERS),
+                None,
+            )
+            .await?;
+
+        if updates.is_empty() {
+            return Ok("No chapter in library yet".to_string());
+        }
+
+        Ok(updates
+            .iter()
+            .map(|update| {
+                format!(
+                    "- <b>{}</b> {} <code>{}</code>",
+                    escape(&update.manga_title),
+                    self.link(
+                        &format!("/chapter/{}", update.chapter_id),
+                        &update.chapter_title
+                    ),
+                    update.chapter_id
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    async fn unread(&self, chat_id: i64) -> Result<String> {
+        let user_id = self.user_id(chat_id).await?;
+
+        // private categories can't be unlocked from chat
+        let hidden = self.library_svc.get_hidden_manga_ids(user_id, &[]).await?;
+
+        // manga in multiple categories are returned once per category
+        let mut seen = HashSet::new();
+        let manga: Vec<_> = self
+            .library_svc
+            .get_manga_from_library(user_id)
+            .await?
+            .into_iter()
+            .filter(|manga| !hidden.contains(&manga.id) && seen.insert(manga.id))
+            .collect();
+        let manga_ids: Vec<i64> = manga.iter().map(|manga| manga.id).collect();
+
+        let unread = self
+            .history_svc
+            .get_unread_chapters_by_manga_ids(user_id, &manga_ids)
+            .await?;
+
+        let mut manga: Vec<_> = manga
+            .into_iter()
+            .filter_map(|manga| {
+                unread
+                    .get(&manga.id)
+                    .filter(|count| **count > 0)
+                    .map(|count| (manga, *count))
+            })
+            .collect();
+        if manga.is_empty() {
+            return Ok("All caught up".to_string());
+        }
+        manga.sort_by(|a, b| b.1.cmp(&a.1));
+
+        Ok(manga
+            .iter()
+            .take(UNREAD_MANGA)
+            .map(|(manga, count)| {
+                format!(
+                    "- {} ({count})",
+                    self.link(&format!("/manga/{}", manga.id), &manga.title)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    async fn mark_read(&self, chat_id: i64, chapter_id: i64) -> Result<String> {
+        let user_id = self.user_id(chat_id).await?;
+
+        self.history_svc
+            .insert_chapters_to_history_as_completed(user_id, vec![chapter_id])
+            .await?;
+
+        let _ = self.automation_tx.send(AutomationEvent::ChapterRead {
+            user_id,
+            chapter_id,
+        });
+
+        Ok(format!("Chapter <code>{chapter_id}</code> marked as read"))
+    }
+}